@@ -1,13 +1,22 @@
+use std::ops::RangeInclusive;
 use std::path::PathBuf;
+use std::time::Duration;
 
 use clap::{Parser, PossibleValue, Subcommand, ValueEnum};
 use clap_verbosity_flag::{InfoLevel, Verbosity};
 use idencomp::context_spec::ContextSpecType;
 use lazy_static::lazy_static;
 
+use crate::cmd::export::ExportFormatCli;
 use crate::cmd::generate_model::GenerateModelMode;
+use crate::error_report::ErrorFormat;
+use crate::logging::LogFormat;
 use crate::opts::InputStream;
-use crate::opts::{directory, input_file, input_stream, Directory, InputFile};
+use crate::opts::{
+    count, count_range, directory, duration, input_file, input_stream, ratio, Directory, InputFile,
+    ModelArgs,
+};
+use crate::progress_bar::ProgressFormat;
 
 #[derive(Parser)]
 #[clap(author, version, about, long_about = None)]
@@ -20,6 +29,26 @@ pub struct Cli {
     #[clap(long, global = true, value_parser)]
     pub no_progress: bool,
 
+    /// Format of the progress reports; `json` prints progress lines to the
+    /// standard error even if it is not a terminal
+    #[clap(long, global = true, arg_enum, value_parser, default_value_t = ProgressFormat::Bar)]
+    pub progress: ProgressFormat,
+
+    /// Format of the log messages
+    #[clap(long, global = true, arg_enum, value_parser, default_value_t = LogFormat::Text)]
+    pub log_format: LogFormat,
+
+    /// Format of the error reported when the command fails; `json` prints
+    /// a single line with the error category and the exit code
+    #[clap(long, global = true, arg_enum, value_parser, default_value_t = ErrorFormat::Text)]
+    pub errors: ErrorFormat,
+
+    /// Read the input files through memory mapping instead of regular reads;
+    /// the files must not be modified while being read
+    #[cfg(feature = "mmap")]
+    #[clap(long, global = true, value_parser)]
+    pub mmap: bool,
+
     #[clap(subcommand)]
     pub command: Commands,
 }
@@ -67,7 +96,7 @@ impl From<&ContextSpecTypeCli> for ContextSpecType {
 
 #[derive(Subcommand)]
 pub enum Commands {
-    /// Generate a new model using statistics from given FASTQ file
+    /// Generate a new model using statistics from given FASTQ or IDN file
     GenerateModel {
         /// Whether to generate acid model or quality score model
         #[clap(arg_enum, value_parser)]
@@ -77,7 +106,9 @@ pub enum Commands {
         #[clap(arg_enum)]
         context: ContextSpecTypeCli,
 
-        /// Input FASTQ file path
+        /// Input FASTQ or IDN file path; `-` is the standard input. IDN files
+        /// are decompressed using the models provided with --model-dir and
+        /// --model
         #[clap(default_value_t, value_parser = input_stream)]
         input: InputStream,
 
@@ -89,11 +120,24 @@ pub enum Commands {
         /// encountered
         #[clap(default_value_t = 10_000_000, long, value_parser)]
         limit: u32,
+
+        /// Bin the generated model down to given number of distinct contexts
+        /// before writing it, like `bin-contexts` does
+        #[clap(long, short, value_name = "CONTEXT_NUM", value_parser = clap::value_parser!(u32).range(1..))]
+        contexts: Option<u32>,
+
+        /// Bin the least probable contexts (all above this number) before doing
+        /// the proper binning; see `bin-contexts --pre-bin`
+        #[clap(long, value_name = "CONTEXT_NUM", value_parser = clap::value_parser!(u32).range(1..), requires = "contexts")]
+        pre_bin: Option<u32>,
+
+        #[clap(flatten)]
+        models: ModelArgs,
     },
 
-    /// Generate all possible models for given FASTQ file
+    /// Generate all possible models for given FASTQ or IDN file
     GenerateModelAll {
-        /// Input FASTQ file path
+        /// Input FASTQ or IDN file path
         #[clap(value_parser = input_file)]
         input: InputFile,
 
@@ -114,11 +158,25 @@ pub enum Commands {
         /// encountered
         #[clap(default_value_t = 500_000, long, value_parser)]
         limit: u32,
+
+        #[clap(flatten)]
+        models: ModelArgs,
+    },
+
+    /// Print information about a model file
+    ModelInfo {
+        /// Input model file path; `-` is the standard input
+        #[clap(default_value_t, value_parser = input_stream)]
+        input: InputStream,
+
+        /// Output the information as JSON
+        #[clap(long, value_parser)]
+        json: bool,
     },
 
     /// Make model more compact by combining multiple contexts into one
     BinContexts {
-        /// Input model file path
+        /// Input model file path; `-` is the standard input
         #[clap(default_value_t, value_parser = input_stream)]
         input: InputStream,
 
@@ -126,20 +184,27 @@ pub enum Commands {
         #[clap(short, long, value_parser)]
         output: Option<PathBuf>,
 
-        /// Number of distinct contexts to generate
-        #[clap(long, short, value_parser, value_name = "CONTEXT_NUM", value_parser = clap::value_parser!(u32).range(1..))]
-        contexts: u32,
+        /// Number of distinct contexts to generate; multiple comma-separated
+        /// values generate one model per value, with the context number
+        /// appended to the output file name
+        #[clap(long, short, value_parser, value_name = "CONTEXT_NUM", value_parser = clap::value_parser!(u32).range(1..), value_delimiter = ',', required = true)]
+        contexts: Vec<u32>,
 
         /// Bin the least probable contexts (all above this number) before doing
         /// the proper binning. This harms the generated context quality, but
         /// increases the performance dramatically
         #[clap(long, value_parser, value_name = "CONTEXT_NUM", value_parser = clap::value_parser!(u32).range(1..))]
         pre_bin: Option<u32>,
+
+        /// Write the cost of each of the context merges as a CSV file to given
+        /// path, to help choosing the number of contexts
+        #[clap(long, value_parser, value_name = "PATH")]
+        cost_curve: Option<PathBuf>,
     },
 
     /// Generate all possible binned variants for given model
     BinContextsAll {
-        /// Input model file path
+        /// Input model file path; `-` is the standard input
         #[clap(value_parser = input_stream)]
         input: InputStream,
 
@@ -167,16 +232,32 @@ pub enum Commands {
         csv: bool,
     },
 
-    /// Compress a FASTQ file
+    /// Compress FASTQ files
     Compress {
-        /// Input FASTQ file to read; `-` is the standard output
-        #[clap(default_value_t, value_parser = input_stream)]
-        input: InputStream,
+        /// Input FASTQ files or directories to read; `-` is the standard input
+        #[clap(default_value = "-", value_parser = input_stream)]
+        inputs: Vec<InputStream>,
 
-        /// Output IDN file path; `-` is the standard output
+        /// Output IDN file path; `-` is the standard output. When compressing
+        /// multiple files, `{name}` is replaced with the input file name
         #[clap(short, long, value_parser)]
         output: Option<PathBuf>,
 
+        /// Pattern of the file names to compress in the input directories
+        #[clap(long, value_parser, default_value = "*.fastq")]
+        glob: String,
+
+        /// Compress all the input files into a single IDN file. Each file is
+        /// stored as a separate member named after its path relative to the
+        /// input directory, which can be extracted with `decompress --member`
+        #[clap(long, value_parser)]
+        single_archive: bool,
+
+        /// Decompress the output file after compressing and check that it
+        /// contains exactly the input sequences
+        #[clap(long, value_parser)]
+        verify: bool,
+
         /// Number of additional threads to spawn
         #[clap(long, value_parser)]
         threads: Option<usize>,
@@ -185,23 +266,150 @@ pub enum Commands {
         #[clap(long, value_parser)]
         block_length: Option<usize>,
 
+        /// Maximum number of reads in a single block (accepts k, M, G
+        /// suffixes)
+        #[clap(long, value_parser = count, value_name = "READ_NUM")]
+        block_reads: Option<usize>,
+
+        /// Size the blocks automatically, so that they contain between MIN and
+        /// MAX reads regardless of the read length; a block can exceed
+        /// `--block-length` up to 4 times to reach MIN (accepts k, M, G
+        /// suffixes)
+        #[clap(long, value_parser = count_range, value_name = "MIN-MAX")]
+        target_block_reads: Option<RangeInclusive<usize>>,
+
+        /// Use larger blocks, so that long reads (e.g. Oxford Nanopore or
+        /// PacBio) of up to 32 Mb can be compressed. The best compression
+        /// ratio is achieved with the models using the `long(...)` context
+        /// spec types
+        #[clap(long, value_parser)]
+        long_reads: bool,
+
         /// Do not include sequence identifiers when compressing data
         #[clap(long, value_parser)]
         no_identifiers: bool,
 
-        /// Compression quality (1 - fast, 9 - best)
-        #[clap(default_value_t = 7, long, value_parser = clap::value_parser!(u8).range(1..=9))]
-        quality: u8,
+        /// Store the text following `+` in the FASTQ separator lines along
+        /// with the identifiers, so that it is restored on decompression
+        #[clap(long, value_parser, conflicts_with = "no_identifiers")]
+        preserve_separator: bool,
+
+        /// Compression quality (1 - fast, 9 - best) [default: 7]
+        #[clap(long, value_parser = clap::value_parser!(u8).range(1..=9))]
+        quality: Option<u8>,
 
         /// Make compression as fast as possible. Affects displaying statistics.
         /// Implies --quality=1
         #[clap(long, value_parser)]
         fast: bool,
+
+        /// Store the reads that have the same bases as an earlier read as
+        /// references to it, followed by their quality scores if they differ
+        #[clap(long, value_parser)]
+        deduplicate: bool,
+
+        /// Do not encode the quality scores of the N bases if all of them are
+        /// equal within a block. The resulting file cannot be read by the
+        /// older versions
+        #[clap(long, value_parser)]
+        skip_n_quality: bool,
+
+        /// Delta-encode the quality scores along each read before compressing
+        /// them. Improves the compression ratio only with the models trained
+        /// on the delta-encoded scores
+        #[clap(long, value_parser)]
+        quality_delta: bool,
+
+        /// Store the blocks uncompressed if their reads would be compressed to
+        /// more than this fraction of their raw size (e.g. 0.9), which bounds
+        /// the expansion of incompressible data. The resulting file cannot be
+        /// read by the older versions
+        #[clap(long, value_parser = ratio, value_name = "RATIO")]
+        raw_fallback_ratio: Option<f32>,
+
+        /// Choose the quality score model once for each group of consecutive
+        /// reads from the same flow cell tile (parsed from Illumina read
+        /// identifiers)
+        #[clap(long, value_parser)]
+        group_by_tile: bool,
+
+        /// Store the SHA-256 digest of the input FASTQ data in the output
+        /// file, so it can be checked with `verify --against-source`
+        #[clap(long, value_parser)]
+        source_digest: bool,
+
+        /// Guarantee identical output for identical input and options,
+        /// regardless of the number of threads and the order of the models
+        #[clap(long, value_parser)]
+        deterministic: bool,
+
+        /// Time each output file should be compressed within (in seconds, or
+        /// with the m or h suffix); the compression effort is lowered
+        /// automatically when the throughput is too low to meet it
+        #[clap(long, value_parser = duration, value_name = "DURATION", conflicts_with = "deterministic")]
+        time_budget: Option<Duration>,
+
+        /// Choose the compression effort for each block based on a sample of
+        /// its quality scores, so that the blocks with simple quality scores
+        /// are compressed faster
+        #[clap(long, value_parser)]
+        adaptive_effort: bool,
+
+        /// Count the symbols encoded with each context of each model and
+        /// write the counts to given file after compressing: as JSON if the
+        /// file name ends with `.json`, or as CSV otherwise
+        #[clap(long, value_parser, value_name = "PATH")]
+        context_usage: Option<PathBuf>,
+
+        /// Write the details of how each read has been compressed (the models
+        /// chosen, the estimated and actual compressed size, and the number of
+        /// symbols not covered by the models) to given CSV file
+        #[clap(long, value_parser, value_name = "PATH")]
+        explain: Option<PathBuf>,
+
+        #[clap(flatten)]
+        models: ModelArgs,
+    },
+
+    /// Compress a pair of FASTQ files with paired-end reads into a single IDN
+    /// file
+    CompressPe {
+        /// Input FASTQ file with the first reads of the pairs
+        #[clap(value_parser = input_file)]
+        input_1: InputFile,
+
+        /// Input FASTQ file with the second reads of the pairs
+        #[clap(value_parser = input_file)]
+        input_2: InputFile,
+
+        /// Output IDN file path; `-` is the standard output
+        #[clap(short, long, value_parser)]
+        output: PathBuf,
+
+        /// Number of additional threads to spawn
+        #[clap(long, value_parser)]
+        threads: Option<usize>,
+
+        /// Do not include sequence identifiers when compressing data
+        #[clap(long, value_parser)]
+        no_identifiers: bool,
+
+        /// Compression quality (1 - fast, 9 - best) [default: 7]
+        #[clap(long, value_parser = clap::value_parser!(u8).range(1..=9))]
+        quality: Option<u8>,
+
+        /// Make compression as fast as possible. Affects displaying statistics.
+        /// Implies --quality=1
+        #[clap(long, value_parser)]
+        fast: bool,
+
+        #[clap(flatten)]
+        models: ModelArgs,
     },
 
     /// Decompress an IDN file to FASTQ file
     Decompress {
-        /// Input IDN file to read
+        /// Input IDN file to read; `-` is the standard input
         #[clap(default_value_t, value_parser = input_stream)]
         input: InputStream,
 
@@ -209,14 +417,296 @@ pub enum Commands {
         #[clap(short, long, value_parser)]
         output: Option<PathBuf>,
 
+        /// Only decompress the member with given name of a file created with
+        /// `compress --single-archive`
+        #[clap(long, value_parser)]
+        member: Option<String>,
+
+        /// Number of additional threads to spawn
+        #[clap(long, value_parser)]
+        threads: Option<usize>,
+
+        /// Number of blocks to read ahead in a separate thread, to overlap the
+        /// I/O with decompression
+        #[clap(long, value_parser)]
+        read_ahead: Option<usize>,
+
+        #[clap(flatten)]
+        models: ModelArgs,
+    },
+
+    /// Decompress an IDN file created with `compress-pe` to a pair of FASTQ
+    /// files
+    DecompressPe {
+        /// Input IDN file to read; `-` is the standard input
+        #[clap(default_value_t, value_parser = input_stream)]
+        input: InputStream,
+
+        /// Output FASTQ file path for the first reads of the pairs
+        #[clap(short = '1', long, value_parser)]
+        output_1: PathBuf,
+
+        /// Output FASTQ file path for the second reads of the pairs
+        #[clap(short = '2', long, value_parser)]
+        output_2: PathBuf,
+
+        /// Number of additional threads to spawn
+        #[clap(long, value_parser)]
+        threads: Option<usize>,
+
+        #[clap(flatten)]
+        models: ModelArgs,
+    },
+
+    /// Check the integrity of an IDN file by decompressing it without writing
+    /// any output
+    Verify {
+        /// Input IDN file to read; `-` is the standard input
+        #[clap(default_value_t, value_parser = input_stream)]
+        input: InputStream,
+
+        /// Also check that the IDN file was created from given FASTQ file,
+        /// using the digest stored with `compress --source-digest`
+        #[clap(long, value_parser = input_file, value_name = "FASTQ")]
+        against_source: Option<InputFile>,
+
+        /// Number of additional threads to spawn
+        #[clap(long, value_parser)]
+        threads: Option<usize>,
+
+        #[clap(flatten)]
+        models: ModelArgs,
+    },
+
+    /// Compare a FASTQ file with an IDN file and report the first mismatching
+    /// read
+    Diff {
+        /// FASTQ file to compare; `-` is the standard input
+        #[clap(value_parser = input_stream)]
+        fastq: InputStream,
+
+        /// IDN file to compare; `-` is the standard input
+        #[clap(value_parser = input_stream)]
+        idn: InputStream,
+
+        /// Do not compare the sequence identifiers (useful for files compressed
+        /// with --no-identifiers)
+        #[clap(long, value_parser)]
+        ignore_identifiers: bool,
+
+        /// Number of additional threads to spawn
+        #[clap(long, value_parser)]
+        threads: Option<usize>,
+
+        #[clap(flatten)]
+        models: ModelArgs,
+    },
+
+    /// Concatenate multiple IDN files into one without recompressing them
+    Cat {
+        /// Input IDN files to read; `-` is the standard input
+        #[clap(value_parser = input_file, required = true)]
+        inputs: Vec<InputFile>,
+
+        /// Output IDN file path; `-` is the standard output
+        #[clap(short, long, value_parser, default_value = "-")]
+        output: PathBuf,
+    },
+
+    /// Decompress an IDN file and compress it again with different options,
+    /// e.g. to use newer models, without writing the FASTQ data anywhere
+    Recompress {
+        /// Input IDN file to read; `-` is the standard input
+        #[clap(value_parser = input_stream)]
+        input: InputStream,
+
+        /// Output IDN file path; `-` is the standard output
+        #[clap(value_parser)]
+        output: PathBuf,
+
+        /// Number of additional threads to spawn
+        #[clap(long, value_parser)]
+        threads: Option<usize>,
+
+        /// Do not include sequence identifiers in the output file
+        #[clap(long, value_parser)]
+        no_identifiers: bool,
+
+        /// Compression quality (1 - fast, 9 - best) [default: 7]
+        #[clap(long, value_parser = clap::value_parser!(u8).range(1..=9))]
+        quality: Option<u8>,
+
+        /// Make compression as fast as possible. Implies --quality=1
+        #[clap(long, value_parser)]
+        fast: bool,
+
+        /// Store the reads that have the same bases as an earlier read as
+        /// references to it, followed by their quality scores if they differ
+        #[clap(long, value_parser)]
+        deduplicate: bool,
+
+        #[clap(flatten)]
+        models: ModelArgs,
+    },
+
+    /// Split an IDN file into multiple files without recompressing it
+    Split {
+        /// Input IDN file to read; `-` is the standard input
+        #[clap(value_parser = input_file)]
+        input: InputFile,
+
+        /// Output file path; the part number is appended to the file name
+        #[clap(short, long, value_parser)]
+        output: Option<PathBuf>,
+
+        /// Maximum number of reads in a single output file (accepts k, M, G
+        /// suffixes); files are always split along the block boundaries
+        #[clap(long, value_parser = count, value_name = "READ_NUM", required_unless_present = "parts", conflicts_with = "parts")]
+        reads_per_file: Option<usize>,
+
+        /// Number of output files to split the input into
+        #[clap(long, value_parser = count, value_name = "PART_NUM")]
+        parts: Option<usize>,
+    },
+
+    /// Decompress only the first reads of an IDN file
+    Head {
+        /// Input IDN file to read; `-` is the standard input
+        #[clap(default_value_t, value_parser = input_stream)]
+        input: InputStream,
+
+        /// Output file path; `-` is the standard output
+        #[clap(short, long, value_parser, default_value = "-")]
+        output: PathBuf,
+
+        /// Number of reads to output (accepts k, M, G suffixes)
+        #[clap(short = 'n', long, value_parser = count, value_name = "READ_NUM", default_value = "10")]
+        reads: usize,
+
         /// Number of additional threads to spawn
         #[clap(long, value_parser)]
         threads: Option<usize>,
+
+        #[clap(flatten)]
+        models: ModelArgs,
+    },
+
+    /// Decompress only the reads with identifiers matching given pattern
+    Grep {
+        /// Input IDN file to read; `-` is the standard input
+        #[clap(default_value_t, value_parser = input_stream)]
+        input: InputStream,
+
+        /// Output file path; `-` is the standard output
+        #[clap(short, long, value_parser, default_value = "-")]
+        output: PathBuf,
+
+        /// Regular expression the read identifiers are matched against
+        #[clap(long, value_parser)]
+        id_pattern: String,
+
+        /// Number of additional threads to spawn
+        #[clap(long, value_parser)]
+        threads: Option<usize>,
+
+        #[clap(flatten)]
+        models: ModelArgs,
+    },
+
+    /// Decompress an IDN file to a format easier to process with tools not
+    /// supporting FASTQ, e.g. only the acids or a matrix of the quality scores
+    Export {
+        /// Input IDN file to read; `-` is the standard input
+        #[clap(default_value_t, value_parser = input_stream)]
+        input: InputStream,
+
+        /// Output file path; `-` is the standard output
+        #[clap(short, long, value_parser, default_value = "-")]
+        output: PathBuf,
+
+        /// Format to export the reads to
+        #[clap(long, arg_enum, value_parser)]
+        format: ExportFormatCli,
+
+        /// Only export the member with given name of a file created with
+        /// `compress --single-archive`
+        #[clap(long, value_parser)]
+        member: Option<String>,
+
+        /// Number of additional threads to spawn
+        #[clap(long, value_parser)]
+        threads: Option<usize>,
+
+        #[clap(flatten)]
+        models: ModelArgs,
+    },
+
+    /// Compress a sample of a FASTQ file with different settings and compare
+    /// the compression ratio and throughput
+    Bench {
+        /// Input FASTQ file to read; `-` is the standard input
+        #[clap(default_value_t, value_parser = input_stream)]
+        input: InputStream,
+
+        /// Compression qualities to test (1 - fast, 9 - best)
+        #[clap(long, value_parser = clap::value_parser!(u8).range(1..=9), value_delimiter = ',', default_value = "1,3,5,7,9")]
+        qualities: Vec<u8>,
+
+        /// Numbers of additional threads to test; by default, only the default
+        /// thread number is tested
+        #[clap(long, value_parser, value_delimiter = ',')]
+        threads: Vec<usize>,
+
+        /// Number of reads from the beginning of the file to use as the sample
+        /// (accepts k, M, G suffixes)
+        #[clap(short = 'n', long, value_parser = count, value_name = "READ_NUM", default_value = "100k")]
+        reads: usize,
+
+        /// Output the results as JSON
+        #[clap(long, value_parser)]
+        json: bool,
+
+        #[clap(flatten)]
+        models: ModelArgs,
+    },
+
+    /// Compress a sample of a FASTQ file with each of the models separately
+    /// and rank the models by the compression rate they achieve
+    EvalModels {
+        /// Input FASTQ file to read; `-` is the standard input
+        #[clap(default_value_t, value_parser = input_stream)]
+        input: InputStream,
+
+        /// Number of reads from the beginning of the file to use as the sample
+        /// (accepts k, M, G suffixes)
+        #[clap(short = 'n', long, value_parser = count, value_name = "READ_NUM", default_value = "100k")]
+        reads: usize,
+
+        /// Output the results as JSON
+        #[clap(long, value_parser)]
+        json: bool,
+
+        #[clap(flatten)]
+        models: ModelArgs,
+    },
+
+    /// Print information about the structure of an IDN file
+    Inspect {
+        /// Input IDN file to read; `-` is the standard input
+        #[clap(default_value_t, value_parser = input_stream)]
+        input: InputStream,
+
+        /// Output the information as JSON
+        #[clap(long, value_parser)]
+        json: bool,
+
+        #[clap(flatten)]
+        models: ModelArgs,
     },
 
-    /// Print statistics about a FASTQ file
+    /// Print statistics about a FASTQ or IDN file
     Stats {
-        /// Input FASTQ file to read; `-` is the standard output
+        /// Input FASTQ or IDN file to read; `-` is the standard input
         #[clap(default_value_t, value_parser = input_stream)]
         input: InputStream,
     },
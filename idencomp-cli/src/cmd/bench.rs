@@ -0,0 +1,176 @@
+use std::io::{BufReader, Read};
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use idencomp::fastq::reader::FastqReader;
+use idencomp::fastq::FastqSequence;
+use idencomp::idn::compressor::{CompressionQuality, IdnCompressor, IdnCompressorParams};
+use idencomp::idn::model_provider::ModelProvider;
+use idencomp::progress::ProgressNotifier;
+use log::info;
+use serde_json::json;
+
+use crate::PROGRESS_BAR;
+
+#[derive(Debug)]
+struct BenchResult {
+    quality: u8,
+    threads: Option<usize>,
+    compressed_bytes: usize,
+    time: Duration,
+}
+
+impl BenchResult {
+    fn threads_str(&self) -> String {
+        self.threads
+            .map_or_else(|| "auto".to_owned(), |threads| threads.to_string())
+    }
+
+    fn rate(&self, in_bytes: usize) -> f64 {
+        self.compressed_bytes as f64 / in_bytes as f64 * 100.0
+    }
+
+    fn throughput(&self, in_bytes: usize) -> f64 {
+        in_bytes as f64 / self.time.as_secs_f64() / 1_000_000.0
+    }
+}
+
+pub fn bench<R: Read>(
+    reader: R,
+    mut model_provider: ModelProvider,
+    qualities: &[u8],
+    threads: &[usize],
+    read_num: usize,
+    output_json: bool,
+) -> anyhow::Result<()> {
+    let sequences = read_sequences(reader, read_num)?;
+    let in_bytes: usize = sequences.iter().map(|sequence| sequence.size().get()).sum();
+    info!(
+        "Benchmarking on {} sequences ({} bytes)",
+        sequences.len(),
+        in_bytes
+    );
+
+    model_provider.preprocess_compressor_models_with_progress(&*PROGRESS_BAR);
+
+    let threads: Vec<Option<usize>> = if threads.is_empty() {
+        vec![None]
+    } else {
+        threads.iter().copied().map(Some).collect()
+    };
+
+    PROGRESS_BAR.set_iter_num((qualities.len() * threads.len()) as u64);
+    let mut results = Vec::new();
+    for &quality in qualities {
+        for &thread_num in &threads {
+            let result = bench_single(&sequences, model_provider.clone(), quality, thread_num)?;
+            info!(
+                "Quality {}, threads {}: {} bytes in {:.3}s",
+                quality,
+                result.threads_str(),
+                result.compressed_bytes,
+                result.time.as_secs_f64()
+            );
+            results.push(result);
+            PROGRESS_BAR.inc_iter();
+        }
+    }
+    PROGRESS_BAR.finish();
+
+    if output_json {
+        print_json(&results, sequences.len(), in_bytes)?;
+    } else {
+        print_table(&results, in_bytes);
+    }
+
+    Ok(())
+}
+
+pub(crate) fn read_sequences<R: Read>(
+    reader: R,
+    read_num: usize,
+) -> anyhow::Result<Vec<FastqSequence>> {
+    let fastq_reader = FastqReader::new(BufReader::new(reader));
+
+    fastq_reader
+        .into_iter()
+        .take(read_num)
+        .map(|sequence| sequence.context("Could not parse a sequence from the FASTQ file"))
+        .collect()
+}
+
+fn bench_single(
+    sequences: &[FastqSequence],
+    model_provider: ModelProvider,
+    quality: u8,
+    threads: Option<usize>,
+) -> anyhow::Result<BenchResult> {
+    let mut params = IdnCompressorParams::builder()
+        .model_provider(model_provider)
+        .quality(CompressionQuality::new(quality));
+    if let Some(threads) = threads {
+        params = params.thread_num(threads);
+    }
+    let params = params.build()?;
+
+    let start_time = Instant::now();
+    let mut data = Vec::new();
+    let mut idn_writer = IdnCompressor::with_params(&mut data, params);
+    for sequence in sequences {
+        idn_writer
+            .add_sequence(sequence.clone())
+            .context("Could not compress a sequence")?;
+    }
+    idn_writer.finish()?;
+    let time = start_time.elapsed();
+
+    Ok(BenchResult {
+        quality,
+        threads,
+        compressed_bytes: data.len(),
+        time,
+    })
+}
+
+fn print_table(results: &[BenchResult], in_bytes: usize) {
+    println!(
+        "{:>7} {:>7} {:>12} {:>9} {:>9} {:>10}",
+        "Quality", "Threads", "Size", "Ratio", "Time", "Throughput"
+    );
+    for result in results {
+        println!(
+            "{:>7} {:>7} {:>12} {:>8.3}% {:>8.3}s {:>5.2} MB/s",
+            result.quality,
+            result.threads_str(),
+            result.compressed_bytes,
+            result.rate(in_bytes),
+            result.time.as_secs_f64(),
+            result.throughput(in_bytes),
+        );
+    }
+}
+
+fn print_json(results: &[BenchResult], sequence_num: usize, in_bytes: usize) -> anyhow::Result<()> {
+    let results: Vec<_> = results
+        .iter()
+        .map(|result| {
+            json!({
+                "quality": result.quality,
+                "threads": result.threads,
+                "compressed_bytes": result.compressed_bytes,
+                "ratio": result.rate(in_bytes) / 100.0,
+                "time_secs": result.time.as_secs_f64(),
+                "throughput_bytes_per_sec": in_bytes as f64 / result.time.as_secs_f64(),
+            })
+        })
+        .collect();
+
+    let value = json!({
+        "sequences": sequence_num,
+        "input_bytes": in_bytes,
+        "results": results,
+    });
+    println!("{}", serde_json::to_string_pretty(&value)?);
+
+    Ok(())
+}
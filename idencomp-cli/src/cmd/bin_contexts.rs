@@ -1,18 +1,18 @@
 use std::io::{BufReader, BufWriter, Read, Write};
 
 use anyhow::Context;
-use idencomp::context_binning::{bin_contexts_with_model, ContextBinningOptions};
+use idencomp::context_binning::{bin_contexts_with_model, ContextBinningOptions, MergeStep};
 use idencomp::model::Model;
 use idencomp::model_serializer::SerializableModel;
 use log::info;
 
 use crate::PROGRESS_BAR;
 
-pub fn bin_contexts<R: Read, W: Write>(
+pub fn bin_contexts<R: Read, W: Write, C: Write>(
     reader: R,
-    writer: W,
-    num_contexts: usize,
+    outputs: Vec<(usize, W)>,
     pre_bin: Option<usize>,
+    cost_curve: Option<C>,
 ) -> anyhow::Result<()> {
     let model = SerializableModel::read_model(BufReader::new(reader))
         .context("Could not read the model")?;
@@ -23,16 +23,42 @@ pub fn bin_contexts<R: Read, W: Write>(
     if let Some(pre_bin) = pre_bin {
         options = options.pre_binning_num(pre_bin);
     }
+    options = options.record_cost_curve(cost_curve.is_some());
     let tree = bin_contexts_with_model(&model, &options.build());
 
-    let model = Model::with_model_and_spec_type(model_type, spec_type, tree.traverse(num_contexts));
-    info!(
-        "Generated model: contexts: {}, rate: {}",
-        model.len(),
-        model.rate()
-    );
-    SerializableModel::write_model(&model, BufWriter::new(writer))
-        .context("Could not write the new model")?;
+    if let Some(writer) = cost_curve {
+        let steps = tree.cost_curve().unwrap_or_default();
+        write_cost_curve(steps, writer).context("Could not write the cost curve")?;
+    }
+
+    let nums_contexts: Vec<usize> = outputs.iter().map(|(num, _)| *num).collect();
+    let contexts = tree.traverse_multiple(&nums_contexts);
+
+    for ((_, writer), contexts) in outputs.into_iter().zip(contexts) {
+        let model = Model::with_model_and_spec_type(model_type, spec_type, contexts);
+        info!(
+            "Generated model: contexts: {}, rate: {}",
+            model.len(),
+            model.rate()
+        );
+        SerializableModel::write_model(&model, BufWriter::new(writer))
+            .context("Could not write the new model")?;
+    }
+
+    Ok(())
+}
+
+fn write_cost_curve<W: Write>(steps: &[MergeStep], writer: W) -> anyhow::Result<()> {
+    let mut writer = csv::Writer::from_writer(writer);
+    writer.write_record(["context num", "merge cost", "total cost"])?;
+    for step in steps {
+        writer.write_record([
+            step.num_contexts().to_string(),
+            step.merge_cost().to_string(),
+            step.total_cost().to_string(),
+        ])?;
+    }
+    writer.flush()?;
 
     Ok(())
 }
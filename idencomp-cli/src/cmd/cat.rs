@@ -0,0 +1,14 @@
+use std::io::{BufReader, BufWriter, Read, Write};
+
+use anyhow::Context;
+use idencomp::idn::concat::concat;
+
+pub fn cat<R: Read, W: Write>(readers: Vec<R>, writer: W) -> anyhow::Result<()> {
+    let readers = readers.into_iter().map(BufReader::new).collect();
+    let mut writer = BufWriter::new(writer);
+
+    concat(readers, &mut writer).context("Could not concatenate the IDN files")?;
+    writer.flush()?;
+
+    Ok(())
+}
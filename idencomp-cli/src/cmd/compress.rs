@@ -1,44 +1,169 @@
-use std::io::{BufReader, Read, Write};
-use std::path::Path;
-use std::sync::Arc;
+use std::collections::hash_map::DefaultHasher;
+use std::fs::File;
+use std::hash::{Hash, Hasher};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-use anyhow::Context;
-use idencomp::fastq::reader::FastqReader;
+use anyhow::{bail, Context};
+use idencomp::fastq::parallel_reader::ParallelFastqReader;
+use idencomp::fastq::reader::FastqReaderParams;
+use idencomp::fastq::FastqSequence;
 use idencomp::idn::compressor::{CompressionQuality, IdnCompressor, IdnCompressorParams};
+use idencomp::idn::context_usage::ContextUsage;
+use idencomp::idn::decompressor::{IdnDecompressor, IdnDecompressorError, IdnDecompressorParams};
+use idencomp::idn::explain::{SequenceExplainer, SequenceExplanation};
+use idencomp::idn::inspector::inspect;
 use idencomp::idn::model_provider::ModelProvider;
-use idencomp::progress::ProgressNotifier;
+use idencomp::idn::source_digest::SourceHasher;
+use idencomp::idn::transform::QualityDelta;
+use idencomp::progress::{ByteNum, ProgressNotifier};
+use idencomp::sequence_source::SequenceSource;
+use itertools::Itertools;
+use log::{info, warn};
+use serde_json::json;
 
-#[allow(clippy::too_many_arguments)]
-pub fn compress<R: Read, W: Write + Send>(
-    reader: R,
+use crate::cmd::inspect::identifier_to_hex;
+use crate::opts::InputReader;
+
+/// Order-dependent digest of a stream of sequences, used to check whether the
+/// decompressed data is the same as the compressor input.
+#[derive(Debug)]
+pub struct SequenceDigest {
+    hasher: DefaultHasher,
+    sequence_num: usize,
+    include_identifiers: bool,
+}
+
+impl SequenceDigest {
+    #[must_use]
+    pub fn new(include_identifiers: bool) -> Self {
+        Self {
+            hasher: DefaultHasher::new(),
+            sequence_num: 0,
+            include_identifiers,
+        }
+    }
+
+    pub fn add(&mut self, sequence: &FastqSequence) {
+        if self.include_identifiers {
+            sequence.identifier().hash(&mut self.hasher);
+        }
+        sequence.acids().hash(&mut self.hasher);
+        sequence.quality_scores().hash(&mut self.hasher);
+        self.sequence_num += 1;
+    }
+
+    #[must_use]
+    fn finish(&self) -> (usize, u64) {
+        (self.sequence_num, self.hasher.finish())
+    }
+}
+
+/// The command line options controlling the compression, shared by the
+/// commands that create IDN files. The options that are not set leave the
+/// defaults of [`IdnCompressorParams`].
+#[derive(Debug, Clone, Default)]
+pub struct CompressOptions {
+    pub threads: Option<usize>,
+    pub block_length: Option<usize>,
+    pub block_reads: Option<usize>,
+    pub target_block_reads: Option<RangeInclusive<usize>>,
+    pub long_reads: bool,
+    pub no_identifiers: bool,
+    /// Only used when the FASTQ data is read by [`compress()`].
+    pub preserve_separator: bool,
+    pub quality: Option<u8>,
+    pub fast: bool,
+    pub deduplicate: bool,
+    pub skip_n_quality: bool,
+    pub quality_delta: bool,
+    pub raw_fallback_ratio: Option<f32>,
+    pub group_by_tile: bool,
+    pub source_hasher: Option<SourceHasher>,
+    pub context_usage: Option<ContextUsage>,
+    pub explainer: Option<Arc<dyn SequenceExplainer>>,
+    pub deterministic: bool,
+    pub time_budget: Option<Duration>,
+    pub adaptive_effort: bool,
+    pub input_size: Option<ByteNum>,
+}
+
+/// Compresses the FASTQ data from given readers. If a reader is paired with a
+/// member name, it is stored as a separate container member.
+pub fn compress<R: BufRead, W: Write + Send>(
+    inputs: Vec<(Option<String>, R)>,
     writer: W,
-    threads: Option<usize>,
-    block_length: Option<usize>,
-    no_identifiers: bool,
-    quality: u8,
-    fast: bool,
+    model_provider: ModelProvider,
+    options: CompressOptions,
     progress_notifier: Arc<dyn ProgressNotifier>,
-) -> anyhow::Result<()> {
-    let fastq_reader = FastqReader::new(BufReader::new(reader));
+) -> anyhow::Result<SequenceDigest> {
+    let reader_params = FastqReaderParams::builder()
+        .preserve_separator(options.preserve_separator)
+        .build();
+    let mut digest = SequenceDigest::new(!options.no_identifiers);
+    let params = compressor_params(model_provider, options, progress_notifier)?;
+    let mut idn_writer = IdnCompressor::with_params(writer, params);
 
-    let mut params = IdnCompressorParams::builder();
-    params
-        .model_provider(ModelProvider::from_directory(Path::new("models/"))?)
-        .progress_notifier(progress_notifier)
-        .quality(CompressionQuality::new(quality))
-        .fast(fast)
-        .include_identifiers(!no_identifiers);
-    if let Some(threads) = threads {
-        params.thread_num(threads);
+    for (member, reader) in inputs {
+        if let Some(name) = member {
+            idn_writer
+                .start_member(name)
+                .context("Could not start a new member of the compressed file")?;
+        }
+        let source = DigestSource {
+            source: ParallelFastqReader::with_params(reader, reader_params.clone()),
+            digest: &mut digest,
+        };
+        idn_writer
+            .compress_from(source)
+            .context("Could not compress the sequences from the FASTQ file")?;
     }
-    if let Some(block_length) = block_length {
-        params.max_block_total_len(block_length);
+
+    idn_writer.finish()?;
+
+    Ok(digest)
+}
+
+/// [`SequenceSource`] adding all the sequences read from the inner source to
+/// a [`SequenceDigest`].
+struct DigestSource<'a, S> {
+    source: S,
+    digest: &'a mut SequenceDigest,
+}
+
+impl<'a, S: SequenceSource> SequenceSource for DigestSource<'a, S> {
+    fn next_sequence(&mut self) -> idencomp::Result<Option<FastqSequence>> {
+        let sequence = self.source.next_sequence()?;
+        if let Some(sequence) = &sequence {
+            self.digest.add(sequence);
+        }
+
+        Ok(sequence)
     }
-    let params = params.build();
+}
+
+/// Compresses given sequences with given options.
+pub fn compress_sequences<I, W>(
+    sequences: I,
+    writer: W,
+    model_provider: ModelProvider,
+    options: CompressOptions,
+    progress_notifier: Arc<dyn ProgressNotifier>,
+) -> anyhow::Result<SequenceDigest>
+where
+    I: Iterator<Item = anyhow::Result<FastqSequence>>,
+    W: Write + Send,
+{
+    let mut digest = SequenceDigest::new(!options.no_identifiers);
+    let params = compressor_params(model_provider, options, progress_notifier)?;
     let mut idn_writer = IdnCompressor::with_params(writer, params);
 
-    for sequence in fastq_reader {
-        let sequence = sequence.context("Could not parse a sequence from the FASTQ file")?;
+    for sequence in sequences {
+        let sequence = sequence?;
+        digest.add(&sequence);
         idn_writer
             .add_sequence(sequence)
             .context("Could not write a sequence to the compressed file")?;
@@ -46,5 +171,307 @@ pub fn compress<R: Read, W: Write + Send>(
 
     idn_writer.finish()?;
 
+    Ok(digest)
+}
+
+/// Builds the compressor parameters from the command line options.
+fn compressor_params(
+    model_provider: ModelProvider,
+    options: CompressOptions,
+    progress_notifier: Arc<dyn ProgressNotifier>,
+) -> anyhow::Result<IdnCompressorParams> {
+    let quality = options
+        .quality
+        .map(CompressionQuality::try_new)
+        .transpose()?
+        .unwrap_or_default();
+    if options.long_reads
+        && !(0..model_provider.len())
+            .any(|index| model_provider[index].context_spec_type().is_long_read())
+    {
+        warn!(
+            "None of the models uses a long read context spec type; generate such models with \
+            `generate-model-all` for the best compression ratio of long reads"
+        );
+    }
+
+    let mut params = IdnCompressorParams::builder()
+        .model_provider(model_provider)
+        .progress_notifier(progress_notifier)
+        .quality(quality)
+        .fast(options.fast)
+        .long_reads(options.long_reads)
+        .deduplicate(options.deduplicate)
+        .skip_n_q_scores(options.skip_n_quality)
+        .group_by_tile(options.group_by_tile)
+        .deterministic(options.deterministic)
+        .adaptive_effort(options.adaptive_effort)
+        .include_identifiers(!options.no_identifiers);
+    if let Some(threads) = options.threads {
+        params = params.thread_num(threads);
+    }
+    if let Some(block_length) = options.block_length {
+        params = params.max_block_total_len(block_length);
+    }
+    if let Some(block_reads) = options.block_reads {
+        params = params.max_block_reads(block_reads);
+    }
+    if options.quality_delta {
+        params = params.transform(Arc::new(QualityDelta));
+    }
+    if let Some(ratio) = options.raw_fallback_ratio {
+        params = params.raw_fallback_ratio(ratio);
+    }
+    if let Some(target_block_reads) = options.target_block_reads {
+        params = params.target_block_reads(target_block_reads);
+    }
+    if let Some(source_hasher) = options.source_hasher {
+        params = params.source_hasher(source_hasher);
+    }
+    if let Some(context_usage) = options.context_usage {
+        params = params.context_usage(context_usage);
+    }
+    if let Some(explainer) = options.explainer {
+        params = params.explainer(explainer);
+    }
+    if let Some(time_budget) = options.time_budget {
+        params = params.time_budget(time_budget);
+    }
+    if let Some(input_size) = options.input_size {
+        params = params.expected_input_size(input_size);
+    }
+
+    Ok(params.build()?)
+}
+
+/// Writes the context usage collected during the compression to given file,
+/// as JSON if the file name ends with `.json`, or as CSV otherwise.
+pub fn write_context_usage(context_usage: &ContextUsage, path: &Path) -> anyhow::Result<()> {
+    let models = context_usage.models();
+    let file = File::create(path)
+        .with_context(|| format!("Could not create the file {}", path.display()))?;
+
+    if path
+        .extension()
+        .map_or(false, |extension| extension == "json")
+    {
+        let value: Vec<_> = models
+            .iter()
+            .map(|model| {
+                json!({
+                    "identifier": identifier_to_hex(model.identifier()),
+                    "type": model.model_type().to_string(),
+                    "total_hits": model.total_hits(),
+                    "dummy_hits": model.dummy_hits(),
+                    "context_hits": model.context_hits(),
+                })
+            })
+            .collect();
+        let mut writer = BufWriter::new(file);
+        serde_json::to_writer_pretty(&mut writer, &value)?;
+        writer.flush()?;
+    } else {
+        let mut writer = csv::Writer::from_writer(file);
+        writer.write_record(["identifier", "type", "context", "hits"])?;
+        for model in &models {
+            let identifier = identifier_to_hex(model.identifier());
+            let model_type = model.model_type().to_string();
+            let contexts = model.context_hits().iter().enumerate();
+            let rows = [("dummy".to_owned(), model.dummy_hits())]
+                .into_iter()
+                .chain(contexts.map(|(index, &hits)| (index.to_string(), hits)));
+            for (context, hits) in rows {
+                writer.write_record([&identifier, &model_type, &context, &hits.to_string()])?;
+            }
+        }
+        writer.flush()?;
+    }
+
+    info!("Context usage written to {}", path.display());
     Ok(())
 }
+
+/// [`SequenceExplainer`] writing the explanation of each sequence as a row of
+/// a CSV file.
+#[derive(Debug)]
+pub struct CsvExplainer {
+    path: PathBuf,
+    state: Mutex<CsvExplainerState>,
+}
+
+#[derive(Debug)]
+struct CsvExplainerState {
+    writer: csv::Writer<File>,
+    /// The first error that occurred when writing; the explanations are not
+    /// written anymore after that.
+    error: Option<csv::Error>,
+}
+
+impl CsvExplainer {
+    /// Creates the CSV file at given path and writes its header.
+    pub fn create(path: &Path) -> anyhow::Result<Self> {
+        let file = File::create(path)
+            .with_context(|| format!("Could not create the file {}", path.display()))?;
+        let mut writer = csv::Writer::from_writer(file);
+        writer.write_record([
+            "block",
+            "index",
+            "identifier",
+            "length",
+            "acid_model",
+            "q_score_model",
+            "estimated_bytes",
+            "actual_bytes",
+            "acid_dummy_hits",
+            "q_score_dummy_hits",
+            "q_score_model_switches",
+        ])?;
+
+        Ok(Self {
+            path: path.to_owned(),
+            state: Mutex::new(CsvExplainerState {
+                writer,
+                error: None,
+            }),
+        })
+    }
+
+    /// Flushes the CSV file, returning the error that occurred when writing
+    /// the explanations, if any.
+    pub fn finish(&self) -> anyhow::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(error) = state.error.take() {
+            return Err(error.into());
+        }
+        state.writer.flush()?;
+
+        info!("Sequence explanations written to {}", self.path.display());
+        Ok(())
+    }
+}
+
+impl SequenceExplainer for CsvExplainer {
+    fn explain(&self, explanation: SequenceExplanation) {
+        let mut state = self.state.lock().unwrap();
+        if state.error.is_some() {
+            return;
+        }
+
+        let result = state.writer.write_record([
+            explanation.block().to_string(),
+            explanation.index().to_string(),
+            explanation.identifier().to_owned(),
+            explanation.length().to_string(),
+            identifier_to_hex(explanation.acid_model()),
+            identifier_to_hex(explanation.q_score_model()),
+            explanation
+                .estimated_bytes()
+                .map_or_else(String::new, |bytes| bytes.to_string()),
+            explanation.actual_bytes().to_string(),
+            explanation.acid_dummy_hits().to_string(),
+            explanation.q_score_dummy_hits().to_string(),
+            explanation.q_score_model_switches().to_string(),
+        ]);
+        if let Err(error) = result {
+            state.error = Some(error);
+        }
+    }
+}
+
+/// Returns the name of the container member to store the data from given
+/// reader as: the path of the file relative to the input directory `root` it
+/// has been found in, the file name if the file has been given directly, or
+/// `-` for the standard input. The path components are always separated with
+/// `/`, so that the names do not depend on the platform.
+#[must_use]
+pub fn member_name(root: &Path, reader: &InputReader) -> String {
+    let path = match reader.file_path() {
+        Some(path) => path,
+        None => return "-".to_owned(),
+    };
+
+    let relative_path = path
+        .strip_prefix(root)
+        .ok()
+        .filter(|relative_path| !relative_path.as_os_str().is_empty())
+        .or_else(|| path.file_name().map(Path::new))
+        .unwrap_or(path);
+    relative_path
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .join("/")
+}
+
+/// Returns the total size of the data from given readers, or `None` if the
+/// size of any of them is unknown (e.g. for the standard input).
+pub fn input_size<'a, I>(readers: I) -> anyhow::Result<Option<ByteNum>>
+where
+    I: IntoIterator<Item = &'a InputReader>,
+{
+    let mut total_size = Some(0);
+    for reader in readers {
+        total_size = total_size
+            .zip(reader.length()?)
+            .map(|(total_size, size)| total_size + size as usize);
+    }
+
+    Ok(total_size.map(ByteNum::new))
+}
+
+pub fn verify_compressed(
+    path: &Path,
+    model_provider: &ModelProvider,
+    threads: Option<usize>,
+    expected_digest: &SequenceDigest,
+) -> anyhow::Result<()> {
+    info!("Verifying {}", path.display());
+
+    let mut params =
+        IdnDecompressorParams::builder().model_provider(used_models(path, model_provider)?);
+    if let Some(threads) = threads {
+        params = params.thread_num(threads);
+    }
+    let params = params.build();
+    let file = File::open(path).context("Could not open the compressed file")?;
+    let idn_reader = IdnDecompressor::with_params(BufReader::new(file), params);
+
+    let mut digest = SequenceDigest::new(expected_digest.include_identifiers);
+    for sequence in idn_reader {
+        let sequence = sequence.context("Could not read a sequence from the compressed file")?;
+        digest.add(&sequence);
+    }
+
+    let (expected_num, expected_hash) = expected_digest.finish();
+    let (actual_num, actual_hash) = digest.finish();
+    if expected_num != actual_num {
+        bail!(
+            "Compressed file contains {} sequences, but {} were expected",
+            actual_num,
+            expected_num
+        );
+    }
+    if expected_hash != actual_hash {
+        bail!("Decompressed sequences do not match the input");
+    }
+
+    info!("Compressed file verified: {} sequences", actual_num);
+    Ok(())
+}
+
+/// Returns a `ModelProvider` with only the models used by given IDN file, so
+/// that the preprocessed models of `model_provider` do not have to be cloned.
+fn used_models(path: &Path, model_provider: &ModelProvider) -> anyhow::Result<ModelProvider> {
+    let file = File::open(path).context("Could not open the compressed file")?;
+    let info = inspect(BufReader::new(file)).context("Could not read the compressed file")?;
+
+    let identifiers = info.model_identifiers();
+    model_provider
+        .has_all_models(identifiers)
+        .map_err(IdnDecompressorError::UnknownModel)?;
+    let models = identifiers
+        .iter()
+        .map(|identifier| model_provider[model_provider.index_of(identifier)].clone())
+        .collect();
+
+    Ok(ModelProvider::new(models))
+}
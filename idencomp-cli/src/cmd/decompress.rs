@@ -1,5 +1,4 @@
 use std::io::{BufWriter, Read, Write};
-use std::path::Path;
 use std::sync::Arc;
 
 use anyhow::Context;
@@ -7,33 +6,53 @@ use idencomp::fastq::writer::FastqWriter;
 use idencomp::idn::decompressor::{IdnDecompressor, IdnDecompressorParams};
 use idencomp::idn::model_provider::ModelProvider;
 use idencomp::progress::ProgressNotifier;
+use log::warn;
 
 pub fn decompress<R: Read + Send, W: Write>(
     reader: R,
     writer: W,
+    model_provider: ModelProvider,
+    member: Option<String>,
     threads: Option<usize>,
+    read_ahead: Option<usize>,
     progress_notifier: Arc<dyn ProgressNotifier>,
 ) -> anyhow::Result<()> {
-    let mut params = IdnDecompressorParams::builder();
-    params
-        .model_provider(ModelProvider::from_directory(Path::new("models/"))?)
+    let mut params = IdnDecompressorParams::builder()
+        .model_provider(model_provider)
         .progress_notifier(progress_notifier);
     if let Some(threads) = threads {
-        params.thread_num(threads);
+        params = params.thread_num(threads);
+    }
+    if let Some(read_ahead) = read_ahead {
+        params = params.read_ahead(read_ahead);
+    }
+    if let Some(member) = member {
+        params = params.member(member);
     }
     let params = params.build();
     let idn_reader = IdnDecompressor::with_params(reader, params);
 
     let mut fastq_writer = FastqWriter::new(BufWriter::new(writer));
 
+    // FASTQ has no place for the auxiliary data, so it is dropped
+    let mut aux_data_reads = 0;
     for sequence in idn_reader {
         let sequence = sequence.context("Could not read a sequence from the compressed file")?;
+        if !sequence.aux_data().is_empty() {
+            aux_data_reads += 1;
+        }
         fastq_writer
             .write_sequence(&sequence)
             .context("Could not write a sequence to the FASTQ file")?;
     }
 
     fastq_writer.flush()?;
+    if aux_data_reads > 0 {
+        warn!(
+            "{} reads have auxiliary data, which cannot be stored in FASTQ and has been dropped",
+            aux_data_reads
+        );
+    }
 
     Ok(())
 }
@@ -0,0 +1,150 @@
+use std::fmt::Display;
+use std::io::{BufReader, Read};
+
+use anyhow::{bail, Context};
+use idencomp::fastq::reader::FastqReader;
+use idencomp::fastq::FastqSequence;
+use idencomp::idn::decompressor::{IdnDecompressor, IdnDecompressorParams};
+use idencomp::idn::model_provider::ModelProvider;
+use itertools::Itertools;
+use log::info;
+
+/// Number of symbols shown on each side of the first mismatching symbol.
+const CONTEXT_LEN: usize = 16;
+
+pub fn diff<R1: Read, R2: Read + Send>(
+    fastq_reader: R1,
+    idn_reader: R2,
+    model_provider: ModelProvider,
+    threads: Option<usize>,
+    ignore_identifiers: bool,
+) -> anyhow::Result<()> {
+    let mut fastq_reader = FastqReader::new(BufReader::new(fastq_reader)).into_iter();
+
+    let mut params = IdnDecompressorParams::builder().model_provider(model_provider);
+    if let Some(threads) = threads {
+        params = params.thread_num(threads);
+    }
+    let params = params.build();
+    let mut idn_reader = IdnDecompressor::with_params(idn_reader, params).into_iter();
+
+    let mut index = 0;
+    let files_equal = loop {
+        let fastq_sequence = fastq_reader
+            .next()
+            .transpose()
+            .context("Could not parse a sequence from the FASTQ file")?;
+        let idn_sequence = idn_reader
+            .next()
+            .transpose()
+            .context("Could not read a sequence from the compressed file")?;
+
+        match (fastq_sequence, idn_sequence) {
+            (None, None) => break true,
+            (Some(fastq_sequence), None) => {
+                println!(
+                    "Read {} (@{}) is missing in the IDN file",
+                    index,
+                    fastq_sequence.identifier()
+                );
+                break false;
+            }
+            (None, Some(idn_sequence)) => {
+                println!(
+                    "Read {} (@{}) is missing in the FASTQ file",
+                    index,
+                    idn_sequence.identifier()
+                );
+                break false;
+            }
+            (Some(fastq_sequence), Some(idn_sequence)) => {
+                if !compare(index, &fastq_sequence, &idn_sequence, ignore_identifiers) {
+                    break false;
+                }
+            }
+        }
+
+        index += 1;
+    };
+
+    if !files_equal {
+        bail!("Files differ");
+    }
+
+    info!("Files are identical: {} sequences", index);
+    Ok(())
+}
+
+fn compare(
+    index: usize,
+    fastq_sequence: &FastqSequence,
+    idn_sequence: &FastqSequence,
+    ignore_identifiers: bool,
+) -> bool {
+    if !ignore_identifiers && fastq_sequence.identifier() != idn_sequence.identifier() {
+        println!("Read {}: identifiers differ", index);
+        println!("  FASTQ: @{}", fastq_sequence.identifier());
+        println!("  IDN:   @{}", idn_sequence.identifier());
+        return false;
+    }
+
+    compare_symbols(
+        index,
+        fastq_sequence,
+        "acids",
+        fastq_sequence.acids(),
+        idn_sequence.acids(),
+    ) && compare_symbols(
+        index,
+        fastq_sequence,
+        "quality scores",
+        fastq_sequence.quality_scores(),
+        idn_sequence.quality_scores(),
+    )
+}
+
+fn compare_symbols<T: PartialEq + Display>(
+    index: usize,
+    fastq_sequence: &FastqSequence,
+    field: &str,
+    fastq_symbols: &[T],
+    idn_symbols: &[T],
+) -> bool {
+    if fastq_symbols == idn_symbols {
+        return true;
+    }
+
+    let position = fastq_symbols
+        .iter()
+        .zip(idn_symbols)
+        .position(|(fastq_symbol, idn_symbol)| fastq_symbol != idn_symbol)
+        .unwrap_or_else(|| fastq_symbols.len().min(idn_symbols.len()));
+
+    println!(
+        "Read {} (@{}): {} differ at position {} (length: {} in FASTQ, {} in IDN)",
+        index,
+        fastq_sequence.identifier(),
+        field,
+        position,
+        fastq_symbols.len(),
+        idn_symbols.len()
+    );
+    println!("  FASTQ: {}", symbol_window(fastq_symbols, position));
+    println!("  IDN:   {}", symbol_window(idn_symbols, position));
+
+    false
+}
+
+fn symbol_window<T: Display>(symbols: &[T], position: usize) -> String {
+    let start = position.saturating_sub(CONTEXT_LEN).min(symbols.len());
+    let end = (position + CONTEXT_LEN).min(symbols.len());
+
+    let prefix = if start > 0 { "..." } else { "" };
+    let suffix = if end < symbols.len() { "..." } else { "" };
+    format!(
+        "{}{}{}",
+        prefix,
+        symbols[start..end].iter().join(""),
+        suffix
+    )
+}
@@ -0,0 +1,110 @@
+use std::cmp::Ordering;
+use std::io::Read;
+
+use idencomp::idn::model_evaluation::{evaluate_models, ModelEvaluation};
+use idencomp::idn::model_provider::ModelProvider;
+use idencomp::model::ModelType;
+use log::info;
+use serde_json::json;
+
+use crate::cmd::bench::read_sequences;
+use crate::cmd::inspect::identifier_to_hex;
+
+pub fn eval_models<R: Read>(
+    reader: R,
+    model_provider: ModelProvider,
+    read_num: usize,
+    output_json: bool,
+) -> anyhow::Result<()> {
+    let sequences = read_sequences(reader, read_num)?;
+    info!(
+        "Evaluating {} models on {} sequences",
+        model_provider.len(),
+        sequences.len()
+    );
+
+    let mut evaluations = evaluate_models(model_provider.clone(), &sequences);
+    evaluations.sort_by(compare_evaluations);
+
+    if output_json {
+        print_json(&evaluations, &model_provider, sequences.len())?;
+    } else {
+        print_table(&evaluations, &model_provider);
+    }
+
+    Ok(())
+}
+
+/// Orders the acid models before the quality score models, and then the best
+/// models (with the lowest actual rate) first.
+fn compare_evaluations(a: &ModelEvaluation, b: &ModelEvaluation) -> Ordering {
+    let type_order = |evaluation: &ModelEvaluation| match evaluation.model_type() {
+        ModelType::Acids => 0,
+        ModelType::QualityScores => 1,
+    };
+
+    type_order(a).cmp(&type_order(b)).then_with(|| {
+        a.actual_rate()
+            .partial_cmp(&b.actual_rate())
+            .unwrap_or(Ordering::Equal)
+    })
+}
+
+fn print_table(evaluations: &[ModelEvaluation], model_provider: &ModelProvider) {
+    println!(
+        "{:>4} {:<14} {:<64} {:<24} {:>8} {:>12} {:>12}",
+        "Rank", "Type", "Identifier", "Context spec type", "Contexts", "Estimated", "Actual"
+    );
+
+    let mut rank = 0;
+    let mut last_type = None;
+    for evaluation in evaluations {
+        if last_type != Some(evaluation.model_type()) {
+            rank = 0;
+            last_type = Some(evaluation.model_type());
+        }
+        rank += 1;
+
+        let model = &model_provider[model_provider.index_of(evaluation.identifier())];
+        println!(
+            "{:>4} {:<14} {:<64} {:<24} {:>8} {:>12} {:>12}",
+            rank,
+            evaluation.model_type().to_string(),
+            identifier_to_hex(evaluation.identifier()),
+            model.context_spec_type().name(),
+            model.len(),
+            evaluation.estimated_rate().to_string(),
+            evaluation.actual_rate().to_string(),
+        );
+    }
+}
+
+fn print_json(
+    evaluations: &[ModelEvaluation],
+    model_provider: &ModelProvider,
+    sequence_num: usize,
+) -> anyhow::Result<()> {
+    let models: Vec<_> = evaluations
+        .iter()
+        .map(|evaluation| {
+            let model = &model_provider[model_provider.index_of(evaluation.identifier())];
+            json!({
+                "identifier": identifier_to_hex(evaluation.identifier()),
+                "model_type": evaluation.model_type().to_string(),
+                "context_spec_type": model.context_spec_type().name(),
+                "contexts": model.len(),
+                "estimated_rate": evaluation.estimated_rate().get(),
+                "actual_rate": evaluation.actual_rate().get(),
+                "compressed_bytes": evaluation.compressed_bytes(),
+            })
+        })
+        .collect();
+
+    let value = json!({
+        "sequences": sequence_num,
+        "models": models,
+    });
+    println!("{}", serde_json::to_string_pretty(&value)?);
+
+    Ok(())
+}
@@ -0,0 +1,64 @@
+use std::io::{BufWriter, Read, Write};
+use std::sync::Arc;
+
+use anyhow::Context;
+use clap::ValueEnum;
+use idencomp::fastq::export::{ExportFormat, SequenceExporter};
+use idencomp::idn::decompressor::{IdnDecompressor, IdnDecompressorParams};
+use idencomp::idn::model_provider::ModelProvider;
+use idencomp::progress::ProgressNotifier;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, ValueEnum)]
+pub enum ExportFormatCli {
+    /// FASTA file with the acids only
+    Fasta,
+    /// Tab-separated numeric quality scores, one row per read
+    QualityMatrix,
+    /// CSV table with the identifiers, acids and quality scores
+    Csv,
+}
+
+impl From<ExportFormatCli> for ExportFormat {
+    fn from(format: ExportFormatCli) -> Self {
+        match format {
+            ExportFormatCli::Fasta => ExportFormat::Fasta,
+            ExportFormatCli::QualityMatrix => ExportFormat::QualityMatrix,
+            ExportFormatCli::Csv => ExportFormat::Csv,
+        }
+    }
+}
+
+pub fn export<R: Read + Send, W: Write>(
+    reader: R,
+    writer: W,
+    format: ExportFormat,
+    model_provider: ModelProvider,
+    member: Option<String>,
+    threads: Option<usize>,
+    progress_notifier: Arc<dyn ProgressNotifier>,
+) -> anyhow::Result<()> {
+    let mut params = IdnDecompressorParams::builder()
+        .model_provider(model_provider)
+        .progress_notifier(progress_notifier);
+    if let Some(threads) = threads {
+        params = params.thread_num(threads);
+    }
+    if let Some(member) = member {
+        params = params.member(member);
+    }
+    let params = params.build();
+    let idn_reader = IdnDecompressor::with_params(reader, params);
+
+    let mut exporter = SequenceExporter::new(BufWriter::new(writer), format);
+
+    for sequence in idn_reader {
+        let sequence = sequence.context("Could not read a sequence from the compressed file")?;
+        exporter
+            .write_sequence(&sequence)
+            .context("Could not write a sequence to the output file")?;
+    }
+
+    exporter.flush()?;
+
+    Ok(())
+}
@@ -1,25 +1,32 @@
 use std::fmt::{Display, Formatter};
 use std::fs::File;
-use std::io::{BufReader, BufWriter, Write};
+use std::io::{BufRead, BufReader, BufWriter, Write};
 use std::mem;
 use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 
+use anyhow::Context;
 use clap::ArgEnum;
+use idencomp::context_binning::{bin_contexts_with_model, ContextBinningOptions};
 use idencomp::context_spec::ContextSpecType;
 use idencomp::fastq::reader::FastqReader;
 use idencomp::fastq::FastqQualityScore;
+use idencomp::idn::decompressor::{IdnDecompressor, IdnDecompressorParams};
+use idencomp::idn::inspector::is_idn;
 use idencomp::model::{CompressionRate, Model, ModelType};
 use idencomp::model_generator::ModelGenerator;
 use idencomp::model_serializer::SerializableModel;
 use idencomp::progress::{ByteNum, ProgressNotifier};
 use idencomp::sequence::{Acid, Symbol};
+use idencomp::sequence_source::SequenceSource;
 use itertools::iproduct;
 use log::info;
 use rayon::iter::ParallelIterator;
 use rayon::prelude::IntoParallelIterator;
 
 use crate::csv_stat::CsvStatOutput;
-use crate::opts::InputReader;
+use crate::opts::{InputReader, ModelArgs};
 use crate::PROGRESS_BAR;
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ArgEnum)]
@@ -44,23 +51,49 @@ impl Display for GenerateModelMode {
 
 pub(crate) struct CliModelGenerator {
     input: InputReader,
+    models: ModelArgs,
     stat_output: CsvStatOutput,
     ctx_limit: u32,
+    binning: Option<ModelBinning>,
+}
+
+/// Context binning performed on the generated model before it is written.
+#[derive(Copy, Clone, Debug)]
+struct ModelBinning {
+    num_contexts: usize,
+    pre_bin: Option<usize>,
 }
 
 impl CliModelGenerator {
+    /// Creates a new generator reading the sequences from given input, which
+    /// can be either a FASTQ or an IDN file. The models are only loaded if the
+    /// input has to be decompressed.
     #[must_use]
-    pub fn new(input: InputReader, output_csv: bool, ctx_limit: u32) -> Self {
+    pub fn new(input: InputReader, models: ModelArgs, output_csv: bool, ctx_limit: u32) -> Self {
         Self {
             input,
+            models,
             stat_output: CsvStatOutput::new(output_csv),
             ctx_limit,
+            binning: None,
         }
     }
 
+    /// Bins the contexts of the generated model down to `num_contexts`
+    /// before writing it, so that the full model never has to be written.
+    #[must_use]
+    pub fn with_binning(mut self, num_contexts: usize, pre_bin: Option<usize>) -> Self {
+        self.binning = Some(ModelBinning {
+            num_contexts,
+            pre_bin,
+        });
+        self
+    }
+
     pub fn generate_model_all(&self, directory: &Path, name: &str) -> anyhow::Result<()> {
         let variant_num = GenerateModelMode::VALUES.len() * ContextSpecType::VALUES.len();
-        PROGRESS_BAR.set_total_bytes(self.input.length()?.unwrap() as u64 * variant_num as u64);
+        let total = self.input.length()?.unwrap_or(0) * variant_num as u64;
+        PROGRESS_BAR.set_total_bytes(total);
 
         let variants: Vec<_> =
             iproduct!(GenerateModelMode::VALUES, ContextSpecType::VALUES).collect();
@@ -127,7 +160,12 @@ impl CliModelGenerator {
     ) -> anyhow::Result<()> {
         if let Some(ctx_gen) = ctx_gen {
             let contexts = ctx_gen.complex_contexts();
-            let model = Model::with_model_and_spec_type(model_type, context_spec_type, contexts);
+            drop(ctx_gen);
+            let mut model =
+                Model::with_model_and_spec_type(model_type, context_spec_type, contexts);
+            if let Some(binning) = self.binning {
+                model = Self::bin_model(&model, binning);
+            }
             SerializableModel::write_model(&model, BufWriter::new(writer))?;
 
             info!(
@@ -161,6 +199,25 @@ impl CliModelGenerator {
         Ok(())
     }
 
+    #[must_use]
+    fn bin_model(model: &Model, binning: ModelBinning) -> Model {
+        info!(
+            "Binning {} contexts into {}",
+            model.len(),
+            binning.num_contexts
+        );
+
+        let mut options =
+            ContextBinningOptions::builder().progress_notifier(Box::new(&*PROGRESS_BAR));
+        if let Some(pre_bin) = binning.pre_bin {
+            options = options.pre_binning_num(pre_bin);
+        }
+        let contexts =
+            bin_contexts_with_model(model, &options.build()).traverse(binning.num_contexts);
+
+        Model::with_model_and_spec_type(model.model_type(), model.context_spec_type(), contexts)
+    }
+
     fn generate_acid_contexts(
         &self,
         input: InputReader,
@@ -185,13 +242,22 @@ impl CliModelGenerator {
     ) -> anyhow::Result<Option<ModelGenerator<T>>> {
         let mut ctx_gen = ModelGenerator::new();
         let input_length = input.length()?.unwrap_or(0);
-        let fastq_reader = FastqReader::new(BufReader::new(input.into_read()));
-
-        let mut processed = ByteNum::ZERO;
-        for seq_result in fastq_reader {
-            let sequence = seq_result?;
-            let seq_size = sequence.size();
+        let mut reader = BufReader::new(input.into_read());
+        let header = reader.fill_buf().context("Could not read the input file")?;
+
+        let progress = Arc::new(InputProgress::default());
+        let idn_input = is_idn(header);
+        let mut source: Box<dyn SequenceSource> = if idn_input {
+            let params = IdnDecompressorParams::builder()
+                .model_provider(self.models.model_provider()?)
+                .progress_notifier(progress.clone())
+                .build();
+            Box::new(IdnDecompressor::with_params(reader, params))
+        } else {
+            Box::new(FastqReader::new(reader))
+        };
 
+        while let Some(sequence) = source.next_sequence()? {
             let mut generator = spec_type.generator(sequence.len());
 
             let acids = sequence.acids().iter();
@@ -202,21 +268,51 @@ impl CliModelGenerator {
                 generator.update(*acid, *q_score);
 
                 if ctx_gen.len() >= self.ctx_limit as usize {
-                    let remaining = input_length.saturating_sub(processed.get() as u64);
+                    // Stop the decompression first, so that nothing is reported
+                    // after the remaining bytes are
+                    drop(source);
+                    let remaining = input_length.saturating_sub(progress.processed() as u64);
                     PROGRESS_BAR.processed_bytes(ByteNum::new(remaining as usize));
 
                     return Ok(None);
                 }
             }
 
-            PROGRESS_BAR.processed_bytes(seq_size);
-            processed += seq_size;
+            // The decompressor reports the compressed bytes it has read itself
+            if !idn_input {
+                progress.processed_bytes(sequence.size());
+            }
         }
 
         Ok(Some(ctx_gen))
     }
 }
 
+/// Forwards the progress of reading the input to the progress bar, keeping
+/// track of the number of bytes processed so far.
+#[derive(Debug, Default)]
+struct InputProgress {
+    processed: AtomicUsize,
+}
+
+impl InputProgress {
+    #[must_use]
+    fn processed(&self) -> usize {
+        self.processed.load(Ordering::Relaxed)
+    }
+}
+
+impl ProgressNotifier for InputProgress {
+    fn processed_bytes(&self, bytes: ByteNum) {
+        self.processed.fetch_add(bytes.get(), Ordering::Relaxed);
+        PROGRESS_BAR.processed_bytes(bytes);
+    }
+
+    fn set_iter_num(&self, _num_iter: u64) {}
+
+    fn inc_iter(&self) {}
+}
+
 impl CsvStatOutput {
     fn add_gen_model_stat(
         &self,
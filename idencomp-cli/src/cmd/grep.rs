@@ -0,0 +1,59 @@
+use std::io::{BufWriter, Read, Write};
+use std::sync::Arc;
+
+use anyhow::Context;
+use idencomp::fastq::writer::FastqWriter;
+use idencomp::fastq::FastqSequence;
+use idencomp::idn::decompressor::{IdnDecompressor, IdnDecompressorParams, SequenceFilter};
+use idencomp::idn::model_provider::ModelProvider;
+use idencomp::progress::ProgressNotifier;
+use log::info;
+use regex::Regex;
+
+#[derive(Debug)]
+struct IdentifierFilter {
+    pattern: Regex,
+}
+
+impl SequenceFilter for IdentifierFilter {
+    fn accepts(&self, sequence: &FastqSequence) -> bool {
+        self.pattern.is_match(sequence.identifier().str())
+    }
+}
+
+pub fn grep<R: Read + Send, W: Write>(
+    reader: R,
+    writer: W,
+    id_pattern: &str,
+    model_provider: ModelProvider,
+    threads: Option<usize>,
+    progress_notifier: Arc<dyn ProgressNotifier>,
+) -> anyhow::Result<()> {
+    let pattern = Regex::new(id_pattern).context("Invalid identifier pattern")?;
+
+    let mut params = IdnDecompressorParams::builder()
+        .model_provider(model_provider)
+        .progress_notifier(progress_notifier)
+        .sequence_filter(Arc::new(IdentifierFilter { pattern }));
+    if let Some(threads) = threads {
+        params = params.thread_num(threads);
+    }
+    let params = params.build();
+    let idn_reader = IdnDecompressor::with_params(reader, params);
+
+    let mut fastq_writer = FastqWriter::new(BufWriter::new(writer));
+
+    let mut matched_num = 0;
+    for sequence in idn_reader {
+        let sequence = sequence.context("Could not read a sequence from the compressed file")?;
+        fastq_writer
+            .write_sequence(&sequence)
+            .context("Could not write a sequence to the FASTQ file")?;
+        matched_num += 1;
+    }
+
+    fastq_writer.flush()?;
+    info!("Found {} matching sequences", matched_num);
+
+    Ok(())
+}
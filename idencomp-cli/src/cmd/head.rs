@@ -0,0 +1,50 @@
+use std::io::{BufReader, BufWriter, Read, Write};
+
+use anyhow::Context;
+use idencomp::fastq::writer::FastqWriter;
+use idencomp::idn::decompressor::{IdnDecompressor, IdnDecompressorParams};
+use idencomp::idn::model_provider::ModelProvider;
+use idencomp::idn::split::IdnSplitter;
+
+pub fn head<R: Read, W: Write>(
+    reader: R,
+    writer: W,
+    num_sequences: usize,
+    model_provider: ModelProvider,
+    threads: Option<usize>,
+) -> anyhow::Result<()> {
+    let mut params = IdnDecompressorParams::builder().model_provider(model_provider);
+    if let Some(threads) = threads {
+        params = params.thread_num(threads);
+    }
+    let params = params.build();
+
+    let mut splitter =
+        IdnSplitter::new(BufReader::new(reader)).context("Could not read the IDN file")?;
+    let mut fastq_writer = FastqWriter::new(BufWriter::new(writer));
+
+    let mut remaining = num_sequences;
+    while remaining > 0 && !splitter.is_finished() {
+        // Only the leading blocks are extracted and decompressed
+        let mut part = Vec::new();
+        splitter
+            .write_part(&mut part, remaining)
+            .context("Could not read the IDN file")?;
+
+        let idn_reader = IdnDecompressor::with_params(part.as_slice(), params.clone());
+        for sequence in idn_reader {
+            let sequence =
+                sequence.context("Could not read a sequence from the compressed file")?;
+            if remaining > 0 {
+                fastq_writer
+                    .write_sequence(&sequence)
+                    .context("Could not write a sequence to the FASTQ file")?;
+                remaining -= 1;
+            }
+        }
+    }
+
+    fastq_writer.flush()?;
+
+    Ok(())
+}
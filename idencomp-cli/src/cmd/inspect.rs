@@ -0,0 +1,193 @@
+use std::fmt::Write as _;
+use std::io::{BufReader, Read};
+use std::slice;
+
+use anyhow::Context;
+use idencomp::idn::inspector::{inspect as inspect_idn, IdnFileInfo};
+use idencomp::idn::model_provider::ModelProvider;
+use idencomp::model::ModelIdentifier;
+use serde_json::json;
+
+pub fn inspect<R: Read>(
+    reader: R,
+    output_json: bool,
+    model_provider: Option<ModelProvider>,
+) -> anyhow::Result<()> {
+    let info = inspect_idn(BufReader::new(reader)).context("Could not read the IDN file")?;
+
+    if output_json {
+        print_json(&info, model_provider.as_ref())?;
+    } else {
+        print_human_readable(&info, model_provider.as_ref());
+    }
+
+    Ok(())
+}
+
+fn model_type_name(
+    model_provider: Option<&ModelProvider>,
+    identifier: &ModelIdentifier,
+) -> Option<String> {
+    let model_provider = model_provider?;
+    model_provider
+        .has_all_models(slice::from_ref(identifier))
+        .ok()?;
+    let model = &model_provider[model_provider.index_of(identifier)];
+
+    Some(model.model_type().to_string())
+}
+
+pub(crate) fn identifier_to_hex(identifier: &ModelIdentifier) -> String {
+    let bytes: [u8; 32] = identifier.into();
+    bytes.iter().fold(String::new(), |mut acc, byte| {
+        write!(acc, "{:02x}", byte).unwrap();
+        acc
+    })
+}
+
+fn print_human_readable(info: &IdnFileInfo, model_provider: Option<&ModelProvider>) {
+    println!("Version: {}", info.version());
+
+    println!("Models: {}", info.model_identifiers().len());
+    for (index, identifier) in info.model_identifiers().iter().enumerate() {
+        let model_type = model_type_name(model_provider, identifier);
+        println!(
+            "  [{}] {} ({})",
+            index,
+            identifier_to_hex(identifier),
+            model_type.as_deref().unwrap_or("unknown model")
+        );
+    }
+
+    if !info.transforms().is_empty() {
+        println!("Transforms: {}", info.transforms().len());
+        for transform in info.transforms() {
+            let lossy = if transform.is_lossy() { " (lossy)" } else { "" };
+            println!("  {}{}", transform.identifier(), lossy);
+        }
+    }
+
+    if !info.quality_alphabet().is_full() {
+        println!(
+            "Quality alphabet: {} ({} values)",
+            info.quality_alphabet(),
+            info.quality_alphabet().symbol_num()
+        );
+    }
+
+    if let Some(source_digest) = info.source_digest() {
+        println!("Source SHA-256: {}", source_digest);
+    }
+    println!("Sequences: {}", info.sequence_num());
+    let members = info.members();
+    if !members.is_empty() {
+        println!("Members: {}", members.len());
+        for member in &members {
+            println!(
+                "  {} (sequences: {}, blocks: {})",
+                member.name(),
+                member.sequence_num(),
+                member.block_num()
+            );
+        }
+    }
+    println!("Blocks: {}", info.blocks().len());
+    for (index, block) in info.blocks().iter().enumerate() {
+        let identifiers = block
+            .identifier_compression()
+            .map_or_else(|| "none".to_owned(), |compression| compression.to_string());
+        println!(
+            "  [{}] length: {}, sequences: {}, nucleotides: {}, model switches: {}, duplicates: {}, packed: {}, raw: {}, identifiers: {}, checksum: {:08X}",
+            index,
+            block.length(),
+            block.sequence_num(),
+            block.total_seq_len(),
+            block.model_switch_num(),
+            block.duplicate_num(),
+            block.is_packed(),
+            block.is_raw(),
+            identifiers,
+            block.seq_checksum(),
+        );
+    }
+}
+
+fn print_json(info: &IdnFileInfo, model_provider: Option<&ModelProvider>) -> anyhow::Result<()> {
+    let models: Vec<_> = info
+        .model_identifiers()
+        .iter()
+        .map(|identifier| {
+            json!({
+                "identifier": identifier_to_hex(identifier),
+                "type": model_type_name(model_provider, identifier),
+            })
+        })
+        .collect();
+    let blocks: Vec<_> = info
+        .blocks()
+        .iter()
+        .map(|block| {
+            json!({
+                "length": block.length(),
+                "sequences": block.sequence_num(),
+                "nucleotides": block.total_seq_len(),
+                "model_switches": block.model_switch_num(),
+                "duplicates": block.duplicate_num(),
+                "packed": block.is_packed(),
+                "raw": block.is_raw(),
+                "n_quality_score": block.n_q_score().map(|q_score| q_score.get()),
+                "identifier_compression": block
+                    .identifier_compression()
+                    .map(|compression| compression.to_string()),
+                "aux_data_compression": block
+                    .aux_data_compression()
+                    .map(|compression| compression.to_string()),
+                "checksum": block.seq_checksum(),
+                "member": block.member(),
+                "group": block.group(),
+            })
+        })
+        .collect();
+    let transforms: Vec<_> = info
+        .transforms()
+        .iter()
+        .map(|transform| {
+            json!({
+                "identifier": transform.identifier(),
+                "lossy": transform.is_lossy(),
+            })
+        })
+        .collect();
+    let quality_alphabet: Vec<_> = info
+        .quality_alphabet()
+        .values()
+        .iter()
+        .map(|value| value.get())
+        .collect();
+    let members: Vec<_> = info
+        .members()
+        .iter()
+        .map(|member| {
+            json!({
+                "name": member.name(),
+                "sequences": member.sequence_num(),
+                "blocks": member.block_num(),
+                "offset": member.offset(),
+            })
+        })
+        .collect();
+
+    let value = json!({
+        "version": info.version(),
+        "models": models,
+        "transforms": transforms,
+        "quality_alphabet": quality_alphabet,
+        "sequences": info.sequence_num(),
+        "source_sha256": info.source_digest().map(|digest| digest.to_string()),
+        "members": members,
+        "blocks": blocks,
+    });
+    println!("{}", serde_json::to_string_pretty(&value)?);
+
+    Ok(())
+}
@@ -1,6 +1,19 @@
+pub(crate) mod bench;
 pub(crate) mod bin_contexts;
 pub(crate) mod bin_contexts_all;
+pub(crate) mod cat;
 pub(crate) mod compress;
 pub(crate) mod decompress;
+pub(crate) mod diff;
+pub(crate) mod eval_models;
+pub(crate) mod export;
 pub(crate) mod generate_model;
+pub(crate) mod grep;
+pub(crate) mod head;
+pub(crate) mod inspect;
+pub(crate) mod model_info;
+pub(crate) mod paired_end;
+pub(crate) mod recompress;
+pub(crate) mod split;
 pub(crate) mod stats;
+pub(crate) mod verify;
@@ -0,0 +1,42 @@
+use std::io::{BufReader, Read};
+
+use anyhow::Context;
+use idencomp::model::Model;
+use idencomp::model_serializer::SerializableModel;
+use serde_json::json;
+
+use crate::cmd::inspect::identifier_to_hex;
+
+pub fn model_info<R: Read>(reader: R, output_json: bool) -> anyhow::Result<()> {
+    let model = SerializableModel::read_model(BufReader::new(reader))
+        .context("Could not read the model file")?;
+
+    if output_json {
+        print_json(&model)?;
+    } else {
+        print_human_readable(&model);
+    }
+
+    Ok(())
+}
+
+fn print_human_readable(model: &Model) {
+    println!("Identifier: {}", identifier_to_hex(model.identifier()));
+    println!("Model type: {}", model.model_type());
+    println!("Context spec type: {}", model.context_spec_type().name());
+    println!("Contexts: {}", model.len());
+    println!("Estimated rate: {}", model.rate());
+}
+
+fn print_json(model: &Model) -> anyhow::Result<()> {
+    let value = json!({
+        "identifier": identifier_to_hex(model.identifier()),
+        "model_type": model.model_type().to_string(),
+        "context_spec_type": model.context_spec_type().name(),
+        "contexts": model.len(),
+        "rate": model.rate().get(),
+    });
+    println!("{}", serde_json::to_string_pretty(&value)?);
+
+    Ok(())
+}
@@ -0,0 +1,96 @@
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::iter;
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail, Context};
+use idencomp::fastq::reader::FastqReader;
+use idencomp::fastq::writer::FastqWriter;
+use idencomp::idn::decompressor::{IdnDecompressor, IdnDecompressorParams};
+use idencomp::idn::model_provider::ModelProvider;
+use idencomp::progress::ProgressNotifier;
+use log::info;
+
+use crate::cmd::compress::{compress_sequences, CompressOptions};
+
+// Paired-end reads are stored in a single IDN file as interleaved sequences:
+// the first read of each pair is directly followed by its mate.
+pub fn compress_pe<R1: Read, R2: Read, W: Write + Send>(
+    reader_1: R1,
+    reader_2: R2,
+    writer: W,
+    model_provider: ModelProvider,
+    options: CompressOptions,
+    progress_notifier: Arc<dyn ProgressNotifier>,
+) -> anyhow::Result<()> {
+    let mut reads_1 = FastqReader::new(BufReader::new(reader_1)).into_iter();
+    let mut reads_2 = FastqReader::new(BufReader::new(reader_2)).into_iter();
+
+    let pairs = iter::from_fn(move || match (reads_1.next(), reads_2.next()) {
+        (None, None) => None,
+        (Some(read_1), Some(read_2)) => Some(vec![
+            read_1.context("Could not parse a sequence from the first FASTQ file"),
+            read_2.context("Could not parse a sequence from the second FASTQ file"),
+        ]),
+        _ => Some(vec![Err(anyhow!(
+            "The input files contain different numbers of reads"
+        ))]),
+    });
+
+    compress_sequences(
+        pairs.flatten(),
+        writer,
+        model_provider,
+        options,
+        progress_notifier,
+    )?;
+
+    Ok(())
+}
+
+pub fn decompress_pe<R: Read + Send, W: Write>(
+    reader: R,
+    writer_1: W,
+    writer_2: W,
+    model_provider: ModelProvider,
+    threads: Option<usize>,
+    progress_notifier: Arc<dyn ProgressNotifier>,
+) -> anyhow::Result<()> {
+    let mut params = IdnDecompressorParams::builder()
+        .model_provider(model_provider)
+        .progress_notifier(progress_notifier);
+    if let Some(threads) = threads {
+        params = params.thread_num(threads);
+    }
+    let params = params.build();
+    let idn_reader = IdnDecompressor::with_params(reader, params);
+
+    let mut fastq_writer_1 = FastqWriter::new(BufWriter::new(writer_1));
+    let mut fastq_writer_2 = FastqWriter::new(BufWriter::new(writer_2));
+
+    let mut sequence_num = 0;
+    for sequence in idn_reader {
+        let sequence = sequence.context("Could not read a sequence from the compressed file")?;
+        let fastq_writer = if sequence_num % 2 == 0 {
+            &mut fastq_writer_1
+        } else {
+            &mut fastq_writer_2
+        };
+        fastq_writer
+            .write_sequence(&sequence)
+            .context("Could not write a sequence to the FASTQ file")?;
+        sequence_num += 1;
+    }
+
+    fastq_writer_1.flush()?;
+    fastq_writer_2.flush()?;
+
+    if sequence_num % 2 != 0 {
+        bail!(
+            "The compressed file contains an odd number of reads ({}), so it does not contain paired-end data",
+            sequence_num
+        );
+    }
+
+    info!("Decompressed {} read pairs", sequence_num / 2);
+    Ok(())
+}
@@ -0,0 +1,55 @@
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::sync::Arc;
+
+use anyhow::Context;
+use idencomp::idn::compressor::{CompressionQuality, IdnCompressorParams};
+use idencomp::idn::decompressor::IdnDecompressorParams;
+use idencomp::idn::model_provider::ModelProvider;
+use idencomp::idn::recompress;
+use idencomp::progress::ProgressNotifier;
+
+#[allow(clippy::too_many_arguments)]
+pub fn recompress<R: Read + Send, W: Write + Send>(
+    reader: R,
+    writer: W,
+    model_provider: ModelProvider,
+    threads: Option<usize>,
+    no_identifiers: bool,
+    quality: Option<u8>,
+    fast: bool,
+    deduplicate: bool,
+    progress_notifier: Arc<dyn ProgressNotifier>,
+) -> anyhow::Result<()> {
+    let quality = quality
+        .map(CompressionQuality::try_new)
+        .transpose()?
+        .unwrap_or_default();
+
+    // The progress is reported by the decompressor, as the input file size is
+    // the only total known in advance
+    let mut decompressor_params = IdnDecompressorParams::builder()
+        .model_provider(model_provider.clone())
+        .progress_notifier(progress_notifier);
+    let mut compressor_params = IdnCompressorParams::builder()
+        .model_provider(model_provider)
+        .quality(quality)
+        .fast(fast)
+        .deduplicate(deduplicate)
+        .include_identifiers(!no_identifiers);
+    if let Some(threads) = threads {
+        decompressor_params = decompressor_params.thread_num(threads);
+        compressor_params = compressor_params.thread_num(threads);
+    }
+
+    let mut writer = BufWriter::new(writer);
+    recompress::recompress(
+        BufReader::new(reader),
+        &mut writer,
+        decompressor_params.build(),
+        compressor_params.build()?,
+    )
+    .context("Could not recompress the IDN file")?;
+    writer.flush()?;
+
+    Ok(())
+}
@@ -0,0 +1,70 @@
+use std::io::{BufReader, BufWriter, Read, Write};
+
+use anyhow::Context;
+use idencomp::idn::inspector::inspect;
+use idencomp::idn::split::IdnSplitter;
+use log::info;
+
+pub fn split<R: Read, W: Write, F>(
+    reader: R,
+    reads_per_file: usize,
+    mut make_writer: F,
+) -> anyhow::Result<()>
+where
+    F: FnMut(usize) -> anyhow::Result<W>,
+{
+    let mut splitter =
+        IdnSplitter::new(BufReader::new(reader)).context("Could not read the IDN file")?;
+
+    let mut part_index = 0;
+    while !splitter.is_finished() {
+        let mut writer = BufWriter::new(make_writer(part_index)?);
+        let sequence_num = splitter
+            .write_part(&mut writer, reads_per_file)
+            .context("Could not write the IDN file part")?;
+        writer.flush()?;
+
+        info!("Part {}: {} sequences", part_index, sequence_num);
+        part_index += 1;
+    }
+
+    Ok(())
+}
+
+/// Returns the smallest number of reads per file that splits the file into at
+/// most `parts` parts.
+pub fn reads_per_file_for_parts<R: Read>(reader: R, parts: usize) -> anyhow::Result<usize> {
+    let info = inspect(BufReader::new(reader)).context("Could not read the IDN file")?;
+    let block_sizes: Vec<usize> = info
+        .blocks()
+        .iter()
+        .map(|block| block.sequence_num())
+        .collect();
+
+    let mut low = block_sizes.iter().copied().max().unwrap_or(1).max(1);
+    let mut high = info.sequence_num().max(low);
+    while low < high {
+        let mid = (low + high) / 2;
+        if count_parts(&block_sizes, mid) <= parts {
+            high = mid;
+        } else {
+            low = mid + 1;
+        }
+    }
+
+    Ok(low)
+}
+
+fn count_parts(block_sizes: &[usize], reads_per_file: usize) -> usize {
+    let mut parts = 0;
+    let mut current = 0;
+    for &size in block_sizes {
+        if current == 0 || current + size > reads_per_file {
+            parts += 1;
+            current = 0;
+        }
+        current += size;
+    }
+
+    parts
+}
@@ -1,16 +1,30 @@
-use std::io::{BufReader, Read};
+use std::io::{BufRead, BufReader, Read};
 
 use anyhow::Context;
 use idencomp::fastq::reader::FastqReader;
 use idencomp::fastq::{FastqQualityScore, FastqSequence};
+use idencomp::idn::inspector::{inspect, is_idn, IdnFileInfo};
+use idencomp::model::CompressionRate;
 use idencomp::model_generator::ContextCounter;
 use idencomp::progress::ProgressNotifier;
 use idencomp::sequence::{Acid, Symbol};
 
+use crate::cmd::inspect::identifier_to_hex;
 use crate::PROGRESS_BAR;
 
 pub(crate) fn stats<R: Read>(reader: R) -> anyhow::Result<()> {
-    let fastq_reader = FastqReader::new(BufReader::new(reader));
+    let mut reader = BufReader::new(reader);
+    let header = reader.fill_buf().context("Could not read the input file")?;
+
+    if is_idn(header) {
+        idn_stats(reader)
+    } else {
+        fastq_stats(reader)
+    }
+}
+
+fn fastq_stats<R: BufRead>(reader: R) -> anyhow::Result<()> {
+    let fastq_reader = FastqReader::new(reader);
     let mut stats = FastqStats::new();
 
     for sequence in fastq_reader {
@@ -29,6 +43,97 @@ pub(crate) fn stats<R: Read>(reader: R) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Prints the statistics of an IDN file. Only the block and slice headers are
+/// read, so the file doesn't need to be decompressed (and the models used do
+/// not need to be available).
+fn idn_stats<R: Read>(reader: R) -> anyhow::Result<()> {
+    let info = inspect(reader).context("Could not read the IDN file")?;
+    PROGRESS_BAR.finish();
+
+    let stats = IdnStats::new(&info);
+    stats.print();
+
+    eprintln!("Models: {}", info.model_identifiers().len());
+    for (index, identifier) in info.model_identifiers().iter().enumerate() {
+        eprintln!("  [{}] {}", index, identifier_to_hex(identifier));
+    }
+
+    Ok(())
+}
+
+#[derive(Debug)]
+struct IdnStats {
+    version: u8,
+    reads: usize,
+    bases: u64,
+    blocks: usize,
+    total_bytes: u64,
+    sequence_bytes: u64,
+    identifier_bytes: u64,
+    aux_data_bytes: u64,
+}
+
+impl IdnStats {
+    pub fn new(info: &IdnFileInfo) -> Self {
+        let blocks = info.blocks();
+
+        Self {
+            version: info.version(),
+            reads: info.sequence_num(),
+            bases: blocks.iter().map(|block| block.total_seq_len()).sum(),
+            blocks: blocks.len(),
+            total_bytes: blocks.iter().map(|block| block.length() as u64).sum(),
+            sequence_bytes: blocks.iter().map(|block| block.sequence_bytes()).sum(),
+            identifier_bytes: blocks.iter().map(|block| block.identifier_bytes()).sum(),
+            aux_data_bytes: blocks.iter().map(|block| block.aux_data_bytes()).sum(),
+        }
+    }
+
+    fn rate(&self, bytes: u64) -> String {
+        if self.bases == 0 {
+            return "-".to_owned();
+        }
+
+        CompressionRate::new((bytes * 8) as f32 / self.bases as f32).to_string()
+    }
+
+    pub fn print(&self) {
+        let other_bytes =
+            self.total_bytes - self.sequence_bytes - self.identifier_bytes - self.aux_data_bytes;
+
+        eprintln!("Version: {}", self.version);
+        eprintln!("Reads: {}", self.reads);
+        eprintln!("Bases: {}", self.bases);
+        eprintln!("Blocks: {}", self.blocks);
+        eprintln!("Compressed size:");
+        eprintln!(
+            "  Sequences: {} bytes ({})",
+            self.sequence_bytes,
+            self.rate(self.sequence_bytes)
+        );
+        eprintln!(
+            "  Identifiers: {} bytes ({})",
+            self.identifier_bytes,
+            self.rate(self.identifier_bytes)
+        );
+        eprintln!(
+            "  Auxiliary data: {} bytes ({})",
+            self.aux_data_bytes,
+            self.rate(self.aux_data_bytes)
+        );
+        eprintln!(
+            "  Other: {} bytes ({})",
+            other_bytes,
+            self.rate(other_bytes)
+        );
+        eprintln!(
+            "  Total: {} bytes ({})",
+            self.total_bytes,
+            self.rate(self.total_bytes)
+        );
+    }
+}
+
 #[derive(Debug)]
 struct FastqStats {
     acid_counter: ContextCounter<Acid>,
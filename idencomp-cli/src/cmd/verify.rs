@@ -0,0 +1,70 @@
+use std::io::{BufReader, Read};
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail, Context};
+use idencomp::idn::decompressor::{IdnDecompressor, IdnDecompressorParams};
+use idencomp::idn::inspector::inspect;
+use idencomp::idn::model_provider::ModelProvider;
+use idencomp::idn::source_digest::SourceDigest;
+use idencomp::progress::{ByteNum, ProgressNotifier};
+use log::info;
+
+use crate::opts::InputReader;
+
+pub fn verify<R: Read + Send>(
+    reader: R,
+    model_provider: ModelProvider,
+    threads: Option<usize>,
+    progress_notifier: Arc<dyn ProgressNotifier>,
+) -> anyhow::Result<()> {
+    let mut params = IdnDecompressorParams::builder()
+        .model_provider(model_provider)
+        .progress_notifier(progress_notifier);
+    if let Some(threads) = threads {
+        params = params.thread_num(threads);
+    }
+    let params = params.build();
+    let idn_reader = IdnDecompressor::with_params(reader, params);
+
+    let mut sequence_num: u64 = 0;
+    let mut total_size = ByteNum::ZERO;
+    for sequence in idn_reader {
+        let sequence = sequence.context("The compressed file is corrupted")?;
+        sequence_num += 1;
+        total_size += sequence.size();
+    }
+
+    info!(
+        "File is valid: {} sequences, {} bytes of FASTQ data",
+        sequence_num,
+        total_size.get()
+    );
+
+    Ok(())
+}
+
+/// Checks that the IDN file was created from given source FASTQ file, using
+/// the source digest stored in the IDN file.
+pub fn verify_source(idn: InputReader, source: InputReader) -> anyhow::Result<()> {
+    let info =
+        inspect(BufReader::new(idn.into_read())).context("Could not read the compressed file")?;
+    let expected_digest = info.source_digest().ok_or_else(|| {
+        anyhow!("The compressed file does not contain the digest of its source; it has to be compressed with --source-digest")
+    })?;
+
+    let digest =
+        SourceDigest::of_reader(source.into_read()).context("Could not read the source file")?;
+    if digest != expected_digest {
+        bail!(
+            "The compressed file was not created from given source file (expected SHA-256: {}, actual: {})",
+            expected_digest,
+            digest
+        );
+    }
+
+    info!(
+        "Source file matches the compressed file (SHA-256: {})",
+        digest
+    );
+    Ok(())
+}
@@ -0,0 +1,135 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use log::debug;
+use serde::Deserialize;
+
+use crate::cli::Commands;
+
+const CONFIG_FILE_NAME: &str = "idencomp.toml";
+
+/// Default parameters read from the `idencomp.toml` file. The values are used
+/// only if they are not provided as command line options.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    threads: Option<usize>,
+    quality: Option<u8>,
+    model_dir: Option<PathBuf>,
+    block_length: Option<usize>,
+    block_reads: Option<usize>,
+}
+
+impl Config {
+    /// Loads the config from the current directory or, if there is no config
+    /// file there, from the user config directory. Returns the default config
+    /// if none of the files exist.
+    pub fn load() -> anyhow::Result<Self> {
+        for path in Self::paths() {
+            if path.is_file() {
+                debug!("Loading config file: {}", path.display());
+                let data = fs::read_to_string(&path)
+                    .with_context(|| format!("Could not read config file {}", path.display()))?;
+                let config: Config = toml::from_str(&data)
+                    .with_context(|| format!("Invalid config file {}", path.display()))?;
+                return Ok(config);
+            }
+        }
+
+        Ok(Self::default())
+    }
+
+    fn paths() -> Vec<PathBuf> {
+        let mut paths = vec![PathBuf::from(CONFIG_FILE_NAME)];
+
+        let config_home = env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")));
+        if let Some(config_home) = config_home {
+            paths.push(config_home.join("idencomp").join(CONFIG_FILE_NAME));
+        }
+
+        paths
+    }
+
+    #[must_use]
+    pub fn model_dir(&self) -> Option<&PathBuf> {
+        self.model_dir.as_ref()
+    }
+}
+
+fn fill<T: Clone>(value: &mut Option<T>, default: &Option<T>) {
+    if value.is_none() {
+        *value = default.clone();
+    }
+}
+
+impl Commands {
+    /// Sets the options that were not provided on the command line to the
+    /// values from given config.
+    pub fn apply_config(&mut self, config: &Config) {
+        match self {
+            Commands::Compress {
+                threads,
+                quality,
+                block_length,
+                block_reads,
+                models,
+                ..
+            } => {
+                fill(threads, &config.threads);
+                fill(quality, &config.quality);
+                fill(block_length, &config.block_length);
+                fill(block_reads, &config.block_reads);
+                models.apply_config(config);
+            }
+            Commands::CompressPe {
+                threads,
+                quality,
+                models,
+                ..
+            }
+            | Commands::Recompress {
+                threads,
+                quality,
+                models,
+                ..
+            } => {
+                fill(threads, &config.threads);
+                fill(quality, &config.quality);
+                models.apply_config(config);
+            }
+            Commands::Decompress {
+                threads, models, ..
+            }
+            | Commands::DecompressPe {
+                threads, models, ..
+            }
+            | Commands::Verify {
+                threads, models, ..
+            }
+            | Commands::Head {
+                threads, models, ..
+            }
+            | Commands::Grep {
+                threads, models, ..
+            }
+            | Commands::Diff {
+                threads, models, ..
+            } => {
+                fill(threads, &config.threads);
+                models.apply_config(config);
+            }
+            Commands::GenerateModel { models, .. }
+            | Commands::GenerateModelAll { models, .. }
+            | Commands::Bench { models, .. }
+            | Commands::EvalModels { models, .. }
+            | Commands::Inspect { models, .. } => {
+                models.apply_config(config);
+            }
+            _ => {}
+        }
+    }
+}
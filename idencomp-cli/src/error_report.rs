@@ -0,0 +1,356 @@
+use std::error::Error;
+use std::io;
+
+use clap::ValueEnum;
+use idencomp::fastq::reader::FastqReaderError;
+use idencomp::fastq::writer::FastqWriterError;
+use idencomp::idn::compressor::IdnCompressorError;
+use idencomp::idn::concat::IdnConcatError;
+use idencomp::idn::decompressor::IdnDecompressorError;
+use idencomp::idn::recompress::IdnRecompressError;
+use idencomp::idn::split::IdnSplitError;
+use serde_json::json;
+
+use crate::PROGRESS_BAR;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, ValueEnum)]
+pub enum ErrorFormat {
+    /// Human-readable message with the list of causes
+    Text,
+    /// One JSON object printed to the standard error
+    Json,
+}
+
+/// The category of an error that made the program fail, which determines the
+/// exit code of the process.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ErrorCategory {
+    /// Any error not covered by the other categories, e.g. invalid options.
+    Other,
+    /// The input file is not a valid FASTQ or IDN file, or contains data that
+    /// cannot be processed.
+    BadInput,
+    /// The IDN file or a model file is damaged.
+    Corruption,
+    /// A model required by the file has not been provided.
+    UnknownModel,
+    /// Reading or writing a file failed.
+    Io,
+    /// The output has been closed before all the data has been written, e.g.
+    /// when it is piped to `head`. This is not reported as a failure.
+    BrokenPipe,
+}
+
+impl ErrorCategory {
+    /// Returns the category of given error, looking for the first error of
+    /// a known type in its chain of causes.
+    #[must_use]
+    pub fn of(error: &anyhow::Error) -> Self {
+        error
+            .chain()
+            .find_map(Self::of_cause)
+            .unwrap_or(Self::Other)
+    }
+
+    /// Returns the exit code of the process failing with this category of
+    /// error. Code 2 is not used, as it is returned for invalid command line
+    /// arguments.
+    #[must_use]
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Self::Other => 1,
+            Self::BadInput => 3,
+            Self::Corruption => 4,
+            Self::UnknownModel => 5,
+            Self::Io => 6,
+            Self::BrokenPipe => 0,
+        }
+    }
+
+    /// Returns the name of this category used in the JSON error reports.
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Other => "other",
+            Self::BadInput => "bad_input",
+            Self::Corruption => "corruption",
+            Self::UnknownModel => "unknown_model",
+            Self::Io => "io",
+            Self::BrokenPipe => "broken_pipe",
+        }
+    }
+
+    fn of_cause(error: &(dyn Error + 'static)) -> Option<Self> {
+        if let Some(e) = error.downcast_ref::<idencomp::Error>() {
+            Self::of_library_error(e)
+        } else if let Some(e) = error.downcast_ref::<IdnDecompressorError>() {
+            Some(Self::of_decompressor_error(e))
+        } else if let Some(e) = error.downcast_ref::<IdnCompressorError>() {
+            Some(Self::of_compressor_error(e))
+        } else if let Some(e) = error.downcast_ref::<FastqReaderError>() {
+            Some(Self::of_fastq_reader_error(e))
+        } else if let Some(FastqWriterError::IoError(e)) = error.downcast_ref::<FastqWriterError>()
+        {
+            Some(Self::of_io_error(e))
+        } else if let Some(e) = error.downcast_ref::<IdnSplitError>() {
+            Some(Self::of_split_error(e))
+        } else if let Some(e) = error.downcast_ref::<IdnConcatError>() {
+            Some(Self::of_concat_error(e))
+        } else if let Some(e) = error.downcast_ref::<IdnRecompressError>() {
+            Some(Self::of_recompress_error(e))
+        } else {
+            error.downcast_ref::<io::Error>().map(Self::of_io_error)
+        }
+    }
+
+    fn of_library_error(error: &idencomp::Error) -> Option<Self> {
+        let category = match error {
+            idencomp::Error::FastqReader(e) => Self::of_fastq_reader_error(e),
+            idencomp::Error::FastqWriter(FastqWriterError::IoError(e)) | idencomp::Error::Io(e) => {
+                Self::of_io_error(e)
+            }
+            idencomp::Error::IdnCompressor(e) => Self::of_compressor_error(e),
+            idencomp::Error::IdnDecompressor(e) => Self::of_decompressor_error(e),
+            idencomp::Error::IdnSplit(e) => Self::of_split_error(e),
+            idencomp::Error::IdnConcat(e) => Self::of_concat_error(e),
+            idencomp::Error::IdnRecompress(e) => Self::of_recompress_error(e),
+            idencomp::Error::ModelFile { source, .. } => return Self::of_library_error(source),
+            idencomp::Error::ModelDecode(_) | idencomp::Error::ModelIdentifierMismatch(_) => {
+                Self::Corruption
+            }
+            // Invalid parameters or values provided by the user
+            _ => return None,
+        };
+
+        Some(category)
+    }
+
+    fn of_split_error(error: &IdnSplitError) -> Self {
+        match error {
+            IdnSplitError::ReadError(e) => Self::of_decompressor_error(e),
+            IdnSplitError::WriteError(e) => Self::of_compressor_error(e),
+        }
+    }
+
+    fn of_concat_error(error: &IdnConcatError) -> Self {
+        match error {
+            IdnConcatError::ReadError(e) => Self::of_decompressor_error(e),
+            IdnConcatError::WriteError(e) => Self::of_compressor_error(e),
+            IdnConcatError::TooManyModels(_)
+            | IdnConcatError::DifferentTransforms
+            | IdnConcatError::DifferentQualityAlphabets => Self::BadInput,
+        }
+    }
+
+    fn of_recompress_error(error: &IdnRecompressError) -> Self {
+        match error {
+            IdnRecompressError::ReadError(e) => Self::of_decompressor_error(e),
+            IdnRecompressError::WriteError(e) => Self::of_compressor_error(e),
+            IdnRecompressError::TooManyTransforms(_) => Self::BadInput,
+        }
+    }
+
+    fn of_decompressor_error(error: &IdnDecompressorError) -> Self {
+        match error {
+            IdnDecompressorError::InvalidState
+            | IdnDecompressorError::UnknownTransform(_)
+            | IdnDecompressorError::UnsupportedIdentifierCompression(_) => Self::Other,
+            // A file that ends prematurely has been truncated
+            IdnDecompressorError::IoError(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                Self::Corruption
+            }
+            IdnDecompressorError::IoError(e) => Self::of_io_error(e),
+            IdnDecompressorError::InvalidVersion(_) | IdnDecompressorError::UnknownMember(_) => {
+                Self::BadInput
+            }
+            IdnDecompressorError::UnknownModel(_) => Self::UnknownModel,
+            IdnDecompressorError::Utf8Error(_)
+            | IdnDecompressorError::SerializeError(_)
+            | IdnDecompressorError::BlockChecksumMismatch(_, _)
+            | IdnDecompressorError::InvalidModelIndex(_, _)
+            | IdnDecompressorError::NoActiveModel(_)
+            | IdnDecompressorError::ModelIdentifierMismatch(_)
+            | IdnDecompressorError::InvalidQualityScore(_)
+            | IdnDecompressorError::InvalidDuplicate(_, _)
+            | IdnDecompressorError::InvalidPackedSequence(_, _)
+            | IdnDecompressorError::InvalidRawSequence(_, _)
+            | IdnDecompressorError::InvalidAcid(_)
+            | IdnDecompressorError::BlockTooLarge(_, _)
+            | IdnDecompressorError::IdentifiersTooLarge(_)
+            | IdnDecompressorError::TooManyMetadataItems(_, _)
+            | IdnDecompressorError::SequenceTooLong(_, _)
+            | IdnDecompressorError::SliceOutOfBounds(_, _)
+            | IdnDecompressorError::EmptyQualityAlphabet
+            | IdnDecompressorError::InvalidModelSwitchOffset(_, _)
+            | IdnDecompressorError::UnusedModelSwitch
+            | IdnDecompressorError::SequenceDataTooShort(_, _) => Self::Corruption,
+        }
+    }
+
+    fn of_compressor_error(error: &IdnCompressorError) -> Self {
+        match error {
+            IdnCompressorError::InvalidState | IdnCompressorError::InvalidModelSelection(_, _) => {
+                Self::Other
+            }
+            IdnCompressorError::IoError(e) => Self::of_io_error(e),
+            IdnCompressorError::SerializeError(_) => Self::Io,
+            IdnCompressorError::SequenceTooLong(_, _)
+            | IdnCompressorError::DuplicateMember(_)
+            | IdnCompressorError::MemberNameTooLong(_)
+            | IdnCompressorError::BlockKeyTooLong(_)
+            | IdnCompressorError::SliceTooLong(_)
+            | IdnCompressorError::QualityScoreNotInAlphabet(_) => Self::BadInput,
+        }
+    }
+
+    fn of_fastq_reader_error(error: &FastqReaderError) -> Self {
+        match error {
+            FastqReaderError::IoError(e) => Self::of_io_error(e),
+            _ => Self::BadInput,
+        }
+    }
+
+    fn of_io_error(error: &io::Error) -> Self {
+        match error.kind() {
+            io::ErrorKind::BrokenPipe => Self::BrokenPipe,
+            _ => Self::Io,
+        }
+    }
+}
+
+/// Prints given error to the standard error in given format and returns the
+/// exit code the process should fail with. Broken pipes are not reported.
+pub fn report_error(error: &anyhow::Error, format: ErrorFormat) -> i32 {
+    let category = ErrorCategory::of(error);
+    if category == ErrorCategory::BrokenPipe {
+        return category.exit_code();
+    }
+
+    let message = format_error(error, category, format);
+    if PROGRESS_BAR.is_hidden() {
+        eprintln!("{}", message);
+    } else {
+        PROGRESS_BAR.println(message);
+    }
+
+    category.exit_code()
+}
+
+fn format_error(error: &anyhow::Error, category: ErrorCategory, format: ErrorFormat) -> String {
+    match format {
+        ErrorFormat::Text => format!("Error: {:?}", error),
+        ErrorFormat::Json => {
+            let causes: Vec<_> = error.chain().skip(1).map(ToString::to_string).collect();
+            json!({
+                "error": category.name(),
+                "exit_code": category.exit_code(),
+                "message": error.to_string(),
+                "causes": causes,
+            })
+            .to_string()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use anyhow::Context;
+    use idencomp::idn::compressor::IdnCompressorError;
+    use idencomp::idn::decompressor::IdnDecompressorError;
+    use idencomp::idn::split::IdnSplitError;
+    use idencomp::model::ModelIdentifier;
+    use serde_json::{json, Value};
+
+    use crate::error_report::{format_error, ErrorCategory, ErrorFormat};
+
+    fn category_of<E>(error: E) -> ErrorCategory
+    where
+        E: std::error::Error + Send + Sync + 'static,
+    {
+        ErrorCategory::of(&anyhow::Error::new(error).context("Could not do the thing"))
+    }
+
+    #[test]
+    fn test_error_category() {
+        assert_eq!(
+            category_of(IdnDecompressorError::BlockChecksumMismatch(1, 2)),
+            ErrorCategory::Corruption
+        );
+        assert_eq!(
+            category_of(IdnDecompressorError::UnknownModel(ModelIdentifier::new(
+                [0; 32]
+            ))),
+            ErrorCategory::UnknownModel
+        );
+        assert_eq!(
+            category_of(IdnDecompressorError::InvalidVersion(9)),
+            ErrorCategory::BadInput
+        );
+        assert_eq!(
+            category_of(IdnDecompressorError::IoError(
+                io::ErrorKind::UnexpectedEof.into()
+            )),
+            ErrorCategory::Corruption
+        );
+        assert_eq!(
+            category_of(IdnSplitError::WriteError(
+                IdnCompressorError::SequenceTooLong(5, 2)
+            )),
+            ErrorCategory::BadInput
+        );
+        assert_eq!(
+            category_of(io::Error::from(io::ErrorKind::PermissionDenied)),
+            ErrorCategory::Io
+        );
+        assert_eq!(
+            category_of(io::Error::from(io::ErrorKind::BrokenPipe)),
+            ErrorCategory::BrokenPipe
+        );
+        assert_eq!(
+            ErrorCategory::of(&anyhow::anyhow!("Invalid option")),
+            ErrorCategory::Other
+        );
+    }
+
+    #[test]
+    fn test_exit_code() {
+        assert_eq!(ErrorCategory::Other.exit_code(), 1);
+        assert_eq!(ErrorCategory::BadInput.exit_code(), 3);
+        assert_eq!(ErrorCategory::Corruption.exit_code(), 4);
+        assert_eq!(ErrorCategory::UnknownModel.exit_code(), 5);
+        assert_eq!(ErrorCategory::Io.exit_code(), 6);
+        assert_eq!(ErrorCategory::BrokenPipe.exit_code(), 0);
+    }
+
+    #[test]
+    fn test_format_error_json() {
+        let error = Err::<(), _>(IdnDecompressorError::InvalidVersion(9))
+            .context("Could not read the file")
+            .unwrap_err();
+        let message = format_error(&error, ErrorCategory::of(&error), ErrorFormat::Json);
+
+        assert!(!message.contains('\n'));
+        assert_eq!(
+            serde_json::from_str::<Value>(&message).unwrap(),
+            json!({
+                "error": "bad_input",
+                "exit_code": 3,
+                "message": "Could not read the file",
+                "causes": ["Invalid IDN file version: 9"],
+            })
+        );
+    }
+
+    #[test]
+    fn test_format_error_text() {
+        let error = Err::<(), _>(IdnDecompressorError::InvalidVersion(9))
+            .context("Could not read the file")
+            .unwrap_err();
+        let message = format_error(&error, ErrorCategory::of(&error), ErrorFormat::Text);
+
+        assert!(message.starts_with("Error: Could not read the file"));
+        assert!(message.contains("Invalid IDN file version: 9"));
+    }
+}
@@ -1,10 +1,45 @@
 use chrono::Local;
+use clap::ValueEnum;
 use log::{LevelFilter, SetLoggerError};
 use log::{Metadata, Record};
+use serde_json::json;
 
 use crate::PROGRESS_BAR;
 
-struct Logger;
+#[derive(Copy, Clone, Debug, Eq, PartialEq, ValueEnum)]
+pub enum LogFormat {
+    /// Human-readable log lines
+    Text,
+    /// One JSON object per line
+    Json,
+}
+
+struct Logger {
+    format: LogFormat,
+}
+
+impl Logger {
+    fn format_record(&self, record: &Record) -> String {
+        let now = Local::now();
+
+        match self.format {
+            LogFormat::Text => format!(
+                "{} [{}] [{}] {}",
+                now.format("%Y-%m-%d %H:%M:%S%.6f"),
+                record.level(),
+                record.target(),
+                record.args()
+            ),
+            LogFormat::Json => json!({
+                "timestamp": now.to_rfc3339(),
+                "level": record.level().as_str(),
+                "target": record.target(),
+                "message": record.args().to_string(),
+            })
+            .to_string(),
+        }
+    }
+}
 
 impl log::Log for Logger {
     fn enabled(&self, _metadata: &Metadata) -> bool {
@@ -13,13 +48,7 @@ impl log::Log for Logger {
 
     fn log(&self, record: &Record) {
         if self.enabled(record.metadata()) {
-            let msg = format!(
-                "{} [{}] [{}] {}",
-                Local::now().format("%Y-%m-%d %H:%M:%S%.6f"),
-                record.level(),
-                record.target(),
-                record.args()
-            );
+            let msg = self.format_record(record);
 
             if PROGRESS_BAR.is_hidden() {
                 eprintln!("{}", msg);
@@ -32,10 +61,9 @@ impl log::Log for Logger {
     fn flush(&self) {}
 }
 
-static LOGGER: Logger = Logger;
-
-pub fn init_logging(filter: LevelFilter) -> Result<(), SetLoggerError> {
-    log::set_logger(&LOGGER)?;
+pub fn init_logging(filter: LevelFilter, format: LogFormat) -> Result<(), SetLoggerError> {
+    let logger = Box::leak(Box::new(Logger { format }));
+    log::set_logger(logger)?;
     log::set_max_level(filter);
 
     Ok(())
@@ -1,26 +1,42 @@
-#[cfg(not(target_env = "msvc"))]
+#[cfg(all(feature = "jemalloc", not(target_env = "msvc")))]
 use tikv_jemallocator::Jemalloc;
 
-#[cfg(not(target_env = "msvc"))]
+#[cfg(all(feature = "jemalloc", not(target_env = "msvc")))]
 #[global_allocator]
 static GLOBAL: Jemalloc = Jemalloc;
 
+use std::io::BufRead;
+use std::path::Path;
+use std::process;
 use std::sync::Arc;
 
-use anyhow::Context;
+use anyhow::{bail, Context};
+use atty::Stream;
 use clap::Parser;
 use cli::{Cli, Commands};
-use cmd::{bin_contexts, bin_contexts_all, compress, decompress, generate_model, stats};
+use cmd::{
+    bench, bin_contexts, bin_contexts_all, cat, compress, decompress, diff, eval_models, export,
+    generate_model, grep, head, inspect, model_info, paired_end, recompress, split, stats, verify,
+};
 use human_panic::setup_panic;
+use idencomp::idn::context_usage::ContextUsage;
+use idencomp::idn::explain::SequenceExplainer;
+use idencomp::idn::source_digest::SourceHasher;
 use lazy_static::lazy_static;
+use log::LevelFilter;
 
-use crate::logging::init_logging;
-use crate::opts::{OutputMode, OutputWriter};
-use crate::progress_bar::IdnProgressBar;
+use crate::cmd::compress::CompressOptions;
+use crate::config::Config;
+use crate::error_report::report_error;
+use crate::logging::{init_logging, LogFormat};
+use crate::opts::{InputReader, OutputMode, OutputWriter, NAME_PLACEHOLDER};
+use crate::progress_bar::{IdnProgressBar, ProgressFormat};
 
 mod cli;
 mod cmd;
+mod config;
 mod csv_stat;
+mod error_report;
 mod logging;
 mod opts;
 mod progress_bar;
@@ -29,16 +45,39 @@ lazy_static! {
     pub(crate) static ref PROGRESS_BAR: IdnProgressBar = IdnProgressBar::new();
 }
 
-fn main() -> anyhow::Result<()> {
+fn main() {
     setup_panic!();
 
     let cli: Cli = Cli::parse();
+    let error_format = cli.errors;
+    if let Err(e) = run(cli) {
+        let exit_code = report_error(&e, error_format);
+        process::exit(exit_code);
+    }
+}
 
+fn run(mut cli: Cli) -> anyhow::Result<()> {
+    // Keep the terminal clean when the output is piped to another program or
+    // when the output is supposed to be read by a machine
+    let quiet = cli.verbose.log_level_filter() < LevelFilter::Info;
     if !cli.no_progress {
-        PROGRESS_BAR.show();
+        match cli.progress {
+            ProgressFormat::Json => PROGRESS_BAR.show_json(),
+            ProgressFormat::Bar => {
+                if !quiet && cli.log_format == LogFormat::Text && atty::is(Stream::Stdout) {
+                    PROGRESS_BAR.show();
+                }
+            }
+        }
     }
 
-    init_logging(cli.verbose.log_level_filter()).expect("Could not initialize logging");
+    init_logging(cli.verbose.log_level_filter(), cli.log_format)
+        .expect("Could not initialize logging");
+    #[cfg(feature = "mmap")]
+    opts::set_use_mmap(cli.mmap);
+
+    let config = Config::load()?;
+    cli.command.apply_config(&config);
 
     match &cli.command {
         Commands::GenerateModel {
@@ -47,15 +86,23 @@ fn main() -> anyhow::Result<()> {
             context,
             mode,
             limit,
+            contexts,
+            pre_bin,
+            models,
         } => {
             let reader = input.as_reader()?;
             let output =
                 OutputWriter::from_path_and_input(output, &reader, "msgpack", OutputMode::Binary)?;
 
-            let generator = generate_model::CliModelGenerator::new(reader, false, *limit);
+            let mut generator =
+                generate_model::CliModelGenerator::new(reader, models.clone(), false, *limit);
+            if let Some(contexts) = contexts {
+                generator = generator
+                    .with_binning(*contexts as usize, pre_bin.map(|pre_bin| pre_bin as usize));
+            }
             generator
                 .generate_model(output.into_write(), *mode, context.into())
-                .context("Failed to generate a model for given FASTQ file")?;
+                .context("Failed to generate a model for given file")?;
         }
         Commands::GenerateModelAll {
             input,
@@ -63,29 +110,65 @@ fn main() -> anyhow::Result<()> {
             name,
             csv,
             limit,
+            models,
         } => {
             let reader = input.as_reader()?;
 
-            let generator = generate_model::CliModelGenerator::new(reader, *csv, *limit);
+            let generator =
+                generate_model::CliModelGenerator::new(reader, models.clone(), *csv, *limit);
             generator
                 .generate_model_all(&output.as_path_buf()?, name)
-                .context("Failed to generate a model for given FASTQ file")?;
+                .context("Failed to generate a model for given file")?;
+        }
+        Commands::ModelInfo { input, json } => {
+            let reader = input.as_reader()?;
+
+            model_info::model_info(reader.into_read(), *json)
+                .context("Failed to read given model")?;
         }
         Commands::BinContexts {
             input,
             output,
             contexts,
             pre_bin,
+            cost_curve,
         } => {
             let reader = input.as_reader()?;
-            let output =
-                OutputWriter::from_path_and_input(output, &reader, "msgpack", OutputMode::Binary)?;
+            let outputs = if let [num_contexts] = contexts.as_slice() {
+                let output = OutputWriter::from_path_and_input(
+                    output,
+                    &reader,
+                    "msgpack",
+                    OutputMode::Binary,
+                )?;
+                vec![(*num_contexts as usize, output.into_write())]
+            } else {
+                contexts
+                    .iter()
+                    .map(|&num_contexts| {
+                        let output = OutputWriter::from_path_and_input_with_suffix(
+                            output,
+                            &reader,
+                            &format!("_{}", num_contexts),
+                            "msgpack",
+                            OutputMode::Binary,
+                        )?;
+                        anyhow::Ok((num_contexts as usize, output.into_write()))
+                    })
+                    .collect::<anyhow::Result<_>>()?
+            };
+
+            let cost_curve = cost_curve
+                .as_ref()
+                .map(|path| OutputWriter::from_path(path, OutputMode::Text))
+                .transpose()?
+                .map(OutputWriter::into_write);
 
             bin_contexts::bin_contexts(
                 reader.into_read(),
-                output.into_write(),
-                *contexts as usize,
+                outputs,
                 pre_bin.map(|x| x as usize),
+                cost_curve,
             )
             .context("Failed to bin contexts of given model")?;
         }
@@ -110,48 +193,462 @@ fn main() -> anyhow::Result<()> {
             .context("Failed to bin contexts of given model")?;
         }
         Commands::Compress {
-            input,
+            inputs,
             output,
+            glob,
+            single_archive,
+            verify,
             threads,
             block_length,
+            block_reads,
+            target_block_reads,
+            long_reads,
+            no_identifiers,
+            preserve_separator,
+            quality,
+            fast,
+            deduplicate,
+            skip_n_quality,
+            quality_delta,
+            raw_fallback_ratio,
+            group_by_tile,
+            source_digest,
+            deterministic,
+            time_budget,
+            adaptive_effort,
+            context_usage,
+            explain,
+            models,
+        } => {
+            let mut readers = Vec::new();
+            let mut member_names = Vec::new();
+            for input in inputs {
+                for reader in input.as_readers(glob)? {
+                    member_names.push(compress::member_name(input.path(), &reader));
+                    readers.push(reader);
+                }
+            }
+            let mut total_bytes = 0;
+            for reader in &readers {
+                total_bytes += reader.length()?.unwrap_or(0);
+            }
+            PROGRESS_BAR.set_total_bytes(total_bytes);
+            // The models are prepared once and shared by all the compressed files
+            let prepared = models
+                .model_provider()?
+                .prepare_for_compression_with_progress(&*PROGRESS_BAR);
+            // A single collector is used for all the files, so that the usage
+            // is summed up over all of them
+            let context_usage_collector = context_usage.as_ref().map(|_| ContextUsage::new());
+            let explainer = explain
+                .as_deref()
+                .map(compress::CsvExplainer::create)
+                .transpose()?
+                .map(Arc::new);
+            let compress_files = |inputs: Vec<(Option<String>, InputReader)>,
+                                  output: OutputWriter| {
+                let output_path = output.file_path().map(Path::to_path_buf);
+                if *verify && output_path.is_none() {
+                    bail!("Cannot verify a file written to the standard output");
+                }
+
+                let source_hasher = source_digest.then(SourceHasher::new);
+                let input_size = compress::input_size(inputs.iter().map(|(_, reader)| reader))?;
+                let digest = compress::compress(
+                    inputs
+                        .into_iter()
+                        .map(|(member, reader)| {
+                            let reader: Box<dyn BufRead + Send> = match &source_hasher {
+                                Some(source_hasher) => {
+                                    Box::new(source_hasher.reader(reader.into_buf_read()))
+                                }
+                                None => reader.into_buf_read(),
+                            };
+                            (member, reader)
+                        })
+                        .collect(),
+                    output.into_write(),
+                    prepared.model_provider().clone(),
+                    CompressOptions {
+                        threads: *threads,
+                        block_length: *block_length,
+                        block_reads: *block_reads,
+                        target_block_reads: target_block_reads.clone(),
+                        long_reads: *long_reads,
+                        no_identifiers: *no_identifiers,
+                        preserve_separator: *preserve_separator,
+                        quality: *quality,
+                        fast: *fast,
+                        deduplicate: *deduplicate,
+                        skip_n_quality: *skip_n_quality,
+                        quality_delta: *quality_delta,
+                        raw_fallback_ratio: *raw_fallback_ratio,
+                        group_by_tile: *group_by_tile,
+                        source_hasher,
+                        context_usage: context_usage_collector.clone(),
+                        explainer: explainer
+                            .clone()
+                            .map(|explainer| explainer as Arc<dyn SequenceExplainer>),
+                        deterministic: *deterministic,
+                        time_budget: *time_budget,
+                        adaptive_effort: *adaptive_effort,
+                        input_size,
+                    },
+                    Arc::new(PROGRESS_BAR.clone()),
+                )
+                .context("Failed to compress given file")?;
+
+                if let (true, Some(output_path)) = (*verify, output_path) {
+                    compress::verify_compressed(
+                        &output_path,
+                        prepared.model_provider(),
+                        *threads,
+                        &digest,
+                    )
+                    .context("Verification of the compressed file failed")?;
+                }
+
+                anyhow::Ok(())
+            };
+
+            if readers.len() > 1 && !*single_archive {
+                if let Some(output) = output {
+                    if !output.to_string_lossy().contains(NAME_PLACEHOLDER) {
+                        bail!(
+                            "Output path must contain `{}` when compressing multiple files; use --single-archive to create one file instead",
+                            NAME_PLACEHOLDER
+                        );
+                    }
+                }
+
+                for reader in readers {
+                    let output = OutputWriter::from_template_and_input(
+                        output,
+                        &reader,
+                        "idn",
+                        OutputMode::Binary,
+                    )?;
+                    compress_files(vec![(None, reader)], output)?;
+                }
+            } else {
+                if readers.len() > 1 && output.is_none() {
+                    bail!("Please specify the output file with -o when creating a single archive from multiple files");
+                }
+
+                let output = OutputWriter::from_path_and_input(
+                    output,
+                    &readers[0],
+                    "idn",
+                    OutputMode::Binary,
+                )?;
+                let inputs = readers
+                    .into_iter()
+                    .zip(member_names)
+                    .map(|(reader, name)| (single_archive.then_some(name), reader))
+                    .collect();
+                compress_files(inputs, output)?;
+            }
+
+            if let (Some(path), Some(collector)) = (context_usage, &context_usage_collector) {
+                compress::write_context_usage(collector, path)
+                    .context("Failed to write the context usage")?;
+            }
+            if let Some(explainer) = &explainer {
+                explainer
+                    .finish()
+                    .context("Failed to write the sequence explanations")?;
+            }
+        }
+        Commands::CompressPe {
+            input_1,
+            input_2,
+            output,
+            threads,
             no_identifiers,
             quality,
             fast,
+            models,
+        } => {
+            let reader_1 = input_1.as_reader()?;
+            let reader_2 = input_2.as_reader()?;
+            PROGRESS_BAR
+                .set_total_bytes(reader_1.length()?.unwrap_or(0) + reader_2.length()?.unwrap_or(0));
+            let output = OutputWriter::from_path(output, OutputMode::Binary)?;
+
+            paired_end::compress_pe(
+                reader_1.into_read(),
+                reader_2.into_read(),
+                output.into_write(),
+                models.model_provider()?,
+                CompressOptions {
+                    threads: *threads,
+                    no_identifiers: *no_identifiers,
+                    quality: *quality,
+                    fast: *fast,
+                    ..CompressOptions::default()
+                },
+                Arc::new(PROGRESS_BAR.clone()),
+            )
+            .context("Failed to compress given files")?;
+        }
+        Commands::Decompress {
+            input,
+            output,
+            member,
+            threads,
+            read_ahead,
+            models,
         } => {
             let reader = input.as_reader()?;
             PROGRESS_BAR.set_total_bytes(reader.length()?.unwrap_or(0));
             let output =
-                OutputWriter::from_path_and_input(output, &reader, "idn", OutputMode::Binary)?;
+                OutputWriter::from_path_and_input(output, &reader, "fastq", OutputMode::Text)?;
+
+            decompress::decompress(
+                reader.into_read(),
+                output.into_write(),
+                models.model_provider()?,
+                member.clone(),
+                *threads,
+                *read_ahead,
+                Arc::new(PROGRESS_BAR.clone()),
+            )
+            .context("Failed to decompress given file")?;
+        }
+        Commands::DecompressPe {
+            input,
+            output_1,
+            output_2,
+            threads,
+            models,
+        } => {
+            let reader = input.as_reader()?;
+            PROGRESS_BAR.set_total_bytes(reader.length()?.unwrap_or(0));
+            let output_1 = OutputWriter::from_path(output_1, OutputMode::Text)?;
+            let output_2 = OutputWriter::from_path(output_2, OutputMode::Text)?;
+
+            paired_end::decompress_pe(
+                reader.into_read(),
+                output_1.into_write(),
+                output_2.into_write(),
+                models.model_provider()?,
+                *threads,
+                Arc::new(PROGRESS_BAR.clone()),
+            )
+            .context("Failed to decompress given file")?;
+        }
+        Commands::Verify {
+            input,
+            against_source,
+            threads,
+            models,
+        } => {
+            let reader = input.as_reader()?;
+            if let Some(source) = against_source {
+                verify::verify_source(reader.reopen_file()?, source.as_reader()?)
+                    .context("Failed to verify given file against its source")?;
+            }
+            PROGRESS_BAR.set_total_bytes(reader.length()?.unwrap_or(0));
+
+            verify::verify(
+                reader.into_read(),
+                models.model_provider()?,
+                *threads,
+                Arc::new(PROGRESS_BAR.clone()),
+            )
+            .context("Failed to verify given file")?;
+        }
+        Commands::Diff {
+            fastq,
+            idn,
+            ignore_identifiers,
+            threads,
+            models,
+        } => {
+            let fastq_reader = fastq.as_reader()?;
+            let idn_reader = idn.as_reader()?;
+
+            diff::diff(
+                fastq_reader.into_read(),
+                idn_reader.into_read(),
+                models.model_provider()?,
+                *threads,
+                *ignore_identifiers,
+            )
+            .context("Failed to compare given files")?;
+        }
+        Commands::Cat { inputs, output } => {
+            let readers = inputs
+                .iter()
+                .map(|input| Ok(input.as_reader()?.into_read()))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            let output = OutputWriter::from_path(output, OutputMode::Binary)?;
+
+            cat::cat(readers, output.into_write()).context("Failed to concatenate given files")?;
+        }
+        Commands::Recompress {
+            input,
+            output,
+            threads,
+            no_identifiers,
+            quality,
+            fast,
+            deduplicate,
+            models,
+        } => {
+            let reader = input.as_reader()?;
+            PROGRESS_BAR.set_total_bytes(reader.length()?.unwrap_or(0));
+            let output = OutputWriter::from_path(output, OutputMode::Binary)?;
 
-            compress::compress(
+            recompress::recompress(
                 reader.into_read(),
                 output.into_write(),
+                models.model_provider()?,
                 *threads,
-                *block_length,
                 *no_identifiers,
                 *quality,
                 *fast,
+                *deduplicate,
                 Arc::new(PROGRESS_BAR.clone()),
             )
-            .context("Failed to compress given file")?;
+            .context("Failed to recompress given file")?;
         }
-        Commands::Decompress {
+        Commands::Head {
             input,
             output,
+            reads,
             threads,
+            models,
+        } => {
+            let reader = input.as_reader()?;
+            let output = OutputWriter::from_path(output, OutputMode::Text)?;
+
+            head::head(
+                reader.into_read(),
+                output.into_write(),
+                *reads,
+                models.model_provider()?,
+                *threads,
+            )
+            .context("Failed to decompress given file")?;
+        }
+        Commands::Grep {
+            input,
+            output,
+            id_pattern,
+            threads,
+            models,
         } => {
             let reader = input.as_reader()?;
             PROGRESS_BAR.set_total_bytes(reader.length()?.unwrap_or(0));
-            let output =
-                OutputWriter::from_path_and_input(output, &reader, "fastq", OutputMode::Text)?;
+            let output = OutputWriter::from_path(output, OutputMode::Text)?;
 
-            decompress::decompress(
+            grep::grep(
                 reader.into_read(),
                 output.into_write(),
+                id_pattern,
+                models.model_provider()?,
                 *threads,
                 Arc::new(PROGRESS_BAR.clone()),
             )
-            .context("Failed to decompress given file")?;
+            .context("Failed to extract sequences from given file")?;
+        }
+        Commands::Export {
+            input,
+            output,
+            format,
+            member,
+            threads,
+            models,
+        } => {
+            let reader = input.as_reader()?;
+            PROGRESS_BAR.set_total_bytes(reader.length()?.unwrap_or(0));
+            let output = OutputWriter::from_path(output, OutputMode::Text)?;
+
+            export::export(
+                reader.into_read(),
+                output.into_write(),
+                (*format).into(),
+                models.model_provider()?,
+                member.clone(),
+                *threads,
+                Arc::new(PROGRESS_BAR.clone()),
+            )
+            .context("Failed to export given file")?;
+        }
+        Commands::Bench {
+            input,
+            qualities,
+            threads,
+            reads,
+            json,
+            models,
+        } => {
+            let reader = input.as_reader()?;
+
+            bench::bench(
+                reader.into_read(),
+                models.model_provider()?,
+                qualities,
+                threads,
+                *reads,
+                *json,
+            )
+            .context("Failed to benchmark given file")?;
+        }
+        Commands::EvalModels {
+            input,
+            reads,
+            json,
+            models,
+        } => {
+            let reader = input.as_reader()?;
+
+            eval_models::eval_models(reader.into_read(), models.model_provider()?, *reads, *json)
+                .context("Failed to evaluate the models")?;
+        }
+        Commands::Inspect {
+            input,
+            json,
+            models,
+        } => {
+            let reader = input.as_reader()?;
+
+            inspect::inspect(reader.into_read(), *json, models.model_provider().ok())
+                .context("Failed to inspect given file")?;
+        }
+        Commands::Split {
+            input,
+            output,
+            reads_per_file,
+            parts,
+        } => {
+            let reader = input.as_reader()?;
+            let reads_per_file = match (reads_per_file, parts) {
+                (Some(reads_per_file), _) => *reads_per_file,
+                (None, Some(parts)) => {
+                    split::reads_per_file_for_parts(reader.reopen_file()?.into_read(), *parts)?
+                }
+                (None, None) => unreachable!(),
+            };
+
+            let make_writer = |part_index: usize| {
+                let output = OutputWriter::from_path_and_input_with_suffix(
+                    output,
+                    &reader,
+                    &format!("_{}", part_index),
+                    "idn",
+                    OutputMode::Binary,
+                )?;
+                Ok(output.into_write())
+            };
+            let input = if reader.file_path().is_some() {
+                reader.reopen_file()?
+            } else {
+                InputReader::default()
+            };
+            split::split(input.into_read(), reads_per_file, make_writer)
+                .context("Failed to split given file")?;
         }
         Commands::Stats { input } => {
             let reader = input.as_reader()?;
@@ -1,13 +1,25 @@
 use std::fmt::Display;
+use std::fs;
 use std::fs::File;
 use std::io;
-use std::io::{Read, Write};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::ops::RangeInclusive;
 use std::path::{Path, PathBuf};
+#[cfg(feature = "mmap")]
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
-use anyhow::bail;
+use anyhow::{bail, Context};
 use atty::Stream;
+use idencomp::idn::model_provider::ModelProvider;
+#[cfg(feature = "mmap")]
+use idencomp::mmap::MmapReader;
 use log::info;
 
+use crate::config::Config;
+
+const DEFAULT_MODEL_DIR: &str = "models/";
+
 #[derive(clap::Args, Debug, Clone)]
 pub struct Directory {
     path: PathBuf,
@@ -41,6 +53,46 @@ impl Directory {
     }
 }
 
+#[derive(clap::Args, Debug, Clone)]
+pub struct ModelArgs {
+    /// Directory to load the models from; defaults to `models/` if no
+    /// --model-dir nor --model option is provided
+    #[clap(long, value_parser)]
+    model_dir: Option<PathBuf>,
+
+    /// Model file to load; can be provided multiple times
+    #[clap(long = "model", value_parser, value_name = "MODEL_FILE")]
+    models: Vec<PathBuf>,
+}
+
+impl ModelArgs {
+    pub fn model_provider(&self) -> anyhow::Result<ModelProvider> {
+        let model_dir = match &self.model_dir {
+            Some(model_dir) => Some(model_dir.as_path()),
+            None if self.models.is_empty() => Some(Path::new(DEFAULT_MODEL_DIR)),
+            None => None,
+        };
+
+        let mut paths = self.models.clone();
+        if let Some(model_dir) = model_dir {
+            let entries = fs::read_dir(model_dir).with_context(|| {
+                format!("Could not read model directory: {}", model_dir.display())
+            })?;
+            for entry in entries {
+                paths.push(entry?.path());
+            }
+        }
+
+        ModelProvider::from_files(&paths).context("Could not load the models")
+    }
+
+    pub fn apply_config(&mut self, config: &Config) {
+        if self.model_dir.is_none() && self.models.is_empty() {
+            self.model_dir = config.model_dir().cloned();
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct InputFile {
     path: PathBuf,
@@ -96,21 +148,65 @@ impl Default for InputStream {
 }
 
 impl InputStream {
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
     pub fn as_reader(&self) -> Result<InputReader, anyhow::Error> {
         InputReader::from_path(&self.path)
     }
+
+    pub fn as_readers(&self, pattern: &str) -> Result<Vec<InputReader>, anyhow::Error> {
+        if !self.path.is_dir() {
+            return Ok(vec![self.as_reader()?]);
+        }
+
+        let pattern = self.path.join(pattern);
+        let paths = glob::glob(&pattern.to_string_lossy())?.collect::<Result<Vec<_>, _>>()?;
+        if paths.is_empty() {
+            bail!("No files matching `{}` found", pattern.display());
+        }
+
+        paths
+            .iter()
+            .map(|path| InputReader::from_path(path))
+            .collect()
+    }
+}
+
+/// Whether the input files should be memory-mapped instead of being read.
+#[cfg(feature = "mmap")]
+static USE_MMAP: AtomicBool = AtomicBool::new(false);
+
+#[cfg(feature = "mmap")]
+pub fn set_use_mmap(use_mmap: bool) {
+    USE_MMAP.store(use_mmap, Ordering::Relaxed);
 }
 
 #[derive(Debug)]
 pub enum InputReader {
     Stdin(io::Stdin),
-    File { file: File, path: PathBuf },
+    File {
+        file: File,
+        path: PathBuf,
+    },
+    #[cfg(feature = "mmap")]
+    Mmap {
+        reader: MmapReader,
+        path: PathBuf,
+    },
 }
 
 impl InputReader {
     fn from_path(path: &Path) -> anyhow::Result<Self> {
         let is_stdin = path.to_string_lossy() == "-";
 
+        #[cfg(feature = "mmap")]
+        if !is_stdin && USE_MMAP.load(Ordering::Relaxed) {
+            return Self::open_mmap(path);
+        }
+
         let val = if is_stdin {
             Self::Stdin(io::stdin())
         } else {
@@ -124,10 +220,27 @@ impl InputReader {
         Ok(val)
     }
 
+    #[cfg(feature = "mmap")]
+    fn open_mmap(path: &Path) -> anyhow::Result<Self> {
+        // SAFETY: the user has explicitly requested memory mapping and is
+        // expected not to modify the input files while they are being read
+        let reader = unsafe { MmapReader::open(path) }
+            .with_context(|| format!("Could not memory-map {}", path.display()))?;
+
+        Ok(Self::Mmap {
+            reader,
+            path: path.to_owned(),
+        })
+    }
+
     pub fn reopen_file(&self) -> anyhow::Result<Self> {
         match self {
             InputReader::File { path, .. } => Self::from_path(path),
-            _ => panic!("Cannot reopen stdin"),
+            #[cfg(feature = "mmap")]
+            InputReader::Mmap { path, .. } => Self::from_path(path),
+            InputReader::Stdin(_) => bail!(
+                "Cannot read the standard input multiple times; please provide a file path instead"
+            ),
         }
     }
 
@@ -135,6 +248,8 @@ impl InputReader {
         let val = match self {
             InputReader::Stdin(_) => None,
             InputReader::File { file, .. } => Some(file.metadata()?.len()),
+            #[cfg(feature = "mmap")]
+            InputReader::Mmap { reader, .. } => Some(reader.len() as u64),
         };
         Ok(val)
     }
@@ -143,6 +258,8 @@ impl InputReader {
         match self {
             InputReader::Stdin(_) => None,
             InputReader::File { path, .. } => Some(path),
+            #[cfg(feature = "mmap")]
+            InputReader::Mmap { path, .. } => Some(path),
         }
     }
 
@@ -151,6 +268,20 @@ impl InputReader {
         match self {
             InputReader::Stdin(stdin) => Box::new(stdin),
             InputReader::File { file, .. } => Box::new(file),
+            #[cfg(feature = "mmap")]
+            InputReader::Mmap { reader, .. } => Box::new(reader),
+        }
+    }
+
+    /// Returns a buffered reader. Memory-mapped files are not wrapped in
+    /// another buffer, as they can be read directly.
+    #[must_use]
+    pub fn into_buf_read(self) -> Box<dyn BufRead + Send> {
+        match self {
+            #[cfg(feature = "mmap")]
+            InputReader::Mmap { reader, .. } => Box::new(reader),
+            InputReader::Stdin(stdin) => Box::new(BufReader::new(stdin)),
+            InputReader::File { file, .. } => Box::new(BufReader::new(file)),
         }
     }
 }
@@ -161,6 +292,8 @@ impl Default for InputReader {
     }
 }
 
+pub const NAME_PLACEHOLDER: &str = "{name}";
+
 #[derive(Debug, Eq, PartialEq)]
 pub enum OutputMode {
     Text,
@@ -170,7 +303,7 @@ pub enum OutputMode {
 #[derive(Debug)]
 pub enum OutputWriter {
     Stdout(io::Stdout),
-    File(File),
+    File { file: File, path: PathBuf },
 }
 
 impl OutputWriter {
@@ -180,19 +313,57 @@ impl OutputWriter {
         new_extension: &str,
         mode: OutputMode,
     ) -> anyhow::Result<Self> {
+        let path = Self::output_path(output, input, new_extension);
+        Self::from_path(&path, mode)
+    }
+
+    pub fn from_template_and_input(
+        template: &Option<PathBuf>,
+        input: &InputReader,
+        new_extension: &str,
+        mode: OutputMode,
+    ) -> anyhow::Result<Self> {
+        let output = template.as_ref().map(|template| {
+            let name = input
+                .file_path()
+                .and_then(|path| path.file_stem())
+                .map_or_else(|| "stdin".into(), |name| name.to_string_lossy());
+            PathBuf::from(template.to_string_lossy().replace(NAME_PLACEHOLDER, &name))
+        });
+        Self::from_path_and_input(&output, input, new_extension, mode)
+    }
+
+    pub fn from_path_and_input_with_suffix(
+        output: &Option<PathBuf>,
+        input: &InputReader,
+        suffix: &str,
+        new_extension: &str,
+        mode: OutputMode,
+    ) -> anyhow::Result<Self> {
+        let path = Self::output_path(output, input, new_extension);
+        if path.to_string_lossy() == "-" {
+            bail!("Cannot output multiple files to stdout; please use -o option instead");
+        }
+
+        let mut file_name = path.file_stem().unwrap_or_default().to_os_string();
+        file_name.push(suffix);
+        let path = path.with_file_name(file_name).with_extension(new_extension);
+
+        Self::from_path(&path, mode)
+    }
+
+    fn output_path(output: &Option<PathBuf>, input: &InputReader, new_extension: &str) -> PathBuf {
         if let Some(path) = output {
-            Self::from_path(path, mode)
+            path.clone()
         } else {
-            let path = input
+            input
                 .file_path()
                 .map(|path| path.with_extension(new_extension))
-                .unwrap_or_else(|| PathBuf::from("-"));
-
-            Self::from_path(&path, mode)
+                .unwrap_or_else(|| PathBuf::from("-"))
         }
     }
 
-    fn from_path(path: &Path, mode: OutputMode) -> anyhow::Result<Self> {
+    pub fn from_path(path: &Path, mode: OutputMode) -> anyhow::Result<Self> {
         info!("Output file: {}", path.display());
 
         let is_stdout = path.to_string_lossy() == "-";
@@ -205,16 +376,93 @@ impl OutputWriter {
             Self::Stdout(io::stdout())
         } else {
             let file = File::create(path)?;
-            Self::File(file)
+            Self::File {
+                file,
+                path: path.to_owned(),
+            }
         };
 
         Ok(writer)
     }
 
+    pub fn file_path(&self) -> Option<&Path> {
+        match self {
+            OutputWriter::Stdout(_) => None,
+            OutputWriter::File { path, .. } => Some(path),
+        }
+    }
+
     pub fn into_write(self) -> Box<dyn Write + Send> {
         match self {
             OutputWriter::Stdout(stdout) => Box::new(stdout),
-            OutputWriter::File(file) => Box::new(file),
+            OutputWriter::File { file, .. } => Box::new(file),
         }
     }
 }
+
+/// Parses a duration given in seconds, or in minutes or hours with the `m` or
+/// `h` suffix.
+pub fn duration(value: &str) -> Result<Duration, String> {
+    let (number, multiplier) = match value.chars().last() {
+        Some('s') => (&value[..value.len() - 1], 1.0),
+        Some('m') => (&value[..value.len() - 1], 60.0),
+        Some('h') => (&value[..value.len() - 1], 3600.0),
+        _ => (value, 1.0),
+    };
+
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("Invalid duration: {}", value))?;
+    if number.is_nan() || number <= 0.0 {
+        return Err("The duration must be greater than 0".to_owned());
+    }
+
+    let seconds = number * multiplier;
+    if seconds >= u64::MAX as f64 {
+        return Err(format!("Duration too large: {}", value));
+    }
+    Ok(Duration::from_secs_f64(seconds))
+}
+
+pub fn ratio(value: &str) -> Result<f32, String> {
+    let number: f32 = value
+        .parse()
+        .map_err(|_| format!("Invalid ratio: {}", value))?;
+    if !number.is_finite() || number <= 0.0 {
+        return Err("The ratio must be greater than 0".to_owned());
+    }
+
+    Ok(number)
+}
+
+pub fn count(value: &str) -> Result<usize, String> {
+    let (number, multiplier) = match value.chars().last() {
+        Some('k' | 'K') => (&value[..value.len() - 1], 1_000),
+        Some('m' | 'M') => (&value[..value.len() - 1], 1_000_000),
+        Some('g' | 'G') => (&value[..value.len() - 1], 1_000_000_000),
+        _ => (value, 1),
+    };
+
+    let number: usize = number
+        .parse()
+        .map_err(|_| format!("Invalid number: {}", value))?;
+    if number == 0 {
+        return Err("The value must be greater than 0".to_owned());
+    }
+
+    number
+        .checked_mul(multiplier)
+        .ok_or_else(|| format!("Number too large: {}", value))
+}
+
+pub fn count_range(value: &str) -> Result<RangeInclusive<usize>, String> {
+    let (start, end) = value
+        .split_once('-')
+        .ok_or_else(|| format!("Invalid range (expected MIN-MAX): {}", value))?;
+    let (start, end) = (count(start)?, count(end)?);
+    if start > end {
+        return Err(format!("The range cannot be empty: {}", value));
+    }
+
+    Ok(start..=end)
+}
@@ -1,14 +1,49 @@
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use idencomp::progress::{ByteNum, ProgressNotifier};
+use clap::ValueEnum;
+use idencomp::progress::{
+    ByteNum, ProgressNotifier, ProgressSnapshot, ProgressStage, ProgressTracker,
+};
 use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use serde_json::json;
+
+/// Minimum time between two consecutive progress lines in the JSON mode.
+const JSON_REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, ValueEnum)]
+pub enum ProgressFormat {
+    /// Progress bar/spinner displayed on the terminal
+    Bar,
+    /// One JSON object per line printed periodically to the standard error
+    Json,
+}
+
+#[derive(Debug)]
+struct StageState {
+    stage: ProgressStage,
+    total: Option<u64>,
+    processed: u64,
+}
+
+impl StageState {
+    fn message(&self) -> String {
+        match self.total {
+            Some(total) => format!("{} ({}/{})", self.stage, self.processed.min(total), total),
+            None => self.stage.to_string(),
+        }
+    }
+}
 
 #[derive(Debug)]
 struct IdnProgressBarState {
     length: u64,
     bytes: bool,
     initialized: bool,
+    json: bool,
+    tracker: ProgressTracker,
+    last_report: Option<Instant>,
+    stage: Option<StageState>,
 }
 
 impl IdnProgressBarState {
@@ -17,7 +52,48 @@ impl IdnProgressBarState {
             length: 0,
             bytes: false,
             initialized: false,
+            json: false,
+            tracker: ProgressTracker::new(),
+            last_report: None,
+            stage: None,
+        }
+    }
+
+    fn reset(&mut self, length: u64, bytes: bool) {
+        self.initialized = false;
+        self.bytes = bytes;
+        self.length = length;
+        self.tracker = ProgressTracker::new();
+        self.tracker
+            .set_total(if length == 0 { None } else { Some(length) });
+    }
+
+    fn report_json(&mut self, force: bool) {
+        let now = Instant::now();
+        if let Some(last_report) = self.last_report {
+            if !force && now - last_report < JSON_REPORT_INTERVAL {
+                return;
+            }
         }
+        self.last_report = Some(now);
+
+        eprintln!("{}", self.format_json(&self.tracker.snapshot()));
+    }
+
+    fn format_json(&self, snapshot: &ProgressSnapshot) -> String {
+        json!({
+            "unit": if self.bytes { "bytes" } else { "items" },
+            "processed": snapshot.processed(),
+            "total": snapshot.total(),
+            "fraction": snapshot.fraction(),
+            "per_sec": snapshot.per_sec(),
+            "elapsed_secs": snapshot.elapsed().as_secs_f64(),
+            "eta_secs": snapshot.eta().map(|eta| eta.as_secs_f64()),
+            "stage": self.stage.as_ref().map(|stage| stage.stage.to_string()),
+            "stage_processed": self.stage.as_ref().map(|stage| stage.processed),
+            "stage_total": self.stage.as_ref().and_then(|stage| stage.total),
+        })
+        .to_string()
     }
 }
 
@@ -44,17 +120,26 @@ impl IdnProgressBar {
         self.bar.set_draw_target(ProgressDrawTarget::stderr());
     }
 
+    /// Enables printing the progress as JSON lines instead of displaying the
+    /// progress bar.
+    pub fn show_json(&self) {
+        self.state.lock().unwrap().json = true;
+    }
+
     pub fn is_hidden(&self) -> bool {
         self.bar.is_hidden()
     }
 
     pub fn finish(&self) {
+        let mut state = self.state.lock().unwrap();
+        if state.json && state.initialized {
+            state.report_json(true);
+        }
         self.bar.finish_and_clear()
     }
 
     #[inline]
-    fn init(&self) {
-        let mut state = self.state.lock().unwrap();
+    fn init(&self, state: &mut IdnProgressBarState) {
         if state.initialized {
             return;
         }
@@ -68,13 +153,15 @@ impl IdnProgressBar {
             if state.length == 0 {
                 self.bar.set_style(
                     ProgressStyle::default_spinner()
-                        .template("{spinner} {bytes}/? ({bytes_per_sec}) {msg}")
+                        .template("{spinner} {bytes}/? ({binary_bytes_per_sec}) {msg}")
                         .expect("Invalid progress bar template"),
                 );
             } else {
                 self.bar.set_style(
                     ProgressStyle::default_bar()
-                        .template("{wide_bar} {bytes}/{total_bytes} [ETA {eta}]")
+                        .template(
+                            "{wide_bar} {bytes}/{total_bytes} ({binary_bytes_per_sec}) [ETA {eta}] {msg}",
+                        )
                         .expect("Invalid progress bar template"),
                 );
             }
@@ -87,7 +174,7 @@ impl IdnProgressBar {
         } else {
             self.bar.set_style(
                 ProgressStyle::default_bar()
-                    .template("{wide_bar} {pos}/{len} [ETA {eta}]")
+                    .template("{wide_bar} {pos}/{len} ({per_sec}) [ETA {eta}] {msg}")
                     .expect("Invalid progress bar template"),
             );
         }
@@ -95,26 +182,45 @@ impl IdnProgressBar {
     }
 
     pub fn set_total_bytes(&self, length: u64) {
-        let mut state = self.state.lock().unwrap();
-
-        state.initialized = false;
-        state.bytes = true;
-        state.length = length;
+        self.state.lock().unwrap().reset(length, true);
     }
 
     pub fn set_length(&self, length: u64) {
-        let mut state = self.state.lock().unwrap();
-
-        state.initialized = false;
-        state.bytes = false;
-        state.length = length;
+        self.state.lock().unwrap().reset(length, false);
     }
 
     pub fn inc(&self, value: u64) {
-        self.init();
+        let mut state = self.state.lock().unwrap();
+        self.init(&mut state);
+        state.tracker.add(value);
+        if state.json {
+            state.report_json(false);
+        }
         self.bar.inc(value);
     }
 
+    pub fn start_stage(&self, stage: ProgressStage, total: Option<u64>) {
+        let mut state = self.state.lock().unwrap();
+        let stage = StageState {
+            stage,
+            total,
+            processed: 0,
+        };
+        self.bar.set_message(stage.message());
+        state.stage = Some(stage);
+        if state.json {
+            state.report_json(true);
+        }
+    }
+
+    pub fn advance_stage(&self, units: u64) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(stage) = &mut state.stage {
+            stage.processed += units;
+            self.bar.set_message(stage.message());
+        }
+    }
+
     pub fn println<I: AsRef<str>>(&self, msg: I) {
         self.bar.println(msg);
     }
@@ -122,8 +228,11 @@ impl IdnProgressBar {
 
 impl ProgressNotifier for IdnProgressBar {
     fn processed_bytes(&self, bytes: ByteNum) {
-        self.init();
-        self.bar.inc(bytes.get() as u64);
+        self.inc(bytes.get() as u64);
+    }
+
+    fn set_total_bytes(&self, bytes: ByteNum) {
+        IdnProgressBar::set_total_bytes(self, bytes.get() as u64);
     }
 
     fn set_iter_num(&self, num_iter: u64) {
@@ -133,4 +242,12 @@ impl ProgressNotifier for IdnProgressBar {
     fn inc_iter(&self) {
         self.inc(1);
     }
+
+    fn start_stage(&self, stage: ProgressStage, total: Option<u64>) {
+        IdnProgressBar::start_stage(self, stage, total);
+    }
+
+    fn advance_stage(&self, units: u64) {
+        IdnProgressBar::advance_stage(self, units);
+    }
 }
@@ -0,0 +1,145 @@
+/*
+ * C bindings for the idencomp IDN compressor and decompressor.
+ *
+ * All the functions returning IdnStatus or a pointer report the details of any
+ * error through idn_last_error(). Panics never cross the C ABI; they are caught
+ * and reported as IDN_STATUS_PANIC (or NULL) instead.
+ */
+
+#ifndef IDENCOMP_H
+#define IDENCOMP_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Status code returned by the functions of this library. */
+typedef enum IdnStatus {
+    /* The operation succeeded. */
+    IDN_STATUS_OK = 0,
+    /* There are no more reads to be returned. */
+    IDN_STATUS_END = 1,
+    /* Invalid argument (e.g. a null pointer or an invalid read) has been
+     * passed. */
+    IDN_STATUS_INVALID_ARGUMENT = -1,
+    /* The operation failed, e.g. because of an I/O error or invalid data. */
+    IDN_STATUS_ERROR = -2,
+    /* An unexpected internal error (a panic) occurred. The handle passed cannot
+     * be used anymore, other than to release it. */
+    IDN_STATUS_PANIC = -3,
+} IdnStatus;
+
+/*
+ * A single FASTQ read.
+ *
+ * The strings are not null-terminated. `acids` and `quality_scores` have to be
+ * `len` bytes long; the quality scores are encoded as in FASTQ files
+ * (Phred+33).
+ */
+typedef struct IdnRead {
+    /* The identifier (title) of the read, without the leading `@`. */
+    const char *identifier;
+    /* The length of the identifier in bytes. */
+    size_t identifier_len;
+    /* The nucleotides of the read (`A`, `C`, `G`, `T` or `N`). */
+    const char *acids;
+    /* The quality scores of the read. */
+    const char *quality_scores;
+    /* The length of the read. */
+    size_t len;
+} IdnRead;
+
+/* IDN compressor handle. */
+typedef struct IdnCompressorHandle IdnCompressorHandle;
+/* IDN decompressor handle. */
+typedef struct IdnDecompressorHandle IdnDecompressorHandle;
+
+/*
+ * Callback used to write the compressed data. Should write at most `len` bytes
+ * from `buf` and return the number of bytes written, or a negative value on
+ * error. May be called from any thread.
+ */
+typedef intptr_t (*IdnWriteFn)(void *ctx, const uint8_t *buf, size_t len);
+
+/*
+ * Callback used to read the compressed data. Should read at most `len` bytes
+ * into `buf` and return the number of bytes read, 0 at the end of the data, or
+ * a negative value on error. May be called from any thread.
+ */
+typedef intptr_t (*IdnReadFn)(void *ctx, uint8_t *buf, size_t len);
+
+/*
+ * Returns the message describing the last error that occurred in the current
+ * thread, or NULL if there was none. The returned string is valid until the
+ * next call to any function of this library in the current thread.
+ */
+const char *idn_last_error(void);
+
+/*
+ * Creates a new IDN compressor writing the data with given callback.
+ *
+ * `model_dir` is the path to the directory with the models to use, or NULL to
+ * use the default models. `quality` is the compression quality (1-9).
+ * `thread_num` is the number of threads to compress with; 0 means that all the
+ * work is done in the calling thread.
+ *
+ * Returns NULL on error. The returned handle has to be released with
+ * idn_compressor_finish() or idn_compressor_free().
+ */
+IdnCompressorHandle *idn_compressor_new(IdnWriteFn write, void *ctx,
+                                        const char *model_dir, uint8_t quality,
+                                        size_t thread_num);
+
+/* Adds a read to be compressed. */
+IdnStatus idn_compressor_add_read(IdnCompressorHandle *compressor,
+                                  const IdnRead *read);
+
+/*
+ * Finishes the compression, writing all the remaining data, and releases the
+ * compressor. The handle cannot be used after calling this function, even if
+ * it returned an error.
+ */
+IdnStatus idn_compressor_finish(IdnCompressorHandle *compressor);
+
+/*
+ * Releases the compressor without finishing the compression, so the output is
+ * not a valid IDN file. Does nothing if `compressor` is NULL.
+ */
+void idn_compressor_free(IdnCompressorHandle *compressor);
+
+/*
+ * Creates a new IDN decompressor reading the data with given callback.
+ *
+ * `model_dir` is the path to the directory with the models the file was
+ * compressed with, or NULL to use the default models. `thread_num` is the
+ * number of threads to decompress with; 0 means that all the work is done in
+ * the calling thread.
+ *
+ * Returns NULL on error. The returned handle has to be released with
+ * idn_decompressor_free().
+ */
+IdnDecompressorHandle *idn_decompressor_new(IdnReadFn read, void *ctx,
+                                            const char *model_dir,
+                                            size_t thread_num);
+
+/*
+ * Decompresses the next read and stores it in `read`. Returns IDN_STATUS_END
+ * if there are no more reads.
+ *
+ * The data pointed to by `read` is owned by the decompressor and is valid until
+ * the next call to this function or until the decompressor is released.
+ */
+IdnStatus idn_decompressor_next_read(IdnDecompressorHandle *decompressor,
+                                     IdnRead *read);
+
+/* Releases the decompressor. Does nothing if `decompressor` is NULL. */
+void idn_decompressor_free(IdnDecompressorHandle *decompressor);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* IDENCOMP_H */
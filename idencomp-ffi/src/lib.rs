@@ -0,0 +1,680 @@
+//! C bindings for the idencomp IDN compressor and decompressor.
+//!
+//! The C declarations of the items exposed here are available in the
+//! `include/idencomp.h` header file.
+//!
+//! All the functions returning [`IdnStatus`] or a pointer report the details
+//! of any error through [`idn_last_error()`]. Panics never cross the C ABI;
+//! they are caught and reported as [`IdnStatus::Panic`] (or null) instead.
+
+#![warn(missing_docs)]
+
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::fmt::{self, Debug, Display, Formatter};
+use std::io::{self, Read, Write};
+use std::os::raw::{c_char, c_void};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::{ptr, slice};
+
+use idencomp::fastq::{FastqQualityScore, FastqSequence};
+use idencomp::idn::compressor::{CompressionQuality, IdnCompressor, IdnCompressorParams};
+use idencomp::idn::decompressor::{IdnDecompressor, IdnDecompressorParams};
+use idencomp::idn::model_provider::ModelProvider;
+use idencomp::sequence::Acid;
+
+/// Callback used to write the compressed data. Should write at most `len`
+/// bytes from `buf` and return the number of bytes written, or a negative value
+/// on error.
+pub type IdnWriteFn = unsafe extern "C" fn(ctx: *mut c_void, buf: *const u8, len: usize) -> isize;
+
+/// Callback used to read the compressed data. Should read at most `len` bytes
+/// into `buf` and return the number of bytes read, `0` at the end of the data,
+/// or a negative value on error.
+pub type IdnReadFn = unsafe extern "C" fn(ctx: *mut c_void, buf: *mut u8, len: usize) -> isize;
+
+/// Status code returned by the functions of this library.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum IdnStatus {
+    /// The operation succeeded.
+    Ok = 0,
+    /// There are no more reads to be returned.
+    End = 1,
+    /// Invalid argument (e.g. a null pointer or an invalid read) has been
+    /// passed.
+    InvalidArgument = -1,
+    /// The operation failed, e.g. because of an I/O error or invalid data.
+    Error = -2,
+    /// An unexpected internal error (a panic) occurred. The handle passed
+    /// cannot be used anymore, other than to release it.
+    Panic = -3,
+}
+
+/// A single FASTQ read.
+///
+/// The strings are not null-terminated. `acids` and `quality_scores` have to
+/// be `len` bytes long; the quality scores are encoded as in FASTQ files
+/// (Phred+33).
+#[repr(C)]
+#[derive(Debug)]
+pub struct IdnRead {
+    /// The identifier (title) of the read, without the leading `@`.
+    pub identifier: *const c_char,
+    /// The length of the identifier in bytes.
+    pub identifier_len: usize,
+    /// The nucleotides of the read (`A`, `C`, `G`, `T` or `N`).
+    pub acids: *const c_char,
+    /// The quality scores of the read.
+    pub quality_scores: *const c_char,
+    /// The length of the read.
+    pub len: usize,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error<T: Display>(error: T) {
+    let message = CString::new(error.to_string().replace('\0', ""))
+        .expect("Null bytes should have been removed");
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = Some(message));
+}
+
+/// Calls `f`, catching any panic so that it does not unwind into the C code.
+/// If `f` panics, the panic message is stored as the last error and
+/// `on_panic` is returned.
+fn catch_panic<T, F: FnOnce() -> T>(on_panic: T, f: F) -> T {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|payload| {
+        let message = payload
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("unknown error");
+        set_last_error(format_args!("Internal error: {}", message));
+        on_panic
+    })
+}
+
+/// Returns the message describing the last error that occurred in the current
+/// thread, or null if there was none. The returned string is valid until the
+/// next call to any function of this library in the current thread.
+#[no_mangle]
+pub extern "C" fn idn_last_error() -> *const c_char {
+    catch_panic(ptr::null(), || {
+        LAST_ERROR.with(|last_error| {
+            last_error
+                .borrow()
+                .as_ref()
+                .map_or(ptr::null(), |message| message.as_ptr())
+        })
+    })
+}
+
+struct CallbackWriter {
+    write: IdnWriteFn,
+    ctx: *mut c_void,
+}
+
+// The callers of `idn_compressor_new` guarantee that the callback can be called
+// from any thread
+unsafe impl Send for CallbackWriter {}
+
+impl Debug for CallbackWriter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CallbackWriter").finish_non_exhaustive()
+    }
+}
+
+impl Write for CallbackWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = unsafe { (self.write)(self.ctx, buf.as_ptr(), buf.len()) };
+        usize::try_from(written)
+            .map_err(|_| io::Error::new(io::ErrorKind::Other, "The write callback failed"))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+struct CallbackReader {
+    read: IdnReadFn,
+    ctx: *mut c_void,
+}
+
+// The callers of `idn_decompressor_new` guarantee that the callback can be
+// called from any thread
+unsafe impl Send for CallbackReader {}
+
+impl Debug for CallbackReader {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CallbackReader").finish_non_exhaustive()
+    }
+}
+
+impl Read for CallbackReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = unsafe { (self.read)(self.ctx, buf.as_mut_ptr(), buf.len()) };
+        usize::try_from(read)
+            .map_err(|_| io::Error::new(io::ErrorKind::Other, "The read callback failed"))
+    }
+}
+
+unsafe fn model_provider(model_dir: *const c_char) -> Result<ModelProvider, IdnStatus> {
+    if model_dir.is_null() {
+        return Ok(ModelProvider::default());
+    }
+
+    let model_dir = CStr::from_ptr(model_dir).to_str().map_err(|error| {
+        set_last_error(format_args!("Invalid model directory: {}", error));
+        IdnStatus::InvalidArgument
+    })?;
+    ModelProvider::from_directory(Path::new(model_dir)).map_err(|error| {
+        set_last_error(error);
+        IdnStatus::Error
+    })
+}
+
+/// IDN compressor handle created with [`idn_compressor_new()`].
+#[derive(Debug)]
+pub struct IdnCompressorHandle {
+    compressor: IdnCompressor<CallbackWriter>,
+}
+
+/// Creates a new IDN compressor writing the data with given callback.
+///
+/// `model_dir` is the path to the directory with the models to use, or null
+/// to use the default models. `quality` is the compression quality (1-9).
+/// `thread_num` is the number of threads to compress with; `0` means that all
+/// the work is done in the calling thread.
+///
+/// Returns null on error. The returned handle has to be released with
+/// [`idn_compressor_finish()`] or [`idn_compressor_free()`].
+///
+/// # Safety
+/// `model_dir` has to be null or a valid null-terminated string. `write` has to
+/// be safe to call from any thread with given `ctx` until the compressor is
+/// released.
+#[no_mangle]
+pub unsafe extern "C" fn idn_compressor_new(
+    write: Option<IdnWriteFn>,
+    ctx: *mut c_void,
+    model_dir: *const c_char,
+    quality: u8,
+    thread_num: usize,
+) -> *mut IdnCompressorHandle {
+    catch_panic(ptr::null_mut(), || {
+        compressor_new(write, ctx, model_dir, quality, thread_num)
+    })
+}
+
+unsafe fn compressor_new(
+    write: Option<IdnWriteFn>,
+    ctx: *mut c_void,
+    model_dir: *const c_char,
+    quality: u8,
+    thread_num: usize,
+) -> *mut IdnCompressorHandle {
+    let write = match write {
+        Some(write) => write,
+        None => {
+            set_last_error("The write callback cannot be null");
+            return ptr::null_mut();
+        }
+    };
+    let quality = match CompressionQuality::try_new(quality) {
+        Ok(quality) => quality,
+        Err(error) => {
+            set_last_error(error);
+            return ptr::null_mut();
+        }
+    };
+    let model_provider = match model_provider(model_dir) {
+        Ok(model_provider) => model_provider,
+        Err(_) => return ptr::null_mut(),
+    };
+    let params = IdnCompressorParams::builder()
+        .model_provider(model_provider)
+        .quality(quality)
+        .thread_num(thread_num)
+        .build();
+    let params = match params {
+        Ok(params) => params,
+        Err(error) => {
+            set_last_error(error);
+            return ptr::null_mut();
+        }
+    };
+
+    let writer = CallbackWriter { write, ctx };
+    let compressor = IdnCompressor::with_params(writer, params);
+    Box::into_raw(Box::new(IdnCompressorHandle { compressor }))
+}
+
+fn acid_from_byte(byte: u8) -> Option<Acid> {
+    match byte {
+        b'A' => Some(Acid::A),
+        b'C' => Some(Acid::C),
+        b'G' => Some(Acid::G),
+        b'T' => Some(Acid::T),
+        b'N' => Some(Acid::N),
+        _ => None,
+    }
+}
+
+fn acid_to_byte(acid: Acid) -> u8 {
+    match acid {
+        Acid::A => b'A',
+        Acid::C => b'C',
+        Acid::G => b'G',
+        Acid::T => b'T',
+        Acid::N => b'N',
+    }
+}
+
+fn q_score_from_byte(byte: u8) -> Option<FastqQualityScore> {
+    byte.checked_sub(b'!')
+        .and_then(|value| FastqQualityScore::try_new(value).ok())
+}
+
+unsafe fn bytes<'a>(data: *const c_char, len: usize) -> &'a [u8] {
+    if len == 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(data.cast(), len)
+    }
+}
+
+unsafe fn read_to_sequence(read: &IdnRead) -> Result<FastqSequence, String> {
+    if (read.identifier.is_null() && read.identifier_len != 0)
+        || ((read.acids.is_null() || read.quality_scores.is_null()) && read.len != 0)
+    {
+        return Err("Null pointer passed as read data".to_owned());
+    }
+
+    let identifier = std::str::from_utf8(bytes(read.identifier, read.identifier_len))
+        .map_err(|error| format!("Invalid identifier: {}", error))?;
+    let acids = bytes(read.acids, read.len)
+        .iter()
+        .map(|&byte| acid_from_byte(byte).ok_or_else(|| format!("Invalid acid: {}", byte as char)))
+        .collect::<Result<Vec<_>, _>>()?;
+    let quality_scores = bytes(read.quality_scores, read.len)
+        .iter()
+        .map(|&byte| {
+            q_score_from_byte(byte)
+                .ok_or_else(|| format!("Invalid quality score: {}", byte as char))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(FastqSequence::new(identifier, acids, quality_scores))
+}
+
+/// Adds a read to be compressed.
+///
+/// # Safety
+/// `compressor` has to be a handle returned by [`idn_compressor_new()`] that
+/// has not been released yet. `read` has to point to a valid [`IdnRead`].
+#[no_mangle]
+pub unsafe extern "C" fn idn_compressor_add_read(
+    compressor: *mut IdnCompressorHandle,
+    read: *const IdnRead,
+) -> IdnStatus {
+    catch_panic(IdnStatus::Panic, || compressor_add_read(compressor, read))
+}
+
+unsafe fn compressor_add_read(
+    compressor: *mut IdnCompressorHandle,
+    read: *const IdnRead,
+) -> IdnStatus {
+    let (compressor, read) = match (compressor.as_mut(), read.as_ref()) {
+        (Some(compressor), Some(read)) => (compressor, read),
+        _ => {
+            set_last_error("Null pointer passed as an argument");
+            return IdnStatus::InvalidArgument;
+        }
+    };
+
+    let sequence = match read_to_sequence(read) {
+        Ok(sequence) => sequence,
+        Err(error) => {
+            set_last_error(error);
+            return IdnStatus::InvalidArgument;
+        }
+    };
+    match compressor.compressor.add_sequence(sequence) {
+        Ok(()) => IdnStatus::Ok,
+        Err(error) => {
+            set_last_error(error);
+            IdnStatus::Error
+        }
+    }
+}
+
+/// Finishes the compression, writing all the remaining data, and releases the
+/// compressor. The handle cannot be used after calling this function, even if
+/// it returned an error.
+///
+/// # Safety
+/// `compressor` has to be a handle returned by [`idn_compressor_new()`] that
+/// has not been released yet.
+#[no_mangle]
+pub unsafe extern "C" fn idn_compressor_finish(compressor: *mut IdnCompressorHandle) -> IdnStatus {
+    catch_panic(IdnStatus::Panic, || compressor_finish(compressor))
+}
+
+unsafe fn compressor_finish(compressor: *mut IdnCompressorHandle) -> IdnStatus {
+    if compressor.is_null() {
+        set_last_error("Null pointer passed as an argument");
+        return IdnStatus::InvalidArgument;
+    }
+
+    let compressor = Box::from_raw(compressor);
+    match compressor.compressor.finish() {
+        Ok(()) => IdnStatus::Ok,
+        Err(error) => {
+            set_last_error(error);
+            IdnStatus::Error
+        }
+    }
+}
+
+/// Releases the compressor without finishing the compression, so the output
+/// is not a valid IDN file. Does nothing if `compressor` is null.
+///
+/// # Safety
+/// `compressor` has to be null or a handle returned by [`idn_compressor_new()`]
+/// that has not been released yet.
+#[no_mangle]
+pub unsafe extern "C" fn idn_compressor_free(compressor: *mut IdnCompressorHandle) {
+    if !compressor.is_null() {
+        catch_panic((), || drop(Box::from_raw(compressor)));
+    }
+}
+
+/// IDN decompressor handle created with [`idn_decompressor_new()`].
+#[derive(Debug)]
+pub struct IdnDecompressorHandle {
+    decompressor: IdnDecompressor<CallbackReader>,
+    identifier: Vec<u8>,
+    acids: Vec<u8>,
+    quality_scores: Vec<u8>,
+}
+
+/// Creates a new IDN decompressor reading the data with given callback.
+///
+/// `model_dir` is the path to the directory with the models the file was
+/// compressed with, or null to use the default models. `thread_num` is the
+/// number of threads to decompress with; `0` means that all the work is done
+/// in the calling thread.
+///
+/// Returns null on error. The returned handle has to be released with
+/// [`idn_decompressor_free()`].
+///
+/// # Safety
+/// `model_dir` has to be null or a valid null-terminated string. `read` has to
+/// be safe to call from any thread with given `ctx` until the decompressor is
+/// released.
+#[no_mangle]
+pub unsafe extern "C" fn idn_decompressor_new(
+    read: Option<IdnReadFn>,
+    ctx: *mut c_void,
+    model_dir: *const c_char,
+    thread_num: usize,
+) -> *mut IdnDecompressorHandle {
+    catch_panic(ptr::null_mut(), || {
+        decompressor_new(read, ctx, model_dir, thread_num)
+    })
+}
+
+unsafe fn decompressor_new(
+    read: Option<IdnReadFn>,
+    ctx: *mut c_void,
+    model_dir: *const c_char,
+    thread_num: usize,
+) -> *mut IdnDecompressorHandle {
+    let read = match read {
+        Some(read) => read,
+        None => {
+            set_last_error("The read callback cannot be null");
+            return ptr::null_mut();
+        }
+    };
+    let model_provider = match model_provider(model_dir) {
+        Ok(model_provider) => model_provider,
+        Err(_) => return ptr::null_mut(),
+    };
+    let params = IdnDecompressorParams::builder()
+        .model_provider(model_provider)
+        .thread_num(thread_num)
+        .build();
+
+    let reader = CallbackReader { read, ctx };
+    let decompressor = IdnDecompressor::with_params(reader, params);
+    Box::into_raw(Box::new(IdnDecompressorHandle {
+        decompressor,
+        identifier: Vec::new(),
+        acids: Vec::new(),
+        quality_scores: Vec::new(),
+    }))
+}
+
+/// Decompresses the next read and stores it in `read`. Returns
+/// [`IdnStatus::End`] if there are no more reads.
+///
+/// The data pointed to by `read` is owned by the decompressor and is valid
+/// until the next call to this function or until the decompressor is released.
+///
+/// # Safety
+/// `decompressor` has to be a handle returned by [`idn_decompressor_new()`]
+/// that has not been released yet. `read` has to point to a writable
+/// [`IdnRead`].
+#[no_mangle]
+pub unsafe extern "C" fn idn_decompressor_next_read(
+    decompressor: *mut IdnDecompressorHandle,
+    read: *mut IdnRead,
+) -> IdnStatus {
+    catch_panic(IdnStatus::Panic, || {
+        decompressor_next_read(decompressor, read)
+    })
+}
+
+unsafe fn decompressor_next_read(
+    decompressor: *mut IdnDecompressorHandle,
+    read: *mut IdnRead,
+) -> IdnStatus {
+    let (decompressor, read) = match (decompressor.as_mut(), read.as_mut()) {
+        (Some(decompressor), Some(read)) => (decompressor, read),
+        _ => {
+            set_last_error("Null pointer passed as an argument");
+            return IdnStatus::InvalidArgument;
+        }
+    };
+
+    let sequence = match decompressor.decompressor.next_sequence() {
+        Ok(Some(sequence)) => sequence,
+        Ok(None) => return IdnStatus::End,
+        Err(error) => {
+            set_last_error(error);
+            return IdnStatus::Error;
+        }
+    };
+
+    decompressor.identifier.clear();
+    decompressor
+        .identifier
+        .extend_from_slice(sequence.identifier().str().as_bytes());
+    decompressor.acids.clear();
+    decompressor
+        .acids
+        .extend(sequence.acids().iter().copied().map(acid_to_byte));
+    decompressor.quality_scores.clear();
+    decompressor.quality_scores.extend(
+        sequence
+            .quality_scores()
+            .iter()
+            .map(|q_score| q_score.as_fastq_char() as u8),
+    );
+
+    *read = IdnRead {
+        identifier: decompressor.identifier.as_ptr().cast(),
+        identifier_len: decompressor.identifier.len(),
+        acids: decompressor.acids.as_ptr().cast(),
+        quality_scores: decompressor.quality_scores.as_ptr().cast(),
+        len: sequence.len(),
+    };
+    IdnStatus::Ok
+}
+
+/// Releases the decompressor. Does nothing if `decompressor` is null.
+///
+/// # Safety
+/// `decompressor` has to be null or a handle returned by
+/// [`idn_decompressor_new()`] that has not been released yet.
+#[no_mangle]
+pub unsafe extern "C" fn idn_decompressor_free(decompressor: *mut IdnDecompressorHandle) {
+    if !decompressor.is_null() {
+        catch_panic((), || drop(Box::from_raw(decompressor)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CStr;
+    use std::os::raw::{c_char, c_void};
+    use std::{ptr, slice};
+
+    use crate::{
+        catch_panic, idn_compressor_add_read, idn_compressor_finish, idn_compressor_new,
+        idn_decompressor_free, idn_decompressor_new, idn_decompressor_next_read, idn_last_error,
+        IdnRead, IdnStatus,
+    };
+
+    unsafe extern "C" fn write_to_vec(ctx: *mut c_void, buf: *const u8, len: usize) -> isize {
+        let vec = &mut *ctx.cast::<Vec<u8>>();
+        vec.extend_from_slice(slice::from_raw_parts(buf, len));
+        len as isize
+    }
+
+    unsafe extern "C" fn read_from_slice(ctx: *mut c_void, buf: *mut u8, len: usize) -> isize {
+        let data = &mut *ctx.cast::<&[u8]>();
+        let len = len.min(data.len());
+        ptr::copy_nonoverlapping(data.as_ptr(), buf, len);
+        *data = &data[len..];
+        len as isize
+    }
+
+    fn make_read(identifier: &str, acids: &str, quality_scores: &str) -> IdnRead {
+        IdnRead {
+            identifier: identifier.as_ptr().cast(),
+            identifier_len: identifier.len(),
+            acids: acids.as_ptr().cast(),
+            quality_scores: quality_scores.as_ptr().cast(),
+            len: acids.len(),
+        }
+    }
+
+    unsafe fn to_str<'a>(data: *const c_char, len: usize) -> &'a str {
+        std::str::from_utf8(slice::from_raw_parts(data.cast(), len)).unwrap()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut data: Vec<u8> = Vec::new();
+        unsafe {
+            let compressor = idn_compressor_new(
+                Some(write_to_vec),
+                (&mut data as *mut Vec<u8>).cast(),
+                ptr::null(),
+                1,
+                0,
+            );
+            assert!(!compressor.is_null());
+            let read = make_read("SEQ_1", "ACGTN", "!!5I~");
+            assert_eq!(idn_compressor_add_read(compressor, &read), IdnStatus::Ok);
+            let read = make_read("SEQ_2", "AC", "ZZ");
+            assert_eq!(idn_compressor_add_read(compressor, &read), IdnStatus::Ok);
+            assert_eq!(idn_compressor_finish(compressor), IdnStatus::Ok);
+        }
+
+        let mut slice = data.as_slice();
+        unsafe {
+            let decompressor = idn_decompressor_new(
+                Some(read_from_slice),
+                (&mut slice as *mut &[u8]).cast(),
+                ptr::null(),
+                0,
+            );
+            assert!(!decompressor.is_null());
+            let mut read = make_read("", "", "");
+
+            assert_eq!(
+                idn_decompressor_next_read(decompressor, &mut read),
+                IdnStatus::Ok
+            );
+            assert_eq!(to_str(read.identifier, read.identifier_len), "SEQ_1");
+            assert_eq!(to_str(read.acids, read.len), "ACGTN");
+            assert_eq!(to_str(read.quality_scores, read.len), "!!5I~");
+
+            assert_eq!(
+                idn_decompressor_next_read(decompressor, &mut read),
+                IdnStatus::Ok
+            );
+            assert_eq!(to_str(read.identifier, read.identifier_len), "SEQ_2");
+
+            assert_eq!(
+                idn_decompressor_next_read(decompressor, &mut read),
+                IdnStatus::End
+            );
+            idn_decompressor_free(decompressor);
+        }
+    }
+
+    #[test]
+    fn test_invalid_read() {
+        let mut data: Vec<u8> = Vec::new();
+        unsafe {
+            let compressor = idn_compressor_new(
+                Some(write_to_vec),
+                (&mut data as *mut Vec<u8>).cast(),
+                ptr::null(),
+                1,
+                0,
+            );
+            let read = make_read("SEQ", "AXA", "!!!");
+            assert_eq!(
+                idn_compressor_add_read(compressor, &read),
+                IdnStatus::InvalidArgument
+            );
+            assert_eq!(
+                CStr::from_ptr(idn_last_error()).to_str().unwrap(),
+                "Invalid acid: X"
+            );
+            assert_eq!(idn_compressor_finish(compressor), IdnStatus::Ok);
+        }
+    }
+
+    #[test]
+    fn test_invalid_quality() {
+        unsafe {
+            let compressor =
+                idn_compressor_new(Some(write_to_vec), ptr::null_mut(), ptr::null(), 0, 0);
+            assert!(compressor.is_null());
+            assert!(!idn_last_error().is_null());
+        }
+    }
+
+    #[test]
+    fn test_panic() {
+        let status = catch_panic(IdnStatus::Panic, || -> IdnStatus { panic!("Test panic") });
+
+        assert_eq!(status, IdnStatus::Panic);
+        assert_eq!(
+            unsafe { CStr::from_ptr(idn_last_error()) }
+                .to_str()
+                .unwrap(),
+            "Internal error: Test panic"
+        );
+    }
+}
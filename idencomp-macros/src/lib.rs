@@ -16,6 +16,21 @@ enum ModelItem {
         q_scores: Lit,
         position_bits: Lit,
         q_score_max: Lit,
+        perceptual: bool,
+    },
+    Long {
+        acids: Lit,
+        q_scores: Lit,
+        position_bits: Lit,
+        q_score_max: Lit,
+        perceptual: bool,
+    },
+    Cycle {
+        acids: Lit,
+        q_scores: Lit,
+        position_bits: Lit,
+        q_score_max: Lit,
+        perceptual: bool,
     },
 }
 
@@ -63,8 +78,32 @@ impl ModelItem {
     fn as_generator_constructor(&self) -> proc_macro2::TokenStream {
         let generator_type = self.as_generator_type();
 
+        if self.is_perceptual() {
+            quote! {
+                #generator_type::with_q_score_scale(length, QScoreScale::Perceptual)
+            }
+        } else {
+            quote! {
+                #generator_type::new(length)
+            }
+        }
+    }
+
+    fn is_perceptual(&self) -> bool {
+        match self {
+            ModelItem::Dummy | ModelItem::Generic { .. } => false,
+            ModelItem::Light { perceptual, .. }
+            | ModelItem::Long { perceptual, .. }
+            | ModelItem::Cycle { perceptual, .. } => *perceptual,
+        }
+    }
+
+    fn as_long_read_variant(&self) -> proc_macro2::TokenStream {
+        let enum_ident = self.enum_identifier();
+        let long_read = matches!(self, ModelItem::Long { .. });
+
         quote! {
-            #generator_type::new(length)
+            ContextSpecType::#enum_ident => #long_read
         }
     }
 
@@ -104,14 +143,33 @@ impl ModelItem {
                 q_scores,
                 position_bits,
                 q_score_max,
+                ..
             } => quote! {
                 LightContextSpecGenerator::<#acids, #q_scores, #position_bits, #q_score_max>
             },
+            ModelItem::Long {
+                acids,
+                q_scores,
+                position_bits,
+                q_score_max,
+                ..
+            } => quote! {
+                LongReadContextSpecGenerator::<#acids, #q_scores, #position_bits, #q_score_max>
+            },
+            ModelItem::Cycle {
+                acids,
+                q_scores,
+                position_bits,
+                q_score_max,
+                ..
+            } => quote! {
+                CycleContextSpecGenerator::<#acids, #q_scores, #position_bits, #q_score_max>
+            },
         }
     }
 
     fn as_enum_variant_doc(&self) -> String {
-        match self {
+        let value = match self {
             ModelItem::Dummy => "Dummy context (i.e. no context information).".to_owned(),
             ModelItem::Generic { acids, q_scores, position_bits } => format!(
                 "Generic context that includes {} prior acids, {} quality scores, and {} position bits.",
@@ -119,18 +177,41 @@ impl ModelItem {
                 q_scores.to_token_stream(),
                 position_bits.to_token_stream(),
             ),
-            ModelItem::Light { acids, q_scores, position_bits, q_score_max } => format!(
+            ModelItem::Light { acids, q_scores, position_bits, q_score_max, .. } => format!(
                 "Light context that includes {} prior acids, {} quality scores (max {}), and {} position bits.",
                 acids.to_token_stream(),
                 q_scores.to_token_stream(),
                 q_score_max.to_token_stream(),
                 position_bits.to_token_stream(),
             ),
+            ModelItem::Long { acids, q_scores, position_bits, q_score_max, .. } => format!(
+                "Long read context that includes {} prior acids, {} quality scores (max {}), and {} logarithmic position bits.",
+                acids.to_token_stream(),
+                q_scores.to_token_stream(),
+                q_score_max.to_token_stream(),
+                position_bits.to_token_stream(),
+            ),
+            ModelItem::Cycle { acids, q_scores, position_bits, q_score_max, .. } => format!(
+                "Per-cycle context that includes {} absolute position bits, {} prior acids, and {} quality scores (max {}).",
+                position_bits.to_token_stream(),
+                acids.to_token_stream(),
+                q_scores.to_token_stream(),
+                q_score_max.to_token_stream(),
+            ),
+        };
+
+        if self.is_perceptual() {
+            format!(
+                "{} The quality scores are mapped using the perceptual scale.",
+                value
+            )
+        } else {
+            value
         }
     }
 
     fn enum_identifier(&self) -> Ident {
-        match self {
+        let value = match self {
             ModelItem::Dummy => {
                 format_ident!("Dummy")
             }
@@ -151,6 +232,7 @@ impl ModelItem {
                 q_scores,
                 position_bits,
                 q_score_max,
+                ..
             } => {
                 format_ident!(
                     "Light{}Acids{}QScores{}PosBits{}MaxQScore",
@@ -160,11 +242,47 @@ impl ModelItem {
                     q_score_max.to_token_stream().to_string(),
                 )
             }
+            ModelItem::Long {
+                acids,
+                q_scores,
+                position_bits,
+                q_score_max,
+                ..
+            } => {
+                format_ident!(
+                    "Long{}Acids{}QScores{}PosBits{}MaxQScore",
+                    acids.to_token_stream().to_string(),
+                    q_scores.to_token_stream().to_string(),
+                    position_bits.to_token_stream().to_string(),
+                    q_score_max.to_token_stream().to_string(),
+                )
+            }
+            ModelItem::Cycle {
+                acids,
+                q_scores,
+                position_bits,
+                q_score_max,
+                ..
+            } => {
+                format_ident!(
+                    "Cycle{}Acids{}QScores{}PosBits{}MaxQScore",
+                    acids.to_token_stream().to_string(),
+                    q_scores.to_token_stream().to_string(),
+                    position_bits.to_token_stream().to_string(),
+                    q_score_max.to_token_stream().to_string(),
+                )
+            }
+        };
+
+        if self.is_perceptual() {
+            format_ident!("{}Perceptual", value)
+        } else {
+            value
         }
     }
 
     fn serde_identifier(&self) -> String {
-        match self {
+        let value = match self {
             ModelItem::Dummy => "dummy".to_string(),
             ModelItem::Generic {
                 acids,
@@ -183,6 +301,7 @@ impl ModelItem {
                 q_scores,
                 position_bits,
                 q_score_max,
+                ..
             } => {
                 format!(
                     "light_ao{}_qo{}_pb{}_qm{}",
@@ -192,6 +311,42 @@ impl ModelItem {
                     q_score_max.to_token_stream(),
                 )
             }
+            ModelItem::Long {
+                acids,
+                q_scores,
+                position_bits,
+                q_score_max,
+                ..
+            } => {
+                format!(
+                    "long_ao{}_qo{}_pb{}_qm{}",
+                    acids.to_token_stream(),
+                    q_scores.to_token_stream(),
+                    position_bits.to_token_stream(),
+                    q_score_max.to_token_stream(),
+                )
+            }
+            ModelItem::Cycle {
+                acids,
+                q_scores,
+                position_bits,
+                q_score_max,
+                ..
+            } => {
+                format!(
+                    "cycle_ao{}_qo{}_pb{}_qm{}",
+                    acids.to_token_stream(),
+                    q_scores.to_token_stream(),
+                    position_bits.to_token_stream(),
+                    q_score_max.to_token_stream(),
+                )
+            }
+        };
+
+        if self.is_perceptual() {
+            format!("{}_perceptual", value)
+        } else {
+            value
         }
     }
 }
@@ -218,7 +373,7 @@ impl Parse for ModelItem {
                 q_scores,
                 position_bits,
             })
-        } else if ident == "light" {
+        } else if ident == "light" || ident == "long" || ident == "cycle" {
             let content;
             parenthesized!(content in input);
             let acids = content.parse::<Lit>()?;
@@ -228,17 +383,46 @@ impl Parse for ModelItem {
             let position_bits = content.parse::<Lit>()?;
             content.parse::<Token![,]>()?;
             let q_score_max = content.parse::<Lit>()?;
-
-            Ok(Self::Light {
-                acids,
-                q_scores,
-                position_bits,
-                q_score_max,
-            })
+            let perceptual = if content.is_empty() {
+                false
+            } else {
+                content.parse::<Token![,]>()?;
+                let scale: Ident = content.parse()?;
+                if scale != "perceptual" {
+                    return Err(syn::Error::new(scale.span(), "expected `perceptual`"));
+                }
+                true
+            };
+
+            if ident == "light" {
+                Ok(Self::Light {
+                    acids,
+                    q_scores,
+                    position_bits,
+                    q_score_max,
+                    perceptual,
+                })
+            } else if ident == "long" {
+                Ok(Self::Long {
+                    acids,
+                    q_scores,
+                    position_bits,
+                    q_score_max,
+                    perceptual,
+                })
+            } else {
+                Ok(Self::Cycle {
+                    acids,
+                    q_scores,
+                    position_bits,
+                    q_score_max,
+                    perceptual,
+                })
+            }
         } else {
             Err(syn::Error::new(
                 ident.span(),
-                "expected `dummy`, `generic`, or `light`",
+                "expected `dummy`, `generic`, `light`, `long`, or `cycle`",
             ))
         }
     }
@@ -280,6 +464,11 @@ pub fn model(input: TokenStream) -> TokenStream {
         .iter()
         .map(|x| x.as_spec_num_variant())
         .collect();
+    let long_read_variants: Vec<proc_macro2::TokenStream> = model
+        .items
+        .iter()
+        .map(|x| x.as_long_read_variant())
+        .collect();
 
     let output = quote! {
         #[doc = "An exact type of a context specifier, which means how it is generated, using acids, quality scores, and position data."]
@@ -317,6 +506,14 @@ pub fn model(input: TokenStream) -> TokenStream {
                     #(#spec_num_variants)*
                 }
             }
+
+            #[doc = "Returns whether this context spec type is meant for long reads, i.e. it is one of the `long(...)` types with logarithmic positions."]
+            #[must_use]
+            pub fn is_long_read(&self) -> bool {
+                match self {
+                    #(#long_read_variants,)*
+                }
+            }
         }
 
         impl std::fmt::Display for ContextSpecType {
@@ -10,6 +10,7 @@ fn compress_1m(c: &mut Criterion) {
                 IdnCompressorParams::builder()
                     .model_provider(SIMPLE_MODEL_PROVIDER.clone())
                     .build()
+                    .unwrap()
             },
             |params| {
                 let mut data = Vec::new();
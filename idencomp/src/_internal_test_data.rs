@@ -232,6 +232,8 @@ lazy_static! {
     ];
     pub static ref TEST_ACID_MODEL_PREFER_A: Model = create_acid_model_prefer_a();
     pub static ref TEST_ACID_MODEL_PREFER_C: Model = create_acid_model_prefer_c();
+    pub static ref TEST_Q_SCORE_MODEL_PREFER_0: Model = create_q_score_model_prefer(0);
+    pub static ref TEST_Q_SCORE_MODEL_PREFER_50: Model = create_q_score_model_prefer(50);
     pub static ref SIMPLE_ACID_MODEL: Model = create_simple_acid_model();
     pub static ref SIMPLE_Q_SCORE_MODEL: Model = create_simple_qscore_model();
     pub static ref SIMPLE_MODEL_PROVIDER: ModelProvider = ModelProvider::new(vec![
@@ -281,6 +283,17 @@ fn create_acid_model_prefer_c() -> Model {
     Model::with_model_and_spec_type(ModelType::Acids, ContextSpecType::Dummy, contexts)
 }
 
+fn create_q_score_model_prefer(q_score: usize) -> Model {
+    let symbols = (0..FASTQ_Q_END).map(|i| if i == q_score { 0.9 } else { 0.001 });
+    let ctx1 = Context::new_from(1.0, symbols.collect::<Vec<_>>());
+    let contexts = [ComplexContext::with_single_spec(
+        GenericContextSpec::without_pos([], []).into(),
+        ctx1,
+    )];
+
+    Model::with_model_and_spec_type(ModelType::QualityScores, ContextSpecType::Dummy, contexts)
+}
+
 fn create_simple_qscore_model() -> Model {
     let mut contexts = Vec::new();
 
@@ -1,8 +1,12 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::mem::size_of;
+
 use rans::byte_decoder::{ByteRansDecSymbol, ByteRansDecoderMulti};
 use rans::byte_encoder::{ByteRansEncSymbol, ByteRansEncoderMulti};
-#[cfg(test)]
-use rans::RansDecoder;
-use rans::{RansDecSymbol, RansDecoderMulti, RansEncSymbol, RansEncoder, RansEncoderMulti};
+use rans::{
+    RansDecSymbol, RansDecoder, RansDecoderMulti, RansEncSymbol, RansEncoder, RansEncoderMulti,
+};
 
 use crate::context::Context;
 
@@ -11,12 +15,21 @@ type EncoderSymbol = ByteRansEncSymbol;
 type Decoder<'a, const N: usize> = ByteRansDecoderMulti<'a, N>;
 type DecoderSymbol = ByteRansDecSymbol;
 
+/// A [`Context`] prepared to be used for encoding the symbols with
+/// [`RansCompressor`].
 #[derive(Debug, Clone)]
-pub struct RansEncContext<const SYMBOLS_NUM: usize> {
-    symbols: [EncoderSymbol; SYMBOLS_NUM],
+pub struct RansEncContext {
+    symbols: Box<[EncoderSymbol]>,
 }
 
-impl<const SYMBOLS_NUM: usize> RansEncContext<SYMBOLS_NUM> {
+impl RansEncContext {
+    /// Creates a new `RansEncContext` from given [`Context`], with the symbol
+    /// probabilities quantized to `scale_bits` bits. The same `scale_bits`
+    /// value has to be used for the matching [`RansDecContext`].
+    ///
+    /// # Panics
+    /// This function panics if `scale_bits` is greater than 16, or too low to
+    /// give each of the symbols of the context a nonzero frequency.
     #[must_use]
     pub fn from_context(context: &Context, scale_bits: u8) -> Self {
         let cum_freqs = context.as_integer_cum_freqs(scale_bits);
@@ -27,84 +40,349 @@ impl<const SYMBOLS_NUM: usize> RansEncContext<SYMBOLS_NUM> {
             .iter()
             .zip(freqs.iter())
             .map(|(&cum_freq, &freq)| EncoderSymbol::new(cum_freq, freq, scale_bits as u32))
-            .collect::<Vec<EncoderSymbol>>()
-            .try_into()
-            .unwrap();
+            .collect();
 
         Self { symbols }
     }
+
+    /// Returns the number of symbols of this context.
+    #[inline]
+    #[must_use]
+    pub fn symbol_num(&self) -> usize {
+        self.symbols.len()
+    }
+
+    /// Returns the approximate number of bytes a context with `symbol_num`
+    /// symbols takes.
+    #[must_use]
+    pub const fn estimated_memory(symbol_num: usize) -> usize {
+        size_of::<Self>() + symbol_num * size_of::<EncoderSymbol>()
+    }
 }
 
+/// rANS entropy coder, encoding the symbols into `N` interleaved channels.
+///
+/// Each call to [`Self::try_put_all()`] encodes one symbol into each of the
+/// channels, each with its own context. The symbols are decoded with
+/// [`RansDecompressor`] with the same number of channels, **in the reverse
+/// order** they have been encoded in. The 1- and 2-channel versions
+/// additionally provide the `try_put()` shorthand.
+///
+/// # Examples
+/// ```
+/// use idencomp::compressor::{RansCompressor, RansDecContext, RansDecompressor, RansEncContext};
+/// use idencomp::context::Context;
+///
+/// let context = Context::new_from(1.0, [0.5, 0.25, 0.125, 0.125]);
+/// let enc_context = RansEncContext::from_context(&context, 10);
+/// let dec_context = RansDecContext::from_context(&context, 10);
+///
+/// let mut compressor = RansCompressor::<3>::with_max_symbols(2);
+/// compressor.try_put_all([(&enc_context, 0), (&enc_context, 1), (&enc_context, 2)])?;
+/// compressor.try_put_all([(&enc_context, 3), (&enc_context, 3), (&enc_context, 0)])?;
+/// // The buffer only holds 2 symbols in each channel
+/// assert!(compressor
+///     .try_put_all([(&enc_context, 0), (&enc_context, 0), (&enc_context, 0)])
+///     .is_err());
+/// compressor.flush();
+/// let mut data = compressor.data().to_vec();
+///
+/// let mut decompressor = RansDecompressor::<3>::new(&mut data)?;
+/// let contexts = [&dec_context, &dec_context, &dec_context];
+/// assert_eq!(decompressor.get_all(contexts), [3, 3, 0]);
+/// assert_eq!(decompressor.get_all(contexts), [0, 1, 2]);
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
 #[derive(Debug)]
 pub struct RansCompressor<const N: usize> {
     encoder: Encoder<N>,
+    max_symbols: usize,
+    symbols: usize,
 }
 
-const MAX_BLOCK_SIZE: usize = 32 * 1024 * 1024; // 32MiB
+// SAFETY: the encoder is `!Send` only because it keeps a raw pointer to the
+// current position in its output buffer. The pointer always points into the
+// heap allocation owned by the encoder itself, which does not move when the
+// encoder is moved, and it is never shared with anything else, so the encoder
+// can be safely moved to (and reused on) another thread.
+unsafe impl<const N: usize> Send for RansCompressor<N> {}
+
+/// The maximum number of bytes a single symbol can be encoded with in a single
+/// channel, given that the scale bits are not greater than 16.
+const MAX_SYMBOL_BYTES: usize = 2;
+/// The number of bytes a single channel takes when flushed.
+const FLUSH_BYTES: usize = 4;
 
 impl<const N: usize> RansCompressor<N> {
+    /// Creates a new `RansCompressor` with the buffer large enough to hold
+    /// `max_symbols` symbols in each channel.
     #[must_use]
-    pub fn new() -> Self {
+    pub fn with_max_symbols(max_symbols: usize) -> Self {
         Self {
-            encoder: Encoder::new(MAX_BLOCK_SIZE),
+            encoder: Encoder::new(Self::max_compressed_len(max_symbols)),
+            max_symbols,
+            symbols: 0,
         }
     }
 
+    /// Resets the compressor and makes sure its buffer is large enough to
+    /// hold `symbols` symbols in each channel, growing it if needed.
+    pub fn reset_for(&mut self, symbols: usize) {
+        if symbols > self.max_symbols {
+            let max_symbols = symbols.max(self.max_symbols * 2);
+            *self = Self::with_max_symbols(max_symbols);
+        } else {
+            self.reset();
+        }
+    }
+
+    /// Returns the maximum number of bytes `symbols` symbols in each channel
+    /// can be compressed to.
+    #[must_use]
+    pub const fn max_compressed_len(symbols: usize) -> usize {
+        N * (symbols * MAX_SYMBOL_BYTES + FLUSH_BYTES)
+    }
+
+    /// Resets the compressor, discarding all the data encoded so far.
     #[inline]
     pub fn reset(&mut self) {
         self.encoder.reset();
+        self.symbols = 0;
     }
 
+    /// Flushes the state of all the channels. This has to be called after
+    /// the last symbol has been encoded, before reading the [`Self::data()`].
     #[inline]
     pub fn flush(&mut self) {
         self.encoder.flush_all();
     }
 
+    /// Returns the encoded data.
     #[inline]
     #[must_use]
     pub fn data(&self) -> &[u8] {
         self.encoder.data()
     }
+
+    /// Encodes one symbol into each of the channels. Each of the elements of
+    /// `symbols` consists of the context and the index of the symbol in that
+    /// context.
+    ///
+    /// # Errors
+    /// Returns [`RansBufferFullError`] if the buffer is too small to hold
+    /// another symbol (see [`Self::reset_for()`]). Nothing is encoded then.
+    ///
+    /// # Panics
+    /// This function panics if any of the symbol indices is out of range of
+    /// its context.
+    #[inline]
+    pub fn try_put_all(
+        &mut self,
+        symbols: [(&RansEncContext, usize); N],
+    ) -> Result<(), RansBufferFullError> {
+        for (context, symbol_index) in symbols {
+            assert!(symbol_index < context.symbols.len());
+        }
+        self.try_reserve_symbol()?;
+
+        for (channel, (context, symbol_index)) in symbols.into_iter().enumerate() {
+            self.encoder.put_at(channel, &context.symbols[symbol_index]);
+        }
+
+        Ok(())
+    }
+
+    /// Reserves the space for one more symbol in each channel. The encoder
+    /// does not check the bounds of its buffer in release builds, so writing
+    /// past it would be undefined behavior.
+    #[inline]
+    fn try_reserve_symbol(&mut self) -> Result<(), RansBufferFullError> {
+        if self.symbols >= self.max_symbols {
+            return Err(RansBufferFullError {
+                max_symbols: self.max_symbols,
+            });
+        }
+        self.symbols += 1;
+
+        Ok(())
+    }
+
+    /// Reserves the space for one more symbol in each channel in the internal
+    /// callers, which always size the buffer with [`Self::reset_for()`]
+    /// beforehand.
+    #[inline]
+    fn reserve_symbol(&mut self) {
+        self.try_reserve_symbol()
+            .expect("rANS buffer too small; use `reset_for()` to grow it");
+    }
+}
+
+/// Error returned by [`RansCompressor`] when its buffer is too small to hold
+/// another symbol.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct RansBufferFullError {
+    max_symbols: usize,
 }
 
+impl RansBufferFullError {
+    /// Returns the number of symbols in each channel the buffer can hold.
+    #[must_use]
+    pub fn max_symbols(&self) -> usize {
+        self.max_symbols
+    }
+}
+
+impl Display for RansBufferFullError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "rANS buffer too small for more than {} symbols",
+            self.max_symbols
+        )
+    }
+}
+
+impl Error for RansBufferFullError {}
+
 impl RansCompressor<1> {
+    /// Encodes the symbol with given index in `context`.
+    ///
+    /// # Errors
+    /// Returns [`RansBufferFullError`] if the buffer is too small to hold
+    /// another symbol (see [`Self::reset_for()`]).
+    ///
+    /// # Panics
+    /// This function panics if the symbol index is out of range.
     #[inline]
-    pub fn put<const SYMBOLS_NUM: usize>(
+    pub fn try_put(
         &mut self,
-        context: &RansEncContext<SYMBOLS_NUM>,
+        context: &RansEncContext,
         symbol_index: usize,
-    ) {
-        assert!(symbol_index < SYMBOLS_NUM);
+    ) -> Result<(), RansBufferFullError> {
+        assert!(symbol_index < context.symbols.len());
+        self.try_reserve_symbol()?;
+
+        self.encoder.put(&context.symbols[symbol_index]);
+        Ok(())
+    }
+
+    /// Encodes the symbol with given index in `context`, in the buffer sized
+    /// with [`Self::reset_for()`].
+    #[inline]
+    pub(crate) fn put(&mut self, context: &RansEncContext, symbol_index: usize) {
+        assert!(symbol_index < context.symbols.len());
+        self.reserve_symbol();
 
         self.encoder.put(&context.symbols[symbol_index]);
     }
 }
 
 impl RansCompressor<2> {
+    /// Encodes a symbol into each of the two channels. This is equivalent to
+    /// [`Self::try_put_all()`].
+    ///
+    /// # Errors
+    /// Returns [`RansBufferFullError`] if the buffer is too small to hold
+    /// another symbol (see [`Self::reset_for()`]).
+    ///
+    /// # Panics
+    /// This function panics if any of the symbol indices is out of range.
+    #[inline]
+    pub fn try_put(
+        &mut self,
+        context_1: &RansEncContext,
+        symbol_index_1: usize,
+        context_2: &RansEncContext,
+        symbol_index_2: usize,
+    ) -> Result<(), RansBufferFullError> {
+        self.try_put_all([(context_1, symbol_index_1), (context_2, symbol_index_2)])
+    }
+
+    /// Encodes a symbol into the first channel only, leaving the second one
+    /// intact. The symbol has to be decoded with
+    /// [`RansDecompressor::get_conditional()`].
+    ///
+    /// # Errors
+    /// Returns [`RansBufferFullError`] if the buffer is too small to hold
+    /// another symbol (see [`Self::reset_for()`]).
+    ///
+    /// # Panics
+    /// This function panics if the symbol index is out of range.
     #[inline]
-    pub fn put<const SYMBOLS_NUM_1: usize, const SYMBOLS_NUM_2: usize>(
+    pub fn try_put_first(
         &mut self,
-        context_1: &RansEncContext<SYMBOLS_NUM_1>,
+        context: &RansEncContext,
+        symbol_index: usize,
+    ) -> Result<(), RansBufferFullError> {
+        assert!(symbol_index < context.symbols.len());
+        self.try_reserve_symbol()?;
+
+        self.encoder.put_at(0, &context.symbols[symbol_index]);
+        Ok(())
+    }
+
+    /// Encodes a symbol into each of the two channels, in the buffer sized
+    /// with [`Self::reset_for()`]. The symbol indices are only checked in
+    /// debug builds.
+    #[inline]
+    pub(crate) fn put(
+        &mut self,
+        context_1: &RansEncContext,
         symbol_index_1: usize,
-        context_2: &RansEncContext<SYMBOLS_NUM_2>,
+        context_2: &RansEncContext,
         symbol_index_2: usize,
     ) {
-        debug_assert!(symbol_index_1 < SYMBOLS_NUM_1);
-        debug_assert!(symbol_index_2 < SYMBOLS_NUM_2);
+        debug_assert!(symbol_index_1 < context_1.symbols.len());
+        debug_assert!(symbol_index_2 < context_2.symbols.len());
+        self.reserve_symbol();
 
         self.encoder.put_at(0, &context_1.symbols[symbol_index_1]);
         self.encoder.put_at(1, &context_2.symbols[symbol_index_2]);
     }
+
+    /// Encodes a symbol into the first channel only, in the buffer sized with
+    /// [`Self::reset_for()`]. The symbol index is only checked in debug
+    /// builds.
+    #[inline]
+    pub(crate) fn put_first(&mut self, context: &RansEncContext, symbol_index: usize) {
+        debug_assert!(symbol_index < context.symbols.len());
+        self.reserve_symbol();
+
+        self.encoder.put_at(0, &context.symbols[symbol_index]);
+    }
 }
 
+/// The number of the most significant bits of a cumulative frequency used to
+/// find the symbol in the lookup table of `RansDecContext`.
+const LOOKUP_BITS: u32 = 8;
+
+/// A [`Context`] prepared to be used for decoding the symbols with
+/// [`RansDecompressor`].
 #[derive(Debug, Clone)]
-pub struct RansDecContext<const SYMBOLS_NUM: usize> {
-    symbols: [DecoderSymbol; SYMBOLS_NUM],
-    freq_to_symbol: Vec<usize>,
+pub struct RansDecContext {
+    symbols: Box<[DecoderSymbol]>,
+    cum_freqs: Box<[u32]>,
+    /// For each of the equally sized cumulative frequency ranges, the index of
+    /// the symbol the range starts in. The exact symbol is then found by
+    /// scanning `cum_freqs` from there, which is typically very short. This is
+    /// much smaller than a table with an entry for every cumulative
+    /// frequency, which matters for models with thousands of contexts.
+    lookup: [u8; 1 << LOOKUP_BITS],
+    lookup_shift: u32,
     scale_bits: u32,
 }
 
-impl<const SYMBOLS_NUM: usize> RansDecContext<SYMBOLS_NUM> {
+impl RansDecContext {
+    /// Creates a new `RansDecContext` from given [`Context`], with the symbol
+    /// probabilities quantized to `scale_bits` bits. Both the context and
+    /// `scale_bits` have to be the same as the ones used for the
+    /// [`RansEncContext`] the data has been encoded with.
+    ///
+    /// # Panics
+    /// This function panics if `scale_bits` is greater than 16, too low to
+    /// give each of the symbols a nonzero frequency, or the context has more
+    /// than 256 symbols.
     #[must_use]
     pub fn from_context(context: &Context, scale_bits: u8) -> Self {
         let total_freq = 1 << scale_bits;
@@ -117,50 +395,163 @@ impl<const SYMBOLS_NUM: usize> RansDecContext<SYMBOLS_NUM> {
             .iter()
             .zip(freqs.iter())
             .map(|(&cum_freq, &freq)| DecoderSymbol::new(cum_freq, freq))
-            .collect::<Vec<DecoderSymbol>>()
-            .try_into()
-            .unwrap();
-
-        let mut freq_to_symbol = Vec::with_capacity(total_freq as usize);
-        for i in 0..cum_freqs.len() - 1 {
-            freq_to_symbol.resize(cum_freqs[i + 1] as usize, i);
+            .collect();
+
+        assert!(cum_freqs.len() <= u8::MAX as usize + 1);
+        let cum_freqs = cum_freqs.into_boxed_slice();
+
+        let lookup_shift = (scale_bits as u32).saturating_sub(LOOKUP_BITS);
+        let mut lookup = [0; 1 << LOOKUP_BITS];
+        let mut symbol_index = 0;
+        for (i, entry) in lookup.iter_mut().enumerate() {
+            let range_start = (i as u32) << lookup_shift;
+            if range_start >= total_freq {
+                break;
+            }
+            symbol_index = Self::find_symbol_index(&cum_freqs, symbol_index, range_start);
+            *entry = symbol_index as u8;
         }
-        freq_to_symbol.resize(total_freq as usize, cum_freqs.len() - 1);
 
         Self {
             symbols,
-            freq_to_symbol,
+            cum_freqs,
+            lookup,
+            lookup_shift,
             scale_bits: scale_bits as u32,
         }
     }
 
+    /// Returns the approximate number of bytes a context with `symbol_num`
+    /// symbols takes.
+    #[must_use]
+    pub const fn estimated_memory(symbol_num: usize) -> usize {
+        size_of::<Self>() + symbol_num * (size_of::<DecoderSymbol>() + size_of::<u32>())
+    }
+
+    /// Returns the number of symbols of this context.
+    #[inline]
+    #[must_use]
+    pub fn symbol_num(&self) -> usize {
+        self.symbols.len()
+    }
+
+    /// Returns the index of the symbol given cumulative frequency belongs to.
+    #[inline]
     #[must_use]
     pub fn cum_freq_to_symbol_index(&self, cum_freq: u32) -> usize {
-        self.freq_to_symbol[cum_freq as usize]
+        let start = self.lookup[(cum_freq >> self.lookup_shift) as usize] as usize;
+        Self::find_symbol_index(&self.cum_freqs, start, cum_freq)
+    }
+
+    /// Returns the index of the symbol `cum_freq` belongs to, starting the
+    /// search at the symbol with index `start`.
+    #[inline]
+    fn find_symbol_index(cum_freqs: &[u32], start: usize, cum_freq: u32) -> usize {
+        let mut index = start;
+        while index + 1 < cum_freqs.len() && cum_freqs[index + 1] <= cum_freq {
+            index += 1;
+        }
+
+        index
     }
 }
 
+/// Error returned by [`RansDecompressor::new()`] when the data is too short to
+/// contain the final states of all the channels.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct RansDataTooShortError {
+    len: usize,
+    min_len: usize,
+}
+
+impl RansDataTooShortError {
+    /// Returns the length of the data.
+    #[must_use]
+    pub fn data_len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns the minimum length of the data for given number of channels.
+    #[must_use]
+    pub fn min_len(&self) -> usize {
+        self.min_len
+    }
+}
+
+impl Display for RansDataTooShortError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "rANS data too short (length: {}, minimum: {})",
+            self.len, self.min_len
+        )
+    }
+}
+
+impl Error for RansDataTooShortError {}
+
+/// rANS entropy decoder, decoding the data encoded with [`RansCompressor`]
+/// with the same number of channels.
+///
+/// The symbols are decoded in the reverse order they have been encoded in, so
+/// the contexts have to be passed in that order as well. See
+/// [`RansCompressor`] for an example.
 pub struct RansDecompressor<'a, const N: usize> {
     decoder: Decoder<'a, N>,
 }
 
 impl<'a, const N: usize> RansDecompressor<'a, N> {
-    #[must_use]
-    pub fn new(data: &'a mut [u8]) -> Self {
-        Self {
+    /// The minimum length of the data: the final states of all the channels.
+    pub const MIN_DATA_LEN: usize = N * FLUSH_BYTES;
+
+    /// Creates a new `RansDecompressor` decoding given data. The data is
+    /// modified in place while decoding.
+    ///
+    /// # Errors
+    /// Returns an error if the data is shorter than [`Self::MIN_DATA_LEN`],
+    /// i.e. it cannot contain the final states of all the channels.
+    pub fn new(data: &'a mut [u8]) -> Result<Self, RansDataTooShortError> {
+        if data.len() < Self::MIN_DATA_LEN {
+            return Err(RansDataTooShortError {
+                len: data.len(),
+                min_len: Self::MIN_DATA_LEN,
+            });
+        }
+
+        Ok(Self {
             decoder: Decoder::new(data),
+        })
+    }
+
+    /// Decodes one symbol from each of the channels, returning their indices
+    /// in the respective `contexts`. The contexts are given in the same order
+    /// as in the matching [`RansCompressor::put_all()`] call.
+    #[inline]
+    #[must_use]
+    pub fn get_all(&mut self, contexts: [&RansDecContext; N]) -> [usize; N] {
+        // The channels are stored in the reverse order, so the last channel
+        // encoded is the first one decoded
+        let mut symbol_indices = [0; N];
+        for (channel, &context) in contexts.iter().rev().enumerate() {
+            let cum_freq = self.decoder.get_at(channel, context.scale_bits);
+            symbol_indices[N - 1 - channel] = context.cum_freq_to_symbol_index(cum_freq);
         }
+        for (channel, &context) in contexts.iter().rev().enumerate() {
+            let symbol = &context.symbols[symbol_indices[N - 1 - channel]];
+            self.decoder
+                .advance_step_at(channel, symbol, context.scale_bits);
+        }
+        self.decoder.renorm_all();
+
+        symbol_indices
     }
 }
 
-#[cfg(test)]
 impl<'a> RansDecompressor<'a, 1> {
+    /// Decodes a symbol, returning its index in `context`.
     #[inline]
     #[must_use]
-    pub fn get<const SYMBOLS_NUM: usize>(
-        &mut self,
-        context: &RansDecContext<SYMBOLS_NUM>,
-    ) -> usize {
+    pub fn get(&mut self, context: &RansDecContext) -> usize {
         let cum_freq = self.decoder.get(context.scale_bits);
         let symbol_index = context.cum_freq_to_symbol_index(cum_freq);
         self.decoder
@@ -171,12 +562,14 @@ impl<'a> RansDecompressor<'a, 1> {
 }
 
 impl<'a> RansDecompressor<'a, 2> {
+    /// Decodes a symbol from each of the two channels. This is equivalent to
+    /// [`Self::get_all()`].
     #[inline]
     #[must_use]
-    pub fn get<const SYMBOLS_NUM_1: usize, const SYMBOLS_NUM_2: usize>(
+    pub fn get(
         &mut self,
-        context_1: &RansDecContext<SYMBOLS_NUM_1>,
-        context_2: &RansDecContext<SYMBOLS_NUM_2>,
+        context_1: &RansDecContext,
+        context_2: &RansDecContext,
     ) -> (usize, usize) {
         let cum_freq_2 = self.decoder.get_at(0, context_2.scale_bits);
         let cum_freq_1 = self.decoder.get_at(1, context_1.scale_bits);
@@ -190,6 +583,41 @@ impl<'a> RansDecompressor<'a, 2> {
 
         (symbol_index_1, symbol_index_2)
     }
+
+    /// Decodes a symbol from the first channel and then, unless `first_only`
+    /// returns `true` for its index, from the second one. This decodes the
+    /// symbols encoded either with [`RansCompressor::put()`] or with
+    /// [`RansCompressor::put_first()`], depending on the first symbol.
+    #[inline]
+    #[must_use]
+    pub fn get_conditional<F: FnOnce(usize) -> bool>(
+        &mut self,
+        context_1: &RansDecContext,
+        context_2: &RansDecContext,
+        first_only: F,
+    ) -> (usize, Option<usize>) {
+        // The first channel encoded is the last one decoded
+        let cum_freq_1 = self.decoder.get_at(1, context_1.scale_bits);
+        let symbol_index_1 = context_1.cum_freq_to_symbol_index(cum_freq_1);
+        self.decoder
+            .advance_step_at(1, &context_1.symbols[symbol_index_1], context_1.scale_bits);
+
+        let symbol_index_2 = if first_only(symbol_index_1) {
+            None
+        } else {
+            let cum_freq_2 = self.decoder.get_at(0, context_2.scale_bits);
+            let symbol_index_2 = context_2.cum_freq_to_symbol_index(cum_freq_2);
+            self.decoder.advance_step_at(
+                0,
+                &context_2.symbols[symbol_index_2],
+                context_2.scale_bits,
+            );
+            Some(symbol_index_2)
+        };
+        self.decoder.renorm_all();
+
+        (symbol_index_1, symbol_index_2)
+    }
 }
 
 #[cfg(test)]
@@ -198,7 +626,9 @@ mod tests {
     use rand_xoshiro::Xoshiro256PlusPlus;
 
     use crate::_internal_test_data::CONTEXTS_10;
-    use crate::compressor::{RansCompressor, RansDecContext, RansDecompressor, RansEncContext};
+    use crate::compressor::{
+        RansBufferFullError, RansCompressor, RansDecContext, RansDecompressor, RansEncContext,
+    };
     use crate::context::Context;
 
     #[test]
@@ -208,7 +638,7 @@ mod tests {
             [0.05, 0.10, 0.125, 0.125, 0.30, 0.03, 0.07, 0.05, 0.12, 0.03],
         );
 
-        let _ctx = RansEncContext::<10>::from_context(&context, 10);
+        let _ctx = RansEncContext::from_context(&context, 10);
     }
 
     #[test]
@@ -218,7 +648,7 @@ mod tests {
             [0.05, 0.10, 0.125, 0.125, 0.30, 0.03, 0.07, 0.05, 0.12, 0.03],
         );
 
-        let _ctx = RansDecContext::<10>::from_context(&context, 10);
+        let _ctx = RansDecContext::from_context(&context, 10);
     }
 
     #[test]
@@ -226,9 +656,9 @@ mod tests {
         const SCALE_BITS: u8 = 16;
 
         let ctx1 = Context::new_from(1.0, [0.001, 0.001, 0.997, 0.001]);
-        let enc_ctx1 = RansEncContext::<4>::from_context(&ctx1, SCALE_BITS);
+        let enc_ctx1 = RansEncContext::from_context(&ctx1, SCALE_BITS);
 
-        let mut compressor = RansCompressor::<1>::new();
+        let mut compressor = RansCompressor::<1>::with_max_symbols(500);
         for _ in 0..500 {
             compressor.put(&enc_ctx1, 2);
         }
@@ -239,13 +669,34 @@ mod tests {
         assert_eq!(compressed.len(), 4);
     }
 
+    #[test]
+    fn test_cum_freq_to_symbol_index() {
+        for scale_bits in [4, 8, 10, 14, 16] {
+            let total_freq = 1 << scale_bits;
+            let context = Context::new_from(
+                1.0,
+                [0.05, 0.10, 0.0, 0.125, 0.30, 0.0, 0.095, 0.05, 0.25, 0.03],
+            );
+            let dec_ctx = RansDecContext::from_context(&context, scale_bits);
+
+            let cum_freqs = context.as_integer_cum_freqs(scale_bits);
+            for cum_freq in 0..total_freq {
+                let expected = cum_freqs
+                    .iter()
+                    .rposition(|&start| start <= cum_freq)
+                    .unwrap();
+                assert_eq!(dec_ctx.cum_freq_to_symbol_index(cum_freq), expected);
+            }
+        }
+    }
+
     #[test]
     fn round_trip_single_ctx() {
         let ctx = Context::new_from(1.0, [0.25, 0.25, 0.25, 0.25]);
 
         let data = vec![(&ctx, 0), (&ctx, 1), (&ctx, 2), (&ctx, 3)];
 
-        test_round_trip::<4>(data);
+        test_round_trip(data);
     }
 
     #[test]
@@ -257,7 +708,79 @@ mod tests {
 
         let data = vec![(&ctx1, 0), (&ctx2, 1), (&ctx3, 2), (&ctx4, 3)];
 
-        test_round_trip::<4>(data);
+        test_round_trip(data);
+    }
+
+    #[test]
+    fn test_max_compressed_len() {
+        const SCALE_BITS: u8 = 16;
+
+        let ctx = Context::new_from(1.0, [0.001, 0.001, 0.997, 0.001]);
+        let enc_ctx = RansEncContext::from_context(&ctx, SCALE_BITS);
+
+        let mut compressor = RansCompressor::<1>::with_max_symbols(1000);
+        for _ in 0..1000 {
+            compressor.put(&enc_ctx, 0);
+        }
+        compressor.flush();
+
+        assert!(compressor.data().len() <= RansCompressor::<1>::max_compressed_len(1000));
+    }
+
+    #[test]
+    fn test_reset_for_grows_buffer() {
+        let ctx = Context::new_from(1.0, [0.25, 0.25, 0.25, 0.25]);
+        let enc_ctx = RansEncContext::from_context(&ctx, 6);
+
+        let mut compressor = RansCompressor::<1>::with_max_symbols(4);
+        compressor.reset_for(1000);
+        assert!(compressor.max_symbols >= 1000);
+        for i in 0..1000 {
+            compressor.put(&enc_ctx, i % 4);
+        }
+        compressor.flush();
+
+        compressor.reset_for(10);
+        assert!(compressor.max_symbols >= 1000);
+    }
+
+    #[test]
+    #[should_panic(expected = "rANS buffer too small")]
+    fn test_put_past_capacity() {
+        let ctx = Context::new_from(1.0, [0.25, 0.25, 0.25, 0.25]);
+        let enc_ctx = RansEncContext::from_context(&ctx, 6);
+
+        let mut compressor = RansCompressor::<1>::with_max_symbols(4);
+        for _ in 0..5 {
+            compressor.put(&enc_ctx, 0);
+        }
+    }
+
+    #[test]
+    fn test_try_put_past_capacity() {
+        let ctx = Context::new_from(1.0, [0.25, 0.25, 0.25, 0.25]);
+        let enc_ctx = RansEncContext::from_context(&ctx, 6);
+
+        let mut compressor = RansCompressor::<2>::with_max_symbols(2);
+        compressor.try_put(&enc_ctx, 0, &enc_ctx, 1).unwrap();
+        compressor.try_put_first(&enc_ctx, 2).unwrap();
+        let error = compressor.try_put_all([(&enc_ctx, 3), (&enc_ctx, 0)]);
+        assert_eq!(error, Err(RansBufferFullError { max_symbols: 2 }));
+        assert_eq!(error.unwrap_err().max_symbols(), 2);
+
+        compressor.reset_for(3);
+        for _ in 0..compressor.max_symbols {
+            compressor.try_put(&enc_ctx, 0, &enc_ctx, 1).unwrap();
+        }
+        assert!(compressor.try_put_first(&enc_ctx, 0).is_err());
+    }
+
+    #[test]
+    fn rans_buffer_full_error_display() {
+        assert_eq!(
+            RansBufferFullError { max_symbols: 4 }.to_string(),
+            "rANS buffer too small for more than 4 symbols"
+        );
     }
 
     #[test]
@@ -268,24 +791,24 @@ mod tests {
             data.push((&CONTEXTS_10[rng.gen_range(0..10)], rng.gen_range(0..10)));
         }
 
-        test_round_trip::<10>(data);
+        test_round_trip(data);
     }
 
-    fn test_round_trip<const SYMBOLS_NUM: usize>(mut data: Vec<(&Context, usize)>) {
+    fn test_round_trip(mut data: Vec<(&Context, usize)>) {
         const SCALE_BITS: u8 = 6;
 
-        let mut compressor = RansCompressor::<1>::new();
+        let mut compressor = RansCompressor::<1>::with_max_symbols(data.len());
         for (ctx, val) in &data {
-            let enc_ctx = RansEncContext::<SYMBOLS_NUM>::from_context(ctx, SCALE_BITS);
+            let enc_ctx = RansEncContext::from_context(ctx, SCALE_BITS);
             compressor.put(&enc_ctx, *val);
         }
         compressor.flush();
         let mut compressed = compressor.data().to_owned();
         data.reverse();
 
-        let mut decompressor = RansDecompressor::<1>::new(&mut compressed);
+        let mut decompressor = RansDecompressor::<1>::new(&mut compressed).unwrap();
         for (ctx, val) in &data {
-            let dec_ctx = RansDecContext::<SYMBOLS_NUM>::from_context(ctx, SCALE_BITS);
+            let dec_ctx = RansDecContext::from_context(ctx, SCALE_BITS);
             assert_eq!(decompressor.get(&dec_ctx), *val);
         }
     }
@@ -299,12 +822,12 @@ mod tests {
             1.0,
             [0.125, 0.125, 0.125, 0.125, 0.125, 0.125, 0.125, 0.125],
         );
-        let enc_ctx1 = RansEncContext::<4>::from_context(&ctx1, SCALE_BITS);
-        let enc_ctx2 = RansEncContext::<8>::from_context(&ctx2, SCALE_BITS);
-        let dec_ctx1 = RansDecContext::<4>::from_context(&ctx1, SCALE_BITS);
-        let dec_ctx2 = RansDecContext::<8>::from_context(&ctx2, SCALE_BITS);
+        let enc_ctx1 = RansEncContext::from_context(&ctx1, SCALE_BITS);
+        let enc_ctx2 = RansEncContext::from_context(&ctx2, SCALE_BITS);
+        let dec_ctx1 = RansDecContext::from_context(&ctx1, SCALE_BITS);
+        let dec_ctx2 = RansDecContext::from_context(&ctx2, SCALE_BITS);
 
-        let mut compressor = RansCompressor::<2>::new();
+        let mut compressor = RansCompressor::<2>::with_max_symbols(4);
         compressor.put(&enc_ctx1, 0, &enc_ctx2, 1);
         compressor.put(&enc_ctx1, 1, &enc_ctx2, 3);
         compressor.put(&enc_ctx1, 2, &enc_ctx2, 5);
@@ -313,10 +836,129 @@ mod tests {
 
         let mut compressed = compressor.data().to_owned();
 
-        let mut decompressor = RansDecompressor::<2>::new(&mut compressed);
+        let mut decompressor = RansDecompressor::<2>::new(&mut compressed).unwrap();
         assert_eq!(decompressor.get(&dec_ctx1, &dec_ctx2), (3, 7));
         assert_eq!(decompressor.get(&dec_ctx1, &dec_ctx2), (2, 5));
         assert_eq!(decompressor.get(&dec_ctx1, &dec_ctx2), (1, 3));
         assert_eq!(decompressor.get(&dec_ctx1, &dec_ctx2), (0, 1));
     }
+
+    #[test]
+    fn round_trip_two_channels_first_only() {
+        const SCALE_BITS: u8 = 10;
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(42);
+
+        let enc_contexts: Vec<_> = CONTEXTS_10
+            .iter()
+            .map(|ctx| RansEncContext::from_context(ctx, SCALE_BITS))
+            .collect();
+        let dec_contexts: Vec<_> = CONTEXTS_10
+            .iter()
+            .map(|ctx| RansDecContext::from_context(ctx, SCALE_BITS))
+            .collect();
+
+        let mut data = Vec::new();
+        for _ in 0..1024 {
+            let first = (rng.gen_range(0..10), rng.gen_range(0..10));
+            let second = rng
+                .gen_bool(0.7)
+                .then(|| (rng.gen_range(0..10), rng.gen_range(0..10)));
+            data.push((first, second));
+        }
+
+        let mut compressor = RansCompressor::<2>::with_max_symbols(data.len());
+        for &((ctx_1, val_1), second) in &data {
+            match second {
+                Some((ctx_2, val_2)) => {
+                    compressor.put(&enc_contexts[ctx_1], val_1, &enc_contexts[ctx_2], val_2);
+                }
+                None => compressor.put_first(&enc_contexts[ctx_1], val_1),
+            }
+        }
+        compressor.flush();
+        let mut compressed = compressor.data().to_owned();
+        data.reverse();
+
+        let mut decompressor = RansDecompressor::<2>::new(&mut compressed).unwrap();
+        for &((ctx_1, val_1), second) in &data {
+            let ctx_2 = second.map_or(0, |(ctx, _)| ctx);
+            let val_2 = second.map(|(_, val)| val);
+            let result =
+                decompressor.get_conditional(&dec_contexts[ctx_1], &dec_contexts[ctx_2], |_| {
+                    second.is_none()
+                });
+            assert_eq!(result, (val_1, val_2));
+        }
+    }
+
+    #[test]
+    fn round_trip_n_channels() {
+        const SCALE_BITS: u8 = 10;
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(42);
+
+        let enc_contexts: Vec<_> = CONTEXTS_10
+            .iter()
+            .map(|ctx| RansEncContext::from_context(ctx, SCALE_BITS))
+            .collect();
+        let dec_contexts: Vec<_> = CONTEXTS_10
+            .iter()
+            .map(|ctx| RansDecContext::from_context(ctx, SCALE_BITS))
+            .collect();
+        let data: Vec<[(usize, usize); 4]> = (0..1000)
+            .map(|_| [(); 4].map(|_| (rng.gen_range(0..10), rng.gen_range(0..10))))
+            .collect();
+
+        let mut compressor = RansCompressor::<4>::with_max_symbols(data.len());
+        for symbols in &data {
+            compressor
+                .try_put_all(symbols.map(|(ctx, val)| (&enc_contexts[ctx], val)))
+                .unwrap();
+        }
+        compressor.flush();
+
+        let mut compressed = compressor.data().to_owned();
+
+        let mut decompressor = RansDecompressor::<4>::new(&mut compressed).unwrap();
+        for symbols in data.iter().rev() {
+            let contexts = symbols.map(|(ctx, _)| &dec_contexts[ctx]);
+            assert_eq!(decompressor.get_all(contexts), symbols.map(|(_, val)| val));
+        }
+    }
+
+    #[test]
+    fn data_too_short() {
+        let mut data = [0; 7];
+        let error = RansDecompressor::<2>::new(&mut data).err().unwrap();
+        assert_eq!((error.data_len(), error.min_len()), (7, 8));
+        assert_eq!(
+            error.to_string(),
+            "rANS data too short (length: 7, minimum: 8)"
+        );
+
+        let mut data = [0; 4];
+        assert!(RansDecompressor::<1>::new(&mut data).is_ok());
+    }
+
+    #[test]
+    fn two_channels_match_put_all() {
+        const SCALE_BITS: u8 = 6;
+
+        let enc_ctx1 = RansEncContext::from_context(&CONTEXTS_10[0], SCALE_BITS);
+        let enc_ctx2 = RansEncContext::from_context(&CONTEXTS_10[1], SCALE_BITS);
+        let dec_ctx1 = RansDecContext::from_context(&CONTEXTS_10[0], SCALE_BITS);
+        let dec_ctx2 = RansDecContext::from_context(&CONTEXTS_10[1], SCALE_BITS);
+
+        let mut compressor = RansCompressor::<2>::with_max_symbols(2);
+        compressor.put(&enc_ctx1, 4, &enc_ctx2, 8);
+        compressor
+            .try_put_all([(&enc_ctx1, 2), (&enc_ctx2, 9)])
+            .unwrap();
+        compressor.flush();
+
+        let mut compressed = compressor.data().to_owned();
+
+        let mut decompressor = RansDecompressor::<2>::new(&mut compressed).unwrap();
+        assert_eq!(decompressor.get(&dec_ctx1, &dec_ctx2), (2, 9));
+        assert_eq!(decompressor.get_all([&dec_ctx1, &dec_ctx2]), [4, 8]);
+    }
 }
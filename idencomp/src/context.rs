@@ -1,13 +1,58 @@
 use std::cmp::Ordering;
+use std::error::Error;
 use std::fmt::{Display, Formatter};
 use std::ops::Add;
 
 use derive_more::Deref;
 use itertools::Itertools;
-use serde::{Deserialize, Serialize};
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// Error returned when trying to create a value object (such as
+/// [`Probability`]) from a float that is outside of its allowed range.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum InvalidValueError {
+    /// The value is NaN or infinite.
+    NotFinite(f32),
+    /// The value is negative, while only non-negative values are allowed.
+    Negative(f32),
+    /// The value is greater than the maximum allowed value (the second field).
+    TooLarge(f32, f32),
+}
+
+impl Display for InvalidValueError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InvalidValueError::NotFinite(value) => write!(f, "Value is not finite: {}", value),
+            InvalidValueError::Negative(value) => write!(f, "Value is negative: {}", value),
+            InvalidValueError::TooLarge(value, max) => {
+                write!(f, "Value is too large: {} (maximum: {})", value, max)
+            }
+        }
+    }
+}
+
+impl Error for InvalidValueError {}
+
+fn check_finite(value: f32) -> Result<(), InvalidValueError> {
+    if value.is_finite() {
+        Ok(())
+    } else {
+        Err(InvalidValueError::NotFinite(value))
+    }
+}
+
+fn check_non_negative(value: f32) -> Result<(), InvalidValueError> {
+    check_finite(value)?;
+    if value == 0.0 || value.is_sign_positive() {
+        Ok(())
+    } else {
+        Err(InvalidValueError::Negative(value))
+    }
+}
 
 /// Probability, as a float between 0.0 and 1.0.
-#[derive(Copy, Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Copy, Debug, Clone, Default, Serialize)]
 #[serde(transparent)]
 pub struct Probability(f32);
 
@@ -30,13 +75,40 @@ impl Probability {
     /// let prob = Probability::new(0.5);
     /// assert_eq!(prob.get(), 0.5);
     /// ```
+    ///
+    /// # Panics
+    /// This function panics if the value is not between 0.0 and 1.0. See
+    /// [`Self::try_new()`] for a non-panicking version.
     #[must_use]
     pub fn new(value: f32) -> Self {
-        assert!(value.is_finite());
-        assert!(value == 0.0 || value.is_sign_positive());
-        assert!(value <= 1.0);
+        Self::try_new(value).unwrap_or_else(|e| panic!("Invalid probability: {}", e))
+    }
+
+    /// Creates a new `Probability` object, or returns an error if the value is
+    /// not between 0.0 and 1.0.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::context::{InvalidValueError, Probability};
+    ///
+    /// assert_eq!(Probability::try_new(0.5), Ok(Probability::new(0.5)));
+    /// assert_eq!(
+    ///     Probability::try_new(-0.5),
+    ///     Err(InvalidValueError::Negative(-0.5))
+    /// );
+    /// assert_eq!(
+    ///     Probability::try_new(1.5),
+    ///     Err(InvalidValueError::TooLarge(1.5, 1.0))
+    /// );
+    /// assert!(Probability::try_new(f32::NAN).is_err());
+    /// ```
+    pub fn try_new(value: f32) -> Result<Self, InvalidValueError> {
+        check_non_negative(value)?;
+        if value > 1.0 {
+            return Err(InvalidValueError::TooLarge(value, 1.0));
+        }
 
-        Self(value)
+        Ok(Self(value))
     }
 
     /// Value of this `Probability` object, as a float.
@@ -62,6 +134,13 @@ impl PartialEq for Probability {
 
 impl Eq for Probability {}
 
+impl<'de> Deserialize<'de> for Probability {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = f32::deserialize(deserializer)?;
+        Self::try_new(value).map_err(D::Error::custom)
+    }
+}
+
 impl From<f32> for Probability {
     fn from(value: f32) -> Self {
         Self::new(value)
@@ -101,13 +180,27 @@ impl Entropy {
     /// ```
     ///
     /// # Panics
-    /// This function panics if the value is negative, or is not finite.
+    /// This function panics if the value is negative, or is not finite. See
+    /// [`Self::try_new()`] for a non-panicking version.
     #[must_use]
     pub fn new(value: f32) -> Self {
-        assert!(value.is_finite());
-        assert!(value == 0.0 || value.is_sign_positive());
+        Self::try_new(value).unwrap_or_else(|e| panic!("Invalid entropy: {}", e))
+    }
 
-        Self(value)
+    /// Creates a new `Entropy` object, or returns an error if the value is
+    /// negative, or is not finite.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::context::{Entropy, InvalidValueError};
+    ///
+    /// assert_eq!(Entropy::try_new(0.5), Ok(Entropy::new(0.5)));
+    /// assert_eq!(Entropy::try_new(-1.0), Err(InvalidValueError::Negative(-1.0)));
+    /// ```
+    pub fn try_new(value: f32) -> Result<Self, InvalidValueError> {
+        check_non_negative(value)?;
+
+        Ok(Self(value))
     }
 
     /// Value of this `Entropy` object, as a float.
@@ -209,6 +302,34 @@ impl Context {
         )
     }
 
+    /// Creates new `Context` object from given float values, or returns an
+    /// error if any of them is not a valid [`Probability`].
+    ///
+    /// ## Examples
+    /// ```
+    /// use idencomp::context::{Context, InvalidValueError};
+    ///
+    /// let context = Context::try_new_from(1.0, [0.5, 0.5])?;
+    /// assert_eq!(context, Context::new_from(1.0, [0.5, 0.5]));
+    ///
+    /// let result = Context::try_new_from(1.0, [0.5, f32::INFINITY]);
+    /// assert_eq!(result, Err(InvalidValueError::NotFinite(f32::INFINITY)));
+    ///
+    /// # Ok::<(), InvalidValueError>(())
+    /// ```
+    pub fn try_new_from<I>(context_prob: f32, symbol_prob: I) -> Result<Self, InvalidValueError>
+    where
+        I: IntoIterator<Item = f32>,
+    {
+        let context_prob = Probability::try_new(context_prob)?;
+        let symbol_prob = symbol_prob
+            .into_iter()
+            .map(Probability::try_new)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self::new(context_prob, symbol_prob))
+    }
+
     /// Creates a new "dummy" context that have the same probability of all
     /// symbols.
     ///
@@ -476,12 +597,27 @@ impl ContextMergeCost {
     /// ```
     ///
     /// # Panics
-    /// This function panics if the is not finite.
+    /// This function panics if the value is not finite. See
+    /// [`Self::try_new()`] for a non-panicking version.
     #[must_use]
     pub fn new(value: f32) -> Self {
-        assert!(value.is_finite());
+        Self::try_new(value).unwrap_or_else(|e| panic!("Invalid merge cost: {}", e))
+    }
 
-        Self(value)
+    /// Creates a new `ContextMergeCost` object, or returns an error if the
+    /// value is not finite.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::context::{ContextMergeCost, InvalidValueError};
+    ///
+    /// assert_eq!(ContextMergeCost::try_new(-0.5), Ok(ContextMergeCost::new(-0.5)));
+    /// assert!(ContextMergeCost::try_new(f32::NAN).is_err());
+    /// ```
+    pub fn try_new(value: f32) -> Result<Self, InvalidValueError> {
+        check_finite(value)?;
+
+        Ok(Self(value))
     }
 
     /// Gets the value for this `ContextMergeCost`.
@@ -535,6 +671,7 @@ impl Ord for ContextMergeCost {
 #[cfg(test)]
 mod tests {
     use approx::assert_abs_diff_eq;
+    use serde_test::{assert_de_tokens, assert_de_tokens_error, assert_ser_tokens, Token};
 
     use crate::context::{Context, Probability};
 
@@ -644,4 +781,15 @@ mod tests {
 
         assert_eq!(cum_freqs, [0, 1, 2, 9]);
     }
+
+    #[test]
+    fn test_probability_serde() {
+        assert_ser_tokens(&Probability::new(0.5), &[Token::F32(0.5)]);
+        assert_de_tokens(&Probability::new(0.5), &[Token::F32(0.5)]);
+        assert_de_tokens_error::<Probability>(
+            &[Token::F32(1.5)],
+            "Value is too large: 1.5 (maximum: 1)",
+        );
+        assert_de_tokens_error::<Probability>(&[Token::F32(f32::NAN)], "Value is not finite: NaN");
+    }
 }
@@ -1,16 +1,19 @@
-use std::cmp::Ordering;
-use std::collections::BinaryHeap;
+use std::cmp::{Ordering, Reverse};
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, BinaryHeap, HashMap};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
 use std::ops::Deref;
 
 use itertools::Itertools;
-use rayon::prelude::{
-    IndexedParallelIterator, IntoParallelIterator, IntoParallelRefIterator, ParallelIterator,
-    ParallelSliceMut,
-};
 
 use crate::context::{Context, ContextMergeCost};
 use crate::context_spec::ContextSpec;
 use crate::model::Model;
+use crate::parallel::{
+    IndexedParallelIterator, IntoParallelIterator, IntoParallelRefIterator, ParallelIterator,
+    ParallelSliceMut,
+};
 use crate::progress::{DummyProgressNotifier, ProgressNotifier};
 
 /// Makes a [`ContextTree`] by performing context binning on all contexts in
@@ -45,6 +48,11 @@ pub fn bin_contexts_with_model(model: &Model, options: &ContextBinningOptions) -
 /// Makes a [`ContextTree`] by performing context binning on given (spec,
 /// context) pairs.
 ///
+/// The groups of specs set with [`ContextBinningOptionsBuilder::merge_group()`]
+/// are merged into single contexts before the binning starts, and the ones set
+/// with [`ContextBinningOptionsBuilder::separate_group()`] are never merged
+/// with the rest of the contexts.
+///
 /// # Examples
 /// ```
 /// use idencomp::context::Context;
@@ -62,46 +70,151 @@ pub fn bin_contexts_with_keys<I>(contexts: I, options: &ContextBinningOptions) -
 where
     I: IntoIterator<Item = (ContextSpec, Context)>,
 {
-    let mut contexts: Vec<(ContextSpec, Context)> = contexts.into_iter().collect();
+    let merge_groups = group_indices(&options.merge_groups);
+    let separate_groups = group_indices(&options.separate_groups);
 
-    let pre_binned = if options.pre_binning_num < contexts.len() {
-        contexts.sort_by(|(_, ctx_1), (_, ctx_2)| ctx_2.context_prob.cmp(&ctx_1.context_prob));
+    let mut contexts = merge_grouped_contexts(contexts, &merge_groups, &separate_groups);
 
-        let (spec, mut context_binned) = contexts.pop().unwrap();
-        let mut specs_binned = vec![spec];
+    // Pre-binning does not merge the separate groups either, so there is one
+    // pre-binned context for each of them
+    let mut pre_binned = BTreeMap::new();
+    if options.pre_binning_num < contexts.len() {
+        contexts.sort_by_key(|(_, ctx, _)| Reverse(ctx.context_prob));
 
-        while options.pre_binning_num < contexts.len() + 1 {
-            let (spec, context) = contexts.pop().unwrap();
-            specs_binned.push(spec);
-            context_binned = context_binned.merge_with(&context);
+        while !contexts.is_empty() && options.pre_binning_num < contexts.len() + pre_binned.len() {
+            let (specs, context, group) = contexts.pop().unwrap();
+            merge_into_group(&mut pre_binned, group, specs, context);
         }
+    }
 
-        let node = ContextNode::new_leaf_multi(specs_binned, context_binned);
-        Some(node)
-    } else {
-        None
-    };
+    let mut nodes = Vec::with_capacity(contexts.len() + pre_binned.len());
+    let mut groups = Vec::with_capacity(contexts.len() + pre_binned.len());
+    for (specs, context, group) in contexts {
+        nodes.push(ContextNode::new_leaf_multi(specs, context));
+        groups.push(group);
+    }
+    for (group, (specs, context)) in pre_binned {
+        nodes.push(ContextNode::new_leaf_multi(specs, context));
+        groups.push(group);
+    }
 
-    let mut nodes: Vec<ContextNode> = contexts
-        .into_iter()
-        .map(|(key, context)| ContextNode::new_leaf(key, context))
-        .collect();
+    bin_contexts_nodes(nodes, groups, options)
+}
+
+/// Maps each of the specs in given groups to the index of the first group it
+/// belongs to.
+#[must_use]
+fn group_indices(groups: &[Vec<ContextSpec>]) -> HashMap<ContextSpec, usize> {
+    let mut indices = HashMap::new();
+    for (index, group) in groups.iter().enumerate() {
+        for &spec in group {
+            indices.entry(spec).or_insert(index);
+        }
+    }
+
+    indices
+}
+
+/// Merges the contexts belonging to the same merge group, leaving the rest of
+/// the contexts intact, and returns them along with the separate group they
+/// belong to. The specs of a merge group that belong to different separate
+/// groups are only merged within each of these.
+#[must_use]
+fn merge_grouped_contexts<I>(
+    contexts: I,
+    merge_groups: &HashMap<ContextSpec, usize>,
+    separate_groups: &HashMap<ContextSpec, usize>,
+) -> Vec<(Vec<ContextSpec>, Context, usize)>
+where
+    I: IntoIterator<Item = (ContextSpec, Context)>,
+{
+    let mut result = Vec::new();
+    let mut merged = BTreeMap::new();
+    for (spec, context) in contexts {
+        let separate_group = separate_group_of(&spec, separate_groups);
+        match merge_groups.get(&spec) {
+            Some(&group) => {
+                merge_into_group(&mut merged, (group, separate_group), vec![spec], context)
+            }
+            None => result.push((vec![spec], context, separate_group)),
+        }
+    }
+
+    result.extend(
+        merged
+            .into_iter()
+            .map(|((_, separate_group), (specs, context))| (specs, context, separate_group)),
+    );
+    result
+}
 
-    if let Some(pre_binned) = pre_binned {
-        nodes.push(pre_binned);
+fn merge_into_group<K: Ord>(
+    groups: &mut BTreeMap<K, (Vec<ContextSpec>, Context)>,
+    group: K,
+    specs: Vec<ContextSpec>,
+    context: Context,
+) {
+    match groups.entry(group) {
+        Entry::Vacant(entry) => {
+            entry.insert((specs, context));
+        }
+        Entry::Occupied(mut entry) => {
+            let (group_specs, group_context) = entry.get_mut();
+            group_specs.extend(specs);
+            *group_context = group_context.merge_with(&context);
+        }
+    }
+}
+
+/// Returns the separate group given spec belongs to; `0` is the group of all
+/// the specs not assigned to any separate group.
+#[must_use]
+fn separate_group_of(spec: &ContextSpec, separate_groups: &HashMap<ContextSpec, usize>) -> usize {
+    separate_groups.get(spec).map_or(0, |&index| index + 1)
+}
+
+/// Checks that none of the merge groups contains specs from different
+/// separate groups, as these could not be merged without merging the separate
+/// groups as well.
+fn check_groups(
+    merge_groups: &[Vec<ContextSpec>],
+    separate_groups: &[Vec<ContextSpec>],
+) -> Result<(), ContextBinningOptionsError> {
+    let merge_indices = group_indices(merge_groups);
+    let separate_indices = group_indices(separate_groups);
+
+    for (index, group) in merge_groups.iter().enumerate() {
+        // Only the first merge group of each spec is used
+        let mut specs = group.iter().filter(|&spec| merge_indices[spec] == index);
+        if let Some(first) = specs.next() {
+            let separate_group = separate_indices.get(first);
+            if let Some(other) = specs.find(|&spec| separate_indices.get(spec) != separate_group) {
+                return Err(ContextBinningOptionsError::MergeAcrossSeparateGroups(
+                    *first, *other,
+                ));
+            }
+        }
     }
 
-    bin_contexts_nodes(nodes, options)
+    Ok(())
 }
 
 #[must_use]
-fn bin_contexts_nodes(mut nodes: Vec<ContextNode>, options: &ContextBinningOptions) -> ContextTree {
+fn bin_contexts_nodes(
+    mut nodes: Vec<ContextNode>,
+    mut groups: Vec<usize>,
+    options: &ContextBinningOptions,
+) -> ContextTree {
     let input_length = nodes.len();
     if input_length == 0 {
         return ContextTree::default();
     }
+    let group_num = groups.iter().unique().count();
 
-    let initial_indices: Vec<(usize, usize)> = (0..nodes.len()).tuple_combinations().collect();
+    let initial_indices: Vec<(usize, usize)> = (0..nodes.len())
+        .tuple_combinations()
+        .filter(|&(i, j)| groups[i] == groups[j])
+        .collect();
     let mut initial_elements = Vec::with_capacity(initial_indices.len());
     initial_indices
         .into_par_iter()
@@ -111,11 +224,13 @@ fn bin_contexts_nodes(mut nodes: Vec<ContextNode>, options: &ContextBinningOptio
 
     let mut available = vec![true; input_length];
     let mut queue: BinaryHeap<QueuedNode> = BinaryHeap::from(initial_elements);
+    let mut cost_curve = Vec::new();
+    let mut total_cost = 0.0;
 
     options
         .progress_notifier
-        .set_iter_num((input_length - 1) as u64);
-    for _ in 1..input_length {
+        .set_iter_num((input_length - group_num) as u64);
+    for _ in group_num..input_length {
         let current = loop {
             let current = queue.pop().unwrap();
             let (left_child, right_child) = current.children();
@@ -129,13 +244,23 @@ fn bin_contexts_nodes(mut nodes: Vec<ContextNode>, options: &ContextBinningOptio
         available[right_child] = false;
 
         nodes.push(current.context_node(&nodes));
+        groups.push(groups[left_child]);
         let current_index = nodes.len() - 1;
 
+        if options.record_cost_curve {
+            total_cost += f64::from(current.merge_cost.get());
+            cost_curve.push(MergeStep {
+                num_contexts: input_length - cost_curve.len() - 1,
+                merge_cost: current.merge_cost,
+                total_cost,
+            });
+        }
+
         let new_items: Vec<QueuedNode> = available
             .par_iter()
             .enumerate()
             .filter_map(|(i, &is_available)| {
-                if is_available {
+                if is_available && groups[i] == groups[current_index] {
                     Some(QueuedNode::from_merge(&nodes, i, current_index))
                 } else {
                     None
@@ -148,14 +273,89 @@ fn bin_contexts_nodes(mut nodes: Vec<ContextNode>, options: &ContextBinningOptio
         options.progress_notifier.inc_iter();
     }
 
-    ContextTree::new(nodes)
+    // The separate groups are only joined at the very top of the tree, so
+    // that the tree still has a single root
+    let mut roots: Vec<usize> = (0..nodes.len()).filter(|&i| available[i]).collect();
+    debug_assert_eq!(roots.len(), group_num);
+    while roots.len() > 1 {
+        let right = roots.pop().unwrap();
+        let left = roots.pop().unwrap();
+        let node = QueuedNode::make_context_node(&nodes, left, right);
+        nodes.push(node);
+        roots.push(nodes.len() - 1);
+    }
+
+    let mut tree = ContextTree::new(nodes);
+    tree.joined_groups = group_num - 1;
+    if options.record_cost_curve {
+        tree.cost_curve = Some(cost_curve);
+    }
+    tree
 }
 
+/// A single step of the context binning, i.e. merging two contexts into one,
+/// as recorded when
+/// [`ContextBinningOptionsBuilder::record_cost_curve()`] is enabled.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct MergeStep {
+    num_contexts: usize,
+    merge_cost: ContextMergeCost,
+    total_cost: f64,
+}
+
+impl MergeStep {
+    /// Returns the number of contexts left after this merge.
+    #[must_use]
+    pub fn num_contexts(&self) -> usize {
+        self.num_contexts
+    }
+
+    /// Returns the cost of this merge, i.e. the increase of the model's rate
+    /// (in bits per symbol) caused by it.
+    #[must_use]
+    pub fn merge_cost(&self) -> ContextMergeCost {
+        self.merge_cost
+    }
+
+    /// Returns the total cost of all the merges up to and including this one.
+    #[must_use]
+    pub fn total_cost(&self) -> f64 {
+        self.total_cost
+    }
+}
+
+/// Error returned by [`ContextBinningOptionsBuilder::try_build()`] when the
+/// options are inconsistent.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ContextBinningOptionsError {
+    /// A merge group contains specs (the two given ones) that belong to
+    /// different separate groups.
+    MergeAcrossSeparateGroups(ContextSpec, ContextSpec),
+}
+
+impl Display for ContextBinningOptionsError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ContextBinningOptionsError::MergeAcrossSeparateGroups(spec_1, spec_2) => write!(
+                f,
+                "Context specs {} and {} have to be merged, but belong to different separate \
+                groups",
+                spec_1, spec_2
+            ),
+        }
+    }
+}
+
+impl Error for ContextBinningOptionsError {}
+
 /// Context binning parameters that can be set by user.
 #[derive(Debug)]
 pub struct ContextBinningOptions {
     progress_notifier: Box<dyn ProgressNotifier>,
     pre_binning_num: usize,
+    record_cost_curve: bool,
+    merge_groups: Vec<Vec<ContextSpec>>,
+    separate_groups: Vec<Vec<ContextSpec>>,
 }
 
 impl ContextBinningOptions {
@@ -182,6 +382,9 @@ impl Default for ContextBinningOptions {
 pub struct ContextBinningOptionsBuilder {
     progress_notifier: Box<dyn ProgressNotifier>,
     pre_binning_num: usize,
+    record_cost_curve: bool,
+    merge_groups: Vec<Vec<ContextSpec>>,
+    separate_groups: Vec<Vec<ContextSpec>>,
 }
 
 impl ContextBinningOptionsBuilder {
@@ -198,6 +401,9 @@ impl ContextBinningOptionsBuilder {
         Self {
             progress_notifier: Box::new(DummyProgressNotifier),
             pre_binning_num: usize::MAX,
+            record_cost_curve: false,
+            merge_groups: Vec::new(),
+            separate_groups: Vec::new(),
         }
     }
 
@@ -214,6 +420,86 @@ impl ContextBinningOptionsBuilder {
         self
     }
 
+    /// Sets whether the cost of each of the merges should be recorded, so that
+    /// it is available as [`ContextTree::cost_curve()`]. The curve shows how
+    /// much the model's rate deteriorates with each context removed, which
+    /// helps choosing a sensible number of contexts.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::context_binning::ContextBinningOptions;
+    ///
+    /// let _options: ContextBinningOptions = ContextBinningOptions::builder()
+    ///     .record_cost_curve(true)
+    ///     .build();
+    /// ```
+    pub fn record_cost_curve(mut self, record_cost_curve: bool) -> Self {
+        self.record_cost_curve = record_cost_curve;
+        self
+    }
+
+    /// Adds a group of context specs that have to be merged into a single
+    /// context before the binning starts, no matter the cost. The specs that
+    /// are not present in the binned contexts are ignored. A spec should belong
+    /// to a single merge group; otherwise, only the first one is used.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::context::Context;
+    /// use idencomp::context_binning::{bin_contexts_with_keys, ContextBinningOptions};
+    /// use idencomp::context_spec::ContextSpec;
+    ///
+    /// let options = ContextBinningOptions::builder()
+    ///     .merge_group([ContextSpec::new(0), ContextSpec::new(2)])
+    ///     .build();
+    /// let tree = bin_contexts_with_keys(
+    ///     (0..3).map(|i| (ContextSpec::new(i), Context::dummy(4))),
+    ///     &options,
+    /// );
+    /// let contexts = tree.traverse(3);
+    /// assert_eq!(contexts.len(), 2);
+    /// let merged_specs = vec![ContextSpec::new(0), ContextSpec::new(2)];
+    /// assert!(contexts.iter().any(|ctx| ctx.specs() == &merged_specs));
+    /// ```
+    pub fn merge_group<T: Into<Vec<ContextSpec>>>(mut self, specs: T) -> Self {
+        self.merge_groups.push(specs.into());
+        self
+    }
+
+    /// Adds a group of context specs that must not be merged with any contexts
+    /// outside of the group, e.g. to keep the contexts after an
+    /// [`Acid::N`](crate::sequence::Acid::N) apart from the rest. The specs
+    /// that do not belong to any separate group form a group of their own.
+    /// A spec should belong to a single separate group; otherwise, only the
+    /// first one is used. The specs of a merge group that belong to different
+    /// separate groups are only merged within each of these; use
+    /// [`Self::try_build()`] to reject such groups instead.
+    ///
+    /// As the groups are never merged, traversing the resulting
+    /// [`ContextTree`] always produces at least one context for each of the
+    /// groups.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::context::Context;
+    /// use idencomp::context_binning::{bin_contexts_with_keys, ContextBinningOptions};
+    /// use idencomp::context_spec::ContextSpec;
+    ///
+    /// let options = ContextBinningOptions::builder()
+    ///     .separate_group([ContextSpec::new(2)])
+    ///     .build();
+    /// let tree = bin_contexts_with_keys(
+    ///     (0..3).map(|i| (ContextSpec::new(i), Context::dummy(4))),
+    ///     &options,
+    /// );
+    /// let contexts = tree.traverse(1);
+    /// assert_eq!(contexts.len(), 2);
+    /// ```
+    pub fn separate_group<T: Into<Vec<ContextSpec>>>(mut self, specs: T) -> Self {
+        self.separate_groups.push(specs.into());
+        self
+    }
+
     /// Builds the `ContextBinningOptions`.
     ///
     /// # Examples
@@ -227,8 +513,46 @@ impl ContextBinningOptionsBuilder {
         ContextBinningOptions {
             progress_notifier: self.progress_notifier,
             pre_binning_num: self.pre_binning_num,
+            record_cost_curve: self.record_cost_curve,
+            merge_groups: self.merge_groups,
+            separate_groups: self.separate_groups,
         }
     }
+
+    /// Builds the `ContextBinningOptions`, checking that the spec groups are
+    /// consistent.
+    ///
+    /// # Errors
+    /// Returns [`ContextBinningOptionsError`] if any of the merge groups
+    /// contains specs from different separate groups.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::context_binning::{
+    ///     ContextBinningOptions, ContextBinningOptionsBuilder, ContextBinningOptionsError,
+    /// };
+    /// use idencomp::context_spec::ContextSpec;
+    ///
+    /// let _options: ContextBinningOptions = ContextBinningOptionsBuilder::new().try_build()?;
+    ///
+    /// let result = ContextBinningOptionsBuilder::new()
+    ///     .merge_group([ContextSpec::new(0), ContextSpec::new(1)])
+    ///     .separate_group([ContextSpec::new(1)])
+    ///     .try_build();
+    /// assert_eq!(
+    ///     result.unwrap_err(),
+    ///     ContextBinningOptionsError::MergeAcrossSeparateGroups(
+    ///         ContextSpec::new(0),
+    ///         ContextSpec::new(1)
+    ///     )
+    /// );
+    /// # Ok::<(), idencomp::Error>(())
+    /// ```
+    pub fn try_build(self) -> Result<ContextBinningOptions, ContextBinningOptionsError> {
+        check_groups(&self.merge_groups, &self.separate_groups)?;
+
+        Ok(self.build())
+    }
 }
 
 impl Default for ContextBinningOptionsBuilder {
@@ -462,6 +786,10 @@ impl<'a> Ord for IndexedContextNode<'a> {
 #[derive(Debug, Clone)]
 pub struct ContextTree {
     vec: Vec<ContextNode>,
+    cost_curve: Option<Vec<MergeStep>>,
+    /// Number of the nodes at the end of the tree that join the separate
+    /// groups together; these are always split when traversing the tree.
+    joined_groups: usize,
 }
 
 impl ContextTree {
@@ -469,7 +797,11 @@ impl ContextTree {
     pub(crate) fn new<T: Into<Vec<ContextNode>>>(vec: T) -> Self {
         let vec = vec.into();
 
-        Self { vec }
+        Self {
+            vec,
+            cost_curve: None,
+            joined_groups: 0,
+        }
     }
 
     /// Returns the number of nodes in this `ContextTree`.
@@ -511,18 +843,104 @@ impl ContextTree {
         &self.vec
     }
 
+    /// Returns the merges performed when building this `ContextTree`, in the
+    /// order they have been done, if
+    /// [`ContextBinningOptionsBuilder::record_cost_curve()`] was enabled. The
+    /// contexts merged by pre-binning are not included.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::context::Context;
+    /// use idencomp::context_binning::{bin_contexts_with_keys, ContextBinningOptions};
+    /// use idencomp::context_spec::ContextSpec;
+    ///
+    /// let options = ContextBinningOptions::builder()
+    ///     .record_cost_curve(true)
+    ///     .build();
+    /// let tree = bin_contexts_with_keys(
+    ///     [
+    ///         (ContextSpec::new(0), Context::dummy(4)),
+    ///         (ContextSpec::new(1), Context::dummy(4)),
+    ///     ],
+    ///     &options,
+    /// );
+    /// let curve = tree.cost_curve().unwrap();
+    /// assert_eq!(curve.len(), 1);
+    /// assert_eq!(curve[0].num_contexts(), 1);
+    /// ```
+    #[must_use]
+    pub fn cost_curve(&self) -> Option<&[MergeStep]> {
+        self.cost_curve.as_deref()
+    }
+
     /// Traverses through this context tree producing a maximum of
     /// `num_contexts` [`ComplexContext`]s. This traverses into the nodes with
     /// the least merge cost first.
+    ///
+    /// If the tree has been built with
+    /// [`ContextBinningOptionsBuilder::separate_group()`], the groups are never
+    /// merged, so more than `num_contexts` contexts are produced if there are
+    /// more groups than that.
     #[must_use]
     pub fn traverse(self, num_contexts: usize) -> Vec<ComplexContext> {
+        self.traverse_ref(num_contexts)
+    }
+
+    /// Traverses through this context tree once for each of the values in
+    /// `nums_contexts`, producing one list of [`ComplexContext`]s per value.
+    /// This is useful to generate binned models of multiple sizes without
+    /// rebuilding the tree.
+    ///
+    /// # Panics
+    /// This function panics if any of the values in `nums_contexts` is zero.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::context::Context;
+    /// use idencomp::context_binning::{bin_contexts_with_keys, ContextBinningOptions};
+    /// use idencomp::context_spec::ContextSpec;
+    ///
+    /// let tree = bin_contexts_with_keys(
+    ///     [
+    ///         (ContextSpec::new(0), Context::dummy(4)),
+    ///         (ContextSpec::new(1), Context::dummy(4)),
+    ///     ],
+    ///     &ContextBinningOptions::default(),
+    /// );
+    /// let contexts = tree.traverse_multiple(&[1, 2]);
+    /// assert_eq!(contexts[0].len(), 1);
+    /// assert_eq!(contexts[1].len(), 2);
+    /// ```
+    #[must_use]
+    pub fn traverse_multiple(&self, nums_contexts: &[usize]) -> Vec<Vec<ComplexContext>> {
+        nums_contexts
+            .par_iter()
+            .map(|&num_contexts| self.traverse_ref(num_contexts))
+            .collect()
+    }
+
+    fn traverse_ref(&self, num_contexts: usize) -> Vec<ComplexContext> {
         assert!(num_contexts > 0);
         if self.vec.is_empty() {
             return Vec::default();
         }
 
         let mut queue: BinaryHeap<IndexedContextNode> = BinaryHeap::new();
-        queue.push(IndexedContextNode::new(&self.vec, self.vec.len() - 1));
+        let first_joined = self.vec.len() - self.joined_groups;
+        let mut to_split = vec![self.vec.len() - 1];
+        while let Some(index) = to_split.pop() {
+            match self.vec[index] {
+                ContextNode::Node {
+                    left_child,
+                    right_child,
+                    ..
+                } if index >= first_joined => {
+                    to_split.push(left_child);
+                    to_split.push(right_child);
+                }
+                _ => queue.push(IndexedContextNode::new(&self.vec, index)),
+            }
+        }
         let mut result = Vec::new();
 
         while !queue.is_empty() && result.len() + queue.len() < num_contexts {
@@ -623,6 +1041,7 @@ impl ContextNode {
         Self::new_node(context, merge_cost, left_index, right_index)
     }
 
+    #[cfg(test)]
     #[must_use]
     pub(crate) fn new_leaf(spec: ContextSpec, context: Context) -> Self {
         Self::Leaf {
@@ -682,7 +1101,7 @@ mod tests {
     use crate::context::Context;
     use crate::context_binning::{
         bin_contexts_with_keys, bin_contexts_with_model, ComplexContext, ContextBinningOptions,
-        ContextMergeCost, ContextNode, ContextTree,
+        ContextBinningOptionsError, ContextMergeCost, ContextNode, ContextTree,
     };
     use crate::context_spec::{ContextSpec, ContextSpecType};
     use crate::model::{Model, ModelType};
@@ -761,6 +1180,121 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_merge_group() {
+        let context1 = Context::new_from(0.4, [1.0, 0.0, 0.0, 0.0]);
+        let context2 = Context::new_from(0.2, [1.0, 0.0, 0.0, 0.0]);
+        let context3 = Context::new_from(0.4, [0.0, 0.0, 0.0, 1.0]);
+        let contexts = [
+            (spec(1), context1),
+            (spec(2), context2.clone()),
+            (spec(3), context3),
+        ];
+
+        let options = ContextBinningOptions::builder()
+            .merge_group([spec(1), spec(3)])
+            .build();
+        let binned = bin_contexts_with_keys(contexts, &options);
+
+        assert_eq!(binned.len(), 3);
+        assert_eq!(binned.nodes()[0], ContextNode::new_leaf(spec(2), context2));
+        let expected_context_merged = Context::new_from(0.8, [0.5, 0.0, 0.0, 0.5]);
+        assert_eq!(
+            binned.nodes()[1],
+            ContextNode::new_leaf_multi([spec(1), spec(3)], expected_context_merged)
+        );
+    }
+
+    #[test]
+    fn test_separate_group() {
+        let context1 = Context::new_from(0.4, [1.0, 0.0, 0.0, 0.0]);
+        let context2 = Context::new_from(0.3, [1.0, 0.0, 0.0, 0.0]);
+        let context3 = Context::new_from(0.2, [0.25, 0.25, 0.25, 0.25]);
+        let context4 = Context::new_from(0.1, [0.0, 0.0, 0.0, 1.0]);
+        let contexts = [
+            (spec(1), context1),
+            (spec(2), context2),
+            (spec(3), context3),
+            (spec(4), context4),
+        ];
+
+        let options = ContextBinningOptions::builder()
+            .separate_group([spec(2), spec(4)])
+            .build();
+        let binned = bin_contexts_with_keys(contexts, &options);
+
+        assert_eq!(binned.len(), 7);
+
+        let traversed = binned.traverse_multiple(&[1, 2, 4]);
+        let mut specs: Vec<Vec<ContextSpec>> = traversed[0]
+            .iter()
+            .map(|ctx| {
+                let mut specs = ctx.specs().clone();
+                specs.sort();
+                specs
+            })
+            .collect();
+        specs.sort();
+        assert_eq!(specs, [vec![spec(1), spec(3)], vec![spec(2), spec(4)]]);
+        assert_eq!(traversed[1].len(), 2);
+        assert_eq!(traversed[2].len(), 4);
+    }
+
+    #[test]
+    fn test_merge_group_across_separate_groups() {
+        let result = ContextBinningOptions::builder()
+            .merge_group([spec(1), spec(2)])
+            .separate_group([spec(2)])
+            .try_build();
+
+        assert_eq!(
+            result.unwrap_err(),
+            ContextBinningOptionsError::MergeAcrossSeparateGroups(spec(1), spec(2))
+        );
+        assert_eq!(
+            ContextBinningOptionsError::MergeAcrossSeparateGroups(spec(1), spec(2)).to_string(),
+            "Context specs 00000001 and 00000002 have to be merged, but belong to different \
+            separate groups"
+        );
+    }
+
+    #[test]
+    fn test_merge_group_within_separate_group() {
+        let options = ContextBinningOptions::builder()
+            .merge_group([spec(1), spec(2)])
+            .separate_group([spec(1), spec(2)])
+            .separate_group([spec(3)])
+            // Only the first merge group of a spec is used
+            .merge_group([spec(2), spec(3)])
+            .try_build();
+
+        assert!(options.is_ok());
+    }
+
+    #[test]
+    fn test_merge_group_across_separate_groups_unchecked() {
+        let contexts = [
+            (spec(1), Context::dummy(4)),
+            (spec(2), Context::dummy(4)),
+            (spec(3), Context::dummy(4)),
+        ];
+
+        let options = ContextBinningOptions::builder()
+            .merge_group([spec(1), spec(2), spec(3)])
+            .separate_group([spec(3)])
+            .build();
+        let binned = bin_contexts_with_keys(contexts, &options);
+
+        // The merge group is split between the separate groups
+        let mut specs: Vec<_> = binned
+            .traverse(1)
+            .iter()
+            .map(|ctx| ctx.specs().to_vec())
+            .collect();
+        specs.sort();
+        assert_eq!(specs, [vec![spec(1), spec(2)], vec![spec(3)]]);
+    }
+
     #[test]
     fn test_bin_multiple_contexts() {
         let context1 = Context::new_from(0.27, [0.1, 0.8, 0.0, 0.1]);
@@ -848,6 +1382,26 @@ mod tests {
     fn test_bin_bigger_model() {
         let tree = bin_contexts_with_model(&RANDOM_200_CTX_Q_SCORE_MODEL, &Default::default());
         assert_eq!(tree.len(), 399);
+        assert_eq!(tree.cost_curve(), None);
+    }
+
+    #[test]
+    fn test_bin_cost_curve() {
+        let options = ContextBinningOptions::builder()
+            .record_cost_curve(true)
+            .build();
+        let tree = bin_contexts_with_model(&RANDOM_200_CTX_Q_SCORE_MODEL, &options);
+
+        let curve = tree.cost_curve().unwrap();
+        assert_eq!(curve.len(), 199);
+        assert_eq!(curve[0].num_contexts(), 199);
+        assert_eq!(curve[198].num_contexts(), 1);
+
+        let total: f64 = curve
+            .iter()
+            .map(|step| step.merge_cost().get() as f64)
+            .sum();
+        assert!((curve[198].total_cost() - total).abs() < 1e-6);
     }
 
     #[test]
@@ -878,4 +1432,14 @@ mod tests {
         let vec = tree.traverse(1);
         assert_eq!(vec, [ComplexContext::new([spec1, spec2], context_combined)]);
     }
+
+    #[test]
+    fn context_tree_traverse_multiple() {
+        let tree = bin_contexts_with_model(&RANDOM_200_CTX_Q_SCORE_MODEL, &Default::default());
+
+        let result = tree.traverse_multiple(&[1, 16, 64, 200]);
+        let lengths: Vec<usize> = result.iter().map(Vec::len).collect();
+        assert_eq!(lengths, [1, 16, 64, 200]);
+        assert_eq!(result[1], tree.clone().traverse(16));
+    }
 }
@@ -1,3 +1,4 @@
+use std::error::Error;
 use std::fmt::{Display, Formatter};
 
 use idencomp_macros::model;
@@ -213,6 +214,14 @@ pub trait ContextSpecGenerator {
     fn update(&mut self, acid: Acid, q_score: FastqQualityScore);
 }
 
+/// Scales `position` in a sequence of given length to a value between `0` and
+/// `max_value` (exclusive). The computation is done on 64-bit integers, so it
+/// does not overflow for long reads.
+#[inline]
+fn relative_position(position: usize, length: usize, max_value: u32) -> u32 {
+    (position as u64 * max_value as u64 / length as u64) as u32
+}
+
 /// An implementation of [`ContextSpecGenerator`] for [`GenericContextSpec`].
 #[derive(Debug)]
 pub struct GenericContextSpecGenerator<
@@ -312,7 +321,7 @@ impl<const ACID_ORDER: usize, const Q_SCORE_ORDER: usize, const POSITION_BITS: u
 
     #[inline]
     fn position(&self) -> u32 {
-        self.position as u32 * Self::max_position_value() / self.length as u32
+        relative_position(self.position, self.length, Self::max_position_value())
     }
 
     #[must_use]
@@ -389,11 +398,140 @@ impl<const ACID_ORDER: usize, const Q_SCORE_ORDER: usize, const POSITION_BITS: u
     }
 }
 
+/// The way the quality scores are mapped to the `0..Q_SCORE_MAX` range by
+/// [`LightContextSpecGenerator`] (and the generators built on top of it).
+///
+/// Both scales map [`FastqQualityScore::ZERO`] to `0`.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub enum QScoreScale {
+    /// Splits the whole quality score range (`0..94`) into equal bins. The
+    /// lowest scores share the bin with [`FastqQualityScore::ZERO`], and the
+    /// top bins are hardly ever used, as the sequencers rarely produce scores
+    /// above 41.
+    #[default]
+    Linear,
+    /// Splits the range of the scores that the sequencers actually produce
+    /// (`1..=41`) into equal bins, and puts all the higher scores in the last
+    /// one. Only [`FastqQualityScore::ZERO`] is mapped to `0`.
+    Perceptual,
+}
+
+impl QScoreScale {
+    /// The highest quality score that gets its own bins in
+    /// [`QScoreScale::Perceptual`].
+    const PERCEPTUAL_MAX_Q_SCORE: usize = 41;
+
+    /// Returns the lowest number of bins this scale can be used with.
+    #[must_use]
+    const fn min_bins(&self) -> u32 {
+        match self {
+            QScoreScale::Linear => 1,
+            // Bin `0` is reserved for the zero quality score
+            QScoreScale::Perceptual => 2,
+        }
+    }
+
+    /// Returns the highest number of bins this scale can be used with; any
+    /// more and some of the bins would never be used.
+    #[must_use]
+    const fn max_bins(&self) -> u32 {
+        match self {
+            QScoreScale::Linear => FastqQualityScore::SIZE as u32,
+            QScoreScale::Perceptual => Self::PERCEPTUAL_MAX_Q_SCORE as u32 + 1,
+        }
+    }
+
+    /// Returns whether this scale can be used to map the quality scores to
+    /// given number of bins.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::context_spec::QScoreScale;
+    ///
+    /// assert!(QScoreScale::Linear.supports(16));
+    /// assert!(QScoreScale::Perceptual.supports(16));
+    /// assert!(!QScoreScale::Perceptual.supports(1));
+    /// assert!(!QScoreScale::Linear.supports(128));
+    /// ```
+    #[must_use]
+    pub const fn supports(&self, bins: u32) -> bool {
+        self.min_bins() <= bins && bins <= self.max_bins()
+    }
+
+    /// Maps given quality score to a value between `0` and `bins`
+    /// (exclusive).
+    #[inline]
+    #[must_use]
+    fn scale(&self, q_score: FastqQualityScore, bins: u32) -> u32 {
+        let q_score = q_score.get();
+        let bins = bins as usize;
+
+        let value = match self {
+            QScoreScale::Linear => q_score * bins / FastqQualityScore::SIZE,
+            QScoreScale::Perceptual if q_score == 0 => 0,
+            QScoreScale::Perceptual => {
+                let q_score = q_score.min(Self::PERCEPTUAL_MAX_Q_SCORE);
+                1 + (q_score - 1) * (bins - 1) / Self::PERCEPTUAL_MAX_Q_SCORE
+            }
+        };
+        value as u32
+    }
+}
+
+impl Display for QScoreScale {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QScoreScale::Linear => write!(f, "linear"),
+            QScoreScale::Perceptual => write!(f, "perceptual"),
+        }
+    }
+}
+
+/// Error returned when trying to create a context spec generator with a
+/// quality score scale that cannot be used with its number of quality score
+/// bins.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct InvalidQScoreScaleError {
+    scale: QScoreScale,
+    bins: u32,
+}
+
+impl InvalidQScoreScaleError {
+    /// Returns the quality score scale that was requested.
+    #[must_use]
+    pub fn scale(&self) -> QScoreScale {
+        self.scale
+    }
+
+    /// Returns the number of quality score bins the scale could not be used
+    /// with.
+    #[must_use]
+    pub fn bins(&self) -> u32 {
+        self.bins
+    }
+}
+
+impl Display for InvalidQScoreScaleError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Invalid number of quality score bins for the {} scale: {} (must be between {} and {})",
+            self.scale,
+            self.bins,
+            self.scale.min_bins(),
+            self.scale.max_bins()
+        )
+    }
+}
+
+impl Error for InvalidQScoreScaleError {}
+
 /// An implementation of [`ContextSpecGenerator`] for [`GenericContextSpec`].
 /// A slightly lighter variant of [`GenericContextSpecGenerator`]. This variant
 /// replaces [`Acid::N`] with [`Acid::A`] (assuming that invalid acid values are
 /// so rare that it doesn't break the statistics), and quantisizes quality
-/// scores to a value between `0` and `Q_SCORE_MAX` (exclusive).
+/// scores to a value between `0` and `Q_SCORE_MAX` (exclusive), using given
+/// [`QScoreScale`].
 #[derive(Debug)]
 pub struct LightContextSpecGenerator<
     const ACID_ORDER: usize,
@@ -403,6 +541,7 @@ pub struct LightContextSpecGenerator<
 > {
     acid_context: IntQueue<4, ACID_ORDER>,
     q_score_context: IntQueue<Q_SCORE_MAX, Q_SCORE_ORDER>,
+    q_score_scale: QScoreScale,
     position: usize,
     length: usize,
 }
@@ -414,7 +553,8 @@ impl<
         const Q_SCORE_MAX: u32,
     > LightContextSpecGenerator<ACID_ORDER, Q_SCORE_ORDER, POSITION_BITS, Q_SCORE_MAX>
 {
-    /// Creates a new instance of `LightContextSpecGenerator`.
+    /// Creates a new instance of `LightContextSpecGenerator`, using
+    /// [`QScoreScale::Linear`].
     ///
     /// # Examples
     /// ```
@@ -427,16 +567,78 @@ impl<
     /// generator.update(Acid::G, FastqQualityScore::new(5));
     /// assert_ne!(generator.current_context().get(), 0);
     /// ```
+    ///
+    /// # Panics
+    /// This function panics if `Q_SCORE_MAX` is not supported by
+    /// [`QScoreScale::Linear`].
     #[must_use]
     pub fn new(length: usize) -> Self {
+        Self::with_q_score_scale(length, QScoreScale::Linear)
+    }
+
+    /// Creates a new instance of `LightContextSpecGenerator`, using given
+    /// quality score scale.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::context_spec::{
+    ///     ContextSpecGenerator, LightContextSpecGenerator, QScoreScale,
+    /// };
+    /// use idencomp::fastq::FastqQualityScore;
+    /// use idencomp::sequence::Acid;
+    ///
+    /// type Generator = LightContextSpecGenerator<0, 1, 0, 8>;
+    ///
+    /// let mut generator = Generator::with_q_score_scale(10, QScoreScale::Perceptual);
+    /// generator.update(Acid::G, FastqQualityScore::new(1));
+    /// assert_eq!(generator.current_context().get(), 1);
+    /// ```
+    ///
+    /// # Panics
+    /// This function panics if `Q_SCORE_MAX` is not supported by given scale.
+    /// See [`Self::try_with_q_score_scale()`] for a non-panicking version.
+    #[must_use]
+    pub fn with_q_score_scale(length: usize, q_score_scale: QScoreScale) -> Self {
+        match Self::try_with_q_score_scale(length, q_score_scale) {
+            Ok(generator) => generator,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Creates a new instance of `LightContextSpecGenerator`, using given
+    /// quality score scale, or returns an error if `Q_SCORE_MAX` is not
+    /// supported by the scale.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::context_spec::{LightContextSpecGenerator, QScoreScale};
+    ///
+    /// type Generator<const Q_SCORE_MAX: u32> = LightContextSpecGenerator<0, 1, 0, Q_SCORE_MAX>;
+    ///
+    /// assert!(Generator::<8>::try_with_q_score_scale(10, QScoreScale::Perceptual).is_ok());
+    /// let error = Generator::<1>::try_with_q_score_scale(10, QScoreScale::Perceptual).unwrap_err();
+    /// assert_eq!(error.bins(), 1);
+    /// ```
+    pub fn try_with_q_score_scale(
+        length: usize,
+        q_score_scale: QScoreScale,
+    ) -> Result<Self, InvalidQScoreScaleError> {
         debug_assert!(Self::total_bits() < 32);
 
-        Self {
+        if !q_score_scale.supports(Q_SCORE_MAX) {
+            return Err(InvalidQScoreScaleError {
+                scale: q_score_scale,
+                bins: Q_SCORE_MAX,
+            });
+        }
+
+        Ok(Self {
             acid_context: IntQueue::with_default(0),
             q_score_context: IntQueue::with_default(0),
+            q_score_scale,
             position: 0,
             length,
-        }
+        })
     }
 
     #[must_use]
@@ -488,7 +690,7 @@ impl<
 
     #[inline]
     fn position(&self) -> u32 {
-        self.position as u32 * Self::max_position_value() / self.length as u32
+        relative_position(self.position, self.length, Self::max_position_value())
     }
 
     #[must_use]
@@ -518,13 +720,278 @@ impl<
             (0, 0)
         } else {
             (
-                acid.to_usize() - 1,
-                q_score.get() * Self::max_q_score_value() as usize / FastqQualityScore::SIZE,
+                acid.to_usize() as u32 - 1,
+                self.q_score_scale.scale(q_score, Self::max_q_score_value()),
             )
         };
 
-        self.push_acid(acid as u32);
-        self.push_q_score(q_score as u32);
+        self.push_acid(acid);
+        self.push_q_score(q_score);
+        self.position += 1;
+    }
+}
+
+/// An implementation of [`ContextSpecGenerator`] meant for long reads (such
+/// as the ones produced by Oxford Nanopore or PacBio sequencers). It uses the
+/// same acid and quality score contexts as [`LightContextSpecGenerator`], but
+/// the position is the base 2 logarithm of the absolute position in the
+/// sequence (saturated at `POSITION_BITS` bits), rather than the position
+/// relative to the sequence length.
+///
+/// Long reads vary greatly in length, so the relative position makes the same
+/// context span thousands of symbols in some sequences and a few in others.
+/// The logarithmic position instead distinguishes the beginning of a read,
+/// where the quality usually changes the most, from the rest of it.
+#[derive(Debug)]
+pub struct LongReadContextSpecGenerator<
+    const ACID_ORDER: usize,
+    const Q_SCORE_ORDER: usize,
+    const POSITION_BITS: usize,
+    const Q_SCORE_MAX: u32,
+> {
+    inner: LightContextSpecGenerator<ACID_ORDER, Q_SCORE_ORDER, 0, Q_SCORE_MAX>,
+    position: usize,
+}
+
+impl<
+        const ACID_ORDER: usize,
+        const Q_SCORE_ORDER: usize,
+        const POSITION_BITS: usize,
+        const Q_SCORE_MAX: u32,
+    > LongReadContextSpecGenerator<ACID_ORDER, Q_SCORE_ORDER, POSITION_BITS, Q_SCORE_MAX>
+{
+    /// Creates a new instance of `LongReadContextSpecGenerator`. The length of
+    /// the sequence is ignored, as the position is absolute.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::context_spec::{ContextSpecGenerator, LongReadContextSpecGenerator};
+    /// use idencomp::fastq::FastqQualityScore;
+    /// use idencomp::sequence::Acid;
+    ///
+    /// let mut generator = LongReadContextSpecGenerator::<2, 2, 3, 16>::new(100_000);
+    /// assert_eq!(generator.current_context().get(), 0);
+    /// generator.update(Acid::G, FastqQualityScore::new(5));
+    /// assert_ne!(generator.current_context().get(), 0);
+    /// ```
+    #[must_use]
+    pub fn new(length: usize) -> Self {
+        Self::with_q_score_scale(length, QScoreScale::Linear)
+    }
+
+    /// Creates a new instance of `LongReadContextSpecGenerator`, using given
+    /// quality score scale. The length of the sequence is ignored, as the
+    /// position is absolute.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::context_spec::{ContextSpecGenerator, LongReadContextSpecGenerator, QScoreScale};
+    /// use idencomp::fastq::FastqQualityScore;
+    /// use idencomp::sequence::Acid;
+    ///
+    /// let mut generator = LongReadContextSpecGenerator::<0, 3, 4, 16>::with_q_score_scale(
+    ///     100_000,
+    ///     QScoreScale::Perceptual,
+    /// );
+    /// generator.update(Acid::G, FastqQualityScore::new(5));
+    /// assert_ne!(generator.current_context().get(), 0);
+    /// ```
+    ///
+    /// # Panics
+    /// This function panics if `Q_SCORE_MAX` is not supported by given scale.
+    #[must_use]
+    pub fn with_q_score_scale(_length: usize, q_score_scale: QScoreScale) -> Self {
+        debug_assert!(Self::total_bits() < 32);
+
+        Self {
+            // The inner generator does not store the position
+            inner: LightContextSpecGenerator::with_q_score_scale(usize::MAX, q_score_scale),
+            position: 0,
+        }
+    }
+
+    #[must_use]
+    const fn total_bits() -> u32 {
+        LightContextSpecGenerator::<ACID_ORDER, Q_SCORE_ORDER, 0, Q_SCORE_MAX>::total_bits()
+            + POSITION_BITS as u32
+    }
+
+    /// Gets the maximum possible value of any [`ContextSpec`] generated by this
+    /// generator.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::context_spec::LongReadContextSpecGenerator;
+    ///
+    /// assert_eq!(LongReadContextSpecGenerator::<2, 1, 5, 16>::spec_num(), 8192);
+    /// ```
+    #[must_use]
+    pub const fn spec_num() -> u32 {
+        1 << Self::total_bits()
+    }
+
+    #[inline]
+    fn position(&self) -> u32 {
+        let log_position = usize::BITS - self.position.leading_zeros();
+        log_position.min(Self::max_position_value() - 1)
+    }
+
+    #[must_use]
+    const fn max_position_value() -> u32 {
+        1 << POSITION_BITS
+    }
+}
+
+impl<
+        const ACID_ORDER: usize,
+        const Q_SCORE_ORDER: usize,
+        const POSITION_BITS: usize,
+        const Q_SCORE_MAX: u32,
+    > ContextSpecGenerator
+    for LongReadContextSpecGenerator<ACID_ORDER, Q_SCORE_ORDER, POSITION_BITS, Q_SCORE_MAX>
+{
+    fn current_context(&self) -> ContextSpec {
+        let val = (self.inner.current_context().get() << POSITION_BITS) | self.position();
+
+        ContextSpec::new(val)
+    }
+
+    fn update(&mut self, acid: Acid, q_score: FastqQualityScore) {
+        self.inner.update(acid, q_score);
+        self.position += 1;
+    }
+}
+
+/// An implementation of [`ContextSpecGenerator`] meant for fixed-length short
+/// reads (such as the ones produced by Illumina sequencers). It uses the same
+/// acid and quality score contexts as [`LightContextSpecGenerator`], but the
+/// position is the absolute position in the sequence (the sequencing cycle,
+/// saturated at `POSITION_BITS` bits), rather than the position relative to
+/// the sequence length.
+///
+/// The quality of such reads depends mostly on the sequencing cycle, so the
+/// position is stored in the most significant bits of the generated
+/// [`ContextSpec`]s, and all the contexts of a single cycle are adjacent to
+/// each other.
+#[derive(Debug)]
+pub struct CycleContextSpecGenerator<
+    const ACID_ORDER: usize,
+    const Q_SCORE_ORDER: usize,
+    const POSITION_BITS: usize,
+    const Q_SCORE_MAX: u32,
+> {
+    inner: LightContextSpecGenerator<ACID_ORDER, Q_SCORE_ORDER, 0, Q_SCORE_MAX>,
+    position: usize,
+}
+
+impl<
+        const ACID_ORDER: usize,
+        const Q_SCORE_ORDER: usize,
+        const POSITION_BITS: usize,
+        const Q_SCORE_MAX: u32,
+    > CycleContextSpecGenerator<ACID_ORDER, Q_SCORE_ORDER, POSITION_BITS, Q_SCORE_MAX>
+{
+    /// Creates a new instance of `CycleContextSpecGenerator`. The length of
+    /// the sequence is ignored, as the position is absolute.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::context_spec::{ContextSpecGenerator, CycleContextSpecGenerator};
+    /// use idencomp::fastq::FastqQualityScore;
+    /// use idencomp::sequence::Acid;
+    ///
+    /// let mut generator = CycleContextSpecGenerator::<0, 2, 8, 16>::new(150);
+    /// assert_eq!(generator.current_context().get(), 0);
+    /// generator.update(Acid::G, FastqQualityScore::new(5));
+    /// assert_ne!(generator.current_context().get(), 0);
+    /// ```
+    #[must_use]
+    pub fn new(length: usize) -> Self {
+        Self::with_q_score_scale(length, QScoreScale::Linear)
+    }
+
+    /// Creates a new instance of `CycleContextSpecGenerator`, using given
+    /// quality score scale. The length of the sequence is ignored, as the
+    /// position is absolute.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::context_spec::{ContextSpecGenerator, CycleContextSpecGenerator, QScoreScale};
+    /// use idencomp::fastq::FastqQualityScore;
+    /// use idencomp::sequence::Acid;
+    ///
+    /// let mut generator = CycleContextSpecGenerator::<0, 2, 8, 16>::with_q_score_scale(
+    ///     150,
+    ///     QScoreScale::Perceptual,
+    /// );
+    /// generator.update(Acid::G, FastqQualityScore::new(5));
+    /// assert_ne!(generator.current_context().get(), 0);
+    /// ```
+    ///
+    /// # Panics
+    /// This function panics if `Q_SCORE_MAX` is not supported by given scale.
+    #[must_use]
+    pub fn with_q_score_scale(_length: usize, q_score_scale: QScoreScale) -> Self {
+        debug_assert!(Self::total_bits() < 32);
+
+        Self {
+            // The inner generator does not store the position
+            inner: LightContextSpecGenerator::with_q_score_scale(usize::MAX, q_score_scale),
+            position: 0,
+        }
+    }
+
+    #[must_use]
+    const fn inner_bits() -> u32 {
+        LightContextSpecGenerator::<ACID_ORDER, Q_SCORE_ORDER, 0, Q_SCORE_MAX>::total_bits()
+    }
+
+    #[must_use]
+    const fn total_bits() -> u32 {
+        Self::inner_bits() + POSITION_BITS as u32
+    }
+
+    /// Gets the maximum possible value of any [`ContextSpec`] generated by this
+    /// generator.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::context_spec::CycleContextSpecGenerator;
+    ///
+    /// assert_eq!(CycleContextSpecGenerator::<0, 2, 8, 16>::spec_num(), 65536);
+    /// ```
+    #[must_use]
+    pub const fn spec_num() -> u32 {
+        1 << Self::total_bits()
+    }
+
+    #[inline]
+    fn position(&self) -> u32 {
+        (self.position as u64).min(Self::max_position_value() as u64 - 1) as u32
+    }
+
+    #[must_use]
+    const fn max_position_value() -> u32 {
+        1 << POSITION_BITS
+    }
+}
+
+impl<
+        const ACID_ORDER: usize,
+        const Q_SCORE_ORDER: usize,
+        const POSITION_BITS: usize,
+        const Q_SCORE_MAX: u32,
+    > ContextSpecGenerator
+    for CycleContextSpecGenerator<ACID_ORDER, Q_SCORE_ORDER, POSITION_BITS, Q_SCORE_MAX>
+{
+    fn current_context(&self) -> ContextSpec {
+        let val = (self.position() << Self::inner_bits()) | self.inner.current_context().get();
+
+        ContextSpec::new(val)
+    }
+
+    fn update(&mut self, acid: Acid, q_score: FastqQualityScore) {
+        self.inner.update(acid, q_score);
         self.position += 1;
     }
 }
@@ -596,16 +1063,42 @@ model! {
     light(4, 4, 4, 16),
     light(5, 4, 4, 16),
     light(3, 5, 4, 16),
+    // ## Perceptual Q Score scale
+    light(0, 3, 0, 8, perceptual),
+    light(0, 4, 2, 8, perceptual),
+    light(2, 3, 2, 16, perceptual),
+    // # Long reads
+    // Insertions and deletions make long acid contexts unreliable, so these
+    // rely on short acid contexts and the quality scores instead
+    // ## Acids
+    long(4, 0, 0, 1),
+    long(6, 0, 0, 1),
+    long(3, 1, 2, 8),
+    // ## Quality Scores
+    long(0, 3, 4, 16),
+    long(0, 4, 3, 16),
+    long(2, 3, 3, 16),
+    long(1, 4, 4, 8),
+    // # Cycles
+    // Full-resolution absolute position, for fixed-length short reads
+    // ## Quality Scores
+    cycle(0, 1, 8, 32),
+    cycle(0, 2, 8, 16),
+    cycle(0, 3, 8, 8),
+    cycle(0, 2, 9, 16),
+    cycle(1, 2, 8, 16),
+    cycle(0, 2, 8, 16, perceptual),
 }
 
 #[cfg(test)]
 mod tests {
     use crate::context_spec::{
-        ContextSpec, ContextSpecGenerator, GenericContextSpec, GenericContextSpecGenerator,
-        LightContextSpecGenerator,
+        ContextSpec, ContextSpecGenerator, ContextSpecType, CycleContextSpecGenerator,
+        GenericContextSpec, GenericContextSpecGenerator, LightContextSpecGenerator,
+        LongReadContextSpecGenerator, QScoreScale,
     };
     use crate::fastq::FastqQualityScore;
-    use crate::sequence::Acid;
+    use crate::sequence::{Acid, Symbol};
 
     #[test]
     fn test_context_spec_display() {
@@ -716,4 +1209,133 @@ mod tests {
         generator.update(Acid::C, FastqQualityScore::new(93));
         assert_eq!(generator.current_context(), ContextSpec::new(0x0000FF5C));
     }
+
+    #[test]
+    fn test_q_score_scale_linear() {
+        for q_score in 0..FastqQualityScore::SIZE {
+            let value = QScoreScale::Linear.scale(FastqQualityScore::new(q_score as u8), 16);
+            assert_eq!(value as usize, q_score * 16 / FastqQualityScore::SIZE);
+        }
+    }
+
+    #[test]
+    fn test_q_score_scale_perceptual() {
+        let scale =
+            |q_score, bins| QScoreScale::Perceptual.scale(FastqQualityScore::new(q_score), bins);
+
+        // With a bin for every score, none of them are aliased
+        for q_score in 0..=41 {
+            assert_eq!(scale(q_score, 42), q_score as u32);
+        }
+        assert_eq!(scale(93, 42), 41);
+
+        let values: Vec<u32> = (0..FastqQualityScore::SIZE as u8)
+            .map(|q_score| scale(q_score, 8))
+            .collect();
+        assert_eq!(values[0], 0);
+        assert_eq!(values[1], 1);
+        assert!(values.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert!(values[41..].iter().all(|&value| value == 7));
+    }
+
+    #[test]
+    fn test_light_context_spec_generator_invalid_scale() {
+        let error = LightContextSpecGenerator::<0, 1, 0, 128>::try_with_q_score_scale(
+            8,
+            QScoreScale::Linear,
+        )
+        .unwrap_err();
+        assert_eq!(error.scale(), QScoreScale::Linear);
+        assert_eq!(error.bins(), 128);
+
+        assert!(
+            LightContextSpecGenerator::<0, 1, 0, 64>::try_with_q_score_scale(
+                8,
+                QScoreScale::Perceptual
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_spec_type_generators() {
+        for spec_type in ContextSpecType::VALUES {
+            let mut generator = spec_type.generator(8);
+            generator.update(Acid::A, FastqQualityScore::new(40));
+            assert!(generator.current_context().get() < spec_type.spec_num());
+        }
+    }
+
+    #[test]
+    fn test_generator_position_long_sequence() {
+        let length = 20_000_000;
+        let mut generator = GenericContextSpecGenerator::<0, 0, 8>::new(length);
+        generator.position = length - 1;
+
+        assert_eq!(generator.current_context(), ContextSpec::new(255));
+    }
+
+    #[test]
+    fn test_long_read_context_spec_generator() {
+        let mut generator = LongReadContextSpecGenerator::<0, 0, 3, 16>::new(1_000_000);
+
+        let mut positions = Vec::new();
+        for _ in 0..300 {
+            positions.push(generator.current_context().get());
+            generator.update(Acid::default(), FastqQualityScore::default());
+        }
+
+        assert_eq!(&positions[0..9], &[0, 1, 2, 2, 3, 3, 3, 3, 4]);
+        assert_eq!(positions[63], 6);
+        assert_eq!(positions[64], 7);
+        assert_eq!(positions[299], 7);
+    }
+
+    #[test]
+    fn test_long_read_context_spec_generator_contexts() {
+        let mut light_generator = LightContextSpecGenerator::<2, 2, 0, 16>::new(8);
+        let mut long_generator = LongReadContextSpecGenerator::<2, 2, 2, 16>::new(8);
+
+        for (acid, q_score) in [(Acid::A, 10), (Acid::C, 93), (Acid::N, 0), (Acid::T, 40)] {
+            light_generator.update(acid, FastqQualityScore::new(q_score));
+            long_generator.update(acid, FastqQualityScore::new(q_score));
+
+            let light_spec = light_generator.current_context().get();
+            let long_spec = long_generator.current_context().get();
+            assert_eq!(long_spec >> 2, light_spec);
+        }
+    }
+
+    #[test]
+    fn test_cycle_context_spec_generator() {
+        let mut generator = CycleContextSpecGenerator::<0, 0, 3, 16>::new(150);
+
+        let mut positions = Vec::new();
+        for _ in 0..10 {
+            positions.push(generator.current_context().get());
+            generator.update(Acid::default(), FastqQualityScore::default());
+        }
+
+        assert_eq!(positions, [0, 1, 2, 3, 4, 5, 6, 7, 7, 7]);
+    }
+
+    #[test]
+    fn test_cycle_context_spec_generator_contexts() {
+        let mut light_generator = LightContextSpecGenerator::<2, 2, 0, 16>::new(8);
+        let mut cycle_generator = CycleContextSpecGenerator::<2, 2, 4, 16>::new(8);
+
+        for (position, (acid, q_score)) in
+            [(Acid::A, 10), (Acid::C, 93), (Acid::N, 0), (Acid::T, 40)]
+                .into_iter()
+                .enumerate()
+        {
+            light_generator.update(acid, FastqQualityScore::new(q_score));
+            cycle_generator.update(acid, FastqQualityScore::new(q_score));
+
+            let light_spec = light_generator.current_context().get();
+            let cycle_spec = cycle_generator.current_context().get();
+            assert_eq!(cycle_spec & 0xFFF, light_spec);
+            assert_eq!(cycle_spec >> 12, position as u32 + 1);
+        }
+    }
 }
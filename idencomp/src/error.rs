@@ -0,0 +1,117 @@
+use std::path::PathBuf;
+
+use crate::context::InvalidValueError;
+use crate::context_binning::ContextBinningOptionsError;
+use crate::fastq::reader::FastqReaderError;
+use crate::fastq::writer::FastqWriterError;
+use crate::idn::compressor::{
+    IdnCompressorError, IdnCompressorParamsError, InvalidCompressionQualityError,
+};
+use crate::idn::concat::IdnConcatError;
+use crate::idn::decompressor::IdnDecompressorError;
+use crate::idn::recompress::IdnRecompressError;
+use crate::idn::split::IdnSplitError;
+use crate::model::ModelIdentifierMismatchError;
+use crate::sequence::InvalidQualityScoreError;
+
+/// Error type that encompasses all the errors that can be returned by this
+/// crate.
+///
+/// Each of the more specific error types can be converted into this one, so
+/// the `?` operator can be used to handle errors coming from different parts
+/// of the library in a single function.
+///
+/// # Examples
+/// ```
+/// use idencomp::fastq::reader::FastqReader;
+/// use idencomp::idn::compressor::IdnCompressor;
+///
+/// fn compress(fastq: &[u8]) -> idencomp::Result<Vec<u8>> {
+///     let mut data = Vec::new();
+///     let mut compressor = IdnCompressor::new(&mut data);
+///     for sequence in FastqReader::new(fastq) {
+///         compressor.add_sequence(sequence?)?;
+///     }
+///     compressor.finish()?;
+///
+///     Ok(data)
+/// }
+///
+/// assert!(compress(b"@SEQ\nACGT\n+\n!!!!\n").is_ok());
+/// assert!(matches!(
+///     compress(b"invalid"),
+///     Err(idencomp::Error::FastqReader(_))
+/// ));
+/// ```
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// Error occurred when reading a FASTQ file.
+    #[error(transparent)]
+    FastqReader(#[from] FastqReaderError),
+    /// Error occurred when writing a FASTQ file.
+    #[error(transparent)]
+    FastqWriter(#[from] FastqWriterError),
+    /// Error occurred when compressing an IDN file.
+    #[error(transparent)]
+    IdnCompressor(#[from] IdnCompressorError),
+    /// Invalid IDN compressor parameters have been provided.
+    #[error(transparent)]
+    IdnCompressorParams(#[from] IdnCompressorParamsError),
+    /// Error occurred when decompressing an IDN file.
+    #[error(transparent)]
+    IdnDecompressor(#[from] IdnDecompressorError),
+    /// Error occurred when splitting an IDN file.
+    #[error(transparent)]
+    IdnSplit(#[from] IdnSplitError),
+    /// Error occurred when concatenating IDN files.
+    #[error(transparent)]
+    IdnConcat(#[from] IdnConcatError),
+    /// Error occurred when recompressing an IDN file.
+    #[error(transparent)]
+    IdnRecompress(#[from] IdnRecompressError),
+    /// Error occurred when deserializing a model.
+    #[error("Could not deserialize the model: {0}")]
+    ModelDecode(#[from] rmp_serde::decode::Error),
+    /// Error occurred when serializing a model.
+    #[error("Could not serialize the model: {0}")]
+    ModelEncode(#[from] rmp_serde::encode::Error),
+    /// Error occurred when loading a model from given file.
+    #[error("Could not load the model from `{}`: {source}", path.display())]
+    ModelFile {
+        /// The path to the model file.
+        path: PathBuf,
+        /// The cause of the error.
+        source: Box<Error>,
+    },
+    /// The identifier stored with a model does not match its contents.
+    #[error(transparent)]
+    ModelIdentifierMismatch(#[from] ModelIdentifierMismatchError),
+    /// A float value outside of the allowed range has been provided.
+    #[error(transparent)]
+    InvalidValue(#[from] InvalidValueError),
+    /// A quality score outside of the allowed range has been provided.
+    #[error(transparent)]
+    InvalidQualityScore(#[from] InvalidQualityScoreError),
+    /// An invalid compression quality has been provided.
+    #[error(transparent)]
+    InvalidCompressionQuality(#[from] InvalidCompressionQualityError),
+    /// Invalid context binning options have been provided.
+    #[error(transparent)]
+    ContextBinningOptions(#[from] ContextBinningOptionsError),
+    /// I/O error occurred.
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+impl Error {
+    #[must_use]
+    pub(crate) fn model_file(path: PathBuf, source: Error) -> Self {
+        Self::ModelFile {
+            path,
+            source: Box::new(source),
+        }
+    }
+}
+
+/// The result type used by this crate, with [`Error`] as the error type.
+pub type Result<T> = std::result::Result<T, Error>;
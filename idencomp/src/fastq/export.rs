@@ -0,0 +1,264 @@
+use std::io::Write;
+
+use crate::fastq::consts::{FASTQ_ACID_TO_BYTE, FASTQ_Q_SCORE_TO_BYTE};
+use crate::fastq::writer::FastqWriterError;
+use crate::fastq::FastqSequence;
+
+type ExportResult<T> = Result<T, FastqWriterError>;
+
+/// The format the sequences are exported to by [`SequenceExporter`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum ExportFormat {
+    /// FASTA file with the titles and the acids of the sequences; the quality
+    /// scores are dropped.
+    Fasta,
+    /// One line per sequence with the numeric (Phred) quality scores separated
+    /// with tabs, so that the column `n` is the quality of the cycle `n`. The
+    /// rows of sequences of different lengths have different lengths as well.
+    QualityMatrix,
+    /// CSV table with the `identifier`, `acids` and `quality_scores` columns;
+    /// the quality scores are stored as in FASTQ.
+    Csv,
+}
+
+/// A serializer for [`FastqSequence`] objects that outputs a single aspect of
+/// the data (e.g. only the acids) in a format that is easy to process with
+/// the tools not supporting FASTQ.
+///
+/// # Examples
+/// ```
+/// use idencomp::fastq::export::{ExportFormat, SequenceExporter};
+/// use idencomp::fastq::{FastqQualityScore, FastqSequence};
+/// # use idencomp::fastq::writer::FastqWriterError;
+/// use idencomp::sequence::Acid;
+///
+/// let sequence = FastqSequence::new(
+///     "seq",
+///     [Acid::A, Acid::C],
+///     [FastqQualityScore::new(5), FastqQualityScore::new(10)],
+/// );
+///
+/// let mut buf = Vec::new();
+/// let mut exporter = SequenceExporter::new(&mut buf, ExportFormat::QualityMatrix);
+/// exporter.write_sequence(&sequence)?;
+/// exporter.flush()?;
+/// assert_eq!(String::from_utf8(buf).unwrap(), "5\t10\n");
+///
+/// # Ok::<(), FastqWriterError>(())
+/// ```
+#[derive(Debug)]
+pub struct SequenceExporter<W> {
+    writer: W,
+    format: ExportFormat,
+    header_written: bool,
+}
+
+impl<W: Write> SequenceExporter<W> {
+    /// Creates new `SequenceExporter` instance writing given format.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::fastq::export::{ExportFormat, SequenceExporter};
+    ///
+    /// let mut buf = Vec::new();
+    /// let _exporter = SequenceExporter::new(&mut buf, ExportFormat::Fasta);
+    /// ```
+    #[must_use]
+    pub fn new(writer: W, format: ExportFormat) -> Self {
+        Self {
+            writer,
+            format,
+            header_written: false,
+        }
+    }
+
+    /// Returns the format the sequences are exported to.
+    #[must_use]
+    pub fn format(&self) -> ExportFormat {
+        self.format
+    }
+
+    /// Writes the sequence in the exporter's format.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::fastq::export::{ExportFormat, SequenceExporter};
+    /// use idencomp::fastq::{FastqQualityScore, FastqSequence};
+    /// # use idencomp::fastq::writer::FastqWriterError;
+    /// use idencomp::sequence::Acid;
+    ///
+    /// let mut buf = Vec::new();
+    /// let mut exporter = SequenceExporter::new(&mut buf, ExportFormat::Fasta);
+    /// let sequence = FastqSequence::new("seq", [Acid::A], [FastqQualityScore::new(5)]);
+    /// exporter.write_sequence(&sequence)?;
+    ///
+    /// assert_eq!(String::from_utf8(buf).unwrap(), ">seq\nA\n");
+    ///
+    /// # Ok::<(), FastqWriterError>(())
+    /// ```
+    pub fn write_sequence(&mut self, sequence: &FastqSequence) -> ExportResult<()> {
+        match self.format {
+            ExportFormat::Fasta => self.write_fasta(sequence),
+            ExportFormat::QualityMatrix => self.write_quality_row(sequence),
+            ExportFormat::Csv => self.write_csv_row(sequence),
+        }
+    }
+
+    fn write_fasta(&mut self, sequence: &FastqSequence) -> ExportResult<()> {
+        writeln!(&mut self.writer, ">{}", sequence.identifier())?;
+        self.writer.write_all(&acid_bytes(sequence))?;
+        writeln!(&mut self.writer)?;
+
+        Ok(())
+    }
+
+    fn write_quality_row(&mut self, sequence: &FastqSequence) -> ExportResult<()> {
+        let row = sequence
+            .quality_scores()
+            .iter()
+            .map(|q_score| q_score.get().to_string())
+            .collect::<Vec<_>>()
+            .join("\t");
+        writeln!(&mut self.writer, "{}", row)?;
+
+        Ok(())
+    }
+
+    fn write_csv_row(&mut self, sequence: &FastqSequence) -> ExportResult<()> {
+        if !self.header_written {
+            writeln!(&mut self.writer, "identifier,acids,quality_scores")?;
+            self.header_written = true;
+        }
+
+        let q_score_bytes: Vec<u8> = sequence
+            .quality_scores()
+            .iter()
+            .map(|q_score| FASTQ_Q_SCORE_TO_BYTE[q_score.get()])
+            .collect();
+        let q_scores =
+            String::from_utf8(q_score_bytes).expect("Quality scores should always be valid UTF-8");
+
+        write!(
+            &mut self.writer,
+            "{},",
+            csv_field(sequence.identifier().str())
+        )?;
+        self.writer.write_all(&acid_bytes(sequence))?;
+        writeln!(&mut self.writer, ",{}", csv_field(&q_scores))?;
+
+        Ok(())
+    }
+
+    /// Flushes the internal writer object.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::fastq::export::{ExportFormat, SequenceExporter};
+    /// # use idencomp::fastq::writer::FastqWriterError;
+    ///
+    /// let mut buf = Vec::new();
+    /// let mut exporter = SequenceExporter::new(&mut buf, ExportFormat::Csv);
+    /// exporter.flush()?;
+    ///
+    /// # Ok::<(), FastqWriterError>(())
+    /// ```
+    pub fn flush(&mut self) -> ExportResult<()> {
+        self.writer.flush()?;
+
+        Ok(())
+    }
+}
+
+#[must_use]
+fn acid_bytes(sequence: &FastqSequence) -> Vec<u8> {
+    sequence
+        .acids()
+        .iter()
+        .map(|&acid| FASTQ_ACID_TO_BYTE[acid as usize])
+        .collect()
+}
+
+/// Quotes the field, if needed, as described in RFC 4180.
+#[must_use]
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::slice;
+
+    use crate::_internal_test_data::SIMPLE_TEST_SEQUENCE;
+    use crate::fastq::export::{csv_field, ExportFormat, SequenceExporter};
+    use crate::fastq::{FastqQualityScore, FastqSequence};
+    use crate::sequence::Acid;
+
+    fn export(format: ExportFormat, sequences: &[FastqSequence]) -> String {
+        let mut buf = Vec::new();
+        let mut exporter = SequenceExporter::new(&mut buf, format);
+        for sequence in sequences {
+            exporter.write_sequence(sequence).unwrap();
+        }
+        exporter.flush().unwrap();
+
+        String::from_utf8(buf).unwrap()
+    }
+
+    fn test_sequences() -> [FastqSequence; 2] {
+        [
+            FastqSequence::new(
+                "seq 1",
+                [Acid::A, Acid::C, Acid::N],
+                [
+                    FastqQualityScore::new(2),
+                    FastqQualityScore::new(30),
+                    FastqQualityScore::new(0),
+                ],
+            )
+            .with_separator_text("comment"),
+            FastqSequence::new("seq,2", [Acid::G], [FastqQualityScore::new(1)]),
+        ]
+    }
+
+    #[test]
+    fn test_export_fasta() {
+        assert_eq!(
+            export(ExportFormat::Fasta, &test_sequences()),
+            ">seq 1\nACN\n>seq,2\nG\n"
+        );
+    }
+
+    #[test]
+    fn test_export_quality_matrix() {
+        assert_eq!(
+            export(ExportFormat::QualityMatrix, &test_sequences()),
+            "2\t30\t0\n1\n"
+        );
+    }
+
+    #[test]
+    fn test_export_csv() {
+        assert_eq!(
+            export(ExportFormat::Csv, &test_sequences()),
+            "identifier,acids,quality_scores\nseq 1,ACN,#?!\n\"seq,2\",G,\"\"\"\"\n"
+        );
+        assert_eq!(
+            export(ExportFormat::Csv, slice::from_ref(&*SIMPLE_TEST_SEQUENCE))
+                .lines()
+                .count(),
+            2
+        );
+        assert_eq!(export(ExportFormat::Csv, &[]), "");
+    }
+
+    #[test]
+    fn test_csv_field() {
+        assert_eq!(csv_field("abc"), "abc");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("a\"b"), "\"a\"\"b\"");
+    }
+}
@@ -1,4 +1,8 @@
 mod consts;
+/// Export of the sequences to non-FASTQ formats.
+pub mod export;
+/// Parallel FASTQ reader.
+pub mod parallel_reader;
 /// FASTQ reader.
 pub mod reader;
 /// FASTQ writer.
@@ -0,0 +1,246 @@
+use std::io::BufRead;
+use std::num::NonZeroUsize;
+use std::vec;
+
+use crate::fastq::reader::{
+    read_until_delimiter, FastqReader, FastqReaderError, FastqReaderParams, FastqResult,
+};
+use crate::fastq::FastqSequence;
+use crate::parallel::{IntoParallelIterator, ParallelIterator};
+
+/// The number of lines a single FASTQ record consists of.
+const LINES_PER_RECORD: usize = 4;
+
+/// FASTQ format reader that splits the input into chunks containing whole
+/// records and parses them on multiple threads.
+///
+/// The sequences are returned in the same order as in the input, so this can
+/// be used as a drop-in replacement for [`FastqReader`] when the parsing
+/// becomes the bottleneck (e.g. when feeding
+/// [`IdnCompressor`](crate::idn::compressor::IdnCompressor) on a machine with
+/// many cores).
+#[derive(Debug)]
+pub struct ParallelFastqReader<R> {
+    reader: R,
+    params: FastqReaderParams,
+    chunks_per_batch: usize,
+    sequences: vec::IntoIter<FastqSequence>,
+    error: Option<FastqReaderError>,
+    finished: bool,
+}
+
+impl<R: BufRead> ParallelFastqReader<R> {
+    /// Creates new `ParallelFastqReader` instance with default parameters.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::fastq::parallel_reader::ParallelFastqReader;
+    ///
+    /// let buf = Vec::new();
+    /// let _reader = ParallelFastqReader::new(buf.as_slice());
+    /// ```
+    #[must_use]
+    pub fn new(reader: R) -> Self {
+        Self::with_params(reader, FastqReaderParams::default())
+    }
+
+    /// Creates new `ParallelFastqReader` instance with given parameters.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::fastq::parallel_reader::ParallelFastqReader;
+    /// use idencomp::fastq::reader::FastqReaderParams;
+    ///
+    /// let buf = Vec::new();
+    /// let params = FastqReaderParams::builder().chunk_size(1024).build();
+    /// let _reader = ParallelFastqReader::with_params(buf.as_slice(), params);
+    /// ```
+    #[must_use]
+    pub fn with_params(reader: R, params: FastqReaderParams) -> Self {
+        let chunks_per_batch = if cfg!(feature = "parallel") {
+            std::thread::available_parallelism().map_or(1, NonZeroUsize::get)
+        } else {
+            1
+        };
+
+        Self {
+            reader,
+            params,
+            chunks_per_batch,
+            sequences: Vec::new().into_iter(),
+            error: None,
+            finished: false,
+        }
+    }
+
+    fn read_batch(&mut self) -> FastqResult<()> {
+        let mut chunks = Vec::with_capacity(self.chunks_per_batch);
+        while chunks.len() < self.chunks_per_batch {
+            let chunk = self.read_chunk()?;
+            if chunk.is_empty() {
+                self.finished = true;
+                break;
+            }
+            chunks.push(chunk);
+        }
+
+        let params = &self.params;
+        let results: Vec<_> = chunks
+            .into_par_iter()
+            .map(|chunk| parse_chunk(&chunk, params))
+            .collect();
+
+        let mut sequences = Vec::new();
+        for (chunk_sequences, error) in results {
+            sequences.extend(chunk_sequences);
+            if error.is_some() {
+                self.error = error;
+                self.finished = true;
+                break;
+            }
+        }
+        self.sequences = sequences.into_iter();
+
+        Ok(())
+    }
+
+    /// Reads whole records until the chunk size is reached. The records are
+    /// split by counting the lines, as the quality score line can start with
+    /// the title prefix as well.
+    fn read_chunk(&mut self) -> FastqResult<Vec<u8>> {
+        let delimiter = self.params.delimiter();
+        let chunk_size = self.params.chunk_size();
+
+        let mut chunk = Vec::with_capacity(chunk_size);
+        let mut record_line = 0;
+        loop {
+            let line_start = chunk.len();
+            let bytes_read = read_until_delimiter(&mut self.reader, delimiter, &mut chunk)?;
+            if bytes_read == 0 {
+                break;
+            }
+
+            // Empty lines are skipped by the reader before the title
+            let line = &chunk[line_start..];
+            let is_empty = line
+                .iter()
+                .all(|ch| delimiter.contains(ch) || ch.is_ascii_whitespace());
+            if record_line == 0 && is_empty {
+                continue;
+            }
+
+            record_line = (record_line + 1) % LINES_PER_RECORD;
+            if record_line == 0 && chunk.len() >= chunk_size {
+                break;
+            }
+        }
+
+        Ok(chunk)
+    }
+}
+
+fn parse_chunk(
+    chunk: &[u8],
+    params: &FastqReaderParams,
+) -> (Vec<FastqSequence>, Option<FastqReaderError>) {
+    let mut sequences = Vec::new();
+    for sequence in FastqReader::with_params(chunk, params.clone()) {
+        match sequence {
+            Ok(sequence) => sequences.push(sequence),
+            Err(e) => return (sequences, Some(e)),
+        }
+    }
+
+    (sequences, None)
+}
+
+impl<R: BufRead> Iterator for ParallelFastqReader<R> {
+    type Item = FastqResult<FastqSequence>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(sequence) = self.sequences.next() {
+                return Some(Ok(sequence));
+            }
+            if let Some(error) = self.error.take() {
+                return Some(Err(error));
+            }
+            if self.finished {
+                return None;
+            }
+
+            if let Err(e) = self.read_batch() {
+                self.finished = true;
+                return Some(Err(e));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::_internal_test_data::{SEQ_1K_READS_FASTQ, SIMPLE_TEST_SEQUENCE_STR};
+    use crate::fastq::parallel_reader::ParallelFastqReader;
+    use crate::fastq::reader::{FastqReader, FastqReaderError, FastqReaderParams};
+
+    #[test]
+    fn test_same_as_fastq_reader() {
+        let expected: Vec<_> = FastqReader::new(SEQ_1K_READS_FASTQ)
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+
+        for chunk_size in [1, 1000, 64 * 1024, 4 * 1024 * 1024] {
+            let params = FastqReaderParams::builder().chunk_size(chunk_size).build();
+            let result: Result<Vec<_>, _> =
+                ParallelFastqReader::with_params(SEQ_1K_READS_FASTQ, params).collect();
+
+            assert_eq!(result.unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_empty_lines() {
+        let data = format!(
+            "\n\n{}\n\n{}\n",
+            SIMPLE_TEST_SEQUENCE_STR, SIMPLE_TEST_SEQUENCE_STR
+        );
+        let params = FastqReaderParams::builder().chunk_size(1).build();
+        let result: Result<Vec<_>, _> =
+            ParallelFastqReader::with_params(data.as_bytes(), params).collect();
+
+        assert_eq!(result.unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_crlf() {
+        let data = String::from_utf8_lossy(SEQ_1K_READS_FASTQ).replace('\n', "\r\n");
+        let params = FastqReaderParams::builder().chunk_size(1000).build();
+        let result: Result<Vec<_>, _> =
+            ParallelFastqReader::with_params(data.as_bytes(), params).collect();
+
+        assert_eq!(result.unwrap().len(), 1000);
+    }
+
+    #[test]
+    fn test_empty() {
+        let reader = "".as_bytes();
+        let vec: Vec<_> = ParallelFastqReader::new(reader).collect();
+
+        assert!(vec.is_empty(), "results not empty: {:?}", vec);
+    }
+
+    #[test]
+    fn test_error() {
+        let data = format!("{}\n@seq\nX\n+\n!\n", SIMPLE_TEST_SEQUENCE_STR);
+        let params = FastqReaderParams::builder().chunk_size(1).build();
+        let mut reader = ParallelFastqReader::with_params(data.as_bytes(), params);
+
+        assert!(reader.next().unwrap().is_ok());
+        assert!(matches!(
+            reader.next().unwrap(),
+            Err(FastqReaderError::InvalidAcid('X'))
+        ));
+        assert!(reader.next().is_none());
+    }
+}
@@ -1,6 +1,8 @@
 use std::error::Error;
 use std::fmt::{Debug, Display, Formatter};
-use std::io::BufRead;
+use std::io::{BufRead, ErrorKind};
+
+use memchr::memchr;
 
 use crate::fastq::consts::{
     FASTQ_BYTE_TO_ACID, FASTQ_BYTE_TO_Q_SCORE, FASTQ_VALID_ACID_BYTES, FASTQ_VALID_Q_SCORE_BYTES,
@@ -66,27 +68,91 @@ pub type FastqResult<T> = Result<T, FastqReaderError>;
 /// A builder for `FastqReaderParams`.
 #[derive(Debug, Clone)]
 pub struct FastqReaderParamsBuilder {
-    delimiter: u8,
+    delimiter: Vec<u8>,
+    chunk_size: usize,
+    preserve_separator: bool,
 }
 
 impl FastqReaderParamsBuilder {
     /// Returns a new instance of `FastqReaderParamsBuilder`.
     #[must_use]
     pub fn new() -> Self {
-        Self { delimiter: b'\n' }
+        Self {
+            delimiter: vec![b'\n'],
+            chunk_size: 4 * 1024 * 1024,
+            preserve_separator: false,
+        }
     }
 
     /// Sets the delimiter character to use instead of a newline.
     pub fn delimiter(&mut self, delimiter: u8) -> &mut Self {
+        self.delimiter_bytes(&[delimiter])
+    }
+
+    /// Sets the (possibly multi-byte) delimiter to use instead of a newline.
+    /// Any carriage returns before the delimiter are ignored, so the files
+    /// with the `\r\n` line endings can be read with the default delimiter.
+    ///
+    /// # Panics
+    /// Panics if `delimiter` is empty.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::fastq::reader::{FastqReader, FastqReaderParams};
+    ///
+    /// let params = FastqReaderParams::builder().delimiter_bytes(b"||").build();
+    /// let mut reader = FastqReader::with_params(b"@SEQ||AC||+||!!||".as_slice(), params);
+    /// assert_eq!(reader.read_sequence()?.len(), 2);
+    ///
+    /// # Ok::<(), idencomp::fastq::reader::FastqReaderError>(())
+    /// ```
+    pub fn delimiter_bytes(&mut self, delimiter: &[u8]) -> &mut Self {
+        assert!(!delimiter.is_empty(), "The delimiter cannot be empty");
+
         let mut new = self;
-        new.delimiter = delimiter;
+        new.delimiter = delimiter.to_vec();
+        new
+    }
+
+    /// Sets the approximate size (in bytes) of the chunks the input is split
+    /// into by [`ParallelFastqReader`](super::parallel_reader::ParallelFastqReader).
+    pub fn chunk_size(&mut self, chunk_size: usize) -> &mut Self {
+        let new = self;
+        new.chunk_size = chunk_size;
         new
     }
 
+    /// Sets whether the text following the quality score separator (`+`)
+    /// should be preserved rather than discarded. The text is kept as the
+    /// [separator text](FastqSequence::separator_text) of the sequence, so it
+    /// is stored along with the identifiers when compressed, and
+    /// [`FastqWriter`](super::writer::FastqWriter) writes the separator line
+    /// back exactly as it has been read.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::fastq::reader::{FastqReader, FastqReaderParams};
+    ///
+    /// let data = b"@SEQ\nAC\n+comment\n!!\n";
+    /// let params = FastqReaderParams::builder()
+    ///     .preserve_separator(true)
+    ///     .build();
+    /// let sequence = FastqReader::with_params(data.as_slice(), params).read_sequence()?;
+    /// assert_eq!(sequence.to_string().as_bytes(), data);
+    ///
+    /// # Ok::<(), idencomp::fastq::reader::FastqReaderError>(())
+    /// ```
+    pub fn preserve_separator(&mut self, preserve_separator: bool) -> &mut Self {
+        self.preserve_separator = preserve_separator;
+        self
+    }
+
     /// Builds and returns [`FastqReaderParams`].
     pub fn build(&self) -> FastqReaderParams {
         FastqReaderParams {
-            delimiter: self.delimiter,
+            delimiter: self.delimiter.clone(),
+            chunk_size: self.chunk_size,
+            preserve_separator: self.preserve_separator,
         }
     }
 }
@@ -100,7 +166,9 @@ impl Default for FastqReaderParamsBuilder {
 /// FASTQ reading params.
 #[derive(Debug, Clone)]
 pub struct FastqReaderParams {
-    delimiter: u8,
+    delimiter: Vec<u8>,
+    chunk_size: usize,
+    preserve_separator: bool,
 }
 
 impl FastqReaderParams {
@@ -109,6 +177,14 @@ impl FastqReaderParams {
     pub fn builder() -> FastqReaderParamsBuilder {
         FastqReaderParamsBuilder::new()
     }
+
+    pub(super) fn delimiter(&self) -> &[u8] {
+        &self.delimiter
+    }
+
+    pub(super) fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
 }
 
 impl Default for FastqReaderParams {
@@ -167,7 +243,12 @@ impl<R: BufRead> FastqReader<R> {
         self.bytes_read = 0;
         let title = self.parse_title()?;
         let acids = self.parse_acids()?;
-        self.parse_separator()?;
+        let separator_text = if self.params.preserve_separator {
+            self.parse_separator_text()?.to_vec()
+        } else {
+            self.parse_separator()?;
+            Vec::new()
+        };
         let quality_scores = self.parse_quality_scores()?;
 
         if acids.len() != quality_scores.len() {
@@ -175,7 +256,8 @@ impl<R: BufRead> FastqReader<R> {
         }
 
         let seq =
-            FastqSequence::with_size(title, acids, quality_scores, ByteNum::new(self.bytes_read));
+            FastqSequence::with_size(title, acids, quality_scores, ByteNum::new(self.bytes_read))
+                .with_separator_text(separator_text);
         Ok(seq)
     }
 
@@ -184,7 +266,7 @@ impl<R: BufRead> FastqReader<R> {
         let line = loop {
             let line = Self::read_line(
                 &mut self.reader,
-                self.params.delimiter,
+                &self.params.delimiter,
                 &mut self.buffer,
                 &mut self.bytes_read,
             )?;
@@ -207,28 +289,39 @@ impl<R: BufRead> FastqReader<R> {
     pub fn parse_acids(&mut self) -> FastqResult<Vec<Acid>> {
         let line = Self::read_line(
             &mut self.reader,
-            self.params.delimiter,
+            &self.params.delimiter,
             &mut self.buffer,
             &mut self.bytes_read,
         )?;
 
-        let mut acids = Vec::with_capacity(line.len());
-        for &ch in line {
-            if FASTQ_VALID_ACID_BYTES[ch as usize] {
-                acids.push(FASTQ_BYTE_TO_ACID[ch as usize]);
-            } else {
-                return Err(FastqReaderError::InvalidAcid(ch as char));
-            }
+        // Validation and translation are done in separate, branchless passes
+        // so that the compiler can vectorize them
+        let valid = line.iter().fold(true, |valid, &ch| {
+            valid & FASTQ_VALID_ACID_BYTES[ch as usize]
+        });
+        if !valid {
+            let ch = Self::first_invalid_byte(line, &FASTQ_VALID_ACID_BYTES);
+            return Err(FastqReaderError::InvalidAcid(ch as char));
         }
 
+        let acids = line
+            .iter()
+            .map(|&ch| FASTQ_BYTE_TO_ACID[ch as usize])
+            .collect();
         Ok(acids)
     }
 
     /// Reads acid-quality score separator from given FASTQ file.
     pub fn parse_separator(&mut self) -> FastqResult<()> {
+        self.parse_separator_text().map(|_| ())
+    }
+
+    /// Reads acid-quality score separator from given FASTQ file, returning
+    /// the text following the `+` character.
+    fn parse_separator_text(&mut self) -> FastqResult<&[u8]> {
         let line = Self::read_line(
             &mut self.reader,
-            self.params.delimiter,
+            &self.params.delimiter,
             &mut self.buffer,
             &mut self.bytes_read,
         )?;
@@ -236,52 +329,120 @@ impl<R: BufRead> FastqReader<R> {
             return Err(FastqReaderError::InvalidFormat);
         }
 
-        Ok(())
+        Ok(&line[1..])
     }
 
     /// Reads the quality score list from given FASTQ file.
     pub fn parse_quality_scores(&mut self) -> FastqResult<Vec<FastqQualityScore>> {
         let line = Self::read_line(
             &mut self.reader,
-            self.params.delimiter,
+            &self.params.delimiter,
             &mut self.buffer,
             &mut self.bytes_read,
         )?;
-        let mut quality_scores = Vec::with_capacity(line.len());
 
-        for &ch in line {
-            if FASTQ_VALID_Q_SCORE_BYTES[ch as usize] {
-                quality_scores.push(FASTQ_BYTE_TO_Q_SCORE[ch as usize]);
-            } else {
-                return Err(FastqReaderError::InvalidQualityScore(ch as char));
-            }
+        let valid = line.iter().fold(true, |valid, &ch| {
+            valid & FASTQ_VALID_Q_SCORE_BYTES[ch as usize]
+        });
+        if !valid {
+            let ch = Self::first_invalid_byte(line, &FASTQ_VALID_Q_SCORE_BYTES);
+            return Err(FastqReaderError::InvalidQualityScore(ch as char));
         }
 
+        let quality_scores = line
+            .iter()
+            .map(|&ch| FASTQ_BYTE_TO_Q_SCORE[ch as usize])
+            .collect();
         Ok(quality_scores)
     }
 
+    fn first_invalid_byte(line: &[u8], valid_bytes: &[bool; 256]) -> u8 {
+        *line
+            .iter()
+            .find(|&&ch| !valid_bytes[ch as usize])
+            .expect("Line should contain an invalid byte")
+    }
+
     fn read_line<'a, T: BufRead>(
         mut buf_reader: T,
-        delimiter: u8,
+        delimiter: &[u8],
         buffer: &'a mut Vec<u8>,
         total_bytes_read: &mut usize,
     ) -> FastqResult<&'a [u8]> {
         buffer.clear();
-        let bytes_read = buf_reader.read_until(delimiter, buffer)?;
+        let bytes_read = read_until_delimiter(&mut buf_reader, delimiter, buffer)?;
         if bytes_read == 0 {
             return Err(FastqReaderError::EofReached);
         }
         *total_bytes_read += bytes_read;
 
         let mut buffer = buffer.as_slice();
-        while buffer.last().copied() == Some(delimiter) {
-            buffer = &buffer[..buffer.len() - 1];
+        while let Some(line) = buffer.strip_suffix(delimiter) {
+            buffer = line;
+        }
+        // Windows line endings
+        while let Some(line) = buffer.strip_suffix(b"\r") {
+            buffer = line;
         }
 
         Ok(buffer)
     }
 }
 
+/// Reads the bytes into `buffer` until the (possibly multi-byte) `delimiter`
+/// (inclusive) or EOF is reached.
+pub(super) fn read_until_delimiter<R: BufRead>(
+    reader: &mut R,
+    delimiter: &[u8],
+    buffer: &mut Vec<u8>,
+) -> std::io::Result<usize> {
+    let (&last, _) = delimiter
+        .split_last()
+        .expect("The delimiter cannot be empty");
+    let start = buffer.len();
+    loop {
+        let bytes_read = read_until(reader, last, buffer)?;
+        if bytes_read == 0 || buffer[start..].ends_with(delimiter) {
+            return Ok(buffer.len() - start);
+        }
+    }
+}
+
+/// Reads the bytes into `buffer` until `delimiter` (inclusive) or EOF is
+/// reached. This is equivalent to [`BufRead::read_until()`], but uses `memchr`
+/// to find the delimiter.
+fn read_until<R: BufRead>(
+    reader: &mut R,
+    delimiter: u8,
+    buffer: &mut Vec<u8>,
+) -> std::io::Result<usize> {
+    let mut bytes_read = 0;
+    loop {
+        let available = match reader.fill_buf() {
+            Ok(available) => available,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        if available.is_empty() {
+            break;
+        }
+
+        let (found, used) = match memchr(delimiter, available) {
+            Some(index) => (true, index + 1),
+            None => (false, available.len()),
+        };
+        buffer.extend_from_slice(&available[..used]);
+        reader.consume(used);
+        bytes_read += used;
+
+        if found {
+            break;
+        }
+    }
+
+    Ok(bytes_read)
+}
+
 impl<R: BufRead> IntoIterator for FastqReader<R> {
     type Item = FastqResult<FastqSequence>;
     type IntoIter = FastqReaderIterator<R>;
@@ -324,13 +485,14 @@ impl<R: BufRead> Iterator for FastqReaderIterator<R> {
 #[cfg(test)]
 mod tests {
     use std::error::Error;
+    use std::io::BufReader;
     use std::io::ErrorKind::NotFound;
 
     use crate::_internal_test_data::{
         EMPTY_TEST_SEQUENCE, EMPTY_TEST_SEQUENCE_STR, SEQ_1K_READS_FASTQ, SEQ_1M_FASTQ,
-        SIMPLE_TEST_SEQUENCE, SIMPLE_TEST_SEQUENCE_STR,
+        SIMPLE_TEST_SEQUENCE, SIMPLE_TEST_SEQUENCE_SEPARATOR_TITLE_STR, SIMPLE_TEST_SEQUENCE_STR,
     };
-    use crate::fastq::reader::{FastqReader, FastqReaderError};
+    use crate::fastq::reader::{FastqReader, FastqReaderError, FastqReaderParams};
 
     #[test]
     fn should_return_empty_seq() {
@@ -393,6 +555,107 @@ A
         assert!(sequences.iter().all(|seq| seq.len() == 76));
     }
 
+    #[test]
+    fn test_read_small_buffer() {
+        let reader = BufReader::with_capacity(3, SEQ_1K_READS_FASTQ);
+        let result: Result<Vec<_>, _> = FastqReader::new(reader).into_iter().collect();
+        let sequences = result.unwrap();
+
+        let expected: Vec<_> = FastqReader::new(SEQ_1K_READS_FASTQ)
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(sequences, expected);
+    }
+
+    #[test]
+    fn should_return_first_invalid_character() {
+        let reader = "@seq
+ACGTXNY
++
+!!!!!!!"
+            .as_bytes();
+        let sequence = FastqReader::new(reader).read_sequence().unwrap_err();
+        assert!(matches!(sequence, FastqReaderError::InvalidAcid('X')));
+
+        let reader = "@seq
+ACGT
++
+!!\x07\x08"
+            .as_bytes();
+        let sequence = FastqReader::new(reader).read_sequence().unwrap_err();
+        assert!(matches!(
+            sequence,
+            FastqReaderError::InvalidQualityScore('\x07')
+        ));
+    }
+
+    #[test]
+    fn test_read_crlf() {
+        let data = String::from_utf8_lossy(SEQ_1K_READS_FASTQ).replace('\n', "\r\n");
+        let result: Result<Vec<_>, _> = FastqReader::new(data.as_bytes()).into_iter().collect();
+        let sequences = result.unwrap();
+
+        let expected: Vec<_> = FastqReader::new(SEQ_1K_READS_FASTQ)
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(sequences, expected);
+        assert_eq!(sequences[0].size().get(), expected[0].size().get() + 4);
+    }
+
+    #[test]
+    fn test_read_preserve_separator() {
+        let params = FastqReaderParams::builder()
+            .preserve_separator(true)
+            .build();
+        let read = |data: &str| {
+            FastqReader::with_params(data.as_bytes(), params.clone())
+                .read_sequence()
+                .unwrap()
+        };
+
+        let sequence = read(SIMPLE_TEST_SEQUENCE_STR);
+        assert_eq!(sequence, *SIMPLE_TEST_SEQUENCE);
+
+        let sequence = read(SIMPLE_TEST_SEQUENCE_SEPARATOR_TITLE_STR);
+        assert_eq!(sequence.identifier().str(), "SEQ_ID");
+        assert_eq!(sequence.separator_text(), b"SEQ_ID");
+        assert_eq!(
+            sequence.to_fastq_string(),
+            SIMPLE_TEST_SEQUENCE_SEPARATOR_TITLE_STR
+        );
+
+        let data = SIMPLE_TEST_SEQUENCE_STR.replace("\n+\n", "\n+ length=60\n");
+        let sequence = read(data.as_str());
+        assert_eq!(sequence.identifier().str(), "SEQ_ID");
+        assert_eq!(sequence.separator_text(), b" length=60");
+        assert_eq!(sequence.to_fastq_string(), data);
+
+        let data = SIMPLE_TEST_SEQUENCE_STR.replace("SEQ_ID", "SEQ\u{1f}ID");
+        let sequence = read(data.as_str());
+        assert_eq!(sequence.identifier().str(), "SEQ\u{1f}ID");
+        assert_eq!(sequence.to_fastq_string(), data);
+
+        let sequence = FastqReader::new(data.as_bytes()).read_sequence().unwrap();
+        assert_eq!(sequence.identifier().str(), "SEQ\u{1f}ID");
+        assert!(sequence.separator_text().is_empty());
+    }
+
+    #[test]
+    fn test_read_multi_byte_delimiter() {
+        let data = SIMPLE_TEST_SEQUENCE_STR.replace('\n', "\n;;\n");
+        let params = FastqReaderParams::builder()
+            .delimiter_bytes(b"\n;;\n")
+            .build();
+        let sequence =
+            FastqReader::with_params(BufReader::with_capacity(3, data.as_bytes()), params)
+                .read_sequence()
+                .unwrap();
+
+        assert_eq!(sequence, *SIMPLE_TEST_SEQUENCE);
+    }
+
     #[test]
     fn test_read_1mb() {
         let mut reader = FastqReader::new(SEQ_1M_FASTQ);
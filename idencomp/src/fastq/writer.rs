@@ -89,7 +89,8 @@ impl FastqWriterParamsBuilder {
     }
 
     /// Whether the FASTQ writer should write the sequence names along with the
-    /// separators.
+    /// separators. The [separator text](FastqSequence::separator_text) of
+    /// a sequence, if not empty, is always written instead.
     ///
     /// # Examples
     /// ```
@@ -190,7 +191,7 @@ impl<W: Write> FastqWriter<W> {
     pub fn write_sequence(&mut self, fastq_sequence: &FastqSequence) -> FastqWriteResult<()> {
         self.output_title(fastq_sequence)?;
         self.output_acids(fastq_sequence.acids())?;
-        self.output_quality_scores_separator(&fastq_sequence.identifier().0)?;
+        self.output_quality_scores_separator(fastq_sequence)?;
         self.output_quality_scores(fastq_sequence.quality_scores())?;
 
         Ok(())
@@ -218,14 +219,20 @@ impl<W: Write> FastqWriter<W> {
         Ok(())
     }
 
-    fn output_quality_scores_separator(&mut self, identifier: &str) -> FastqWriteResult<()> {
+    fn output_quality_scores_separator(
+        &mut self,
+        fastq_sequence: &FastqSequence,
+    ) -> FastqWriteResult<()> {
         write!(
             &mut self.writer,
             "{}",
             FASTQ_QUALITY_SCORE_SEPARATOR as char
         )?;
-        if self.params.output_title_with_separator {
-            write!(&mut self.writer, "{}", identifier)?;
+        let separator_text = fastq_sequence.separator_text();
+        if !separator_text.is_empty() {
+            self.writer.write_all(separator_text)?;
+        } else if self.params.output_title_with_separator {
+            write!(&mut self.writer, "{}", fastq_sequence.identifier())?;
         }
         writeln!(&mut self.writer)?;
 
@@ -266,6 +273,47 @@ impl<W: Write> FastqWriter<W> {
     }
 }
 
+impl FastqSequence {
+    /// Returns the sequence serialized as FASTQ, in the same way as
+    /// [`FastqWriter`] with the default parameters would write it.
+    ///
+    /// The same result can be obtained using the [`Display`] implementation.
+    /// A [separator text](Self::separator_text) that is not valid UTF-8 is
+    /// converted lossily; use [`FastqWriter`] to write it exactly.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::fastq::{FastqQualityScore, FastqSequence};
+    /// use idencomp::sequence::Acid;
+    ///
+    /// let sequence = FastqSequence::new(
+    ///     "seq",
+    ///     [Acid::A, Acid::C],
+    ///     [FastqQualityScore::new(5), FastqQualityScore::new(10)],
+    /// );
+    /// assert_eq!(sequence.to_fastq_string(), "@seq\nAC\n+\n&+\n");
+    /// assert_eq!(sequence.to_string(), "@seq\nAC\n+\n&+\n");
+    /// ```
+    #[must_use]
+    pub fn to_fastq_string(&self) -> String {
+        let mut buf = Vec::new();
+        FastqWriter::new(&mut buf)
+            .write_sequence(self)
+            .expect("Writing to a Vec should never fail");
+
+        match String::from_utf8(buf) {
+            Ok(fastq) => fastq,
+            Err(e) => String::from_utf8_lossy(e.as_bytes()).into_owned(),
+        }
+    }
+}
+
+impl Display for FastqSequence {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.to_fastq_string())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::error::Error;
@@ -327,6 +375,44 @@ mod tests {
         );
     }
 
+    #[test]
+    fn should_return_simple_seq_with_preserved_separator() {
+        let sequence = SIMPLE_TEST_SEQUENCE
+            .clone()
+            .with_separator_text(*b"comment\xff");
+        let mut buf = Vec::new();
+        FastqWriter::new(&mut buf)
+            .write_sequence(&sequence)
+            .unwrap();
+
+        let mut expected = SIMPLE_TEST_SEQUENCE_STR.as_bytes().to_vec();
+        let separator_pos = expected.iter().position(|&byte| byte == b'+').unwrap();
+        expected.splice(
+            separator_pos + 1..separator_pos + 1,
+            b"comment\xff".iter().copied(),
+        );
+        assert_eq!(buf, expected);
+
+        let sequence = SIMPLE_TEST_SEQUENCE.clone().with_separator_text("SEQ_ID");
+        assert_eq!(
+            sequence.to_fastq_string(),
+            SIMPLE_TEST_SEQUENCE_SEPARATOR_TITLE_STR
+        );
+    }
+
+    #[test]
+    fn test_to_fastq_string() {
+        assert_eq!(
+            EMPTY_TEST_SEQUENCE.to_fastq_string(),
+            EMPTY_TEST_SEQUENCE_STR
+        );
+        assert_eq!(
+            SIMPLE_TEST_SEQUENCE.to_fastq_string(),
+            SIMPLE_TEST_SEQUENCE_STR
+        );
+        assert_eq!(SIMPLE_TEST_SEQUENCE.to_string(), SIMPLE_TEST_SEQUENCE_STR);
+    }
+
     #[test]
     fn test_write_1mb() {
         let mut buf = Vec::new();
@@ -0,0 +1,363 @@
+use std::collections::BTreeMap;
+use std::ops::RangeInclusive;
+
+use arbitrary::{Arbitrary, Unstructured};
+use proptest::arbitrary::any;
+use proptest::strategy::{BoxedStrategy, Just, Strategy};
+
+use crate::context::{Context, Probability};
+use crate::context_binning::ComplexContext;
+use crate::context_spec::{ContextSpec, ContextSpecType};
+use crate::fastq::{FastqQualityScore, FastqSequence, FASTQ_Q_END};
+use crate::model::{Model, ModelType};
+use crate::sequence::{Acid, Symbol};
+
+/// Characters the generated sequence identifiers consist of; printable ASCII
+/// without whitespace, so that the identifiers survive the FASTQ round trip.
+const IDENTIFIER_CHARS: RangeInclusive<u8> = b'!'..=b'~';
+const MAX_IDENTIFIER_LEN: usize = 64;
+const MAX_SEQUENCE_LEN: usize = 512;
+const MAX_MODEL_CONTEXTS: usize = 64;
+
+/// Returns a [`proptest`] strategy generating [`FastqSequence`]s.
+///
+/// # Examples
+/// ```
+/// use idencomp::fuzzing::fastq_sequence;
+/// use proptest::strategy::{Strategy, ValueTree};
+/// use proptest::test_runner::TestRunner;
+///
+/// let mut runner = TestRunner::default();
+/// let sequence = fastq_sequence().new_tree(&mut runner).unwrap().current();
+/// assert_eq!(sequence.acids().len(), sequence.quality_scores().len());
+/// ```
+pub fn fastq_sequence() -> impl Strategy<Value = FastqSequence> {
+    let identifier = proptest::collection::vec(IDENTIFIER_CHARS, 0..=MAX_IDENTIFIER_LEN);
+    let symbols = proptest::collection::vec((0..Acid::SIZE, 0..FASTQ_Q_END), 0..=MAX_SEQUENCE_LEN);
+
+    (identifier, symbols).prop_map(|(identifier, symbols)| {
+        let (acids, quality_scores): (Vec<_>, Vec<_>) = symbols
+            .into_iter()
+            .map(|(acid, q_score)| make_symbols(acid, q_score))
+            .unzip();
+        FastqSequence::new(make_identifier(identifier), acids, quality_scores)
+    })
+}
+
+/// Returns a [`proptest`] strategy generating [`Context`]s with given number
+/// of symbols.
+///
+/// # Examples
+/// ```
+/// use idencomp::fuzzing::context;
+/// use proptest::strategy::{Strategy, ValueTree};
+/// use proptest::test_runner::TestRunner;
+///
+/// let mut runner = TestRunner::default();
+/// let context = context(5).new_tree(&mut runner).unwrap().current();
+/// assert_eq!(context.symbol_num(), 5);
+/// ```
+pub fn context(symbol_num: usize) -> impl Strategy<Value = Context> {
+    (
+        any::<u8>(),
+        proptest::collection::vec(any::<u16>(), symbol_num),
+    )
+        .prop_map(|(context_prob, weights)| make_context(context_prob, &weights))
+}
+
+/// Returns a [`proptest`] strategy generating [`Model`]s of given type.
+///
+/// # Examples
+/// ```
+/// use idencomp::fuzzing::model;
+/// use idencomp::model::ModelType;
+/// use proptest::strategy::{Strategy, ValueTree};
+/// use proptest::test_runner::TestRunner;
+///
+/// let mut runner = TestRunner::default();
+/// let model = model(ModelType::Acids)
+///     .new_tree(&mut runner)
+///     .unwrap()
+///     .current();
+/// assert_eq!(model.model_type(), ModelType::Acids);
+/// ```
+pub fn model(model_type: ModelType) -> impl Strategy<Value = Model> {
+    proptest::sample::select(ContextSpecType::VALUES.to_vec()).prop_flat_map(move |spec_type| {
+        let context_num = max_context_num(spec_type);
+        proptest::collection::btree_map(
+            0..spec_type.spec_num(),
+            context(model_type.symbols_num()),
+            0..=context_num,
+        )
+        .prop_map(move |contexts| make_model(model_type, spec_type, contexts))
+    })
+}
+
+/// Returns a [`proptest`] strategy generating [`ContextSpec`]s.
+///
+/// # Examples
+/// ```
+/// use idencomp::fuzzing::context_spec;
+/// use proptest::strategy::{Strategy, ValueTree};
+/// use proptest::test_runner::TestRunner;
+///
+/// let mut runner = TestRunner::default();
+/// let _spec = context_spec().new_tree(&mut runner).unwrap().current();
+/// ```
+pub fn context_spec() -> impl Strategy<Value = ContextSpec> {
+    any::<u32>().prop_map(ContextSpec::new)
+}
+
+#[must_use]
+fn make_identifier(bytes: Vec<u8>) -> String {
+    bytes.into_iter().map(char::from).collect()
+}
+
+#[must_use]
+fn make_symbols(acid: usize, q_score: usize) -> (Acid, FastqQualityScore) {
+    (
+        Acid::from_usize(acid),
+        FastqQualityScore::new(q_score as u8),
+    )
+}
+
+/// Creates a context with the symbol probabilities proportional to given
+/// weights. If all the weights are zero, the symbols are equally likely.
+#[must_use]
+fn make_context(context_prob: u8, weights: &[u16]) -> Context {
+    let context_prob = Probability::new(f32::from(context_prob) / f32::from(u8::MAX));
+
+    let total: u32 = weights.iter().map(|&weight| u32::from(weight)).sum();
+    if total == 0 {
+        return Context::new(context_prob, Context::dummy(weights.len()).symbol_prob);
+    }
+
+    let symbol_prob: Vec<Probability> = weights
+        .iter()
+        .map(|&weight| Probability::new((f64::from(weight) / f64::from(total)) as f32))
+        .collect();
+    Context::new(context_prob, symbol_prob)
+}
+
+#[must_use]
+fn max_context_num(spec_type: ContextSpecType) -> usize {
+    MAX_MODEL_CONTEXTS.min(spec_type.spec_num() as usize)
+}
+
+#[must_use]
+fn make_model(
+    model_type: ModelType,
+    spec_type: ContextSpecType,
+    contexts: BTreeMap<u32, Context>,
+) -> Model {
+    let contexts: Vec<ComplexContext> = contexts
+        .into_iter()
+        .map(|(spec, context)| ComplexContext::with_single_spec(ContextSpec::new(spec), context))
+        .collect();
+
+    Model::with_model_and_spec_type(model_type, spec_type, contexts)
+}
+
+fn arbitrary_context(u: &mut Unstructured<'_>, symbol_num: usize) -> arbitrary::Result<Context> {
+    let context_prob = u.arbitrary()?;
+    let weights = (0..symbol_num)
+        .map(|_| u.arbitrary())
+        .collect::<arbitrary::Result<Vec<u16>>>()?;
+
+    Ok(make_context(context_prob, &weights))
+}
+
+impl<'a> Arbitrary<'a> for FastqSequence {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        let identifier_len = u.int_in_range(0..=MAX_IDENTIFIER_LEN)?;
+        let identifier = (0..identifier_len)
+            .map(|_| u.int_in_range(IDENTIFIER_CHARS))
+            .collect::<arbitrary::Result<Vec<u8>>>()?;
+
+        let len = u.int_in_range(0..=MAX_SEQUENCE_LEN)?;
+        let mut acids = Vec::with_capacity(len);
+        let mut quality_scores = Vec::with_capacity(len);
+        for _ in 0..len {
+            let acid = u.int_in_range(0..=Acid::SIZE - 1)?;
+            let q_score = u.int_in_range(0..=FASTQ_Q_END - 1)?;
+            let (acid, q_score) = make_symbols(acid, q_score);
+            acids.push(acid);
+            quality_scores.push(q_score);
+        }
+
+        Ok(FastqSequence::new(
+            make_identifier(identifier),
+            acids,
+            quality_scores,
+        ))
+    }
+}
+
+/// Generates contexts with any number of symbols between 1 and the number of
+/// the quality scores.
+impl<'a> Arbitrary<'a> for Context {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        let symbol_num = u.int_in_range(1..=FastqQualityScore::SIZE)?;
+        arbitrary_context(u, symbol_num)
+    }
+}
+
+impl<'a> Arbitrary<'a> for ModelType {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(if u.arbitrary()? {
+            ModelType::Acids
+        } else {
+            ModelType::QualityScores
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for Model {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        let model_type: ModelType = u.arbitrary()?;
+        let spec_type = *u.choose(&ContextSpecType::VALUES)?;
+
+        let context_num = u.int_in_range(0..=max_context_num(spec_type))?;
+        let mut contexts = BTreeMap::new();
+        for _ in 0..context_num {
+            let spec = u.int_in_range(0..=spec_type.spec_num() - 1)?;
+            let context = arbitrary_context(u, model_type.symbols_num())?;
+            contexts.insert(spec, context);
+        }
+
+        Ok(make_model(model_type, spec_type, contexts))
+    }
+}
+
+impl<'a> Arbitrary<'a> for ContextSpec {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        u.arbitrary().map(ContextSpec::new)
+    }
+}
+
+impl proptest::arbitrary::Arbitrary for FastqSequence {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_args: Self::Parameters) -> Self::Strategy {
+        fastq_sequence().boxed()
+    }
+}
+
+/// Generates contexts with any number of symbols between 1 and the number of
+/// the quality scores.
+impl proptest::arbitrary::Arbitrary for Context {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_args: Self::Parameters) -> Self::Strategy {
+        (1..=FastqQualityScore::SIZE).prop_flat_map(context).boxed()
+    }
+}
+
+impl proptest::arbitrary::Arbitrary for ModelType {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_args: Self::Parameters) -> Self::Strategy {
+        proptest::prop_oneof![Just(ModelType::Acids), Just(ModelType::QualityScores)].boxed()
+    }
+}
+
+impl proptest::arbitrary::Arbitrary for Model {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_args: Self::Parameters) -> Self::Strategy {
+        any::<ModelType>().prop_flat_map(model).boxed()
+    }
+}
+
+impl proptest::arbitrary::Arbitrary for ContextSpec {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_args: Self::Parameters) -> Self::Strategy {
+        context_spec().boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arbitrary::{Arbitrary, Unstructured};
+    use proptest::prelude::any;
+    use proptest::proptest;
+
+    use crate::context::Context;
+    use crate::fastq::reader::FastqReader;
+    use crate::fastq::writer::FastqWriter;
+    use crate::fastq::FastqSequence;
+    use crate::idn::compressor::IdnCompressor;
+    use crate::idn::decompressor::IdnDecompressor;
+    use crate::model::Model;
+    use crate::model_serializer::SerializableModel;
+
+    #[test]
+    fn test_arbitrary_exhausted_data() {
+        let mut unstructured = Unstructured::new(&[]);
+        let sequence = FastqSequence::arbitrary(&mut unstructured).unwrap();
+        assert!(sequence.acids().is_empty());
+
+        let mut unstructured = Unstructured::new(&[]);
+        let model = Model::arbitrary(&mut unstructured).unwrap();
+        assert!(model.is_empty());
+    }
+
+    #[test]
+    fn test_arbitrary_context_is_normalized() {
+        let data: Vec<u8> = (0..=u8::MAX).collect();
+        let mut unstructured = Unstructured::new(&data);
+        let context = Context::arbitrary(&mut unstructured).unwrap();
+
+        let total: f32 = context.symbol_prob.iter().map(|prob| prob.get()).sum();
+        approx::assert_relative_eq!(total, 1.0, epsilon = 1e-4);
+    }
+
+    proptest! {
+        #[test]
+        fn test_fastq_round_trip(sequence in any::<FastqSequence>()) {
+            let mut data = Vec::new();
+            {
+                let mut writer = FastqWriter::new(&mut data);
+                writer.write_sequence(&sequence).unwrap();
+                writer.flush().unwrap();
+            }
+
+            let sequences: Vec<FastqSequence> = FastqReader::new(data.as_slice())
+                .into_iter()
+                .collect::<Result<_, _>>()
+                .unwrap();
+            assert_eq!(sequences, [sequence]);
+        }
+
+        #[test]
+        fn test_idn_round_trip(sequences in proptest::collection::vec(any::<FastqSequence>(), 0..8)) {
+            let mut data = Vec::new();
+            let mut compressor = IdnCompressor::new(&mut data);
+            for sequence in &sequences {
+                compressor.add_sequence(sequence.clone()).unwrap();
+            }
+            compressor.finish().unwrap();
+
+            let mut decompressor = IdnDecompressor::new(data.as_slice());
+            for sequence in &sequences {
+                assert_eq!(decompressor.next_sequence().unwrap().as_ref(), Some(sequence));
+            }
+            assert_eq!(decompressor.next_sequence().unwrap(), None);
+        }
+
+        #[test]
+        fn test_model_serialization_round_trip(model in any::<Model>()) {
+            let mut data = Vec::new();
+            SerializableModel::write_model(&model, &mut data).unwrap();
+
+            let read_model = SerializableModel::read_model(data.as_slice()).unwrap();
+            assert_eq!(read_model, model);
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use crate::fastq::{FastqSequence, FASTQ_Q_END};
+use crate::idn::time_budget::CompressionEffort;
+
+/// The maximum number of sequences sampled from a block.
+const SAMPLE_SIZE: usize = 64;
+/// The quality score entropy (in bits per value) below which the block is
+/// compressed with [`CompressionEffort::Minimal`].
+const MINIMAL_EFFORT_MAX_ENTROPY: f64 = 1.0;
+/// The quality score entropy (in bits per value) below which the block is
+/// compressed with [`CompressionEffort::Reduced`].
+const REDUCED_EFFORT_MAX_ENTROPY: f64 = 2.5;
+
+/// Chooses the effort to compress a block with by sampling its sequences.
+///
+/// The quality scores make up most of the compressed data and choosing the
+/// models for them is the most expensive part of the compression. When they
+/// are simple (e.g. binned, or mostly the same value), any of the models
+/// compresses them well, so there is little to gain from choosing the models
+/// thoroughly.
+#[must_use]
+pub(super) fn sampled_effort(sequences: &[FastqSequence]) -> CompressionEffort {
+    let step = (sequences.len() / SAMPLE_SIZE).max(1);
+    let mut counts = [0_usize; FASTQ_Q_END];
+    for sequence in sequences.iter().step_by(step).take(SAMPLE_SIZE) {
+        for q_score in sequence.quality_scores() {
+            counts[q_score.get()] += 1;
+        }
+    }
+
+    effort_for_entropy(entropy(&counts))
+}
+
+#[must_use]
+fn entropy(counts: &[usize]) -> f64 {
+    let total: usize = counts.iter().sum();
+    if total == 0 {
+        return 0.0;
+    }
+
+    counts
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let probability = count as f64 / total as f64;
+            -probability * probability.log2()
+        })
+        .sum()
+}
+
+#[must_use]
+fn effort_for_entropy(entropy: f64) -> CompressionEffort {
+    if entropy < MINIMAL_EFFORT_MAX_ENTROPY {
+        CompressionEffort::Minimal
+    } else if entropy < REDUCED_EFFORT_MAX_ENTROPY {
+        CompressionEffort::Reduced
+    } else {
+        CompressionEffort::Full
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::_internal_test_data::SEQ_1K_READS;
+    use crate::fastq::{FastqQualityScore, FastqSequence};
+    use crate::idn::adaptive_effort::{entropy, sampled_effort};
+    use crate::idn::time_budget::CompressionEffort;
+    use crate::sequence::Acid;
+
+    #[test]
+    fn test_entropy() {
+        assert_eq!(entropy(&[]), 0.0);
+        assert_eq!(entropy(&[10, 0]), 0.0);
+        assert_eq!(entropy(&[5, 5]), 1.0);
+        assert_eq!(entropy(&[1, 1, 1, 1]), 2.0);
+    }
+
+    #[test]
+    fn test_sampled_effort() {
+        let constant = FastqSequence::new("SEQ", [Acid::A; 100], [FastqQualityScore::new(40); 100]);
+        assert_eq!(
+            sampled_effort(&vec![constant; 10]),
+            CompressionEffort::Minimal
+        );
+        assert_eq!(sampled_effort(&[]), CompressionEffort::Minimal);
+        assert_eq!(sampled_effort(&SEQ_1K_READS), CompressionEffort::Full);
+    }
+}
@@ -1,3 +1,4 @@
+use std::collections::BTreeMap;
 use std::mem;
 use std::sync::{Condvar, Mutex, MutexGuard};
 use std::time::Instant;
@@ -6,55 +7,20 @@ use number_prefix::NumberPrefix;
 
 use crate::progress::ByteNum;
 
-#[derive(Debug)]
-pub(super) struct IdnBlockLock {
-    current_block: Mutex<u32>,
-    current_block_cvar: Condvar,
-}
-
-impl IdnBlockLock {
-    #[must_use]
-    pub fn new() -> Self {
-        Self {
-            current_block: Mutex::new(0),
-            current_block_cvar: Condvar::new(),
-        }
-    }
-
-    pub fn lock(&self, block_index: u32) -> IdnBlockLockGuard<'_> {
-        IdnBlockLockGuard::new(&self.current_block, &self.current_block_cvar, block_index)
-    }
-}
-
-#[derive(Debug)]
-#[must_use]
-pub(super) struct IdnBlockLockGuard<'a> {
-    current_block: MutexGuard<'a, u32>,
-    current_block_cvar: &'a Condvar,
-}
-
-impl<'a> IdnBlockLockGuard<'a> {
-    fn new(current_block: &'a Mutex<u32>, cvar: &'a Condvar, block_index: u32) -> Self {
-        let mut current_block = current_block.lock().expect("Could not acquire block lock");
-        while *current_block != block_index {
-            current_block = cvar
-                .wait(current_block)
-                .expect("Could not acquire block lock");
-        }
-
-        Self {
-            current_block,
-            current_block_cvar: cvar,
+/// Implements [`Debug`](std::fmt::Debug) for a type by printing only its name.
+/// Used for the types whose contents either cannot be printed (e.g. boxed
+/// closures) or are not useful in the debug output.
+macro_rules! opaque_debug {
+    ($type:ident) => {
+        impl std::fmt::Debug for $type {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.debug_struct(stringify!($type)).finish_non_exhaustive()
+            }
         }
-    }
+    };
 }
 
-impl<'a> Drop for IdnBlockLockGuard<'a> {
-    fn drop(&mut self) {
-        *self.current_block += 1;
-        self.current_block_cvar.notify_all();
-    }
-}
+pub(super) use opaque_debug;
 
 #[derive(Debug)]
 struct DataQueueState<T> {
@@ -87,13 +53,13 @@ impl<T> DataQueue<T> {
         }
     }
 
-    pub fn add(&self, data: T) {
+    pub fn add_all<I: IntoIterator<Item = T>>(&self, data: I) {
         let mut state = self
             .state
             .lock()
             .expect("Could not acquire data queue lock");
 
-        state.data.push(data);
+        state.data.extend(data);
         self.cvar.notify_all();
     }
 
@@ -107,41 +73,202 @@ impl<T> DataQueue<T> {
         self.cvar.notify_all();
     }
 
-    pub fn add_all(&self, mut data: Vec<T>) {
+    pub fn retrieve_all(&self) -> Vec<T> {
         let mut state = self
             .state
             .lock()
             .expect("Could not acquire data queue lock");
+        while !state.finished && state.data.is_empty() {
+            state = self
+                .cvar
+                .wait(state)
+                .expect("Could not acquire data queue lock");
+        }
 
-        if data.is_empty() {
-            state.finished = true;
-        } else {
-            state.data.append(&mut data);
+        mem::take(&mut state.data)
+    }
+}
+
+#[derive(Debug)]
+struct OrderedQueueState<T> {
+    items: BTreeMap<u32, T>,
+    next_index: u32,
+    finished: bool,
+}
+
+/// A bounded queue of the items (e.g. blocks) that are produced out of order,
+/// but have to be consumed in the order of their indices.
+///
+/// Adding an item blocks the producer as long as the item is at least
+/// `capacity` positions ahead of the one to be consumed next, which limits the
+/// number of items waiting in the queue. The item to be consumed next is always
+/// accepted, so the producers cannot block each other forever.
+#[derive(Debug)]
+pub(super) struct OrderedQueue<T> {
+    state: Mutex<OrderedQueueState<T>>,
+    cvar: Condvar,
+    capacity: usize,
+}
+
+impl<T> OrderedQueue<T> {
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "Ordered queue capacity must be positive");
+
+        Self {
+            state: Mutex::new(OrderedQueueState {
+                items: BTreeMap::new(),
+                next_index: 0,
+                finished: false,
+            }),
+            cvar: Condvar::new(),
+            capacity,
         }
-        self.cvar.notify_all();
     }
 
-    pub fn retrieve_all(&self) -> Vec<T> {
+    /// Adds the item with given index, waiting until there is room for it. If
+    /// the queue has been finished in the meantime, the item is dropped.
+    pub fn add(&self, index: u32, item: T) {
+        let mut state = self.wait_for_room_locked(index);
+        if !state.finished {
+            state.items.insert(index, item);
+            self.cvar.notify_all();
+        }
+    }
+
+    /// Waits until there is room for the item with given index, so that the
+    /// item can be added without waiting once it is produced. Returns `false`
+    /// if the queue has been finished in the meantime.
+    pub fn wait_for_room(&self, index: u32) -> bool {
+        !self.wait_for_room_locked(index).finished
+    }
+
+    fn wait_for_room_locked(&self, index: u32) -> MutexGuard<'_, OrderedQueueState<T>> {
         let mut state = self
             .state
             .lock()
-            .expect("Could not acquire data queue lock");
-        while !state.finished && state.data.is_empty() {
+            .expect("Could not acquire ordered queue lock");
+        while !state.finished && index as usize >= state.next_index as usize + self.capacity {
             state = self
                 .cvar
                 .wait(state)
-                .expect("Could not acquire data queue lock");
+                .expect("Could not acquire ordered queue lock");
         }
 
-        mem::take(&mut state.data)
+        state
     }
+
+    /// Returns the next item, waiting until it is added. Returns `None` once
+    /// the queue is finished and the next item is not available.
+    pub fn take_next(&self) -> Option<T> {
+        let mut state = self
+            .state
+            .lock()
+            .expect("Could not acquire ordered queue lock");
+        loop {
+            if let Some(item) = Self::pop_next(&mut state) {
+                self.cvar.notify_all();
+                return Some(item);
+            }
+            if state.finished {
+                return None;
+            }
+
+            state = self
+                .cvar
+                .wait(state)
+                .expect("Could not acquire ordered queue lock");
+        }
+    }
+
+    /// Returns the next item if it has already been added.
+    pub fn try_take_next(&self) -> Option<T> {
+        let mut state = self
+            .state
+            .lock()
+            .expect("Could not acquire ordered queue lock");
+
+        let item = Self::pop_next(&mut state);
+        if item.is_some() {
+            self.cvar.notify_all();
+        }
+        item
+    }
+
+    /// Marks the queue as finished: the consumer stops waiting for the items
+    /// that have not been added, and the producers stop waiting for room.
+    pub fn set_finished(&self) {
+        let mut state = self
+            .state
+            .lock()
+            .expect("Could not acquire ordered queue lock");
+
+        state.finished = true;
+        self.cvar.notify_all();
+    }
+
+    fn pop_next(state: &mut OrderedQueueState<T>) -> Option<T> {
+        let item = state.items.remove(&state.next_index)?;
+        state.next_index += 1;
+        Some(item)
+    }
+}
+
+/// A pool of objects that are expensive to create (e.g. because of the large
+/// buffers they contain), so that they can be reused instead of being created
+/// from scratch every time.
+#[derive(Debug)]
+pub(super) struct ObjectPool<T> {
+    objects: Mutex<Vec<T>>,
 }
 
+impl<T> ObjectPool<T> {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            objects: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Takes an object from the pool, or creates a new one with `create` if
+    /// the pool is empty.
+    pub fn take_or_else<F: FnOnce() -> T>(&self, create: F) -> T {
+        let object = self
+            .objects
+            .lock()
+            .expect("Could not acquire object pool lock")
+            .pop();
+
+        object.unwrap_or_else(create)
+    }
+
+    /// Returns the object to the pool, so it can be taken again later.
+    pub fn put_back(&self, object: T) {
+        self.objects
+            .lock()
+            .expect("Could not acquire object pool lock")
+            .push(object);
+    }
+}
+
+/// Returns the current time, or `None` on `wasm32-unknown-unknown`, where
+/// [`Instant::now()`] panics.
 #[must_use]
-pub(crate) fn format_stats(start_time: Instant, bytes_compressed: ByteNum) -> String {
-    let elapsed = start_time.elapsed();
+pub(crate) fn start_time() -> Option<Instant> {
+    if cfg!(all(target_arch = "wasm32", target_os = "unknown")) {
+        None
+    } else {
+        Some(Instant::now())
+    }
+}
 
+#[must_use]
+pub(crate) fn format_stats(start_time: Option<Instant>, bytes_compressed: ByteNum) -> String {
     let size_human = format_bytes(bytes_compressed);
+    let elapsed = match start_time {
+        Some(start_time) => start_time.elapsed(),
+        None => return size_human,
+    };
 
     let rate = bytes_compressed.get() as f32 / elapsed.as_secs_f32();
     let rate_human = match NumberPrefix::decimal(rate) {
@@ -175,7 +302,10 @@ pub(crate) fn format_bytes(bytes: ByteNum) -> String {
 
 #[cfg(test)]
 mod tests {
-    use crate::idn::common::format_bytes;
+    use std::sync::Arc;
+    use std::thread;
+
+    use crate::idn::common::{format_bytes, OrderedQueue};
     use crate::progress::ByteNum;
 
     #[test]
@@ -188,4 +318,38 @@ mod tests {
         assert_eq!(format_bytes(ByteNum::new(1_000_000)), "1.00 MB");
         assert_eq!(format_bytes(ByteNum::new(1_000_000_000)), "1.00 GB");
     }
+
+    #[test]
+    fn test_ordered_queue_reorders() {
+        let queue = OrderedQueue::new(4);
+        queue.add(1, "b");
+        assert_eq!(queue.try_take_next(), None);
+        queue.add(0, "a");
+        queue.add(2, "c");
+        queue.set_finished();
+
+        assert_eq!(queue.take_next(), Some("a"));
+        assert_eq!(queue.take_next(), Some("b"));
+        assert_eq!(queue.take_next(), Some("c"));
+        assert_eq!(queue.take_next(), None);
+    }
+
+    #[test]
+    fn test_ordered_queue_backpressure() {
+        let queue = Arc::new(OrderedQueue::new(2));
+        let producers: Vec<_> = (0..8)
+            .rev()
+            .map(|index| {
+                let queue = queue.clone();
+                thread::spawn(move || queue.add(index, index))
+            })
+            .collect();
+
+        for index in 0..8 {
+            assert_eq!(queue.take_next(), Some(index));
+        }
+        for producer in producers {
+            producer.join().unwrap();
+        }
+    }
 }
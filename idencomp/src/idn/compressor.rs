@@ -1,22 +1,43 @@
+use std::collections::HashSet;
 use std::error::Error;
-use std::fmt::{Display, Formatter};
-use std::io::Write;
+use std::fmt::{Debug, Display, Formatter};
+use std::io::{Seek, Write};
 use std::mem;
+use std::ops::RangeInclusive;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex, MutexGuard};
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use log::info;
 
-use crate::fastq::FastqSequence;
-use crate::idn::common::{format_stats, DataQueue, IdnBlockLock};
-use crate::idn::compressor_block::IdnBlockCompressor;
+use crate::fastq::{FastqQualityScore, FastqSequence};
+use crate::idn::common::{
+    format_stats, opaque_debug, start_time, DataQueue, ObjectPool, OrderedQueue,
+};
+use crate::idn::compressor_block::{BlockCoders, CompressedBlock, IdnBlockCompressor};
 use crate::idn::compressor_initializer::CompressorInitializer;
-use crate::idn::model_provider::ModelProvider;
+use crate::idn::context_usage::ContextUsage;
+use crate::idn::encode_info::{SequenceCallback, SequenceEncodeInfo};
+use crate::idn::explain::SequenceExplainer;
+use crate::idn::model_provider::{ModelProvider, PreparedModels};
+use crate::idn::model_selection::{ExhaustiveSelection, ModelSelectionStrategy};
 use crate::idn::no_seek::NoSeek;
+use crate::idn::source_digest::{PreservedDigest, SourceDigest, SourceHasher};
 use crate::idn::thread_pool::ThreadPool;
+use crate::idn::time_budget::TimeBudget;
+use crate::idn::transform::{
+    SequenceTransform, TransformInfo, MAX_TRANSFORMS, MAX_TRANSFORM_IDENTIFIER_LEN,
+};
+use crate::idn::warning::WarningSink;
 use crate::idn::writer_idn::IdnWriter;
-use crate::progress::{ByteNum, DummyProgressNotifier, ProgressNotifier};
+use crate::model::ModelType;
+use crate::parallel::WorkerPool;
+use crate::progress::{
+    ByteNum, DummyProgressNotifier, ProgressNotifier, ProgressStage, DEFAULT_PROGRESS_GRANULARITY,
+};
+use crate::quality_alphabet::QualityAlphabet;
+use crate::sequence_compressor::SequenceCompressor;
+use crate::sequence_source::SequenceSource;
 
 /// Error occurring during compression of an IDN file.
 #[derive(Debug, Default)]
@@ -30,12 +51,53 @@ pub enum IdnCompressorError {
     SerializeError(binrw::Error),
     /// Requested to compress a sequence longer than the configured limit.
     SequenceTooLong(usize, usize),
+    /// A container member with given name has already been started.
+    DuplicateMember(String),
+    /// The name of a container member is longer than 65535 bytes.
+    MemberNameTooLong(usize),
+    /// The key returned by the function set with
+    /// [`IdnCompressorParamsBuilder::group_blocks_by()`] is longer than 65535
+    /// bytes.
+    BlockKeyTooLong(usize),
+    /// Requested to compress a sequence with a quality score that is not in
+    /// the quality alphabet.
+    QualityScoreNotInAlphabet(FastqQualityScore),
+    /// The [`ModelSelectionStrategy`] returned a model index that is out of
+    /// range (the index and the number of the candidate models).
+    InvalidModelSelection(usize, usize),
+    /// The auxiliary data or the separator text of a sequence, or all of them
+    /// in a block, are longer than 4294967295 bytes.
+    SliceTooLong(usize),
 }
 
 impl IdnCompressorError {
     pub(super) fn sequence_too_long(sequence_len: usize, max_len: usize) -> Self {
         Self::SequenceTooLong(sequence_len, max_len)
     }
+
+    pub(super) fn duplicate_member<T: Into<String>>(name: T) -> Self {
+        Self::DuplicateMember(name.into())
+    }
+
+    pub(super) fn member_name_too_long(name_len: usize) -> Self {
+        Self::MemberNameTooLong(name_len)
+    }
+
+    pub(super) fn block_key_too_long(key_len: usize) -> Self {
+        Self::BlockKeyTooLong(key_len)
+    }
+
+    pub(super) fn quality_score_not_in_alphabet(q_score: FastqQualityScore) -> Self {
+        Self::QualityScoreNotInAlphabet(q_score)
+    }
+
+    pub(super) fn invalid_model_selection(index: usize, candidate_num: usize) -> Self {
+        Self::InvalidModelSelection(index, candidate_num)
+    }
+
+    pub(super) fn slice_too_long(length: usize) -> Self {
+        Self::SliceTooLong(length)
+    }
 }
 
 impl From<std::io::Error> for IdnCompressorError {
@@ -61,6 +123,37 @@ impl Display for IdnCompressorError {
                 "Sequence too long (sequence length: {}, limit: {})",
                 sequence_len, max_len
             ),
+            IdnCompressorError::DuplicateMember(name) => {
+                write!(f, "Duplicate container member name: `{}`", name)
+            }
+            IdnCompressorError::MemberNameTooLong(name_len) => write!(
+                f,
+                "Container member name too long ({} bytes, limit: {})",
+                name_len,
+                u16::MAX
+            ),
+            IdnCompressorError::BlockKeyTooLong(key_len) => write!(
+                f,
+                "Block key too long ({} bytes, limit: {})",
+                key_len,
+                u16::MAX
+            ),
+            IdnCompressorError::QualityScoreNotInAlphabet(q_score) => write!(
+                f,
+                "Quality score not in the quality alphabet: {}",
+                q_score.get()
+            ),
+            IdnCompressorError::InvalidModelSelection(index, candidate_num) => write!(
+                f,
+                "Model selection strategy returned invalid index {} (there are {} candidates)",
+                index, candidate_num
+            ),
+            IdnCompressorError::SliceTooLong(length) => write!(
+                f,
+                "Block slice data too long ({} bytes, limit: {})",
+                length,
+                u32::MAX
+            ),
         }
     }
 }
@@ -78,12 +171,172 @@ impl Error for IdnCompressorError {
 /// The result of compressing IDN.
 pub type IdnCompressResult<T> = Result<T, IdnCompressorError>;
 
+/// Error returned by [`IdnCompressorParamsBuilder::build()`] when the
+/// parameters are inconsistent.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum IdnCompressorParamsError {
+    /// The model provider does not contain any model of given type.
+    MissingModel(ModelType),
+    /// The maximum block length is zero.
+    ZeroMaxBlockTotalLen,
+    /// The maximum number of reads in a block is zero.
+    ZeroMaxBlockReads,
+    /// The target range of the number of reads in a block (the lower and the
+    /// upper bound are given) is empty or starts at zero.
+    InvalidTargetBlockReads(usize, usize),
+    /// The window the quality score model is chosen for within the sequences
+    /// is zero.
+    ZeroIntraReadWindow,
+    /// The fast mode has been enabled along with a compression quality other
+    /// than 1.
+    FastModeQuality(CompressionQuality),
+    /// The Brotli quality is greater than 11.
+    InvalidBrotliQuality(u32),
+    /// The Brotli window size is not between 10 and 24.
+    InvalidBrotliWindow(u32),
+    /// The Deflate level is greater than 9.
+    InvalidDeflateLevel(u32),
+    /// The time budget has been set along with the deterministic mode.
+    DeterministicTimeBudget,
+    /// The raw fallback ratio is not a positive finite number.
+    InvalidRawFallbackRatio,
+    /// More transforms have been set than can be stored in the file metadata
+    /// (the number of transforms is given).
+    TooManyTransforms(usize),
+    /// The identifier of a transform is longer than can be stored in the file
+    /// metadata (its length in bytes is given).
+    TransformIdentifierTooLong(usize),
+}
+
+impl Display for IdnCompressorParamsError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IdnCompressorParamsError::MissingModel(model_type) => {
+                write!(
+                    f,
+                    "The model provider does not contain any {} model",
+                    model_type
+                )
+            }
+            IdnCompressorParamsError::ZeroMaxBlockTotalLen => {
+                write!(f, "The maximum block length must be greater than 0")
+            }
+            IdnCompressorParamsError::ZeroMaxBlockReads => {
+                write!(
+                    f,
+                    "The maximum number of reads in a block must be greater than 0"
+                )
+            }
+            IdnCompressorParamsError::InvalidTargetBlockReads(min, max) => write!(
+                f,
+                "Invalid target number of reads in a block: {}..={} (the bounds must be \
+                greater than 0 and the range cannot be empty)",
+                min, max
+            ),
+            IdnCompressorParamsError::ZeroIntraReadWindow => {
+                write!(f, "The intra-read model window must be greater than 0")
+            }
+            IdnCompressorParamsError::FastModeQuality(quality) => write!(
+                f,
+                "The fast mode requires compression quality 1, but {} was set",
+                quality.get()
+            ),
+            IdnCompressorParamsError::InvalidBrotliQuality(quality) => write!(
+                f,
+                "Invalid Brotli quality: {} (must be at most {})",
+                quality, BROTLI_MAX_QUALITY
+            ),
+            IdnCompressorParamsError::InvalidBrotliWindow(window) => write!(
+                f,
+                "Invalid Brotli window: {} (must be between {} and {})",
+                window,
+                BROTLI_WINDOWS.start(),
+                BROTLI_WINDOWS.end()
+            ),
+            IdnCompressorParamsError::InvalidDeflateLevel(level) => write!(
+                f,
+                "Invalid Deflate level: {} (must be at most {})",
+                level, DEFLATE_MAX_LEVEL
+            ),
+            IdnCompressorParamsError::DeterministicTimeBudget => write!(
+                f,
+                "The time budget cannot be used in the deterministic mode"
+            ),
+            IdnCompressorParamsError::InvalidRawFallbackRatio => {
+                write!(f, "The raw fallback ratio must be a positive finite number")
+            }
+            IdnCompressorParamsError::TooManyTransforms(num_transforms) => write!(
+                f,
+                "Too many transforms: {} (must be at most {})",
+                num_transforms, MAX_TRANSFORMS
+            ),
+            IdnCompressorParamsError::TransformIdentifierTooLong(identifier_len) => write!(
+                f,
+                "Transform identifier too long: {} bytes (must be at most {})",
+                identifier_len, MAX_TRANSFORM_IDENTIFIER_LEN
+            ),
+        }
+    }
+}
+
+impl Error for IdnCompressorParamsError {}
+
+const BROTLI_MAX_QUALITY: u32 = 11;
+const BROTLI_WINDOWS: RangeInclusive<u32> = 10..=24;
+const DEFLATE_MAX_LEVEL: u32 = 9;
+
+const DEFAULT_MAX_BLOCK_TOTAL_LEN: usize = 4 * 1024 * 1024;
+/// The maximum block length used for long reads. This makes it possible to
+/// compress reads of up to 32 Mb.
+const LONG_READ_MAX_BLOCK_TOTAL_LEN: usize = 64 * 1024 * 1024;
+/// How many times a block can exceed the maximum block length to gather the
+/// minimum number of reads set with
+/// [`IdnCompressorParamsBuilder::target_block_reads()`].
+const TARGET_BLOCK_READS_MAX_GROWTH: usize = 4;
+/// The maximum total length of the blocks of all the groups set with
+/// [`IdnCompressorParamsBuilder::group_blocks_by()`] being filled at the same
+/// time, as a multiple of the maximum block length. When it would be exceeded,
+/// the largest of the blocks is finished early.
+const MAX_PENDING_BLOCKS_LEN_FACTOR: usize = 8;
+/// The window the quality score model is chosen for within the sequences in
+/// the long read mode.
+const LONG_READ_INTRA_READ_WINDOW: usize = 16 * 1024;
+/// The maximum sequence length the coders preallocate their buffers for. The
+/// buffers grow when a longer sequence is encountered, so the memory is not
+/// wasted when the blocks are large enough to contain very long reads, but the
+/// reads themselves are mostly short.
+const MAX_PREALLOCATED_SEQ_LEN: usize = DEFAULT_MAX_BLOCK_TOTAL_LEN / 2;
+
+/// Error returned when trying to create a [`CompressionQuality`] with a value
+/// that is not between 1 and 9.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct InvalidCompressionQualityError(u8);
+
+impl Display for InvalidCompressionQualityError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Invalid compression quality: {} (must be between {} and {})",
+            self.0,
+            CompressionQuality::MIN.get(),
+            CompressionQuality::MAX.get()
+        )
+    }
+}
+
+impl Error for InvalidCompressionQualityError {}
+
 /// Compression quality, as a number between 1 and 9. 1 means the fastest, while
 /// 9 is the slowest and producing the smallest files.
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
 pub struct CompressionQuality(u8);
 
 impl CompressionQuality {
+    /// The lowest (fastest) compression quality.
+    pub const MIN: CompressionQuality = CompressionQuality(1);
+    /// The highest (best) compression quality.
+    pub const MAX: CompressionQuality = CompressionQuality(9);
+
     /// Creates new `CompressionQuality` instance.
     ///
     /// # Examples
@@ -96,12 +349,32 @@ impl CompressionQuality {
     ///
     /// # Panic
     /// This function panics if the value is not between 1 and 9 (inclusive).
+    /// See [`Self::try_new()`] for a non-panicking version.
     #[must_use]
     pub const fn new(value: u8) -> Self {
-        assert!(value >= 1);
-        assert!(value <= 9);
+        match Self::try_new(value) {
+            Ok(quality) => quality,
+            Err(_) => panic!("Invalid compression quality"),
+        }
+    }
 
-        Self(value)
+    /// Creates new `CompressionQuality` instance, or returns an error if the
+    /// value is not between 1 and 9 (inclusive).
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::idn::compressor::CompressionQuality;
+    ///
+    /// assert_eq!(CompressionQuality::try_new(5), Ok(CompressionQuality::new(5)));
+    /// assert!(CompressionQuality::try_new(0).is_err());
+    /// assert!(CompressionQuality::try_new(10).is_err());
+    /// ```
+    pub const fn try_new(value: u8) -> Result<Self, InvalidCompressionQualityError> {
+        if value >= Self::MIN.0 && value <= Self::MAX.0 {
+            Ok(Self(value))
+        } else {
+            Err(InvalidCompressionQualityError(value))
+        }
     }
 
     /// Returns this `CompressionQuality` value as a number.
@@ -117,6 +390,56 @@ impl CompressionQuality {
     pub const fn get(&self) -> u8 {
         self.0
     }
+
+    /// Returns the Brotli quality (0-11) used to compress the sequence
+    /// identifiers at this compression quality, unless overridden with
+    /// [`IdnCompressorParamsBuilder::brotli_params()`].
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::idn::compressor::CompressionQuality;
+    ///
+    /// assert_eq!(CompressionQuality::MAX.brotli_quality(), 11);
+    /// ```
+    #[must_use]
+    pub const fn brotli_quality(&self) -> u32 {
+        const QUALITIES: [u32; 9] = [1, 2, 3, 4, 5, 6, 7, 9, 11];
+        QUALITIES[(self.0 - Self::MIN.0) as usize]
+    }
+
+    /// Returns the base 2 logarithm of the Brotli window size used to compress
+    /// the sequence identifiers at this compression quality, unless
+    /// overridden with [`IdnCompressorParamsBuilder::brotli_params()`].
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::idn::compressor::CompressionQuality;
+    ///
+    /// assert_eq!(CompressionQuality::MAX.brotli_window(), 22);
+    /// ```
+    #[must_use]
+    pub const fn brotli_window(&self) -> u32 {
+        match self.0 {
+            1..=4 => 18,
+            5..=8 => 20,
+            _ => 22,
+        }
+    }
+
+    /// Returns the Deflate level (0-9) used to compress the sequence
+    /// identifiers at this compression quality, unless overridden with
+    /// [`IdnCompressorParamsBuilder::deflate_level()`].
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::idn::compressor::CompressionQuality;
+    ///
+    /// assert_eq!(CompressionQuality::new(5).deflate_level(), 5);
+    /// ```
+    #[must_use]
+    pub const fn deflate_level(&self) -> u32 {
+        self.0 as u32
+    }
 }
 
 impl Default for CompressionQuality {
@@ -130,11 +453,41 @@ impl Default for CompressionQuality {
 pub struct IdnCompressorParams {
     model_provider: ModelProvider,
     max_block_total_len: usize,
+    max_block_reads: usize,
+    target_block_reads: Option<RangeInclusive<usize>>,
+    block_key: Option<BlockKey>,
+    intra_read_window: Option<usize>,
     progress_notifier: Arc<dyn ProgressNotifier>,
+    progress_granularity: usize,
     thread_num: usize,
     include_identifiers: bool,
     quality: CompressionQuality,
     fast: bool,
+    model_selection: Arc<dyn ModelSelectionStrategy>,
+    brotli_params: Option<(u32, u32)>,
+    deflate_level: Option<u32>,
+    deterministic: bool,
+    deduplicate: bool,
+    skip_n_q_scores: bool,
+    raw_fallback_ratio: Option<f32>,
+    group_by_tile: bool,
+    pub(super) source_hasher: Option<SourceHasher>,
+    context_usage: Option<ContextUsage>,
+    explainer: Option<Arc<dyn SequenceExplainer>>,
+    on_sequence: Option<SequenceCallback>,
+    warning_sink: Option<Arc<dyn WarningSink>>,
+    time_budget: Option<Duration>,
+    expected_input_size: Option<ByteNum>,
+    adaptive_effort: bool,
+    transforms: Vec<Arc<dyn SequenceTransform>>,
+    quality_alphabet: QualityAlphabet,
+    /// The transforms that have already been applied to the sequences before
+    /// they are passed to the compressor, which are only recorded in the
+    /// metadata (before the ones in `transforms`).
+    pub(super) applied_transforms: Vec<TransformInfo>,
+    /// The digest to store in the file instead of the one computed by
+    /// `source_hasher`, set once the input of the recompressor has been read.
+    pub(super) preserved_digest: Option<PreservedDigest>,
 }
 
 impl IdnCompressorParams {
@@ -144,17 +497,29 @@ impl IdnCompressorParams {
     /// ```
     /// use idencomp::idn::compressor::IdnCompressorParams;
     ///
-    /// let _params: IdnCompressorParams = IdnCompressorParams::builder().build();
+    /// let _params: IdnCompressorParams = IdnCompressorParams::builder().build()?;
+    ///
+    /// # Ok::<(), idencomp::Error>(())
     /// ```
     #[must_use]
     pub fn builder() -> IdnCompressorParamsBuilder {
         IdnCompressorParamsBuilder::new()
     }
+
+    fn max_seq_len(&self) -> usize {
+        self.max_block_total_len / 2
+    }
+
+    pub(super) fn num_transforms(&self) -> usize {
+        self.transforms.len()
+    }
 }
 
 impl Default for IdnCompressorParams {
     fn default() -> Self {
-        Self::builder().build()
+        Self::builder()
+            .build()
+            .expect("Default compressor params should be valid")
     }
 }
 
@@ -163,12 +528,36 @@ impl Default for IdnCompressorParams {
 #[derive(Debug, Clone)]
 pub struct IdnCompressorParamsBuilder {
     model_provider: ModelProvider,
-    max_block_total_len: usize,
+    max_block_total_len: Option<usize>,
+    long_reads: bool,
+    max_block_reads: usize,
+    target_block_reads: Option<RangeInclusive<usize>>,
+    block_key: Option<BlockKey>,
+    intra_read_window: Option<usize>,
     progress_notifier: Arc<dyn ProgressNotifier>,
+    progress_granularity: usize,
     thread_num: usize,
     include_identifiers: bool,
     quality: CompressionQuality,
     fast: bool,
+    model_selection: Arc<dyn ModelSelectionStrategy>,
+    brotli_params: Option<(u32, u32)>,
+    deflate_level: Option<u32>,
+    deterministic: bool,
+    deduplicate: bool,
+    skip_n_q_scores: bool,
+    raw_fallback_ratio: Option<f32>,
+    group_by_tile: bool,
+    source_hasher: Option<SourceHasher>,
+    context_usage: Option<ContextUsage>,
+    explainer: Option<Arc<dyn SequenceExplainer>>,
+    on_sequence: Option<SequenceCallback>,
+    warning_sink: Option<Arc<dyn WarningSink>>,
+    time_budget: Option<Duration>,
+    expected_input_size: Option<ByteNum>,
+    adaptive_effort: bool,
+    transforms: Vec<Arc<dyn SequenceTransform>>,
+    quality_alphabet: QualityAlphabet,
 }
 
 impl IdnCompressorParamsBuilder {
@@ -178,98 +567,826 @@ impl IdnCompressorParamsBuilder {
     /// ```
     /// use idencomp::idn::compressor::{IdnCompressorParams, IdnCompressorParamsBuilder};
     ///
-    /// let _params: IdnCompressorParams = IdnCompressorParamsBuilder::new().build();
+    /// let _params: IdnCompressorParams = IdnCompressorParamsBuilder::new().build()?;
+    ///
+    /// # Ok::<(), idencomp::Error>(())
     /// ```
     #[must_use]
     pub fn new() -> Self {
         Self {
             model_provider: ModelProvider::default(),
-            max_block_total_len: 4 * 1024 * 1024,
+            max_block_total_len: None,
+            long_reads: false,
+            max_block_reads: usize::MAX,
+            target_block_reads: None,
+            block_key: None,
+            intra_read_window: None,
             progress_notifier: Arc::new(DummyProgressNotifier),
+            progress_granularity: DEFAULT_PROGRESS_GRANULARITY,
             thread_num: 0,
             include_identifiers: true,
             quality: CompressionQuality::default(),
             fast: false,
+            model_selection: Arc::new(ExhaustiveSelection),
+            brotli_params: None,
+            deflate_level: None,
+            deterministic: false,
+            deduplicate: false,
+            skip_n_q_scores: false,
+            raw_fallback_ratio: None,
+            group_by_tile: false,
+            source_hasher: None,
+            context_usage: None,
+            explainer: None,
+            on_sequence: None,
+            warning_sink: None,
+            time_budget: None,
+            adaptive_effort: false,
+            expected_input_size: None,
+            transforms: Vec::new(),
+            quality_alphabet: QualityAlphabet::full(),
         }
     }
 
-    /// Sets the [`ModelProvider`] for this compressor.
-    pub fn model_provider(&mut self, model_provider: ModelProvider) -> &mut Self {
-        let mut new = self;
-        new.model_provider = model_provider;
-        new
+    /// Sets the [`ModelProvider`] for this compressor. The provider has to
+    /// contain at least one acid model and one quality score model.
+    #[must_use]
+    pub fn model_provider(mut self, model_provider: ModelProvider) -> Self {
+        self.model_provider = model_provider;
+        self
     }
 
     /// Sets the maximum block length. The number is the maximum total length of
-    /// sequences in a single block.
-    pub fn max_block_total_len(&mut self, max_block_total_len: usize) -> &mut Self {
-        let mut new = self;
-        new.max_block_total_len = max_block_total_len;
-        new
+    /// sequences in a single block and has to be greater than 0.
+    #[must_use]
+    pub fn max_block_total_len(mut self, max_block_total_len: usize) -> Self {
+        self.max_block_total_len = Some(max_block_total_len);
+        self
+    }
+
+    /// Sets the long read mode, meant for the data produced by Oxford Nanopore
+    /// or PacBio sequencers, with reads of up to tens of megabases. This
+    /// increases the maximum block length, so that such reads fit within the
+    /// sequence length limit, and enables switching the quality score model
+    /// within the reads (see [`Self::intra_read_window()`]). These are only
+    /// defaults: the values set explicitly with
+    /// [`Self::max_block_total_len()`] and [`Self::intra_read_window()`] are
+    /// used instead, no matter the order the methods are called in.
+    ///
+    /// The best compression ratio for such data is achieved with models using
+    /// the long read context spec types (see
+    /// [`LongReadContextSpecGenerator`](crate::context_spec::LongReadContextSpecGenerator)).
+    #[must_use]
+    pub fn long_reads(mut self, long_reads: bool) -> Self {
+        self.long_reads = long_reads;
+        self
+    }
+
+    /// Enables switching the quality score model within the sequences longer
+    /// than `window`, which has to be greater than 0. The best model is chosen
+    /// for each `window` symbols of such sequences separately, which improves
+    /// the compression ratio when the characteristics of the quality scores
+    /// drift along very long reads. By default, the model is only chosen once
+    /// per sequence.
+    ///
+    /// This has no effect in the fast mode and for the sequences whose
+    /// quality score model is chosen per tile (see
+    /// [`Self::group_by_tile()`]).
+    #[must_use]
+    pub fn intra_read_window(mut self, window: usize) -> Self {
+        self.intra_read_window = Some(window);
+        self
+    }
+
+    /// Sets the maximum number of sequences in a single block, which has to be
+    /// greater than 0. By default, the blocks are limited only by
+    /// [`Self::max_block_total_len()`].
+    #[must_use]
+    pub fn max_block_reads(mut self, max_block_reads: usize) -> Self {
+        self.max_block_reads = max_block_reads;
+        self
+    }
+
+    /// Enables automatic block sizing, which aims at blocks of `target`
+    /// reads regardless of the read length. Otherwise, a block holds tens of
+    /// thousands of short reads, but only a few long ones, which makes the
+    /// latency and the model switching overhead differ wildly between the
+    /// inputs.
+    ///
+    /// A block is finished as soon as it contains `target.end()` reads, or
+    /// when it reaches [`Self::max_block_total_len()`] and contains at least
+    /// `target.start()` reads. To gather the minimum number of reads, the
+    /// block can grow up to 4 times the maximum block length (the maximum
+    /// sequence length does not change). Both bounds have to be greater than
+    /// 0. [`Self::max_block_reads()`] still applies.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::idn::compressor::IdnCompressorParams;
+    ///
+    /// let _params = IdnCompressorParams::builder()
+    ///     .target_block_reads(1_000..=20_000)
+    ///     .build()?;
+    ///
+    /// # Ok::<(), idencomp::Error>(())
+    /// ```
+    #[must_use]
+    pub fn target_block_reads(mut self, target: RangeInclusive<usize>) -> Self {
+        self.target_block_reads = Some(target);
+        self
+    }
+
+    /// Partitions the reads into blocks by the key returned by given function
+    /// (e.g. the flow cell lane or the barcode), instead of their order. Each
+    /// of the blocks only contains the reads with the same key, which keeps
+    /// similar data together and usually makes the models fit better.
+    ///
+    /// **This changes the order of the reads**: they are decompressed block
+    /// by block, so the reads with the same key stay in their original order,
+    /// but the groups are interleaved block-wise, and the original order is
+    /// not stored. The blocks of the groups are filled at the same time, up to
+    /// 8 times the maximum block length in total; past that, the largest of
+    /// the incomplete blocks is finished early. The blocks are also finished
+    /// when a new container member is started.
+    ///
+    /// The key of each block is stored in the file and can be read with
+    /// [`IdnBlockInfo::group()`](crate::idn::inspector::IdnBlockInfo::group).
+    /// It cannot be longer than 65535 bytes.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::idn::compressor::IdnCompressorParams;
+    ///
+    /// let _params = IdnCompressorParams::builder()
+    ///     .group_blocks_by(|sequence| {
+    ///         sequence
+    ///             .identifier()
+    ///             .illumina_coordinates()
+    ///             .map_or_else(String::new, |coordinates| coordinates.lane.to_string())
+    ///     })
+    ///     .build()?;
+    ///
+    /// # Ok::<(), idencomp::Error>(())
+    /// ```
+    #[must_use]
+    pub fn group_blocks_by<F>(mut self, key: F) -> Self
+    where
+        F: Fn(&FastqSequence) -> String + Send + Sync + 'static,
+    {
+        self.block_key = Some(BlockKey(Arc::new(key)));
+        self
     }
 
     /// Sets given [`ProgressNotifier`] instance for this compressor.
-    pub fn progress_notifier(&mut self, progress_notifier: Arc<dyn ProgressNotifier>) -> &mut Self {
-        let mut new = self;
-        new.progress_notifier = progress_notifier;
-        new
+    #[must_use]
+    pub fn progress_notifier(mut self, progress_notifier: Arc<dyn ProgressNotifier>) -> Self {
+        self.progress_notifier = progress_notifier;
+        self
+    }
+
+    /// Sets the number of bytes of the input sequences each of the block
+    /// compressors gathers before reporting them to the [`ProgressNotifier`].
+    /// The notifier is shared between all the threads, so reporting every
+    /// sequence separately causes contention when there are many of them. `0`
+    /// reports every sequence. The default is 64 KiB.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::idn::compressor::IdnCompressorParams;
+    ///
+    /// let _params = IdnCompressorParams::builder()
+    ///     .progress_granularity(1024 * 1024)
+    ///     .build()?;
+    ///
+    /// # Ok::<(), idencomp::Error>(())
+    /// ```
+    #[must_use]
+    pub fn progress_granularity(mut self, bytes: usize) -> Self {
+        self.progress_granularity = bytes;
+        self
     }
 
     /// Sets the number of additional threads that can be spawned when
-    /// compressing the data.
-    pub fn thread_num(&mut self, thread_num: usize) -> &mut Self {
-        let mut new = self;
-        new.thread_num = thread_num;
-        new
+    /// compressing the data. If it is nonzero, one more thread is spawned to
+    /// write the compressed blocks to the output, and the sequences within
+    /// each block are encoded in parallel, so that even the inputs smaller
+    /// than a single block can use multiple cores.
+    #[must_use]
+    pub fn thread_num(mut self, thread_num: usize) -> Self {
+        self.thread_num = thread_num;
+        self
     }
 
     /// Sets whether the sequence identifiers should be stored in the compressed
     /// file.
-    pub fn include_identifiers(&mut self, include_identifiers: bool) -> &mut Self {
-        let mut new = self;
-        new.include_identifiers = include_identifiers;
-        new
+    #[must_use]
+    pub fn include_identifiers(mut self, include_identifiers: bool) -> Self {
+        self.include_identifiers = include_identifiers;
+        self
     }
 
     /// Sets the desired compression quality.
-    pub fn quality(&mut self, quality: CompressionQuality) -> &mut Self {
-        let mut new = self;
-        new.quality = quality;
-        new
+    #[must_use]
+    pub fn quality(mut self, quality: CompressionQuality) -> Self {
+        self.quality = quality;
+        self
     }
 
     /// Sets the "fast" mode, which sets the compression quality to 1 and
     /// ignores generating some statistics (this doesn't hurt the compression
-    /// quality any more, though).
-    pub fn fast(&mut self, fast: bool) -> &mut Self {
-        let mut new = self;
-        new.fast = fast;
+    /// quality any more, though). Setting a different quality afterwards makes
+    /// [`Self::build()`] fail.
+    ///
+    /// In the fast mode, the blocks that contain no `N` acids and have the
+    /// same quality score everywhere are stored using 2 bits per acid instead
+    /// of being compressed with the models.
+    #[must_use]
+    pub fn fast(mut self, fast: bool) -> Self {
+        self.fast = fast;
         if fast {
-            new.quality = CompressionQuality::new(1);
+            self.quality = CompressionQuality::MIN;
         }
-        new
+        self
     }
 
-    /// Builds and returns a [`IdnCompressorParams`] instance from the date set
-    /// in this builder.
+    /// Sets the [`ModelSelectionStrategy`] choosing the model each of the
+    /// sequences is encoded with. The default is [`ExhaustiveSelection`],
+    /// which tests all the models on every sequence. Has no effect in the
+    /// [`fast()`](Self::fast) mode, where the models are not chosen at all.
     ///
     /// # Examples
     /// ```
-    /// use idencomp::idn::compressor::{IdnCompressorParams, IdnCompressorParamsBuilder};
+    /// use std::sync::Arc;
+    ///
+    /// use idencomp::idn::compressor::IdnCompressorParams;
+    /// use idencomp::idn::model_selection::SampledSelection;
+    ///
+    /// let _params = IdnCompressorParams::builder()
+    ///     .model_selection(Arc::new(SampledSelection::new(512)))
+    ///     .build()?;
+    ///
+    /// # Ok::<(), idencomp::Error>(())
+    /// ```
+    #[must_use]
+    pub fn model_selection(mut self, model_selection: Arc<dyn ModelSelectionStrategy>) -> Self {
+        self.model_selection = model_selection;
+        self
+    }
+
+    /// Sets the Brotli quality (0-11) and the base 2 logarithm of the window
+    /// size (10-24) used to compress the sequence identifiers, instead of the
+    /// ones derived from the compression quality.
+    #[must_use]
+    pub fn brotli_params(mut self, quality: u32, window: u32) -> Self {
+        self.brotli_params = Some((quality, window));
+        self
+    }
+
+    /// Sets the Deflate level (0-9) used to compress the sequence identifiers,
+    /// instead of the one derived from the compression quality.
+    #[must_use]
+    pub fn deflate_level(mut self, level: u32) -> Self {
+        self.deflate_level = Some(level);
+        self
+    }
+
+    /// Sets whether the sequences that have the same acids as an earlier
+    /// sequence in the same block should be stored as references to that
+    /// sequence. This is useful for data with many PCR duplicates, and costs a
+    /// little time otherwise. The identifiers of the duplicates are still
+    /// stored, and so are their quality scores, unless they are the same as the
+    /// ones of the earlier sequence.
+    #[must_use]
+    pub fn deduplicate(mut self, deduplicate: bool) -> Self {
+        self.deduplicate = deduplicate;
+        self
+    }
+
+    /// Sets whether the quality scores of the [`Acid::N`] symbols should be
+    /// skipped when all of them are equal within a block (which is often the
+    /// case, as sequencers usually assign them a constant low quality, such as
+    /// 0 or 2). The quality score is then stored once per block and only the
+    /// acids are encoded at the `N` positions. The files created with this
+    /// option cannot be read by the older versions of the decompressor.
+    ///
+    /// [`Acid::N`]: crate::sequence::Acid::N
+    #[must_use]
+    pub fn skip_n_q_scores(mut self, skip_n_q_scores: bool) -> Self {
+        self.skip_n_q_scores = skip_n_q_scores;
+        self
+    }
+
+    /// Sets the ratio that bounds the size of the compressed sequences. If the
+    /// sequences of a block are encoded into more bytes than `ratio` times
+    /// their raw size (one byte per acid and one per quality score, as in a
+    /// FASTQ file), the block is stored raw instead. This bounds the
+    /// worst-case expansion of the data the models do not fit at all (such as
+    /// the data encrypted upstream), at the cost of encoding such blocks
+    /// twice. The ratio has to be a positive number; by default, the blocks are
+    /// never stored raw. The files created with this option cannot be read by
+    /// the older versions of the decompressor.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::fastq::{FastqQualityScore, FastqSequence};
+    /// use idencomp::idn::compressor::{IdnCompressor, IdnCompressorParams};
+    /// use idencomp::idn::inspector::inspect;
+    /// use idencomp::sequence::Acid;
+    ///
+    /// let params = IdnCompressorParams::builder()
+    ///     .raw_fallback_ratio(0.01)
+    ///     .build()?;
+    /// let sequence = FastqSequence::new(
+    ///     "SEQ",
+    ///     [Acid::A, Acid::C, Acid::G, Acid::T],
+    ///     [FastqQualityScore::new(30); 4],
+    /// );
+    ///
+    /// let mut vec = Vec::new();
+    /// let mut compressor = IdnCompressor::with_params(&mut vec, params);
+    /// compressor.add_sequence(sequence)?;
+    /// compressor.finish()?;
+    ///
+    /// let info = inspect(vec.as_slice())?;
+    /// assert!(info.blocks()[0].is_raw());
+    ///
+    /// # Ok::<(), idencomp::Error>(())
+    /// ```
+    #[must_use]
+    pub fn raw_fallback_ratio(mut self, ratio: f32) -> Self {
+        self.raw_fallback_ratio = Some(ratio);
+        self
+    }
+
+    /// Sets whether the quality score model should be chosen for whole groups
+    /// of consecutive reads coming from the same flow cell tile, as parsed
+    /// from their Illumina identifiers (see
+    /// [`NucleotideSequenceIdentifier::illumina_coordinates()`]). The quality
+    /// scores of Illumina reads depend strongly on the tile, so this usually
+    /// results in fewer model switches. The reads without the coordinates in
+    /// their identifiers get the model chosen individually, as usual. Has no
+    /// effect in the fast mode.
+    ///
+    /// [`NucleotideSequenceIdentifier::illumina_coordinates()`]: crate::sequence::NucleotideSequenceIdentifier::illumina_coordinates
+    #[must_use]
+    pub fn group_by_tile(mut self, group_by_tile: bool) -> Self {
+        self.group_by_tile = group_by_tile;
+        self
+    }
+
+    /// Sets the [`SourceHasher`] that the original input data is read through.
+    /// Its digest is stored in the file once all the sequences have been
+    /// compressed, so it can be later used to prove that the file was created
+    /// from a specific FASTQ file (see [`IdnFileInfo::source_digest()`]).
+    ///
+    /// If the compressor was created with
+    /// [`IdnCompressor::with_params_seekable()`], the digest is patched into
+    /// the metadata at the start of the file; otherwise, it is stored in the
+    /// trailing metadata, after all the blocks.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::fastq::parallel_reader::ParallelFastqReader;
+    /// use idencomp::idn::compressor::{IdnCompressor, IdnCompressorParams};
+    /// use idencomp::idn::inspector::inspect;
+    /// use idencomp::idn::source_digest::{SourceDigest, SourceHasher};
+    ///
+    /// let fastq = b"@SEQ_ID\nGATTTGGGG\n+\n!''*((((*\n";
+    /// let hasher = SourceHasher::new();
+    /// let params = IdnCompressorParams::builder()
+    ///     .source_hasher(hasher.clone())
+    ///     .build()?;
+    ///
+    /// let mut vec = Vec::new();
+    /// let mut compressor = IdnCompressor::with_params(&mut vec, params);
+    /// for sequence in ParallelFastqReader::new(hasher.reader(fastq.as_slice())) {
+    ///     compressor.add_sequence(sequence?)?;
+    /// }
+    /// compressor.finish()?;
+    ///
+    /// let info = inspect(vec.as_slice())?;
+    /// assert_eq!(
+    ///     info.source_digest(),
+    ///     Some(SourceDigest::of_reader(fastq.as_slice())?)
+    /// );
+    ///
+    /// # Ok::<(), idencomp::Error>(())
+    /// ```
+    ///
+    /// [`IdnFileInfo::source_digest()`]: crate::idn::inspector::IdnFileInfo::source_digest
+    #[must_use]
+    pub fn source_hasher(mut self, source_hasher: SourceHasher) -> Self {
+        self.source_hasher = Some(source_hasher);
+        self
+    }
+
+    /// Sets the [`ContextUsage`] collector that counts the symbols encoded
+    /// with each context of each model. Collecting the usage makes the
+    /// compression slower, so it is disabled by default.
+    #[must_use]
+    pub fn context_usage(mut self, context_usage: ContextUsage) -> Self {
+        self.context_usage = Some(context_usage);
+        self
+    }
+
+    /// Sets the [`SequenceExplainer`] that receives the details of how each of
+    /// the sequences has been compressed: the models chosen, the estimated
+    /// and actual compressed size, and the number of symbols encoded with the
+    /// dummy contexts. Explaining the sequences makes the compression slower,
+    /// so it is disabled by default.
+    #[must_use]
+    pub fn explainer(mut self, explainer: Arc<dyn SequenceExplainer>) -> Self {
+        self.explainer = Some(explainer);
+        self
+    }
+
+    /// Sets the callback invoked after each of the sequences has been encoded,
+    /// with the sequence (after the [transforms](Self::transform) have been
+    /// applied) and the details of how it has been stored. This makes it
+    /// possible to compute statistics of the data, such as the GC content or
+    /// the mean quality score, without reading it for the second time.
+    ///
+    /// The callback is invoked from the threads compressing the blocks, so
+    /// the sequences of different blocks can be reported in any order and
+    /// concurrently; the sequences of a single block are reported in order.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    ///
+    /// use idencomp::fastq::reader::FastqReader;
+    /// use idencomp::idn::compressor::{IdnCompressor, IdnCompressorParams};
+    /// use idencomp::sequence::Acid;
+    ///
+    /// let gc_count = Arc::new(AtomicUsize::new(0));
+    /// let counter = gc_count.clone();
+    /// let params = IdnCompressorParams::builder()
+    ///     .on_sequence(move |sequence, _info| {
+    ///         let gc = sequence
+    ///             .acids()
+    ///             .iter()
+    ///             .filter(|&&acid| acid == Acid::G || acid == Acid::C)
+    ///             .count();
+    ///         counter.fetch_add(gc, Ordering::Relaxed);
+    ///     })
+    ///     .build()?;
+    /// let mut data = Vec::new();
+    /// let mut compressor = IdnCompressor::with_params(&mut data, params);
+    /// for sequence in FastqReader::new(b"@SEQ1\nACGT\n+\n!!!!\n".as_slice()) {
+    ///     compressor.add_sequence(sequence?)?;
+    /// }
+    /// compressor.finish()?;
+    ///
+    /// assert_eq!(gc_count.load(Ordering::Relaxed), 2);
+    ///
+    /// # Ok::<(), idencomp::Error>(())
+    /// ```
+    #[must_use]
+    pub fn on_sequence<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&FastqSequence, &SequenceEncodeInfo) + Send + Sync + 'static,
+    {
+        self.on_sequence = Some(SequenceCallback::new(callback));
+        self
+    }
+
+    /// Sets the [`WarningSink`] that receives the non-fatal issues
+    /// encountered during compression, such as the models not fitting the
+    /// data well. The warnings of a block are reported when the block is
+    /// written, so they come in the order of the blocks in the file. The
+    /// warnings are logged regardless of whether a sink is set.
+    #[must_use]
+    pub fn warning_sink(mut self, warning_sink: Arc<dyn WarningSink>) -> Self {
+        self.warning_sink = Some(warning_sink);
+        self
+    }
+
+    /// Sets whether the output should be reproducible. When enabled, the
+    /// compressed data is guaranteed to be byte-for-byte identical for the
+    /// same input, parameters and set of models, regardless of the number of
+    /// threads used and the order the models were loaded in (e.g. with
+    /// [`ModelProvider::from_directory()`]).
+    #[must_use]
+    pub fn deterministic(mut self, deterministic: bool) -> Self {
+        self.deterministic = deterministic;
+        self
+    }
+
+    /// Sets the time the compression should finish within. The compressor
+    /// monitors its throughput and, when the budget is at risk, lowers the
+    /// effort for the subsequent blocks: the models are chosen less often and
+    /// the identifiers are compressed with faster Brotli and Deflate settings.
+    /// The budget is best-effort; the compression is never aborted.
+    ///
+    /// The throughput can only be extrapolated when the input size is known,
+    /// so it is advisable to also set
+    /// [`expected_input_size()`](Self::expected_input_size). Otherwise, the
+    /// effort is lowered as the budget gets used up.
+    ///
+    /// The output depends on the timing, so the time budget cannot be used in
+    /// the [`deterministic()`](Self::deterministic) mode. The bits per value
+    /// statistics only include the sequences the models have been chosen for.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::time::Duration;
+    ///
+    /// use idencomp::idn::compressor::IdnCompressorParams;
+    /// use idencomp::progress::ByteNum;
+    ///
+    /// let _params = IdnCompressorParams::builder()
+    ///     .time_budget(Duration::from_secs(60))
+    ///     .expected_input_size(ByteNum::new(512 * 1024 * 1024))
+    ///     .build()?;
+    ///
+    /// # Ok::<(), idencomp::Error>(())
+    /// ```
+    #[must_use]
+    pub fn time_budget(mut self, time_budget: Duration) -> Self {
+        self.time_budget = Some(time_budget);
+        self
+    }
+
+    /// Sets the expected size of the FASTQ data to be compressed, which is
+    /// used to check whether the [`time_budget()`](Self::time_budget) is
+    /// going to be met. Has no effect when no time budget is set.
+    #[must_use]
+    pub fn expected_input_size(mut self, expected_input_size: ByteNum) -> Self {
+        self.expected_input_size = Some(expected_input_size);
+        self
+    }
+
+    /// Sets whether the compression effort should be chosen for each block
+    /// separately, based on a sample of its quality scores. The blocks whose
+    /// quality scores are simple (e.g. binned, or mostly the same value)
+    /// gain little from choosing the models thoroughly, so the models are
+    /// chosen less often for them and their identifiers are compressed with
+    /// the faster codec and settings. This keeps the throughput steadier
+    /// across heterogeneous files, at the cost of a slightly worse
+    /// compression ratio.
+    ///
+    /// The effort only depends on the data, so this can be used in the
+    /// [`deterministic()`](Self::deterministic) mode. When a
+    /// [`time_budget()`](Self::time_budget) is set as well, the lower of the
+    /// two efforts is used.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::idn::compressor::IdnCompressorParams;
+    ///
+    /// let _params = IdnCompressorParams::builder()
+    ///     .adaptive_effort(true)
+    ///     .build()?;
+    ///
+    /// # Ok::<(), idencomp::Error>(())
+    /// ```
+    #[must_use]
+    pub fn adaptive_effort(mut self, adaptive_effort: bool) -> Self {
+        self.adaptive_effort = adaptive_effort;
+        self
+    }
+
+    /// Adds a [`SequenceTransform`] to be applied to the sequences before they
+    /// are compressed. The transforms are applied in the order they have been
+    /// added and their identifiers are stored in the compressed file, so that
+    /// they can be reverted (or, if they are lossy, reported) when
+    /// decompressing.
     ///
-    /// let _params: IdnCompressorParams = IdnCompressorParamsBuilder::new().build();
+    /// # Examples
+    /// ```
+    /// use std::sync::Arc;
+    ///
+    /// use idencomp::fastq::{FastqQualityScore, FastqSequence};
+    /// use idencomp::idn::compressor::{IdnCompressor, IdnCompressorParams};
+    /// use idencomp::idn::inspector::inspect;
+    /// use idencomp::idn::transform::{QualityBinning, QualityMasking};
+    /// use idencomp::sequence::Acid;
+    ///
+    /// let params = IdnCompressorParams::builder()
+    ///     .transform(Arc::new(QualityMasking::new(3)))
+    ///     .transform(Arc::new(QualityBinning))
+    ///     .build()?;
+    /// let mut data = Vec::new();
+    /// let mut compressor = IdnCompressor::with_params(&mut data, params);
+    /// compressor.add_sequence(FastqSequence::new(
+    ///     "SEQ_1",
+    ///     [Acid::A],
+    ///     [FastqQualityScore::new(12)],
+    /// ))?;
+    /// compressor.finish()?;
+    ///
+    /// let info = inspect(data.as_slice())?;
+    /// assert_eq!(info.transforms().len(), 2);
+    /// assert_eq!(info.transforms()[0].identifier(), "quality-masking-q3");
+    /// assert!(info.transforms()[1].is_lossy());
+    ///
+    /// # Ok::<(), idencomp::Error>(())
     /// ```
+    ///
+    /// At most 255 transforms can be set, and their identifiers can be at most
+    /// 255 bytes long; otherwise, [`Self::build()`] fails.
     #[must_use]
-    pub fn build(&mut self) -> IdnCompressorParams {
-        IdnCompressorParams {
-            model_provider: self.model_provider.clone(),
-            max_block_total_len: self.max_block_total_len,
-            progress_notifier: self.progress_notifier.clone(),
+    pub fn transform(mut self, transform: Arc<dyn SequenceTransform>) -> Self {
+        self.transforms.push(transform);
+        self
+    }
+
+    /// Sets the [`QualityAlphabet`] the quality scores are encoded with. For
+    /// the data that only contains a few distinct quality scores (e.g. after
+    /// [`QualityBinning`](crate::idn::transform::QualityBinning)), a reduced
+    /// alphabet makes the quality score models proportionally smaller, which
+    /// speeds up both compression and decompression and improves the
+    /// compression ratio.
+    ///
+    /// The quality score models built for the full alphabet are adapted to
+    /// the reduced one (see
+    /// [`ModelProvider::with_quality_alphabet()`]). The alphabet is stored in
+    /// the compressed file, so the decompressor adapts its models the same
+    /// way. Compressing a sequence with a quality score that is not in the
+    /// alphabet fails with [`IdnCompressorError::QualityScoreNotInAlphabet`].
+    ///
+    /// # Examples
+    /// ```
+    /// use std::sync::Arc;
+    ///
+    /// use idencomp::fastq::{FastqQualityScore, FastqSequence};
+    /// use idencomp::idn::compressor::{IdnCompressor, IdnCompressorParams};
+    /// use idencomp::idn::decompressor::IdnDecompressor;
+    /// use idencomp::idn::inspector::inspect;
+    /// use idencomp::idn::transform::QualityBinning;
+    /// use idencomp::sequence::Acid;
+    ///
+    /// let params = IdnCompressorParams::builder()
+    ///     .transform(Arc::new(QualityBinning))
+    ///     .quality_alphabet(QualityBinning::alphabet())
+    ///     .build()?;
+    /// let mut data = Vec::new();
+    /// let mut compressor = IdnCompressor::with_params(&mut data, params);
+    /// compressor.add_sequence(FastqSequence::new(
+    ///     "SEQ_1",
+    ///     [Acid::A, Acid::C],
+    ///     [FastqQualityScore::new(12), FastqQualityScore::new(41)],
+    /// ))?;
+    /// compressor.finish()?;
+    ///
+    /// let info = inspect(data.as_slice())?;
+    /// assert_eq!(info.quality_alphabet().symbol_num(), 9);
+    /// let sequence = IdnDecompressor::new(data.as_slice()).next_sequence()?.unwrap();
+    /// assert_eq!(
+    ///     sequence.quality_scores(),
+    ///     [FastqQualityScore::new(15), FastqQualityScore::new(40)]
+    /// );
+    ///
+    /// # Ok::<(), idencomp::Error>(())
+    /// ```
+    #[must_use]
+    pub fn quality_alphabet(mut self, quality_alphabet: QualityAlphabet) -> Self {
+        self.quality_alphabet = quality_alphabet;
+        self
+    }
+
+    /// Builds and returns a [`IdnCompressorParams`] instance from the date set
+    /// in this builder, or returns an error if the parameters are
+    /// inconsistent.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::idn::compressor::{
+    ///     CompressionQuality, IdnCompressorParams, IdnCompressorParamsBuilder,
+    ///     IdnCompressorParamsError,
+    /// };
+    /// use idencomp::idn::model_provider::ModelProvider;
+    ///
+    /// let _params: IdnCompressorParams = IdnCompressorParamsBuilder::new().build()?;
+    ///
+    /// let result = IdnCompressorParamsBuilder::new()
+    ///     .fast(true)
+    ///     .quality(CompressionQuality::new(5))
+    ///     .build();
+    /// assert_eq!(
+    ///     result.unwrap_err(),
+    ///     IdnCompressorParamsError::FastModeQuality(CompressionQuality::new(5))
+    /// );
+    ///
+    /// let result = IdnCompressorParamsBuilder::new()
+    ///     .model_provider(ModelProvider::new(vec![]))
+    ///     .build();
+    /// assert!(result.is_err());
+    ///
+    /// # Ok::<(), idencomp::Error>(())
+    /// ```
+    pub fn build(self) -> Result<IdnCompressorParams, IdnCompressorParamsError> {
+        let model_provider = self
+            .model_provider
+            .with_quality_alphabet(&self.quality_alphabet);
+        for model_type in [ModelType::Acids, ModelType::QualityScores] {
+            if !model_provider.has_model_type(model_type) {
+                return Err(IdnCompressorParamsError::MissingModel(model_type));
+            }
+        }
+        let (default_max_block_total_len, default_intra_read_window) = if self.long_reads {
+            (
+                LONG_READ_MAX_BLOCK_TOTAL_LEN,
+                Some(LONG_READ_INTRA_READ_WINDOW),
+            )
+        } else {
+            (DEFAULT_MAX_BLOCK_TOTAL_LEN, None)
+        };
+        let max_block_total_len = self
+            .max_block_total_len
+            .unwrap_or(default_max_block_total_len);
+        let intra_read_window = self.intra_read_window.or(default_intra_read_window);
+
+        if max_block_total_len == 0 {
+            return Err(IdnCompressorParamsError::ZeroMaxBlockTotalLen);
+        }
+        if self.max_block_reads == 0 {
+            return Err(IdnCompressorParamsError::ZeroMaxBlockReads);
+        }
+        if let Some(target) = &self.target_block_reads {
+            if *target.start() == 0 || target.is_empty() {
+                return Err(IdnCompressorParamsError::InvalidTargetBlockReads(
+                    *target.start(),
+                    *target.end(),
+                ));
+            }
+        }
+        if intra_read_window == Some(0) {
+            return Err(IdnCompressorParamsError::ZeroIntraReadWindow);
+        }
+        if self.fast && self.quality != CompressionQuality::MIN {
+            return Err(IdnCompressorParamsError::FastModeQuality(self.quality));
+        }
+        if let Some((quality, window)) = self.brotli_params {
+            if quality > BROTLI_MAX_QUALITY {
+                return Err(IdnCompressorParamsError::InvalidBrotliQuality(quality));
+            }
+            if !BROTLI_WINDOWS.contains(&window) {
+                return Err(IdnCompressorParamsError::InvalidBrotliWindow(window));
+            }
+        }
+        if let Some(level) = self.deflate_level {
+            if level > DEFLATE_MAX_LEVEL {
+                return Err(IdnCompressorParamsError::InvalidDeflateLevel(level));
+            }
+        }
+        if self.deterministic && self.time_budget.is_some() {
+            return Err(IdnCompressorParamsError::DeterministicTimeBudget);
+        }
+        if let Some(ratio) = self.raw_fallback_ratio {
+            if !ratio.is_finite() || ratio <= 0.0 {
+                return Err(IdnCompressorParamsError::InvalidRawFallbackRatio);
+            }
+        }
+        if self.transforms.len() > MAX_TRANSFORMS {
+            return Err(IdnCompressorParamsError::TooManyTransforms(
+                self.transforms.len(),
+            ));
+        }
+        for transform in &self.transforms {
+            let identifier_len = transform.identifier().len();
+            if identifier_len > MAX_TRANSFORM_IDENTIFIER_LEN {
+                return Err(IdnCompressorParamsError::TransformIdentifierTooLong(
+                    identifier_len,
+                ));
+            }
+        }
+
+        Ok(IdnCompressorParams {
+            model_provider,
+            max_block_total_len,
+            max_block_reads: self.max_block_reads,
+            target_block_reads: self.target_block_reads,
+            block_key: self.block_key,
+            intra_read_window,
+            progress_notifier: self.progress_notifier,
+            progress_granularity: self.progress_granularity,
             thread_num: self.thread_num,
             include_identifiers: self.include_identifiers,
             quality: self.quality,
             fast: self.fast,
-        }
+            model_selection: self.model_selection,
+            brotli_params: self.brotli_params,
+            deflate_level: self.deflate_level,
+            deterministic: self.deterministic,
+            deduplicate: self.deduplicate,
+            skip_n_q_scores: self.skip_n_q_scores,
+            raw_fallback_ratio: self.raw_fallback_ratio,
+            group_by_tile: self.group_by_tile,
+            source_hasher: self.source_hasher,
+            context_usage: self.context_usage,
+            explainer: self.explainer,
+            on_sequence: self.on_sequence,
+            warning_sink: self.warning_sink,
+            time_budget: self.time_budget,
+            expected_input_size: self.expected_input_size,
+            adaptive_effort: self.adaptive_effort,
+            transforms: self.transforms,
+            quality_alphabet: self.quality_alphabet,
+            applied_transforms: Vec::new(),
+            preserved_digest: None,
+        })
     }
 }
 
@@ -283,19 +1400,109 @@ impl Default for IdnCompressorParamsBuilder {
 pub(super) struct IdnCompressorOptions {
     pub(super) model_provider: ModelProvider,
     pub(super) progress_notifier: Arc<dyn ProgressNotifier>,
+    pub(super) progress_granularity: usize,
     pub(super) include_identifiers: bool,
     pub(super) quality: CompressionQuality,
     pub(super) fast: bool,
+    pub(super) model_selection: Arc<dyn ModelSelectionStrategy>,
+    pub(super) max_seq_len: usize,
+    pub(super) intra_read_window: Option<usize>,
+    /// Whether the slice data is compressed no matter how short it is, as the
+    /// compression has been configured explicitly.
+    pub(super) force_slice_compression: bool,
+    #[cfg(feature = "brotli")]
+    pub(super) brotli_quality: u32,
+    #[cfg(feature = "brotli")]
+    pub(super) brotli_window: u32,
+    #[cfg(feature = "deflate")]
+    pub(super) deflate_level: u32,
+    pub(super) deterministic: bool,
+    pub(super) deduplicate: bool,
+    pub(super) skip_n_q_scores: bool,
+    pub(super) raw_fallback_ratio: Option<f32>,
+    pub(super) group_by_tile: bool,
+    pub(super) source_hasher: Option<SourceHasher>,
+    pub(super) preserved_digest: Option<PreservedDigest>,
+    pub(super) context_usage: Option<ContextUsage>,
+    pub(super) explainer: Option<Arc<dyn SequenceExplainer>>,
+    pub(super) on_sequence: Option<SequenceCallback>,
+    pub(super) warning_sink: Option<Arc<dyn WarningSink>>,
+    pub(super) time_budget: Option<TimeBudget>,
+    pub(super) adaptive_effort: bool,
+    pub(super) transforms: Vec<TransformInfo>,
+    pub(super) quality_alphabet: QualityAlphabet,
+}
+
+impl IdnCompressorOptions {
+    /// Returns the digest to store in the file once all the data has been
+    /// read, if any.
+    #[must_use]
+    pub(super) fn source_digest(&self) -> Option<SourceDigest> {
+        match &self.preserved_digest {
+            Some(preserved_digest) => preserved_digest.get(),
+            None => self.source_hasher.as_ref().map(SourceHasher::digest),
+        }
+    }
 }
 
 impl From<IdnCompressorParams> for IdnCompressorOptions {
     fn from(params: IdnCompressorParams) -> Self {
+        let max_seq_len = params.max_seq_len().min(MAX_PREALLOCATED_SEQ_LEN);
+        let force_slice_compression =
+            params.brotli_params.is_some() || params.deflate_level.is_some();
+        #[cfg(feature = "brotli")]
+        let (brotli_quality, brotli_window) = params.brotli_params.unwrap_or((
+            params.quality.brotli_quality(),
+            params.quality.brotli_window(),
+        ));
+        #[cfg(feature = "deflate")]
+        let deflate_level = params
+            .deflate_level
+            .unwrap_or_else(|| params.quality.deflate_level());
+
         Self {
             model_provider: params.model_provider,
             progress_notifier: params.progress_notifier,
+            progress_granularity: params.progress_granularity,
             include_identifiers: params.include_identifiers,
             quality: params.quality,
             fast: params.fast,
+            model_selection: params.model_selection,
+            max_seq_len,
+            intra_read_window: params.intra_read_window,
+            force_slice_compression,
+            #[cfg(feature = "brotli")]
+            brotli_quality,
+            #[cfg(feature = "brotli")]
+            brotli_window,
+            #[cfg(feature = "deflate")]
+            deflate_level,
+            deterministic: params.deterministic,
+            deduplicate: params.deduplicate,
+            skip_n_q_scores: params.skip_n_q_scores,
+            raw_fallback_ratio: params.raw_fallback_ratio,
+            group_by_tile: params.group_by_tile,
+            source_hasher: params.source_hasher,
+            preserved_digest: params.preserved_digest,
+            context_usage: params.context_usage,
+            explainer: params.explainer,
+            on_sequence: params.on_sequence,
+            warning_sink: params.warning_sink,
+            time_budget: params
+                .time_budget
+                .map(|budget| TimeBudget::new(budget, params.expected_input_size)),
+            adaptive_effort: params.adaptive_effort,
+            transforms: params
+                .applied_transforms
+                .into_iter()
+                .chain(
+                    params
+                        .transforms
+                        .iter()
+                        .map(|transform| TransformInfo::of(transform.as_ref())),
+                )
+                .collect(),
+            quality_alphabet: params.quality_alphabet,
         }
     }
 }
@@ -303,15 +1510,41 @@ impl From<IdnCompressorParams> for IdnCompressorOptions {
 #[derive(Debug)]
 pub(super) struct IdnCompressorOutState<W> {
     writer: Mutex<IdnWriter<NoSeek<W>>>,
-    block_lock: IdnBlockLock,
+    compressed_blocks: Arc<OrderedQueue<CompressedBlock>>,
+    background_writer: bool,
+    coder_pool: ObjectPool<BlockCoders>,
+    compressor_pool: ObjectPool<SequenceCompressor>,
+    encode_pool: Option<WorkerPool>,
 }
 
 impl<W: Write> IdnCompressorOutState<W> {
+    /// Creates a new output state. If `background_writer` is `true`, the
+    /// compressed blocks have to be written with [`Self::write_all_blocks()`]
+    /// running in a separate thread; otherwise, they are written as soon as
+    /// they are compressed. `compressed_blocks` is shared with the thread
+    /// pools, so that it can be finished if any of the jobs panics. The
+    /// sequences of each block are encoded with `thread_num` threads, or in
+    /// the thread compressing the block if it is `0`.
     #[must_use]
-    pub fn new(writer: W) -> Self {
+    pub fn new(
+        writer: NoSeek<W>,
+        background_writer: bool,
+        compressed_blocks: Arc<OrderedQueue<CompressedBlock>>,
+        thread_num: usize,
+    ) -> Self {
+        let writer = if writer.is_seekable() {
+            IdnWriter::new_seekable(writer)
+        } else {
+            IdnWriter::new(writer)
+        };
+
         Self {
-            writer: Mutex::new(IdnWriter::new(NoSeek::new(writer))),
-            block_lock: IdnBlockLock::new(),
+            writer: Mutex::new(writer),
+            compressed_blocks,
+            background_writer,
+            coder_pool: ObjectPool::new(),
+            compressor_pool: ObjectPool::new(),
+            encode_pool: WorkerPool::new(thread_num, "idn-compressor-encoder"),
         }
     }
 
@@ -319,12 +1552,102 @@ impl<W: Write> IdnCompressorOutState<W> {
         self.writer.lock().expect("Could not acquire writer lock")
     }
 
-    pub fn block_lock(&self) -> &IdnBlockLock {
-        &self.block_lock
+    /// Queues the compressed block to be written, waiting if too many blocks
+    /// are already waiting for the writer.
+    pub fn add_compressed_block(
+        &self,
+        block_index: u32,
+        block: CompressedBlock,
+    ) -> IdnCompressResult<()> {
+        self.compressed_blocks.add(block_index, block);
+
+        if !self.background_writer {
+            while let Some(block) = self.compressed_blocks.try_take_next() {
+                block.write_to(&mut self.writer())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes the compressed blocks in order, as they become available, until
+    /// [`Self::finish_writing()`] is called.
+    pub fn write_all_blocks(&self) -> IdnCompressResult<()> {
+        while let Some(block) = self.compressed_blocks.take_next() {
+            let result = block.write_to(&mut self.writer());
+            if result.is_err() {
+                // Do not let the compressing threads wait for the writer anymore
+                self.compressed_blocks.set_finished();
+                return result;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Signals that no more blocks are going to be compressed.
+    pub fn finish_writing(&self) {
+        self.compressed_blocks.set_finished();
+    }
+
+    pub fn coder_pool(&self) -> &ObjectPool<BlockCoders> {
+        &self.coder_pool
+    }
+
+    /// Returns the pool of the additional sequence compressors used to encode
+    /// the sequences of a block in parallel.
+    pub fn compressor_pool(&self) -> &ObjectPool<SequenceCompressor> {
+        &self.compressor_pool
+    }
+
+    /// Returns the pool encoding the sequences of a block in parallel, or
+    /// `None` if they should be encoded in the current thread.
+    pub fn encode_pool(&self) -> Option<&WorkerPool> {
+        self.encode_pool.as_ref()
+    }
+}
+
+#[derive(Debug)]
+pub(super) struct SequenceBlock {
+    /// Name of the container member starting with this block.
+    pub(super) member: Option<String>,
+    /// The key of the group the sequences belong to, if the blocks are
+    /// grouped.
+    pub(super) group: Option<String>,
+    pub(super) sequences: Vec<FastqSequence>,
+}
+
+impl SequenceBlock {
+    /// Returns whether this block marks the end of the data.
+    #[must_use]
+    pub(super) fn is_eof(&self) -> bool {
+        self.member.is_none() && self.sequences.is_empty()
     }
 }
 
-type SequenceBlock = Vec<FastqSequence>;
+/// The function returning the key the reads are grouped into blocks by.
+#[derive(Clone)]
+struct BlockKey(Arc<dyn Fn(&FastqSequence) -> String + Send + Sync>);
+
+impl BlockKey {
+    #[must_use]
+    fn key_for(&self, sequence: &FastqSequence) -> String {
+        (self.0)(sequence)
+    }
+}
+
+// The key function is a closure, so there is nothing to print
+opaque_debug!(BlockKey);
+
+/// The sequences gathered for a block that has not been finished yet.
+#[derive(Debug)]
+struct PendingBlock {
+    /// The key of the group the sequences belong to, or `None` if the blocks
+    /// are not grouped.
+    key: Option<String>,
+    sequences: Vec<FastqSequence>,
+    length: usize,
+}
 
 #[derive(Debug)]
 struct IdnCompressorInner<W> {
@@ -340,14 +1663,14 @@ struct IdnCompressorInner<W> {
 impl<W: Write + Send> IdnCompressorInner<W> {
     #[must_use]
     fn new(
-        writer: W,
+        state: Arc<IdnCompressorOutState<W>>,
         params: IdnCompressorParams,
         thread_pool: ThreadPool<IdnCompressorError>,
         data_queue: Arc<DataQueue<SequenceBlock>>,
         stats: Arc<CompressionStats>,
     ) -> Self {
         Self {
-            state: Arc::new(IdnCompressorOutState::new(writer)),
+            state,
             options: Arc::new(params.into()),
             current_block: 0,
             initialized: false,
@@ -360,7 +1683,7 @@ impl<W: Write + Send> IdnCompressorInner<W> {
     fn initialize(&mut self, first_block: &SequenceBlock) -> IdnCompressResult<()> {
         let mut writer = self.state.writer();
         let options = Arc::get_mut(&mut self.options).unwrap();
-        let initializer = CompressorInitializer::new(&mut writer, options, first_block);
+        let initializer = CompressorInitializer::new(&mut writer, options, &first_block.sequences);
         initializer.initialize()?;
         self.initialized = true;
 
@@ -394,6 +1717,14 @@ impl<W: Write + Send> IdnCompressorInner<W> {
         if !self.initialized {
             self.initialize(&block)?;
         }
+        if block.is_eof() {
+            // An empty block marks the end of the data, so only the blocks that
+            // are still being compressed are remaining
+            let remaining = self.current_block as usize + 1 - self.stats.blocks();
+            self.options
+                .progress_notifier
+                .start_stage(ProgressStage::Flushing, Some(remaining as u64));
+        }
 
         {
             let options = self.options.clone();
@@ -417,16 +1748,30 @@ impl<W: Write + Send> IdnCompressorInner<W> {
 pub struct IdnCompressor<W> {
     // Inner communication
     inner: Option<IdnCompressorInner<W>>,
+    state: Arc<IdnCompressorOutState<W>>,
     thread_pool: ThreadPool<IdnCompressorError>,
+    writer_pool: ThreadPool<IdnCompressorError>,
     data_queue: Arc<DataQueue<SequenceBlock>>,
+    stats: Arc<CompressionStats>,
 
     // Options
     max_block_total_len: usize,
+    max_block_reads: usize,
+    target_block_reads: Option<RangeInclusive<usize>>,
+    block_key: Option<BlockKey>,
     include_identifiers: bool,
-
-    // Current block
-    block: SequenceBlock,
-    block_length: usize,
+    transforms: Vec<Arc<dyn SequenceTransform>>,
+    quality_alphabet: QualityAlphabet,
+
+    // Current blocks
+    pending_blocks: Vec<PendingBlock>,
+    /// The total length of the pending blocks.
+    pending_blocks_len: usize,
+    finished_blocks: Vec<SequenceBlock>,
+    batching_blocks: bool,
+    max_batched_blocks: usize,
+    member: Option<String>,
+    member_names: HashSet<String>,
 }
 
 impl<W: Write + Send> IdnCompressor<W> {
@@ -444,6 +1789,54 @@ impl<W: Write + Send> IdnCompressor<W> {
         Self::with_params(writer, IdnCompressorParams::default())
     }
 
+    /// Creates a new `IdnCompressor` instance with given params, using the
+    /// [`PreparedModels`] instead of the model provider set in the params.
+    /// This way, the models do not have to be preprocessed again for every
+    /// compressed file.
+    ///
+    /// # Panics
+    /// Panics if the prepared models do not contain both an acid and a
+    /// quality score model.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::idn::compressor::{IdnCompressor, IdnCompressorParams};
+    /// use idencomp::idn::model_provider::ModelProvider;
+    ///
+    /// let prepared = ModelProvider::with_empty_models().prepare_for_compression();
+    /// let params = IdnCompressorParams::builder().build()?;
+    /// for _ in 0..3 {
+    ///     let mut vec = Vec::new();
+    ///     let compressor = IdnCompressor::with_prepared(&mut vec, &prepared, params.clone());
+    ///     compressor.finish()?;
+    /// }
+    ///
+    /// # Ok::<(), idencomp::Error>(())
+    /// ```
+    #[must_use]
+    pub fn with_prepared(
+        writer: W,
+        prepared: &PreparedModels,
+        mut params: IdnCompressorParams,
+    ) -> Self {
+        // Adapting the models to the alphabet keeps them preprocessed as long
+        // as they have been prepared with the same alphabet
+        let model_provider = prepared
+            .model_provider()
+            .clone()
+            .with_quality_alphabet(&params.quality_alphabet);
+        for model_type in [ModelType::Acids, ModelType::QualityScores] {
+            assert!(
+                model_provider.has_model_type(model_type),
+                "The prepared models do not contain any {} model",
+                model_type
+            );
+        }
+
+        params.model_provider = model_provider;
+        Self::with_params(writer, params)
+    }
+
     /// Creates a new `IdnCompressor` instance with given params.
     ///
     /// # Examples
@@ -451,53 +1844,144 @@ impl<W: Write + Send> IdnCompressor<W> {
     /// use idencomp::idn::compressor::{IdnCompressor, IdnCompressorParams};
     ///
     /// let mut vec = Vec::new();
-    /// let mut params = IdnCompressorParams::builder().fast(true).build();
+    /// let params = IdnCompressorParams::builder().fast(true).build()?;
     /// let compressor = IdnCompressor::with_params(&mut vec, params);
+    ///
+    /// # Ok::<(), idencomp::Error>(())
     /// ```
     #[must_use]
     pub fn with_params(writer: W, params: IdnCompressorParams) -> Self {
+        Self::with_writer(NoSeek::new(writer), params)
+    }
+
+    /// Creates a new `IdnCompressor` instance with given params over a
+    /// seekable writer. Unlike [`IdnCompressor::with_params()`], this allows
+    /// the compressor to patch the metadata at the start of the file once all
+    /// the data has been written, instead of appending it at the end.
+    ///
+    /// # Errors
+    /// Returns an error if the current position of the writer could not be
+    /// determined.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Cursor;
+    ///
+    /// use idencomp::idn::compressor::{IdnCompressor, IdnCompressorParams};
+    ///
+    /// let mut cursor = Cursor::new(Vec::new());
+    /// let params = IdnCompressorParams::builder().fast(true).build()?;
+    /// let compressor = IdnCompressor::with_params_seekable(&mut cursor, params)?;
+    /// compressor.finish()?;
+    ///
+    /// # Ok::<(), idencomp::Error>(())
+    /// ```
+    pub fn with_params_seekable(writer: W, params: IdnCompressorParams) -> IdnCompressResult<Self>
+    where
+        W: Seek,
+    {
+        Ok(Self::with_writer(NoSeek::seekable(writer)?, params))
+    }
+
+    #[must_use]
+    fn with_writer(writer: NoSeek<W>, params: IdnCompressorParams) -> Self {
         let max_block_total_len = params.max_block_total_len;
+        let max_block_reads = params.max_block_reads;
+        let target_block_reads = params.target_block_reads.clone();
+        let block_key = params.block_key.clone();
         let include_identifiers = params.include_identifiers;
+        let transforms = params.transforms.clone();
+        let quality_alphabet = params.quality_alphabet.clone();
+
+        let max_batched_blocks = Self::max_pending_blocks(params.thread_num);
 
-        let thread_pool = ThreadPool::new(params.thread_num, "idn-compressor");
         let data_queue = Arc::new(DataQueue::new());
+        let compressed_blocks = Arc::new(OrderedQueue::new(Self::max_pending_blocks(
+            params.thread_num,
+        )));
+        // If any of the jobs panics, the others would otherwise wait forever
+        // for the blocks it was supposed to produce or consume
+        let on_panic = {
+            let data_queue = data_queue.clone();
+            let compressed_blocks = compressed_blocks.clone();
+            move || {
+                data_queue.set_finished();
+                compressed_blocks.set_finished();
+            }
+        };
+        let thread_pool =
+            ThreadPool::new(params.thread_num, "idn-compressor").on_panic(on_panic.clone());
+        // Writing the output gets its own thread, so that the slow output
+        // devices do not stall the threads compressing the blocks
+        let writer_pool =
+            ThreadPool::new(params.thread_num.min(1), "idn-compressor-writer").on_panic(on_panic);
+        let stats = Arc::new(CompressionStats::new());
+        params
+            .progress_notifier
+            .start_stage(ProgressStage::Parsing, None);
+
+        let state = Arc::new(IdnCompressorOutState::new(
+            writer,
+            !writer_pool.is_foreground(),
+            compressed_blocks,
+            params.thread_num,
+        ));
+        if !writer_pool.is_foreground() {
+            let state = state.clone();
+            writer_pool.spawn(move || state.write_all_blocks());
+        }
 
         let inner = IdnCompressorInner::new(
-            writer,
+            state.clone(),
             params,
             thread_pool.make_child(),
             data_queue.clone(),
-            Arc::new(CompressionStats::new()),
+            stats.clone(),
         );
         let inner = if thread_pool.is_foreground() {
             Some(inner)
         } else {
             let mut inner = inner;
-            thread_pool
-                .execute(move || {
-                    inner.write_all_blocks()?;
-                    Ok(())
-                })
-                .expect("Unexpected Thread Pool error");
+            thread_pool.spawn(move || {
+                inner.write_all_blocks()?;
+                Ok(())
+            });
 
             None
         };
 
         Self {
             inner,
+            state,
             thread_pool,
+            writer_pool,
             data_queue,
+            stats,
 
             max_block_total_len,
+            max_block_reads,
+            target_block_reads,
+            block_key,
             include_identifiers,
-
-            block: SequenceBlock::new(),
-            block_length: 0,
+            transforms,
+            quality_alphabet,
+
+            pending_blocks: Vec::new(),
+            pending_blocks_len: 0,
+            finished_blocks: Vec::new(),
+            batching_blocks: false,
+            max_batched_blocks,
+            member: None,
+            member_names: HashSet::new(),
         }
     }
 
     /// Adds given sequence to be compressed in given file.
     ///
+    /// The auxiliary data attached to the sequence (see
+    /// [`FastqSequence::aux_data()`]) is compressed along with it and restored
+    /// by the decompressor.
+    ///
     /// # Examples
     /// ```
     /// use idencomp::fastq::{FastqQualityScore, FastqSequence};
@@ -510,31 +1994,239 @@ impl<W: Write + Send> IdnCompressor<W> {
     ///     NucleotideSequenceIdentifier::EMPTY,
     ///     [Acid::A],
     ///     [FastqQualityScore::new(5)],
-    /// ))?;
+    /// ))?;
+    ///
+    /// # Ok::<(), IdnCompressorError>(())
+    /// ```
+    pub fn add_sequence(&mut self, sequence: FastqSequence) -> IdnCompressResult<()> {
+        self.add_sequence_internal(sequence, self.max_seq_len())
+    }
+
+    /// Adds given sequences to be compressed in given file, as if they were
+    /// passed to [`Self::add_sequence()`] one by one.
+    ///
+    /// The blocks finished while adding the sequences are handed over to the
+    /// compression threads together, which makes this method a better fit
+    /// for the batches of sequences, such as the ones returned by
+    /// [`IdnDecompressor::into_batches()`].
+    ///
+    /// [`IdnDecompressor::into_batches()`]: crate::idn::decompressor::IdnDecompressor::into_batches
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::fastq::{FastqQualityScore, FastqSequence};
+    /// use idencomp::idn::compressor::IdnCompressor;
+    /// use idencomp::idn::decompressor::IdnDecompressor;
+    /// use idencomp::sequence::{Acid, NucleotideSequenceIdentifier};
+    ///
+    /// let sequence = FastqSequence::new(
+    ///     NucleotideSequenceIdentifier::EMPTY,
+    ///     [Acid::A],
+    ///     [FastqQualityScore::new(5)],
+    /// );
+    ///
+    /// let mut vec = Vec::new();
+    /// let mut compressor = IdnCompressor::new(&mut vec);
+    /// compressor.add_sequences(vec![sequence.clone(), sequence])?;
+    /// compressor.finish()?;
+    ///
+    /// let mut recompressed = Vec::new();
+    /// let mut compressor = IdnCompressor::new(&mut recompressed);
+    /// for batch in IdnDecompressor::new(vec.as_slice()).into_batches() {
+    ///     compressor.add_sequences(batch?)?;
+    /// }
+    /// compressor.finish()?;
+    ///
+    /// # Ok::<(), idencomp::Error>(())
+    /// ```
+    pub fn add_sequences<I>(&mut self, sequences: I) -> IdnCompressResult<()>
+    where
+        I: IntoIterator<Item = FastqSequence>,
+    {
+        let max_seq_len = self.max_seq_len();
+
+        self.batching_blocks = true;
+        let result = sequences
+            .into_iter()
+            .try_for_each(|sequence| self.add_sequence_internal(sequence, max_seq_len));
+        self.batching_blocks = false;
+
+        // The blocks finished before an error are queued anyway, just like
+        // with the sequences added one by one
+        let queued = self.queue_finished_blocks();
+        result.and(queued)
+    }
+
+    fn add_sequence_internal(
+        &mut self,
+        sequence: FastqSequence,
+        max_seq_len: usize,
+    ) -> IdnCompressResult<()> {
+        // The transforms rebuild the sequences, so the auxiliary data and the
+        // separator text are attached back afterwards
+        let (sequence, aux_data) = sequence.split_aux_data();
+        let (sequence, separator_text) = sequence.split_separator_text();
+        let sequence = self
+            .transforms
+            .iter()
+            .fold(sequence, |sequence, transform| transform.apply(sequence))
+            .with_aux_data(aux_data)
+            .with_separator_text(separator_text);
+        let seq_len = sequence.len();
+        if seq_len > max_seq_len {
+            return Err(IdnCompressorError::sequence_too_long(seq_len, max_seq_len));
+        }
+        if !self.quality_alphabet.is_full() {
+            let q_score = sequence
+                .quality_scores()
+                .iter()
+                .find(|&&q_score| self.quality_alphabet.index_of(q_score).is_none());
+            if let Some(&q_score) = q_score {
+                return Err(IdnCompressorError::quality_score_not_in_alphabet(q_score));
+            }
+        }
+
+        // Zero-length sequences still take space in the block (the header and
+        // the identifier), so they are counted as having one symbol;
+        // otherwise, a block of such sequences would never be finished
+        let block_seq_len = seq_len.max(1);
+        let key = self
+            .block_key
+            .as_ref()
+            .map(|block_key| block_key.key_for(&sequence));
+        if let Some(key) = &key {
+            if key.len() > u16::MAX as usize {
+                return Err(IdnCompressorError::block_key_too_long(key.len()));
+            }
+        }
+        let index = self.pending_block_for(key, block_seq_len)?;
+
+        let sequence = if self.include_identifiers {
+            sequence
+        } else {
+            sequence.with_identifier_discarded()
+        };
+
+        let block = &mut self.pending_blocks[index];
+        block.sequences.push(sequence);
+        block.length += block_seq_len;
+        self.pending_blocks_len += block_seq_len;
+
+        Ok(())
+    }
+
+    /// Returns the index of the pending block a sequence of given length and
+    /// block key should be added to, finishing the blocks that cannot take it
+    /// first.
+    fn pending_block_for(
+        &mut self,
+        key: Option<String>,
+        seq_len: usize,
+    ) -> IdnCompressResult<usize> {
+        if let Some(index) = self.pending_block_index(&key) {
+            if self.is_block_full(&self.pending_blocks[index], seq_len) {
+                self.finish_pending_block(index)?;
+            }
+        }
+
+        // Many groups could otherwise take an unbounded amount of memory
+        let max_pending_len = self
+            .max_block_total_len
+            .saturating_mul(MAX_PENDING_BLOCKS_LEN_FACTOR);
+        while !self.pending_blocks.is_empty() && self.pending_blocks_len + seq_len > max_pending_len
+        {
+            let (largest, _) = self
+                .pending_blocks
+                .iter()
+                .enumerate()
+                .max_by_key(|(_, block)| block.length)
+                .unwrap();
+            self.finish_pending_block(largest)?;
+        }
+
+        if let Some(index) = self.pending_block_index(&key) {
+            return Ok(index);
+        }
+        self.pending_blocks.push(PendingBlock {
+            key,
+            sequences: Vec::new(),
+            length: 0,
+        });
+        Ok(self.pending_blocks.len() - 1)
+    }
+
+    /// Adds all the sequences from given source, as if they were passed to
+    /// [`Self::add_sequence()`] one by one, and returns the number of the
+    /// sequences added.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::fastq::reader::FastqReader;
+    /// use idencomp::idn::compressor::IdnCompressor;
+    ///
+    /// let fastq = b"@SEQ1\nACGT\n+\n!!!!\n@SEQ2\nTT\n+\n!!\n";
+    /// let mut vec = Vec::new();
+    /// let mut compressor = IdnCompressor::new(&mut vec);
+    /// assert_eq!(compressor.compress_from(FastqReader::new(fastq.as_slice()))?, 2);
+    /// compressor.finish()?;
+    ///
+    /// # Ok::<(), idencomp::Error>(())
+    /// ```
+    pub fn compress_from<S: SequenceSource>(&mut self, mut source: S) -> crate::Result<usize> {
+        let mut sequence_num = 0;
+        while let Some(sequence) = source.next_sequence()? {
+            self.add_sequence(sequence)?;
+            sequence_num += 1;
+        }
+
+        Ok(sequence_num)
+    }
+
+    /// Starts a new container member with given name. All the sequences added
+    /// after this call (until the next member is started) belong to this
+    /// member, and can be extracted separately with
+    /// [`IdnDecompressor::extract()`](crate::idn::decompressor::IdnDecompressor::extract).
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::fastq::{FastqQualityScore, FastqSequence};
+    /// use idencomp::idn::compressor::IdnCompressor;
+    /// use idencomp::idn::decompressor::IdnDecompressor;
+    /// use idencomp::sequence::{Acid, NucleotideSequenceIdentifier};
+    ///
+    /// let sequence = FastqSequence::new(
+    ///     NucleotideSequenceIdentifier::EMPTY,
+    ///     [Acid::A],
+    ///     [FastqQualityScore::new(5)],
+    /// );
     ///
-    /// # Ok::<(), IdnCompressorError>(())
+    /// let mut vec = Vec::new();
+    /// let mut compressor = IdnCompressor::new(&mut vec);
+    /// compressor.start_member("first.fastq")?;
+    /// compressor.add_sequence(sequence.clone())?;
+    /// compressor.start_member("second.fastq")?;
+    /// compressor.add_sequence(sequence.clone())?;
+    /// compressor.add_sequence(sequence)?;
+    /// compressor.finish()?;
+    ///
+    /// let members = IdnDecompressor::members(vec.as_slice())?;
+    /// assert_eq!(members.len(), 2);
+    /// assert_eq!(members[1].name(), "second.fastq");
+    /// assert_eq!(members[1].sequence_num(), 2);
+    ///
+    /// # Ok::<(), idencomp::Error>(())
     /// ```
-    pub fn add_sequence(&mut self, sequence: FastqSequence) -> IdnCompressResult<()> {
-        let seq_len = sequence.len();
-        if seq_len > self.max_seq_len() {
-            return Err(IdnCompressorError::sequence_too_long(
-                seq_len,
-                self.max_seq_len(),
-            ));
+    pub fn start_member<T: Into<String>>(&mut self, name: T) -> IdnCompressResult<()> {
+        let name = name.into();
+        if name.len() > u16::MAX as usize {
+            return Err(IdnCompressorError::member_name_too_long(name.len()));
         }
-
-        if self.block_length + seq_len > self.max_block_total_len {
-            self.make_block()?;
+        if !self.member_names.insert(name.clone()) {
+            return Err(IdnCompressorError::duplicate_member(name));
         }
 
-        let sequence = if self.include_identifiers {
-            sequence
-        } else {
-            sequence.with_identifier_discarded()
-        };
-
-        self.block.push(sequence);
-        self.block_length += seq_len;
+        self.finish_pending_blocks()?;
+        self.member = Some(name);
 
         Ok(())
     }
@@ -543,13 +2235,93 @@ impl<W: Write + Send> IdnCompressor<W> {
         self.max_block_total_len / 2
     }
 
-    fn make_block(&mut self) -> IdnCompressResult<()> {
-        self.thread_pool.get_status()?;
+    /// Returns whether given block has to be finished before adding a
+    /// sequence of given length.
+    fn is_block_full(&self, block: &PendingBlock, seq_len: usize) -> bool {
+        let block_reads = block.sequences.len();
+        let block_length = block.length + seq_len;
+        if block_reads >= self.max_block_reads {
+            return true;
+        }
+
+        match &self.target_block_reads {
+            Some(target) => {
+                block_reads >= *target.end()
+                    || (block_length > self.max_block_total_len && block_reads >= *target.start())
+                    || block_length
+                        > self
+                            .max_block_total_len
+                            .saturating_mul(TARGET_BLOCK_READS_MAX_GROWTH)
+            }
+            None => block_length > self.max_block_total_len,
+        }
+    }
+
+    /// Returns the maximum number of compressed blocks waiting to be written,
+    /// which is enough for all the threads to keep compressing while the
+    /// output catches up.
+    fn max_pending_blocks(thread_num: usize) -> usize {
+        2 * thread_num.max(1)
+    }
+
+    /// Returns the index of the pending block of the group with given key.
+    fn pending_block_index(&self, key: &Option<String>) -> Option<usize> {
+        self.pending_blocks
+            .iter()
+            .position(|block| block.key == *key)
+    }
+
+    fn finish_pending_block(&mut self, index: usize) -> IdnCompressResult<()> {
+        let block = self.pending_blocks.remove(index);
+        self.pending_blocks_len -= block.length;
+        self.make_block(block.key, block.sequences)
+    }
+
+    /// Finishes all the pending blocks, in the order they have been started.
+    /// If there are none, but a container member has been started, an empty
+    /// block is made to store it.
+    fn finish_pending_blocks(&mut self) -> IdnCompressResult<()> {
+        if self.pending_blocks.is_empty() && self.member.is_some() {
+            return self.make_block(None, Vec::new());
+        }
 
-        let block = mem::take(&mut self.block);
-        self.block_length = 0;
+        self.pending_blocks_len = 0;
+        for block in mem::take(&mut self.pending_blocks) {
+            self.make_block(block.key, block.sequences)?;
+        }
+
+        Ok(())
+    }
+
+    fn make_block(
+        &mut self,
+        group: Option<String>,
+        sequences: Vec<FastqSequence>,
+    ) -> IdnCompressResult<()> {
+        self.finished_blocks.push(SequenceBlock {
+            member: self.member.take(),
+            group,
+            sequences,
+        });
+
+        // The batches are still handed over every few blocks, so that the
+        // threads do not run out of work when a batch is large
+        if self.batching_blocks && self.finished_blocks.len() < self.max_batched_blocks {
+            return Ok(());
+        }
+        self.queue_finished_blocks()
+    }
+
+    fn queue_finished_blocks(&mut self) -> IdnCompressResult<()> {
+        if self.finished_blocks.is_empty() {
+            return Ok(());
+        }
+
+        self.thread_pool.get_status()?;
+        self.writer_pool.get_status()?;
 
-        self.data_queue.add(block);
+        self.data_queue
+            .add_all(mem::take(&mut self.finished_blocks));
 
         if let Some(inner) = &mut self.inner {
             inner.write_current_blocks()?;
@@ -572,30 +2344,72 @@ impl<W: Write + Send> IdnCompressor<W> {
     ///
     /// # Ok::<(), IdnCompressorError>(())
     /// ```
-    pub fn finish(mut self) -> IdnCompressResult<()> {
-        if !self.block.is_empty() {
-            self.make_block()?;
-        }
-        self.make_block()?;
+    pub fn finish(self) -> IdnCompressResult<()> {
+        self.finish_with_stats().map(|_| ())
+    }
+
+    /// Does the same as [`Self::finish()`], but returns the final statistics
+    /// of the compression.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::idn::compressor::{IdnCompressor, IdnCompressorError};
+    ///
+    /// let mut vec = Vec::new();
+    /// let compressor = IdnCompressor::new(&mut vec);
+    /// let stats = compressor.finish_with_stats()?;
+    /// assert_eq!(stats.in_bytes().get(), 0);
+    /// assert_eq!(stats.out_bytes(), vec.len());
+    ///
+    /// # Ok::<(), IdnCompressorError>(())
+    /// ```
+    pub fn finish_with_stats(mut self) -> IdnCompressResult<CompressionStatsSnapshot> {
+        self.finish_pending_blocks()?;
+        self.make_block(None, Vec::new())?;
 
         self.data_queue.set_finished();
         self.thread_pool.join()?;
+        self.state.finish_writing();
+        self.writer_pool.join()?;
 
-        Ok(())
+        Ok(self.stats.snapshot())
+    }
+
+    /// Returns the statistics of the compression so far.
+    ///
+    /// Only the blocks that have already been compressed are included, so
+    /// the sequences added recently might not be reflected in the result
+    /// yet.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::idn::compressor::IdnCompressor;
+    ///
+    /// let mut vec = Vec::new();
+    /// let compressor = IdnCompressor::new(&mut vec);
+    /// assert_eq!(compressor.stats().blocks(), 0);
+    /// ```
+    #[must_use]
+    pub fn stats(&self) -> CompressionStatsSnapshot {
+        self.stats.snapshot()
     }
 }
 
 impl<W> Drop for IdnCompressor<W> {
     fn drop(&mut self) {
-        self.thread_pool
-            .join()
-            .expect("Could not wait for the thread pool to finish");
+        // If the compression has failed, the inner thread may still be waiting
+        // for more blocks
+        self.data_queue.set_finished();
+        // Errors cannot be reported here; use `finish()` to get them
+        let _ = self.thread_pool.join();
+        self.state.compressed_blocks.set_finished();
+        let _ = self.writer_pool.join();
     }
 }
 
 #[derive(Debug)]
 pub(super) struct CompressionStats {
-    start_time: Instant,
+    start_time: Option<Instant>,
 
     in_bytes: AtomicUsize,
     in_identifier_bytes: AtomicUsize,
@@ -609,13 +2423,14 @@ pub(super) struct CompressionStats {
     blocks: AtomicUsize,
     acid_model_switches: AtomicUsize,
     q_score_model_switches: AtomicUsize,
+    identifier_fallbacks: AtomicUsize,
 }
 
 impl CompressionStats {
     #[must_use]
     pub fn new() -> Self {
         Self {
-            start_time: Instant::now(),
+            start_time: start_time(),
 
             in_bytes: AtomicUsize::new(0),
             in_identifier_bytes: AtomicUsize::new(0),
@@ -629,6 +2444,7 @@ impl CompressionStats {
             blocks: AtomicUsize::new(0),
             acid_model_switches: AtomicUsize::new(0),
             q_score_model_switches: AtomicUsize::new(0),
+            identifier_fallbacks: AtomicUsize::new(0),
         }
     }
 
@@ -636,6 +2452,11 @@ impl CompressionStats {
         self.in_bytes.fetch_add(bytes.get(), Ordering::Relaxed);
     }
 
+    #[must_use]
+    pub fn in_bytes(&self) -> ByteNum {
+        ByteNum::new(self.in_bytes.load(Ordering::Relaxed))
+    }
+
     pub fn add_in_identifier_bytes(&self, num: usize) {
         self.in_identifier_bytes.fetch_add(num, Ordering::Relaxed);
     }
@@ -664,6 +2485,17 @@ impl CompressionStats {
         self.blocks.fetch_add(1, Ordering::Relaxed);
     }
 
+    #[must_use]
+    pub fn blocks(&self) -> usize {
+        self.blocks.load(Ordering::SeqCst)
+    }
+
+    /// Increments the number of blocks with the identifiers stored
+    /// uncompressed as a fallback, returning the previous number.
+    pub fn inc_identifier_fallbacks(&self) -> usize {
+        self.identifier_fallbacks.fetch_add(1, Ordering::Relaxed)
+    }
+
     pub fn add_acid_model_switches(&self, num: usize) {
         self.acid_model_switches.fetch_add(num, Ordering::Relaxed);
     }
@@ -673,30 +2505,52 @@ impl CompressionStats {
             .fetch_add(num, Ordering::Relaxed);
     }
 
+    #[must_use]
+    pub fn snapshot(&self) -> CompressionStatsSnapshot {
+        CompressionStatsSnapshot {
+            elapsed: self.start_time.map(|start_time| start_time.elapsed()),
+
+            in_bytes: ByteNum::new(self.in_bytes.load(Ordering::SeqCst)),
+            in_identifier_bytes: self.in_identifier_bytes.load(Ordering::SeqCst),
+            in_symbols: self.in_symbols.load(Ordering::SeqCst),
+
+            out_bytes: self.out_bytes.load(Ordering::SeqCst),
+            out_identifier_bytes: self.out_identifier_bytes.load(Ordering::SeqCst),
+            out_acid_bytes: self.out_acid_bytes.load(Ordering::SeqCst),
+            out_q_score_bytes: self.out_q_score_bytes.load(Ordering::SeqCst),
+
+            blocks: self.blocks.load(Ordering::SeqCst),
+            acid_model_switches: self.acid_model_switches.load(Ordering::SeqCst),
+            q_score_model_switches: self.q_score_model_switches.load(Ordering::SeqCst),
+        }
+    }
+
     fn print_stats(&self) {
-        let in_bytes = self.in_bytes.load(Ordering::SeqCst);
-        let in_identifier_bytes = self.in_identifier_bytes.load(Ordering::SeqCst);
-        let in_symbols = self.in_symbols.load(Ordering::SeqCst);
+        let stats = self.snapshot();
 
-        let out_bytes = self.out_bytes.load(Ordering::SeqCst);
-        let out_identifier_bytes = self.out_identifier_bytes.load(Ordering::SeqCst);
-        let out_acid_bytes = self.out_acid_bytes.load(Ordering::SeqCst);
-        let out_q_score_bytes = self.out_q_score_bytes.load(Ordering::SeqCst);
+        let in_bytes = stats.in_bytes.get();
+        let in_identifier_bytes = stats.in_identifier_bytes;
+        let in_symbols = stats.in_symbols;
 
-        let blocks = self.blocks.load(Ordering::SeqCst);
-        let acid_model_switches = self.acid_model_switches.load(Ordering::SeqCst);
-        let q_score_model_switches = self.q_score_model_switches.load(Ordering::SeqCst);
+        let out_bytes = stats.out_bytes;
+        let out_identifier_bytes = stats.out_identifier_bytes;
+        let out_acid_bytes = stats.out_acid_bytes;
+        let out_q_score_bytes = stats.out_q_score_bytes;
+
+        let blocks = stats.blocks;
+        let acid_model_switches = stats.acid_model_switches;
+        let q_score_model_switches = stats.q_score_model_switches;
 
         info!(
             "Compressed {}",
-            format_stats(self.start_time, ByteNum::new(in_bytes))
+            format_stats(self.start_time, stats.in_bytes)
         );
         info!("{} symbols", in_symbols);
 
         let rate = out_bytes as f32 / in_bytes as f32 * 100.0;
         info!("File: {:>9} -> {:>9} ({:>7.3}%)", in_bytes, out_bytes, rate);
 
-        let header_bytes = out_bytes - out_identifier_bytes - out_acid_bytes - out_q_score_bytes;
+        let header_bytes = stats.out_header_bytes();
         let header_rate = header_bytes as f32 / out_bytes as f32 * 100.0;
         info!(
             "Hder: {:>9} -> {:>9} ({:>7.3}%)",
@@ -736,20 +2590,392 @@ impl Drop for CompressionStats {
     }
 }
 
+/// The statistics of the compression at some point in time, as returned by
+/// [`IdnCompressor::stats()`].
+///
+/// The "input" values describe the FASTQ data fed to the compressor, while
+/// the "output" values describe the IDN data written so far.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct CompressionStatsSnapshot {
+    elapsed: Option<Duration>,
+
+    in_bytes: ByteNum,
+    in_identifier_bytes: usize,
+    in_symbols: usize,
+
+    out_bytes: usize,
+    out_identifier_bytes: usize,
+    out_acid_bytes: usize,
+    out_q_score_bytes: usize,
+
+    blocks: usize,
+    acid_model_switches: usize,
+    q_score_model_switches: usize,
+}
+
+impl CompressionStatsSnapshot {
+    /// Returns the time elapsed since the compressor was created, or `None`
+    /// if the time is not available on the current platform.
+    #[must_use]
+    pub fn elapsed(&self) -> Option<Duration> {
+        self.elapsed
+    }
+
+    /// Returns the number of FASTQ bytes compressed.
+    #[must_use]
+    pub fn in_bytes(&self) -> ByteNum {
+        self.in_bytes
+    }
+
+    /// Returns the number of bytes of the sequence identifiers compressed.
+    #[must_use]
+    pub fn in_identifier_bytes(&self) -> usize {
+        self.in_identifier_bytes
+    }
+
+    /// Returns the number of symbols (i.e. acids, each accompanied by a
+    /// quality score) compressed.
+    #[must_use]
+    pub fn in_symbols(&self) -> usize {
+        self.in_symbols
+    }
+
+    /// Returns the number of bytes of the IDN file written.
+    #[must_use]
+    pub fn out_bytes(&self) -> usize {
+        self.out_bytes
+    }
+
+    /// Returns the number of bytes the compressed identifiers take.
+    #[must_use]
+    pub fn out_identifier_bytes(&self) -> usize {
+        self.out_identifier_bytes
+    }
+
+    /// Returns the number of bytes the compressed acids take.
+    #[must_use]
+    pub fn out_acid_bytes(&self) -> usize {
+        self.out_acid_bytes
+    }
+
+    /// Returns the number of bytes the compressed quality scores take.
+    #[must_use]
+    pub fn out_q_score_bytes(&self) -> usize {
+        self.out_q_score_bytes
+    }
+
+    /// Returns the number of bytes of the IDN file that are not the compressed
+    /// data (i.e. the file header, metadata and block headers).
+    #[must_use]
+    pub fn out_header_bytes(&self) -> usize {
+        self.out_bytes
+            .saturating_sub(self.out_identifier_bytes)
+            .saturating_sub(self.out_acid_bytes)
+            .saturating_sub(self.out_q_score_bytes)
+    }
+
+    /// Returns the average number of bits used per identifier byte, or `None`
+    /// if no identifiers have been compressed.
+    #[must_use]
+    pub fn identifier_bpv(&self) -> Option<f32> {
+        bits_per_value(self.out_identifier_bytes, self.in_identifier_bytes)
+    }
+
+    /// Returns the average number of bits used per acid, or `None` if no
+    /// acids have been compressed.
+    #[must_use]
+    pub fn acid_bpv(&self) -> Option<f32> {
+        bits_per_value(self.out_acid_bytes, self.in_symbols)
+    }
+
+    /// Returns the average number of bits used per quality score, or `None`
+    /// if no quality scores have been compressed.
+    #[must_use]
+    pub fn q_score_bpv(&self) -> Option<f32> {
+        bits_per_value(self.out_q_score_bytes, self.in_symbols)
+    }
+
+    /// Returns the number of blocks written, including the empty block marking
+    /// the end of the file.
+    #[must_use]
+    pub fn blocks(&self) -> usize {
+        self.blocks
+    }
+
+    /// Returns the number of times the acid model has been switched, including
+    /// choosing the initial model in each block. This is always `0` in the fast
+    /// mode.
+    #[must_use]
+    pub fn acid_model_switches(&self) -> usize {
+        self.acid_model_switches
+    }
+
+    /// Returns the number of times the quality score model has been switched,
+    /// including choosing the initial model in each block. This is always `0`
+    /// in the fast mode.
+    #[must_use]
+    pub fn q_score_model_switches(&self) -> usize {
+        self.q_score_model_switches
+    }
+}
+
+#[must_use]
+fn bits_per_value(out_bytes: usize, in_values: usize) -> Option<f32> {
+    if in_values == 0 {
+        None
+    } else {
+        Some(out_bytes as f32 * 8.0 / in_values as f32)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::error::Error;
     use std::io;
     use std::io::ErrorKind::NotFound;
+    use std::ops::RangeInclusive;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use itertools::Itertools;
+
+    use crate::_internal_test_data::{SEQ_1K_READS, SHORT_TEST_SEQUENCE};
+    use crate::fastq::{FastqQualityScore, FastqSequence};
+    use crate::idn::compressor::{
+        CompressionQuality, IdnCompressor, IdnCompressorError, IdnCompressorOptions,
+        IdnCompressorParams, IdnCompressorParamsError, DEFAULT_MAX_BLOCK_TOTAL_LEN,
+        LONG_READ_INTRA_READ_WINDOW, MAX_PREALLOCATED_SEQ_LEN,
+    };
+    use crate::idn::decompressor::IdnDecompressor;
+    use crate::idn::inspector::inspect;
+    use crate::idn::transform::SequenceTransform;
+
+    /// A lossy transform that does nothing, with arbitrary identifier.
+    #[derive(Debug)]
+    struct NamedTransform(String);
+
+    impl SequenceTransform for NamedTransform {
+        fn identifier(&self) -> String {
+            self.0.clone()
+        }
+
+        fn is_lossy(&self) -> bool {
+            true
+        }
+
+        fn apply(&self, sequence: FastqSequence) -> FastqSequence {
+            sequence
+        }
+    }
+
+    #[test]
+    fn test_max_block_reads() {
+        let mut data = Vec::new();
+        let params = IdnCompressorParams::builder()
+            .fast(true)
+            .max_block_reads(300)
+            .build()
+            .unwrap();
+        let mut compressor = IdnCompressor::with_params(&mut data, params);
+        for sequence in SEQ_1K_READS.iter() {
+            compressor.add_sequence(sequence.clone()).unwrap();
+        }
+        compressor.finish().unwrap();
+
+        let info = inspect(data.as_slice()).unwrap();
+        let block_sizes: Vec<_> = info.blocks().iter().map(|b| b.sequence_num()).collect();
+        assert_eq!(block_sizes, [300, 300, 300, 100]);
+    }
+
+    fn target_block_sizes(max_block_total_len: usize, target: RangeInclusive<usize>) -> Vec<usize> {
+        let mut data = Vec::new();
+        let params = IdnCompressorParams::builder()
+            .fast(true)
+            .max_block_total_len(max_block_total_len)
+            .target_block_reads(target)
+            .build()
+            .unwrap();
+        let mut compressor = IdnCompressor::with_params(&mut data, params);
+        for sequence in SEQ_1K_READS.iter() {
+            compressor.add_sequence(sequence.clone()).unwrap();
+        }
+        compressor.finish().unwrap();
+
+        let info = inspect(data.as_slice()).unwrap();
+        info.blocks().iter().map(|b| b.sequence_num()).collect()
+    }
+
+    #[test]
+    fn test_target_block_reads() {
+        // Short blocks are limited by the upper bound
+        assert_eq!(
+            target_block_sizes(DEFAULT_MAX_BLOCK_TOTAL_LEN, 1..=300),
+            [300, 300, 300, 100]
+        );
+        // The block length of 10 reads is exceeded to gather 20 reads
+        assert_eq!(target_block_sizes(760, 20..=300), vec![20; 50]);
+        // ...but only up to 4 times the block length
+        assert_eq!(target_block_sizes(760, 100..=300), vec![40; 25]);
+    }
+
+    fn first_acid(sequence: &FastqSequence) -> String {
+        format!("{:?}", sequence.acids().first())
+    }
+
+    #[test]
+    fn test_group_blocks_by() {
+        let mut data = Vec::new();
+        let params = IdnCompressorParams::builder()
+            .fast(true)
+            .max_block_reads(100)
+            .group_blocks_by(first_acid)
+            .build()
+            .unwrap();
+        let mut compressor = IdnCompressor::with_params(&mut data, params);
+        for sequence in SEQ_1K_READS.iter() {
+            compressor.add_sequence(sequence.clone()).unwrap();
+        }
+        compressor.finish().unwrap();
+
+        let info = inspect(data.as_slice()).unwrap();
+        let sequences: Vec<_> = IdnDecompressor::new(data.as_slice())
+            .into_iter()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(sequences.len(), SEQ_1K_READS.len());
+
+        let mut remaining = sequences.as_slice();
+        for block in info.blocks() {
+            let (block_sequences, rest) = remaining.split_at(block.sequence_num());
+            let key = first_acid(&block_sequences[0]);
+            assert_eq!(block.group(), Some(key.as_str()));
+            assert!(block_sequences
+                .iter()
+                .all(|sequence| first_acid(sequence) == key));
+            remaining = rest;
+        }
+
+        // The reads with the same key keep their order
+        for key in SEQ_1K_READS.iter().map(first_acid).unique() {
+            let expected: Vec<_> = SEQ_1K_READS
+                .iter()
+                .filter(|sequence| first_acid(sequence) == key)
+                .collect();
+            let actual: Vec<_> = sequences
+                .iter()
+                .filter(|sequence| first_acid(sequence) == key)
+                .collect();
+            assert_eq!(actual, expected);
+        }
+    }
+
+    #[test]
+    fn test_group_blocks_by_bounded() {
+        let mut data = Vec::new();
+        // 20 groups with the blocks of 10 reads would need to buffer more
+        // reads than the limit of 8 blocks allows
+        let params = IdnCompressorParams::builder()
+            .fast(true)
+            .max_block_total_len(760)
+            .group_blocks_by(|sequence| (sequence.identifier().str().len() % 20).to_string())
+            .build()
+            .unwrap();
+        let mut compressor = IdnCompressor::with_params(&mut data, params);
+        for (index, sequence) in SEQ_1K_READS.iter().enumerate() {
+            let sequence = sequence.clone().with_identifier("x".repeat(index % 20));
+            compressor.add_sequence(sequence).unwrap();
+        }
+        compressor.finish().unwrap();
+
+        let info = inspect(data.as_slice()).unwrap();
+        // Without the limit, each of the groups would be stored in 5 full
+        // blocks of 10 reads
+        assert!(info.blocks().len() > 100);
+        assert!(info.blocks().iter().all(|block| block.sequence_num() <= 10));
+        let sequence_num: usize = info.blocks().iter().map(|b| b.sequence_num()).sum();
+        assert_eq!(sequence_num, SEQ_1K_READS.len());
+    }
+
+    #[test]
+    fn test_block_key_too_long() {
+        let mut data = Vec::new();
+        let params = IdnCompressorParams::builder()
+            .group_blocks_by(|_| "x".repeat(70000))
+            .build()
+            .unwrap();
+        let mut compressor = IdnCompressor::with_params(&mut data, params);
+        let result = compressor.add_sequence(SEQ_1K_READS[0].clone());
+        assert!(matches!(
+            result,
+            Err(IdnCompressorError::BlockKeyTooLong(70000))
+        ));
+    }
+
+    #[test]
+    fn test_target_block_reads_invalid() {
+        let result = IdnCompressorParams::builder()
+            .target_block_reads(0..=10)
+            .build();
+        assert_eq!(
+            result.unwrap_err(),
+            IdnCompressorParamsError::InvalidTargetBlockReads(0, 10)
+        );
+
+        let (min, max) = (10, 5);
+        let result = IdnCompressorParams::builder()
+            .target_block_reads(min..=max)
+            .build();
+        assert_eq!(
+            result.unwrap_err(),
+            IdnCompressorParamsError::InvalidTargetBlockReads(10, 5)
+        );
+    }
+
+    #[test]
+    fn test_stats() {
+        let mut data = Vec::new();
+        let params = IdnCompressorParams::builder()
+            .fast(true)
+            .max_block_reads(300)
+            .build()
+            .unwrap();
+        let mut compressor = IdnCompressor::with_params(&mut data, params);
+        for sequence in SEQ_1K_READS.iter() {
+            compressor.add_sequence(sequence.clone()).unwrap();
+        }
+        let stats = compressor.finish_with_stats().unwrap();
+
+        let in_bytes: usize = SEQ_1K_READS.iter().map(|seq| seq.size().get()).sum();
+        let in_symbols: usize = SEQ_1K_READS.iter().map(|seq| seq.len()).sum();
+        assert_eq!(stats.in_bytes().get(), in_bytes);
+        assert_eq!(stats.in_symbols(), in_symbols);
+        assert_eq!(stats.out_bytes(), data.len());
+        // 4 blocks with the data and the empty block marking the end of file
+        assert_eq!(stats.blocks(), 5);
+        assert!(stats.out_header_bytes() > 0);
+        assert!(stats.acid_bpv().unwrap() < 8.0);
+        assert!(stats.q_score_bpv().unwrap() < 8.0);
+    }
 
-    use crate::_internal_test_data::SHORT_TEST_SEQUENCE;
-    use crate::idn::compressor::{IdnCompressor, IdnCompressorError, IdnCompressorParams};
+    #[test]
+    fn test_stats_empty() {
+        let mut data = Vec::new();
+        let compressor = IdnCompressor::new(&mut data);
+        assert_eq!(compressor.stats().blocks(), 0);
+        let stats = compressor.finish_with_stats().unwrap();
+
+        assert_eq!(stats.blocks(), 1);
+        assert_eq!(stats.in_symbols(), 0);
+        assert_eq!(stats.acid_bpv(), None);
+        assert_eq!(stats.identifier_bpv(), None);
+    }
 
     #[test]
     fn test_sequence_too_long() {
         let options = IdnCompressorParams::builder()
             .max_block_total_len(1)
-            .build();
+            .build()
+            .unwrap();
 
         let mut data = Vec::new();
         let mut writer = IdnCompressor::with_params(&mut data, options);
@@ -762,6 +2988,149 @@ mod tests {
         assert!(matches!(error, IdnCompressorError::SequenceTooLong(4, _)));
     }
 
+    #[test]
+    fn test_long_reads() {
+        let params = IdnCompressorParams::builder()
+            .long_reads(true)
+            .build()
+            .unwrap();
+        assert_eq!(params.max_seq_len(), 32 * 1024 * 1024);
+        let options = IdnCompressorOptions::from(params);
+        assert_eq!(options.max_seq_len, MAX_PREALLOCATED_SEQ_LEN);
+        assert_eq!(options.intra_read_window, Some(LONG_READ_INTRA_READ_WINDOW));
+
+        let params = IdnCompressorParams::builder()
+            .long_reads(true)
+            .max_block_total_len(100)
+            .build()
+            .unwrap();
+        assert_eq!(params.max_seq_len(), 50);
+
+        let params = IdnCompressorParams::builder()
+            .max_block_total_len(100)
+            .intra_read_window(10)
+            .long_reads(true)
+            .long_reads(false)
+            .build()
+            .unwrap();
+        assert_eq!(params.max_seq_len(), 50);
+        assert_eq!(params.intra_read_window, Some(10));
+
+        let params = IdnCompressorParams::builder()
+            .long_reads(true)
+            .long_reads(false)
+            .build()
+            .unwrap();
+        assert_eq!(params.max_seq_len(), DEFAULT_MAX_BLOCK_TOTAL_LEN / 2);
+        assert_eq!(params.intra_read_window, None);
+
+        let result = IdnCompressorParams::builder().intra_read_window(0).build();
+        assert_eq!(
+            result.unwrap_err(),
+            IdnCompressorParamsError::ZeroIntraReadWindow
+        );
+    }
+
+    #[test]
+    fn test_identifier_compression_params() {
+        let qualities = || {
+            (CompressionQuality::MIN.get()..=CompressionQuality::MAX.get())
+                .map(CompressionQuality::new)
+        };
+        assert!(qualities()
+            .zip(qualities().skip(1))
+            .all(|(a, b)| a.brotli_quality() <= b.brotli_quality()
+                && a.brotli_window() <= b.brotli_window()
+                && a.deflate_level() <= b.deflate_level()));
+
+        assert!(IdnCompressorParams::builder()
+            .brotli_params(5, 16)
+            .deflate_level(0)
+            .build()
+            .is_ok());
+        assert_eq!(
+            IdnCompressorParams::builder()
+                .brotli_params(12, 20)
+                .build()
+                .unwrap_err(),
+            IdnCompressorParamsError::InvalidBrotliQuality(12)
+        );
+        assert_eq!(
+            IdnCompressorParams::builder()
+                .brotli_params(11, 25)
+                .build()
+                .unwrap_err(),
+            IdnCompressorParamsError::InvalidBrotliWindow(25)
+        );
+        assert_eq!(
+            IdnCompressorParams::builder()
+                .deflate_level(10)
+                .build()
+                .unwrap_err(),
+            IdnCompressorParamsError::InvalidDeflateLevel(10)
+        );
+        assert_eq!(
+            IdnCompressorParams::builder()
+                .deterministic(true)
+                .time_budget(Duration::from_secs(1))
+                .build()
+                .unwrap_err(),
+            IdnCompressorParamsError::DeterministicTimeBudget
+        );
+        for ratio in [0.0, -1.0, f32::NAN, f32::INFINITY] {
+            assert_eq!(
+                IdnCompressorParams::builder()
+                    .raw_fallback_ratio(ratio)
+                    .build()
+                    .unwrap_err(),
+                IdnCompressorParamsError::InvalidRawFallbackRatio
+            );
+        }
+    }
+
+    #[test]
+    fn test_transform_params() {
+        let transform = Arc::new(NamedTransform("a".repeat(255)));
+        let builder = (0..255).fold(IdnCompressorParams::builder(), |builder, _| {
+            builder.transform(transform.clone())
+        });
+        assert!(builder.clone().build().is_ok());
+        assert_eq!(
+            builder.transform(transform).build().unwrap_err(),
+            IdnCompressorParamsError::TooManyTransforms(256)
+        );
+
+        assert_eq!(
+            IdnCompressorParams::builder()
+                .transform(Arc::new(NamedTransform("a".repeat(256))))
+                .build()
+                .unwrap_err(),
+            IdnCompressorParamsError::TransformIdentifierTooLong(256)
+        );
+    }
+
+    #[test]
+    fn test_identifier_compression_round_trip() {
+        let params = IdnCompressorParams::builder()
+            .quality(CompressionQuality::MAX)
+            .brotli_params(1, 10)
+            .deflate_level(1)
+            .build()
+            .unwrap();
+        let mut data = Vec::new();
+        let mut compressor = IdnCompressor::with_params(&mut data, params);
+        for sequence in SEQ_1K_READS.iter() {
+            compressor.add_sequence(sequence.clone()).unwrap();
+        }
+        compressor.finish().unwrap();
+
+        let sequences: Vec<_> = IdnDecompressor::new(data.as_slice())
+            .into_iter()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(sequences, SEQ_1K_READS.as_slice());
+    }
+
     #[test]
     fn test_error_display() {
         assert_eq!(
@@ -783,6 +3152,63 @@ mod tests {
             format!("{}", IdnCompressorError::sequence_too_long(5, 2)),
             "Sequence too long (sequence length: 5, limit: 2)"
         );
+        assert_eq!(
+            IdnCompressorError::duplicate_member("sample.fastq").to_string(),
+            "Duplicate container member name: `sample.fastq`"
+        );
+        assert_eq!(
+            IdnCompressorError::member_name_too_long(70000).to_string(),
+            "Container member name too long (70000 bytes, limit: 65535)"
+        );
+        assert_eq!(
+            IdnCompressorError::block_key_too_long(70000).to_string(),
+            "Block key too long (70000 bytes, limit: 65535)"
+        );
+        assert_eq!(
+            IdnCompressorError::quality_score_not_in_alphabet(FastqQualityScore::new(13))
+                .to_string(),
+            "Quality score not in the quality alphabet: 13"
+        );
+        assert_eq!(
+            IdnCompressorError::invalid_model_selection(3, 2).to_string(),
+            "Model selection strategy returned invalid index 3 (there are 2 candidates)"
+        );
+        assert_eq!(
+            IdnCompressorError::slice_too_long(5_000_000_000).to_string(),
+            "Block slice data too long (5000000000 bytes, limit: 4294967295)"
+        );
+        assert_eq!(
+            IdnCompressorParamsError::InvalidBrotliQuality(12).to_string(),
+            "Invalid Brotli quality: 12 (must be at most 11)"
+        );
+        assert_eq!(
+            IdnCompressorParamsError::InvalidBrotliWindow(9).to_string(),
+            "Invalid Brotli window: 9 (must be between 10 and 24)"
+        );
+        assert_eq!(
+            IdnCompressorParamsError::InvalidDeflateLevel(10).to_string(),
+            "Invalid Deflate level: 10 (must be at most 9)"
+        );
+        assert_eq!(
+            IdnCompressorParamsError::ZeroIntraReadWindow.to_string(),
+            "The intra-read model window must be greater than 0"
+        );
+        assert_eq!(
+            IdnCompressorParamsError::DeterministicTimeBudget.to_string(),
+            "The time budget cannot be used in the deterministic mode"
+        );
+        assert_eq!(
+            IdnCompressorParamsError::InvalidRawFallbackRatio.to_string(),
+            "The raw fallback ratio must be a positive finite number"
+        );
+        assert_eq!(
+            IdnCompressorParamsError::TooManyTransforms(256).to_string(),
+            "Too many transforms: 256 (must be at most 255)"
+        );
+        assert_eq!(
+            IdnCompressorParamsError::TransformIdentifierTooLong(300).to_string(),
+            "Transform identifier too long: 300 bytes (must be at most 255)"
+        );
     }
 
     #[test]
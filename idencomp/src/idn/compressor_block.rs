@@ -1,34 +1,191 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
 use std::io::Write;
 use std::mem;
 use std::sync::Arc;
 
+use byteorder::{BigEndian, WriteBytesExt};
+#[cfg(feature = "deflate")]
 use flate2::write::DeflateEncoder;
 use itertools::Itertools;
 use log::debug;
 
-use crate::fastq::FastqSequence;
+use crate::fastq::{FastqQualityScore, FastqSequence};
+use crate::idn::adaptive_effort;
+use crate::idn::common::ObjectPool;
 use crate::idn::compressor::{
-    CompressionQuality, CompressionStats, IdnCompressResult, IdnCompressorOptions,
-    IdnCompressorOutState,
+    CompressionQuality, CompressionStats, IdnCompressResult, IdnCompressorError,
+    IdnCompressorOptions, IdnCompressorOutState, SequenceBlock,
 };
+use crate::idn::context_usage::BlockContextUsage;
 use crate::idn::data::IdnIdentifierCompression;
+use crate::idn::encode_info::{SequenceEncodeInfo, SequenceEncoding};
+use crate::idn::explain::SequenceExplanation;
 use crate::idn::model_chooser::ModelChooser;
+use crate::idn::no_seek::NoSeek;
+use crate::idn::time_budget::CompressionEffort;
+use crate::idn::warning::{self, Warning};
 use crate::idn::writer_block::BlockWriter;
-use crate::progress::ByteNum;
-use crate::sequence_compressor::{AcidRansEncModel, QScoreRansEncModel, SequenceCompressor};
+use crate::idn::writer_idn::IdnWriter;
+use crate::idn::{packing, raw};
+use crate::parallel::{IntoParallelIterator, ParallelIterator};
+use crate::progress::{BatchedProgress, ByteNum};
+use crate::quality_alphabet::QualityAlphabet;
+use crate::sequence::Acid;
+use crate::sequence_compressor::{
+    AcidRansEncModel, ModelSegment, QScoreRansEncModel, SequenceCompressor,
+};
+
+/// The coders used to compress a block. These contain buffers sized to the
+/// maximum sequence length, so they are reused across the blocks instead of
+/// being created for each of them. The sequence compressors are kept in a
+/// separate pool, as a block may use several of them at once.
+#[derive(Debug)]
+pub(super) struct BlockCoders {
+    model_chooser: ModelChooser,
+}
+
+impl BlockCoders {
+    #[must_use]
+    pub fn new(max_seq_len: usize, quality_alphabet: &QualityAlphabet) -> Self {
+        Self {
+            model_chooser: ModelChooser::new(max_seq_len, quality_alphabet.clone()),
+        }
+    }
+}
+
+/// A block that has been compressed and is waiting for its turn to be written
+/// to the output.
+#[derive(Debug)]
+pub(super) struct CompressedBlock {
+    options: Arc<IdnCompressorOptions>,
+    block_index: u32,
+    member: Option<String>,
+    sequence_num: usize,
+    is_last: bool,
+    stats: Arc<CompressionStats>,
+
+    block_writer: BlockWriter,
+    context_usage: Option<BlockContextUsage>,
+    explanations: Option<Vec<SequenceExplanation>>,
+    warnings: Vec<Warning>,
+
+    // Stats
+    in_bytes: ByteNum,
+    in_symbols: usize,
+    in_identifier_bytes: usize,
+    out_identifier_bytes: usize,
+    out_acid_bytes: usize,
+    out_q_score_bytes: usize,
+    acid_model_switches: usize,
+    q_score_model_switches: usize,
+}
+
+impl CompressedBlock {
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "write_block", skip_all, fields(block = self.block_index)))]
+    pub fn write_to<W: Write>(self, writer: &mut IdnWriter<NoSeek<W>>) -> IdnCompressResult<()> {
+        debug!("Writing block {}", self.block_index);
+        if !self.is_last {
+            writer.record_block(self.member.as_deref(), self.sequence_num)?;
+        }
+        self.block_writer.write_to(writer.writer_for_block())?;
+        if let (Some(context_usage), Some(block_usage)) =
+            (&self.options.context_usage, &self.context_usage)
+        {
+            context_usage.merge(block_usage);
+        }
+        if let Some(explainer) = &self.options.explainer {
+            for explanation in self.explanations.into_iter().flatten() {
+                explainer.explain(explanation);
+            }
+        }
+        for warning in self.warnings {
+            if matches!(warning, Warning::IdentifierFallback { .. })
+                && self.stats.inc_identifier_fallbacks() > 0
+            {
+                continue;
+            }
+            warning::emit(self.options.warning_sink.as_deref(), warning);
+        }
+        if self.is_last {
+            let source_digest = self.options.source_digest();
+            writer.write_trailing_metadata(source_digest.as_ref())?;
+        }
+
+        let w = writer.writer_for_block();
+        w.flush()?;
+
+        self.stats.add_in_bytes(self.in_bytes);
+        self.stats.add_in_identifier_bytes(self.in_identifier_bytes);
+        self.stats.add_in_symbols(self.in_symbols);
+        self.stats.set_out_bytes(w.position() as usize);
+        self.stats
+            .add_out_identifier_bytes(self.out_identifier_bytes);
+        self.stats.add_out_acid_bytes(self.out_acid_bytes);
+        self.stats.add_out_q_score_bytes(self.out_q_score_bytes);
+        self.stats.inc_blocks();
+        self.stats.add_acid_model_switches(self.acid_model_switches);
+        self.stats
+            .add_q_score_model_switches(self.q_score_model_switches);
+        self.options.progress_notifier.advance_stage(1);
+
+        Ok(())
+    }
+}
+
+/// How a sequence is going to be written to the block, decided before the
+/// sequences are encoded.
+#[derive(Debug)]
+enum PlannedSequence<'a> {
+    /// The sequence has the same acids as the one given number of sequences
+    /// before. If the quality scores differ, they are encoded with given
+    /// model.
+    Duplicate {
+        distance: usize,
+        q_score_model: Option<&'a QScoreRansEncModel>,
+    },
+    /// The acids of the sequence are packed, as all the quality scores are
+    /// the same.
+    Packed,
+    /// The sequence has no symbols.
+    Empty,
+    /// The sequence is encoded with given models.
+    Encoded {
+        acid_model: &'a AcidRansEncModel,
+        q_score_models: Vec<ModelSegment<'a, QScoreRansEncModel>>,
+        estimated_bytes: Option<usize>,
+    },
+}
 
 pub(super) struct IdnBlockCompressor<W> {
     options: Arc<IdnCompressorOptions>,
     out_state: Arc<IdnCompressorOutState<W>>,
     block_index: u32,
+    member: Option<String>,
+    group: Option<String>,
     sequences: Vec<FastqSequence>,
+    sequence_num: usize,
+    is_last: bool,
     stats: Arc<CompressionStats>,
 
     block_writer: BlockWriter,
-    compressor: SequenceCompressor,
+    coders: BlockCoders,
+    /// The models chosen for the last sequences, which the model chooser
+    /// compares the other models to.
     current_acid_model: Option<u8>,
     current_q_score_model: Option<u8>,
-    model_chooser: ModelChooser,
+    /// The models switched to by the last slices written to the block.
+    written_acid_model: Option<u8>,
+    written_q_score_model: Option<u8>,
+    context_usage: Option<BlockContextUsage>,
+    explanations: Option<Vec<SequenceExplanation>>,
+    warnings: Vec<Warning>,
+    /// The number of sequences compressed to more than one byte per symbol.
+    poor_fit_sequences: usize,
+    /// The details of how the sequences have been encoded, passed to the
+    /// `on_sequence` callback once the whole block is encoded, as it might
+    /// still fall back to being stored raw.
+    reports: Vec<SequenceEncodeInfo>,
 
     // Stats
     in_bytes: ByteNum,
@@ -46,21 +203,44 @@ impl<W: Write> IdnBlockCompressor<W> {
         options: Arc<IdnCompressorOptions>,
         out_state: Arc<IdnCompressorOutState<W>>,
         block_index: u32,
-        sequences: Vec<FastqSequence>,
+        block: SequenceBlock,
         stats: Arc<CompressionStats>,
     ) -> Self {
+        let mut coders = out_state
+            .coder_pool()
+            .take_or_else(|| BlockCoders::new(options.max_seq_len, &options.quality_alphabet));
+        coders.model_chooser.start_block();
+
+        let is_last = block.is_eof();
+        let sequence_num = block.sequences.len();
+        let context_usage = options
+            .context_usage
+            .as_ref()
+            .map(|_| BlockContextUsage::new());
+        let explanations = options.explainer.as_ref().map(|_| Vec::new());
+
         Self {
             options,
             out_state,
             block_index,
-            sequences,
+            member: block.member,
+            group: block.group,
+            sequences: block.sequences,
+            sequence_num,
+            is_last,
             stats,
 
             block_writer: BlockWriter::new(),
-            compressor: SequenceCompressor::new(),
+            coders,
             current_acid_model: None,
             current_q_score_model: None,
-            model_chooser: ModelChooser::new(),
+            written_acid_model: None,
+            written_q_score_model: None,
+            context_usage,
+            explanations,
+            warnings: Vec::new(),
+            poor_fit_sequences: 0,
+            reports: Vec::new(),
 
             in_bytes: ByteNum::ZERO,
             in_symbols: 0,
@@ -73,127 +253,619 @@ impl<W: Write> IdnBlockCompressor<W> {
         }
     }
 
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "compress_block", skip_all, fields(block = self.block_index)))]
     pub fn process(mut self) -> IdnCompressResult<()> {
-        self.prepare_to_write()?;
-        self.write()?;
+        if let Err(error) = self.prepare_to_write() {
+            // This block is never going to be written, so the writer cannot
+            // wait for it
+            self.out_state.finish_writing();
+            return Err(error);
+        }
 
-        Ok(())
+        let out_state = self.out_state.clone();
+        let block_index = self.block_index;
+        out_state.add_compressed_block(block_index, self.finish())
     }
 
     fn prepare_to_write(&mut self) -> IdnCompressResult<()> {
+        if let Some(member) = &self.member {
+            self.block_writer.write_member(member)?;
+        }
         if self.sequences.is_empty() {
             return Ok(());
         }
+        if let Some(group) = &self.group {
+            self.block_writer.write_group(group)?;
+        }
 
         let sequences = mem::take(&mut self.sequences);
         let options = self.options.clone();
+        let effort = self.effort(&sequences);
+        if effort != CompressionEffort::Full {
+            debug!(
+                "Compressing block {} with {:?} effort",
+                self.block_index, effort
+            );
+        }
 
         if options.include_identifiers {
-            self.write_identifiers(&sequences, &options)?;
+            self.write_identifiers(&sequences, effort, &options)?;
+        }
+        if sequences
+            .iter()
+            .any(|sequence| !sequence.aux_data().is_empty())
+        {
+            self.write_aux_data(&sequences, effort, &options)?;
         }
+        if options.include_identifiers
+            && sequences
+                .iter()
+                .any(|sequence| !sequence.separator_text().is_empty())
+        {
+            self.write_separator_texts(&sequences, effort, &options)?;
+        }
+        let sequences_start = self.block_writer.data_len();
 
-        if options.fast {
+        let packed_q_score = if options.fast {
+            packing::packable_q_score(&sequences)
+        } else {
+            None
+        };
+        if let Some(q_score) = packed_q_score {
+            self.block_writer.write_packed(q_score)?;
+        } else if options.fast {
             assert_eq!(self.options.model_provider.len(), 2);
             self.block_writer.write_switch_model(0)?;
             self.block_writer.write_switch_model(1)?;
         }
+        let n_q_score = if options.skip_n_q_scores && packed_q_score.is_none() {
+            Self::shared_n_q_score(&sequences)
+        } else {
+            None
+        };
+        if let Some(q_score) = n_q_score {
+            self.block_writer.write_n_q_score(q_score)?;
+        }
         let default_acid_model = options.model_provider.acid_enc_models().next().unwrap();
         let default_q_score_model = options.model_provider.q_score_enc_models().next().unwrap();
 
-        for sequence in sequences.iter() {
-            let (acid_model, q_score_model) = if options.fast {
-                (default_acid_model, default_q_score_model)
+        let model_chooser_interval = effort.model_chooser_interval();
+        let mut current_models = None;
+        let mut encoded_num = 0;
+        let mut last_occurrences = HashMap::new();
+        let mut tile_group = None;
+        let mut plan = Vec::with_capacity(sequences.len());
+        for (index, sequence) in sequences.iter().enumerate() {
+            if options.deduplicate {
+                if let Some(distance) =
+                    Self::duplicate_distance(&mut last_occurrences, sequence, index)
+                {
+                    let original = &sequences[index - distance];
+                    let q_score_model = (original.quality_scores() != sequence.quality_scores())
+                        .then(|| match current_models {
+                            Some((_, q_score_model)) if !options.fast => q_score_model,
+                            _ => default_q_score_model,
+                        });
+                    plan.push(PlannedSequence::Duplicate {
+                        distance,
+                        q_score_model,
+                    });
+                    continue;
+                }
+            }
+
+            if packed_q_score.is_some() {
+                plan.push(PlannedSequence::Packed);
+                continue;
+            }
+            if sequence.is_empty() {
+                // There is nothing to choose the models for, and the current
+                // models stay active for the next sequences
+                plan.push(PlannedSequence::Empty);
+                continue;
+            }
+
+            let (acid_model, q_score_models, estimated_bytes) = if options.fast {
+                (default_acid_model, vec![(0, default_q_score_model)], None)
             } else {
-                let acid_model = self.switch_to_best_acid_model_for(sequence, &options)?;
-                let q_score_model = self.switch_to_best_q_score_model_for(sequence, &options)?;
-                (acid_model, q_score_model)
+                let (models, estimated_bytes) = match current_models {
+                    Some((acid_model, q_score_model))
+                        if encoded_num % model_chooser_interval != 0 =>
+                    {
+                        ((acid_model, vec![(0, q_score_model)]), None)
+                    }
+                    _ => {
+                        let (acid_bytes, acid_model) =
+                            self.choose_best_acid_model_for(sequence, &options)?;
+                        let (q_score_bytes, q_score_models) = if options.group_by_tile {
+                            let q_score_model = self.choose_best_q_score_model_for_tile(
+                                &sequences,
+                                index,
+                                &mut tile_group,
+                                &options,
+                            )?;
+                            (None, vec![(0, q_score_model)])
+                        } else {
+                            match options.intra_read_window {
+                                Some(window) if sequence.len() > window => {
+                                    let (bytes, q_score_models) = self
+                                        .choose_best_q_score_models_for(
+                                            sequence, window, &options,
+                                        )?;
+                                    (bytes, q_score_models)
+                                }
+                                _ => {
+                                    let (bytes, q_score_model) =
+                                        self.choose_best_q_score_model_for(sequence, &options)?;
+                                    (bytes, vec![(0, q_score_model)])
+                                }
+                            }
+                        };
+                        let estimated_bytes = acid_bytes
+                            .zip(q_score_bytes)
+                            .map(|(acid_bytes, q_score_bytes)| acid_bytes + q_score_bytes);
+                        ((acid_model, q_score_models), estimated_bytes)
+                    }
+                };
+                // The model the sequence ends with stays active for the next
+                // sequences
+                let (_, last_q_score_model) = *models.1.last().unwrap();
+                current_models = Some((models.0, last_q_score_model));
+                encoded_num += 1;
+                (models.0, models.1, estimated_bytes)
             };
 
+            plan.push(PlannedSequence::Encoded {
+                acid_model,
+                q_score_models,
+                estimated_bytes,
+            });
+        }
+
+        let encoded = self.encode_sequences(&sequences, &plan, n_q_score, &options);
+        // Unless they have been encoded in parallel, the sequences are encoded
+        // right before being written, straight from the compressor's buffer
+        let mut compressor = match encoded {
+            Some(_) => None,
+            None => Some(Self::take_sequence_compressor(
+                self.out_state.compressor_pool(),
+                n_q_score,
+                &options,
+            )),
+        };
+        let mut progress = BatchedProgress::new(
+            options.progress_notifier.clone(),
+            options.progress_granularity,
+        );
+        for (index, (sequence, planned)) in sequences.iter().zip(&plan).enumerate() {
+            let data = match (&encoded, &mut compressor) {
+                (Some(encoded), _) => encoded[index].as_deref(),
+                (None, Some(compressor)) => Self::encode_planned(compressor, sequence, planned),
+                (None, None) => unreachable!("Sequence compressor has not been taken"),
+            };
+            match planned {
+                PlannedSequence::Duplicate {
+                    distance,
+                    q_score_model,
+                } => {
+                    let data = data.unwrap_or_default();
+                    if let Some(q_score_model) = q_score_model {
+                        if !options.fast {
+                            self.write_q_score_model_switch(q_score_model, &options)?;
+                        }
+                    }
+                    self.write_duplicate(sequence, index, *distance, data)?
+                }
+                PlannedSequence::Packed => self.write_packed_sequence(sequence, index)?,
+                PlannedSequence::Empty => self.write_empty_sequence(sequence, index)?,
+                PlannedSequence::Encoded {
+                    acid_model,
+                    q_score_models,
+                    estimated_bytes,
+                } => {
+                    if !options.fast {
+                        self.write_model_switches(acid_model, q_score_models, &options)?;
+                    }
+                    let data = data.expect("Sequence has not been encoded");
+                    self.write_sequence(
+                        sequence,
+                        index,
+                        data,
+                        acid_model,
+                        q_score_models,
+                        *estimated_bytes,
+                    )?;
+                }
+            }
+
             self.in_bytes += sequence.size();
             self.in_symbols += sequence.len();
             self.in_identifier_bytes += sequence.identifier().len();
+            progress.processed_bytes(sequence.size());
+        }
+        if let Some(compressor) = compressor {
+            self.out_state.compressor_pool().put_back(compressor);
+        }
 
-            self.write_sequence(sequence, acid_model, q_score_model, &options)?;
+        if let Some(ratio) = options.raw_fallback_ratio {
+            self.fall_back_to_raw_if_needed(&sequences, sequences_start, ratio)?;
         }
+        self.emit_reports(&sequences, &options);
 
         Ok(())
     }
 
-    fn write(self) -> IdnCompressResult<()> {
-        let _guard = self.out_state.block_lock().lock(self.block_index);
-        let mut writer_guard = self.out_state.writer();
-        let mut w = writer_guard.writer_for_block();
+    /// Rewrites the sequences of the block raw if they have been encoded into
+    /// more than `ratio` times their raw size. `sequences_start` is the
+    /// position in the block where the first sequence has been written.
+    fn fall_back_to_raw_if_needed(
+        &mut self,
+        sequences: &[FastqSequence],
+        sequences_start: usize,
+        ratio: f32,
+    ) -> IdnCompressResult<()> {
+        let symbols: usize = sequences.iter().map(|sequence| sequence.len()).sum();
+        let raw_bytes = raw::raw_len(symbols);
+        let encoded_bytes = self.block_writer.data_len() - sequences_start;
+        if encoded_bytes as f64 <= ratio as f64 * raw_bytes as f64 {
+            return Ok(());
+        }
 
-        self.block_writer.write_to(&mut w)?;
-        w.flush()?;
+        debug!(
+            "Sequences of block {} have been encoded into {} bytes (raw size: {} bytes), \
+            storing them raw",
+            self.block_index, encoded_bytes, raw_bytes
+        );
+        self.block_writer.truncate(sequences_start);
+        self.block_writer.write_raw()?;
+        self.reports.clear();
+        for (index, sequence) in sequences.iter().enumerate() {
+            let data = raw::store_raw(sequence);
+            self.block_writer.write_sequence(sequence, &data)?;
+            self.report_sequence(index, SequenceEncoding::Raw, data.len());
+        }
 
-        self.stats.add_in_bytes(self.in_bytes);
-        self.stats.add_in_identifier_bytes(self.in_identifier_bytes);
-        self.stats.add_in_symbols(self.in_symbols);
-        self.stats.set_out_bytes(w.position() as usize);
-        self.stats
-            .add_out_identifier_bytes(self.out_identifier_bytes);
-        self.stats.add_out_acid_bytes(self.out_acid_bytes);
-        self.stats.add_out_q_score_bytes(self.out_q_score_bytes);
-        self.stats.inc_blocks();
-        self.stats.add_acid_model_switches(self.acid_model_switches);
-        self.stats
-            .add_q_score_model_switches(self.q_score_model_switches);
+        if let Some(context_usage) = &mut self.context_usage {
+            *context_usage = BlockContextUsage::new();
+        }
+        self.poor_fit_sequences = 0;
+        self.out_acid_bytes = symbols;
+        self.out_q_score_bytes = symbols;
+        self.acid_model_switches = 0;
+        self.q_score_model_switches = 0;
+        self.warnings.push(Warning::RawFallback {
+            block: self.block_index,
+        });
 
         Ok(())
     }
 
+    /// Passes the details of how the sequences have been encoded to the
+    /// `on_sequence` callback, if one is set.
+    fn emit_reports(&mut self, sequences: &[FastqSequence], options: &IdnCompressorOptions) {
+        if let Some(on_sequence) = &options.on_sequence {
+            for info in self.reports.drain(..) {
+                on_sequence.call(&sequences[info.index], &info);
+            }
+        }
+    }
+
+    /// Encodes the sequences planned to be encoded with the models in
+    /// parallel chunks, returning the data of each of them at its index. As
+    /// each sequence is a separate rANS stream, the chunks are encoded by the
+    /// threads of the compressor's encoding pool. Returns `None` if the
+    /// compressor has no threads to use or the block is too small to be
+    /// split, in which case the sequences should be encoded one by one as
+    /// they are written.
+    fn encode_sequences(
+        &self,
+        sequences: &[FastqSequence],
+        plan: &[PlannedSequence<'_>],
+        n_q_score: Option<FastqQualityScore>,
+        options: &IdnCompressorOptions,
+    ) -> Option<Vec<Option<Vec<u8>>>> {
+        let encode_pool = match self.out_state.encode_pool() {
+            Some(encode_pool) if sequences.len() > Self::ENCODE_CHUNK_LEN => encode_pool,
+            _ => return None,
+        };
+
+        let compressor_pool = self.out_state.compressor_pool();
+        let chunks: Vec<_> = sequences
+            .chunks(Self::ENCODE_CHUNK_LEN)
+            .zip(plan.chunks(Self::ENCODE_CHUNK_LEN))
+            .collect();
+        let encoded: Vec<Vec<_>> = encode_pool.install(|| {
+            chunks
+                .into_par_iter()
+                .map(|(sequences, plan)| {
+                    let mut compressor =
+                        Self::take_sequence_compressor(compressor_pool, n_q_score, options);
+                    let encoded = sequences
+                        .iter()
+                        .zip(plan)
+                        .map(|(sequence, planned)| {
+                            Self::encode_planned(&mut compressor, sequence, planned)
+                                .map(<[u8]>::to_vec)
+                        })
+                        .collect();
+                    compressor_pool.put_back(compressor);
+
+                    encoded
+                })
+                .collect()
+        });
+
+        Some(encoded.into_iter().flatten().collect())
+    }
+
+    /// Takes a sequence compressor from the pool, set up to skip given
+    /// quality score of the `N` acids.
+    fn take_sequence_compressor(
+        compressor_pool: &ObjectPool<SequenceCompressor>,
+        n_q_score: Option<FastqQualityScore>,
+        options: &IdnCompressorOptions,
+    ) -> SequenceCompressor {
+        let mut compressor = compressor_pool.take_or_else(|| {
+            SequenceCompressor::new(options.max_seq_len, options.quality_alphabet.clone())
+        });
+        // The compressors are reused across the blocks, so this has to be set
+        // even if there is no quality score to skip
+        compressor.set_n_q_score(n_q_score);
+
+        compressor
+    }
+
+    /// The number of sequences encoded by a single parallel job.
+    const ENCODE_CHUNK_LEN: usize = 64;
+
+    fn encode_planned<'a>(
+        compressor: &'a mut SequenceCompressor,
+        sequence: &FastqSequence,
+        planned: &PlannedSequence<'_>,
+    ) -> Option<&'a [u8]> {
+        match planned {
+            PlannedSequence::Encoded {
+                acid_model,
+                q_score_models,
+                ..
+            } => Some(compressor.compress_segmented(sequence, &[(0, *acid_model)], q_score_models)),
+            PlannedSequence::Duplicate {
+                q_score_model: Some(q_score_model),
+                ..
+            } => Some(compressor.compress_q_scores(sequence, q_score_model)),
+            _ => None,
+        }
+    }
+
+    /// Returns the coders to the pool, so that they can be used by other
+    /// blocks while this one is waiting for its turn to be written.
+    fn finish(mut self) -> CompressedBlock {
+        self.out_state.coder_pool().put_back(self.coders);
+        if self.poor_fit_sequences > 0 {
+            self.warnings.push(Warning::ModelPoorFit {
+                block: self.block_index,
+                sequences: self.poor_fit_sequences,
+            });
+        }
+
+        CompressedBlock {
+            options: self.options,
+            block_index: self.block_index,
+            member: self.member,
+            sequence_num: self.sequence_num,
+            is_last: self.is_last,
+            stats: self.stats,
+
+            block_writer: self.block_writer,
+            context_usage: self.context_usage,
+            explanations: self.explanations,
+            warnings: self.warnings,
+
+            in_bytes: self.in_bytes,
+            in_symbols: self.in_symbols,
+            in_identifier_bytes: self.in_identifier_bytes,
+            out_identifier_bytes: self.out_identifier_bytes,
+            out_acid_bytes: self.out_acid_bytes,
+            out_q_score_bytes: self.out_q_score_bytes,
+            acid_model_switches: self.acid_model_switches,
+            q_score_model_switches: self.q_score_model_switches,
+        }
+    }
+
+    /// Returns the effort to compress this block with, which is lowered when
+    /// the time budget is at risk of being exceeded or, with the adaptive
+    /// effort, when the sequences are simple enough.
+    fn effort(&self, sequences: &[FastqSequence]) -> CompressionEffort {
+        let budget_effort = match &self.options.time_budget {
+            Some(time_budget) => time_budget.effort(self.stats.in_bytes()),
+            None => CompressionEffort::Full,
+        };
+        let sampled_effort = if self.options.adaptive_effort {
+            adaptive_effort::sampled_effort(sequences)
+        } else {
+            CompressionEffort::Full
+        };
+
+        budget_effort.min(sampled_effort)
+    }
+
     const BROTLI_THRESHOLD: CompressionQuality = CompressionQuality::new(8);
+    /// The slice data shorter than this is stored uncompressed, as setting up
+    /// Brotli or Deflate would take longer than compressing it saves (which
+    /// matters when compressing many small files).
+    const MIN_COMPRESSED_SLICE_LEN: usize = 1024;
     fn write_identifiers(
         &mut self,
         sequences: &[FastqSequence],
+        effort: CompressionEffort,
         options: &IdnCompressorOptions,
     ) -> IdnCompressResult<()> {
-        if options.quality >= Self::BROTLI_THRESHOLD {
-            let data = Self::compress_identifiers_brotli(sequences)?;
-            self.out_identifier_bytes += data.len();
-            self.block_writer
-                .write_identifiers(IdnIdentifierCompression::Brotli, &data)
-        } else {
-            let data = Self::compress_identifiers_deflate(sequences)?;
-            self.out_identifier_bytes += data.len();
-            self.block_writer
-                .write_identifiers(IdnIdentifierCompression::Deflate, &data)
+        let identifiers = Self::identifiers_as_lines(sequences);
+        let (mut compression, mut data) =
+            Self::compress_slice_data(identifiers.as_bytes(), effort, options)?;
+        if !matches!(compression, IdnIdentifierCompression::Uncompressed)
+            && data.len() >= identifiers.len()
+        {
+            debug!(
+                "Identifiers did not compress ({} bytes into {} bytes), storing them uncompressed",
+                identifiers.len(),
+                data.len()
+            );
+            compression = IdnIdentifierCompression::Uncompressed;
+            data = identifiers.into_bytes();
+            self.warnings.push(Warning::IdentifierFallback {
+                block: self.block_index,
+            });
         }
+
+        self.out_identifier_bytes += data.len();
+        self.block_writer.write_identifiers(compression, &data)
     }
 
-    fn compress_identifiers_brotli(sequences: &[FastqSequence]) -> IdnCompressResult<Vec<u8>> {
-        let identifiers = Self::identifiers_as_lines(sequences);
+    /// Writes the auxiliary data of given sequences, each one prefixed with its
+    /// length, compressed the same way as the identifiers.
+    fn write_aux_data(
+        &mut self,
+        sequences: &[FastqSequence],
+        effort: CompressionEffort,
+        options: &IdnCompressorOptions,
+    ) -> IdnCompressResult<()> {
+        let items = sequences.iter().map(|sequence| sequence.aux_data());
+        let (compression, data) = Self::compress_length_prefixed(items, effort, options)?;
+
+        self.block_writer.write_aux_data(compression, &data)
+    }
+
+    /// Writes the separator texts of given sequences, encoded the same way as
+    /// the auxiliary data.
+    fn write_separator_texts(
+        &mut self,
+        sequences: &[FastqSequence],
+        effort: CompressionEffort,
+        options: &IdnCompressorOptions,
+    ) -> IdnCompressResult<()> {
+        let items = sequences.iter().map(|sequence| sequence.separator_text());
+        let (compression, data) = Self::compress_length_prefixed(items, effort, options)?;
+
+        self.block_writer.write_separator_texts(compression, &data)
+    }
 
-        let mut data = Vec::new();
+    /// Concatenates given items, each one prefixed with its length as a
+    /// big-endian `u32`, and compresses the result (unless the compression
+    /// would not make it any smaller).
+    ///
+    /// Both the lengths of the items and the length of the resulting slice
+    /// data are stored as `u32`, so an error is returned if any of them does
+    /// not fit.
+    fn compress_length_prefixed<'a>(
+        items: impl Iterator<Item = &'a [u8]>,
+        effort: CompressionEffort,
+        options: &IdnCompressorOptions,
+    ) -> IdnCompressResult<(IdnIdentifierCompression, Vec<u8>)> {
+        let mut raw_data = Vec::new();
+        for item in items {
+            let item_len = u32::try_from(item.len())
+                .map_err(|_| IdnCompressorError::slice_too_long(item.len()))?;
+            raw_data.write_u32::<BigEndian>(item_len)?;
+            raw_data.extend_from_slice(item);
+        }
+
+        let (mut compression, mut data) = Self::compress_slice_data(&raw_data, effort, options)?;
+        if !matches!(compression, IdnIdentifierCompression::Uncompressed)
+            && data.len() >= raw_data.len()
         {
-            let mut br_writer = brotli::enc::writer::CompressorWriter::new(&mut data, 4096, 11, 20);
-            br_writer.write_all(identifiers.as_bytes())?;
+            compression = IdnIdentifierCompression::Uncompressed;
+            data = raw_data;
+        }
+        if u32::try_from(data.len()).is_err() {
+            return Err(IdnCompressorError::slice_too_long(data.len()));
         }
 
-        debug!(
-            "Compressed {} bytes of identifiers into {} bytes with Brotli",
-            identifiers.len(),
-            data.len()
+        Ok((compression, data))
+    }
+
+    /// Compresses the slice data (the identifiers or the auxiliary data) with
+    /// Brotli at the high qualities (unless the effort is minimal) and with
+    /// Deflate otherwise. If only one of these methods is enabled, it is always
+    /// used; if none is, or the data is shorter than
+    /// [`Self::MIN_COMPRESSED_SLICE_LEN`] (and the compression has not been
+    /// configured explicitly), the data is not compressed at all.
+    fn compress_slice_data(
+        data: &[u8],
+        effort: CompressionEffort,
+        options: &IdnCompressorOptions,
+    ) -> IdnCompressResult<(IdnIdentifierCompression, Vec<u8>)> {
+        if data.len() < Self::MIN_COMPRESSED_SLICE_LEN && !options.force_slice_compression {
+            return Ok((IdnIdentifierCompression::Uncompressed, data.to_vec()));
+        }
+
+        let use_brotli = cfg!(feature = "brotli")
+            && ((options.quality >= Self::BROTLI_THRESHOLD
+                && effort != CompressionEffort::Minimal)
+                || cfg!(not(feature = "deflate")));
+        if use_brotli {
+            #[cfg(feature = "brotli")]
+            return Ok((
+                IdnIdentifierCompression::Brotli,
+                Self::compress_slice_data_brotli(data, effort, options)?,
+            ));
+        }
+
+        #[cfg(feature = "deflate")]
+        let result = (
+            IdnIdentifierCompression::Deflate,
+            Self::compress_slice_data_deflate(data, effort, options)?,
         );
+        #[cfg(not(feature = "deflate"))]
+        let result = (IdnIdentifierCompression::Uncompressed, data.to_vec());
 
-        Ok(data)
+        Ok(result)
     }
 
-    fn compress_identifiers_deflate(sequences: &[FastqSequence]) -> IdnCompressResult<Vec<u8>> {
-        let identifiers = Self::identifiers_as_lines(sequences);
+    #[cfg(feature = "brotli")]
+    fn compress_slice_data_brotli(
+        data: &[u8],
+        effort: CompressionEffort,
+        options: &IdnCompressorOptions,
+    ) -> IdnCompressResult<Vec<u8>> {
+        let mut compressed = Vec::new();
+        {
+            let mut br_writer = brotli::enc::writer::CompressorWriter::new(
+                &mut compressed,
+                4096,
+                effort.brotli_quality(options.brotli_quality),
+                options.brotli_window,
+            );
+            br_writer.write_all(data)?;
+        }
+
+        debug!(
+            "Compressed {} bytes of slice data into {} bytes with Brotli",
+            data.len(),
+            compressed.len()
+        );
+
+        Ok(compressed)
+    }
 
-        let mut encoder = DeflateEncoder::new(Vec::new(), flate2::Compression::default());
-        encoder.write_all(identifiers.as_bytes())?;
-        let data = encoder.finish()?;
+    #[cfg(feature = "deflate")]
+    fn compress_slice_data_deflate(
+        data: &[u8],
+        effort: CompressionEffort,
+        options: &IdnCompressorOptions,
+    ) -> IdnCompressResult<Vec<u8>> {
+        let level = effort.deflate_level(options.deflate_level);
+        let mut encoder = DeflateEncoder::new(Vec::new(), flate2::Compression::new(level));
+        encoder.write_all(data)?;
+        let compressed = encoder.finish()?;
 
         debug!(
-            "Compressed {} bytes of identifiers into {} bytes with Deflate",
-            identifiers.len(),
-            data.len()
+            "Compressed {} bytes of slice data into {} bytes with Deflate",
+            data.len(),
+            compressed.len()
         );
 
-        Ok(data)
+        Ok(compressed)
     }
 
     fn identifiers_as_lines(sequences: &[FastqSequence]) -> String {
@@ -205,18 +877,109 @@ impl<W: Write> IdnBlockCompressor<W> {
         identifiers
     }
 
-    pub fn write_sequence(
+    /// Returns the quality score shared by all the [`Acid::N`] symbols of
+    /// given sequences, or `None` if there are none, or they have different
+    /// quality scores.
+    fn shared_n_q_score(sequences: &[FastqSequence]) -> Option<FastqQualityScore> {
+        let mut n_q_scores = sequences.iter().flat_map(|sequence| {
+            sequence
+                .acids()
+                .iter()
+                .zip(sequence.quality_scores())
+                .filter(|&(&acid, _)| acid == Acid::N)
+                .map(|(_, &q_score)| q_score)
+        });
+        let q_score = n_q_scores.next()?;
+
+        n_q_scores.all(|other| other == q_score).then_some(q_score)
+    }
+
+    /// Returns the distance to the last occurrence of the sequence with the
+    /// same acids, or `None` if this is the first one.
+    fn duplicate_distance<'a>(
+        last_occurrences: &mut HashMap<&'a [Acid], usize>,
+        sequence: &'a FastqSequence,
+        index: usize,
+    ) -> Option<usize> {
+        match last_occurrences.entry(sequence.acids()) {
+            Entry::Occupied(mut entry) => Some(index - entry.insert(index)),
+            Entry::Vacant(entry) => {
+                entry.insert(index);
+                None
+            }
+        }
+    }
+
+    /// Writes a duplicate of the sequence `distance` sequences before, along
+    /// with its encoded quality scores if they differ from the ones of that
+    /// sequence (otherwise, `q_score_data` is empty).
+    fn write_duplicate(
+        &mut self,
+        sequence: &FastqSequence,
+        index: usize,
+        distance: usize,
+        q_score_data: &[u8],
+    ) -> IdnCompressResult<()> {
+        debug!(
+            "Sequence `{}` is a duplicate of the one {} sequences before \
+            (quality scores encoded with {} bytes)",
+            sequence.identifier(),
+            distance,
+            q_score_data.len()
+        );
+        self.block_writer
+            .write_duplicate(sequence, distance, q_score_data)?;
+        self.out_q_score_bytes += q_score_data.len();
+        self.report_sequence(index, SequenceEncoding::Duplicate, q_score_data.len());
+        Ok(())
+    }
+
+    fn write_packed_sequence(
+        &mut self,
+        sequence: &FastqSequence,
+        index: usize,
+    ) -> IdnCompressResult<()> {
+        let data = packing::pack_acids(sequence.acids());
+        debug!(
+            "Packed sequence `{}` (length: {}) into {} bytes",
+            sequence.identifier(),
+            sequence.len(),
+            data.len()
+        );
+
+        self.out_acid_bytes += data.len();
+        self.block_writer.write_sequence(sequence, &data)?;
+        self.report_sequence(index, SequenceEncoding::Packed, data.len());
+        Ok(())
+    }
+
+    /// Writes a zero-length sequence, which consists of the header only, with
+    /// no data to decode.
+    fn write_empty_sequence(
         &mut self,
         sequence: &FastqSequence,
+        index: usize,
+    ) -> IdnCompressResult<()> {
+        debug!("Encoded empty sequence `{}`", sequence.identifier());
+
+        self.block_writer.write_sequence(sequence, &[])?;
+        self.report_sequence(index, SequenceEncoding::Empty, 0);
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn write_sequence(
+        &mut self,
+        sequence: &FastqSequence,
+        index: usize,
+        data: &[u8],
         acid_model: &AcidRansEncModel,
-        q_score_model: &QScoreRansEncModel,
-        options: &IdnCompressorOptions,
+        q_score_models: &[ModelSegment<'_, QScoreRansEncModel>],
+        estimated_bytes: Option<usize>,
     ) -> IdnCompressResult<()> {
         let seq_len = sequence.len();
         let seq_identifier = sequence.identifier().clone();
-        let data = self
-            .compressor
-            .compress(sequence, acid_model, q_score_model);
+        let acid_models = [(0, acid_model)];
         debug!(
             "Encoded sequence `{}` (length: {}) with {} bytes",
             seq_identifier,
@@ -224,58 +987,244 @@ impl<W: Write> IdnBlockCompressor<W> {
             data.len()
         );
 
+        let actual_bytes = data.len();
         self.block_writer.write_sequence(sequence, data)?;
-        options.progress_notifier.processed_bytes(sequence.size());
+        if actual_bytes > seq_len {
+            self.poor_fit_sequences += 1;
+        }
+        if let Some(context_usage) = &mut self.context_usage {
+            context_usage.add(sequence, &acid_models, q_score_models);
+        }
+        if let Some(explanations) = &mut self.explanations {
+            let (acid_dummy_hits, q_score_dummy_hits) =
+                SequenceExplanation::count_dummy_hits(sequence, &acid_models, q_score_models);
+            let (_, q_score_model) = q_score_models[0];
+            explanations.push(SequenceExplanation {
+                block: self.block_index,
+                index,
+                identifier: seq_identifier.str().to_owned(),
+                length: seq_len,
+                acid_model: acid_model.identifier().clone(),
+                q_score_model: q_score_model.identifier().clone(),
+                estimated_bytes,
+                actual_bytes,
+                acid_dummy_hits,
+                q_score_dummy_hits,
+                q_score_model_switches: q_score_models.len() - 1,
+            });
+        }
+        self.report_sequence(index, SequenceEncoding::Models, actual_bytes);
         Ok(())
     }
 
-    fn switch_to_best_acid_model_for<'a>(
+    /// Records the details of how the sequence with given index has been
+    /// encoded, if there is an `on_sequence` callback to pass them to.
+    fn report_sequence(
+        &mut self,
+        index: usize,
+        encoding: SequenceEncoding,
+        compressed_bytes: usize,
+    ) {
+        if self.options.on_sequence.is_some() {
+            self.reports.push(SequenceEncodeInfo {
+                block: self.block_index,
+                index,
+                encoding,
+                compressed_bytes,
+            });
+        }
+    }
+
+    /// Chooses the acid model that is the best for given sequence, returning
+    /// it along with the length the acids would be compressed to, if known.
+    fn choose_best_acid_model_for<'a>(
         &mut self,
         sequence: &FastqSequence,
         options: &'a IdnCompressorOptions,
-    ) -> IdnCompressResult<&'a AcidRansEncModel> {
+    ) -> IdnCompressResult<(Option<usize>, &'a AcidRansEncModel)> {
         let current_identifier = self
             .current_acid_model
             .map(|index| self.options.model_provider[index as usize].identifier());
-        let (bytes, model) =
-            self.model_chooser
-                .get_best_acid_model_for(sequence, options, current_identifier);
-        let index = options.model_provider.index_of(model.identifier()) as u8;
+        let (bytes, model) = self.coders.model_chooser.get_best_acid_model_for(
+            sequence,
+            options,
+            current_identifier,
+        )?;
+        self.current_acid_model = Some(options.model_provider.index_of(model.identifier()) as u8);
 
-        if self.current_acid_model != Some(index) {
-            self.block_writer.write_switch_model(index)?;
-            self.current_acid_model = Some(index);
+        self.out_acid_bytes += bytes.unwrap_or(0);
+        Ok((bytes, model))
+    }
 
-            debug!("Switching to acid model: {}", model.identifier());
-            self.acid_model_switches += 1;
-        }
+    /// Chooses the quality score model that is the best for given sequence,
+    /// returning it along with the length the quality scores would be
+    /// compressed to, if known.
+    fn choose_best_q_score_model_for<'a>(
+        &mut self,
+        sequence: &FastqSequence,
+        options: &'a IdnCompressorOptions,
+    ) -> IdnCompressResult<(Option<usize>, &'a QScoreRansEncModel)> {
+        let current_identifier = self
+            .current_q_score_model
+            .map(|index| self.options.model_provider[index as usize].identifier());
+        let (bytes, model) = self.coders.model_chooser.get_best_q_score_model_for(
+            sequence,
+            options,
+            current_identifier,
+        )?;
 
-        self.out_acid_bytes += bytes;
-        Ok(model)
+        let model = self.choose_q_score_model(model, bytes, options);
+        Ok((bytes, model))
     }
 
-    fn switch_to_best_q_score_model_for<'a>(
+    /// Chooses the quality score models that are the best for each of the
+    /// `window`-long parts of given sequence, returning the model segments
+    /// along with the length the quality scores would be compressed to, if
+    /// known.
+    fn choose_best_q_score_models_for<'a>(
         &mut self,
         sequence: &FastqSequence,
+        window: usize,
+        options: &'a IdnCompressorOptions,
+    ) -> IdnCompressResult<(Option<usize>, Vec<ModelSegment<'a, QScoreRansEncModel>>)> {
+        let current_identifier = self
+            .current_q_score_model
+            .map(|index| self.options.model_provider[index as usize].identifier());
+        let (bytes, segments) = self
+            .coders
+            .model_chooser
+            .get_best_q_score_model_segments_for(sequence, window, options, current_identifier)?;
+
+        let (_, last_model) = *segments.last().unwrap();
+        self.choose_q_score_model(last_model, bytes, options);
+        Ok((bytes, segments))
+    }
+
+    /// Chooses the quality score model that is the best for the group of
+    /// consecutive sequences from the same tile that the sequence at `index`
+    /// belongs to. The model is only chosen once per group; `tile_group` keeps
+    /// the tile and the model of the current group.
+    fn choose_best_q_score_model_for_tile<'a>(
+        &mut self,
+        sequences: &[FastqSequence],
+        index: usize,
+        tile_group: &mut Option<((u32, u32), &'a QScoreRansEncModel)>,
         options: &'a IdnCompressorOptions,
     ) -> IdnCompressResult<&'a QScoreRansEncModel> {
+        let tile = match Self::tile_of(&sequences[index]) {
+            Some(tile) => tile,
+            None => {
+                *tile_group = None;
+                let (_, model) = self.choose_best_q_score_model_for(&sequences[index], options)?;
+                return Ok(model);
+            }
+        };
+        if let Some((group_tile, model)) = *tile_group {
+            if group_tile == tile {
+                return Ok(model);
+            }
+        }
+
+        let group_len = sequences[index..]
+            .iter()
+            .take_while(|sequence| Self::tile_of(sequence) == Some(tile))
+            .count();
+        let group = &sequences[index..index + group_len];
+        debug!(
+            "Found {} sequences from lane {}, tile {}",
+            group_len, tile.0, tile.1
+        );
+
         let current_identifier = self
             .current_q_score_model
             .map(|index| self.options.model_provider[index as usize].identifier());
-        let (bytes, model) =
-            self.model_chooser
-                .get_best_q_score_model_for(sequence, options, current_identifier);
+        let (bytes, model) = self.coders.model_chooser.get_best_q_score_model_for_group(
+            group,
+            options,
+            current_identifier,
+        )?;
+        let model = self.choose_q_score_model(model, bytes, options);
+
+        *tile_group = Some((tile, model));
+        Ok(model)
+    }
+
+    /// Returns the lane and the tile the sequence comes from, if its
+    /// identifier contains them.
+    fn tile_of(sequence: &FastqSequence) -> Option<(u32, u32)> {
+        sequence
+            .identifier()
+            .illumina_coordinates()
+            .map(|coordinates| (coordinates.lane, coordinates.tile))
+    }
+
+    /// Makes given quality score model the current one, i.e. the one the next
+    /// model choice is compared to.
+    fn choose_q_score_model<'a>(
+        &mut self,
+        model: &'a QScoreRansEncModel,
+        bytes: Option<usize>,
+        options: &'a IdnCompressorOptions,
+    ) -> &'a QScoreRansEncModel {
         let index = options.model_provider.index_of(model.identifier()) as u8;
+        self.current_q_score_model = Some(index);
+
+        self.out_q_score_bytes += bytes.unwrap_or(0);
+        model
+    }
+
+    /// Writes the slices switching to the models given sequence is encoded
+    /// with, unless they are already active. The first quality score model is
+    /// switched to before the sequence, and the subsequent ones within it.
+    fn write_model_switches(
+        &mut self,
+        acid_model: &AcidRansEncModel,
+        q_score_models: &[ModelSegment<'_, QScoreRansEncModel>],
+        options: &IdnCompressorOptions,
+    ) -> IdnCompressResult<()> {
+        let index = options.model_provider.index_of(acid_model.identifier()) as u8;
+        if self.written_acid_model != Some(index) {
+            self.block_writer.write_switch_model(index)?;
+            self.written_acid_model = Some(index);
+
+            debug!("Switching to acid model: {}", acid_model.identifier());
+            self.acid_model_switches += 1;
+        }
+
+        let (_, first_model) = q_score_models[0];
+        self.write_q_score_model_switch(first_model, options)?;
+        for &(start, model) in &q_score_models[1..] {
+            let index = options.model_provider.index_of(model.identifier()) as u8;
+            self.block_writer.write_switch_model_at(start, index)?;
+            self.written_q_score_model = Some(index);
 
-        if self.current_q_score_model != Some(index) {
+            debug!(
+                "Switching to quality score model {} at offset {}",
+                model.identifier(),
+                start
+            );
+            self.q_score_model_switches += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the slice switching to given quality score model, unless it is
+    /// the current one already.
+    fn write_q_score_model_switch(
+        &mut self,
+        model: &QScoreRansEncModel,
+        options: &IdnCompressorOptions,
+    ) -> IdnCompressResult<()> {
+        let index = options.model_provider.index_of(model.identifier()) as u8;
+        if self.written_q_score_model != Some(index) {
             self.block_writer.write_switch_model(index)?;
-            self.current_q_score_model = Some(index);
+            self.written_q_score_model = Some(index);
 
             debug!("Switching to quality score model: {}", model.identifier());
             self.q_score_model_switches += 1;
         }
 
-        self.out_q_score_bytes += bytes;
-        Ok(model)
+        Ok(())
     }
 }
@@ -7,6 +7,7 @@ use crate::idn::compressor::{IdnCompressResult, IdnCompressorOptions};
 use crate::idn::model_chooser::ModelChooser;
 use crate::idn::writer_idn::IdnWriter;
 use crate::model::ModelIdentifier;
+use crate::progress::ProgressStage;
 
 pub(super) struct CompressorInitializer<'a, W> {
     writer: &'a mut IdnWriter<W>,
@@ -22,24 +23,43 @@ impl<'a, W: Write + Seek> CompressorInitializer<'a, W> {
         options: &'a mut IdnCompressorOptions,
         initial_sequences: &'a [FastqSequence],
     ) -> Self {
+        let model_chooser =
+            ModelChooser::new(options.max_seq_len, options.quality_alphabet.clone());
+
         Self {
             writer,
             options,
             sequences: initial_sequences,
-            model_chooser: ModelChooser::new(),
+            model_chooser,
         }
     }
 
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "initialize_compressor", skip_all)
+    )]
     pub fn initialize(mut self) -> IdnCompressResult<()> {
         self.writer.write_header(1)?;
         self.retain_best_models();
         self.write_metadata()?;
+        self.options
+            .progress_notifier
+            .start_stage(ProgressStage::Compressing, None);
 
         Ok(())
     }
 
     fn write_metadata(&mut self) -> IdnCompressResult<()> {
+        // The alphabet has to precede the models, as the decompressor needs it
+        // to adapt its models before looking up the identifiers
+        self.writer
+            .add_quality_alphabet_metadata(&self.options.quality_alphabet);
         self.add_models_metadata();
+        self.writer
+            .add_transforms_metadata(&self.options.transforms);
+        if self.options.source_hasher.is_some() {
+            self.writer.reserve_source_digest();
+        }
         self.writer.write_metadata()?;
 
         Ok(())
@@ -51,7 +71,17 @@ impl<'a, W: Write + Seek> CompressorInitializer<'a, W> {
     }
 
     fn retain_best_models(&mut self) {
-        self.options.model_provider.preprocess_compressor_models();
+        if self.options.deterministic {
+            // The order of the models affects which one is chosen when they
+            // perform equally well, so it has to be independent of how they
+            // were loaded
+            self.options.model_provider.sort_by_identifier();
+        }
+
+        let progress_notifier = self.options.progress_notifier.clone();
+        self.options
+            .model_provider
+            .preprocess_compressor_models_with_progress(progress_notifier.as_ref());
 
         let model_num = (self.options.quality.get() as usize + 1) / 2;
         let acid_models = self
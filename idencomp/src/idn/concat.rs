@@ -0,0 +1,351 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+
+use binrw::{BinRead, BinWrite};
+use log::debug;
+
+use crate::idn::compressor::IdnCompressorError;
+use crate::idn::data::{
+    IdnAuxDataHeader, IdnBlockHeader, IdnDuplicateHeader, IdnIdentifiersHeader,
+    IdnSeparatorTextHeader, IdnSequenceHeader, IdnSliceHeader, IdnSwitchModelAtHeader,
+    IdnSwitchModelHeader,
+};
+use crate::idn::decompressor::IdnDecompressorError;
+use crate::idn::inspector::{read_block, read_file_start};
+use crate::idn::no_seek::NoSeek;
+use crate::idn::transform::TransformInfo;
+use crate::idn::writer_idn::IdnWriter;
+use crate::model::ModelIdentifier;
+use crate::quality_alphabet::QualityAlphabet;
+
+/// Error occurring when concatenating IDN files.
+#[derive(Debug)]
+pub enum IdnConcatError {
+    /// Error occurred when reading one of the input files.
+    ReadError(IdnDecompressorError),
+    /// Error occurred when writing the output file.
+    WriteError(IdnCompressorError),
+    /// The input files use more distinct models than a single IDN file can
+    /// refer to.
+    TooManyModels(usize),
+    /// The input files have been compressed with different sequence
+    /// transforms.
+    DifferentTransforms,
+    /// The input files have been compressed with different quality alphabets.
+    DifferentQualityAlphabets,
+}
+
+impl From<IdnDecompressorError> for IdnConcatError {
+    fn from(e: IdnDecompressorError) -> Self {
+        Self::ReadError(e)
+    }
+}
+
+impl From<IdnCompressorError> for IdnConcatError {
+    fn from(e: IdnCompressorError) -> Self {
+        Self::WriteError(e)
+    }
+}
+
+impl Display for IdnConcatError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IdnConcatError::ReadError(e) => write!(f, "Read error: {}", e),
+            IdnConcatError::WriteError(e) => write!(f, "Write error: {}", e),
+            IdnConcatError::TooManyModels(num) => write!(
+                f,
+                "Too many distinct models in the input files (found: {}, limit: {})",
+                num,
+                u8::MAX
+            ),
+            IdnConcatError::DifferentTransforms => write!(
+                f,
+                "The input files have been compressed with different sequence transforms"
+            ),
+            IdnConcatError::DifferentQualityAlphabets => write!(
+                f,
+                "The input files have been compressed with different quality alphabets"
+            ),
+        }
+    }
+}
+
+impl Error for IdnConcatError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            IdnConcatError::ReadError(e) => Some(e),
+            IdnConcatError::WriteError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// The result of concatenating IDN files.
+pub type IdnConcatResult<T> = Result<T, IdnConcatError>;
+
+/// Concatenates multiple IDN files into one, without recompressing any of the
+/// blocks. The model tables of the input files are merged and the model
+/// indices in the blocks are rewritten where necessary.
+///
+/// Decompressing the output file yields the sequences from all the input files,
+/// in order. All the input files must have been compressed with the same
+/// sequence transforms and quality alphabet.
+///
+/// # Examples
+/// ```
+/// use idencomp::idn::compressor::IdnCompressor;
+/// use idencomp::idn::concat::{concat, IdnConcatError};
+/// use idencomp::idn::inspector::inspect;
+///
+/// let mut file_1 = Vec::new();
+/// IdnCompressor::new(&mut file_1).finish().unwrap();
+/// let mut file_2 = Vec::new();
+/// IdnCompressor::new(&mut file_2).finish().unwrap();
+///
+/// let mut output = Vec::new();
+/// concat(vec![file_1.as_slice(), file_2.as_slice()], &mut output)?;
+/// assert_eq!(inspect(output.as_slice()).unwrap().blocks().len(), 0);
+///
+/// # Ok::<(), IdnConcatError>(())
+/// ```
+pub fn concat<R: Read, W: Write>(readers: Vec<R>, writer: W) -> IdnConcatResult<()> {
+    let mut readers: Vec<NoSeek<R>> = readers.into_iter().map(NoSeek::new).collect();
+
+    let mut all_identifiers: Vec<ModelIdentifier> = Vec::new();
+    let mut index_map: HashMap<ModelIdentifier, u8> = HashMap::new();
+    let mut index_mappings: Vec<Vec<u8>> = Vec::new();
+    let mut all_transforms: Option<Vec<TransformInfo>> = None;
+    let mut all_quality_alphabet: Option<QualityAlphabet> = None;
+    for reader in &mut readers {
+        let file_start = read_file_start(reader)?;
+        let identifiers = file_start.model_identifiers;
+        match &all_transforms {
+            Some(transforms) if *transforms != file_start.transforms => {
+                return Err(IdnConcatError::DifferentTransforms);
+            }
+            Some(_) => {}
+            None => all_transforms = Some(file_start.transforms),
+        }
+        match &all_quality_alphabet {
+            Some(alphabet) if *alphabet != file_start.quality_alphabet => {
+                return Err(IdnConcatError::DifferentQualityAlphabets);
+            }
+            Some(_) => {}
+            None => all_quality_alphabet = Some(file_start.quality_alphabet),
+        }
+
+        let mut mapping = Vec::new();
+        for identifier in identifiers {
+            let next_index = all_identifiers.len();
+            let index = *index_map.entry(identifier.clone()).or_insert_with(|| {
+                all_identifiers.push(identifier);
+                next_index as u8
+            });
+            mapping.push(index);
+        }
+        index_mappings.push(mapping);
+
+        if all_identifiers.len() > u8::MAX as usize {
+            return Err(IdnConcatError::TooManyModels(all_identifiers.len()));
+        }
+    }
+    debug!("Merged model identifiers:");
+    for (index, identifier) in all_identifiers.iter().enumerate() {
+        debug!("[{}] {}", index, identifier);
+    }
+
+    let mut writer = IdnWriter::new(NoSeek::new(writer));
+    writer.write_header(1)?;
+    writer.add_quality_alphabet_metadata(&all_quality_alphabet.unwrap_or_default());
+    writer.add_models_metadata(&all_identifiers);
+    writer.add_transforms_metadata(&all_transforms.unwrap_or_default());
+    writer.write_metadata()?;
+
+    let writer = writer.writer_for_block();
+    for (reader, mapping) in readers.iter_mut().zip(&index_mappings) {
+        while let Some((header, mut data)) = read_block(reader)? {
+            remap_model_indices(&mut data, mapping)?;
+
+            header.write_to(writer).map_err(IdnCompressorError::from)?;
+            writer.write_all(&data).map_err(IdnCompressorError::from)?;
+        }
+    }
+
+    let eof_header = IdnBlockHeader {
+        length: 0,
+        seq_checksum: crc32fast::Hasher::new().finalize(),
+    };
+    eof_header
+        .write_to(writer)
+        .map_err(IdnCompressorError::from)?;
+
+    Ok(())
+}
+
+fn remap_model_indices(data: &mut [u8], mapping: &[u8]) -> IdnConcatResult<()> {
+    let data_len = data.len() as u64;
+    let mut cursor = Cursor::new(data);
+
+    while cursor.position() < data_len {
+        let slice_header = IdnSliceHeader::read(&mut cursor).map_err(IdnDecompressorError::from)?;
+        match slice_header {
+            IdnSliceHeader::Identifiers(IdnIdentifiersHeader { length, .. })
+            | IdnSliceHeader::AuxData(IdnAuxDataHeader { length, .. })
+            | IdnSliceHeader::SeparatorText(IdnSeparatorTextHeader { length, .. }) => {
+                cursor
+                    .seek(SeekFrom::Current(length as i64))
+                    .map_err(IdnDecompressorError::from)?;
+            }
+            IdnSliceHeader::SwitchModel(IdnSwitchModelHeader { model_index })
+            | IdnSliceHeader::SwitchModelAt(IdnSwitchModelAtHeader { model_index, .. }) => {
+                let new_index = *mapping.get(model_index as usize).ok_or_else(|| {
+                    IdnDecompressorError::invalid_model_index(model_index, mapping.len() as u8)
+                })?;
+
+                // The model index is the last field of both headers
+                let index_pos = cursor.position() as usize - 1;
+                cursor.get_mut()[index_pos] = new_index;
+            }
+            IdnSliceHeader::Sequence(IdnSequenceHeader { length, .. })
+            | IdnSliceHeader::Duplicate(IdnDuplicateHeader { length, .. }) => {
+                cursor
+                    .seek(SeekFrom::Current(length as i64))
+                    .map_err(IdnDecompressorError::from)?;
+            }
+            IdnSliceHeader::Packed(_)
+            | IdnSliceHeader::Raw(_)
+            | IdnSliceHeader::NQualityScore(_)
+            | IdnSliceHeader::Member(_)
+            | IdnSliceHeader::Group(_) => {}
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::_internal_test_data::{
+        TEST_ACID_MODEL_PREFER_A, TEST_ACID_MODEL_PREFER_C, TEST_SEQUENCE_PREFER_A,
+        TEST_SEQUENCE_PREFER_C,
+    };
+    use crate::fastq::FastqSequence;
+    use crate::idn::compressor::{IdnCompressor, IdnCompressorParams};
+    use crate::idn::concat::{concat, IdnConcatError};
+    use crate::idn::decompressor::{IdnDecompressor, IdnDecompressorParams};
+    use crate::idn::inspector::inspect;
+    use crate::idn::model_provider::ModelProvider;
+    use crate::idn::transform::{QualityBinning, TransformInfo};
+    use crate::model::{Model, ModelType};
+
+    fn compress(sequence: &FastqSequence, model_provider: ModelProvider) -> Vec<u8> {
+        compress_with_params(
+            sequence,
+            IdnCompressorParams::builder()
+                .model_provider(model_provider)
+                .build()
+                .unwrap(),
+        )
+    }
+
+    fn compress_with_params(sequence: &FastqSequence, params: IdnCompressorParams) -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut compressor = IdnCompressor::with_params(&mut data, params);
+        compressor.add_sequence(sequence.clone()).unwrap();
+        compressor.finish().unwrap();
+
+        data
+    }
+
+    #[test]
+    fn test_concat_with_different_models() {
+        let file_1 = compress(
+            &TEST_SEQUENCE_PREFER_A,
+            ModelProvider::new(vec![
+                TEST_ACID_MODEL_PREFER_A.clone(),
+                Model::empty(ModelType::QualityScores),
+            ]),
+        );
+        let file_2 = compress(
+            &TEST_SEQUENCE_PREFER_C,
+            ModelProvider::new(vec![
+                TEST_ACID_MODEL_PREFER_C.clone(),
+                Model::empty(ModelType::QualityScores),
+            ]),
+        );
+
+        let mut output = Vec::new();
+        concat(vec![file_1.as_slice(), file_2.as_slice()], &mut output).unwrap();
+
+        let info = inspect(output.as_slice()).unwrap();
+        assert_eq!(info.model_identifiers().len(), 3);
+        assert_eq!(info.sequence_num(), 2);
+
+        let params = IdnDecompressorParams::builder()
+            .model_provider(ModelProvider::new(vec![
+                TEST_ACID_MODEL_PREFER_A.clone(),
+                TEST_ACID_MODEL_PREFER_C.clone(),
+                Model::empty(ModelType::QualityScores),
+            ]))
+            .build();
+        let mut decompressor = IdnDecompressor::with_params(output.as_slice(), params);
+        assert_eq!(
+            decompressor.next_sequence().unwrap().as_ref(),
+            Some(&*TEST_SEQUENCE_PREFER_A)
+        );
+        assert_eq!(
+            decompressor.next_sequence().unwrap().as_ref(),
+            Some(&*TEST_SEQUENCE_PREFER_C)
+        );
+        assert_eq!(decompressor.next_sequence().unwrap(), None);
+    }
+
+    #[test]
+    fn test_concat_with_transforms() {
+        let params = || {
+            IdnCompressorParams::builder()
+                .transform(Arc::new(QualityBinning))
+                .build()
+                .unwrap()
+        };
+        let file_1 = compress_with_params(&TEST_SEQUENCE_PREFER_A, params());
+        let file_2 = compress_with_params(&TEST_SEQUENCE_PREFER_C, params());
+        let file_3 = compress(&TEST_SEQUENCE_PREFER_C, ModelProvider::default());
+
+        let mut output = Vec::new();
+        concat(vec![file_1.as_slice(), file_2.as_slice()], &mut output).unwrap();
+        let info = inspect(output.as_slice()).unwrap();
+        assert_eq!(info.transforms(), [TransformInfo::of(&QualityBinning)]);
+
+        let result = concat(vec![file_1.as_slice(), file_3.as_slice()], Vec::new());
+        assert!(matches!(result, Err(IdnConcatError::DifferentTransforms)));
+    }
+
+    #[test]
+    fn test_concat_error_display() {
+        assert_eq!(
+            IdnConcatError::TooManyModels(300).to_string(),
+            "Too many distinct models in the input files (found: 300, limit: 255)"
+        );
+        assert_eq!(
+            IdnConcatError::DifferentTransforms.to_string(),
+            "The input files have been compressed with different sequence transforms"
+        );
+        assert_eq!(
+            IdnConcatError::DifferentQualityAlphabets.to_string(),
+            "The input files have been compressed with different quality alphabets"
+        );
+    }
+
+    #[test]
+    fn test_concat_invalid_input() {
+        let mut output = Vec::new();
+        let result = concat(vec![b"INVALID".as_slice()], &mut output);
+
+        assert!(result.is_err());
+    }
+}
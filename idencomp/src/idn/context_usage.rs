@@ -0,0 +1,246 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::context_spec::ContextSpec;
+use crate::fastq::FastqSequence;
+use crate::idn::common::opaque_debug;
+use crate::model::{ModelIdentifier, ModelType};
+use crate::sequence_compressor::{
+    AcidRansEncModel, ModelSegment, QScoreRansEncModel, RansEncModel, SequenceCompressor,
+};
+
+/// The number of symbols encoded with each context of a single model.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ModelContextUsage {
+    identifier: ModelIdentifier,
+    model_type: ModelType,
+    /// The number of hits of the dummy context, followed by the number of hits
+    /// of each of the model contexts.
+    hits: Vec<u64>,
+}
+
+impl ModelContextUsage {
+    #[must_use]
+    fn new(identifier: ModelIdentifier, model_type: ModelType, context_num: usize) -> Self {
+        Self {
+            identifier,
+            model_type,
+            hits: vec![0; context_num],
+        }
+    }
+
+    fn merge(&mut self, other: &Self) {
+        for (hits, other_hits) in self.hits.iter_mut().zip(&other.hits) {
+            *hits += other_hits;
+        }
+    }
+
+    /// Returns the identifier of the model.
+    #[must_use]
+    pub fn identifier(&self) -> &ModelIdentifier {
+        &self.identifier
+    }
+
+    /// Returns the type of the model.
+    #[must_use]
+    pub fn model_type(&self) -> ModelType {
+        self.model_type
+    }
+
+    /// Returns the number of symbols whose context was not present in the
+    /// model, so they were encoded with the dummy (uniform) context.
+    #[must_use]
+    pub fn dummy_hits(&self) -> u64 {
+        self.hits[0]
+    }
+
+    /// Returns the number of symbols encoded with each of the model contexts,
+    /// in the order they are stored in the model.
+    #[must_use]
+    pub fn context_hits(&self) -> &[u64] {
+        &self.hits[1..]
+    }
+
+    /// Returns the total number of symbols encoded with this model.
+    #[must_use]
+    pub fn total_hits(&self) -> u64 {
+        self.hits.iter().sum()
+    }
+}
+
+/// Collects the number of symbols encoded with each context of each model
+/// during the compression, which shows how well the context spec space of the
+/// models matches the data.
+///
+/// The counts are accumulated into a collector shared by all of its clones:
+/// the compressor updates the clone passed to
+/// [`IdnCompressorParamsBuilder::context_usage()`](crate::idn::compressor::IdnCompressorParamsBuilder::context_usage)
+/// as the blocks are written, and [`Self::models()`] can be called on the
+/// original once the compression has finished. Only the symbols encoded with
+/// the models are counted, i.e. the duplicate and packed sequences are not
+/// included.
+///
+/// # Examples
+/// ```
+/// use idencomp::fastq::reader::FastqReader;
+/// use idencomp::idn::compressor::{IdnCompressor, IdnCompressorParams};
+/// use idencomp::idn::context_usage::ContextUsage;
+///
+/// let context_usage = ContextUsage::new();
+/// let params = IdnCompressorParams::builder()
+///     .context_usage(context_usage.clone())
+///     .build()?;
+/// let mut data = Vec::new();
+/// let mut compressor = IdnCompressor::with_params(&mut data, params);
+/// for sequence in FastqReader::new(b"@SEQ1\nACGT\n+\n!!!!\n".as_slice()) {
+///     compressor.add_sequence(sequence?)?;
+/// }
+/// compressor.finish()?;
+///
+/// let models = context_usage.models();
+/// let total_hits: u64 = models.iter().map(|model| model.total_hits()).sum();
+/// assert_eq!(total_hits, 8);
+///
+/// # Ok::<(), idencomp::Error>(())
+/// ```
+#[derive(Clone, Default)]
+pub struct ContextUsage {
+    models: Arc<Mutex<BTreeMap<ModelIdentifier, ModelContextUsage>>>,
+}
+
+impl ContextUsage {
+    /// Creates a new, empty `ContextUsage` instance.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the usage of all the models that have been used so far, ordered
+    /// by the model identifier.
+    #[must_use]
+    pub fn models(&self) -> Vec<ModelContextUsage> {
+        self.lock_models().values().cloned().collect()
+    }
+
+    pub(super) fn merge(&self, block_usage: &BlockContextUsage) {
+        let mut models = self.lock_models();
+        for usage in block_usage.models.values() {
+            models
+                .entry(usage.identifier.clone())
+                .and_modify(|model| model.merge(usage))
+                .or_insert_with(|| usage.clone());
+        }
+    }
+
+    fn lock_models(&self) -> MutexGuard<'_, BTreeMap<ModelIdentifier, ModelContextUsage>> {
+        self.models
+            .lock()
+            .expect("Could not acquire context usage lock")
+    }
+}
+
+// The hit counts of every context of every model would flood the output
+opaque_debug!(ContextUsage);
+
+/// Context usage collected in a single block, merged into the [`ContextUsage`]
+/// once the block is compressed, so that the threads do not contend for the
+/// lock for each sequence.
+#[derive(Debug, Default)]
+pub(super) struct BlockContextUsage {
+    models: HashMap<ModelIdentifier, ModelContextUsage>,
+}
+
+impl BlockContextUsage {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(
+        &mut self,
+        sequence: &FastqSequence,
+        acid_models: &[ModelSegment<'_, AcidRansEncModel>],
+        q_score_models: &[ModelSegment<'_, QScoreRansEncModel>],
+    ) {
+        let (acid_specs, q_score_specs) =
+            SequenceCompressor::gen_contexts(sequence, acid_models, q_score_models);
+
+        self.add_specs(&acid_specs, acid_models, ModelType::Acids);
+        self.add_specs(&q_score_specs, q_score_models, ModelType::QualityScores);
+    }
+
+    fn add_specs(
+        &mut self,
+        specs: &[ContextSpec],
+        models: &[ModelSegment<'_, RansEncModel>],
+        model_type: ModelType,
+    ) {
+        let segment_ends = models
+            .iter()
+            .skip(1)
+            .map(|(start, _)| *start)
+            .chain([specs.len()]);
+        for (&(start, model), end) in models.iter().zip(segment_ends) {
+            let hits = self.hits_for(model.identifier(), model_type, model.context_num());
+            for &spec in &specs[start..end] {
+                hits[model.context_index_for(spec)] += 1;
+            }
+        }
+    }
+
+    fn hits_for(
+        &mut self,
+        identifier: &ModelIdentifier,
+        model_type: ModelType,
+        context_num: usize,
+    ) -> &mut [u64] {
+        &mut self
+            .models
+            .entry(identifier.clone())
+            .or_insert_with(|| ModelContextUsage::new(identifier.clone(), model_type, context_num))
+            .hits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::_internal_test_data::{
+        TEST_ACID_MODEL_PREFER_A, TEST_SEQUENCE_PREFER_A, TEST_SEQUENCE_PREFER_C,
+    };
+    use crate::idn::context_usage::{BlockContextUsage, ContextUsage};
+    use crate::model::{Model, ModelType};
+    use crate::sequence_compressor::{AcidRansEncModel, QScoreRansEncModel};
+
+    const SCALE_BITS: u8 = 10;
+
+    #[test]
+    fn test_block_usage_merge() {
+        let acid_model = AcidRansEncModel::from_model(&TEST_ACID_MODEL_PREFER_A, SCALE_BITS);
+        let q_score_model =
+            QScoreRansEncModel::from_model(&Model::empty(ModelType::QualityScores), SCALE_BITS);
+
+        let context_usage = ContextUsage::new();
+        for sequence in [&*TEST_SEQUENCE_PREFER_A, &*TEST_SEQUENCE_PREFER_C] {
+            let mut block_usage = BlockContextUsage::new();
+            block_usage.add(sequence, &[(0, &acid_model)], &[(0, &q_score_model)]);
+            context_usage.merge(&block_usage);
+        }
+
+        let models = context_usage.models();
+        assert_eq!(models.len(), 2);
+        let seq_len = (TEST_SEQUENCE_PREFER_A.len() + TEST_SEQUENCE_PREFER_C.len()) as u64;
+        for model in &models {
+            assert_eq!(model.total_hits(), seq_len);
+        }
+
+        let q_score_usage = models
+            .iter()
+            .find(|model| model.model_type() == ModelType::QualityScores)
+            .unwrap();
+        // The empty model does not have any contexts, so all the symbols fall
+        // through to the dummy one
+        assert_eq!(q_score_usage.dummy_hits(), seq_len);
+        assert!(q_score_usage.context_hits().is_empty());
+    }
+}
@@ -1,4 +1,6 @@
-use binrw::binrw;
+use std::io::{Read, Seek};
+
+use binrw::{binrw, BinRead, BinResult};
 
 #[binrw]
 #[brw(big, magic = b"IDENCOMP")]
@@ -20,6 +22,36 @@ pub struct IdnMetadataHeader {
 pub enum IdnMetadataItem {
     #[brw(magic = 0u8)]
     Models(IdnModelsMetadata),
+    #[brw(magic = 1u8)]
+    SourceDigest(IdnSourceDigestMetadata),
+    #[brw(magic = 2u8)]
+    Transforms(IdnTransformsMetadata),
+    #[brw(magic = 3u8)]
+    QualityAlphabet(IdnQualityAlphabetMetadata),
+    #[brw(magic = 4u8)]
+    MemberIndex(IdnMemberIndexMetadata),
+}
+
+impl IdnMetadataItem {
+    /// Reads a metadata item without ever seeking back. The derived
+    /// [`BinRead`] implementation rewinds the reader after each of the
+    /// variants whose magic does not match, which the non-seekable readers of
+    /// the IDN files do not allow.
+    pub fn read_forward<R: Read + Seek>(reader: &mut R) -> BinResult<Self> {
+        let pos = reader.stream_position()?;
+        let magic = u8::read(reader)?;
+        match magic {
+            0 => IdnModelsMetadata::read(reader).map(Self::Models),
+            1 => IdnSourceDigestMetadata::read(reader).map(Self::SourceDigest),
+            2 => IdnTransformsMetadata::read(reader).map(Self::Transforms),
+            3 => IdnQualityAlphabetMetadata::read(reader).map(Self::QualityAlphabet),
+            4 => IdnMemberIndexMetadata::read(reader).map(Self::MemberIndex),
+            _ => Err(binrw::Error::BadMagic {
+                pos,
+                found: Box::new(magic),
+            }),
+        }
+    }
 }
 
 #[binrw]
@@ -32,6 +64,71 @@ pub struct IdnModelsMetadata {
     pub model_identifiers: Vec<[u8; 32]>,
 }
 
+#[binrw]
+#[brw(big)]
+#[derive(Debug)]
+pub struct IdnSourceDigestMetadata {
+    pub sha256: [u8; 32],
+}
+
+#[binrw]
+#[brw(big)]
+#[derive(Debug)]
+pub struct IdnTransformsMetadata {
+    pub num_transforms: u8,
+
+    #[br(count = num_transforms)]
+    pub transforms: Vec<IdnTransformMetadata>,
+}
+
+#[binrw]
+#[brw(big)]
+#[derive(Debug)]
+pub struct IdnTransformMetadata {
+    pub lossy: u8,
+    pub identifier_len: u8,
+
+    #[br(count = identifier_len)]
+    pub identifier: Vec<u8>,
+}
+
+#[binrw]
+#[brw(big)]
+#[derive(Debug)]
+pub struct IdnQualityAlphabetMetadata {
+    pub num_values: u8,
+
+    #[br(count = num_values)]
+    pub values: Vec<u8>,
+}
+
+/// The list of the container members, stored in the trailing metadata, so
+/// that the members can be listed without reading the block data.
+#[binrw]
+#[brw(big)]
+#[derive(Debug)]
+pub struct IdnMemberIndexMetadata {
+    pub num_members: u32,
+
+    #[br(count = num_members)]
+    pub members: Vec<IdnMemberIndexEntry>,
+}
+
+#[binrw]
+#[brw(big)]
+#[derive(Debug, Clone)]
+pub struct IdnMemberIndexEntry {
+    /// Position of the header of the first block of the member, relative to
+    /// the start of the file.
+    pub offset: u64,
+    pub block_num: u32,
+    pub sequence_num: u64,
+    pub name_len: u16,
+
+    #[br(count = name_len)]
+    pub name: Vec<u8>,
+}
+
 #[binrw]
 #[brw(big)]
 #[derive(Debug)]
@@ -50,6 +147,24 @@ pub enum IdnSliceHeader {
     SwitchModel(IdnSwitchModelHeader),
     #[brw(magic = 2u8)]
     Sequence(IdnSequenceHeader),
+    #[brw(magic = 3u8)]
+    Duplicate(IdnDuplicateHeader),
+    #[brw(magic = 4u8)]
+    Packed(IdnPackedHeader),
+    #[brw(magic = 5u8)]
+    Member(IdnMemberHeader),
+    #[brw(magic = 6u8)]
+    SwitchModelAt(IdnSwitchModelAtHeader),
+    #[brw(magic = 7u8)]
+    NQualityScore(IdnNQualityScoreHeader),
+    #[brw(magic = 8u8)]
+    AuxData(IdnAuxDataHeader),
+    #[brw(magic = 9u8)]
+    Raw(IdnRawHeader),
+    #[brw(magic = 10u8)]
+    SeparatorText(IdnSeparatorTextHeader),
+    #[brw(magic = 11u8)]
+    Group(IdnGroupHeader),
 }
 
 #[binrw]
@@ -58,6 +173,7 @@ pub enum IdnSliceHeader {
 pub enum IdnIdentifierCompression {
     Brotli,
     Deflate,
+    Uncompressed,
 }
 
 #[binrw]
@@ -68,6 +184,27 @@ pub struct IdnIdentifiersHeader {
     pub compression: IdnIdentifierCompression,
 }
 
+/// The auxiliary data of all the sequences in the block, each one prefixed
+/// with its length as a big-endian `u32`, compressed the same way as the
+/// identifiers.
+#[binrw]
+#[brw(big)]
+#[derive(Debug)]
+pub struct IdnAuxDataHeader {
+    pub length: u32,
+    pub compression: IdnIdentifierCompression,
+}
+
+/// The separator line texts (the text after the `+` in FASTQ) of all the
+/// sequences in the block, encoded the same way as the auxiliary data.
+#[binrw]
+#[brw(big)]
+#[derive(Debug)]
+pub struct IdnSeparatorTextHeader {
+    pub length: u32,
+    pub compression: IdnIdentifierCompression,
+}
+
 #[binrw]
 #[brw(big)]
 #[derive(Debug)]
@@ -75,6 +212,17 @@ pub struct IdnSwitchModelHeader {
     pub model_index: u8,
 }
 
+/// Switches the model within the next sequence slice, starting at the symbol
+/// with given offset. The model index is the last field, so that it can be
+/// remapped in place the same way as in [`IdnSwitchModelHeader`].
+#[binrw]
+#[brw(big)]
+#[derive(Debug)]
+pub struct IdnSwitchModelAtHeader {
+    pub offset: u32,
+    pub model_index: u8,
+}
+
 #[binrw]
 #[brw(big)]
 #[derive(Debug)]
@@ -82,3 +230,62 @@ pub struct IdnSequenceHeader {
     pub length: u32,
     pub seq_len: u32,
 }
+
+/// A sequence with the same acids as the one `distance` sequences earlier in
+/// the block. If `length` is non-zero, the header is followed by the quality
+/// scores of the sequence, encoded with the current quality score model;
+/// otherwise, the quality scores are the same as the ones of the earlier
+/// sequence.
+#[binrw]
+#[brw(big)]
+#[derive(Debug)]
+pub struct IdnDuplicateHeader {
+    pub distance: u32,
+    pub seq_len: u32,
+    pub length: u32,
+}
+
+#[binrw]
+#[brw(big)]
+#[derive(Debug)]
+pub struct IdnPackedHeader {
+    pub q_score: u8,
+}
+
+/// Marks the rest of the block as raw: the subsequent sequences are stored
+/// uncompressed, using a single byte per acid and per quality score.
+#[binrw]
+#[brw(big)]
+#[derive(Debug)]
+pub struct IdnRawHeader {}
+
+/// Sets the quality score of all the `N` acids in the rest of the block, which
+/// are then encoded without their quality scores.
+#[binrw]
+#[brw(big)]
+#[derive(Debug)]
+pub struct IdnNQualityScoreHeader {
+    pub q_score: u8,
+}
+
+#[binrw]
+#[brw(big)]
+#[derive(Debug)]
+pub struct IdnMemberHeader {
+    pub name_len: u16,
+
+    #[br(count = name_len)]
+    pub name: Vec<u8>,
+}
+
+/// The key of the group all the sequences in the block belong to (see
+/// [`IdnCompressorParamsBuilder::group_blocks_by()`](crate::idn::compressor::IdnCompressorParamsBuilder::group_blocks_by)).
+#[binrw]
+#[brw(big)]
+#[derive(Debug)]
+pub struct IdnGroupHeader {
+    pub key_len: u16,
+
+    #[br(count = key_len)]
+    pub key: Vec<u8>,
+}
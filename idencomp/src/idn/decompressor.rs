@@ -1,25 +1,41 @@
+use std::collections::BTreeMap;
 use std::error::Error;
 use std::fmt::{Debug, Display, Formatter};
-use std::io::Read;
+use std::io::{Cursor, Read};
+use std::mem;
 use std::string::FromUtf8Error;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::Instant;
 
 use binrw::BinRead;
+use crossbeam_channel::Receiver;
 use itertools::Itertools;
 use log::{debug, info, trace};
 
 use super::no_seek::NoSeek;
 use crate::fastq::FastqSequence;
-use crate::idn::common::{format_stats, DataQueue, IdnBlockLock};
+use crate::idn::common::{format_stats, start_time, ObjectPool, OrderedQueue};
 use crate::idn::data::{
     IdnBlockHeader, IdnHeader, IdnMetadataHeader, IdnMetadataItem, IdnModelsMetadata,
+    IdnSliceHeader,
+};
+use crate::idn::decompressor_block::{BlockScratch, IdnBlockDecompressor};
+use crate::idn::inspector::{
+    inspect, read_quality_alphabet, read_trailing_metadata, IdentifierCompression, IdnMember,
 };
-use crate::idn::decompressor_block::IdnBlockDecompressor;
 use crate::idn::model_provider::ModelProvider;
+use crate::idn::source_digest::SourceDigest;
 use crate::idn::thread_pool::ThreadPool;
-use crate::model::{ModelIdentifier, ModelType};
-use crate::progress::{ByteNum, DummyProgressNotifier, ProgressNotifier};
+use crate::idn::transform::{QualityDelta, SequenceTransform, TransformInfo};
+use crate::idn::warning::{self, Warning, WarningSink};
+use crate::model::{ModelIdentifier, ModelIdentifierMismatchError, ModelType};
+use crate::progress::{
+    ByteNum, DummyProgressNotifier, ProgressNotifier, ProgressStage, DEFAULT_PROGRESS_GRANULARITY,
+};
+use crate::quality_alphabet::QualityAlphabet;
+use crate::sequence::InvalidQualityScoreError;
+use crate::sequence_compressor::SequenceDecodeError;
 
 /// Error occurring during decompression of an IDN file.
 #[derive(Debug, Default)]
@@ -45,6 +61,56 @@ pub enum IdnDecompressorError {
     NoActiveModel(ModelType),
     /// Unknown model identifier occurred in the file metadata.
     UnknownModel(ModelIdentifier),
+    /// The identifier of a model used by the file does not match the model
+    /// contents, which means the model has been corrupted or edited.
+    ModelIdentifierMismatch(ModelIdentifierMismatchError),
+    /// Invalid quality score has been decoded.
+    InvalidQualityScore(InvalidQualityScoreError),
+    /// The identifiers are compressed with a method that has been disabled
+    /// at compile time.
+    UnsupportedIdentifierCompression(IdentifierCompression),
+    /// Duplicate slice references a sequence that is not present in the
+    /// block, or has different length.
+    InvalidDuplicate(u32, usize),
+    /// The length of a packed sequence slice does not match the sequence
+    /// length.
+    InvalidPackedSequence(usize, usize),
+    /// The length of a raw sequence slice does not match the sequence length.
+    InvalidRawSequence(usize, usize),
+    /// Invalid acid value has been read from a raw sequence slice.
+    InvalidAcid(u8),
+    /// The container member requested to be extracted is not present in the
+    /// file.
+    UnknownMember(String),
+    /// The block length exceeds [`IdnDecompressorLimits::max_block_len()`].
+    BlockTooLarge(usize, usize),
+    /// The length of the identifier or auxiliary data (either compressed or
+    /// decompressed) exceeds [`IdnDecompressorLimits::max_identifiers_len()`].
+    IdentifiersTooLarge(usize),
+    /// The number of the metadata items exceeds
+    /// [`IdnDecompressorLimits::max_metadata_items()`].
+    TooManyMetadataItems(usize, usize),
+    /// The sequence length exceeds
+    /// [`IdnDecompressorLimits::max_sequence_len()`].
+    SequenceTooLong(usize, usize),
+    /// The length of a slice exceeds the length of the remaining block data.
+    SliceOutOfBounds(usize, usize),
+    /// A lossless sequence transform has been applied to the sequences, but
+    /// it has not been registered with
+    /// [`IdnDecompressorParamsBuilder::transform()`], so the original
+    /// sequences cannot be restored.
+    UnknownTransform(String),
+    /// The quality alphabet stored in the file metadata is empty.
+    EmptyQualityAlphabet,
+    /// The offset of a model switch within a sequence is not greater than the
+    /// offset of the previous switch, or not less than the sequence length.
+    InvalidModelSwitchOffset(u32, usize),
+    /// A model switch within a sequence is not followed by a sequence encoded
+    /// with the models, e.g. by a duplicate or a packed sequence.
+    UnusedModelSwitch,
+    /// The data of a non-empty sequence slice is shorter than the final
+    /// states of the rANS encoder, which are always stored.
+    SequenceDataTooShort(usize, usize),
 }
 
 impl IdnDecompressorError {
@@ -63,10 +129,75 @@ impl IdnDecompressorError {
         Self::NoActiveModel(model_type)
     }
 
+    #[must_use]
+    pub(super) fn invalid_model_switch_offset(offset: u32, seq_len: usize) -> Self {
+        Self::InvalidModelSwitchOffset(offset, seq_len)
+    }
+
+    #[must_use]
+    pub(super) fn invalid_duplicate(distance: u32, sequence_num: usize) -> Self {
+        Self::InvalidDuplicate(distance, sequence_num)
+    }
+
+    #[must_use]
+    pub(super) fn invalid_packed_sequence(data_len: usize, seq_len: usize) -> Self {
+        Self::InvalidPackedSequence(data_len, seq_len)
+    }
+
+    #[must_use]
+    pub(super) fn invalid_raw_sequence(data_len: usize, seq_len: usize) -> Self {
+        Self::InvalidRawSequence(data_len, seq_len)
+    }
+
+    #[must_use]
+    pub(super) fn invalid_acid(value: u8) -> Self {
+        Self::InvalidAcid(value)
+    }
+
+    #[must_use]
+    pub(super) fn unknown_member<T: Into<String>>(name: T) -> Self {
+        Self::UnknownMember(name.into())
+    }
+
     #[must_use]
     pub(super) fn unknown_model(model_identifier: ModelIdentifier) -> Self {
         Self::UnknownModel(model_identifier)
     }
+
+    #[must_use]
+    pub(super) fn block_too_large(length: usize, limit: usize) -> Self {
+        Self::BlockTooLarge(length, limit)
+    }
+
+    #[must_use]
+    pub(super) fn identifiers_too_large(limit: usize) -> Self {
+        Self::IdentifiersTooLarge(limit)
+    }
+
+    #[must_use]
+    pub(super) fn too_many_metadata_items(item_num: usize, limit: usize) -> Self {
+        Self::TooManyMetadataItems(item_num, limit)
+    }
+
+    #[must_use]
+    pub(super) fn sequence_too_long(seq_len: usize, limit: usize) -> Self {
+        Self::SequenceTooLong(seq_len, limit)
+    }
+
+    #[must_use]
+    pub(super) fn slice_out_of_bounds(length: usize, remaining: usize) -> Self {
+        Self::SliceOutOfBounds(length, remaining)
+    }
+
+    #[must_use]
+    pub(super) fn unknown_transform<T: Into<String>>(identifier: T) -> Self {
+        Self::UnknownTransform(identifier.into())
+    }
+
+    #[must_use]
+    pub(super) fn sequence_data_too_short(data_len: usize, min_len: usize) -> Self {
+        Self::SequenceDataTooShort(data_len, min_len)
+    }
 }
 
 impl From<std::io::Error> for IdnDecompressorError {
@@ -81,6 +212,29 @@ impl From<FromUtf8Error> for IdnDecompressorError {
     }
 }
 
+impl From<InvalidQualityScoreError> for IdnDecompressorError {
+    fn from(e: InvalidQualityScoreError) -> Self {
+        Self::InvalidQualityScore(e)
+    }
+}
+
+impl From<SequenceDecodeError> for IdnDecompressorError {
+    fn from(e: SequenceDecodeError) -> Self {
+        match e {
+            SequenceDecodeError::Truncated(e) => {
+                Self::sequence_data_too_short(e.data_len(), e.min_len())
+            }
+            SequenceDecodeError::InvalidQualityScore(e) => Self::InvalidQualityScore(e),
+        }
+    }
+}
+
+impl From<ModelIdentifierMismatchError> for IdnDecompressorError {
+    fn from(e: ModelIdentifierMismatchError) -> Self {
+        Self::ModelIdentifierMismatch(e)
+    }
+}
+
 impl From<binrw::Error> for IdnDecompressorError {
     fn from(e: binrw::Error) -> Self {
         Self::SerializeError(e)
@@ -115,6 +269,75 @@ impl Display for IdnDecompressorError {
             IdnDecompressorError::UnknownModel(model_identifier) => {
                 write!(f, "Unknown model {} used by the file", model_identifier)
             }
+            IdnDecompressorError::ModelIdentifierMismatch(e) => write!(f, "{}", e),
+            IdnDecompressorError::InvalidQualityScore(e) => write!(f, "{}", e),
+            IdnDecompressorError::UnsupportedIdentifierCompression(compression) => write!(
+                f,
+                "Identifier compression method not supported by this build: {}",
+                compression
+            ),
+            IdnDecompressorError::InvalidDuplicate(distance, sequence_num) => write!(
+                f,
+                "Invalid duplicate sequence reference (distance: {}, preceding sequences in block: {})",
+                distance, sequence_num
+            ),
+            IdnDecompressorError::InvalidPackedSequence(data_len, seq_len) => write!(
+                f,
+                "Invalid packed sequence (data length: {}, sequence length: {})",
+                data_len, seq_len
+            ),
+            IdnDecompressorError::InvalidRawSequence(data_len, seq_len) => write!(
+                f,
+                "Invalid raw sequence (data length: {}, sequence length: {})",
+                data_len, seq_len
+            ),
+            IdnDecompressorError::InvalidAcid(value) => write!(f, "Invalid acid value: {}", value),
+            IdnDecompressorError::UnknownMember(name) => {
+                write!(f, "Container member not found: `{}`", name)
+            }
+            IdnDecompressorError::BlockTooLarge(length, limit) => write!(
+                f,
+                "Block too large (length: {}, limit: {})",
+                length, limit
+            ),
+            IdnDecompressorError::IdentifiersTooLarge(limit) => {
+                write!(f, "Identifier data exceeds the limit of {} bytes", limit)
+            }
+            IdnDecompressorError::TooManyMetadataItems(item_num, limit) => write!(
+                f,
+                "Too many metadata items (number: {}, limit: {})",
+                item_num, limit
+            ),
+            IdnDecompressorError::SequenceTooLong(seq_len, limit) => write!(
+                f,
+                "Sequence too long (length: {}, limit: {})",
+                seq_len, limit
+            ),
+            IdnDecompressorError::SliceOutOfBounds(length, remaining) => write!(
+                f,
+                "Slice exceeds the block (slice length: {}, remaining block length: {})",
+                length, remaining
+            ),
+            IdnDecompressorError::UnknownTransform(identifier) => {
+                write!(f, "Unknown lossless sequence transform: `{}`", identifier)
+            }
+            IdnDecompressorError::EmptyQualityAlphabet => {
+                write!(f, "Empty quality alphabet in the file metadata")
+            }
+            IdnDecompressorError::InvalidModelSwitchOffset(offset, seq_len) => write!(
+                f,
+                "Invalid model switch offset (offset: {}, sequence length: {})",
+                offset, seq_len
+            ),
+            IdnDecompressorError::UnusedModelSwitch => write!(
+                f,
+                "Model switch within a sequence not followed by an encoded sequence"
+            ),
+            IdnDecompressorError::SequenceDataTooShort(data_len, min_len) => write!(
+                f,
+                "Sequence data too short (length: {}, minimum: {})",
+                data_len, min_len
+            ),
         }
     }
 }
@@ -125,6 +348,8 @@ impl Error for IdnDecompressorError {
             IdnDecompressorError::IoError(e) => Some(e),
             IdnDecompressorError::Utf8Error(e) => Some(e),
             IdnDecompressorError::SerializeError(e) => Some(e),
+            IdnDecompressorError::InvalidQualityScore(e) => Some(e),
+            IdnDecompressorError::ModelIdentifierMismatch(e) => Some(e),
             _ => None,
         }
     }
@@ -133,12 +358,49 @@ impl Error for IdnDecompressorError {
 /// The result of decompressing IDN.
 pub type IdnDecompressResult<T> = Result<T, IdnDecompressorError>;
 
+/// A decompressed sequence along with its position in the IDN file: the index
+/// of the block it is stored in (counting all the blocks of the file, as
+/// returned by [`IdnFileInfo::blocks()`]) and its index within the block. The
+/// sequences that have been filtered out are counted as well, so the position
+/// does not depend on the [`SequenceFilter`] or the selected container member.
+///
+/// [`IdnFileInfo::blocks()`]: crate::idn::inspector::IdnFileInfo::blocks
+pub type IndexedSequence = (usize, usize, FastqSequence);
+
+const UNTRUSTED_MAX_BLOCK_LEN: usize = 256 * 1024 * 1024;
+const UNTRUSTED_MAX_IDENTIFIERS_LEN: usize = 64 * 1024 * 1024;
+const UNTRUSTED_MAX_METADATA_ITEMS: usize = 16;
+const UNTRUSTED_MAX_SEQUENCE_LEN: usize = 64 * 1024 * 1024;
+
+/// Filter deciding which of the decompressed sequences should be returned by
+/// the [`IdnDecompressor`]. The filter is run inside the decompressor worker
+/// threads.
+pub trait SequenceFilter: Debug + Send + Sync {
+    /// Returns whether given sequence should be returned by the decompressor.
+    fn accepts(&self, sequence: &FastqSequence) -> bool;
+}
+
 /// IDN decompression parameters that can be set by user.
 #[derive(Debug, Clone)]
 pub struct IdnDecompressorParams {
     pub(super) model_provider: ModelProvider,
     pub(super) progress_notifier: Arc<dyn ProgressNotifier>,
+    pub(super) progress_granularity: usize,
     pub(super) thread_num: usize,
+    pub(super) sequence_filter: Option<Arc<dyn SequenceFilter>>,
+    pub(super) read_ahead_blocks: usize,
+    pub(super) max_buffered_blocks: Option<usize>,
+    pub(super) member: Option<String>,
+    pub(super) limits: IdnDecompressorLimits,
+    pub(super) transforms: Vec<Arc<dyn SequenceTransform>>,
+    pub(super) warning_sink: Option<Arc<dyn WarningSink>>,
+    /// The registered transforms that have been applied to the sequences of
+    /// the file being decompressed, in the order their inverses should be
+    /// applied. Set when reading the file metadata.
+    pub(super) inverse_transforms: Vec<Arc<dyn SequenceTransform>>,
+    /// The quality alphabet the quality scores of the file being decompressed
+    /// are encoded with. Set when reading the file metadata.
+    pub(super) quality_alphabet: QualityAlphabet,
 }
 
 impl IdnDecompressorParams {
@@ -167,7 +429,15 @@ impl Default for IdnDecompressorParams {
 pub struct IdnDecompressorParamsBuilder {
     model_provider: ModelProvider,
     progress_notifier: Arc<dyn ProgressNotifier>,
+    progress_granularity: usize,
     thread_num: usize,
+    sequence_filter: Option<Arc<dyn SequenceFilter>>,
+    read_ahead_blocks: usize,
+    max_buffered_blocks: Option<usize>,
+    member: Option<String>,
+    limits: IdnDecompressorLimits,
+    transforms: Vec<Arc<dyn SequenceTransform>>,
+    warning_sink: Option<Arc<dyn WarningSink>>,
 }
 
 impl IdnDecompressorParamsBuilder {
@@ -184,30 +454,135 @@ impl IdnDecompressorParamsBuilder {
         Self {
             model_provider: ModelProvider::default(),
             progress_notifier: Arc::new(DummyProgressNotifier),
+            progress_granularity: DEFAULT_PROGRESS_GRANULARITY,
             thread_num: 0,
+            sequence_filter: None,
+            read_ahead_blocks: 0,
+            max_buffered_blocks: None,
+            member: None,
+            limits: IdnDecompressorLimits::default(),
+            transforms: Vec::new(),
+            warning_sink: None,
         }
     }
 
     /// Sets the model provider instance to be used for decompression.
-    pub fn model_provider(&mut self, model_provider: ModelProvider) -> &mut Self {
-        let mut new = self;
-        new.model_provider = model_provider;
-        new
+    #[must_use]
+    pub fn model_provider(mut self, model_provider: ModelProvider) -> Self {
+        self.model_provider = model_provider;
+        self
     }
 
     /// Sets the progress notifier instance.
-    pub fn progress_notifier(&mut self, progress_notifier: Arc<dyn ProgressNotifier>) -> &mut Self {
-        let mut new = self;
-        new.progress_notifier = progress_notifier;
-        new
+    #[must_use]
+    pub fn progress_notifier(mut self, progress_notifier: Arc<dyn ProgressNotifier>) -> Self {
+        self.progress_notifier = progress_notifier;
+        self
+    }
+
+    /// Sets the number of bytes of the compressed data each of the block
+    /// decompressors gathers before reporting them to the
+    /// [`ProgressNotifier`]. `0` reports every sequence. The default is 64 KiB.
+    #[must_use]
+    pub fn progress_granularity(mut self, bytes: usize) -> Self {
+        self.progress_granularity = bytes;
+        self
     }
 
     /// Sets the maximum number of additional CPU threads to be spawned during
     /// decompression.
-    pub fn thread_num(&mut self, thread_num: usize) -> &mut Self {
-        let mut new = self;
-        new.thread_num = thread_num;
-        new
+    #[must_use]
+    pub fn thread_num(mut self, thread_num: usize) -> Self {
+        self.thread_num = thread_num;
+        self
+    }
+
+    /// Sets the filter deciding which sequences should be returned by the
+    /// decompressor. All the sequences are returned if no filter is set.
+    #[must_use]
+    pub fn sequence_filter(mut self, sequence_filter: Arc<dyn SequenceFilter>) -> Self {
+        self.sequence_filter = Some(sequence_filter);
+        self
+    }
+
+    /// Sets the maximum number of blocks to be read from the input ahead of
+    /// the ones being decompressed.
+    ///
+    /// If non-zero, the input is read in a separate thread (which is not
+    /// included in [`Self::thread_num()`]), so that the I/O latency is
+    /// overlapped with decompression. This is useful for slow inputs, such as
+    /// network filesystems. Zero (the default) disables the read-ahead. The
+    /// read-ahead is not available if the `parallel` feature is disabled.
+    #[must_use]
+    pub fn read_ahead(mut self, blocks: usize) -> Self {
+        self.read_ahead_blocks = blocks;
+        self
+    }
+
+    /// Sets the maximum number of blocks that are decompressed, or being
+    /// decompressed, ahead of the sequences being returned. When the limit is
+    /// reached, no more blocks are decompressed until the already decompressed
+    /// sequences are retrieved, which caps the memory usage when the sequences
+    /// are consumed slower than they are decompressed (e.g. when they are
+    /// written to a network storage).
+    ///
+    /// By default, twice the number of threads (see [`Self::thread_num()`])
+    /// are buffered. At least one block is always buffered.
+    #[must_use]
+    pub fn max_buffered_blocks(mut self, blocks: usize) -> Self {
+        self.max_buffered_blocks = Some(blocks);
+        self
+    }
+
+    /// Sets the name of the container member to be decompressed. Only the
+    /// sequences belonging to this member are returned, and the decompression
+    /// fails with [`IdnDecompressorError::UnknownMember`] if the file does
+    /// not contain it. All the sequences are returned if no member is set.
+    #[must_use]
+    pub fn member<T: Into<String>>(mut self, name: T) -> Self {
+        self.member = Some(name.into());
+        self
+    }
+
+    /// Sets the limits of the input file structure. By default, there are no
+    /// limits, so [`IdnDecompressorLimits::untrusted()`] should be used when
+    /// decompressing files coming from untrusted sources.
+    #[must_use]
+    pub fn limits(mut self, limits: IdnDecompressorLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Registers a [`SequenceTransform`], so that its inverse is applied to
+    /// the decompressed sequences if the file has been compressed with it.
+    /// The transforms are matched by their identifiers. The built-in
+    /// lossless transforms, such as [`QualityDelta`], are registered by
+    /// default; a transform registered here takes precedence over the
+    /// built-in one with the same identifier.
+    ///
+    /// The decompression fails with [`IdnDecompressorError::UnknownTransform`]
+    /// if the file has been compressed with a lossless transform that has not
+    /// been registered. The lossy transforms do not have to be registered, but
+    /// a [`Warning::LossyTransform`] is emitted for each of them, as the
+    /// original sequences cannot be restored.
+    #[must_use]
+    pub fn transform(mut self, transform: Arc<dyn SequenceTransform>) -> Self {
+        self.transforms.push(transform);
+        self
+    }
+
+    /// Sets the [`WarningSink`] that receives the non-fatal issues
+    /// encountered during decompression, such as the file having been
+    /// compressed with a lossy transform. The warnings are logged regardless
+    /// of whether a sink is set.
+    #[must_use]
+    pub fn warning_sink(mut self, warning_sink: Arc<dyn WarningSink>) -> Self {
+        self.warning_sink = Some(warning_sink);
+        self
+    }
+
+    fn builtin_transforms() -> [Arc<dyn SequenceTransform>; 1] {
+        [Arc::new(QualityDelta)]
     }
 
     /// Builds the `IdnDecompressorParams`.
@@ -219,11 +594,25 @@ impl IdnDecompressorParamsBuilder {
     /// let _params: IdnDecompressorParams = IdnDecompressorParamsBuilder::new().build();
     /// ```
     #[must_use]
-    pub fn build(&mut self) -> IdnDecompressorParams {
+    pub fn build(self) -> IdnDecompressorParams {
         IdnDecompressorParams {
-            model_provider: self.model_provider.clone(),
-            progress_notifier: self.progress_notifier.clone(),
+            model_provider: self.model_provider,
+            progress_notifier: self.progress_notifier,
+            progress_granularity: self.progress_granularity,
             thread_num: self.thread_num,
+            sequence_filter: self.sequence_filter,
+            read_ahead_blocks: self.read_ahead_blocks,
+            max_buffered_blocks: self.max_buffered_blocks,
+            member: self.member,
+            limits: self.limits,
+            transforms: self
+                .transforms
+                .into_iter()
+                .chain(Self::builtin_transforms())
+                .collect(),
+            warning_sink: self.warning_sink,
+            inverse_transforms: Vec::new(),
+            quality_alphabet: QualityAlphabet::full(),
         }
     }
 }
@@ -234,27 +623,293 @@ impl Default for IdnDecompressorParamsBuilder {
     }
 }
 
+/// Hard limits of the IDN file structure, checked before the memory is
+/// allocated, so that a malicious file cannot make the decompressor allocate
+/// huge amounts of memory. Exceeding any of the limits makes the decompression
+/// fail with a corresponding [`IdnDecompressorError`].
+///
+/// # Examples
+/// ```
+/// use idencomp::idn::decompressor::{
+///     IdnDecompressor, IdnDecompressorError, IdnDecompressorLimits, IdnDecompressorParams,
+/// };
+///
+/// let mut data = b"IDENCOMP\x01\x00".to_vec();
+/// // Block header: length and sequence checksum
+/// data.extend([0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00]);
+///
+/// let limits = IdnDecompressorLimits::untrusted().max_block_len(1024);
+/// let params = IdnDecompressorParams::builder().limits(limits).build();
+/// let mut decompressor = IdnDecompressor::with_params(data.as_slice(), params);
+/// assert!(matches!(
+///     decompressor.next_sequence(),
+///     Err(IdnDecompressorError::BlockTooLarge(4294967295, 1024))
+/// ));
+/// ```
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct IdnDecompressorLimits {
+    pub(super) max_block_len: usize,
+    pub(super) max_identifiers_len: usize,
+    pub(super) max_metadata_items: usize,
+    pub(super) max_sequence_len: usize,
+}
+
+impl IdnDecompressorLimits {
+    /// Returns a new instance of `IdnDecompressorLimits` that does not limit
+    /// anything.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::idn::decompressor::IdnDecompressorLimits;
+    ///
+    /// let limits = IdnDecompressorLimits::new().max_block_len(1024 * 1024);
+    /// assert_ne!(limits, IdnDecompressorLimits::new());
+    /// ```
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            max_block_len: usize::MAX,
+            max_identifiers_len: usize::MAX,
+            max_metadata_items: usize::MAX,
+            max_sequence_len: usize::MAX,
+        }
+    }
+
+    /// Returns a new instance of `IdnDecompressorLimits` suitable for the
+    /// files coming from untrusted sources, such as user uploads. The limits
+    /// are high enough for the files created by the compressor, including the
+    /// ones in the long read mode.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::idn::decompressor::IdnDecompressorLimits;
+    ///
+    /// let limits = IdnDecompressorLimits::untrusted().max_metadata_items(4);
+    /// assert_ne!(limits, IdnDecompressorLimits::untrusted());
+    /// ```
+    #[must_use]
+    pub fn untrusted() -> Self {
+        Self {
+            max_block_len: UNTRUSTED_MAX_BLOCK_LEN,
+            max_identifiers_len: UNTRUSTED_MAX_IDENTIFIERS_LEN,
+            max_metadata_items: UNTRUSTED_MAX_METADATA_ITEMS,
+            max_sequence_len: UNTRUSTED_MAX_SEQUENCE_LEN,
+        }
+    }
+
+    /// Sets the maximum length of a single block, in bytes.
+    #[must_use]
+    pub fn max_block_len(mut self, max_block_len: usize) -> Self {
+        self.max_block_len = max_block_len;
+        self
+    }
+
+    /// Sets the maximum length of the identifier data in a single block, in
+    /// bytes. The limit applies to both the compressed and the decompressed
+    /// data, and to the auxiliary per-read data as well.
+    #[must_use]
+    pub fn max_identifiers_len(mut self, max_identifiers_len: usize) -> Self {
+        self.max_identifiers_len = max_identifiers_len;
+        self
+    }
+
+    /// Sets the maximum number of the metadata items in the file header.
+    #[must_use]
+    pub fn max_metadata_items(mut self, max_metadata_items: usize) -> Self {
+        self.max_metadata_items = max_metadata_items;
+        self
+    }
+
+    /// Sets the maximum length of a single sequence.
+    #[must_use]
+    pub fn max_sequence_len(mut self, max_sequence_len: usize) -> Self {
+        self.max_sequence_len = max_sequence_len;
+        self
+    }
+}
+
+impl Default for IdnDecompressorLimits {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The sequences decompressed from a single block, after filtering.
+#[derive(Debug)]
+pub(super) struct DecompressedBlock {
+    pub(super) sequences: Vec<IndexedSequence>,
+    /// Whether this is the end-of-file block.
+    pub(super) is_last: bool,
+    /// The name of the container member starting with this block, if any.
+    pub(super) member: Option<String>,
+}
+
+/// The number of sequences and symbols decoded with a single model, as
+/// returned by [`IdnDecompressor::model_stats()`].
+///
+/// Only the sequences actually decoded with the models are counted, i.e. the
+/// duplicate, packed, raw and empty sequences are not included. A sequence
+/// whose models are switched in the middle is counted once for each model
+/// used.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ModelDecodeStats {
+    identifier: ModelIdentifier,
+    model_type: ModelType,
+    sequences: u64,
+    symbols: u64,
+}
+
+impl ModelDecodeStats {
+    #[must_use]
+    pub(super) fn new(identifier: ModelIdentifier, model_type: ModelType) -> Self {
+        Self {
+            identifier,
+            model_type,
+            sequences: 0,
+            symbols: 0,
+        }
+    }
+
+    pub(super) fn add(&mut self, sequences: u64, symbols: u64) {
+        self.sequences += sequences;
+        self.symbols += symbols;
+    }
+
+    /// Returns the identifier of the model.
+    #[must_use]
+    pub fn identifier(&self) -> &ModelIdentifier {
+        &self.identifier
+    }
+
+    /// Returns the type of the model.
+    #[must_use]
+    pub fn model_type(&self) -> ModelType {
+        self.model_type
+    }
+
+    /// Returns the number of sequences (or their parts) decoded with the
+    /// model.
+    #[must_use]
+    pub fn sequences(&self) -> u64 {
+        self.sequences
+    }
+
+    /// Returns the number of symbols (acids or quality scores, depending on
+    /// the model type) decoded with the model.
+    #[must_use]
+    pub fn symbols(&self) -> u64 {
+        self.symbols
+    }
+}
+
 #[derive(Debug)]
 pub(super) struct IdnDecompressorOutState {
-    data_queue: DataQueue<FastqSequence>,
-    block_lock: IdnBlockLock,
+    blocks: OrderedQueue<DecompressedBlock>,
+    aborted: AtomicBool,
+    scratch_pool: ObjectPool<BlockScratch>,
+    model_stats: Mutex<BTreeMap<ModelIdentifier, ModelDecodeStats>>,
+    transforms: Mutex<Vec<TransformInfo>>,
+    source_digest: Mutex<Option<SourceDigest>>,
 }
 
 impl IdnDecompressorOutState {
     #[must_use]
-    pub fn new() -> Self {
+    pub fn new(max_buffered_blocks: usize) -> Self {
         Self {
-            data_queue: DataQueue::new(),
-            block_lock: IdnBlockLock::new(),
+            blocks: OrderedQueue::new(max_buffered_blocks.max(1)),
+            aborted: AtomicBool::new(false),
+            scratch_pool: ObjectPool::new(),
+            model_stats: Mutex::new(BTreeMap::new()),
+            transforms: Mutex::new(Vec::new()),
+            source_digest: Mutex::new(None),
+        }
+    }
+
+    /// Requests the reader and the block decompressors to stop as soon as
+    /// possible.
+    pub fn abort(&self) {
+        self.aborted.store(true, Ordering::Relaxed);
+        self.blocks.set_finished();
+    }
+
+    #[must_use]
+    pub fn is_aborted(&self) -> bool {
+        self.aborted.load(Ordering::Relaxed)
+    }
+
+    /// Returns the queue of the decompressed blocks. The queue is finished
+    /// when the decompression fails or is aborted, so that neither the reader
+    /// nor the consumer wait for the blocks that are never going to come.
+    pub fn blocks(&self) -> &OrderedQueue<DecompressedBlock> {
+        &self.blocks
+    }
+
+    /// Returns the pool of the buffers used by the block decompressors. There
+    /// are at most as many of them as the blocks decompressed at once, so the
+    /// buffers are effectively kept per worker thread.
+    pub fn scratch_pool(&self) -> &ObjectPool<BlockScratch> {
+        &self.scratch_pool
+    }
+
+    /// Adds the statistics of the models used by a single block.
+    pub fn add_model_stats<I>(&self, block_stats: I)
+    where
+        I: IntoIterator<Item = ModelDecodeStats>,
+    {
+        let mut model_stats = self
+            .model_stats
+            .lock()
+            .expect("Could not acquire model stats lock");
+
+        for stats in block_stats {
+            model_stats
+                .entry(stats.identifier.clone())
+                .or_insert_with(|| {
+                    ModelDecodeStats::new(stats.identifier.clone(), stats.model_type)
+                })
+                .add(stats.sequences, stats.symbols);
         }
     }
 
-    pub fn data_queue(&self) -> &DataQueue<FastqSequence> {
-        &self.data_queue
+    #[must_use]
+    pub fn model_stats(&self) -> Vec<ModelDecodeStats> {
+        self.model_stats
+            .lock()
+            .expect("Could not acquire model stats lock")
+            .values()
+            .cloned()
+            .collect()
+    }
+
+    /// Adds the transforms read from the file metadata.
+    pub fn add_transforms(&self, transforms: &[TransformInfo]) {
+        self.transforms
+            .lock()
+            .expect("Could not acquire transforms lock")
+            .extend_from_slice(transforms);
+    }
+
+    #[must_use]
+    pub fn transforms(&self) -> Vec<TransformInfo> {
+        self.transforms
+            .lock()
+            .expect("Could not acquire transforms lock")
+            .clone()
+    }
+
+    pub fn set_source_digest(&self, source_digest: SourceDigest) {
+        *self
+            .source_digest
+            .lock()
+            .expect("Could not acquire source digest lock") = Some(source_digest);
     }
 
-    pub fn block_lock(&self) -> &IdnBlockLock {
-        &self.block_lock
+    #[must_use]
+    pub fn source_digest(&self) -> Option<SourceDigest> {
+        *self
+            .source_digest
+            .lock()
+            .expect("Could not acquire source digest lock")
     }
 }
 
@@ -271,18 +926,131 @@ impl IdnDecompressorState {
     }
 }
 
+/// A block that has been read from the file, but not decompressed yet.
+#[derive(Debug)]
+struct RawBlock {
+    header: IdnBlockHeader,
+    data: Vec<u8>,
+}
+
+impl RawBlock {
+    /// Reads the next block. Once the end-of-file block is reached, the
+    /// trailing metadata placed after it is read as well, and the source
+    /// digest stored there (if any) is passed to `out_state`.
+    fn read<R: Read>(
+        reader: &mut NoSeek<R>,
+        limits: &IdnDecompressorLimits,
+        out_state: &IdnDecompressorOutState,
+    ) -> IdnDecompressResult<Self> {
+        trace!("Reading next block");
+        let header = IdnBlockHeader::read(reader)?;
+        let data_len = header.length as usize;
+        trace!("Reading block with length {}", data_len);
+        if data_len == 0 {
+            // The trailing metadata is not needed to decompress the
+            // sequences, so a damaged one does not make the file unreadable
+            match read_trailing_metadata(reader) {
+                Ok(trailing) => {
+                    if let Some(source_digest) = trailing.source_digest {
+                        out_state.set_source_digest(source_digest);
+                    }
+                }
+                Err(e) => debug!("Could not read the trailing metadata: {}", e),
+            }
+        }
+        if data_len > limits.max_block_len {
+            return Err(IdnDecompressorError::block_too_large(
+                data_len,
+                limits.max_block_len,
+            ));
+        }
+
+        let mut data = vec![0; data_len];
+        reader.read_exact(&mut data)?;
+
+        Ok(Self { header, data })
+    }
+
+    /// Returns an empty end-of-file block.
+    #[must_use]
+    fn end_of_file() -> Self {
+        Self {
+            header: IdnBlockHeader {
+                length: 0,
+                seq_checksum: crc32fast::Hasher::new().finalize(),
+            },
+            data: Vec::new(),
+        }
+    }
+
+    #[must_use]
+    fn is_last(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Returns the name of the container member starting with this block, if
+    /// any.
+    fn member_name(&self) -> IdnDecompressResult<Option<String>> {
+        if self.is_last() {
+            return Ok(None);
+        }
+
+        let header = IdnSliceHeader::read(&mut Cursor::new(self.data.as_slice()))?;
+        match header {
+            IdnSliceHeader::Member(header) => Ok(Some(String::from_utf8(header.name)?)),
+            _ => Ok(None),
+        }
+    }
+}
+
+/// The background job reading the blocks ahead of the ones being
+/// decompressed.
+#[derive(Debug)]
+struct ReadAhead {
+    receiver: Option<Receiver<IdnDecompressResult<RawBlock>>>,
+    thread_pool: ThreadPool<IdnDecompressorError>,
+}
+
+impl ReadAhead {
+    /// Returns the next block read, or `None` if the reading has been stopped
+    /// because the decompression was aborted.
+    fn next_block(&self) -> IdnDecompressResult<Option<RawBlock>> {
+        let receiver = self
+            .receiver
+            .as_ref()
+            .expect("Read-ahead receiver already dropped");
+
+        receiver.recv().ok().transpose()
+    }
+}
+
+impl Drop for ReadAhead {
+    fn drop(&mut self) {
+        // Dropping the receiver makes the job stop if it is waiting for the
+        // space in the channel
+        self.receiver = None;
+        // The job does not return any errors; they are sent over the channel
+        let _ = self.thread_pool.join();
+    }
+}
+
 #[derive(Debug)]
 struct IdnDecompressorInner<R> {
-    reader: NoSeek<R>,
+    reader: Option<NoSeek<R>>,
     options: Arc<IdnDecompressorParams>,
     out_state: Arc<IdnDecompressorOutState>,
     thread_pool: ThreadPool<IdnDecompressorError>,
+    read_ahead: Option<ReadAhead>,
 
     state: IdnDecompressorState,
     current_block: u32,
+    /// The index of the next block in the file, which, unlike
+    /// `current_block`, also counts the blocks of the skipped members.
+    file_block: usize,
+    member_found: bool,
 }
 
-impl<R: Read> IdnDecompressorInner<R> {
+impl<R: Read + Send> IdnDecompressorInner<R> {
     #[must_use]
     fn new(
         reader: R,
@@ -291,13 +1059,16 @@ impl<R: Read> IdnDecompressorInner<R> {
         thread_pool: ThreadPool<IdnDecompressorError>,
     ) -> Self {
         Self {
-            reader: NoSeek::new(reader),
+            reader: Some(NoSeek::new(reader)),
             options: Arc::new(params),
             out_state: state,
             thread_pool,
+            read_ahead: None,
 
             state: IdnDecompressorState::Uninitialized,
             current_block: 0,
+            file_block: 0,
+            member_found: false,
         }
     }
 
@@ -306,13 +1077,56 @@ impl<R: Read> IdnDecompressorInner<R> {
 
         self.read_header()?;
         self.read_metadata()?;
+        self.start_read_ahead();
         self.state = IdnDecompressorState::Reading;
 
         Ok(())
     }
 
+    fn reader(&mut self) -> &mut NoSeek<R> {
+        self.reader
+            .as_mut()
+            .expect("Reader moved to the read-ahead job")
+    }
+
+    fn start_read_ahead(&mut self) {
+        let depth = self.options.read_ahead_blocks;
+        if depth == 0 {
+            return;
+        }
+
+        let thread_pool = ThreadPool::new(1, "idn-read-ahead");
+        if thread_pool.is_foreground() {
+            debug!("Threads not available, read-ahead disabled");
+            return;
+        }
+
+        debug!("Starting read-ahead of {} blocks", depth);
+        let mut reader = self.reader.take().expect("Reader already taken");
+        let out_state = self.out_state.clone();
+        let limits = self.options.limits;
+        let (sender, receiver) = crossbeam_channel::bounded(depth);
+        thread_pool.spawn(move || {
+            while !out_state.is_aborted() {
+                let block = RawBlock::read(&mut reader, &limits, &out_state);
+                let is_last = !matches!(&block, Ok(block) if !block.is_last());
+
+                if sender.send(block).is_err() || is_last {
+                    break;
+                }
+            }
+
+            Ok(())
+        });
+
+        self.read_ahead = Some(ReadAhead {
+            receiver: Some(receiver),
+            thread_pool,
+        });
+    }
+
     fn read_header(&mut self) -> IdnDecompressResult<()> {
-        let header = IdnHeader::read(&mut self.reader)?;
+        let header = IdnHeader::read(self.reader())?;
         debug!("Read IDN header: {:?}", header);
         if header.version != 1 {
             return Err(IdnDecompressorError::InvalidVersion(header.version));
@@ -322,13 +1136,20 @@ impl<R: Read> IdnDecompressorInner<R> {
     }
 
     fn read_metadata(&mut self) -> IdnDecompressResult<()> {
-        let header = IdnMetadataHeader::read(&mut self.reader)?;
+        let header = IdnMetadataHeader::read(self.reader())?;
         debug!("Read metadata header: {:?}", header);
+        let max_items = self.options.limits.max_metadata_items;
+        if header.item_num as usize > max_items {
+            return Err(IdnDecompressorError::too_many_metadata_items(
+                header.item_num as usize,
+                max_items,
+            ));
+        }
         for _ in 0..header.item_num {
             self.read_metadata_item()?;
         }
 
-        let bytes_read = self.reader.position();
+        let bytes_read = self.reader().position();
         self.options
             .progress_notifier
             .processed_bytes(ByteNum::new(bytes_read as usize));
@@ -337,17 +1158,78 @@ impl<R: Read> IdnDecompressorInner<R> {
     }
 
     fn read_metadata_item(&mut self) -> IdnDecompressResult<()> {
-        let item: IdnMetadataItem = IdnMetadataItem::read(&mut self.reader)?;
+        let item: IdnMetadataItem = IdnMetadataItem::read_forward(self.reader())?;
         debug!("Read metadata item: {:?}", item);
         match item {
             IdnMetadataItem::Models(models_metadata) => {
                 self.handle_models_metadata(models_metadata)?
             }
+            IdnMetadataItem::SourceDigest(metadata) => self
+                .out_state
+                .set_source_digest(SourceDigest::from(metadata.sha256)),
+            IdnMetadataItem::MemberIndex(_) => {}
+            IdnMetadataItem::Transforms(transforms_metadata) => {
+                let transforms: Vec<_> = transforms_metadata
+                    .transforms
+                    .iter()
+                    .map(TransformInfo::from_metadata)
+                    .collect();
+                self.handle_transforms_metadata(&transforms)?;
+                self.out_state.add_transforms(&transforms);
+            }
+            IdnMetadataItem::QualityAlphabet(alphabet_metadata) => {
+                let alphabet = read_quality_alphabet(&alphabet_metadata)?;
+                self.handle_quality_alphabet_metadata(alphabet);
+            }
         }
 
         Ok(())
     }
 
+    fn handle_transforms_metadata(
+        &mut self,
+        transforms: &[TransformInfo],
+    ) -> IdnDecompressResult<()> {
+        let options =
+            Arc::get_mut(&mut self.options).expect("IdnReaderOptions unexpectedly cloned");
+
+        let mut inverse_transforms = Vec::new();
+        for info in transforms {
+            if info.is_lossy() {
+                warning::emit(
+                    options.warning_sink.as_deref(),
+                    Warning::LossyTransform {
+                        identifier: info.identifier().to_owned(),
+                    },
+                );
+            }
+
+            let transform = options
+                .transforms
+                .iter()
+                .find(|transform| transform.identifier() == info.identifier());
+            match transform {
+                Some(transform) => inverse_transforms.push(transform.clone()),
+                None if info.is_lossy() => {}
+                None => return Err(IdnDecompressorError::unknown_transform(info.identifier())),
+            }
+        }
+        inverse_transforms.reverse();
+        options.inverse_transforms.extend(inverse_transforms);
+
+        Ok(())
+    }
+
+    fn handle_quality_alphabet_metadata(&mut self, alphabet: QualityAlphabet) {
+        let options =
+            Arc::get_mut(&mut self.options).expect("IdnReaderOptions unexpectedly cloned");
+
+        debug!("Quality alphabet: {}", alphabet);
+        let model_provider = mem::replace(&mut options.model_provider, ModelProvider::new(vec![]));
+        options.model_provider = model_provider.with_quality_alphabet(&alphabet);
+        options.quality_alphabet = alphabet;
+    }
+
     fn handle_models_metadata(
         &mut self,
         models_metadata: IdnModelsMetadata,
@@ -364,26 +1246,35 @@ impl<R: Read> IdnDecompressorInner<R> {
             .has_all_models(&identifiers)
             .map_err(IdnDecompressorError::unknown_model)?;
         options.model_provider.filter_by_identifiers(&identifiers);
+        for index in 0..options.model_provider.len() {
+            options.model_provider[index].verify_identifier()?;
+        }
         debug!("Model identifiers:");
         for (index, identifier) in identifiers.iter().enumerate() {
             debug!("[{}] {}", index, identifier);
         }
-        options.model_provider.preprocess_decompressor_models();
+        options
+            .model_provider
+            .preprocess_decompressor_models_with_progress(options.progress_notifier.as_ref());
+        options
+            .progress_notifier
+            .start_stage(ProgressStage::Decompressing, None);
 
         Ok(())
     }
 
     fn read_all(&mut self) -> IdnDecompressResult<()> {
-        while self.state.not_finished() {
+        while self.state.not_finished() && !self.out_state.is_aborted() {
             let result = self.read_next_block();
             if result.is_err() {
-                self.out_state.data_queue.set_finished();
+                self.out_state.blocks().set_finished();
             }
             result?;
         }
         Ok(())
     }
 
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, fields(block = self.current_block)))]
     fn read_next_block(&mut self) -> IdnDecompressResult<()> {
         match self.state {
             IdnDecompressorState::Uninitialized => self.initialize()?,
@@ -391,15 +1282,38 @@ impl<R: Read> IdnDecompressorInner<R> {
             IdnDecompressorState::LastBlockReached => return Ok(()),
         }
 
-        trace!("Reading next block");
-        let header = IdnBlockHeader::read(&mut self.reader)?;
-        let data_len = header.length as usize;
-        trace!("Reading block with length {}", data_len);
+        let block = match &self.read_ahead {
+            Some(read_ahead) => read_ahead.next_block()?,
+            None => {
+                let limits = self.options.limits;
+                let out_state = self.out_state.clone();
+                Some(RawBlock::read(self.reader(), &limits, &out_state)?)
+            }
+        };
+        let block = match block {
+            Some(block) => block,
+            None if self.out_state.is_aborted() => {
+                self.state = IdnDecompressorState::LastBlockReached;
+                return Ok(());
+            }
+            None => return Err(IdnDecompressorError::InvalidState),
+        };
+        let file_block = self.file_block;
+        self.file_block += 1;
+        let block = match self.select_member_block(block)? {
+            Some(block) => block,
+            None => return Ok(()),
+        };
+        let is_last = block.is_last();
+        // Do not decompress the blocks too far ahead of the ones the
+        // sequences are being retrieved from
+        if !self.out_state.blocks().wait_for_room(self.current_block) {
+            self.state = IdnDecompressorState::LastBlockReached;
+            return Ok(());
+        }
 
         {
-            let mut data = vec![0; data_len];
-            self.reader.read_exact(&mut data)?;
-
+            let RawBlock { header, data } = block;
             let current_block = self.current_block;
             let out_state = self.out_state.clone();
             let seq_checksum = header.seq_checksum;
@@ -408,6 +1322,7 @@ impl<R: Read> IdnDecompressorInner<R> {
             self.thread_pool.execute(move || {
                 let block = IdnBlockDecompressor::new(
                     current_block,
+                    file_block,
                     data,
                     out_state,
                     seq_checksum,
@@ -419,23 +1334,56 @@ impl<R: Read> IdnDecompressorInner<R> {
         }
 
         self.current_block += 1;
-        if data_len == 0 {
+        if is_last {
             self.state = IdnDecompressorState::LastBlockReached;
             debug!("End of file block reached");
         }
 
         Ok(())
     }
+
+    /// Returns the block if it should be decompressed, i.e. it belongs to the
+    /// requested container member, or `None` if it should be skipped. Once the
+    /// member has ended, an end-of-file block is returned, so that the rest of
+    /// the file is not read at all.
+    fn select_member_block(&mut self, block: RawBlock) -> IdnDecompressResult<Option<RawBlock>> {
+        let name = match &self.options.member {
+            Some(name) => name,
+            None => return Ok(Some(block)),
+        };
+
+        if block.is_last() {
+            if !self.member_found {
+                return Err(IdnDecompressorError::unknown_member(name.as_str()));
+            }
+            return Ok(Some(block));
+        }
+
+        if let Some(member) = block.member_name()? {
+            if self.member_found {
+                debug!("End of member `{}` reached", name);
+                return Ok(Some(RawBlock::end_of_file()));
+            }
+            self.member_found = member == *name;
+        }
+
+        if self.member_found {
+            Ok(Some(block))
+        } else {
+            Ok(None)
+        }
+    }
 }
 
 /// IDN file format decompressor.
 #[derive(Debug)]
 pub struct IdnDecompressor<R> {
     out_state: Arc<IdnDecompressorOutState>,
-    start_time: Instant,
+    start_time: Option<Instant>,
     bytes_decompressed: ByteNum,
     thread_pool: ThreadPool<IdnDecompressorError>,
-    sequences_to_get: Vec<FastqSequence>,
+    sequences_to_get: Vec<IndexedSequence>,
+    started_members: Vec<String>,
     eof_reached: bool,
     inner: Option<IdnDecompressorInner<R>>,
 }
@@ -469,9 +1417,18 @@ impl<R: Read + Send> IdnDecompressor<R> {
     /// ```
     #[must_use]
     pub fn with_params(reader: R, params: IdnDecompressorParams) -> Self {
-        let start_time = Instant::now();
-        let out_state = Arc::new(IdnDecompressorOutState::new());
-        let thread_pool = ThreadPool::new(params.thread_num, "idn-decompressor");
+        let start_time = start_time();
+        let max_buffered_blocks = params
+            .max_buffered_blocks
+            .unwrap_or(2 * params.thread_num.max(1));
+        let out_state = Arc::new(IdnDecompressorOutState::new(max_buffered_blocks));
+        let thread_pool = {
+            let out_state = out_state.clone();
+            // The consumer would otherwise wait forever for the blocks of the
+            // job that has panicked
+            ThreadPool::new(params.thread_num, "idn-decompressor")
+                .on_panic(move || out_state.abort())
+        };
 
         let inner =
             IdnDecompressorInner::new(reader, params, out_state.clone(), thread_pool.make_child());
@@ -480,12 +1437,10 @@ impl<R: Read + Send> IdnDecompressor<R> {
             Some(inner)
         } else {
             let mut inner = inner;
-            thread_pool
-                .execute(move || {
-                    inner.read_all()?;
-                    Ok(())
-                })
-                .expect("Unexpected Thread Pool error");
+            thread_pool.spawn(move || {
+                inner.read_all()?;
+                Ok(())
+            });
 
             None
         };
@@ -496,37 +1451,110 @@ impl<R: Read + Send> IdnDecompressor<R> {
             bytes_decompressed: ByteNum::ZERO,
             thread_pool,
             sequences_to_get: Vec::new(),
+            started_members: Vec::new(),
             eof_reached: false,
             inner,
         }
     }
 
+    /// Creates a new `IdnDecompressor` instance returning only the sequences
+    /// of the container member with given name.
+    ///
+    /// This is a shorthand for
+    /// [`IdnDecompressorParamsBuilder::member()`]; use that to combine it
+    /// with the other parameters.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::fastq::reader::FastqReader;
+    /// use idencomp::idn::compressor::IdnCompressor;
+    /// use idencomp::idn::decompressor::IdnDecompressor;
+    ///
+    /// let mut data = Vec::new();
+    /// let mut compressor = IdnCompressor::new(&mut data);
+    /// compressor.start_member("first.fastq")?;
+    /// for sequence in FastqReader::new(b"@SEQ1\nACGT\n+\n!!!!\n".as_slice()) {
+    ///     compressor.add_sequence(sequence?)?;
+    /// }
+    /// compressor.start_member("second.fastq")?;
+    /// for sequence in FastqReader::new(b"@SEQ2\nTGCA\n+\n!!!!\n".as_slice()) {
+    ///     compressor.add_sequence(sequence?)?;
+    /// }
+    /// compressor.finish()?;
+    ///
+    /// let mut decompressor = IdnDecompressor::extract(data.as_slice(), "second.fastq");
+    /// let sequence = decompressor.next_sequence()?.unwrap();
+    /// assert_eq!(sequence.identifier().str(), "SEQ2");
+    /// assert!(decompressor.next_sequence()?.is_none());
+    ///
+    /// # Ok::<(), idencomp::Error>(())
+    /// ```
+    #[must_use]
+    pub fn extract<T: Into<String>>(reader: R, name: T) -> Self {
+        Self::with_params(
+            reader,
+            IdnDecompressorParams::builder().member(name).build(),
+        )
+    }
+
+    /// Returns the list of the container members stored in given IDN file.
+    /// The list is empty if the file is not a container.
+    ///
+    /// This only reads the structure of the file (see
+    /// [`inspect()`](crate::idn::inspector::inspect)), without decompressing
+    /// any of the sequences.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::idn::compressor::IdnCompressor;
+    /// use idencomp::idn::decompressor::IdnDecompressor;
+    ///
+    /// let mut data = Vec::new();
+    /// let mut compressor = IdnCompressor::new(&mut data);
+    /// compressor.start_member("empty.fastq")?;
+    /// compressor.finish()?;
+    ///
+    /// let members = IdnDecompressor::members(data.as_slice())?;
+    /// assert_eq!(members.len(), 1);
+    /// assert_eq!(members[0].name(), "empty.fastq");
+    /// assert_eq!(members[0].sequence_num(), 0);
+    ///
+    /// # Ok::<(), idencomp::Error>(())
+    /// ```
+    pub fn members(reader: R) -> IdnDecompressResult<Vec<IdnMember>> {
+        Ok(inspect(reader)?.members())
+    }
+
     /// Reads and returns next sequence in the file. Returns `Ok(None)` if the
     /// end of file has been reached.
     pub fn next_sequence(&mut self) -> IdnDecompressResult<Option<FastqSequence>> {
+        Ok(self
+            .next_indexed_sequence()?
+            .map(|(_, _, sequence)| sequence))
+    }
+
+    /// Reads and returns next sequence in the file along with its position,
+    /// as described in [`IndexedSequence`]. Returns `Ok(None)` if the end of
+    /// file has been reached.
+    pub fn next_indexed_sequence(&mut self) -> IdnDecompressResult<Option<IndexedSequence>> {
         if self.eof_reached {
             return Ok(None);
         }
 
-        let result = self.next_sequence_internal();
+        let result = self.next_indexed_sequence_internal();
 
-        if let Ok(Some(seq)) = &result {
+        if let Ok(Some((_, _, seq))) = &result {
             self.bytes_decompressed += seq.size();
         } else {
-            self.eof_reached = true;
-            self.thread_pool.join()?;
+            self.finish_jobs(&result)?;
         }
 
         result
     }
 
-    fn next_sequence_internal(&mut self) -> IdnDecompressResult<Option<FastqSequence>> {
+    fn next_indexed_sequence_internal(&mut self) -> IdnDecompressResult<Option<IndexedSequence>> {
         if self.sequences_to_get.is_empty() {
-            if let Some(inner) = self.inner.as_mut() {
-                inner.read_next_block()?;
-            }
-
-            self.sequences_to_get = self.out_state.data_queue.retrieve_all();
+            self.sequences_to_get = self.retrieve_sequences()?;
             if self.sequences_to_get.is_empty() {
                 return Ok(None);
             }
@@ -535,6 +1563,313 @@ impl<R: Read + Send> IdnDecompressor<R> {
 
         Ok(Some(self.sequences_to_get.pop().unwrap()))
     }
+
+    /// Reads and returns the next batch of sequences in the file. Returns
+    /// `Ok(None)` if the end of file has been reached.
+    ///
+    /// A batch consists of the sequences of a single IDN block (the ones
+    /// left after filtering, if a [`SequenceFilter`] is set). The
+    /// returned [`Vec`] can be processed in parallel, e.g. with `rayon`'s
+    /// `into_par_iter()`, while the next batch is being decompressed in the
+    /// background.
+    ///
+    /// This can be freely mixed with [`Self::next_sequence()`]; the sequences
+    /// are always returned in the order they appear in the file.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::fastq::reader::FastqReader;
+    /// use idencomp::idn::compressor::IdnCompressor;
+    /// use idencomp::idn::decompressor::IdnDecompressor;
+    /// use rayon::prelude::*;
+    ///
+    /// let mut data = Vec::new();
+    /// let mut compressor = IdnCompressor::new(&mut data);
+    /// let fastq = b"@SEQ1\nACGT\n+\n!!!!\n@SEQ2\nTGCA\n+\n!!!!\n";
+    /// for sequence in FastqReader::new(fastq.as_slice()) {
+    ///     compressor.add_sequence(sequence?)?;
+    /// }
+    /// compressor.finish()?;
+    ///
+    /// let mut decompressor = IdnDecompressor::new(data.as_slice());
+    /// let mut total_len = 0;
+    /// while let Some(batch) = decompressor.next_batch()? {
+    ///     total_len += batch.into_par_iter().map(|seq| seq.len()).sum::<usize>();
+    /// }
+    /// assert_eq!(total_len, 8);
+    ///
+    /// # Ok::<(), idencomp::Error>(())
+    /// ```
+    pub fn next_batch(&mut self) -> IdnDecompressResult<Option<Vec<FastqSequence>>> {
+        if self.eof_reached {
+            return Ok(None);
+        }
+
+        let result = self.next_batch_internal();
+
+        if let Ok(Some(batch)) = &result {
+            for (_, _, seq) in batch {
+                self.bytes_decompressed += seq.size();
+            }
+        } else {
+            self.finish_jobs(&result)?;
+        }
+
+        Ok(result?.map(|batch| batch.into_iter().map(|(_, _, sequence)| sequence).collect()))
+    }
+
+    fn next_batch_internal(&mut self) -> IdnDecompressResult<Option<Vec<IndexedSequence>>> {
+        if !self.sequences_to_get.is_empty() {
+            let mut batch = mem::take(&mut self.sequences_to_get);
+            batch.reverse();
+            return Ok(Some(batch));
+        }
+
+        let batch = self.retrieve_sequences()?;
+        if batch.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(batch))
+        }
+    }
+
+    /// Returns the sequences of the next block that has any sequences left
+    /// after filtering, or an empty [`Vec`] if the end of file has been
+    /// reached.
+    fn retrieve_sequences(&mut self) -> IdnDecompressResult<Vec<IndexedSequence>> {
+        loop {
+            let mut block = match self.inner.as_mut() {
+                Some(inner) => match self.out_state.blocks().try_take_next() {
+                    Some(block) => Some(block),
+                    None if inner.state.not_finished() => {
+                        inner.read_next_block()?;
+                        continue;
+                    }
+                    None => None,
+                },
+                None => self.out_state.blocks().take_next(),
+            };
+
+            if let Some(block) = &mut block {
+                self.started_members.extend(block.member.take());
+            }
+            match block {
+                Some(block) if block.is_last => {
+                    self.out_state.blocks().set_finished();
+                    return Ok(block.sequences);
+                }
+                // Blocks with all the sequences filtered out are skipped
+                Some(block) if block.sequences.is_empty() => {}
+                Some(block) => return Ok(block.sequences),
+                None => return Ok(Vec::new()),
+            }
+        }
+    }
+
+    /// Converts this decompressor into an iterator over batches of sequences,
+    /// as returned by [`Self::next_batch()`].
+    #[must_use]
+    pub fn into_batches(self) -> IdnDecompressorBatches<R> {
+        IdnDecompressorBatches { decompressor: self }
+    }
+
+    /// Converts this decompressor into an iterator over the sequences along
+    /// with their positions in the file, as described in [`IndexedSequence`].
+    /// This can be used to build external indexes that refer back to the
+    /// blocks of the file.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::fastq::reader::FastqReader;
+    /// use idencomp::idn::compressor::{IdnCompressor, IdnCompressorParams};
+    /// use idencomp::idn::decompressor::IdnDecompressor;
+    /// use idencomp::idn::inspector::inspect;
+    ///
+    /// let mut data = Vec::new();
+    /// let params = IdnCompressorParams::builder().max_block_reads(2).build()?;
+    /// let mut compressor = IdnCompressor::with_params(&mut data, params);
+    /// let fastq = b"@SEQ1\nACGT\n+\n!!!!\n@SEQ2\nTGCA\n+\n!!!!\n@SEQ3\nGG\n+\n!!\n";
+    /// for sequence in FastqReader::new(fastq.as_slice()) {
+    ///     compressor.add_sequence(sequence?)?;
+    /// }
+    /// compressor.finish()?;
+    ///
+    /// let positions = IdnDecompressor::new(data.as_slice())
+    ///     .indexed_sequences()
+    ///     .map(|result| {
+    ///         result.map(|(block_index, read_index, sequence)| {
+    ///             (block_index, read_index, sequence.identifier().to_string())
+    ///         })
+    ///     })
+    ///     .collect::<Result<Vec<_>, _>>()?;
+    /// assert_eq!(
+    ///     positions,
+    ///     [
+    ///         (0, 0, "SEQ1".to_owned()),
+    ///         (0, 1, "SEQ2".to_owned()),
+    ///         (1, 0, "SEQ3".to_owned()),
+    ///     ]
+    /// );
+    ///
+    /// let file_info = inspect(data.as_slice())?;
+    /// assert_eq!(file_info.blocks()[1].sequence_num(), 1);
+    ///
+    /// # Ok::<(), idencomp::Error>(())
+    /// ```
+    #[must_use]
+    pub fn indexed_sequences(self) -> IdnDecompressorIndexedSequences<R> {
+        IdnDecompressorIndexedSequences { decompressor: self }
+    }
+
+    /// Stops the decompression and waits for all the background threads to
+    /// finish. This can be used to stop reading the file before its end is
+    /// reached.
+    ///
+    /// Returns an error if any of the background threads failed.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::fastq::reader::FastqReader;
+    /// use idencomp::idn::compressor::IdnCompressor;
+    /// use idencomp::idn::decompressor::IdnDecompressor;
+    ///
+    /// let mut data = Vec::new();
+    /// let mut compressor = IdnCompressor::new(&mut data);
+    /// let fastq = b"@SEQ1\nACGT\n+\n!!!!\n@SEQ2\nTGCA\n+\n!!!!\n";
+    /// for sequence in FastqReader::new(fastq.as_slice()) {
+    ///     compressor.add_sequence(sequence?)?;
+    /// }
+    /// compressor.finish()?;
+    ///
+    /// let mut decompressor = IdnDecompressor::new(data.as_slice());
+    /// let sequence = decompressor.next_sequence()?;
+    /// assert!(sequence.is_some());
+    /// decompressor.close()?;
+    ///
+    /// # Ok::<(), idencomp::Error>(())
+    /// ```
+    pub fn close(mut self) -> IdnDecompressResult<()> {
+        self.abort()
+    }
+
+    /// Returns the number of sequences and symbols decoded with each of the
+    /// models so far, ordered by the model identifier. Only the models that
+    /// have been used are included, so once the end of file is reached, the
+    /// models missing from the result are not needed to decompress the file.
+    ///
+    /// The blocks are decompressed ahead of the sequences returned, so before
+    /// the end of file is reached, the result may include the blocks whose
+    /// sequences have not been returned yet.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::fastq::reader::FastqReader;
+    /// use idencomp::idn::compressor::IdnCompressor;
+    /// use idencomp::idn::decompressor::IdnDecompressor;
+    ///
+    /// let mut data = Vec::new();
+    /// let mut compressor = IdnCompressor::new(&mut data);
+    /// for sequence in FastqReader::new(b"@SEQ1\nACGT\n+\n!!!!\n".as_slice()) {
+    ///     compressor.add_sequence(sequence?)?;
+    /// }
+    /// compressor.finish()?;
+    ///
+    /// let mut decompressor = IdnDecompressor::new(data.as_slice());
+    /// while decompressor.next_sequence()?.is_some() {}
+    ///
+    /// let models = decompressor.model_stats();
+    /// assert_eq!(models.len(), 2);
+    /// assert!(models.iter().all(|model| model.symbols() == 4));
+    ///
+    /// # Ok::<(), idencomp::Error>(())
+    /// ```
+    #[must_use]
+    pub fn model_stats(&self) -> Vec<ModelDecodeStats> {
+        self.out_state.model_stats()
+    }
+
+    /// Returns the names of the container members started since the last
+    /// call, in the order they are stored in the file. A member is started
+    /// once the first sequence or batch of its first block is returned; the
+    /// empty members are reported along with the next member (or once the
+    /// end of file has been reached). Thus, all the names but the last one
+    /// belong to the empty members, and the sequences returned by the last
+    /// call belong to the last member.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::fastq::reader::FastqReader;
+    /// use idencomp::idn::compressor::IdnCompressor;
+    /// use idencomp::idn::decompressor::IdnDecompressor;
+    ///
+    /// let mut data = Vec::new();
+    /// let mut compressor = IdnCompressor::new(&mut data);
+    /// compressor.start_member("empty.fastq")?;
+    /// compressor.start_member("first.fastq")?;
+    /// for sequence in FastqReader::new(b"@SEQ1\nACGT\n+\n!!!!\n".as_slice()) {
+    ///     compressor.add_sequence(sequence?)?;
+    /// }
+    /// compressor.finish()?;
+    ///
+    /// let mut decompressor = IdnDecompressor::new(data.as_slice());
+    /// assert!(decompressor.take_started_members().is_empty());
+    /// decompressor.next_sequence()?;
+    /// assert_eq!(
+    ///     decompressor.take_started_members(),
+    ///     ["empty.fastq", "first.fastq"]
+    /// );
+    ///
+    /// # Ok::<(), idencomp::Error>(())
+    /// ```
+    pub fn take_started_members(&mut self) -> Vec<String> {
+        mem::take(&mut self.started_members)
+    }
+
+    /// Returns the transforms that have been applied to the sequences before
+    /// compressing them, as stored in the file metadata (see
+    /// [`IdnFileInfo::transforms()`]). The list is available once the first
+    /// sequence or batch has been requested.
+    ///
+    /// [`IdnFileInfo::transforms()`]: crate::idn::inspector::IdnFileInfo::transforms
+    #[must_use]
+    pub fn transforms(&self) -> Vec<TransformInfo> {
+        self.out_state.transforms()
+    }
+
+    /// Returns the digest of the original input data stored in the file, if
+    /// any (see [`IdnFileInfo::source_digest()`]). If the digest is stored at
+    /// the end of the file, it is available only once the end of file has
+    /// been reached.
+    ///
+    /// [`IdnFileInfo::source_digest()`]: crate::idn::inspector::IdnFileInfo::source_digest
+    #[must_use]
+    pub fn source_digest(&self) -> Option<SourceDigest> {
+        self.out_state.source_digest()
+    }
+
+    /// Waits for all the jobs once the end of file has been reached or
+    /// retrieving the sequences has failed. The error of `result` takes
+    /// precedence, as the pool only reports a placeholder error once the
+    /// actual one has been taken from it.
+    fn finish_jobs<T>(&mut self, result: &IdnDecompressResult<T>) -> IdnDecompressResult<()> {
+        self.eof_reached = true;
+        let join_result = self.thread_pool.join();
+        if result.is_ok() {
+            join_result
+        } else {
+            Ok(())
+        }
+    }
+
+    fn abort(&mut self) -> IdnDecompressResult<()> {
+        if self.eof_reached {
+            return Ok(());
+        }
+
+        self.eof_reached = true;
+        self.out_state.abort();
+        self.thread_pool.join()
+    }
 }
 
 impl<R: Read + Send> IntoIterator for IdnDecompressor<R> {
@@ -565,12 +1900,56 @@ impl<R: Read + Send> Iterator for IdnDecompressorIterator<R> {
     }
 }
 
+/// Iterable object for [`IdnDecompressor`], returning [`Result`]s of batches
+/// of [`FastqSequence`]s. Created with [`IdnDecompressor::into_batches()`].
+#[derive(Debug)]
+pub struct IdnDecompressorBatches<R> {
+    decompressor: IdnDecompressor<R>,
+}
+
+impl<R: Read + Send> Iterator for IdnDecompressorBatches<R> {
+    type Item = IdnDecompressResult<Vec<FastqSequence>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let result = self.decompressor.next_batch();
+        match result {
+            Ok(val) => val.map(Ok),
+            Err(val) => Some(Err(val)),
+        }
+    }
+}
+
+/// Iterable object for [`IdnDecompressor`], returning [`Result`]s of
+/// [`IndexedSequence`]s. Created with [`IdnDecompressor::indexed_sequences()`].
+#[derive(Debug)]
+pub struct IdnDecompressorIndexedSequences<R> {
+    decompressor: IdnDecompressor<R>,
+}
+
+impl<R: Read + Send> Iterator for IdnDecompressorIndexedSequences<R> {
+    type Item = IdnDecompressResult<IndexedSequence>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let result = self.decompressor.next_indexed_sequence();
+        match result {
+            Ok(val) => val.map(Ok),
+            Err(val) => Some(Err(val)),
+        }
+    }
+}
+
 impl<R> IdnDecompressor<R> {
     fn print_stats(&self) {
         info!(
             "Decompressed {}",
             format_stats(self.start_time, self.bytes_decompressed)
         );
+        for stats in self.out_state.model_stats() {
+            info!(
+                "{} model {}: {} sequences, {} symbols",
+                stats.model_type, stats.identifier, stats.sequences, stats.symbols
+            );
+        }
     }
 }
 
@@ -579,7 +1958,11 @@ impl<R> Drop for IdnDecompressor<R> {
         self.print_stats();
 
         if !self.eof_reached {
-            panic!("Cannot drop IdnDecompressor while still reading");
+            debug!("IdnDecompressor dropped before the end of file, aborting");
+            self.eof_reached = true;
+            self.out_state.abort();
+            // Errors cannot be reported here; use `close()` to get them
+            let _ = self.thread_pool.join();
         }
     }
 }
@@ -591,6 +1974,7 @@ mod tests {
     use std::io::ErrorKind::NotFound;
 
     use crate::idn::decompressor::IdnDecompressorError;
+    use crate::idn::inspector::IdentifierCompression;
 
     #[test]
     fn test_error_display() {
@@ -618,6 +2002,71 @@ mod tests {
             IdnDecompressorError::invalid_model_index(12, 5).to_string(),
             "Invalid model index (read: 12, number of active models: 5)"
         );
+        assert_eq!(
+            IdnDecompressorError::UnsupportedIdentifierCompression(IdentifierCompression::Brotli)
+                .to_string(),
+            "Identifier compression method not supported by this build: brotli"
+        );
+        assert_eq!(
+            IdnDecompressorError::invalid_duplicate(3, 2).to_string(),
+            "Invalid duplicate sequence reference (distance: 3, preceding sequences in block: 2)"
+        );
+        assert_eq!(
+            IdnDecompressorError::invalid_packed_sequence(3, 20).to_string(),
+            "Invalid packed sequence (data length: 3, sequence length: 20)"
+        );
+        assert_eq!(
+            IdnDecompressorError::invalid_raw_sequence(3, 20).to_string(),
+            "Invalid raw sequence (data length: 3, sequence length: 20)"
+        );
+        assert_eq!(
+            IdnDecompressorError::invalid_acid(7).to_string(),
+            "Invalid acid value: 7"
+        );
+        assert_eq!(
+            IdnDecompressorError::unknown_member("sample.fastq").to_string(),
+            "Container member not found: `sample.fastq`"
+        );
+        assert_eq!(
+            IdnDecompressorError::block_too_large(4096, 1024).to_string(),
+            "Block too large (length: 4096, limit: 1024)"
+        );
+        assert_eq!(
+            IdnDecompressorError::identifiers_too_large(1024).to_string(),
+            "Identifier data exceeds the limit of 1024 bytes"
+        );
+        assert_eq!(
+            IdnDecompressorError::too_many_metadata_items(200, 16).to_string(),
+            "Too many metadata items (number: 200, limit: 16)"
+        );
+        assert_eq!(
+            IdnDecompressorError::sequence_too_long(300, 100).to_string(),
+            "Sequence too long (length: 300, limit: 100)"
+        );
+        assert_eq!(
+            IdnDecompressorError::slice_out_of_bounds(20, 5).to_string(),
+            "Slice exceeds the block (slice length: 20, remaining block length: 5)"
+        );
+        assert_eq!(
+            IdnDecompressorError::UnusedModelSwitch.to_string(),
+            "Model switch within a sequence not followed by an encoded sequence"
+        );
+        assert_eq!(
+            IdnDecompressorError::sequence_data_too_short(3, 8).to_string(),
+            "Sequence data too short (length: 3, minimum: 8)"
+        );
+        assert_eq!(
+            IdnDecompressorError::unknown_transform("reverse").to_string(),
+            "Unknown lossless sequence transform: `reverse`"
+        );
+        assert_eq!(
+            IdnDecompressorError::EmptyQualityAlphabet.to_string(),
+            "Empty quality alphabet in the file metadata"
+        );
+        assert_eq!(
+            IdnDecompressorError::invalid_model_switch_offset(150, 100).to_string(),
+            "Invalid model switch offset (offset: 150, sequence length: 100)"
+        );
     }
 
     #[test]
@@ -1,62 +1,208 @@
+use std::fmt::{Debug, Formatter};
 use std::hash::Hash;
-use std::io::{Cursor, Read, Seek, SeekFrom};
+use std::io::{BufRead, BufReader, Cursor, Read, Seek, SeekFrom, Take};
 use std::mem;
 use std::sync::Arc;
 
 use binrw::BinRead;
+use byteorder::{BigEndian, ReadBytesExt};
+#[cfg(feature = "deflate")]
 use flate2::read::DeflateDecoder;
 use log::debug;
 
-use crate::fastq::FastqSequence;
+use crate::compressor::RansDecompressor;
+use crate::fastq::{FastqQualityScore, FastqSequence};
 use crate::idn::data::{
-    IdnIdentifierCompression, IdnIdentifiersHeader, IdnSequenceHeader, IdnSliceHeader,
-    IdnSwitchModelHeader,
+    IdnAuxDataHeader, IdnDuplicateHeader, IdnIdentifierCompression, IdnIdentifiersHeader,
+    IdnNQualityScoreHeader, IdnPackedHeader, IdnSeparatorTextHeader, IdnSequenceHeader,
+    IdnSliceHeader, IdnSwitchModelAtHeader, IdnSwitchModelHeader,
 };
 use crate::idn::decompressor::{
-    IdnDecompressResult, IdnDecompressorError, IdnDecompressorOutState, IdnDecompressorParams,
+    DecompressedBlock, IdnDecompressResult, IdnDecompressorError, IdnDecompressorOutState,
+    IdnDecompressorParams, IndexedSequence, ModelDecodeStats,
 };
+use crate::idn::{packing, raw};
 use crate::model::ModelType;
-use crate::progress::ByteNum;
-use crate::sequence_compressor::{AcidRansDecModel, QScoreRansDecModel, SequenceDecompressor};
+use crate::progress::{BatchedProgress, ByteNum};
+use crate::quality_alphabet::QualityAlphabet;
+use crate::sequence_compressor::{
+    AcidRansDecModel, ModelSegment, QScoreRansDecModel, RansDecModel, SequenceDecompressor,
+};
+
+/// The minimum length of an encoded sequence: the final states of both of the
+/// interleaved rANS channels.
+const ENCODED_SEQUENCE_MIN_LEN: usize = RansDecompressor::<2>::MIN_DATA_LEN;
+/// The minimum length of the quality scores of a duplicate sequence, encoded
+/// with a single rANS channel.
+const ENCODED_Q_SCORES_MIN_LEN: usize = RansDecompressor::<1>::MIN_DATA_LEN;
+
+/// The buffers used to decompress a block. They are taken from the pool of
+/// [`IdnDecompressorOutState`] and returned once the block is decompressed, so
+/// that they are not allocated again for every block.
+#[derive(Debug)]
+pub(super) struct BlockScratch {
+    decompressor: SequenceDecompressor,
+    /// The decompressed data of the auxiliary data slice.
+    slice_data: Vec<u8>,
+}
+
+impl BlockScratch {
+    #[must_use]
+    fn new(quality_alphabet: &QualityAlphabet) -> Self {
+        Self {
+            decompressor: SequenceDecompressor::new(quality_alphabet.clone()),
+            slice_data: Vec::new(),
+        }
+    }
+}
+
+/// The identifiers of the sequences of a block, decompressed line by line as
+/// the sequences are decoded, so that the identifiers of the whole block never
+/// have to be held in memory at once.
+struct IdentifierStream {
+    reader: BufReader<Take<Box<dyn Read + Send>>>,
+    read_len: usize,
+    max_len: usize,
+}
+
+impl IdentifierStream {
+    /// Creates a new stream reading the decompressed identifier data from
+    /// given reader. The decompressed data is limited to `max_len` bytes.
+    #[must_use]
+    fn new(reader: Box<dyn Read + Send>, max_len: usize) -> Self {
+        let limit = (max_len as u64).saturating_add(1);
+
+        Self {
+            reader: BufReader::new(reader.take(limit)),
+            read_len: 0,
+            max_len,
+        }
+    }
+
+    /// Returns the next identifier, or `None` if there are no more
+    /// identifiers.
+    fn next_identifier(&mut self) -> IdnDecompressResult<Option<String>> {
+        let mut line = Vec::new();
+        let len = self.reader.read_until(b'\n', &mut line)?;
+        if len == 0 {
+            return Ok(None);
+        }
+        self.read_len += len;
+        if self.read_len > self.max_len {
+            return Err(IdnDecompressorError::identifiers_too_large(self.max_len));
+        }
+
+        if line.last() == Some(&b'\n') {
+            line.pop();
+            if line.last() == Some(&b'\r') {
+                line.pop();
+            }
+        }
+        Ok(Some(String::from_utf8(line)?))
+    }
+}
+
+impl Debug for IdentifierStream {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("IdentifierStream")
+            .field("read_len", &self.read_len)
+            .field("max_len", &self.max_len)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The number of sequences and symbols decoded with a single model within a
+/// block.
+#[derive(Debug, Default, Copy, Clone)]
+struct ModelTally {
+    sequences: u64,
+    symbols: u64,
+}
 
 #[derive(Debug)]
 pub(super) struct IdnBlockDecompressor {
     block_index: u32,
+    file_block_index: usize,
     data: Cursor<Vec<u8>>,
     out_state: Arc<IdnDecompressorOutState>,
     seq_checksum: u32,
     options: Arc<IdnDecompressorParams>,
+    is_last: bool,
+    /// The name of the container member starting with this block, if any.
+    member: Option<String>,
 
     last_pos: usize,
-    decompressor: SequenceDecompressor,
-    identifiers: Vec<String>,
+    progress: BatchedProgress,
+    scratch: BlockScratch,
+    /// All the sequences read from this block so far (before filtering), so
+    /// that the duplicate slices can refer to them.
+    sequences: Vec<FastqSequence>,
+    identifiers: Option<IdentifierStream>,
+    /// The auxiliary data of the remaining sequences of this block, in
+    /// reverse order.
+    aux_data: Vec<Vec<u8>>,
+    /// The separator texts of the remaining sequences of this block, in
+    /// reverse order.
+    separator_texts: Vec<Vec<u8>>,
     hasher: crc32fast::Hasher,
     current_acid_model: Option<u8>,
     current_q_score_model: Option<u8>,
+    /// The offsets and the model indices of the model switches within the
+    /// next sequence.
+    pending_switches: Vec<(u32, u8)>,
+    packed_q_score: Option<FastqQualityScore>,
+    raw: bool,
+    /// The usage of each of the models, indexed by the model index.
+    model_tallies: Vec<ModelTally>,
 }
 
 impl IdnBlockDecompressor {
     #[must_use]
     pub fn new(
         block_index: u32,
+        file_block_index: usize,
         data: Vec<u8>,
         out_state: Arc<IdnDecompressorOutState>,
         seq_checksum: u32,
         options: Arc<IdnDecompressorParams>,
     ) -> Self {
+        // Only the end-of-file block is empty
+        let is_last = data.is_empty();
+        let mut scratch = out_state
+            .scratch_pool()
+            .take_or_else(|| BlockScratch::new(&options.quality_alphabet));
+        // The scratch may come from a block with the N quality scores skipped
+        scratch.decompressor.set_n_q_score(None);
+        let model_tallies = vec![ModelTally::default(); options.model_provider.len()];
+        let progress = BatchedProgress::new(
+            options.progress_notifier.clone(),
+            options.progress_granularity,
+        );
+
         Self {
             block_index,
+            file_block_index,
             data: Cursor::new(data),
             out_state,
             seq_checksum,
             options,
+            is_last,
+            member: None,
 
             last_pos: 0,
-            decompressor: SequenceDecompressor::new(),
-            identifiers: Vec::new(),
+            progress,
+            scratch,
+            sequences: Vec::new(),
+            identifiers: None,
+            aux_data: Vec::new(),
+            separator_texts: Vec::new(),
             hasher: crc32fast::Hasher::new(),
             current_acid_model: None,
             current_q_score_model: None,
+            pending_switches: Vec::new(),
+            packed_q_score: None,
+            raw: false,
+            model_tallies,
         }
     }
 
@@ -70,25 +216,95 @@ impl IdnBlockDecompressor {
         &mut data.get_mut()[pos..]
     }
 
+    /// Returns the data of the slice of given length starting at the current
+    /// position, or an error if the slice exceeds the block.
+    fn slice_data(data: &Cursor<Vec<u8>>, data_len: usize) -> IdnDecompressResult<&[u8]> {
+        let remaining = Self::remaining(data);
+        let remaining_len = remaining.len();
+        remaining
+            .get(..data_len)
+            .ok_or_else(|| IdnDecompressorError::slice_out_of_bounds(data_len, remaining_len))
+    }
+
+    fn slice_data_mut(
+        data: &mut Cursor<Vec<u8>>,
+        data_len: usize,
+    ) -> IdnDecompressResult<&mut [u8]> {
+        let remaining = Self::remaining_mut(data);
+        let remaining_len = remaining.len();
+        remaining
+            .get_mut(..data_len)
+            .ok_or_else(|| IdnDecompressorError::slice_out_of_bounds(data_len, remaining_len))
+    }
+
     fn is_empty(&self) -> bool {
         Self::remaining(&self.data).is_empty()
     }
 
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "decompress_block", skip_all, fields(block = self.block_index)))]
     pub fn process(mut self) -> IdnDecompressResult<()> {
-        let mut sequences = Vec::new();
-        while let Some(sequence) = self.next_sequence_catch_error()? {
-            sequences.push(sequence);
+        if self.out_state.is_aborted() {
+            return Ok(());
         }
 
-        let _guard = self.out_state.block_lock().lock(self.block_index);
-        self.out_state.data_queue().add_all(sequences);
+        // If reading the sequences fails, the queue is finished, so the
+        // subsequent blocks are not waited for
+        let sequences = self.read_sequences();
+        let model_stats = self.model_stats();
+        self.out_state.scratch_pool().put_back(self.scratch);
+        let sequences = sequences?;
+        self.out_state.add_model_stats(model_stats);
+        let block = DecompressedBlock {
+            sequences,
+            is_last: self.is_last,
+            member: self.member,
+        };
+        self.out_state.blocks().add(self.block_index, block);
+        self.progress.flush();
+        self.options.progress_notifier.advance_stage(1);
+
         Ok(())
     }
 
+    fn read_sequences(&mut self) -> IdnDecompressResult<Vec<IndexedSequence>> {
+        while let Some(sequence) = self.next_sequence_catch_error()? {
+            self.sequences.push(sequence);
+        }
+
+        let file_block_index = self.file_block_index;
+        let sequences = mem::take(&mut self.sequences)
+            .into_iter()
+            .map(|sequence| self.invert_transforms(sequence))
+            .enumerate()
+            .filter(|(_, sequence)| self.is_sequence_accepted(sequence))
+            .map(|(index, sequence)| (file_block_index, index, sequence))
+            .collect();
+
+        Ok(sequences)
+    }
+
+    fn invert_transforms(&self, sequence: FastqSequence) -> FastqSequence {
+        let (sequence, aux_data) = sequence.split_aux_data();
+        let (sequence, separator_text) = sequence.split_separator_text();
+        self.options
+            .inverse_transforms
+            .iter()
+            .fold(sequence, |sequence, transform| transform.inverse(sequence))
+            .with_aux_data(aux_data)
+            .with_separator_text(separator_text)
+    }
+
+    fn is_sequence_accepted(&self, sequence: &FastqSequence) -> bool {
+        self.options
+            .sequence_filter
+            .as_ref()
+            .map_or(true, |filter| filter.accepts(sequence))
+    }
+
     fn next_sequence_catch_error(&mut self) -> IdnDecompressResult<Option<FastqSequence>> {
         let result = self.next_sequence();
         if result.is_err() {
-            self.out_state.data_queue().set_finished();
+            self.out_state.blocks().set_finished();
         }
         result
     }
@@ -99,9 +315,7 @@ impl IdnBlockDecompressor {
         let current_pos = self.data.position() as usize;
         let processed = current_pos - self.last_pos;
         self.last_pos = current_pos;
-        self.options
-            .progress_notifier
-            .processed_bytes(ByteNum::new(processed));
+        self.progress.processed_bytes(ByteNum::new(processed));
 
         match &sequence_result {
             Some(sequence) => {
@@ -115,6 +329,7 @@ impl IdnBlockDecompressor {
     fn next_sequence_internal(&mut self) -> IdnDecompressResult<Option<FastqSequence>> {
         loop {
             if self.is_empty() {
+                self.check_no_pending_switches()?;
                 return Ok(None);
             }
 
@@ -122,8 +337,25 @@ impl IdnBlockDecompressor {
             debug!("Read block slice header: {:?}", header);
             match header {
                 IdnSliceHeader::Identifiers(header) => self.handle_identifiers_slice(header)?,
+                IdnSliceHeader::AuxData(header) => self.handle_aux_data_slice(header)?,
+                IdnSliceHeader::SeparatorText(header) => {
+                    self.handle_separator_text_slice(header)?
+                }
                 IdnSliceHeader::SwitchModel(header) => self.handle_switch_model_slice(header)?,
+                IdnSliceHeader::SwitchModelAt(header) => {
+                    self.handle_switch_model_at_slice(header)?
+                }
                 IdnSliceHeader::Sequence(header) => return self.handle_sequence_slice(header),
+                IdnSliceHeader::Duplicate(header) => return self.handle_duplicate_slice(header),
+                IdnSliceHeader::Packed(header) => self.handle_packed_slice(header)?,
+                IdnSliceHeader::Raw(_) => self.raw = true,
+                IdnSliceHeader::NQualityScore(header) => self.handle_n_q_score_slice(header)?,
+                // The group keys are only reported by the inspector
+                IdnSliceHeader::Group(_) => {}
+                // Member boundaries are handled by the main decompressor
+                IdnSliceHeader::Member(header) => {
+                    self.member = Some(String::from_utf8(header.name)?)
+                }
             }
         }
     }
@@ -148,68 +380,201 @@ impl IdnBlockDecompressor {
         header: IdnIdentifiersHeader,
     ) -> IdnDecompressResult<()> {
         let data_len = header.length as usize;
-        let data = &Self::remaining(&self.data)[..data_len];
+        let max_len = self.options.limits.max_identifiers_len;
+        if data_len > max_len {
+            return Err(IdnDecompressorError::identifiers_too_large(max_len));
+        }
+        // Only the compressed data is copied; the identifiers are
+        // decompressed as the sequences are decoded
+        let data = Self::slice_data(&self.data, data_len)?.to_vec();
+        let reader = Self::slice_data_reader(Cursor::new(data), &header.compression)?;
+        self.identifiers = Some(IdentifierStream::new(reader, max_len));
 
-        let identifiers = match header.compression {
-            IdnIdentifierCompression::Brotli => Self::handle_identifiers_slice_brotli(data)?,
-            IdnIdentifierCompression::Deflate => Self::handle_identifiers_slice_deflate(data)?,
-        };
-        self.identifiers = identifiers;
+        self.data.seek(SeekFrom::Current(data_len as i64))?;
+        Ok(())
+    }
+
+    fn handle_aux_data_slice(&mut self, header: IdnAuxDataHeader) -> IdnDecompressResult<()> {
+        let data_len = header.length as usize;
+        let aux_data = self.read_slice_data(data_len, &header.compression)?;
+        self.aux_data = Self::length_prefixed_from_bytes(&aux_data)?;
+        self.scratch.slice_data = aux_data;
 
         self.data.seek(SeekFrom::Current(data_len as i64))?;
         Ok(())
     }
 
-    fn handle_identifiers_slice_brotli(data: &[u8]) -> IdnDecompressResult<Vec<String>> {
-        let identifier_data = {
-            let mut identifier_data = Vec::new();
-            let mut reader = brotli::Decompressor::new(data, 4096);
-            reader.read_to_end(&mut identifier_data)?;
-            identifier_data
-        };
+    fn handle_separator_text_slice(
+        &mut self,
+        header: IdnSeparatorTextHeader,
+    ) -> IdnDecompressResult<()> {
+        let data_len = header.length as usize;
+        let separator_texts = self.read_slice_data(data_len, &header.compression)?;
+        self.separator_texts = Self::length_prefixed_from_bytes(&separator_texts)?;
+        self.scratch.slice_data = separator_texts;
 
-        Self::identifiers_from_lines(identifier_data)
+        self.data.seek(SeekFrom::Current(data_len as i64))?;
+        Ok(())
     }
 
-    fn handle_identifiers_slice_deflate(data: &[u8]) -> IdnDecompressResult<Vec<String>> {
-        let identifier_data = {
-            let mut identifier_data = Vec::new();
-            let mut reader = DeflateDecoder::new(data);
-            reader.read_to_end(&mut identifier_data)?;
-            identifier_data
-        };
+    /// Reads and decompresses the data of an auxiliary data slice of given
+    /// length, starting at the current position. Both the compressed and the
+    /// decompressed data are limited by
+    /// [`IdnDecompressorLimits::max_identifiers_len()`].
+    ///
+    /// The data is returned in the scratch buffer, which should be put back
+    /// once the data is processed.
+    ///
+    /// [`IdnDecompressorLimits::max_identifiers_len()`]: crate::idn::decompressor::IdnDecompressorLimits::max_identifiers_len
+    fn read_slice_data(
+        &mut self,
+        data_len: usize,
+        compression: &IdnIdentifierCompression,
+    ) -> IdnDecompressResult<Vec<u8>> {
+        let max_len = self.options.limits.max_identifiers_len;
+        if data_len > max_len {
+            return Err(IdnDecompressorError::identifiers_too_large(max_len));
+        }
+        let data = Self::slice_data(&self.data, data_len)?;
+        let mut buf = mem::take(&mut self.scratch.slice_data);
+        buf.clear();
+
+        let reader = Self::slice_data_reader(data, compression)?;
+        Self::read_decompressed_data(reader, max_len, &mut buf)?;
 
-        Self::identifiers_from_lines(identifier_data)
+        Ok(buf)
     }
 
-    fn identifiers_from_lines(identifier_data: Vec<u8>) -> IdnDecompressResult<Vec<String>> {
-        let identifiers = String::from_utf8(identifier_data)?;
-        let mut identifiers: Vec<String> =
-            identifiers.lines().map(|line| line.to_owned()).collect();
-        identifiers.reverse();
+    /// Returns a reader decompressing the data of an identifier or auxiliary
+    /// data slice.
+    fn slice_data_reader<'a, R: Read + Send + 'a>(
+        data: R,
+        compression: &IdnIdentifierCompression,
+    ) -> IdnDecompressResult<Box<dyn Read + Send + 'a>> {
+        match compression {
+            IdnIdentifierCompression::Brotli => Self::slice_data_reader_brotli(data),
+            IdnIdentifierCompression::Deflate => Self::slice_data_reader_deflate(data),
+            IdnIdentifierCompression::Uncompressed => Ok(Box::new(data)),
+        }
+    }
+
+    #[cfg(feature = "brotli")]
+    fn slice_data_reader_brotli<'a, R: Read + Send + 'a>(
+        data: R,
+    ) -> IdnDecompressResult<Box<dyn Read + Send + 'a>> {
+        Ok(Box::new(brotli::Decompressor::new(data, 4096)))
+    }
+
+    #[cfg(not(feature = "brotli"))]
+    fn slice_data_reader_brotli<'a, R: Read + Send + 'a>(
+        _data: R,
+    ) -> IdnDecompressResult<Box<dyn Read + Send + 'a>> {
+        Err(IdnDecompressorError::UnsupportedIdentifierCompression(
+            crate::idn::inspector::IdentifierCompression::Brotli,
+        ))
+    }
+
+    #[cfg(feature = "deflate")]
+    fn slice_data_reader_deflate<'a, R: Read + Send + 'a>(
+        data: R,
+    ) -> IdnDecompressResult<Box<dyn Read + Send + 'a>> {
+        Ok(Box::new(DeflateDecoder::new(data)))
+    }
+
+    #[cfg(not(feature = "deflate"))]
+    fn slice_data_reader_deflate<'a, R: Read + Send + 'a>(
+        _data: R,
+    ) -> IdnDecompressResult<Box<dyn Read + Send + 'a>> {
+        Err(IdnDecompressorError::UnsupportedIdentifierCompression(
+            crate::idn::inspector::IdentifierCompression::Deflate,
+        ))
+    }
+
+    /// Reads the decompressed slice data into `buf`, failing as soon as it
+    /// exceeds `max_len` bytes, so that a small compressed slice cannot expand
+    /// into a huge allocation.
+    fn read_decompressed_data<T: Read>(
+        reader: T,
+        max_len: usize,
+        buf: &mut Vec<u8>,
+    ) -> IdnDecompressResult<()> {
+        let limit = (max_len as u64).saturating_add(1);
+        reader.take(limit).read_to_end(buf)?;
+        if buf.len() > max_len {
+            return Err(IdnDecompressorError::identifiers_too_large(max_len));
+        }
+
+        Ok(())
+    }
+
+    /// Splits the auxiliary data (or the separator texts) into the data of the
+    /// single sequences, each one prefixed with its length, and returns them in
+    /// reverse order.
+    fn length_prefixed_from_bytes(mut data: &[u8]) -> IdnDecompressResult<Vec<Vec<u8>>> {
+        let mut aux_data = Vec::new();
+        while !data.is_empty() {
+            let len = data.read_u32::<BigEndian>()? as usize;
+            let item = data
+                .get(..len)
+                .ok_or_else(|| IdnDecompressorError::slice_out_of_bounds(len, data.len()))?;
+            aux_data.push(item.to_vec());
+            data = &data[len..];
+        }
+        aux_data.reverse();
 
-        Ok(identifiers)
+        Ok(aux_data)
     }
 
     fn handle_switch_model_slice(
         &mut self,
         header: IdnSwitchModelHeader,
     ) -> IdnDecompressResult<()> {
-        let model_index = header.model_index as usize;
+        self.switch_model(header.model_index)
+    }
+
+    fn handle_switch_model_at_slice(
+        &mut self,
+        header: IdnSwitchModelAtHeader,
+    ) -> IdnDecompressResult<()> {
+        self.check_model_index(header.model_index)?;
+        self.pending_switches
+            .push((header.offset, header.model_index));
+
+        Ok(())
+    }
+
+    /// Checks that there are no model switches within a sequence waiting for
+    /// the sequence, as they only apply to the sequences encoded with the
+    /// models. Otherwise, they would be applied to some later sequence.
+    fn check_no_pending_switches(&self) -> IdnDecompressResult<()> {
+        if self.pending_switches.is_empty() {
+            Ok(())
+        } else {
+            Err(IdnDecompressorError::UnusedModelSwitch)
+        }
+    }
+
+    fn switch_model(&mut self, model_index: u8) -> IdnDecompressResult<()> {
+        self.check_model_index(model_index)?;
+
+        let model = &self.options.model_provider[model_index as usize];
+        match model.model_type() {
+            ModelType::Acids => self.current_acid_model = Some(model_index),
+            ModelType::QualityScores => self.current_q_score_model = Some(model_index),
+        }
+
+        Ok(())
+    }
+
+    fn check_model_index(&self, model_index: u8) -> IdnDecompressResult<()> {
         let num_models = self.options.model_provider.len();
-        if model_index >= num_models {
+        if model_index as usize >= num_models {
             return Err(IdnDecompressorError::invalid_model_index(
-                model_index as u8,
+                model_index,
                 num_models as u8,
             ));
         }
 
-        let model = &self.options.model_provider[model_index];
-        match model.model_type() {
-            ModelType::Acids => self.current_acid_model = Some(model_index as u8),
-            ModelType::QualityScores => self.current_q_score_model = Some(model_index as u8),
-        }
-
         Ok(())
     }
 
@@ -219,44 +584,293 @@ impl IdnBlockDecompressor {
     ) -> IdnDecompressResult<Option<FastqSequence>> {
         let data_len = header.length as usize;
         let seq_len = header.seq_len as usize;
+        let max_seq_len = self.options.limits.max_sequence_len;
+        if seq_len > max_seq_len {
+            return Err(IdnDecompressorError::sequence_too_long(
+                seq_len,
+                max_seq_len,
+            ));
+        }
+
+        if let Some(q_score) = self.packed_q_score {
+            self.check_no_pending_switches()?;
+            return self.handle_packed_sequence_slice(data_len, seq_len, q_score);
+        }
+        if self.raw {
+            self.check_no_pending_switches()?;
+            return self.handle_raw_sequence_slice(data_len, seq_len);
+        }
+        if seq_len == 0 && self.pending_switches.is_empty() {
+            return self.handle_empty_sequence_slice(data_len);
+        }
+        // Checked before the models are looked up, so that a truncated slice
+        // is reported as such (the rANS decoder would reject it as well)
+        if data_len < ENCODED_SEQUENCE_MIN_LEN {
+            return Err(IdnDecompressorError::sequence_data_too_short(
+                data_len,
+                ENCODED_SEQUENCE_MIN_LEN,
+            ));
+        }
 
         let options = self.options.clone();
-        let acid_model = self.get_current_acid_model(&options)?;
-        let q_score_model = self.get_current_q_score_model(&options)?;
-        let data = &mut Self::remaining_mut(&mut self.data)[..data_len];
-
-        let sequence = self
-            .decompressor
-            .decompress(data, seq_len, acid_model, q_score_model);
-        let sequence = if let Some(identifer) = self.identifiers.pop() {
-            sequence.with_identifier(identifer)
+        let (acid_index, acid_model) = self.get_current_acid_model(&options)?;
+        let (q_score_index, q_score_model) = self.get_current_q_score_model(&options)?;
+        let sequence = if self.pending_switches.is_empty() {
+            // Most of the sequences use a single pair of models, so there is
+            // no need to allocate the segment lists for them
+            let data = Self::slice_data_mut(&mut self.data, data_len)?;
+            let sequence = self.scratch.decompressor.decompress_segmented(
+                data,
+                seq_len,
+                &[(0, acid_model)],
+                &[(0, q_score_model)],
+            )?;
+
+            self.tally_segments(&[(0, acid_index)], seq_len);
+            self.tally_segments(&[(0, q_score_index)], seq_len);
+            sequence
         } else {
+            let mut acid_models = vec![(0, acid_model)];
+            let mut q_score_models = vec![(0, q_score_model)];
+            let mut acid_indices = vec![(0, acid_index)];
+            let mut q_score_indices = vec![(0, q_score_index)];
+            for (offset, model_index) in mem::take(&mut self.pending_switches) {
+                let model = &options.model_provider.decompressor_models()[model_index as usize];
+                let (models, indices, model) = match model.model_type() {
+                    ModelType::Acids => (&mut acid_models, &mut acid_indices, model.as_acid()),
+                    ModelType::QualityScores => (
+                        &mut q_score_models,
+                        &mut q_score_indices,
+                        model.as_quality_score(),
+                    ),
+                };
+                Self::add_model_segment(models, offset, seq_len, model)?;
+                indices.push((offset as usize, model_index));
+                self.switch_model(model_index)?;
+            }
+            let data = Self::slice_data_mut(&mut self.data, data_len)?;
+
+            let sequence = self.scratch.decompressor.decompress_segmented(
+                data,
+                seq_len,
+                &acid_models,
+                &q_score_models,
+            )?;
+
+            self.tally_segments(&acid_indices, seq_len);
+            self.tally_segments(&q_score_indices, seq_len);
             sequence
         };
+        let sequence = self.with_next_metadata(sequence)?;
 
         self.data.seek(SeekFrom::Current(data_len as i64))?;
         Ok(Some(sequence))
     }
 
+    /// Counts the symbols of a sequence decoded with each of the models, given
+    /// the start offsets and the indices of the models of the segments.
+    fn tally_segments(&mut self, segments: &[(usize, u8)], seq_len: usize) {
+        for (i, &(start, model_index)) in segments.iter().enumerate() {
+            let end = segments.get(i + 1).map_or(seq_len, |(start, _)| *start);
+            let first_use = segments[..i].iter().all(|(_, index)| *index != model_index);
+
+            let tally = &mut self.model_tallies[model_index as usize];
+            tally.sequences += u64::from(first_use);
+            tally.symbols += (end - start) as u64;
+        }
+    }
+
+    /// Returns the statistics of the models used in this block so far.
+    fn model_stats(&self) -> Vec<ModelDecodeStats> {
+        self.model_tallies
+            .iter()
+            .enumerate()
+            .filter(|(_, tally)| tally.sequences > 0)
+            .map(|(index, tally)| {
+                let model = &self.options.model_provider[index];
+                let mut stats =
+                    ModelDecodeStats::new(model.identifier().clone(), model.model_type());
+                stats.add(tally.sequences, tally.symbols);
+                stats
+            })
+            .collect()
+    }
+
+    /// Adds the segment of the sequence starting at given offset, checking
+    /// that it starts after the previous segment and within the sequence.
+    fn add_model_segment<'a>(
+        models: &mut Vec<ModelSegment<'a, RansDecModel>>,
+        offset: u32,
+        seq_len: usize,
+        model: &'a RansDecModel,
+    ) -> IdnDecompressResult<()> {
+        let start = offset as usize;
+        let last_start = models.last().map_or(0, |(start, _)| *start);
+        if start <= last_start || start >= seq_len {
+            return Err(IdnDecompressorError::invalid_model_switch_offset(
+                offset, seq_len,
+            ));
+        }
+
+        models.push((start, model));
+        Ok(())
+    }
+
+    fn handle_packed_slice(&mut self, header: IdnPackedHeader) -> IdnDecompressResult<()> {
+        self.packed_q_score = Some(FastqQualityScore::try_new(header.q_score)?);
+
+        Ok(())
+    }
+
+    fn handle_n_q_score_slice(
+        &mut self,
+        header: IdnNQualityScoreHeader,
+    ) -> IdnDecompressResult<()> {
+        let q_score = FastqQualityScore::try_new(header.q_score)?;
+        self.scratch.decompressor.set_n_q_score(Some(q_score));
+
+        Ok(())
+    }
+
+    fn handle_packed_sequence_slice(
+        &mut self,
+        data_len: usize,
+        seq_len: usize,
+        q_score: FastqQualityScore,
+    ) -> IdnDecompressResult<Option<FastqSequence>> {
+        if data_len != packing::packed_len(seq_len) {
+            return Err(IdnDecompressorError::invalid_packed_sequence(
+                data_len, seq_len,
+            ));
+        }
+
+        let data = Self::slice_data(&self.data, data_len)?;
+        let acids = packing::unpack_acids(data, seq_len);
+        let sequence = FastqSequence::new("", acids, vec![q_score; seq_len]);
+        let sequence = self.with_next_metadata(sequence)?;
+
+        self.data.seek(SeekFrom::Current(data_len as i64))?;
+        Ok(Some(sequence))
+    }
+
+    fn handle_raw_sequence_slice(
+        &mut self,
+        data_len: usize,
+        seq_len: usize,
+    ) -> IdnDecompressResult<Option<FastqSequence>> {
+        if data_len != raw::raw_len(seq_len) {
+            return Err(IdnDecompressorError::invalid_raw_sequence(
+                data_len, seq_len,
+            ));
+        }
+
+        let data = Self::slice_data(&self.data, data_len)?;
+        let (acids, q_scores) = raw::load_raw(data, seq_len)?;
+        let sequence = FastqSequence::new("", acids, q_scores);
+        let sequence = self.with_next_metadata(sequence)?;
+
+        self.data.seek(SeekFrom::Current(data_len as i64))?;
+        Ok(Some(sequence))
+    }
+
+    /// Handles a zero-length sequence, which does not need any model. The data
+    /// is skipped without decoding, as the older versions of the compressor
+    /// wrote the (meaningless) coder state for such sequences.
+    fn handle_empty_sequence_slice(
+        &mut self,
+        data_len: usize,
+    ) -> IdnDecompressResult<Option<FastqSequence>> {
+        Self::slice_data(&self.data, data_len)?;
+        let sequence = FastqSequence::new("", [], []);
+        let sequence = self.with_next_metadata(sequence)?;
+
+        self.data.seek(SeekFrom::Current(data_len as i64))?;
+        Ok(Some(sequence))
+    }
+
+    fn handle_duplicate_slice(
+        &mut self,
+        header: IdnDuplicateHeader,
+    ) -> IdnDecompressResult<Option<FastqSequence>> {
+        self.check_no_pending_switches()?;
+        let sequence_num = self.sequences.len();
+        let distance = header.distance as usize;
+        let original = sequence_num
+            .checked_sub(distance)
+            .filter(|_| distance > 0)
+            .map(|index| &self.sequences[index])
+            .filter(|original| original.len() == header.seq_len as usize)
+            .ok_or_else(|| {
+                IdnDecompressorError::invalid_duplicate(header.distance, sequence_num)
+            })?;
+
+        let data_len = header.length as usize;
+        let sequence = if data_len == 0 {
+            original.clone()
+        } else if data_len < ENCODED_Q_SCORES_MIN_LEN {
+            return Err(IdnDecompressorError::sequence_data_too_short(
+                data_len,
+                ENCODED_Q_SCORES_MIN_LEN,
+            ));
+        } else {
+            let acids = original.acids().to_vec();
+            let options = self.options.clone();
+            let (q_score_index, q_score_model) = self.get_current_q_score_model(&options)?;
+            let data = Self::slice_data_mut(&mut self.data, data_len)?;
+            let q_scores =
+                self.scratch
+                    .decompressor
+                    .decompress_q_scores(data, &acids, q_score_model)?;
+            self.data.seek(SeekFrom::Current(data_len as i64))?;
+
+            self.tally_segments(&[(0, q_score_index)], acids.len());
+            FastqSequence::new("", acids, q_scores)
+        };
+        Ok(Some(self.with_next_metadata(sequence)?))
+    }
+
+    /// Attaches the next identifier, the next auxiliary data and the next
+    /// separator text (if they are stored in this block) to given sequence.
+    fn with_next_metadata(
+        &mut self,
+        sequence: FastqSequence,
+    ) -> IdnDecompressResult<FastqSequence> {
+        let identifier = match &mut self.identifiers {
+            Some(identifiers) => identifiers.next_identifier()?,
+            None => None,
+        };
+        let sequence = if let Some(identifier) = identifier {
+            sequence.with_identifier(identifier)
+        } else {
+            sequence
+        };
+
+        Ok(sequence
+            .with_aux_data(self.aux_data.pop().unwrap_or_default())
+            .with_separator_text(self.separator_texts.pop().unwrap_or_default()))
+    }
+
     fn get_current_acid_model<'a>(
         &self,
         options: &'a IdnDecompressorParams,
-    ) -> IdnDecompressResult<&'a AcidRansDecModel> {
+    ) -> IdnDecompressResult<(u8, &'a AcidRansDecModel)> {
         let index = self
             .current_acid_model
             .ok_or_else(|| IdnDecompressorError::no_active_model(ModelType::Acids))?;
 
-        Ok(options.model_provider.decompressor_models()[index as usize].as_acid())
+        let model = options.model_provider.decompressor_models()[index as usize].as_acid();
+        Ok((index, model))
     }
 
     fn get_current_q_score_model<'a>(
         &self,
         options: &'a IdnDecompressorParams,
-    ) -> IdnDecompressResult<&'a QScoreRansDecModel> {
+    ) -> IdnDecompressResult<(u8, &'a QScoreRansDecModel)> {
         let index = self
             .current_q_score_model
             .ok_or_else(|| IdnDecompressorError::no_active_model(ModelType::QualityScores))?;
 
-        Ok(options.model_provider.decompressor_models()[index as usize].as_quality_score())
+        let model = options.model_provider.decompressor_models()[index as usize].as_quality_score();
+        Ok((index, model))
     }
 }
@@ -0,0 +1,88 @@
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use crate::fastq::FastqSequence;
+use crate::idn::common::opaque_debug;
+
+/// The way a single sequence has been stored in the compressed file.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum SequenceEncoding {
+    /// The sequence has been encoded with the acid and quality score models.
+    Models,
+    /// The sequence has the same acids as an earlier sequence of the same
+    /// block, so only a reference to it has been stored, followed by the
+    /// quality scores if they differ.
+    Duplicate,
+    /// The acids have been packed and the quality scores, all equal, have been
+    /// stored once for the whole block.
+    Packed,
+    /// The sequence has no symbols, so only its header has been stored.
+    Empty,
+    /// The sequences of the block could not be compressed well enough with
+    /// the models, so the acids and the quality scores have been stored as
+    /// they are.
+    Raw,
+}
+
+/// The details of how a single sequence has been encoded, passed to the
+/// callback set with
+/// [`IdnCompressorParamsBuilder::on_sequence()`](crate::idn::compressor::IdnCompressorParamsBuilder::on_sequence).
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SequenceEncodeInfo {
+    pub(super) block: u32,
+    pub(super) index: usize,
+    pub(super) encoding: SequenceEncoding,
+    pub(super) compressed_bytes: usize,
+}
+
+impl SequenceEncodeInfo {
+    /// Returns the index of the block the sequence has been written to.
+    #[must_use]
+    pub fn block(&self) -> u32 {
+        self.block
+    }
+
+    /// Returns the index of the sequence within its block.
+    #[must_use]
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the way the sequence has been stored.
+    #[must_use]
+    pub fn encoding(&self) -> SequenceEncoding {
+        self.encoding
+    }
+
+    /// Returns the number of bytes the acids and quality scores of the
+    /// sequence have been compressed to, not including the sequence header.
+    /// This is 0 for the duplicates.
+    #[must_use]
+    pub fn compressed_bytes(&self) -> usize {
+        self.compressed_bytes
+    }
+}
+
+type SequenceCallbackFn = dyn Fn(&FastqSequence, &SequenceEncodeInfo) + Send + Sync;
+
+/// The callback invoked by the compressor after each of the sequences has been
+/// encoded.
+#[derive(Clone)]
+pub(super) struct SequenceCallback(Arc<SequenceCallbackFn>);
+
+impl SequenceCallback {
+    #[must_use]
+    pub fn new<F>(callback: F) -> Self
+    where
+        F: Fn(&FastqSequence, &SequenceEncodeInfo) + Send + Sync + 'static,
+    {
+        Self(Arc::new(callback))
+    }
+
+    pub fn call(&self, sequence: &FastqSequence, info: &SequenceEncodeInfo) {
+        (self.0)(sequence, info);
+    }
+}
+
+// The user-provided closure has no `Debug` implementation
+opaque_debug!(SequenceCallback);
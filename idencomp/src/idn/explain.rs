@@ -0,0 +1,214 @@
+use std::fmt::Debug;
+
+use crate::context_spec::ContextSpec;
+use crate::fastq::FastqSequence;
+use crate::model::ModelIdentifier;
+use crate::sequence_compressor::{
+    AcidRansEncModel, ModelSegment, QScoreRansEncModel, RansEncModel, SegmentCursor,
+    SequenceCompressor,
+};
+
+/// The details of how a single sequence has been compressed, reported to the
+/// [`SequenceExplainer`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SequenceExplanation {
+    pub(super) block: u32,
+    pub(super) index: usize,
+    pub(super) identifier: String,
+    pub(super) length: usize,
+    pub(super) acid_model: ModelIdentifier,
+    pub(super) q_score_model: ModelIdentifier,
+    pub(super) estimated_bytes: Option<usize>,
+    pub(super) actual_bytes: usize,
+    pub(super) acid_dummy_hits: usize,
+    pub(super) q_score_dummy_hits: usize,
+    pub(super) q_score_model_switches: usize,
+}
+
+impl SequenceExplanation {
+    /// Returns the index of the block the sequence has been written to.
+    #[must_use]
+    pub fn block(&self) -> u32 {
+        self.block
+    }
+
+    /// Returns the index of the sequence within its block. The duplicate,
+    /// packed and empty sequences, which are not explained, are counted as
+    /// well.
+    #[must_use]
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the identifier of the sequence.
+    #[must_use]
+    pub fn identifier(&self) -> &str {
+        &self.identifier
+    }
+
+    /// Returns the length of the sequence.
+    #[must_use]
+    pub fn length(&self) -> usize {
+        self.length
+    }
+
+    /// Returns the identifier of the model the acids have been encoded with.
+    #[must_use]
+    pub fn acid_model(&self) -> &ModelIdentifier {
+        &self.acid_model
+    }
+
+    /// Returns the identifier of the model the quality scores have been
+    /// encoded with.
+    #[must_use]
+    pub fn q_score_model(&self) -> &ModelIdentifier {
+        &self.q_score_model
+    }
+
+    /// Returns the number of bytes the model chooser estimated the sequence
+    /// would be compressed to, or `None` if the models have not been chosen
+    /// specifically for this sequence (e.g. in the fast mode, or when they
+    /// have been chosen for a group of sequences), or the
+    /// [`ModelSelectionStrategy`](crate::idn::model_selection::ModelSelectionStrategy)
+    /// has not computed their compressed sizes.
+    #[must_use]
+    pub fn estimated_bytes(&self) -> Option<usize> {
+        self.estimated_bytes
+    }
+
+    /// Returns the number of bytes the sequence has actually been compressed
+    /// to, not including the sequence header.
+    #[must_use]
+    pub fn actual_bytes(&self) -> usize {
+        self.actual_bytes
+    }
+
+    /// Returns the number of acids whose context was not present in the acid
+    /// model, so they were encoded with the dummy (uniform) context.
+    #[must_use]
+    pub fn acid_dummy_hits(&self) -> usize {
+        self.acid_dummy_hits
+    }
+
+    /// Returns the number of quality scores whose context was not present in
+    /// the quality score model, so they were encoded with the dummy (uniform)
+    /// context.
+    #[must_use]
+    pub fn q_score_dummy_hits(&self) -> usize {
+        self.q_score_dummy_hits
+    }
+
+    /// Returns the number of times the quality score model has been switched
+    /// within the sequence. If it is nonzero, [`Self::q_score_model()`] is
+    /// the model used at the start of the sequence.
+    #[must_use]
+    pub fn q_score_model_switches(&self) -> usize {
+        self.q_score_model_switches
+    }
+
+    pub(super) fn count_dummy_hits(
+        sequence: &FastqSequence,
+        acid_models: &[ModelSegment<'_, AcidRansEncModel>],
+        q_score_models: &[ModelSegment<'_, QScoreRansEncModel>],
+    ) -> (usize, usize) {
+        let (acid_specs, q_score_specs) =
+            SequenceCompressor::gen_contexts(sequence, acid_models, q_score_models);
+
+        (
+            Self::count_dummy_specs(acid_specs, acid_models),
+            Self::count_dummy_specs(q_score_specs, q_score_models),
+        )
+    }
+
+    fn count_dummy_specs(
+        specs: Vec<ContextSpec>,
+        models: &[ModelSegment<'_, RansEncModel>],
+    ) -> usize {
+        let mut segments = SegmentCursor::new(models);
+        specs
+            .into_iter()
+            .enumerate()
+            .filter(|&(position, spec)| segments.model_at(position).context_index_for(spec) == 0)
+            .count()
+    }
+}
+
+/// Receiver of the [`SequenceExplanation`]s, which can be used to diagnose
+/// why a file does not compress well.
+///
+/// The explainer is set with
+/// [`IdnCompressorParamsBuilder::explainer()`](crate::idn::compressor::IdnCompressorParamsBuilder::explainer).
+/// Only the sequences encoded with the models are explained, i.e. the
+/// duplicate and packed sequences are not. The explanations of a block are
+/// reported when the block is written, so they come in the order the
+/// sequences are stored in the file.
+///
+/// # Examples
+/// ```
+/// use std::sync::{Arc, Mutex};
+///
+/// use idencomp::fastq::reader::FastqReader;
+/// use idencomp::idn::compressor::{IdnCompressor, IdnCompressorParams};
+/// use idencomp::idn::explain::{SequenceExplainer, SequenceExplanation};
+///
+/// #[derive(Debug, Default)]
+/// struct Collector(Mutex<Vec<SequenceExplanation>>);
+///
+/// impl SequenceExplainer for Collector {
+///     fn explain(&self, explanation: SequenceExplanation) {
+///         self.0.lock().unwrap().push(explanation);
+///     }
+/// }
+///
+/// let collector = Arc::new(Collector::default());
+/// let params = IdnCompressorParams::builder()
+///     .explainer(collector.clone())
+///     .build()?;
+/// let mut data = Vec::new();
+/// let mut compressor = IdnCompressor::with_params(&mut data, params);
+/// for sequence in FastqReader::new(b"@SEQ1\nACGT\n+\n!!!!\n".as_slice()) {
+///     compressor.add_sequence(sequence?)?;
+/// }
+/// compressor.finish()?;
+///
+/// let explanations = collector.0.lock().unwrap();
+/// assert_eq!(explanations.len(), 1);
+/// assert_eq!(explanations[0].identifier(), "SEQ1");
+/// assert_eq!(explanations[0].length(), 4);
+///
+/// # Ok::<(), idencomp::Error>(())
+/// ```
+pub trait SequenceExplainer: Debug + Send + Sync {
+    /// Receives the explanation of a single sequence.
+    fn explain(&self, explanation: SequenceExplanation);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::_internal_test_data::{TEST_ACID_MODEL_PREFER_A, TEST_SEQUENCE_PREFER_A};
+    use crate::idn::explain::SequenceExplanation;
+    use crate::model::{Model, ModelType};
+    use crate::sequence_compressor::{AcidRansEncModel, QScoreRansEncModel};
+
+    const SCALE_BITS: u8 = 10;
+
+    #[test]
+    fn test_count_dummy_hits() {
+        let acid_model = AcidRansEncModel::from_model(&TEST_ACID_MODEL_PREFER_A, SCALE_BITS);
+        let q_score_model =
+            QScoreRansEncModel::from_model(&Model::empty(ModelType::QualityScores), SCALE_BITS);
+
+        let (acid_dummy_hits, q_score_dummy_hits) = SequenceExplanation::count_dummy_hits(
+            &TEST_SEQUENCE_PREFER_A,
+            &[(0, &acid_model)],
+            &[(0, &q_score_model)],
+        );
+
+        // The acid model has a context for every spec, so the dummy one is
+        // never used
+        assert_eq!(acid_dummy_hits, 0);
+        // The empty model does not have any contexts, so all the symbols fall
+        // through to the dummy one
+        assert_eq!(q_score_dummy_hits, TEST_SEQUENCE_PREFER_A.len());
+    }
+}
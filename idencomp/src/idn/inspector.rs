@@ -0,0 +1,677 @@
+use std::fmt::{Display, Formatter};
+use std::io::{Cursor, Read, Seek, SeekFrom};
+
+use binrw::BinRead;
+use log::debug;
+
+use crate::fastq::FastqQualityScore;
+use crate::idn::data::{
+    IdnBlockHeader, IdnHeader, IdnIdentifierCompression, IdnMemberIndexEntry, IdnMetadataHeader,
+    IdnMetadataItem, IdnQualityAlphabetMetadata, IdnSliceHeader,
+};
+use crate::idn::decompressor::{IdnDecompressResult, IdnDecompressorError};
+use crate::idn::no_seek::NoSeek;
+use crate::idn::source_digest::SourceDigest;
+use crate::idn::transform::TransformInfo;
+use crate::model::ModelIdentifier;
+use crate::quality_alphabet::QualityAlphabet;
+
+/// Compression method used to store the sequence identifiers in a block.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum IdentifierCompression {
+    /// Brotli compression.
+    Brotli,
+    /// Deflate compression.
+    Deflate,
+    /// No compression.
+    Uncompressed,
+}
+
+impl From<&IdnIdentifierCompression> for IdentifierCompression {
+    fn from(compression: &IdnIdentifierCompression) -> Self {
+        match compression {
+            IdnIdentifierCompression::Brotli => Self::Brotli,
+            IdnIdentifierCompression::Deflate => Self::Deflate,
+            IdnIdentifierCompression::Uncompressed => Self::Uncompressed,
+        }
+    }
+}
+
+impl Display for IdentifierCompression {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IdentifierCompression::Brotli => write!(f, "brotli"),
+            IdentifierCompression::Deflate => write!(f, "deflate"),
+            IdentifierCompression::Uncompressed => write!(f, "none"),
+        }
+    }
+}
+
+/// The signature every IDN file starts with.
+pub const IDN_SIGNATURE: &[u8] = b"IDENCOMP";
+
+/// Returns whether given data (e.g. the beginning of a file) starts with the
+/// IDN file signature.
+///
+/// # Examples
+/// ```
+/// use idencomp::idn::compressor::IdnCompressor;
+/// use idencomp::idn::inspector::is_idn;
+///
+/// let mut vec = Vec::new();
+/// IdnCompressor::new(&mut vec).finish().unwrap();
+///
+/// assert!(is_idn(&vec));
+/// assert!(!is_idn(b"@SEQ\nACGT\n+\n!!!!\n"));
+/// ```
+#[must_use]
+pub fn is_idn(data: &[u8]) -> bool {
+    data.starts_with(IDN_SIGNATURE)
+}
+
+/// Structural information about a single IDN block.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct IdnBlockInfo {
+    length: u32,
+    seq_checksum: u32,
+    sequence_num: usize,
+    total_seq_len: u64,
+    sequence_bytes: u64,
+    identifier_bytes: u64,
+    aux_data_bytes: u64,
+    model_switch_num: usize,
+    duplicate_num: usize,
+    packed: bool,
+    raw: bool,
+    n_q_score: Option<FastqQualityScore>,
+    identifier_compression: Option<IdentifierCompression>,
+    aux_data_compression: Option<IdentifierCompression>,
+    member: Option<String>,
+    group: Option<String>,
+}
+
+impl IdnBlockInfo {
+    /// Returns the length of the block data in bytes (excluding the block
+    /// header).
+    #[must_use]
+    pub fn length(&self) -> u32 {
+        self.length
+    }
+
+    /// Returns the checksum of the sequences stored in the block.
+    #[must_use]
+    pub fn seq_checksum(&self) -> u32 {
+        self.seq_checksum
+    }
+
+    /// Returns the number of sequences stored in the block, including the
+    /// duplicates.
+    #[must_use]
+    pub fn sequence_num(&self) -> usize {
+        self.sequence_num
+    }
+
+    /// Returns the total number of nucleotides stored in the block.
+    #[must_use]
+    pub fn total_seq_len(&self) -> u64 {
+        self.total_seq_len
+    }
+
+    /// Returns the total length of the compressed sequence data (the acids
+    /// and the quality scores) in the block, in bytes.
+    #[must_use]
+    pub fn sequence_bytes(&self) -> u64 {
+        self.sequence_bytes
+    }
+
+    /// Returns the length of the (possibly compressed) identifier data in the
+    /// block, including the preserved separator texts, in bytes.
+    #[must_use]
+    pub fn identifier_bytes(&self) -> u64 {
+        self.identifier_bytes
+    }
+
+    /// Returns the length of the (possibly compressed) auxiliary per-read
+    /// data in the block, in bytes.
+    #[must_use]
+    pub fn aux_data_bytes(&self) -> u64 {
+        self.aux_data_bytes
+    }
+
+    /// Returns the number of "switch model" slices in the block.
+    #[must_use]
+    pub fn model_switch_num(&self) -> usize {
+        self.model_switch_num
+    }
+
+    /// Returns the number of sequences stored in the block as references to
+    /// identical earlier sequences.
+    #[must_use]
+    pub fn duplicate_num(&self) -> usize {
+        self.duplicate_num
+    }
+
+    /// Returns whether the acids in the block are stored packed (using 2 bits
+    /// per acid) instead of being compressed with the models.
+    #[must_use]
+    pub fn is_packed(&self) -> bool {
+        self.packed
+    }
+
+    /// Returns whether the sequences in the block are stored raw (one byte per
+    /// acid and per quality score), because they could not be compressed well
+    /// enough with the models. See
+    /// [`IdnCompressorParamsBuilder::raw_fallback_ratio()`].
+    ///
+    /// [`IdnCompressorParamsBuilder::raw_fallback_ratio()`]: crate::idn::compressor::IdnCompressorParamsBuilder::raw_fallback_ratio
+    #[must_use]
+    pub fn is_raw(&self) -> bool {
+        self.raw
+    }
+
+    /// Returns the quality score shared by all the `N` acids in the block,
+    /// which are then stored without their quality scores, or `None` if they
+    /// are stored as usual.
+    #[must_use]
+    pub fn n_q_score(&self) -> Option<FastqQualityScore> {
+        self.n_q_score
+    }
+
+    /// Returns the compression method used for the sequence identifiers, or
+    /// `None` if the block does not contain identifiers.
+    #[must_use]
+    pub fn identifier_compression(&self) -> Option<IdentifierCompression> {
+        self.identifier_compression
+    }
+
+    /// Returns the compression method used for the auxiliary per-read data,
+    /// or `None` if the block does not contain such data.
+    #[must_use]
+    pub fn aux_data_compression(&self) -> Option<IdentifierCompression> {
+        self.aux_data_compression
+    }
+
+    /// Returns the name of the container member starting with this block, or
+    /// `None` if the block continues the previous member (or the file is not
+    /// a container).
+    #[must_use]
+    pub fn member(&self) -> Option<&str> {
+        self.member.as_deref()
+    }
+
+    /// Returns the key of the group all the sequences in this block belong
+    /// to, or `None` if the blocks have not been grouped with
+    /// [`IdnCompressorParamsBuilder::group_blocks_by()`](crate::idn::compressor::IdnCompressorParamsBuilder::group_blocks_by).
+    /// The reads of such files are decompressed block by block, so they are
+    /// not in their original order.
+    #[must_use]
+    pub fn group(&self) -> Option<&str> {
+        self.group.as_deref()
+    }
+}
+
+/// A single named member of an IDN container file (see
+/// [`IdnCompressor::start_member()`](crate::idn::compressor::IdnCompressor::start_member)).
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct IdnMember {
+    name: String,
+    sequence_num: usize,
+    block_num: usize,
+    offset: Option<u64>,
+}
+
+impl IdnMember {
+    /// Returns the name of the member.
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the number of sequences stored in the member.
+    #[must_use]
+    pub fn sequence_num(&self) -> usize {
+        self.sequence_num
+    }
+
+    /// Returns the number of blocks the member is stored in.
+    #[must_use]
+    pub fn block_num(&self) -> usize {
+        self.block_num
+    }
+
+    /// Returns the position of the first block of the member, relative to the
+    /// start of the file, or `None` if the file does not contain the member
+    /// index (i.e. it has been created by an older version).
+    #[must_use]
+    pub fn offset(&self) -> Option<u64> {
+        self.offset
+    }
+}
+
+impl TryFrom<IdnMemberIndexEntry> for IdnMember {
+    type Error = IdnDecompressorError;
+
+    fn try_from(entry: IdnMemberIndexEntry) -> Result<Self, Self::Error> {
+        Ok(Self {
+            name: String::from_utf8(entry.name)?,
+            sequence_num: entry.sequence_num as usize,
+            block_num: entry.block_num as usize,
+            offset: Some(entry.offset),
+        })
+    }
+}
+
+/// Structural information about an IDN file, obtained without decompressing
+/// any of the sequences.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct IdnFileInfo {
+    version: u8,
+    model_identifiers: Vec<ModelIdentifier>,
+    transforms: Vec<TransformInfo>,
+    quality_alphabet: QualityAlphabet,
+    blocks: Vec<IdnBlockInfo>,
+    source_digest: Option<SourceDigest>,
+    member_index: Option<Vec<IdnMember>>,
+}
+
+impl IdnFileInfo {
+    /// Returns the IDN format version of the file.
+    #[must_use]
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    /// Returns the identifiers of the models used by the file, in the order
+    /// they are referenced by the "switch model" slices.
+    #[must_use]
+    pub fn model_identifiers(&self) -> &[ModelIdentifier] {
+        &self.model_identifiers
+    }
+
+    /// Returns the transforms that have been applied to the sequences before
+    /// compressing them, in the order they have been applied (see
+    /// [`SequenceTransform`](crate::idn::transform::SequenceTransform)).
+    #[must_use]
+    pub fn transforms(&self) -> &[TransformInfo] {
+        &self.transforms
+    }
+
+    /// Returns the [`QualityAlphabet`] the quality scores of the file are
+    /// encoded with (see
+    /// [`IdnCompressorParamsBuilder::quality_alphabet()`](crate::idn::compressor::IdnCompressorParamsBuilder::quality_alphabet)).
+    #[must_use]
+    pub fn quality_alphabet(&self) -> &QualityAlphabet {
+        &self.quality_alphabet
+    }
+
+    /// Returns the information about all the blocks in the file, excluding the
+    /// end-of-file block.
+    #[must_use]
+    pub fn blocks(&self) -> &[IdnBlockInfo] {
+        &self.blocks
+    }
+
+    /// Returns the total number of sequences stored in the file.
+    #[must_use]
+    pub fn sequence_num(&self) -> usize {
+        self.blocks.iter().map(IdnBlockInfo::sequence_num).sum()
+    }
+
+    /// Returns the digest of the original input data, if it has been stored
+    /// in the file (see
+    /// [`IdnCompressorParamsBuilder::source_hasher()`](crate::idn::compressor::IdnCompressorParamsBuilder::source_hasher)).
+    #[must_use]
+    pub fn source_digest(&self) -> Option<SourceDigest> {
+        self.source_digest
+    }
+
+    /// Returns the members stored in the file, in the order they have been
+    /// added. The list is empty if the file is not a container. The sequences
+    /// stored before the first member (if any) are not included.
+    ///
+    /// The members are taken from the member index stored at the end of the
+    /// file; for the files without the index, they are reconstructed from the
+    /// block headers.
+    #[must_use]
+    pub fn members(&self) -> Vec<IdnMember> {
+        if let Some(member_index) = &self.member_index {
+            return member_index.clone();
+        }
+
+        let mut members: Vec<IdnMember> = Vec::new();
+
+        for block in &self.blocks {
+            if let Some(name) = &block.member {
+                members.push(IdnMember {
+                    name: name.clone(),
+                    sequence_num: block.sequence_num,
+                    block_num: 1,
+                    offset: None,
+                });
+            } else if let Some(member) = members.last_mut() {
+                member.sequence_num += block.sequence_num;
+                member.block_num += 1;
+            }
+        }
+
+        members
+    }
+}
+
+/// Reads the structure of an IDN file: the header, the metadata, and the
+/// headers of all blocks and slices. The sequence data is skipped, so this is
+/// much faster than decompressing the file and does not require any models.
+///
+/// # Examples
+/// ```
+/// use idencomp::idn::compressor::IdnCompressor;
+/// use idencomp::idn::decompressor::IdnDecompressorError;
+/// use idencomp::idn::inspector::inspect;
+///
+/// let mut vec = Vec::new();
+/// IdnCompressor::new(&mut vec).finish().unwrap();
+///
+/// let info = inspect(vec.as_slice())?;
+/// assert_eq!(info.version(), 1);
+/// assert_eq!(info.blocks().len(), 0);
+/// assert_eq!(info.source_digest(), None);
+/// assert!(info.transforms().is_empty());
+/// assert!(info.quality_alphabet().is_full());
+///
+/// # Ok::<(), IdnDecompressorError>(())
+/// ```
+pub fn inspect<R: Read>(reader: R) -> IdnDecompressResult<IdnFileInfo> {
+    let mut reader = NoSeek::new(reader);
+
+    let FileStart {
+        version,
+        model_identifiers,
+        transforms,
+        quality_alphabet,
+        source_digest,
+    } = read_file_start(&mut reader)?;
+
+    let mut blocks = Vec::new();
+    while let Some((block_header, data)) = read_block(&mut reader)? {
+        blocks.push(inspect_block(&block_header, &data)?);
+    }
+    // The digest is patched into the leading metadata if the file was written
+    // to a seekable output, and is appended after the blocks otherwise
+    let trailing = read_trailing_metadata(&mut reader)?;
+    let source_digest = source_digest.or(trailing.source_digest);
+
+    Ok(IdnFileInfo {
+        version,
+        model_identifiers,
+        transforms,
+        quality_alphabet,
+        blocks,
+        source_digest,
+        member_index: trailing.member_index,
+    })
+}
+
+/// The information stored in the IDN header and metadata.
+#[derive(Debug)]
+pub(super) struct FileStart {
+    pub version: u8,
+    pub model_identifiers: Vec<ModelIdentifier>,
+    pub transforms: Vec<TransformInfo>,
+    pub quality_alphabet: QualityAlphabet,
+    pub source_digest: Option<SourceDigest>,
+}
+
+/// Reads the IDN header and metadata.
+pub(super) fn read_file_start<R: Read>(reader: &mut NoSeek<R>) -> IdnDecompressResult<FileStart> {
+    let header = IdnHeader::read(reader)?;
+    debug!("Read IDN header: {:?}", header);
+    if header.version != 1 {
+        return Err(IdnDecompressorError::InvalidVersion(header.version));
+    }
+
+    let mut model_identifiers = Vec::new();
+    let mut transforms = Vec::new();
+    let mut quality_alphabet = QualityAlphabet::full();
+    let mut source_digest = None;
+    let metadata_header = IdnMetadataHeader::read(reader)?;
+    for _ in 0..metadata_header.item_num {
+        let item = IdnMetadataItem::read_forward(reader)?;
+        match item {
+            IdnMetadataItem::Models(models_metadata) => model_identifiers.extend(
+                models_metadata
+                    .model_identifiers
+                    .iter()
+                    .map(ModelIdentifier::from),
+            ),
+            IdnMetadataItem::SourceDigest(metadata) => {
+                source_digest = Some(SourceDigest::from(metadata.sha256));
+            }
+            IdnMetadataItem::Transforms(transforms_metadata) => transforms.extend(
+                transforms_metadata
+                    .transforms
+                    .iter()
+                    .map(TransformInfo::from_metadata),
+            ),
+            IdnMetadataItem::QualityAlphabet(alphabet_metadata) => {
+                quality_alphabet = read_quality_alphabet(&alphabet_metadata)?;
+            }
+            IdnMetadataItem::MemberIndex(_) => {}
+        }
+    }
+
+    Ok(FileStart {
+        version: header.version,
+        model_identifiers,
+        transforms,
+        quality_alphabet,
+        source_digest,
+    })
+}
+
+/// Converts the quality alphabet metadata item to a [`QualityAlphabet`],
+/// validating its values.
+pub(super) fn read_quality_alphabet(
+    metadata: &IdnQualityAlphabetMetadata,
+) -> IdnDecompressResult<QualityAlphabet> {
+    if metadata.values.is_empty() {
+        return Err(IdnDecompressorError::EmptyQualityAlphabet);
+    }
+
+    let values = metadata
+        .values
+        .iter()
+        .map(|&value| FastqQualityScore::try_new(value))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(QualityAlphabet::new(values))
+}
+
+/// The metadata placed after the end-of-file block.
+#[derive(Debug, Default)]
+pub(super) struct TrailingMetadata {
+    pub source_digest: Option<SourceDigest>,
+    pub member_index: Option<Vec<IdnMember>>,
+}
+
+/// Reads the optional metadata placed after the end-of-file block.
+pub(super) fn read_trailing_metadata<R: Read>(
+    reader: &mut NoSeek<R>,
+) -> IdnDecompressResult<TrailingMetadata> {
+    let mut item_num = [0];
+    if reader.read(&mut item_num)? == 0 {
+        return Ok(TrailingMetadata::default());
+    }
+
+    let mut trailing = TrailingMetadata::default();
+    for _ in 0..item_num[0] {
+        let item = IdnMetadataItem::read_forward(reader)?;
+        match item {
+            IdnMetadataItem::SourceDigest(metadata) => {
+                trailing.source_digest = Some(SourceDigest::from(metadata.sha256));
+            }
+            IdnMetadataItem::MemberIndex(metadata) => {
+                let members = metadata
+                    .members
+                    .into_iter()
+                    .map(IdnMember::try_from)
+                    .collect::<IdnDecompressResult<_>>()?;
+                trailing.member_index = Some(members);
+            }
+            _ => {}
+        }
+    }
+
+    Ok(trailing)
+}
+
+/// Reads the next block header and the block data, or returns `None` if the
+/// end-of-file block has been reached.
+pub(super) fn read_block<R: Read>(
+    reader: &mut NoSeek<R>,
+) -> IdnDecompressResult<Option<(IdnBlockHeader, Vec<u8>)>> {
+    let block_header = IdnBlockHeader::read(reader)?;
+    if block_header.length == 0 {
+        return Ok(None);
+    }
+
+    let mut data = vec![0; block_header.length as usize];
+    reader.read_exact(&mut data)?;
+
+    Ok(Some((block_header, data)))
+}
+
+/// Reads the slice headers of a single block, skipping the slice contents.
+pub(super) fn inspect_block(
+    header: &IdnBlockHeader,
+    data: &[u8],
+) -> IdnDecompressResult<IdnBlockInfo> {
+    let data_len = data.len() as u64;
+    let mut data = Cursor::new(data);
+
+    let mut info = IdnBlockInfo {
+        length: header.length,
+        seq_checksum: header.seq_checksum,
+        sequence_num: 0,
+        total_seq_len: 0,
+        sequence_bytes: 0,
+        identifier_bytes: 0,
+        aux_data_bytes: 0,
+        model_switch_num: 0,
+        duplicate_num: 0,
+        packed: false,
+        raw: false,
+        n_q_score: None,
+        identifier_compression: None,
+        aux_data_compression: None,
+        member: None,
+        group: None,
+    };
+
+    while data.position() < data_len {
+        let slice_header = IdnSliceHeader::read(&mut data)?;
+        match slice_header {
+            IdnSliceHeader::Identifiers(header) => {
+                info.identifier_compression = Some((&header.compression).into());
+                info.identifier_bytes += header.length as u64;
+                data.seek(SeekFrom::Current(header.length as i64))?;
+            }
+            IdnSliceHeader::AuxData(header) => {
+                info.aux_data_compression = Some((&header.compression).into());
+                info.aux_data_bytes += header.length as u64;
+                data.seek(SeekFrom::Current(header.length as i64))?;
+            }
+            // The separator texts are a part of the sequence titles
+            IdnSliceHeader::SeparatorText(header) => {
+                info.identifier_bytes += header.length as u64;
+                data.seek(SeekFrom::Current(header.length as i64))?;
+            }
+            IdnSliceHeader::SwitchModel(_) | IdnSliceHeader::SwitchModelAt(_) => {
+                info.model_switch_num += 1;
+            }
+            IdnSliceHeader::Sequence(header) => {
+                info.sequence_num += 1;
+                info.total_seq_len += header.seq_len as u64;
+                info.sequence_bytes += header.length as u64;
+                data.seek(SeekFrom::Current(header.length as i64))?;
+            }
+            IdnSliceHeader::Duplicate(header) => {
+                info.sequence_num += 1;
+                info.duplicate_num += 1;
+                info.total_seq_len += header.seq_len as u64;
+                info.sequence_bytes += header.length as u64;
+                data.seek(SeekFrom::Current(header.length as i64))?;
+            }
+            IdnSliceHeader::Raw(_) => {
+                info.raw = true;
+            }
+            IdnSliceHeader::Packed(_) => {
+                info.packed = true;
+            }
+            IdnSliceHeader::NQualityScore(header) => {
+                info.n_q_score = Some(FastqQualityScore::try_new(header.q_score)?);
+            }
+            IdnSliceHeader::Member(header) => {
+                info.member = Some(String::from_utf8(header.name)?);
+            }
+            IdnSliceHeader::Group(header) => {
+                info.group = Some(String::from_utf8(header.key)?);
+            }
+        }
+    }
+
+    Ok(info)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::_internal_test_data::{SEQ_1K_READS, SEQ_1M_IDN};
+    use crate::idn::compressor::{IdnCompressor, IdnCompressorParams};
+    use crate::idn::decompressor::IdnDecompressorError;
+    use crate::idn::inspector::{inspect, is_idn, IdentifierCompression};
+
+    #[test]
+    fn test_inspect_1m() {
+        let info = inspect(SEQ_1M_IDN).unwrap();
+
+        assert_eq!(info.version(), 1);
+        assert_eq!(info.sequence_num(), 1);
+        assert_eq!(info.blocks()[0].total_seq_len(), 1_000_000 / 2);
+    }
+
+    #[test]
+    fn test_inspect_1k_reads() {
+        let mut data = Vec::new();
+        let params = IdnCompressorParams::builder().fast(true).build().unwrap();
+        let mut compressor = IdnCompressor::with_params(&mut data, params);
+        for sequence in SEQ_1K_READS.iter() {
+            compressor.add_sequence(sequence.clone()).unwrap();
+        }
+        compressor.finish().unwrap();
+
+        let info = inspect(data.as_slice()).unwrap();
+        assert_eq!(info.sequence_num(), SEQ_1K_READS.len());
+        let total_len: usize = SEQ_1K_READS.iter().map(|seq| seq.len()).sum();
+        let info_total_len: u64 = info.blocks().iter().map(|b| b.total_seq_len()).sum();
+        assert_eq!(info_total_len, total_len as u64);
+        assert_eq!(
+            info.blocks()[0].identifier_compression(),
+            Some(IdentifierCompression::Deflate)
+        );
+        let block = &info.blocks()[0];
+        assert!(block.sequence_bytes() > 0);
+        assert!(block.identifier_bytes() > 0);
+        assert_eq!(block.aux_data_bytes(), 0);
+        assert!(block.sequence_bytes() + block.identifier_bytes() <= block.length() as u64);
+        assert!(is_idn(&data));
+    }
+
+    #[test]
+    fn test_inspect_invalid_version() {
+        let data = b"IDENCOMP\x02";
+
+        let result = inspect(data.as_slice());
+        assert!(matches!(
+            result,
+            Err(IdnDecompressorError::InvalidVersion(2))
+        ));
+    }
+}
@@ -1,21 +1,54 @@
+mod adaptive_effort;
 mod common;
 /// IDN file compressor.
 pub mod compressor;
 mod compressor_block;
 mod compressor_initializer;
+/// Concatenating multiple IDN files without recompressing them.
+pub mod concat;
+/// Collecting the statistics of the model contexts used during compression.
+pub mod context_usage;
 mod data;
 /// IDN file decompressor.
 pub mod decompressor;
 mod decompressor_block;
+/// Reporting how each of the sequences has been encoded during compression.
+pub mod encode_info;
+/// Explaining how each of the sequences has been compressed, to diagnose the
+/// files that do not compress well.
+pub mod explain;
+/// Reading the structure of IDN files without decompressing them.
+pub mod inspector;
 mod model_chooser;
+/// Evaluating how well the models compress a sample of sequences.
+pub mod model_evaluation;
 /// The collection of models that can be used when compressing or decompressing
 /// an IDN file.
 pub mod model_provider;
-/// Wrapper over a [`std::io::Read`] or [`std::io::Write`] object that provides
-/// a dummy [`std::io::Seek`] implementation.
+/// Strategies of choosing the model each of the sequences is encoded with.
+pub mod model_selection;
+/// Wrapper over a [`std::io::Read`] or [`std::io::Write`] object that tracks
+/// the stream position and provides a [`std::io::Seek`] implementation, either
+/// a dummy one or one passing the seeks through to a seekable object.
 pub mod no_seek;
+mod packing;
+mod raw;
+/// Recompressing IDN files with different compression parameters.
+pub mod recompress;
+/// Computing the digest of the original FASTQ data, which can be stored in
+/// IDN files.
+pub mod source_digest;
+/// Splitting IDN files into multiple parts without recompressing them.
+pub mod split;
 #[cfg(test)]
 mod tests;
 mod thread_pool;
+mod time_budget;
+/// Transforming the sequences before they are compressed, e.g. quality score
+/// binning.
+pub mod transform;
+/// Reporting the non-fatal issues encountered during compression and
+/// decompression.
+pub mod warning;
 mod writer_block;
 mod writer_idn;
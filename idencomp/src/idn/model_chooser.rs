@@ -1,30 +1,52 @@
+use std::slice;
+
 use itertools::Itertools;
 use log::debug;
 
 use crate::clustering::{ClusterCostCalculator, Clustering};
 use crate::compressor::RansCompressor;
-use crate::context_spec::ContextSpecGenerator;
+use crate::context_spec::{ContextSpec, ContextSpecGenerator, ContextSpecType};
 use crate::fastq::{FastqQualityScore, FastqSequence};
-use crate::idn::compressor::{CompressionQuality, IdnCompressorOptions};
-use crate::model::ModelIdentifier;
-use crate::sequence::{Acid, Symbol};
-use crate::sequence_compressor::{AcidRansEncModel, QScoreRansEncModel, RansEncModel};
+use crate::idn::common::opaque_debug;
+use crate::idn::compressor::{CompressionQuality, IdnCompressResult, IdnCompressorOptions};
+use crate::idn::model_selection::ModelCandidates;
+use crate::model::{ModelIdentifier, ModelType};
+use crate::quality_alphabet::QualityAlphabet;
+use crate::sequence::Acid;
+use crate::sequence_compressor::{
+    AcidRansEncModel, ModelSegment, QScoreRansEncModel, RansEncModel,
+};
 
 #[derive(Debug)]
 pub(super) struct ModelChooser {
     model_tester: ModelTester,
     clustering: Clustering,
+    /// The number of acid and quality score model selections made within the
+    /// current block.
+    selection_nums: [usize; 2],
 }
 
 impl ModelChooser {
+    /// Creates a new `ModelChooser` with the buffers preallocated for testing
+    /// the models on sequences of at most `max_seq_len` length, with the
+    /// quality scores encoded as their indices in `quality_alphabet`.
     #[must_use]
-    pub fn new() -> Self {
+    pub fn new(max_seq_len: usize, quality_alphabet: QualityAlphabet) -> Self {
         Self {
-            model_tester: ModelTester::new(),
+            model_tester: ModelTester::new(max_seq_len, quality_alphabet),
             clustering: Clustering::new(),
+            selection_nums: [0; 2],
         }
     }
 
+    /// Resets the selection counters passed to the
+    /// [`ModelSelectionStrategy`](crate::idn::model_selection::ModelSelectionStrategy),
+    /// so that the selections only depend on the block being compressed.
+    pub fn start_block(&mut self) {
+        self.selection_nums = [0; 2];
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
     pub fn get_best_acid_models<'a>(
         &mut self,
         sequences: &[FastqSequence],
@@ -47,6 +69,7 @@ impl ModelChooser {
         }
     }
 
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
     pub fn get_best_q_score_models<'a>(
         &mut self,
         sequences: &[FastqSequence],
@@ -75,9 +98,9 @@ impl ModelChooser {
         options.quality >= Self::CLUSTERING_THRESHOLD
     }
 
-    fn cluster_models<'a, const SYMBOLS_NUM: usize>(
+    fn cluster_models(
         &mut self,
-        models: &[&'a RansEncModel<SYMBOLS_NUM>],
+        models: &[&RansEncModel],
         sequences: &[FastqSequence],
         model_num: usize,
     ) -> Vec<ModelIdentifier> {
@@ -100,9 +123,9 @@ impl ModelChooser {
             .collect()
     }
 
-    fn get_model_ranking<'a, const SYMBOLS_NUM: usize>(
+    fn get_model_ranking(
         &mut self,
-        models: &[&'a RansEncModel<SYMBOLS_NUM>],
+        models: &[&RansEncModel],
         sequences: &[FastqSequence],
         model_num: usize,
     ) -> Vec<ModelIdentifier> {
@@ -137,102 +160,265 @@ impl ModelChooser {
         ranking_sorted.collect()
     }
 
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
     pub fn get_best_acid_model_for<'a>(
         &mut self,
         sequence: &FastqSequence,
         options: &'a IdnCompressorOptions,
         current_model: Option<&ModelIdentifier>,
-    ) -> (usize, &'a AcidRansEncModel) {
+    ) -> IdnCompressResult<(Option<usize>, &'a AcidRansEncModel)> {
         debug!(
             "Calculating the best acid model for `{}`",
             sequence.identifier()
         );
         let models = options.model_provider.acid_enc_models();
-        self.get_best_model_for(sequence, models, current_model)
+        self.get_best_model_for(
+            slice::from_ref(sequence),
+            None,
+            models,
+            current_model,
+            options,
+        )
     }
 
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
     pub fn get_best_q_score_model_for<'a>(
         &mut self,
         sequence: &FastqSequence,
         options: &'a IdnCompressorOptions,
         current_model: Option<&ModelIdentifier>,
-    ) -> (usize, &'a QScoreRansEncModel) {
+    ) -> IdnCompressResult<(Option<usize>, &'a QScoreRansEncModel)> {
         debug!(
             "Calculating the best quality score model for `{}`",
             sequence.identifier()
         );
         let models = options.model_provider.q_score_enc_models();
-        self.get_best_model_for(sequence, models, current_model)
+        self.get_best_model_for(
+            slice::from_ref(sequence),
+            None,
+            models,
+            current_model,
+            options,
+        )
     }
 
-    fn get_best_model_for<'a, const SYMBOLS_NUM: usize, T>(
+    /// Returns the quality score models that are the best for each of the
+    /// `window`-long parts of given sequence, along with the total length the
+    /// quality scores would be compressed to, if known for all the parts. The
+    /// consecutive parts with the same model are merged into a single
+    /// segment.
+    ///
+    /// The models are tested on each part with the contexts it is actually
+    /// compressed with, i.e. the ones generated from the start of the whole
+    /// sequence.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn get_best_q_score_model_segments_for<'a>(
         &mut self,
         sequence: &FastqSequence,
+        window: usize,
+        options: &'a IdnCompressorOptions,
+        current_model: Option<&ModelIdentifier>,
+    ) -> IdnCompressResult<(Option<usize>, Vec<ModelSegment<'a, QScoreRansEncModel>>)> {
+        debug!(
+            "Calculating the best quality score models for `{}` with window {}",
+            sequence.identifier(),
+            window
+        );
+
+        let mut total_bytes = Some(0);
+        let mut segments: Vec<ModelSegment<'a, QScoreRansEncModel>> = Vec::new();
+        let mut current_model = current_model.cloned();
+        let mut part_specs = PartSpecs::new(
+            options
+                .model_provider
+                .q_score_enc_models()
+                .map(RansEncModel::context_spec_type),
+            sequence.len(),
+        );
+        for start in (0..sequence.len()).step_by(window) {
+            let end = (start + window).min(sequence.len());
+            let part = FastqSequence::new(
+                "",
+                sequence.acids()[start..end].to_vec(),
+                sequence.quality_scores()[start..end].to_vec(),
+            );
+            part_specs.advance(part.acids(), part.quality_scores());
+
+            let models = options.model_provider.q_score_enc_models();
+            let (bytes, model) = self.get_best_model_for(
+                slice::from_ref(&part),
+                Some(&part_specs),
+                models,
+                current_model.as_ref(),
+                options,
+            )?;
+            total_bytes = total_bytes.zip(bytes).map(|(total, bytes)| total + bytes);
+
+            if current_model.as_ref() != Some(model.identifier()) || segments.is_empty() {
+                segments.push((start, model));
+                current_model = Some(model.identifier().clone());
+            }
+        }
+
+        Ok((total_bytes, segments))
+    }
+
+    /// Returns the quality score model that is the best for all `sequences`
+    /// taken together, along with the total length they would be compressed
+    /// to, if known.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn get_best_q_score_model_for_group<'a>(
+        &mut self,
+        sequences: &[FastqSequence],
+        options: &'a IdnCompressorOptions,
+        current_model: Option<&ModelIdentifier>,
+    ) -> IdnCompressResult<(Option<usize>, &'a QScoreRansEncModel)> {
+        debug!(
+            "Calculating the best quality score model for a group of {} sequences",
+            sequences.len()
+        );
+        let models = options.model_provider.q_score_enc_models();
+        self.get_best_model_for(sequences, None, models, current_model, options)
+    }
+
+    /// Chooses the model for given sequences with the
+    /// [`ModelSelectionStrategy`](crate::idn::model_selection::ModelSelectionStrategy)
+    /// set in the options, returning it along with the length the sequences
+    /// would be compressed to, if the strategy has computed it.
+    fn get_best_model_for<'a, T>(
+        &mut self,
+        sequences: &[FastqSequence],
+        part_specs: Option<&PartSpecs>,
         models: T,
         current_model: Option<&ModelIdentifier>,
-    ) -> (usize, &'a RansEncModel<SYMBOLS_NUM>)
+        options: &IdnCompressorOptions,
+    ) -> IdnCompressResult<(Option<usize>, &'a RansEncModel)>
     where
-        T: Iterator<Item = &'a RansEncModel<SYMBOLS_NUM>>,
+        T: Iterator<Item = &'a RansEncModel>,
     {
-        const SWITCH_MODEL_PENALTY: usize = 2;
-
-        models
-            .map(|model| {
-                let len = self.model_tester.compute_size(sequence, model);
-                let penalty = if Some(model.identifier()) != current_model {
-                    SWITCH_MODEL_PENALTY
-                } else {
-                    0
-                };
-                debug!(
-                    "Length with model {}: {} + {} (penalty)",
-                    model.identifier(),
-                    len,
-                    penalty
-                );
+        let models: Vec<&RansEncModel> = models.collect();
+        assert!(!models.is_empty(), "No models provided");
+        let selection_num = match models[0].model_type() {
+            ModelType::Acids => &mut self.selection_nums[0],
+            ModelType::QualityScores => &mut self.selection_nums[1],
+        };
+        let selection_index = *selection_num;
+        *selection_num += 1;
 
-                (len + penalty, model)
-            })
-            .min_by(|(len_1, _), (len_2, _)| len_1.cmp(len_2))
-            .expect("No quality models provided")
+        let mut candidates = ModelCandidates::new(
+            &mut self.model_tester,
+            models,
+            sequences,
+            part_specs,
+            current_model,
+            selection_index,
+        );
+        let index = options.model_selection.select(&mut candidates);
+        candidates.into_selected(index)
     }
 }
 
+/// The context specs of consecutive parts of a sequence, for each of the
+/// context spec types of the models tested on them. The specs are generated
+/// from the start of the whole sequence, the same way as when the sequence is
+/// compressed, rather than from the start of each part.
+pub(super) struct PartSpecs {
+    generators: Vec<(
+        ContextSpecType,
+        Box<dyn ContextSpecGenerator>,
+        Vec<ContextSpec>,
+    )>,
+}
+
+// The generators do not implement `Debug`, and the specs are too long to be
+// printed anyway
+opaque_debug!(PartSpecs);
+
+impl PartSpecs {
+    /// Creates a new `PartSpecs` for a sequence of given length, with a
+    /// generator for each of the distinct `spec_types`.
+    #[must_use]
+    pub fn new<I: IntoIterator<Item = ContextSpecType>>(spec_types: I, seq_len: usize) -> Self {
+        let generators = spec_types
+            .into_iter()
+            .unique()
+            .map(|spec_type| (spec_type, spec_type.generator(seq_len), Vec::new()))
+            .collect();
+
+        Self { generators }
+    }
+
+    /// Generates the specs of the next part of the sequence, which directly
+    /// follows the previous one, given its acids and quality scores.
+    pub fn advance(&mut self, acids: &[Acid], q_scores: &[FastqQualityScore]) {
+        for (_, generator, specs) in &mut self.generators {
+            specs.clear();
+            for (&acid, &q_score) in acids.iter().zip(q_scores) {
+                specs.push(generator.current_context());
+                generator.update(acid, q_score);
+            }
+        }
+    }
+
+    /// Returns the specs of the current part of the sequence for given
+    /// context spec type.
+    #[must_use]
+    pub fn get(&self, spec_type: ContextSpecType) -> &[ContextSpec] {
+        self.generators
+            .iter()
+            .find(|(generator_spec_type, _, _)| *generator_spec_type == spec_type)
+            .map(|(_, _, specs)| specs.as_slice())
+            .expect("No specs for given context spec type")
+    }
+}
+
+/// Computes the length given sequences would be compressed to with given
+/// models.
+///
+/// The quality scores missing in the quality alphabet are counted as the
+/// closest value of the alphabet, so that the models adapted to an alphabet
+/// can be tested on any data.
 #[derive(Debug)]
-struct ModelTester {
+pub(super) struct ModelTester {
     compressor: RansCompressor<1>,
+    quality_alphabet: QualityAlphabet,
 }
 
 impl ModelTester {
     #[must_use]
-    fn new() -> Self {
+    pub fn new(max_seq_len: usize, quality_alphabet: QualityAlphabet) -> Self {
         Self {
-            compressor: RansCompressor::new(),
+            compressor: RansCompressor::with_max_symbols(max_seq_len),
+            quality_alphabet,
         }
     }
 
     #[must_use]
-    fn compute_size<const SYMBOLS_NUM: usize>(
+    pub fn compute_size(&mut self, sequence: &FastqSequence, model: &RansEncModel) -> usize {
+        self.compute_size_of_prefix(sequence, sequence.len(), model)
+    }
+
+    /// Computes the length the first `max_symbols` symbols of given sequence
+    /// would be compressed to.
+    #[must_use]
+    pub fn compute_size_of_prefix(
         &mut self,
         sequence: &FastqSequence,
-        model: &RansEncModel<SYMBOLS_NUM>,
+        max_symbols: usize,
+        model: &RansEncModel,
     ) -> usize {
-        self.compressor.reset();
+        let len = sequence.len().min(max_symbols);
+        self.compressor.reset_for(len);
 
-        let acids = sequence.acids().iter().cloned();
-        let q_scores = sequence.quality_scores().iter().cloned();
+        let acids = sequence.acids()[..len].iter().cloned();
+        let q_scores = sequence.quality_scores()[..len].iter().cloned();
 
         let mut spec_generator: Box<dyn ContextSpecGenerator> =
             model.context_spec_type().generator(sequence.len());
 
         for (acid, q_score) in acids.zip(q_scores) {
             let spec = spec_generator.current_context();
-            let symbol_num = match SYMBOLS_NUM {
-                Acid::SIZE => acid as usize,
-                FastqQualityScore::SIZE => q_score.get(),
-                _ => unimplemented!(),
-            };
-
+            let symbol_num = self.symbol_num(model, acid, q_score);
             self.compressor.put(model.context_for(spec), symbol_num);
 
             spec_generator.update(acid, q_score);
@@ -241,12 +427,82 @@ impl ModelTester {
 
         self.compressor.data().len()
     }
+
+    /// Computes the length the first `max_symbols` symbols of given part of a
+    /// sequence would be compressed to, given the context specs of its
+    /// symbols.
+    #[must_use]
+    pub fn compute_size_of_part_prefix(
+        &mut self,
+        part: &FastqSequence,
+        specs: &[ContextSpec],
+        max_symbols: usize,
+        model: &RansEncModel,
+    ) -> usize {
+        let len = part.len().min(max_symbols);
+        self.compressor.reset_for(len);
+
+        let acids = part.acids()[..len].iter().cloned();
+        let q_scores = part.quality_scores()[..len].iter().cloned();
+        for ((acid, q_score), &spec) in acids.zip(q_scores).zip(specs) {
+            let symbol_num = self.symbol_num(model, acid, q_score);
+            self.compressor.put(model.context_for(spec), symbol_num);
+        }
+        self.compressor.flush();
+
+        self.compressor.data().len()
+    }
+
+    fn symbol_num(&self, model: &RansEncModel, acid: Acid, q_score: FastqQualityScore) -> usize {
+        match model.model_type() {
+            ModelType::Acids => acid as usize,
+            ModelType::QualityScores => self
+                .quality_alphabet
+                .index_of(q_score)
+                .unwrap_or_else(|| self.quality_alphabet.nearest_index(q_score)),
+        }
+    }
 }
 
-impl<const SYMBOLS_NUM: usize> ClusterCostCalculator<FastqSequence, &RansEncModel<SYMBOLS_NUM>>
-    for &mut ModelTester
-{
-    fn cost_for(&mut self, value: &FastqSequence, centroid: &&RansEncModel<SYMBOLS_NUM>) -> u32 {
+impl ClusterCostCalculator<FastqSequence, &RansEncModel> for &mut ModelTester {
+    fn cost_for(&mut self, value: &FastqSequence, centroid: &&RansEncModel) -> u32 {
         self.compute_size(value, centroid) as u32
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::_internal_test_data::SEQ_1K_READS;
+    use crate::context_spec::ContextSpecType;
+    use crate::idn::model_chooser::PartSpecs;
+
+    #[test]
+    fn test_part_specs_continue_across_parts() {
+        let sequence = &SEQ_1K_READS[0];
+        let spec_type = ContextSpecType::Generic1Acids3QScores2PosBits;
+        let mut generator = spec_type.generator(sequence.len());
+        let expected: Vec<_> = sequence
+            .acids()
+            .iter()
+            .zip(sequence.quality_scores())
+            .map(|(&acid, &q_score)| {
+                let spec = generator.current_context();
+                generator.update(acid, q_score);
+                spec
+            })
+            .collect();
+
+        let mut part_specs = PartSpecs::new([spec_type, spec_type], sequence.len());
+        let mut specs = Vec::new();
+        for start in (0..sequence.len()).step_by(7) {
+            let end = (start + 7).min(sequence.len());
+            part_specs.advance(
+                &sequence.acids()[start..end],
+                &sequence.quality_scores()[start..end],
+            );
+            specs.extend_from_slice(part_specs.get(spec_type));
+        }
+
+        assert_eq!(specs, expected);
+    }
+}
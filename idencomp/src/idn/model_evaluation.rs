@@ -0,0 +1,188 @@
+use crate::fastq::FastqSequence;
+use crate::idn::model_chooser::ModelTester;
+use crate::idn::model_provider::ModelProvider;
+use crate::model::{CompressionRate, ModelIdentifier, ModelType};
+use crate::quality_alphabet::QualityAlphabet;
+use crate::sequence_compressor::RansEncModel;
+
+/// The result of compressing a sample of sequences with a single model.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelEvaluation {
+    identifier: ModelIdentifier,
+    model_type: ModelType,
+    estimated_rate: CompressionRate,
+    symbol_num: usize,
+    compressed_bytes: usize,
+}
+
+impl ModelEvaluation {
+    /// Returns the identifier of the evaluated model.
+    #[must_use]
+    pub fn identifier(&self) -> &ModelIdentifier {
+        &self.identifier
+    }
+
+    /// Returns the type of the evaluated model.
+    #[must_use]
+    pub fn model_type(&self) -> ModelType {
+        self.model_type
+    }
+
+    /// Returns the compression rate the model is expected to achieve, i.e.
+    /// [`Model::rate()`](crate::model::Model::rate), which is computed from the
+    /// data the model has been generated from.
+    #[must_use]
+    pub fn estimated_rate(&self) -> CompressionRate {
+        self.estimated_rate
+    }
+
+    /// Returns the compression rate the model has actually achieved on the
+    /// sample.
+    #[must_use]
+    pub fn actual_rate(&self) -> CompressionRate {
+        if self.symbol_num == 0 {
+            return CompressionRate::ZERO;
+        }
+
+        CompressionRate::new((self.compressed_bytes * 8) as f32 / self.symbol_num as f32)
+    }
+
+    /// Returns the number of symbols (acids or quality scores) in the sample.
+    #[must_use]
+    pub fn symbol_num(&self) -> usize {
+        self.symbol_num
+    }
+
+    /// Returns the number of bytes the sample has been compressed to with the
+    /// model.
+    #[must_use]
+    pub fn compressed_bytes(&self) -> usize {
+        self.compressed_bytes
+    }
+}
+
+/// Compresses given sample of sequences with each of the models of the model
+/// provider separately, and returns the results in the order the models are
+/// stored in the provider.
+///
+/// Only the symbols encoded with each model are counted, i.e. the acid models
+/// compress the acids and the quality score models compress the quality
+/// scores of the sequences. The identifiers are not included. The quality
+/// score models adapted to a reduced
+/// [`QualityAlphabet`](crate::quality_alphabet::QualityAlphabet) are skipped.
+///
+/// # Examples
+/// ```
+/// use idencomp::fastq::reader::FastqReader;
+/// use idencomp::idn::model_evaluation::evaluate_models;
+/// use idencomp::idn::model_provider::ModelProvider;
+///
+/// let sequences = FastqReader::new(b"@SEQ1\nACGT\n+\n!!!!\n".as_slice())
+///     .into_iter()
+///     .collect::<Result<Vec<_>, _>>()?;
+/// let evaluations = evaluate_models(ModelProvider::with_empty_models(), &sequences);
+/// assert_eq!(evaluations.len(), 2);
+/// assert!(evaluations
+///     .iter()
+///     .all(|evaluation| evaluation.symbol_num() == 4));
+///
+/// # Ok::<(), idencomp::Error>(())
+/// ```
+#[must_use]
+pub fn evaluate_models(
+    model_provider: ModelProvider,
+    sequences: &[FastqSequence],
+) -> Vec<ModelEvaluation> {
+    let quality_alphabet = QualityAlphabet::full();
+    let mut model_provider = model_provider.with_quality_alphabet(&quality_alphabet);
+    model_provider.preprocess_compressor_models();
+
+    let max_seq_len = sequences.iter().map(|sequence| sequence.len()).max();
+    let mut model_tester = ModelTester::new(max_seq_len.unwrap_or(0), quality_alphabet);
+
+    let acid_evaluations = model_provider
+        .acid_enc_models()
+        .map(|model| evaluate_model(&mut model_tester, &model_provider, model, sequences))
+        .collect::<Vec<_>>();
+    let q_score_evaluations = model_provider
+        .q_score_enc_models()
+        .map(|model| evaluate_model(&mut model_tester, &model_provider, model, sequences));
+
+    let mut evaluations = acid_evaluations;
+    evaluations.extend(q_score_evaluations);
+    evaluations.sort_by_key(|evaluation| model_provider.index_of(evaluation.identifier()));
+    evaluations
+}
+
+#[must_use]
+fn evaluate_model(
+    model_tester: &mut ModelTester,
+    model_provider: &ModelProvider,
+    enc_model: &RansEncModel,
+    sequences: &[FastqSequence],
+) -> ModelEvaluation {
+    let model = &model_provider[model_provider.index_of(enc_model.identifier())];
+    let compressed_bytes = sequences
+        .iter()
+        .map(|sequence| model_tester.compute_size(sequence, enc_model))
+        .sum();
+    let symbol_num = sequences.iter().map(|sequence| sequence.len()).sum();
+
+    ModelEvaluation {
+        identifier: model.identifier().clone(),
+        model_type: model.model_type(),
+        estimated_rate: model.rate(),
+        symbol_num,
+        compressed_bytes,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::_internal_test_data::{
+        TEST_ACID_MODEL_PREFER_A, TEST_ACID_MODEL_PREFER_C, TEST_SEQUENCE_PREFER_A,
+    };
+    use crate::idn::model_evaluation::evaluate_models;
+    use crate::idn::model_provider::ModelProvider;
+    use crate::model::{CompressionRate, Model, ModelType};
+
+    #[test]
+    fn test_evaluate_models() {
+        let model_provider = ModelProvider::new(vec![
+            TEST_ACID_MODEL_PREFER_C.clone(),
+            TEST_ACID_MODEL_PREFER_A.clone(),
+            Model::empty(ModelType::QualityScores),
+        ]);
+        let sequences = [TEST_SEQUENCE_PREFER_A.clone()];
+
+        let evaluations = evaluate_models(model_provider.clone(), &sequences);
+        assert_eq!(evaluations.len(), 3);
+        for (evaluation, identifier) in evaluations.iter().zip(model_provider.identifiers()) {
+            assert_eq!(evaluation.identifier(), identifier);
+            assert_eq!(evaluation.symbol_num(), TEST_SEQUENCE_PREFER_A.len());
+        }
+
+        let prefer_a = evaluations
+            .iter()
+            .find(|evaluation| evaluation.identifier() == TEST_ACID_MODEL_PREFER_A.identifier())
+            .unwrap();
+        let prefer_c = evaluations
+            .iter()
+            .find(|evaluation| evaluation.identifier() == TEST_ACID_MODEL_PREFER_C.identifier())
+            .unwrap();
+        assert!(prefer_a.compressed_bytes() < prefer_c.compressed_bytes());
+        assert!(prefer_a.actual_rate() < prefer_c.actual_rate());
+        assert_eq!(prefer_a.estimated_rate(), TEST_ACID_MODEL_PREFER_A.rate());
+    }
+
+    #[test]
+    fn test_evaluate_models_empty_sample() {
+        let evaluations = evaluate_models(ModelProvider::with_empty_models(), &[]);
+
+        assert_eq!(evaluations.len(), 2);
+        for evaluation in evaluations {
+            assert_eq!(evaluation.compressed_bytes(), 0);
+            assert_eq!(evaluation.actual_rate(), CompressionRate::ZERO);
+        }
+    }
+}
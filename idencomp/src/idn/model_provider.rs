@@ -1,17 +1,23 @@
 use std::collections::{HashMap, HashSet};
-use std::fs::{DirEntry, File};
+use std::fs;
+use std::fs::File;
 use std::ops::Index;
-use std::path::Path;
-use std::{fs, mem};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 use log::debug;
-use rayon::prelude::{IntoParallelIterator, IntoParallelRefIterator, ParallelIterator};
 
+use crate::fastq::FastqQualityScore;
 use crate::model::{Model, ModelIdentifier, ModelType};
 use crate::model_serializer::SerializableModel;
+use crate::parallel::{IntoParallelRefIterator, ParallelIterator};
+use crate::progress::{ByteNum, DummyProgressNotifier, ProgressNotifier, ProgressStage};
+use crate::quality_alphabet::QualityAlphabet;
+use crate::sequence::Symbol;
 use crate::sequence_compressor::{
     AcidRansDecModel, AcidRansEncModel, QScoreRansDecModel, QScoreRansEncModel,
 };
+use crate::Error;
 
 /// A store for [`Model`]s that can be used with
 /// [`IdnCompressor`](crate::idn::compressor::IdnCompressor) and
@@ -23,13 +29,17 @@ use crate::sequence_compressor::{
 /// also make a new instance by filtering the models inside by a list of
 /// identifiers. It also can internally convert [`Model`]s to
 /// [`CompressorModel`]s and [`DecompressorModel`]s.
+///
+/// The models are reference-counted, so cloning a `ModelProvider` or
+/// filtering it with [`Self::filter_by_identifiers()`] is cheap and does not
+/// copy any of the models (nor their preprocessed versions).
 #[derive(Debug, Clone)]
 pub struct ModelProvider {
-    models: Vec<Model>,
+    models: Vec<Arc<Model>>,
     index_map: HashMap<ModelIdentifier, usize>,
 
-    compressor_models: Vec<CompressorModel>,
-    decompressor_models: Vec<DecompressorModel>,
+    compressor_models: Vec<Arc<CompressorModel>>,
+    decompressor_models: Vec<Arc<DecompressorModel>>,
 }
 
 impl ModelProvider {
@@ -52,7 +62,7 @@ impl ModelProvider {
         let model_num = models.len();
 
         let mut provider = Self {
-            models,
+            models: models.into_iter().map(Arc::new).collect(),
             index_map: HashMap::with_capacity(model_num),
             compressor_models: Vec::new(),
             decompressor_models: Vec::new(),
@@ -84,23 +94,43 @@ impl ModelProvider {
     ///
     /// This functions tries to load *all* files as models and uses
     /// [`SerializableModel::read_model`] function to deserialize them.
-    pub fn from_directory(directory: &Path) -> Result<Self, anyhow::Error> {
-        let paths = fs::read_dir(directory)?;
-        let paths: Vec<Result<DirEntry, _>> = paths.collect();
-
-        let models: Result<Vec<Model>, anyhow::Error> = paths
-            .into_par_iter()
-            .map(|dir_entry| {
-                let dir_entry = dir_entry?;
-                let path = &dir_entry.path();
-                let file = File::open(path)?;
-                let model = SerializableModel::read_model(file)?;
+    pub fn from_directory(directory: &Path) -> crate::Result<Self> {
+        let paths: Result<Vec<PathBuf>, _> = fs::read_dir(directory)?
+            .map(|dir_entry| dir_entry.map(|dir_entry| dir_entry.path()))
+            .collect();
+
+        Self::from_files(&paths?)
+    }
+
+    /// Creates a new `ModelProvider` instance containing the models loaded from
+    /// given files.
+    ///
+    /// The files are deserialized using [`SerializableModel::read_model`]
+    /// function, which verifies each model's identifier, so corrupted or
+    /// hand-edited model files are rejected.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::idn::model_provider::ModelProvider;
+    ///
+    /// let result = ModelProvider::from_files(&["nonexistent.msgpack"]);
+    /// assert!(matches!(result, Err(idencomp::Error::ModelFile { .. })));
+    /// ```
+    pub fn from_files<P: AsRef<Path> + Sync>(paths: &[P]) -> crate::Result<Self> {
+        let models: crate::Result<Vec<Model>> = paths
+            .par_iter()
+            .map(|path| {
+                let path = path.as_ref();
+                let model = File::open(path)
+                    .map_err(Error::from)
+                    .and_then(SerializableModel::read_model)
+                    .map_err(|e| Error::model_file(path.to_path_buf(), e))?;
 
                 debug!(
                     "Registering model {} with type {} from `{}`",
                     model.identifier(),
                     model.model_type(),
-                    path.file_name().unwrap().to_string_lossy()
+                    path.display()
                 );
 
                 Ok(model)
@@ -140,7 +170,9 @@ impl ModelProvider {
 
     /// Converts [`Model`]s inside this `ModelProvider` to [`CompressorModel`]s
     /// so they can be obtained with [`Self::acid_enc_models()`] and
-    /// [`Self::q_score_enc_models()`].
+    /// [`Self::q_score_enc_models()`]. Does nothing if the models have already
+    /// been converted, so a preprocessed `ModelProvider` can be cloned and
+    /// reused by multiple compressors.
     ///
     /// # Examples
     /// ```
@@ -152,12 +184,103 @@ impl ModelProvider {
     /// assert!(model_provider.acid_enc_models().next().is_some());
     /// ```
     pub fn preprocess_compressor_models(&mut self) {
-        self.compressor_models = self.models.par_iter().map(|x| x.into()).collect();
+        self.preprocess_compressor_models_with_progress(&DummyProgressNotifier);
+    }
+
+    /// Returns the approximate number of bytes the [`CompressorModel`]s
+    /// created by [`Self::preprocess_compressor_models()`] would take. This
+    /// can be used to check whether preprocessing fits in the available memory
+    /// before actually doing it.
+    ///
+    /// See [`Model::estimated_enc_memory()`] for details.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::idn::model_provider::ModelProvider;
+    ///
+    /// let model_provider = ModelProvider::with_empty_models();
+    /// assert!(model_provider.estimated_preprocess_memory().get() > 0);
+    /// ```
+    #[must_use]
+    pub fn estimated_preprocess_memory(&self) -> ByteNum {
+        self.models
+            .iter()
+            .map(|model| model.estimated_enc_memory())
+            .fold(ByteNum::ZERO, |acc, bytes| acc + bytes)
+    }
+
+    /// Returns the approximate number of bytes the [`DecompressorModel`]s
+    /// created by [`Self::preprocess_decompressor_models()`] would take.
+    ///
+    /// See [`Model::estimated_dec_memory()`] for details.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::idn::model_provider::ModelProvider;
+    ///
+    /// let model_provider = ModelProvider::with_empty_models();
+    /// assert!(model_provider.estimated_preprocess_decompressor_memory().get() > 0);
+    /// ```
+    #[must_use]
+    pub fn estimated_preprocess_decompressor_memory(&self) -> ByteNum {
+        self.models
+            .iter()
+            .map(|model| model.estimated_dec_memory())
+            .fold(ByteNum::ZERO, |acc, bytes| acc + bytes)
+    }
+
+    /// Does the same as [`Self::preprocess_compressor_models()`], but reports
+    /// the [`ProgressStage::ModelPreprocessing`] stage to given
+    /// [`ProgressNotifier`], advancing it after each converted model.
+    pub fn preprocess_compressor_models_with_progress(&mut self, notifier: &dyn ProgressNotifier) {
+        if !self.compressor_models.is_empty() {
+            return;
+        }
+
+        notifier.start_stage(ProgressStage::ModelPreprocessing, Some(self.len() as u64));
+        self.compressor_models = Self::convert_models(
+            &self.models,
+            Model::estimated_enc_memory,
+            |model| CompressorModel::from(model),
+            notifier,
+        );
+    }
+
+    /// Converts the models to [`CompressorModel`]s (if they have not been
+    /// converted already) and returns a [`PreparedModels`] handle, which can
+    /// be passed to
+    /// [`IdnCompressor::with_prepared()`](crate::idn::compressor::IdnCompressor::with_prepared)
+    /// any number of times without rebuilding the encoder tables.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::idn::model_provider::ModelProvider;
+    ///
+    /// let prepared = ModelProvider::with_empty_models().prepare_for_compression();
+    /// assert!(prepared.model_provider().acid_enc_models().next().is_some());
+    /// ```
+    #[must_use]
+    pub fn prepare_for_compression(&self) -> PreparedModels {
+        self.prepare_for_compression_with_progress(&DummyProgressNotifier)
+    }
+
+    /// Does the same as [`Self::prepare_for_compression()`], but reports the
+    /// progress of preprocessing to given [`ProgressNotifier`].
+    #[must_use]
+    pub fn prepare_for_compression_with_progress(
+        &self,
+        notifier: &dyn ProgressNotifier,
+    ) -> PreparedModels {
+        let mut model_provider = self.clone();
+        model_provider.preprocess_compressor_models_with_progress(notifier);
+
+        PreparedModels { model_provider }
     }
 
     /// Converts [`Model`]s inside this `ModelProvider` to
     /// [`DecompressorModel`]s so they can be obtained with
-    /// [`Self::decompressor_models()`].
+    /// [`Self::decompressor_models()`]. Does nothing if the models have
+    /// already been converted.
     ///
     /// # Examples
     /// ```
@@ -169,7 +292,60 @@ impl ModelProvider {
     /// assert_eq!(model_provider.decompressor_models().len(), 2);
     /// ```
     pub fn preprocess_decompressor_models(&mut self) {
-        self.decompressor_models = self.models.par_iter().map(|x| x.into()).collect();
+        self.preprocess_decompressor_models_with_progress(&DummyProgressNotifier);
+    }
+
+    /// Does the same as [`Self::preprocess_decompressor_models()`], but
+    /// reports the [`ProgressStage::ModelPreprocessing`] stage to given
+    /// [`ProgressNotifier`], advancing it after each converted model.
+    pub fn preprocess_decompressor_models_with_progress(
+        &mut self,
+        notifier: &dyn ProgressNotifier,
+    ) {
+        if !self.decompressor_models.is_empty() {
+            return;
+        }
+
+        notifier.start_stage(ProgressStage::ModelPreprocessing, Some(self.len() as u64));
+        self.decompressor_models = Self::convert_models(
+            &self.models,
+            Model::estimated_dec_memory,
+            |model| DecompressorModel::from(model),
+            notifier,
+        );
+    }
+
+    /// Converts the models in parallel, advancing the progress stage after
+    /// each of them.
+    ///
+    /// The conversion needs a lot of temporary memory on top of the resulting
+    /// model, so the models are converted in windows of at most
+    /// [`PREPROCESS_WINDOW_MEMORY`] bytes (as estimated by `estimate_memory`)
+    /// rather than all at once.
+    fn convert_models<T, F>(
+        models: &[Arc<Model>],
+        estimate_memory: fn(&Model) -> ByteNum,
+        convert: F,
+        notifier: &dyn ProgressNotifier,
+    ) -> Vec<Arc<T>>
+    where
+        T: Send + Sync,
+        F: Fn(&Model) -> T + Sync,
+    {
+        let mut converted = Vec::with_capacity(models.len());
+        for window in memory_windows(models, estimate_memory, PREPROCESS_WINDOW_MEMORY) {
+            let window_models: Vec<_> = window
+                .par_iter()
+                .map(|model| {
+                    let model = Arc::new(convert(model.as_ref()));
+                    notifier.advance_stage(1);
+                    model
+                })
+                .collect();
+            converted.extend(window_models);
+        }
+
+        converted
     }
 
     /// Returns a slice of all decoder models of this `ModelProvider`.
@@ -188,7 +364,7 @@ impl ModelProvider {
     /// assert_eq!(model_provider.decompressor_models().len(), 2);
     /// ```
     #[must_use]
-    pub fn decompressor_models(&self) -> &[DecompressorModel] {
+    pub fn decompressor_models(&self) -> &[Arc<DecompressorModel>] {
         &self.decompressor_models
     }
 
@@ -268,7 +444,11 @@ impl ModelProvider {
     }
 
     /// Modifies `ModelProvider` in-place so that it only contains models with
-    /// given identifiers.
+    /// given identifiers, in the order of the identifiers.
+    ///
+    /// The models, as well as their already preprocessed compressor and
+    /// decompressor versions, are shared with the original instance, so no
+    /// model is copied or converted again.
     ///
     /// # Examples
     /// ```
@@ -280,9 +460,12 @@ impl ModelProvider {
     /// let identifier = model_1.identifier().clone();
     /// let mut model_provider = ModelProvider::new(vec![model_1, model_2]);
     ///
+    /// model_provider.preprocess_decompressor_models();
+    ///
     /// assert_eq!(model_provider.len(), 2);
     /// model_provider.filter_by_identifiers(&[identifier]);
     /// assert_eq!(model_provider.len(), 1);
+    /// assert_eq!(model_provider.decompressor_models().len(), 1);
     /// ```
     ///
     /// # Panics
@@ -290,44 +473,58 @@ impl ModelProvider {
     pub fn filter_by_identifiers(&mut self, identifiers: &[ModelIdentifier]) {
         self.has_all_models(identifiers).expect("Unknown model");
 
-        let dummy_model = Model::empty(ModelType::Acids);
-
         let indices: Vec<usize> = identifiers
             .iter()
             .map(|identifier| self.index_of(identifier))
             .collect();
 
-        self.models = indices
-            .iter()
-            .map(|&index| mem::replace(&mut self.models[index], dummy_model.clone()))
-            .collect();
-
+        self.models = Self::select(&self.models, &indices);
         if !self.compressor_models.is_empty() {
-            let dummy_comp_model = CompressorModel::from(&dummy_model);
-            self.compressor_models = indices
-                .iter()
-                .map(|&index| {
-                    mem::replace(&mut self.compressor_models[index], dummy_comp_model.clone())
-                })
-                .collect();
+            self.compressor_models = Self::select(&self.compressor_models, &indices);
+        }
+        if !self.decompressor_models.is_empty() {
+            self.decompressor_models = Self::select(&self.decompressor_models, &indices);
         }
 
+        self.rebuild_index_map();
+    }
+
+    /// Sorts the models in this `ModelProvider` by their identifiers, so that
+    /// the order (and hence the indices) of the models does not depend on the
+    /// order they were loaded in.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::idn::model_provider::ModelProvider;
+    ///
+    /// let mut model_provider = ModelProvider::with_empty_models();
+    /// model_provider.sort_by_identifier();
+    ///
+    /// let identifiers: Vec<_> = model_provider.identifiers().collect();
+    /// assert!(identifiers[0] < identifiers[1]);
+    /// ```
+    pub fn sort_by_identifier(&mut self) {
+        let mut indices: Vec<usize> = (0..self.len()).collect();
+        indices.sort_by_key(|&index| self.models[index].identifier());
+
+        self.models = Self::select(&self.models, &indices);
+        if !self.compressor_models.is_empty() {
+            self.compressor_models = Self::select(&self.compressor_models, &indices);
+        }
         if !self.decompressor_models.is_empty() {
-            let dummy_decomp_model = DecompressorModel::from(&dummy_model);
-            self.decompressor_models = indices
-                .iter()
-                .map(|&index| {
-                    mem::replace(
-                        &mut self.decompressor_models[index],
-                        dummy_decomp_model.clone(),
-                    )
-                })
-                .collect();
+            self.decompressor_models = Self::select(&self.decompressor_models, &indices);
         }
 
         self.rebuild_index_map();
     }
 
+    fn select<T>(items: &[Arc<T>], indices: &[usize]) -> Vec<Arc<T>> {
+        indices
+            .iter()
+            .map(|&index| Arc::clone(&items[index]))
+            .collect()
+    }
+
     /// Returns the number of [`Model`]s this `ModelProvider` contains.
     ///
     /// # Examples
@@ -378,6 +575,110 @@ impl ModelProvider {
     pub fn identifiers(&self) -> impl Iterator<Item = &ModelIdentifier> {
         self.models.iter().map(|model| model.identifier())
     }
+
+    /// Returns whether this provider contains at least one model of given
+    /// type.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::idn::model_provider::ModelProvider;
+    /// use idencomp::model::{Model, ModelType};
+    ///
+    /// let model_provider = ModelProvider::new(vec![Model::empty(ModelType::Acids)]);
+    /// assert!(model_provider.has_model_type(ModelType::Acids));
+    /// assert!(!model_provider.has_model_type(ModelType::QualityScores));
+    /// ```
+    #[must_use]
+    pub fn has_model_type(&self, model_type: ModelType) -> bool {
+        self.models
+            .iter()
+            .any(|model| model.model_type() == model_type)
+    }
+
+    /// Returns a `ModelProvider` with the quality score models adapted to
+    /// given [`QualityAlphabet`]. The models built for the full alphabet are
+    /// converted with [`Model::with_quality_alphabet()`], and the models built
+    /// for an alphabet of a different size are removed. The acid models are
+    /// left intact.
+    ///
+    /// The provider is returned unchanged if all the models already match the
+    /// alphabet, which keeps the models preprocessed.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::fastq::FastqQualityScore;
+    /// use idencomp::idn::model_provider::ModelProvider;
+    /// use idencomp::quality_alphabet::QualityAlphabet;
+    ///
+    /// let alphabet = QualityAlphabet::new([2, 12, 23, 37].map(FastqQualityScore::new));
+    /// let provider = ModelProvider::with_empty_models().with_quality_alphabet(&alphabet);
+    /// assert_eq!(provider.len(), 2);
+    /// assert_eq!(provider[1].symbol_num(), 4);
+    /// ```
+    #[must_use]
+    pub fn with_quality_alphabet(self, alphabet: &QualityAlphabet) -> Self {
+        let matches = |model: &Arc<Model>| {
+            model.model_type() == ModelType::Acids || model.symbol_num() == alphabet.symbol_num()
+        };
+        if self.models.iter().all(matches) {
+            return self;
+        }
+
+        let models = self
+            .models
+            .into_iter()
+            .filter_map(|model| {
+                if matches(&model) {
+                    Some(model)
+                } else if model.symbol_num() == FastqQualityScore::SIZE {
+                    Some(Arc::new(model.with_quality_alphabet(alphabet)))
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        let mut provider = Self {
+            models,
+            index_map: HashMap::new(),
+            compressor_models: Vec::new(),
+            decompressor_models: Vec::new(),
+        };
+        provider.rebuild_index_map();
+        provider
+    }
+}
+
+/// The maximum estimated number of bytes of the models converted at once by
+/// [`ModelProvider::preprocess_compressor_models()`] and
+/// [`ModelProvider::preprocess_decompressor_models()`].
+const PREPROCESS_WINDOW_MEMORY: ByteNum = ByteNum::new(512 * 1024 * 1024);
+
+/// Splits the models into consecutive windows, each taking at most
+/// `max_memory` bytes, unless it consists of a single model.
+#[must_use]
+fn memory_windows(
+    models: &[Arc<Model>],
+    estimate_memory: fn(&Model) -> ByteNum,
+    max_memory: ByteNum,
+) -> Vec<&[Arc<Model>]> {
+    let mut windows = Vec::new();
+    let mut start = 0;
+    let mut memory = ByteNum::ZERO;
+    for (index, model) in models.iter().enumerate() {
+        let model_memory = estimate_memory(model);
+        if index > start && memory + model_memory > max_memory {
+            windows.push(&models[start..index]);
+            start = index;
+            memory = ByteNum::ZERO;
+        }
+        memory += model_memory;
+    }
+    if start < models.len() {
+        windows.push(&models[start..]);
+    }
+
+    windows
 }
 
 impl Default for ModelProvider {
@@ -390,7 +691,30 @@ impl Index<usize> for ModelProvider {
     type Output = Model;
 
     fn index(&self, index: usize) -> &Self::Output {
-        &self.models[index]
+        self.models[index].as_ref()
+    }
+}
+
+/// A [`ModelProvider`] whose models have been converted to
+/// [`CompressorModel`]s, created with
+/// [`ModelProvider::prepare_for_compression()`].
+///
+/// Building the encoder tables can take longer than compressing a small file,
+/// so when many files are compressed with the same models, the models should
+/// be prepared once and the handle reused with
+/// [`IdnCompressor::with_prepared()`](crate::idn::compressor::IdnCompressor::with_prepared).
+/// The preprocessed models are reference-counted, so cloning the handle is
+/// cheap.
+#[derive(Debug, Clone)]
+pub struct PreparedModels {
+    model_provider: ModelProvider,
+}
+
+impl PreparedModels {
+    /// Returns the preprocessed [`ModelProvider`].
+    #[must_use]
+    pub fn model_provider(&self) -> &ModelProvider {
+        &self.model_provider
     }
 }
 
@@ -516,3 +840,30 @@ impl<A, B> CoderModel<A, B> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::idn::model_provider::memory_windows;
+    use crate::model::{Model, ModelType};
+    use crate::progress::ByteNum;
+
+    #[test]
+    fn test_memory_windows() {
+        let models: Vec<_> = [ModelType::Acids, ModelType::QualityScores, ModelType::Acids]
+            .into_iter()
+            .map(|model_type| Arc::new(Model::empty(model_type)))
+            .collect();
+        let window_lens = |max_memory| -> Vec<_> {
+            memory_windows(&models, Model::estimated_enc_memory, max_memory)
+                .iter()
+                .map(|window| window.len())
+                .collect()
+        };
+
+        assert_eq!(window_lens(ByteNum::new(usize::MAX / 2)), [3]);
+        assert_eq!(window_lens(ByteNum::ZERO), [1, 1, 1]);
+        assert!(memory_windows(&[], Model::estimated_enc_memory, ByteNum::ZERO).is_empty());
+    }
+}
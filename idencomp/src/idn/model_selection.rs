@@ -0,0 +1,421 @@
+use std::fmt::Debug;
+
+use log::debug;
+
+use crate::fastq::FastqSequence;
+use crate::idn::compressor::{IdnCompressResult, IdnCompressorError};
+use crate::idn::model_chooser::{ModelTester, PartSpecs};
+use crate::model::{ModelIdentifier, ModelType};
+use crate::sequence_compressor::RansEncModel;
+
+/// Policy of choosing the model each of the sequences (or a part of it) is
+/// encoded with, among the models used in the file.
+///
+/// The strategy is set with
+/// [`IdnCompressorParamsBuilder::model_selection()`](crate::idn::compressor::IdnCompressorParamsBuilder::model_selection).
+/// It is called separately for the acid and the quality score models, so the
+/// candidates are always of a single [`ModelType`]. The strategy is shared by
+/// all the threads compressing the blocks, so any state it needs should be
+/// derived from the [`ModelCandidates`] rather than stored in the strategy.
+///
+/// # Examples
+/// ```
+/// use std::sync::Arc;
+///
+/// use idencomp::idn::compressor::IdnCompressorParams;
+/// use idencomp::idn::model_selection::{ModelCandidates, ModelSelectionStrategy};
+///
+/// /// Always uses the first model.
+/// #[derive(Debug)]
+/// struct FirstModel;
+///
+/// impl ModelSelectionStrategy for FirstModel {
+///     fn select(&self, _candidates: &mut ModelCandidates) -> usize {
+///         0
+///     }
+/// }
+///
+/// let _params = IdnCompressorParams::builder()
+///     .model_selection(Arc::new(FirstModel))
+///     .build()?;
+///
+/// # Ok::<(), idencomp::Error>(())
+/// ```
+pub trait ModelSelectionStrategy: Debug + Send + Sync {
+    /// Returns the index of the candidate to encode the sequences with. The
+    /// index has to be lower than [`ModelCandidates::len()`]; otherwise, the
+    /// compression fails with
+    /// [`IdnCompressorError::InvalidModelSelection`].
+    ///
+    /// The size of the selected model reported in the statistics and the
+    /// [`SequenceExplanation`](crate::idn::explain::SequenceExplanation)s is
+    /// only known if the strategy has called
+    /// [`ModelCandidates::compressed_size()`] for it.
+    fn select(&self, candidates: &mut ModelCandidates) -> usize;
+}
+
+/// The models a [`ModelSelectionStrategy`] chooses from, along with the
+/// sequences the model is chosen for.
+///
+/// The compressed sizes are only computed when requested, and then cached, so
+/// the strategies that do not need all of them are cheaper.
+#[derive(Debug)]
+pub struct ModelCandidates<'a, 'b> {
+    model_tester: &'a mut ModelTester,
+    models: Vec<&'b RansEncModel>,
+    sequences: &'a [FastqSequence],
+    part_specs: Option<&'a PartSpecs>,
+    current: Option<usize>,
+    selection_index: usize,
+    sizes: Vec<Option<usize>>,
+}
+
+impl<'a, 'b> ModelCandidates<'a, 'b> {
+    pub(super) fn new(
+        model_tester: &'a mut ModelTester,
+        models: Vec<&'b RansEncModel>,
+        sequences: &'a [FastqSequence],
+        part_specs: Option<&'a PartSpecs>,
+        current_model: Option<&ModelIdentifier>,
+        selection_index: usize,
+    ) -> Self {
+        let current = current_model.and_then(|identifier| {
+            models
+                .iter()
+                .position(|model| model.identifier() == identifier)
+        });
+        let sizes = vec![None; models.len()];
+
+        Self {
+            model_tester,
+            models,
+            sequences,
+            part_specs,
+            current,
+            selection_index,
+            sizes,
+        }
+    }
+
+    /// Returns the number of the candidate models.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.models.len()
+    }
+
+    /// Returns whether there are no candidate models. This is never the case
+    /// when the strategy is called.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.models.is_empty()
+    }
+
+    /// Returns the type of the candidate models.
+    #[must_use]
+    pub fn model_type(&self) -> ModelType {
+        self.models[0].model_type()
+    }
+
+    /// Returns the identifier of the model at given index.
+    #[must_use]
+    pub fn identifier(&self, index: usize) -> &ModelIdentifier {
+        self.models[index].identifier()
+    }
+
+    /// Returns the index of the model the previous sequence (or part of the
+    /// sequence) has been encoded with, if it is one of the candidates.
+    /// Switching to a different model costs a few bytes in the compressed
+    /// file.
+    #[must_use]
+    pub fn current(&self) -> Option<usize> {
+        self.current
+    }
+
+    /// Returns the number of times a model of this type has already been
+    /// selected within the current block.
+    #[must_use]
+    pub fn selection_index(&self) -> usize {
+        self.selection_index
+    }
+
+    /// Returns the sequences the model is chosen for. When the model is chosen
+    /// for a part of a long sequence, this is just the part.
+    #[must_use]
+    pub fn sequences(&self) -> &[FastqSequence] {
+        self.sequences
+    }
+
+    /// Returns the number of bytes the sequences would be compressed to with
+    /// the model at given index.
+    pub fn compressed_size(&mut self, index: usize) -> usize {
+        if let Some(size) = self.sizes[index] {
+            return size;
+        }
+
+        let size = self.sample_compressed_size(index, usize::MAX);
+        let model = self.models[index];
+        debug!("Length with model {}: {}", model.identifier(), size);
+
+        self.sizes[index] = Some(size);
+        size
+    }
+
+    /// Returns the number of bytes the first `max_symbols` symbols of each of
+    /// the sequences would be compressed to with the model at given index.
+    pub fn sample_compressed_size(&mut self, index: usize, max_symbols: usize) -> usize {
+        let model = self.models[index];
+        let specs = self
+            .part_specs
+            .map(|part_specs| part_specs.get(model.context_spec_type()));
+        self.sequences
+            .iter()
+            .map(|sequence| match specs {
+                Some(specs) => self.model_tester.compute_size_of_part_prefix(
+                    sequence,
+                    specs,
+                    max_symbols,
+                    model,
+                ),
+                None => self
+                    .model_tester
+                    .compute_size_of_prefix(sequence, max_symbols, model),
+            })
+            .sum()
+    }
+
+    /// Returns the model at given index, along with the number of bytes the
+    /// sequences would be compressed to with it, if the strategy has computed
+    /// it. The size is not computed here, so that the strategies that only
+    /// sample the sequences stay cheap.
+    pub(super) fn into_selected(
+        self,
+        index: usize,
+    ) -> IdnCompressResult<(Option<usize>, &'b RansEncModel)> {
+        if index >= self.len() {
+            return Err(IdnCompressorError::invalid_model_selection(
+                index,
+                self.len(),
+            ));
+        }
+
+        Ok((self.sizes[index], self.models[index]))
+    }
+}
+
+/// Tests all the models on the whole sequence and chooses the one that
+/// compresses it best, taking into account the cost of switching the model.
+/// This is the default strategy.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct ExhaustiveSelection;
+
+/// The number of bytes a model switch is assumed to take.
+const SWITCH_MODEL_PENALTY: usize = 2;
+
+impl ModelSelectionStrategy for ExhaustiveSelection {
+    fn select(&self, candidates: &mut ModelCandidates) -> usize {
+        (0..candidates.len())
+            .min_by_key(|&index| {
+                let penalty = if candidates.current() == Some(index) {
+                    0
+                } else {
+                    SWITCH_MODEL_PENALTY
+                };
+                candidates.compressed_size(index) + penalty
+            })
+            .expect("No models provided")
+    }
+}
+
+/// Like [`ExhaustiveSelection`], but tests the models only on the first
+/// symbols of each sequence, which makes choosing the models for long
+/// sequences considerably faster.
+#[derive(Debug, Copy, Clone)]
+pub struct SampledSelection {
+    max_symbols: usize,
+}
+
+impl SampledSelection {
+    /// Creates a new `SampledSelection` instance testing the models on at
+    /// most `max_symbols` first symbols of each sequence.
+    ///
+    /// # Panics
+    /// Panics if `max_symbols` is 0.
+    #[must_use]
+    pub fn new(max_symbols: usize) -> Self {
+        assert!(max_symbols > 0, "The sample cannot be empty");
+
+        Self { max_symbols }
+    }
+}
+
+impl Default for SampledSelection {
+    fn default() -> Self {
+        Self::new(1024)
+    }
+}
+
+impl ModelSelectionStrategy for SampledSelection {
+    fn select(&self, candidates: &mut ModelCandidates) -> usize {
+        (0..candidates.len())
+            .min_by_key(|&index| {
+                let penalty = if candidates.current() == Some(index) {
+                    0
+                } else {
+                    SWITCH_MODEL_PENALTY
+                };
+                candidates.sample_compressed_size(index, self.max_symbols) + penalty
+            })
+            .expect("No models provided")
+    }
+}
+
+/// Keeps the model the previous sequence has been encoded with, unless
+/// another model compresses the sequence better by more than given
+/// percentage. This results in fewer, longer runs of the same model.
+#[derive(Debug, Copy, Clone)]
+pub struct StickySelection {
+    min_gain_percent: usize,
+}
+
+impl StickySelection {
+    /// Creates a new `StickySelection` instance that switches the model only
+    /// if it makes the sequence smaller by more than `min_gain_percent`
+    /// percent.
+    #[must_use]
+    pub fn new(min_gain_percent: usize) -> Self {
+        Self { min_gain_percent }
+    }
+}
+
+impl Default for StickySelection {
+    fn default() -> Self {
+        Self::new(5)
+    }
+}
+
+impl ModelSelectionStrategy for StickySelection {
+    fn select(&self, candidates: &mut ModelCandidates) -> usize {
+        let best = ExhaustiveSelection.select(candidates);
+        let current = match candidates.current() {
+            Some(current) if current != best => current,
+            _ => return best,
+        };
+
+        let best_size = candidates.compressed_size(best);
+        let current_size = candidates.compressed_size(current);
+        if current_size.saturating_sub(best_size) * 100 > current_size * self.min_gain_percent {
+            best
+        } else {
+            current
+        }
+    }
+}
+
+/// Uses the models in turns, without testing them at all. This is mostly
+/// useful as a baseline when comparing the other strategies, or to make sure
+/// all the models are exercised.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct RoundRobinSelection;
+
+impl ModelSelectionStrategy for RoundRobinSelection {
+    fn select(&self, candidates: &mut ModelCandidates) -> usize {
+        candidates.selection_index() % candidates.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::_internal_test_data::{
+        TEST_ACID_MODEL_PREFER_A, TEST_ACID_MODEL_PREFER_C, TEST_SEQUENCE_PREFER_A,
+        TEST_SEQUENCE_PREFER_C,
+    };
+    use crate::fastq::FastqSequence;
+    use crate::idn::model_chooser::ModelTester;
+    use crate::idn::model_selection::{
+        ExhaustiveSelection, ModelCandidates, ModelSelectionStrategy, RoundRobinSelection,
+        SampledSelection, StickySelection,
+    };
+    use crate::quality_alphabet::QualityAlphabet;
+    use crate::sequence_compressor::AcidRansEncModel;
+
+    const SCALE_BITS: u8 = 10;
+
+    fn select(
+        strategy: &dyn ModelSelectionStrategy,
+        sequence: &FastqSequence,
+        current: Option<usize>,
+        selection_index: usize,
+    ) -> usize {
+        let model_a = AcidRansEncModel::from_model(&TEST_ACID_MODEL_PREFER_A, SCALE_BITS);
+        let model_c = AcidRansEncModel::from_model(&TEST_ACID_MODEL_PREFER_C, SCALE_BITS);
+        let models = vec![&model_a, &model_c];
+        let current_model = current.map(|index| models[index].identifier().clone());
+
+        let mut model_tester = ModelTester::new(sequence.len(), QualityAlphabet::default());
+        let sequences = [sequence.clone()];
+        let mut candidates = ModelCandidates::new(
+            &mut model_tester,
+            models,
+            &sequences,
+            None,
+            current_model.as_ref(),
+            selection_index,
+        );
+        strategy.select(&mut candidates)
+    }
+
+    #[test]
+    fn test_exhaustive_selection() {
+        assert_eq!(
+            select(&ExhaustiveSelection, &TEST_SEQUENCE_PREFER_A, None, 0),
+            0
+        );
+        assert_eq!(
+            select(&ExhaustiveSelection, &TEST_SEQUENCE_PREFER_C, Some(0), 0),
+            1
+        );
+    }
+
+    #[test]
+    fn test_sampled_selection() {
+        let strategy = SampledSelection::new(16);
+
+        assert_eq!(select(&strategy, &TEST_SEQUENCE_PREFER_A, None, 0), 0);
+        assert_eq!(select(&strategy, &TEST_SEQUENCE_PREFER_C, None, 0), 1);
+    }
+
+    #[test]
+    fn test_sticky_selection() {
+        assert_eq!(
+            select(
+                &StickySelection::new(0),
+                &TEST_SEQUENCE_PREFER_C,
+                Some(0),
+                0
+            ),
+            1
+        );
+        assert_eq!(
+            select(
+                &StickySelection::new(100),
+                &TEST_SEQUENCE_PREFER_C,
+                Some(0),
+                0
+            ),
+            0
+        );
+        assert_eq!(
+            select(&StickySelection::new(100), &TEST_SEQUENCE_PREFER_C, None, 0),
+            1
+        );
+    }
+
+    #[test]
+    fn test_round_robin_selection() {
+        let selected: Vec<_> = (0..4)
+            .map(|index| select(&RoundRobinSelection, &TEST_SEQUENCE_PREFER_A, None, index))
+            .collect();
+
+        assert_eq!(selected, [0, 1, 0, 1]);
+    }
+}
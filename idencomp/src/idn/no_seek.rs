@@ -1,21 +1,37 @@
 use std::fmt::Debug;
-use std::io::{Error, ErrorKind, Read, Seek, Write};
+use std::io::{Error, ErrorKind, Read, Seek, SeekFrom, Write};
 
-/// Wrapper over a [`std::io::Read`] or [`std::io::Write`] object that provides
-/// a dummy [`std::io::Seek`] implementation.
+type SeekFn<T> = fn(&mut T, SeekFrom) -> std::io::Result<u64>;
+
+/// Wrapper over a [`std::io::Read`] or [`std::io::Write`] object that keeps
+/// track of the stream position and provides a [`std::io::Seek`]
+/// implementation.
+///
+/// The wrapper can work in two modes, depending on how it was constructed:
+/// * [`NoSeek::new()`] accepts any object and provides a dummy [`Seek`]
+///   implementation that does nothing for no-op seeks and returns errors
+///   otherwise. This may be useful for libraries/functions that require
+///   [`Seek`], but are only doing no-op seeks in some specific cases.
+/// * [`NoSeek::seekable()`] accepts objects that implement [`Seek`] and passes
+///   all the seeks through to the wrapped object. Use
+///   [`NoSeek::is_seekable()`] to find out at runtime which mode is used.
+///
+/// In both modes, the positions are relative to the position of the wrapped
+/// object at the time the wrapper was created, i.e. [`SeekFrom::Start(0)`]
+/// rewinds to the place the wrapper started reading or writing at, rather than
+/// to the very beginning of the underlying stream.
 ///
-/// The [`Seek`] implementation does nothing for no-op seeks, and
-/// returns errors otherwise. This may be useful for libraries/functions that
-/// require [`Seek`], but are only doing no-op seeks in some specific
-/// cases.
+/// [`SeekFrom::Start(0)`]: SeekFrom::Start
 #[derive(Debug)]
 pub struct NoSeek<T> {
     inner: T,
     position: u64,
+    start: u64,
+    seek_fn: Option<SeekFn<T>>,
 }
 
 impl<T> NoSeek<T> {
-    /// Constructs a new [`NoSeek<T>`] object.
+    /// Constructs a new [`NoSeek<T>`] object that only allows no-op seeks.
     ///
     /// # Examples
     /// ```
@@ -26,14 +42,66 @@ impl<T> NoSeek<T> {
     /// let data: Vec<u8> = Vec::new();
     /// let mut reader = NoSeek::new(&data);
     ///
+    /// assert!(!reader.is_seekable());
     /// assert!(reader.seek(SeekFrom::Start(0)).is_ok());
     /// assert!(reader.seek(SeekFrom::Start(1)).is_err());
     /// ```
     pub fn new(inner: T) -> Self {
-        Self { inner, position: 0 }
+        Self {
+            inner,
+            position: 0,
+            start: 0,
+            seek_fn: None,
+        }
     }
 
-    /// Returns the position of this [`NoSeek<T>`] object.
+    /// Constructs a new [`NoSeek<T>`] object over a seekable object. All the
+    /// seeks are passed through to the wrapped object.
+    ///
+    /// # Errors
+    /// Returns an error if the current position of the wrapped object could
+    /// not be determined.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::{Cursor, Seek, SeekFrom, Write};
+    ///
+    /// use idencomp::idn::no_seek::NoSeek;
+    ///
+    /// let mut cursor = Cursor::new(Vec::new());
+    /// cursor.write_all(b"xyz")?;
+    /// let mut writer = NoSeek::seekable(cursor)?;
+    /// writer.write_all(b"abc")?;
+    ///
+    /// assert!(writer.is_seekable());
+    /// assert_eq!(writer.seek(SeekFrom::Start(1))?, 1);
+    /// writer.write_all(b"B")?;
+    /// assert_eq!(writer.into_inner().into_inner(), b"xyzaBc");
+    ///
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn seekable(mut inner: T) -> std::io::Result<Self>
+    where
+        T: Seek,
+    {
+        let start = inner.stream_position()?;
+
+        Ok(Self {
+            inner,
+            position: 0,
+            start,
+            seek_fn: Some(T::seek),
+        })
+    }
+
+    /// Returns whether this object passes the seeks through to the wrapped
+    /// object, i.e. whether it was created with [`NoSeek::seekable()`].
+    pub fn is_seekable(&self) -> bool {
+        self.seek_fn.is_some()
+    }
+
+    /// Returns the position of this [`NoSeek<T>`] object, relative to the
+    /// position the wrapped object was at when this object was created.
     ///
     /// # Examples
     /// ```
@@ -50,24 +118,67 @@ impl<T> NoSeek<T> {
         self.position
     }
 
+    /// Returns a reference to the wrapped object.
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the wrapped object.
+    ///
+    /// Reading, writing or seeking the wrapped object directly makes the
+    /// position returned by [`NoSeek::position()`] out of date.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// Unwraps this [`NoSeek<T>`], returning the wrapped object.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
     fn seek_error() -> Error {
         Error::new(ErrorKind::Other, "Non-noop seek on a NoSeek object")
     }
+
+    fn seek_inner(&mut self, seek_fn: SeekFn<T>, pos: SeekFrom) -> std::io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(i) => SeekFrom::Start(self.start + i),
+            pos => pos,
+        };
+
+        let absolute = seek_fn(&mut self.inner, pos)?;
+        if absolute < self.start {
+            // Go back to the previous position, as the data before the start
+            // is not available through this object
+            seek_fn(&mut self.inner, SeekFrom::Start(self.start + self.position))?;
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Seek before the start of a NoSeek object",
+            ));
+        }
+
+        self.position = absolute - self.start;
+        Ok(self.position)
+    }
 }
 
 impl<T> Seek for NoSeek<T> {
     #[inline]
-    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        if let Some(seek_fn) = self.seek_fn {
+            return self.seek_inner(seek_fn, pos);
+        }
+
         match pos {
-            std::io::SeekFrom::Start(i) => {
+            SeekFrom::Start(i) => {
                 if i == self.position {
                     Ok(self.position)
                 } else {
                     Err(Self::seek_error())
                 }
             }
-            std::io::SeekFrom::End(_) => unimplemented!(),
-            std::io::SeekFrom::Current(i) => {
+            SeekFrom::End(_) => Err(Self::seek_error()),
+            SeekFrom::Current(i) => {
                 if i == 0 {
                     Ok(self.position)
                 } else {
@@ -114,3 +225,55 @@ impl<W: Write> Write for NoSeek<W> {
         self.inner.flush()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+
+    use crate::idn::no_seek::NoSeek;
+
+    #[test]
+    fn test_no_seek_tracks_position() {
+        let mut reader = NoSeek::new(b"abcdef".as_slice());
+        let mut buf = [0; 4];
+        reader.read_exact(&mut buf).unwrap();
+
+        assert_eq!(reader.position(), 4);
+        assert_eq!(reader.stream_position().unwrap(), 4);
+        assert_eq!(reader.seek(SeekFrom::Start(4)).unwrap(), 4);
+        assert!(reader.seek(SeekFrom::Start(0)).is_err());
+        assert!(reader.seek(SeekFrom::Current(-1)).is_err());
+        assert!(reader.seek(SeekFrom::End(0)).is_err());
+    }
+
+    #[test]
+    fn test_seekable_passes_seeks_through() {
+        let mut writer = NoSeek::seekable(Cursor::new(Vec::new())).unwrap();
+        writer.write_all(b"abcdef").unwrap();
+
+        assert_eq!(writer.seek(SeekFrom::Current(-4)).unwrap(), 2);
+        writer.write_all(b"C").unwrap();
+        assert_eq!(writer.position(), 3);
+        assert_eq!(writer.seek(SeekFrom::End(-1)).unwrap(), 5);
+        writer.write_all(b"F").unwrap();
+        assert_eq!(writer.into_inner().into_inner(), b"abCdeF");
+    }
+
+    #[test]
+    fn test_seekable_positions_relative_to_start() {
+        let mut cursor = Cursor::new(b"xyzabc".to_vec());
+        cursor.set_position(3);
+        let mut reader = NoSeek::seekable(cursor).unwrap();
+        let mut buf = [0; 2];
+
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"ab");
+        assert_eq!(reader.seek(SeekFrom::Start(0)).unwrap(), 0);
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"ab");
+
+        assert!(reader.seek(SeekFrom::Current(-3)).is_err());
+        assert_eq!(reader.position(), 2);
+        assert_eq!(reader.get_ref().position(), 5);
+    }
+}
@@ -0,0 +1,114 @@
+use crate::fastq::{FastqQualityScore, FastqSequence};
+use crate::sequence::{Acid, Symbol};
+
+/// The number of acids stored in a single byte.
+const ACIDS_PER_BYTE: usize = 4;
+
+/// Returns the quality score shared by all the symbols of given sequences if
+/// they can be stored packed, i.e. none of them contains [`Acid::N`] and all
+/// of them have the same quality score everywhere. Returns `None` otherwise.
+#[must_use]
+pub(super) fn packable_q_score(sequences: &[FastqSequence]) -> Option<FastqQualityScore> {
+    let mut q_scores = sequences
+        .iter()
+        .flat_map(|sequence| sequence.quality_scores().iter().copied());
+    let q_score = q_scores.next().unwrap_or_default();
+
+    let any_n = sequences
+        .iter()
+        .any(|sequence| sequence.acids().contains(&Acid::N));
+    if any_n || !q_scores.all(|other| other == q_score) {
+        return None;
+    }
+
+    Some(q_score)
+}
+
+/// Returns the number of bytes `seq_len` acids take when packed.
+#[must_use]
+pub(super) const fn packed_len(seq_len: usize) -> usize {
+    (seq_len + ACIDS_PER_BYTE - 1) / ACIDS_PER_BYTE
+}
+
+/// Stores the acids using 2 bits per acid.
+///
+/// # Panics
+/// Panics if any of the acids is [`Acid::N`].
+#[must_use]
+pub(super) fn pack_acids(acids: &[Acid]) -> Vec<u8> {
+    acids
+        .chunks(ACIDS_PER_BYTE)
+        .map(|chunk| {
+            chunk.iter().enumerate().fold(0, |byte, (i, acid)| {
+                assert_ne!(*acid, Acid::N, "Acid::N cannot be packed");
+                byte | ((acid.to_usize() - 1) as u8) << (2 * i)
+            })
+        })
+        .collect()
+}
+
+/// Restores `seq_len` acids packed with [`pack_acids()`]. `data` has to be
+/// exactly [`packed_len()`] bytes long.
+#[must_use]
+pub(super) fn unpack_acids(data: &[u8], seq_len: usize) -> Vec<Acid> {
+    debug_assert_eq!(data.len(), packed_len(seq_len));
+
+    data.iter()
+        .flat_map(|&byte| (0..ACIDS_PER_BYTE).map(move |i| (byte >> (2 * i)) & 0b11))
+        .take(seq_len)
+        .map(|value| Acid::from_usize(value as usize + 1))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::slice;
+
+    use crate::_internal_test_data::{SIMPLE_TEST_SEQUENCE, TEST_SEQUENCE_PREFER_A};
+    use crate::fastq::{FastqQualityScore, FastqSequence};
+    use crate::idn::packing::{pack_acids, packable_q_score, packed_len, unpack_acids};
+    use crate::sequence::Acid;
+
+    #[test]
+    fn test_pack_round_trip() {
+        let acids = [Acid::A, Acid::C, Acid::G, Acid::T, Acid::T, Acid::G];
+        for len in 0..=acids.len() {
+            let data = pack_acids(&acids[..len]);
+            assert_eq!(data.len(), packed_len(len));
+            assert_eq!(unpack_acids(&data, len), &acids[..len]);
+        }
+    }
+
+    #[test]
+    fn test_pack() {
+        assert_eq!(
+            pack_acids(&[Acid::A, Acid::C, Acid::T, Acid::G, Acid::C]),
+            [0b11_10_01_00, 0b01]
+        );
+    }
+
+    #[test]
+    fn test_packable_q_score() {
+        let q_score = FastqQualityScore::new(30);
+        let sequence =
+            |acids: &[Acid]| FastqSequence::new("", acids.to_vec(), vec![q_score; acids.len()]);
+
+        assert_eq!(packable_q_score(&[]), Some(FastqQualityScore::default()));
+        assert_eq!(
+            packable_q_score(&[sequence(&[Acid::A, Acid::C]), sequence(&[Acid::G])]),
+            Some(q_score)
+        );
+        assert_eq!(
+            packable_q_score(&[sequence(&[Acid::A]), sequence(&[Acid::N])]),
+            None
+        );
+        assert_eq!(
+            packable_q_score(slice::from_ref(&*SIMPLE_TEST_SEQUENCE)),
+            None
+        );
+        assert_eq!(
+            packable_q_score(&[sequence(&[Acid::A]), TEST_SEQUENCE_PREFER_A.clone()]),
+            None
+        );
+    }
+}
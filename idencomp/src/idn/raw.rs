@@ -0,0 +1,80 @@
+use crate::fastq::{FastqQualityScore, FastqSequence};
+use crate::idn::decompressor::{IdnDecompressResult, IdnDecompressorError};
+use crate::sequence::{Acid, Symbol};
+
+/// Returns the number of bytes a sequence of `seq_len` symbols takes when
+/// stored raw.
+#[must_use]
+pub(super) const fn raw_len(seq_len: usize) -> usize {
+    2 * seq_len
+}
+
+/// Stores the acids and the quality scores of given sequence as they are,
+/// using a single byte for each of them: all the acids go first, followed by
+/// all the quality scores.
+#[must_use]
+pub(super) fn store_raw(sequence: &FastqSequence) -> Vec<u8> {
+    let acids = sequence.acids().iter().map(|acid| acid.to_usize() as u8);
+    let q_scores = sequence
+        .quality_scores()
+        .iter()
+        .map(|q_score| q_score.get() as u8);
+
+    acids.chain(q_scores).collect()
+}
+
+/// Restores `seq_len` acids and quality scores stored with [`store_raw()`].
+/// `data` has to be exactly [`raw_len()`] bytes long.
+pub(super) fn load_raw(
+    data: &[u8],
+    seq_len: usize,
+) -> IdnDecompressResult<(Vec<Acid>, Vec<FastqQualityScore>)> {
+    debug_assert_eq!(data.len(), raw_len(seq_len));
+    let (acid_data, q_score_data) = data.split_at(seq_len);
+
+    let acids = acid_data
+        .iter()
+        .map(|&value| {
+            if value as usize >= Acid::SIZE {
+                return Err(IdnDecompressorError::invalid_acid(value));
+            }
+            Ok(Acid::from_usize(value as usize))
+        })
+        .collect::<IdnDecompressResult<_>>()?;
+    let q_scores = q_score_data
+        .iter()
+        .map(|&value| Ok(FastqQualityScore::try_new(value)?))
+        .collect::<IdnDecompressResult<_>>()?;
+
+    Ok((acids, q_scores))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::_internal_test_data::SIMPLE_TEST_SEQUENCE;
+    use crate::idn::decompressor::IdnDecompressorError;
+    use crate::idn::raw::{load_raw, raw_len, store_raw};
+
+    #[test]
+    fn test_raw_round_trip() {
+        let sequence = SIMPLE_TEST_SEQUENCE.clone();
+
+        let data = store_raw(&sequence);
+        assert_eq!(data.len(), raw_len(sequence.len()));
+        let (acids, q_scores) = load_raw(&data, sequence.len()).unwrap();
+        assert_eq!(acids, sequence.acids());
+        assert_eq!(q_scores, sequence.quality_scores());
+    }
+
+    #[test]
+    fn test_load_raw_invalid() {
+        assert!(matches!(
+            load_raw(&[1, 5, 30, 30], 2),
+            Err(IdnDecompressorError::InvalidAcid(5))
+        ));
+        assert!(matches!(
+            load_raw(&[1, 2, 30, 94], 2),
+            Err(IdnDecompressorError::InvalidQualityScore(_))
+        ));
+    }
+}
@@ -0,0 +1,383 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::io::{Read, Write};
+
+use crate::idn::compressor::{
+    CompressionStatsSnapshot, IdnCompressor, IdnCompressorError, IdnCompressorParams,
+};
+use crate::idn::decompressor::{IdnDecompressor, IdnDecompressorError, IdnDecompressorParams};
+use crate::idn::source_digest::PreservedDigest;
+use crate::idn::transform::{TransformInfo, MAX_TRANSFORMS};
+
+/// Error occurring when recompressing an IDN file.
+#[derive(Debug)]
+pub enum IdnRecompressError {
+    /// Error occurred when decompressing the input file.
+    ReadError(IdnDecompressorError),
+    /// Error occurred when compressing the output file.
+    WriteError(IdnCompressorError),
+    /// The lossy transforms of the input file along with the transforms set
+    /// in the compressor parameters are more than can be stored in the output
+    /// file metadata.
+    TooManyTransforms(usize),
+}
+
+impl From<IdnDecompressorError> for IdnRecompressError {
+    fn from(e: IdnDecompressorError) -> Self {
+        Self::ReadError(e)
+    }
+}
+
+impl From<IdnCompressorError> for IdnRecompressError {
+    fn from(e: IdnCompressorError) -> Self {
+        Self::WriteError(e)
+    }
+}
+
+impl Display for IdnRecompressError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IdnRecompressError::ReadError(e) => write!(f, "Read error: {}", e),
+            IdnRecompressError::WriteError(e) => write!(f, "Write error: {}", e),
+            IdnRecompressError::TooManyTransforms(num) => write!(
+                f,
+                "Too many transforms in the output file (found: {}, limit: {})",
+                num, MAX_TRANSFORMS
+            ),
+        }
+    }
+}
+
+impl Error for IdnRecompressError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            IdnRecompressError::ReadError(e) => Some(e),
+            IdnRecompressError::WriteError(e) => Some(e),
+            IdnRecompressError::TooManyTransforms(_) => None,
+        }
+    }
+}
+
+/// The result of recompressing an IDN file.
+pub type IdnRecompressResult<T> = Result<T, IdnRecompressError>;
+
+/// Decompresses an IDN file and compresses its sequences again with given
+/// parameters, e.g. to use better models or a higher compression quality.
+///
+/// The sequences are passed directly from the decompressor to the compressor,
+/// so the FASTQ data is never stored anywhere. The container members and the
+/// source digest of the input file are carried over to the output file; the
+/// [`SourceHasher`](crate::idn::source_digest::SourceHasher) set in
+/// `compressor_params` (if any) is not used, as the original input data is not
+/// available.
+///
+/// The lossy transforms of the input file are recorded in the output file as
+/// well, as the decompressed sequences are still affected by them. The
+/// lossless ones are reverted by the decompressor (provided they are set in
+/// `decompressor_params`), so they have to be set in `compressor_params`
+/// again to be applied to the output file.
+///
+/// Returns the statistics of the compression of the output file.
+///
+/// # Examples
+/// ```
+/// use idencomp::fastq::reader::FastqReader;
+/// use idencomp::idn::compressor::{CompressionQuality, IdnCompressor, IdnCompressorParams};
+/// use idencomp::idn::decompressor::{IdnDecompressor, IdnDecompressorParams};
+/// use idencomp::idn::recompress::recompress;
+///
+/// let mut data = Vec::new();
+/// let params = IdnCompressorParams::builder().fast(true).build()?;
+/// let mut compressor = IdnCompressor::with_params(&mut data, params);
+/// for sequence in FastqReader::new(b"@SEQ1\nACGT\n+\n!!!!\n".as_slice()) {
+///     compressor.add_sequence(sequence?)?;
+/// }
+/// compressor.finish()?;
+///
+/// let mut output = Vec::new();
+/// let params = IdnCompressorParams::builder()
+///     .quality(CompressionQuality::MAX)
+///     .build()?;
+/// let stats = recompress(
+///     data.as_slice(),
+///     &mut output,
+///     IdnDecompressorParams::default(),
+///     params,
+/// )?;
+/// assert_eq!(stats.in_symbols(), 4);
+///
+/// let mut decompressor = IdnDecompressor::new(output.as_slice());
+/// let sequence = decompressor.next_sequence()?.unwrap();
+/// assert_eq!(sequence.identifier().str(), "SEQ1");
+///
+/// # Ok::<(), idencomp::Error>(())
+/// ```
+pub fn recompress<R: Read + Send, W: Write + Send>(
+    reader: R,
+    writer: W,
+    decompressor_params: IdnDecompressorParams,
+    compressor_params: IdnCompressorParams,
+) -> IdnRecompressResult<CompressionStatsSnapshot> {
+    let mut decompressor = IdnDecompressor::with_params(reader, decompressor_params);
+    // The metadata of the input file is needed to set up the compressor, and
+    // it is read along with the first batch
+    let mut batch = decompressor.next_batch()?;
+
+    let mut compressor_params = compressor_params;
+    compressor_params.applied_transforms = decompressor
+        .transforms()
+        .into_iter()
+        .filter(TransformInfo::is_lossy)
+        .collect();
+    let num_transforms =
+        compressor_params.applied_transforms.len() + compressor_params.num_transforms();
+    if num_transforms > MAX_TRANSFORMS {
+        return Err(IdnRecompressError::TooManyTransforms(num_transforms));
+    }
+    let preserved_digest = PreservedDigest::default();
+    compressor_params.source_hasher = None;
+    compressor_params.preserved_digest = Some(preserved_digest.clone());
+    let mut compressor = IdnCompressor::with_params(writer, compressor_params);
+
+    loop {
+        for name in decompressor.take_started_members() {
+            compressor.start_member(name)?;
+        }
+        let sequences = match batch {
+            Some(sequences) => sequences,
+            None => break,
+        };
+        for sequence in sequences {
+            compressor.add_sequence(sequence)?;
+        }
+
+        batch = decompressor.next_batch()?;
+    }
+
+    // The digest might be stored at the end of the input file, so it is only
+    // available once all of it has been read
+    preserved_digest.set(decompressor.source_digest());
+    Ok(compressor.finish_with_stats()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::sync::Arc;
+
+    use crate::_internal_test_data::{
+        SEQ_1K_READS, TEST_ACID_MODEL_PREFER_A, TEST_ACID_MODEL_PREFER_C, TEST_SEQUENCE_PREFER_A,
+    };
+    use crate::idn::compressor::{IdnCompressor, IdnCompressorParams};
+    use crate::idn::decompressor::{IdnDecompressor, IdnDecompressorParams};
+    use crate::idn::inspector::inspect;
+    use crate::idn::model_provider::ModelProvider;
+    use crate::idn::recompress::{recompress, IdnRecompressError};
+    use crate::idn::source_digest::SourceHasher;
+    use crate::idn::transform::{QualityBinning, SequenceTransform, TransformInfo};
+    use crate::model::{Model, ModelType};
+
+    #[test]
+    fn test_recompress_with_different_models() {
+        let old_models = ModelProvider::new(vec![
+            TEST_ACID_MODEL_PREFER_C.clone(),
+            Model::empty(ModelType::QualityScores),
+        ]);
+        let new_models = ModelProvider::new(vec![
+            TEST_ACID_MODEL_PREFER_A.clone(),
+            Model::empty(ModelType::QualityScores),
+        ]);
+
+        let mut data = Vec::new();
+        let params = IdnCompressorParams::builder()
+            .model_provider(old_models.clone())
+            .build()
+            .unwrap();
+        let mut compressor = IdnCompressor::with_params(&mut data, params);
+        compressor
+            .add_sequence(TEST_SEQUENCE_PREFER_A.clone())
+            .unwrap();
+        compressor.finish().unwrap();
+
+        let mut output = Vec::new();
+        let decompressor_params = IdnDecompressorParams::builder()
+            .model_provider(old_models)
+            .build();
+        let compressor_params = IdnCompressorParams::builder()
+            .model_provider(new_models.clone())
+            .build()
+            .unwrap();
+        recompress(
+            data.as_slice(),
+            &mut output,
+            decompressor_params,
+            compressor_params,
+        )
+        .unwrap();
+
+        let info = inspect(output.as_slice()).unwrap();
+        assert!(info
+            .model_identifiers()
+            .contains(TEST_ACID_MODEL_PREFER_A.identifier()));
+        assert!(!info
+            .model_identifiers()
+            .contains(TEST_ACID_MODEL_PREFER_C.identifier()));
+
+        let params = IdnDecompressorParams::builder()
+            .model_provider(new_models)
+            .build();
+        let mut decompressor = IdnDecompressor::with_params(output.as_slice(), params);
+        assert_eq!(
+            decompressor.next_sequence().unwrap().as_ref(),
+            Some(&*TEST_SEQUENCE_PREFER_A)
+        );
+        assert_eq!(decompressor.next_sequence().unwrap(), None);
+    }
+
+    #[test]
+    fn test_recompress_carries_over_file_metadata() {
+        let (first, second) = SEQ_1K_READS.split_at(300);
+
+        for seekable in [false, true] {
+            let hasher = SourceHasher::new();
+            hasher.update(b"source data");
+            let params = IdnCompressorParams::builder()
+                .fast(true)
+                .source_hasher(hasher.clone())
+                .transform(Arc::new(QualityBinning))
+                .build()
+                .unwrap();
+            let mut data = Cursor::new(Vec::new());
+            let mut compressor = if seekable {
+                IdnCompressor::with_params_seekable(&mut data, params).unwrap()
+            } else {
+                IdnCompressor::with_params(&mut data, params)
+            };
+            for (name, sequences) in [("first", first), ("empty", &[]), ("second", second)] {
+                compressor.start_member(name).unwrap();
+                for sequence in sequences {
+                    compressor.add_sequence(sequence.clone()).unwrap();
+                }
+            }
+            compressor.finish().unwrap();
+
+            let mut output = Vec::new();
+            let compressor_params = IdnCompressorParams::builder().fast(true).build().unwrap();
+            recompress(
+                data.get_ref().as_slice(),
+                &mut output,
+                IdnDecompressorParams::default(),
+                compressor_params,
+            )
+            .unwrap();
+
+            let info = inspect(output.as_slice()).unwrap();
+            assert_eq!(info.source_digest(), Some(hasher.digest()));
+            assert_eq!(info.transforms(), [TransformInfo::of(&QualityBinning)]);
+            let members: Vec<_> = info
+                .members()
+                .iter()
+                .map(|member| (member.name().to_owned(), member.sequence_num()))
+                .collect();
+            assert_eq!(
+                members,
+                [
+                    ("first".to_owned(), 300),
+                    ("empty".to_owned(), 0),
+                    ("second".to_owned(), 700)
+                ]
+            );
+
+            let decompressor = IdnDecompressor::new(output.as_slice());
+            let sequences: Vec<_> = decompressor.into_iter().collect::<Result<_, _>>().unwrap();
+            let expected: Vec<_> = SEQ_1K_READS
+                .iter()
+                .map(|sequence| QualityBinning.apply(sequence.clone()))
+                .collect();
+            assert_eq!(sequences, expected);
+        }
+    }
+
+    #[test]
+    fn test_recompress_without_file_metadata() {
+        let mut data = Vec::new();
+        let params = IdnCompressorParams::builder().fast(true).build().unwrap();
+        let mut compressor = IdnCompressor::with_params(&mut data, params);
+        compressor
+            .add_sequence(TEST_SEQUENCE_PREFER_A.clone())
+            .unwrap();
+        compressor.finish().unwrap();
+
+        let mut output = Vec::new();
+        let compressor_params = IdnCompressorParams::builder()
+            .fast(true)
+            .source_hasher(SourceHasher::new())
+            .build()
+            .unwrap();
+        recompress(
+            data.as_slice(),
+            &mut output,
+            IdnDecompressorParams::default(),
+            compressor_params,
+        )
+        .unwrap();
+
+        let info = inspect(output.as_slice()).unwrap();
+        assert_eq!(info.source_digest(), None);
+        assert!(info.transforms().is_empty());
+        assert!(info.members().is_empty());
+    }
+
+    #[test]
+    fn test_recompress_invalid_input() {
+        let mut output = Vec::new();
+        let result = recompress(
+            b"INVALID".as_slice(),
+            &mut output,
+            IdnDecompressorParams::default(),
+            IdnCompressorParams::default(),
+        );
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_recompress_too_many_transforms() {
+        let transform: Arc<dyn SequenceTransform> = Arc::new(QualityBinning);
+        let builder = (0..255).fold(IdnCompressorParams::builder().fast(true), |builder, _| {
+            builder.transform(transform.clone())
+        });
+        let mut data = Vec::new();
+        let mut compressor = IdnCompressor::with_params(&mut data, builder.build().unwrap());
+        compressor
+            .add_sequence(TEST_SEQUENCE_PREFER_A.clone())
+            .unwrap();
+        compressor.finish().unwrap();
+
+        let mut output = Vec::new();
+        let compressor_params = IdnCompressorParams::builder()
+            .fast(true)
+            .transform(transform)
+            .build()
+            .unwrap();
+        let result = recompress(
+            data.as_slice(),
+            &mut output,
+            IdnDecompressorParams::default(),
+            compressor_params,
+        );
+
+        assert!(matches!(
+            result,
+            Err(IdnRecompressError::TooManyTransforms(256))
+        ));
+    }
+
+    #[test]
+    fn test_recompress_error_display() {
+        assert_eq!(
+            IdnRecompressError::TooManyTransforms(300).to_string(),
+            "Too many transforms in the output file (found: 300, limit: 255)"
+        );
+    }
+}
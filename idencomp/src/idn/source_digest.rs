@@ -0,0 +1,206 @@
+use std::fmt::{Debug, Display, Formatter};
+use std::io::{BufRead, Read};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use sha2::{Digest, Sha256};
+
+use crate::idn::common::opaque_debug;
+
+/// SHA-256 digest of the original (uncompressed) input data, stored in the
+/// IDN file metadata.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct SourceDigest([u8; 32]);
+
+impl SourceDigest {
+    /// Computes the digest of all the data read from given reader.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::idn::source_digest::SourceDigest;
+    ///
+    /// let digest = SourceDigest::of_reader(b"abc".as_slice())?;
+    /// assert_eq!(
+    ///     digest.to_string(),
+    ///     "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    /// );
+    ///
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn of_reader<R: Read>(mut reader: R) -> std::io::Result<Self> {
+        let mut hasher = Sha256::new();
+        std::io::copy(&mut reader, &mut hasher)?;
+
+        Ok(Self(hasher.finalize().into()))
+    }
+
+    /// Returns the raw bytes of this digest.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::idn::source_digest::SourceDigest;
+    ///
+    /// let digest = SourceDigest::from([1; 32]);
+    /// assert_eq!(digest.as_bytes(), &[1; 32]);
+    /// ```
+    #[must_use]
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+impl From<[u8; 32]> for SourceDigest {
+    fn from(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+}
+
+impl Display for SourceDigest {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for byte in self.0 {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+/// Computes a [`SourceDigest`] of the data as it is read by the FASTQ readers.
+///
+/// The input has to be read through [`Self::reader()`] (or passed to
+/// [`Self::update()`]) while the compressor, given a clone of the hasher with
+/// [`IdnCompressorParamsBuilder::source_hasher()`](crate::idn::compressor::IdnCompressorParamsBuilder::source_hasher),
+/// only reads the final digest once all the sequences have been compressed.
+/// The clones feed the same SHA-256 state, so it does not matter which one
+/// wraps the input.
+#[derive(Clone, Default)]
+pub struct SourceHasher {
+    hasher: Arc<Mutex<Sha256>>,
+}
+
+impl SourceHasher {
+    /// Creates a new `SourceHasher` instance.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wraps given reader, so that all the data read from it is added to this
+    /// hasher.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Read;
+    ///
+    /// use idencomp::idn::source_digest::{SourceDigest, SourceHasher};
+    ///
+    /// let hasher = SourceHasher::new();
+    /// let mut reader = hasher.reader(b"abc".as_slice());
+    /// reader.read_to_end(&mut Vec::new())?;
+    /// assert_eq!(hasher.digest(), SourceDigest::of_reader(b"abc".as_slice())?);
+    ///
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    #[must_use]
+    pub fn reader<R>(&self, reader: R) -> SourceHashingReader<R> {
+        SourceHashingReader {
+            inner: reader,
+            hasher: self.clone(),
+        }
+    }
+
+    /// Adds given data to this hasher.
+    pub fn update(&self, data: &[u8]) {
+        self.hasher().update(data);
+    }
+
+    /// Returns the digest of all the data added so far.
+    #[must_use]
+    pub fn digest(&self) -> SourceDigest {
+        SourceDigest(self.hasher().clone().finalize().into())
+    }
+
+    fn hasher(&self) -> MutexGuard<'_, Sha256> {
+        self.hasher
+            .lock()
+            .expect("Could not acquire source hasher lock")
+    }
+}
+
+// The intermediate SHA-256 state tells nothing about the data hashed so far
+opaque_debug!(SourceHasher);
+
+/// The digest of the source data of a recompressed IDN file, which is known
+/// only once all of the input file has been read. All the clones share the
+/// same value.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct PreservedDigest(Arc<Mutex<Option<SourceDigest>>>);
+
+impl PreservedDigest {
+    pub fn set(&self, digest: Option<SourceDigest>) {
+        *self.value() = digest;
+    }
+
+    #[must_use]
+    pub fn get(&self) -> Option<SourceDigest> {
+        *self.value()
+    }
+
+    fn value(&self) -> MutexGuard<'_, Option<SourceDigest>> {
+        self.0
+            .lock()
+            .expect("Could not acquire preserved digest lock")
+    }
+}
+
+/// Reader that adds all the data read through it to a [`SourceHasher`].
+/// Created with [`SourceHasher::reader()`].
+#[derive(Debug)]
+pub struct SourceHashingReader<R> {
+    inner: R,
+    hasher: SourceHasher,
+}
+
+impl<R: Read> Read for SourceHashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let size = self.inner.read(buf)?;
+        self.hasher.update(&buf[..size]);
+        Ok(size)
+    }
+}
+
+impl<R: BufRead> BufRead for SourceHashingReader<R> {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        // The buffer has already been filled, so this does not do any I/O
+        if let Ok(buf) = self.inner.fill_buf() {
+            self.hasher.update(&buf[..amt]);
+        }
+        self.inner.consume(amt);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Read};
+
+    use crate::idn::source_digest::{SourceDigest, SourceHasher};
+
+    #[test]
+    fn test_buf_read() {
+        let data: Vec<u8> = (0..10000).map(|i| (i % 251) as u8).collect();
+        let hasher = SourceHasher::new();
+        let mut reader = hasher.reader(BufReader::with_capacity(64, data.as_slice()));
+
+        let mut line = Vec::new();
+        reader.read_until(7, &mut line).unwrap();
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+
+        assert_eq!(
+            hasher.digest(),
+            SourceDigest::of_reader(data.as_slice()).unwrap()
+        );
+    }
+}
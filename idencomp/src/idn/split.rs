@@ -0,0 +1,236 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::io::{Read, Write};
+
+use binrw::BinWrite;
+
+use crate::idn::compressor::IdnCompressorError;
+use crate::idn::data::IdnBlockHeader;
+use crate::idn::decompressor::{IdnDecompressResult, IdnDecompressorError};
+use crate::idn::inspector::{inspect_block, read_block, read_file_start};
+use crate::idn::no_seek::NoSeek;
+use crate::idn::transform::TransformInfo;
+use crate::idn::writer_idn::IdnWriter;
+use crate::model::ModelIdentifier;
+use crate::quality_alphabet::QualityAlphabet;
+
+/// Error occurring when splitting an IDN file.
+#[derive(Debug)]
+pub enum IdnSplitError {
+    /// Error occurred when reading the input file.
+    ReadError(IdnDecompressorError),
+    /// Error occurred when writing one of the output files.
+    WriteError(IdnCompressorError),
+}
+
+impl From<IdnDecompressorError> for IdnSplitError {
+    fn from(e: IdnDecompressorError) -> Self {
+        Self::ReadError(e)
+    }
+}
+
+impl From<IdnCompressorError> for IdnSplitError {
+    fn from(e: IdnCompressorError) -> Self {
+        Self::WriteError(e)
+    }
+}
+
+impl Display for IdnSplitError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IdnSplitError::ReadError(e) => write!(f, "Read error: {}", e),
+            IdnSplitError::WriteError(e) => write!(f, "Write error: {}", e),
+        }
+    }
+}
+
+impl Error for IdnSplitError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            IdnSplitError::ReadError(e) => Some(e),
+            IdnSplitError::WriteError(e) => Some(e),
+        }
+    }
+}
+
+/// The result of splitting an IDN file.
+pub type IdnSplitResult<T> = Result<T, IdnSplitError>;
+
+#[derive(Debug)]
+struct PendingBlock {
+    header: IdnBlockHeader,
+    data: Vec<u8>,
+    sequence_num: usize,
+}
+
+/// Object that cuts an IDN file into multiple valid IDN files along the block
+/// boundaries, without recompressing any of the blocks.
+///
+/// # Examples
+/// ```
+/// use idencomp::idn::compressor::IdnCompressor;
+/// use idencomp::idn::split::{IdnSplitError, IdnSplitter};
+///
+/// let mut data = Vec::new();
+/// IdnCompressor::new(&mut data).finish().unwrap();
+///
+/// let mut splitter = IdnSplitter::new(data.as_slice())?;
+/// assert!(splitter.is_finished());
+///
+/// # Ok::<(), IdnSplitError>(())
+/// ```
+#[derive(Debug)]
+pub struct IdnSplitter<R> {
+    reader: NoSeek<R>,
+    model_identifiers: Vec<ModelIdentifier>,
+    transforms: Vec<TransformInfo>,
+    quality_alphabet: QualityAlphabet,
+    pending_block: Option<PendingBlock>,
+}
+
+impl<R: Read> IdnSplitter<R> {
+    /// Creates a new `IdnSplitter` instance, reading the IDN file header and
+    /// the first block.
+    pub fn new(reader: R) -> IdnSplitResult<Self> {
+        let mut reader = NoSeek::new(reader);
+        let file_start = read_file_start(&mut reader)?;
+
+        let mut splitter = Self {
+            reader,
+            model_identifiers: file_start.model_identifiers,
+            transforms: file_start.transforms,
+            quality_alphabet: file_start.quality_alphabet,
+            pending_block: None,
+        };
+        splitter.read_next_block()?;
+
+        Ok(splitter)
+    }
+
+    /// Returns whether all the blocks from the input file have been written.
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        self.pending_block.is_none()
+    }
+
+    /// Writes the next part of the input file as a complete IDN file to
+    /// `writer` and returns the number of sequences written.
+    ///
+    /// Blocks are added to the part as long as the total number of sequences
+    /// does not exceed `max_sequences`. At least one block is always written,
+    /// even if it alone exceeds the limit. If the splitter is already finished,
+    /// this writes an empty (but valid) IDN file.
+    pub fn write_part<W: Write>(
+        &mut self,
+        writer: W,
+        max_sequences: usize,
+    ) -> IdnSplitResult<usize> {
+        let mut writer = IdnWriter::new(NoSeek::new(writer));
+        writer.write_header(1)?;
+        writer.add_quality_alphabet_metadata(&self.quality_alphabet);
+        writer.add_models_metadata(&self.model_identifiers);
+        writer.add_transforms_metadata(&self.transforms);
+        writer.write_metadata()?;
+        let writer = writer.writer_for_block();
+
+        let mut sequence_num = 0;
+        while let Some(block) = &self.pending_block {
+            if sequence_num > 0 && sequence_num + block.sequence_num > max_sequences {
+                break;
+            }
+
+            block
+                .header
+                .write_to(writer)
+                .map_err(IdnCompressorError::from)?;
+            writer
+                .write_all(&block.data)
+                .map_err(IdnCompressorError::from)?;
+            sequence_num += block.sequence_num;
+
+            self.read_next_block()?;
+        }
+
+        let eof_header = IdnBlockHeader {
+            length: 0,
+            seq_checksum: crc32fast::Hasher::new().finalize(),
+        };
+        eof_header
+            .write_to(writer)
+            .map_err(IdnCompressorError::from)?;
+
+        Ok(sequence_num)
+    }
+
+    fn read_next_block(&mut self) -> IdnDecompressResult<()> {
+        self.pending_block = match read_block(&mut self.reader)? {
+            Some((header, data)) => {
+                let info = inspect_block(&header, &data)?;
+                Some(PendingBlock {
+                    header,
+                    data,
+                    sequence_num: info.sequence_num(),
+                })
+            }
+            None => None,
+        };
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::_internal_test_data::SEQ_1K_READS;
+    use crate::idn::compressor::{IdnCompressor, IdnCompressorParams};
+    use crate::idn::decompressor::{IdnDecompressor, IdnDecompressorParams};
+    use crate::idn::inspector::inspect;
+    use crate::idn::model_provider::ModelProvider;
+    use crate::idn::split::IdnSplitter;
+    use crate::model::{Model, ModelType};
+
+    #[test]
+    fn test_split() {
+        let model_provider = ModelProvider::new(vec![
+            Model::empty(ModelType::Acids),
+            Model::empty(ModelType::QualityScores),
+        ]);
+        let mut data = Vec::new();
+        let params = IdnCompressorParams::builder()
+            .model_provider(model_provider.clone())
+            .max_block_total_len(2000)
+            .build()
+            .unwrap();
+        let mut compressor = IdnCompressor::with_params(&mut data, params);
+        for sequence in SEQ_1K_READS.iter() {
+            compressor.add_sequence(sequence.clone()).unwrap();
+        }
+        compressor.finish().unwrap();
+
+        let mut splitter = IdnSplitter::new(data.as_slice()).unwrap();
+        let mut parts = Vec::new();
+        while !splitter.is_finished() {
+            let mut part = Vec::new();
+            let sequence_num = splitter.write_part(&mut part, 300).unwrap();
+            assert!(sequence_num <= 300);
+            assert_eq!(
+                inspect(part.as_slice()).unwrap().sequence_num(),
+                sequence_num
+            );
+            parts.push(part);
+        }
+        assert_eq!(parts.len(), 4);
+
+        let mut sequences = Vec::new();
+        for part in &parts {
+            let params = IdnDecompressorParams::builder()
+                .model_provider(model_provider.clone())
+                .build();
+            let decompressor = IdnDecompressor::with_params(part.as_slice(), params);
+            for sequence in decompressor {
+                sequences.push(sequence.unwrap());
+            }
+        }
+        assert_eq!(&sequences, &*SEQ_1K_READS);
+    }
+}
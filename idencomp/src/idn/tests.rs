@@ -1,14 +1,42 @@
+use std::io::Cursor;
+use std::ops::Range;
+use std::slice;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
 use crate::_internal_test_data::{
-    SHORT_TEST_SEQUENCE, SIMPLE_TEST_SEQUENCE, TEST_ACID_MODEL_PREFER_A, TEST_ACID_MODEL_PREFER_C,
-    TEST_SEQUENCE_PREFER_A, TEST_SEQUENCE_PREFER_C,
+    SEQ_1K_READS, SEQ_1K_READS_FASTQ, SHORT_TEST_SEQUENCE, SIMPLE_ACID_MODEL, SIMPLE_Q_SCORE_MODEL,
+    SIMPLE_TEST_SEQUENCE, TEST_ACID_MODEL_PREFER_A, TEST_ACID_MODEL_PREFER_C,
+    TEST_Q_SCORE_MODEL_PREFER_0, TEST_Q_SCORE_MODEL_PREFER_50, TEST_SEQUENCE_PREFER_A,
+    TEST_SEQUENCE_PREFER_C,
 };
-use crate::fastq::FastqSequence;
+use crate::fastq::parallel_reader::ParallelFastqReader;
+use crate::fastq::reader::FastqReader;
+use crate::fastq::{FastqQualityScore, FastqSequence};
 use crate::idn::compressor::{
-    CompressionQuality, IdnCompressor, IdnCompressorParams, IdnCompressorParamsBuilder,
+    CompressionQuality, IdnCompressor, IdnCompressorError, IdnCompressorParams,
+    IdnCompressorParamsBuilder,
+};
+use crate::idn::decompressor::{
+    IdnDecompressor, IdnDecompressorError, IdnDecompressorLimits, IdnDecompressorParams,
+    SequenceFilter,
 };
-use crate::idn::decompressor::{IdnDecompressor, IdnDecompressorParams};
+use crate::idn::encode_info::SequenceEncoding;
+use crate::idn::explain::{SequenceExplainer, SequenceExplanation};
+use crate::idn::inspector::{inspect, IdentifierCompression};
 use crate::idn::model_provider::ModelProvider;
+use crate::idn::model_selection::{
+    ExhaustiveSelection, ModelCandidates, ModelSelectionStrategy, RoundRobinSelection,
+    SampledSelection, StickySelection,
+};
+use crate::idn::source_digest::{SourceDigest, SourceHasher};
+use crate::idn::time_budget::REDUCED_EFFORT_MODEL_CHOOSER_INTERVAL;
+use crate::idn::transform::{QualityBinning, QualityDelta, SequenceTransform};
+use crate::idn::warning::{Warning, WarningSink};
 use crate::model::{Model, ModelType};
+use crate::progress::{ByteNum, ProgressNotifier, ProgressStage};
+use crate::quality_alphabet::QualityAlphabet;
+use crate::sequence::Acid;
 
 #[test_log::test]
 fn test_round_trip_empty_file() {
@@ -35,9 +63,7 @@ fn test_round_trip_sequence_identifiers_disabled() {
         &sequences_in,
         &sequences_out,
         ModelProvider::default(),
-        |builder| {
-            builder.include_identifiers(false);
-        },
+        |builder| builder.include_identifiers(false),
     );
 }
 
@@ -47,6 +73,95 @@ fn test_round_trip_multiple_sequences() {
     round_trip_sequences(&sequences);
 }
 
+#[test]
+fn test_round_trip_empty_sequences() {
+    let empty = FastqSequence::new("empty", [], []);
+    let sequences = [
+        empty.clone(),
+        SIMPLE_TEST_SEQUENCE.clone(),
+        empty.clone(),
+        SHORT_TEST_SEQUENCE.clone(),
+    ];
+    round_trip_sequences(&sequences);
+    round_trip_sequences_custom(
+        &sequences,
+        &sequences,
+        ModelProvider::default(),
+        |builder| builder.fast(true),
+    );
+
+    let only_empty = vec![empty; 10];
+    round_trip_sequences(&only_empty);
+    round_trip_sequences_custom(
+        &only_empty,
+        &only_empty,
+        ModelProvider::default(),
+        |builder| builder.fast(true),
+    );
+}
+
+#[test]
+fn test_empty_sequences_block_length() {
+    let params = IdnCompressorParams::builder()
+        .max_block_total_len(100)
+        .build()
+        .unwrap();
+    let mut data = Vec::new();
+    let mut idn_writer = IdnCompressor::with_params(&mut data, params);
+    for _ in 0..250 {
+        idn_writer
+            .add_sequence(FastqSequence::new("", [], []))
+            .unwrap();
+    }
+    idn_writer.finish().unwrap();
+
+    let info = inspect(data.as_slice()).unwrap();
+    let block_sizes: Vec<_> = info.blocks().iter().map(|b| b.sequence_num()).collect();
+    assert_eq!(block_sizes, [100, 100, 50]);
+}
+
+#[test]
+fn test_empty_sequences_encoding() {
+    let reported = Arc::new(Mutex::new(Vec::new()));
+    let recorder = reported.clone();
+    let warnings = Arc::new(WarningRecorder::default());
+    // The models fit the non-empty sequence well, so a poor fit could only be
+    // caused by the empty one
+    let model_provider = ModelProvider::new(vec![
+        TEST_ACID_MODEL_PREFER_A.clone(),
+        TEST_Q_SCORE_MODEL_PREFER_0.clone(),
+    ]);
+    let params = IdnCompressorParams::builder()
+        .model_provider(model_provider)
+        .on_sequence(move |_, info| recorder.lock().unwrap().push(info.clone()))
+        .warning_sink(warnings.clone())
+        .build()
+        .unwrap();
+
+    let mut idn_writer = IdnCompressor::with_params(Vec::new(), params);
+    idn_writer
+        .add_sequence(FastqSequence::new("empty", [], []))
+        .unwrap();
+    idn_writer
+        .add_sequence(TEST_SEQUENCE_PREFER_A.clone())
+        .unwrap();
+    idn_writer.finish().unwrap();
+
+    let reported = reported.lock().unwrap();
+    let encodings: Vec<_> = reported.iter().map(|info| info.encoding()).collect();
+    assert_eq!(
+        encodings,
+        [SequenceEncoding::Empty, SequenceEncoding::Models]
+    );
+    assert_eq!(reported[0].compressed_bytes(), 0);
+    assert!(!warnings
+        .warnings
+        .lock()
+        .unwrap()
+        .iter()
+        .any(|warning| matches!(warning, Warning::ModelPoorFit { .. })));
+}
+
 #[test_log::test]
 fn test_round_trip_multiple_models() {
     let models = vec![
@@ -63,6 +178,194 @@ fn test_round_trip_multiple_models() {
     round_trip_sequences_with_model_provider(&sequences, model_provider);
 }
 
+#[test]
+fn test_decompressor_model_stats() {
+    let models = vec![
+        TEST_ACID_MODEL_PREFER_A.clone(),
+        TEST_ACID_MODEL_PREFER_C.clone(),
+        Model::empty(ModelType::QualityScores),
+    ];
+    let model_provider = ModelProvider::new(models.clone());
+    let sequences = [
+        TEST_SEQUENCE_PREFER_A.clone(),
+        TEST_SEQUENCE_PREFER_C.clone(),
+    ];
+
+    let mut data = Vec::new();
+    let params = IdnCompressorParams::builder()
+        .model_provider(model_provider.clone())
+        .build()
+        .unwrap();
+    let mut idn_writer = IdnCompressor::with_params(&mut data, params);
+    for sequence in &sequences {
+        idn_writer.add_sequence(sequence.clone()).unwrap();
+    }
+    idn_writer.finish().unwrap();
+
+    let reader_params = IdnDecompressorParams::builder()
+        .model_provider(model_provider)
+        .build();
+    let mut idn_reader = IdnDecompressor::with_params(data.as_slice(), reader_params);
+    while idn_reader.next_sequence().unwrap().is_some() {}
+
+    let mut stats: Vec<_> = idn_reader
+        .model_stats()
+        .into_iter()
+        .map(|stats| {
+            (
+                stats.identifier().clone(),
+                stats.sequences(),
+                stats.symbols(),
+            )
+        })
+        .collect();
+    stats.sort();
+    let mut expected: Vec<_> = [
+        (&models[0], 1, 100),
+        (&models[1], 1, 100),
+        (&models[2], 2, 200),
+    ]
+    .into_iter()
+    .map(|(model, sequences, symbols)| (model.identifier().clone(), sequences, symbols))
+    .collect();
+    expected.sort();
+    assert_eq!(stats, expected);
+}
+
+#[test]
+fn test_round_trip_model_selection() {
+    let strategies: [Arc<dyn ModelSelectionStrategy>; 4] = [
+        Arc::new(ExhaustiveSelection),
+        Arc::new(SampledSelection::new(16)),
+        Arc::new(StickySelection::default()),
+        Arc::new(RoundRobinSelection),
+    ];
+    let model_provider = ModelProvider::new(vec![
+        TEST_ACID_MODEL_PREFER_A.clone(),
+        TEST_ACID_MODEL_PREFER_C.clone(),
+        Model::empty(ModelType::QualityScores),
+    ]);
+    let sequences = [
+        TEST_SEQUENCE_PREFER_A.clone(),
+        TEST_SEQUENCE_PREFER_C.clone(),
+        TEST_SEQUENCE_PREFER_C.clone(),
+    ];
+
+    for strategy in strategies {
+        round_trip_sequences_custom(&sequences, &sequences, model_provider.clone(), |builder| {
+            builder.model_selection(strategy)
+        });
+    }
+}
+
+#[derive(Debug)]
+struct InvalidSelection;
+
+impl ModelSelectionStrategy for InvalidSelection {
+    fn select(&self, candidates: &mut ModelCandidates) -> usize {
+        candidates.len()
+    }
+}
+
+#[test]
+fn test_invalid_model_selection() {
+    let model_provider = ModelProvider::new(vec![
+        TEST_ACID_MODEL_PREFER_A.clone(),
+        TEST_ACID_MODEL_PREFER_C.clone(),
+        Model::empty(ModelType::QualityScores),
+    ]);
+    let params = IdnCompressorParams::builder()
+        .model_provider(model_provider)
+        .model_selection(Arc::new(InvalidSelection))
+        .build()
+        .unwrap();
+    let mut idn_writer = IdnCompressor::with_params(Vec::new(), params);
+    idn_writer
+        .add_sequence(TEST_SEQUENCE_PREFER_A.clone())
+        .unwrap();
+
+    let result = idn_writer.finish();
+    assert!(matches!(
+        result,
+        Err(IdnCompressorError::InvalidModelSelection(index, candidate_num))
+            if index == candidate_num
+    ));
+}
+
+#[test]
+fn test_deterministic_output() {
+    let compress = |models: Vec<Model>, thread_num: usize| {
+        let params = IdnCompressorParams::builder()
+            .model_provider(ModelProvider::new(models))
+            .max_block_total_len(2000)
+            .thread_num(thread_num)
+            .deterministic(true)
+            .build()
+            .unwrap();
+
+        let mut data = Vec::new();
+        let mut idn_writer = IdnCompressor::with_params(&mut data, params);
+        for sequence in SEQ_1K_READS.iter() {
+            idn_writer.add_sequence(sequence.clone()).unwrap();
+        }
+        idn_writer.finish().unwrap();
+
+        data
+    };
+
+    let models = vec![
+        TEST_ACID_MODEL_PREFER_A.clone(),
+        TEST_ACID_MODEL_PREFER_C.clone(),
+        Model::empty(ModelType::Acids),
+        Model::empty(ModelType::QualityScores),
+    ];
+    let mut models_reversed = models.clone();
+    models_reversed.reverse();
+
+    let expected = compress(models, 0);
+    assert_eq!(compress(models_reversed.clone(), 0), expected);
+    assert_eq!(compress(models_reversed, 4), expected);
+}
+
+#[test]
+fn test_parallel_sequence_encoding() {
+    let model_provider = ModelProvider::new(vec![
+        TEST_ACID_MODEL_PREFER_A.clone(),
+        TEST_ACID_MODEL_PREFER_C.clone(),
+        Model::empty(ModelType::QualityScores),
+    ]);
+    let compress = |thread_num: usize| {
+        let params = IdnCompressorParams::builder()
+            .model_provider(model_provider.clone())
+            .thread_num(thread_num)
+            .build()
+            .unwrap();
+
+        let mut data = Vec::new();
+        let mut idn_writer = IdnCompressor::with_params(&mut data, params);
+        for sequence in SEQ_1K_READS.iter() {
+            idn_writer.add_sequence(sequence.clone()).unwrap();
+        }
+        idn_writer.finish().unwrap();
+
+        data
+    };
+
+    // All the sequences fit in a single block, so they can only be compressed
+    // in parallel within the block
+    let expected = compress(0);
+    assert_eq!(inspect(expected.as_slice()).unwrap().blocks().len(), 1);
+    let data = compress(4);
+    assert_eq!(data, expected);
+
+    let params = IdnDecompressorParams::builder()
+        .model_provider(model_provider)
+        .build();
+    let idn_reader = IdnDecompressor::with_params(data.as_slice(), params);
+    let sequences: Vec<FastqSequence> = idn_reader.into_iter().collect::<Result<_, _>>().unwrap();
+    assert_eq!(sequences, SEQ_1K_READS.as_slice());
+}
+
 #[test_log::test]
 fn test_round_trip_all_quals() {
     let models = vec![
@@ -79,11 +382,2032 @@ fn test_round_trip_all_quals() {
 
     for quality in 1..=9 {
         round_trip_sequences_custom(&sequences, &sequences, model_provider.clone(), |builder| {
-            builder.quality(CompressionQuality::new(quality));
+            builder.quality(CompressionQuality::new(quality))
+        });
+    }
+}
+
+#[test]
+fn test_round_trip_group_by_tile() {
+    let sequences: Vec<_> = SEQ_1K_READS
+        .iter()
+        .take(60)
+        .enumerate()
+        .map(|(index, sequence)| {
+            let identifier = match index {
+                0..=19 => format!("M00123:42:FC:1:1101:{}:1000 1:N:0:1", index),
+                20..=29 => format!("SEQ_{}", index),
+                _ => format!("M00123:42:FC:1:1102:{}:1000 1:N:0:1", index),
+            };
+            sequence.clone().with_identifier(identifier)
+        })
+        .collect();
+
+    round_trip_sequences_custom(
+        &sequences,
+        &sequences,
+        ModelProvider::default(),
+        |builder| builder.group_by_tile(true),
+    );
+}
+
+#[test]
+fn test_round_trip_time_budget() {
+    let models = vec![
+        TEST_ACID_MODEL_PREFER_A.clone(),
+        TEST_ACID_MODEL_PREFER_C.clone(),
+        Model::empty(ModelType::QualityScores),
+    ];
+    let model_provider = ModelProvider::new(models);
+    let sequences: Vec<_> = SEQ_1K_READS
+        .iter()
+        .take(100)
+        .chain([&*TEST_SEQUENCE_PREFER_A, &*TEST_SEQUENCE_PREFER_C])
+        .cloned()
+        .collect();
+
+    for (time_budget, expected_input_size) in [
+        // Already exceeded, so the effort is minimal from the start
+        (Duration::ZERO, None),
+        (Duration::from_secs(3600), None),
+        (Duration::from_secs(3600), Some(ByteNum::new(1))),
+    ] {
+        round_trip_sequences_custom(&sequences, &sequences, model_provider.clone(), |builder| {
+            let builder = builder
+                .quality(CompressionQuality::MAX)
+                .max_block_total_len(2000)
+                .time_budget(time_budget);
+            match expected_input_size {
+                Some(size) => builder.expected_input_size(size),
+                None => builder,
+            }
         });
     }
 }
 
+#[test]
+fn test_round_trip_adaptive_effort() {
+    // The binned blocks are compressed with a lower effort than the rest
+    let sequences: Vec<_> = SEQ_1K_READS
+        .iter()
+        .enumerate()
+        .map(|(index, sequence)| {
+            if index < 500 {
+                QualityBinning.apply(sequence.clone())
+            } else {
+                sequence.clone()
+            }
+        })
+        .collect();
+
+    let recorder = Arc::new(ExplanationRecorder::default());
+    round_trip_sequences_custom(
+        &sequences,
+        &sequences,
+        ModelProvider::default(),
+        |builder| {
+            builder
+                .adaptive_effort(true)
+                .max_block_reads(100)
+                .explainer(recorder.clone())
+        },
+    );
+
+    // The models are only chosen for the sequences with an estimated size
+    let explanations = recorder.explanations.lock().unwrap();
+    let chosen_in_blocks = |blocks: Range<u32>| {
+        explanations
+            .iter()
+            .filter(|explanation| blocks.contains(&explanation.block()))
+            .filter(|explanation| explanation.estimated_bytes().is_some())
+            .count()
+    };
+    assert!(chosen_in_blocks(0..5) <= 5 * 100 / REDUCED_EFFORT_MODEL_CHOOSER_INTERVAL + 5);
+    assert_eq!(chosen_in_blocks(5..10), 500);
+}
+
+fn sequences_with_duplicates() -> Vec<FastqSequence> {
+    vec![
+        SIMPLE_TEST_SEQUENCE.clone(),
+        SHORT_TEST_SEQUENCE.clone(),
+        SIMPLE_TEST_SEQUENCE.clone().with_identifier("SEQ_DUP_1"),
+        SIMPLE_TEST_SEQUENCE.clone().with_identifier("SEQ_DUP_2"),
+        SHORT_TEST_SEQUENCE.clone(),
+    ]
+}
+
+#[test]
+fn test_round_trip_duplicates() {
+    let sequences = sequences_with_duplicates();
+    round_trip_sequences_custom(
+        &sequences,
+        &sequences,
+        ModelProvider::default(),
+        |builder| builder.deduplicate(true),
+    );
+
+    let sequences_out: Vec<_> = sequences
+        .iter()
+        .map(|sequence| sequence.clone().with_identifier_discarded())
+        .collect();
+    round_trip_sequences_custom(
+        &sequences,
+        &sequences_out,
+        ModelProvider::default(),
+        |builder| builder.deduplicate(true).include_identifiers(false),
+    );
+}
+
+/// Returns sequences with the same acids as the earlier ones, but different
+/// quality scores.
+fn sequences_with_acid_duplicates() -> Vec<FastqSequence> {
+    let with_q_score = |sequence: &FastqSequence, identifier: &str, q_score: u8| {
+        FastqSequence::new(
+            identifier,
+            sequence.acids(),
+            vec![FastqQualityScore::new(q_score); sequence.len()],
+        )
+    };
+
+    vec![
+        SIMPLE_TEST_SEQUENCE.clone(),
+        SHORT_TEST_SEQUENCE.clone(),
+        with_q_score(&SIMPLE_TEST_SEQUENCE, "SEQ_DUP_1", 20),
+        with_q_score(&SHORT_TEST_SEQUENCE, "SEQ_DUP_2", 30),
+        with_q_score(&SIMPLE_TEST_SEQUENCE, "SEQ_DUP_3", 20),
+    ]
+}
+
+#[test]
+fn test_round_trip_acid_duplicates() {
+    let sequences = sequences_with_acid_duplicates();
+    round_trip_sequences_custom(
+        &sequences,
+        &sequences,
+        ModelProvider::default(),
+        |builder| builder.deduplicate(true),
+    );
+    round_trip_sequences_custom(
+        &sequences,
+        &sequences,
+        ModelProvider::default(),
+        |builder| builder.deduplicate(true).fast(true),
+    );
+    round_trip_sequences_custom(
+        &sequences,
+        &sequences,
+        ModelProvider::default(),
+        |builder| builder.deduplicate(true).skip_n_q_scores(true),
+    );
+
+    let params = IdnCompressorParams::builder()
+        .deduplicate(true)
+        .build()
+        .unwrap();
+    let mut data = Vec::new();
+    let mut idn_writer = IdnCompressor::with_params(&mut data, params);
+    for sequence in sequences {
+        idn_writer.add_sequence(sequence).unwrap();
+    }
+    idn_writer.finish().unwrap();
+
+    let info = inspect(data.as_slice()).unwrap();
+    assert_eq!(info.blocks()[0].duplicate_num(), 3);
+}
+
+#[test]
+fn test_on_sequence() {
+    let reported = Arc::new(Mutex::new(Vec::new()));
+    let recorder = reported.clone();
+    let params = IdnCompressorParams::builder()
+        .deduplicate(true)
+        .on_sequence(move |sequence, info| {
+            recorder
+                .lock()
+                .unwrap()
+                .push((sequence.clone(), info.clone()));
+        })
+        .build()
+        .unwrap();
+
+    let sequences = sequences_with_duplicates();
+    let mut idn_writer = IdnCompressor::with_params(Vec::new(), params);
+    for sequence in &sequences {
+        idn_writer.add_sequence(sequence.clone()).unwrap();
+    }
+    idn_writer.finish().unwrap();
+
+    let reported = reported.lock().unwrap();
+    let reported_sequences: Vec<_> = reported
+        .iter()
+        .map(|(sequence, _)| sequence.clone())
+        .collect();
+    assert_eq!(reported_sequences, sequences);
+    let encodings: Vec<_> = reported.iter().map(|(_, info)| info.encoding()).collect();
+    assert_eq!(
+        encodings,
+        [
+            SequenceEncoding::Models,
+            SequenceEncoding::Models,
+            SequenceEncoding::Duplicate,
+            SequenceEncoding::Duplicate,
+            SequenceEncoding::Duplicate,
+        ]
+    );
+    assert!(reported
+        .iter()
+        .enumerate()
+        .all(|(index, (_, info))| info.index() == index));
+    assert!(reported[0].1.compressed_bytes() > 0);
+    assert_eq!(reported[2].1.compressed_bytes(), 0);
+}
+
+#[test]
+fn test_deduplicate() {
+    let compress = |deduplicate: bool| {
+        let params = IdnCompressorParams::builder()
+            .deduplicate(deduplicate)
+            .build()
+            .unwrap();
+
+        let mut data = Vec::new();
+        let mut idn_writer = IdnCompressor::with_params(&mut data, params);
+        for sequence in sequences_with_duplicates() {
+            idn_writer.add_sequence(sequence).unwrap();
+        }
+        idn_writer.finish().unwrap();
+
+        data
+    };
+
+    let data = compress(false);
+    let data_deduplicated = compress(true);
+    assert!(data_deduplicated.len() < data.len());
+
+    let info = inspect(data_deduplicated.as_slice()).unwrap();
+    assert_eq!(info.sequence_num(), 5);
+    assert_eq!(info.blocks()[0].duplicate_num(), 3);
+    let info = inspect(data.as_slice()).unwrap();
+    assert_eq!(info.blocks()[0].duplicate_num(), 0);
+}
+
+#[test]
+fn test_source_digest() {
+    let expected_digest = SourceDigest::of_reader(SEQ_1K_READS_FASTQ).unwrap();
+
+    for thread_num in [0, 4] {
+        let hasher = SourceHasher::new();
+        let params = IdnCompressorParams::builder()
+            .fast(true)
+            .max_block_total_len(760)
+            .thread_num(thread_num)
+            .source_hasher(hasher.clone())
+            .build()
+            .unwrap();
+
+        let mut data = Vec::new();
+        let mut idn_writer = IdnCompressor::with_params(&mut data, params);
+        for sequence in ParallelFastqReader::new(hasher.reader(SEQ_1K_READS_FASTQ)) {
+            idn_writer.add_sequence(sequence.unwrap()).unwrap();
+        }
+        let stats = idn_writer.finish_with_stats().unwrap();
+        assert_eq!(stats.out_bytes(), data.len());
+
+        let info = inspect(data.as_slice()).unwrap();
+        assert_eq!(info.source_digest(), Some(expected_digest));
+
+        let idn_reader = IdnDecompressor::new(data.as_slice());
+        let sequences: Vec<FastqSequence> =
+            idn_reader.into_iter().collect::<Result<_, _>>().unwrap();
+        assert_eq!(sequences, SEQ_1K_READS.as_slice());
+    }
+
+    let data = compress_fast(&SEQ_1K_READS);
+    assert_eq!(inspect(data.as_slice()).unwrap().source_digest(), None);
+}
+
+#[test]
+fn test_source_digest_seekable() {
+    let expected_digest = SourceDigest::of_reader(SEQ_1K_READS_FASTQ).unwrap();
+    let prefix = b"prefix";
+
+    for thread_num in [0, 4] {
+        let hasher = SourceHasher::new();
+        let params = IdnCompressorParams::builder()
+            .fast(true)
+            .max_block_total_len(760)
+            .thread_num(thread_num)
+            .source_hasher(hasher.clone())
+            .build()
+            .unwrap();
+
+        let mut cursor = Cursor::new(prefix.to_vec());
+        cursor.set_position(prefix.len() as u64);
+        let mut idn_writer = IdnCompressor::with_params_seekable(&mut cursor, params).unwrap();
+        for sequence in ParallelFastqReader::new(hasher.reader(SEQ_1K_READS_FASTQ)) {
+            idn_writer.add_sequence(sequence.unwrap()).unwrap();
+        }
+        let stats = idn_writer.finish_with_stats().unwrap();
+        let data = cursor.into_inner();
+        assert_eq!(&data[..prefix.len()], prefix);
+        let data = &data[prefix.len()..];
+        assert_eq!(stats.out_bytes(), data.len());
+
+        let info = inspect(data).unwrap();
+        assert_eq!(info.source_digest(), Some(expected_digest));
+
+        let idn_reader = IdnDecompressor::new(data);
+        let sequences: Vec<FastqSequence> =
+            idn_reader.into_iter().collect::<Result<_, _>>().unwrap();
+        assert_eq!(sequences, SEQ_1K_READS.as_slice());
+    }
+}
+
+fn compress_fast(sequences: &[FastqSequence]) -> Vec<u8> {
+    let mut data = Vec::new();
+    let params = IdnCompressorParams::builder().fast(true).build().unwrap();
+    let mut idn_writer = IdnCompressor::with_params(&mut data, params);
+    for sequence in sequences {
+        idn_writer.add_sequence(sequence.clone()).unwrap();
+    }
+    idn_writer.finish().unwrap();
+
+    data
+}
+
+#[test]
+fn test_compress_from_source() {
+    let mut data = Vec::new();
+    let params = IdnCompressorParams::builder().fast(true).build().unwrap();
+    let mut idn_writer = IdnCompressor::with_params(&mut data, params);
+    let sequence_num = idn_writer
+        .compress_from(FastqReader::new(SEQ_1K_READS_FASTQ))
+        .unwrap();
+    idn_writer.finish().unwrap();
+    assert_eq!(sequence_num, SEQ_1K_READS.len());
+
+    // Recompress the file by using the decompressor as a source
+    let mut recompressed = Vec::new();
+    let mut idn_writer = IdnCompressor::new(&mut recompressed);
+    idn_writer
+        .compress_from(IdnDecompressor::new(data.as_slice()))
+        .unwrap();
+    idn_writer.finish().unwrap();
+
+    let idn_reader = IdnDecompressor::new(recompressed.as_slice());
+    let sequences: Vec<FastqSequence> = idn_reader.into_iter().collect::<Result<_, _>>().unwrap();
+    assert_eq!(sequences, SEQ_1K_READS.as_slice());
+}
+
+fn sequences_with_aux_data() -> Vec<FastqSequence> {
+    SEQ_1K_READS
+        .iter()
+        .take(100)
+        .enumerate()
+        .map(|(index, sequence)| match index % 3 {
+            0 => sequence.clone(),
+            _ => sequence.clone().with_aux_data(format!("UMI:{}", index)),
+        })
+        .collect()
+}
+
+#[test]
+fn test_round_trip_aux_data() {
+    let sequences = sequences_with_aux_data();
+    round_trip_sequences(&sequences);
+    round_trip_sequences_custom(
+        &sequences,
+        &sequences,
+        ModelProvider::default(),
+        |builder| builder.fast(true),
+    );
+
+    let sequences_out: Vec<_> = sequences
+        .iter()
+        .map(|sequence| sequence.clone().with_identifier_discarded())
+        .collect();
+    round_trip_sequences_custom(
+        &sequences,
+        &sequences_out,
+        ModelProvider::default(),
+        |builder| builder.include_identifiers(false),
+    );
+
+    let data = compress_fast(&sequences);
+    let info = inspect(data.as_slice()).unwrap();
+    assert!(info.blocks()[0].aux_data_compression().is_some());
+    let data = compress_fast(&SEQ_1K_READS);
+    let info = inspect(data.as_slice()).unwrap();
+    assert_eq!(info.blocks()[0].aux_data_compression(), None);
+}
+
+fn sequences_with_separator_texts() -> Vec<FastqSequence> {
+    SEQ_1K_READS
+        .iter()
+        .take(100)
+        .enumerate()
+        .map(|(index, sequence)| {
+            let separator_text = match index % 4 {
+                0 => Vec::new(),
+                1 => sequence.identifier().str().as_bytes().to_vec(),
+                2 => format!("length={}", sequence.len()).into_bytes(),
+                _ => vec![b'\x1f', 0xff, index as u8],
+            };
+            sequence.clone().with_separator_text(separator_text)
+        })
+        .collect()
+}
+
+#[test]
+fn test_round_trip_separator_texts() {
+    let sequences = sequences_with_separator_texts();
+    round_trip_sequences(&sequences);
+    round_trip_sequences_custom(
+        &sequences,
+        &sequences,
+        ModelProvider::default(),
+        |builder| builder.fast(true),
+    );
+
+    let sequences_out: Vec<_> = sequences
+        .iter()
+        .map(|sequence| sequence.clone().with_identifier_discarded())
+        .collect();
+    assert!(sequences_out
+        .iter()
+        .all(|sequence| sequence.separator_text().is_empty()));
+    round_trip_sequences_custom(
+        &sequences,
+        &sequences_out,
+        ModelProvider::default(),
+        |builder| builder.include_identifiers(false),
+    );
+}
+
+#[test]
+fn test_round_trip_duplicates_aux_data() {
+    let sequences: Vec<_> = sequences_with_duplicates()
+        .into_iter()
+        .enumerate()
+        .map(|(index, sequence)| sequence.with_aux_data(vec![index as u8; index]))
+        .collect();
+    round_trip_sequences_custom(
+        &sequences,
+        &sequences,
+        ModelProvider::default(),
+        |builder| builder.deduplicate(true),
+    );
+}
+
+#[test]
+fn test_round_trip_packed() {
+    let q_score = FastqQualityScore::new(30);
+    let sequences: Vec<_> = [
+        vec![Acid::A, Acid::C, Acid::G, Acid::T, Acid::T],
+        vec![Acid::G; 17],
+        vec![],
+        vec![Acid::T, Acid::C, Acid::A],
+    ]
+    .into_iter()
+    .enumerate()
+    .map(|(index, acids)| {
+        let len = acids.len();
+        FastqSequence::new(format!("seq{}", index), acids, vec![q_score; len])
+    })
+    .collect();
+
+    let data = compress_fast(&sequences);
+    let info = inspect(data.as_slice()).unwrap();
+    assert!(info.blocks()[0].is_packed());
+    round_trip_sequences_custom(
+        &sequences,
+        &sequences,
+        ModelProvider::default(),
+        |builder| builder.fast(true),
+    );
+
+    let data = compress_fast(slice::from_ref(&*SIMPLE_TEST_SEQUENCE));
+    let info = inspect(data.as_slice()).unwrap();
+    assert!(!info.blocks()[0].is_packed());
+}
+
+#[test]
+fn test_round_trip_raw_fallback() {
+    let sequences = [
+        SIMPLE_TEST_SEQUENCE.clone(),
+        FastqSequence::new("empty", [], []),
+        SHORT_TEST_SEQUENCE.clone(),
+        SIMPLE_TEST_SEQUENCE.clone(),
+    ];
+    let reported = Arc::new(Mutex::new(Vec::new()));
+    let recorder = reported.clone();
+    let warnings = Arc::new(WarningRecorder::default());
+    let params = IdnCompressorParams::builder()
+        .raw_fallback_ratio(0.01)
+        .deduplicate(true)
+        .on_sequence(move |_, info| recorder.lock().unwrap().push(info.encoding()))
+        .warning_sink(warnings.clone())
+        .build()
+        .unwrap();
+
+    let mut data = Vec::new();
+    let mut idn_writer = IdnCompressor::with_params(&mut data, params);
+    for sequence in &sequences {
+        idn_writer.add_sequence(sequence.clone()).unwrap();
+    }
+    idn_writer.finish().unwrap();
+
+    let info = inspect(data.as_slice()).unwrap();
+    assert!(info.blocks()[0].is_raw());
+    assert_eq!(info.blocks()[0].model_switch_num(), 0);
+    assert_eq!(*reported.lock().unwrap(), [SequenceEncoding::Raw; 4]);
+    assert!(warnings
+        .warnings
+        .lock()
+        .unwrap()
+        .contains(&Warning::RawFallback { block: 0 }));
+    round_trip_sequences_custom(
+        &sequences,
+        &sequences,
+        ModelProvider::default(),
+        |builder| builder.raw_fallback_ratio(0.01),
+    );
+}
+
+#[test]
+fn test_raw_fallback_not_needed() {
+    let mut data = Vec::new();
+    let params = IdnCompressorParams::builder()
+        .raw_fallback_ratio(1.0)
+        .build()
+        .unwrap();
+    let mut idn_writer = IdnCompressor::with_params(&mut data, params);
+    for sequence in SEQ_1K_READS.iter() {
+        idn_writer.add_sequence(sequence.clone()).unwrap();
+    }
+    idn_writer.finish().unwrap();
+
+    let info = inspect(data.as_slice()).unwrap();
+    assert!(info.blocks().iter().all(|block| !block.is_raw()));
+}
+
+fn sequences_with_n(other_n_q_score: FastqQualityScore) -> Vec<FastqSequence> {
+    let n_q_score = FastqQualityScore::new(2);
+    let q_score = FastqQualityScore::new(30);
+    vec![
+        FastqSequence::new(
+            "seq0",
+            [Acid::N, Acid::A, Acid::C, Acid::N],
+            [n_q_score, q_score, q_score, n_q_score],
+        ),
+        FastqSequence::new("seq1", [Acid::G; 5], [q_score; 5]),
+        FastqSequence::new(
+            "seq2",
+            [Acid::T, Acid::N, Acid::N],
+            [q_score, n_q_score, other_n_q_score],
+        ),
+    ]
+}
+
+#[test]
+fn test_round_trip_skip_n_q_scores() {
+    let n_q_score = FastqQualityScore::new(2);
+    for (other_n_q_score, expected) in [
+        (n_q_score, Some(n_q_score)),
+        (FastqQualityScore::new(0), None),
+    ] {
+        let sequences = sequences_with_n(other_n_q_score);
+
+        let mut data = Vec::new();
+        let params = IdnCompressorParams::builder()
+            .skip_n_q_scores(true)
+            .build()
+            .unwrap();
+        let mut idn_writer = IdnCompressor::with_params(&mut data, params);
+        for sequence in &sequences {
+            idn_writer.add_sequence(sequence.clone()).unwrap();
+        }
+        idn_writer.finish().unwrap();
+        let info = inspect(data.as_slice()).unwrap();
+        assert_eq!(info.blocks()[0].n_q_score(), expected);
+
+        round_trip_sequences_custom(
+            &sequences,
+            &sequences,
+            ModelProvider::default(),
+            |builder| builder.skip_n_q_scores(true),
+        );
+        round_trip_sequences_custom(
+            &sequences,
+            &sequences,
+            ModelProvider::default(),
+            |builder| builder.skip_n_q_scores(true).fast(true),
+        );
+    }
+}
+
+#[test]
+fn test_round_trip_reused_block_buffers() {
+    // Every sequence is in a separate block, so the blocks with the N quality
+    // scores skipped are decompressed with the buffers used by the blocks
+    // without them, and the other way round
+    let sequences: Vec<FastqSequence> = (0..4)
+        .flat_map(|_| sequences_with_n(FastqQualityScore::new(0)))
+        .collect();
+    let params = IdnCompressorParams::builder()
+        .skip_n_q_scores(true)
+        .max_block_reads(1)
+        .build()
+        .unwrap();
+    let mut data = Vec::new();
+    let mut idn_writer = IdnCompressor::with_params(&mut data, params);
+    for sequence in &sequences {
+        idn_writer.add_sequence(sequence.clone()).unwrap();
+    }
+    idn_writer.finish().unwrap();
+    let info = inspect(data.as_slice()).unwrap();
+    assert_eq!(info.blocks().len(), sequences.len());
+
+    for thread_num in [0, 4] {
+        let reader_params = IdnDecompressorParams::builder()
+            .thread_num(thread_num)
+            .build();
+        let idn_reader = IdnDecompressor::with_params(data.as_slice(), reader_params);
+        let decompressed: Vec<FastqSequence> =
+            idn_reader.into_iter().collect::<Result<_, _>>().unwrap();
+
+        assert_eq!(decompressed, sequences);
+    }
+}
+
+#[test]
+fn test_compressor_add_sequences() {
+    let expected_blocks = inspect(compress_in_small_blocks(SEQ_1K_READS.as_slice()).as_slice())
+        .unwrap()
+        .blocks()
+        .len();
+
+    for thread_num in [0, 4] {
+        let params = IdnCompressorParams::builder()
+            .fast(true)
+            .max_block_total_len(760)
+            .thread_num(thread_num)
+            .build()
+            .unwrap();
+        let mut data = Vec::new();
+        let mut idn_writer = IdnCompressor::with_params(&mut data, params);
+        // Each batch spans multiple blocks, so some of them are handed over
+        // before the batch is over
+        for batch in SEQ_1K_READS.chunks(100) {
+            idn_writer.add_sequences(batch.to_vec()).unwrap();
+        }
+        idn_writer.finish().unwrap();
+
+        let info = inspect(data.as_slice()).unwrap();
+        assert_eq!(info.blocks().len(), expected_blocks);
+        let idn_reader = IdnDecompressor::new(data.as_slice());
+        let sequences: Vec<FastqSequence> =
+            idn_reader.into_iter().collect::<Result<_, _>>().unwrap();
+        assert_eq!(sequences, SEQ_1K_READS.as_slice());
+    }
+}
+
+#[test]
+fn test_compressor_add_sequences_too_long() {
+    let params = IdnCompressorParams::builder()
+        .max_block_total_len(760)
+        .build()
+        .unwrap();
+    let mut data = Vec::new();
+    let mut idn_writer = IdnCompressor::with_params(&mut data, params);
+    let too_long = FastqSequence::new("", [Acid::A; 381], [FastqQualityScore::new(0); 381]);
+
+    let error = idn_writer
+        .add_sequences([SEQ_1K_READS[0].clone(), too_long])
+        .unwrap_err();
+    assert!(matches!(
+        error,
+        IdnCompressorError::SequenceTooLong(381, 380)
+    ));
+}
+
+#[test]
+fn test_container_members() {
+    let (first, second) = SEQ_1K_READS.split_at(300);
+    let empty: &[FastqSequence] = &[];
+    let data = compress_members(&[("first", first), ("empty", empty), ("second", second)]);
+
+    let members = IdnDecompressor::members(data.as_slice()).unwrap();
+    let members: Vec<_> = members
+        .iter()
+        .map(|member| (member.name(), member.sequence_num()))
+        .collect();
+    assert_eq!(members, [("first", 300), ("empty", 0), ("second", 700)]);
+
+    let idn_reader = IdnDecompressor::new(data.as_slice());
+    let sequences: Vec<FastqSequence> = idn_reader.into_iter().collect::<Result<_, _>>().unwrap();
+    assert_eq!(sequences, SEQ_1K_READS.as_slice());
+}
+
+#[test]
+fn test_container_member_index() {
+    let (first, second) = SEQ_1K_READS.split_at(300);
+    let empty: &[FastqSequence] = &[];
+    let data = compress_members(&[("first", first), ("empty", empty), ("second", second)]);
+
+    let info = inspect(data.as_slice()).unwrap();
+    let members = info.members();
+    let names: Vec<_> = members.iter().map(|member| member.name()).collect();
+    assert_eq!(names, ["first", "empty", "second"]);
+    let block_num: usize = members.iter().map(|member| member.block_num()).sum();
+    assert_eq!(block_num, info.blocks().len());
+
+    for member in &members {
+        let offset = member.offset().unwrap() as usize;
+        // Block header (8 bytes), then the member slice: magic (1 byte), name
+        // length (2 bytes) and the name
+        let name_start = offset + 11;
+        assert_eq!(data[offset + 8], 5);
+        assert_eq!(
+            &data[name_start..name_start + member.name().len()],
+            member.name().as_bytes()
+        );
+    }
+}
+
+#[test]
+fn test_container_member_index_with_source_digest() {
+    let hasher = SourceHasher::new();
+    let params = IdnCompressorParams::builder()
+        .fast(true)
+        .source_hasher(hasher.clone())
+        .build()
+        .unwrap();
+    let mut data = Cursor::new(Vec::new());
+    let mut idn_writer = IdnCompressor::with_params_seekable(&mut data, params).unwrap();
+    idn_writer.start_member("first").unwrap();
+    for sequence in SEQ_1K_READS.iter() {
+        idn_writer.add_sequence(sequence.clone()).unwrap();
+    }
+    idn_writer.finish().unwrap();
+
+    let info = inspect(data.get_ref().as_slice()).unwrap();
+    assert_eq!(info.source_digest(), Some(hasher.digest()));
+    let members = info.members();
+    assert_eq!(members.len(), 1);
+    assert_eq!(members[0].sequence_num(), 1000);
+    assert!(members[0].offset().is_some());
+}
+
+#[test]
+fn test_container_extract() {
+    let (first, second) = SEQ_1K_READS.split_at(300);
+    let empty: &[FastqSequence] = &[];
+    let data = compress_members(&[("first", first), ("empty", empty), ("second", second)]);
+
+    for thread_num in [0, 4] {
+        for read_ahead in [0, 1] {
+            for (name, expected) in [("first", first), ("empty", empty), ("second", second)] {
+                let reader_params = IdnDecompressorParams::builder()
+                    .thread_num(thread_num)
+                    .read_ahead(read_ahead)
+                    .member(name)
+                    .build();
+                let idn_reader = IdnDecompressor::with_params(data.as_slice(), reader_params);
+                let sequences: Vec<FastqSequence> =
+                    idn_reader.into_iter().collect::<Result<_, _>>().unwrap();
+
+                assert_eq!(sequences, expected);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_container_unknown_member() {
+    let data = compress_members(&[("first", SEQ_1K_READS.as_slice())]);
+
+    let mut idn_reader = IdnDecompressor::extract(data.as_slice(), "second");
+    let error = idn_reader.next_sequence().unwrap_err();
+    assert!(matches!(error, IdnDecompressorError::UnknownMember(name) if name == "second"));
+}
+
+#[test]
+fn test_container_duplicate_member() {
+    let mut data = Vec::new();
+    let mut idn_writer = IdnCompressor::new(&mut data);
+    idn_writer.start_member("first").unwrap();
+    let error = idn_writer.start_member("first").unwrap_err();
+    assert!(matches!(error, IdnCompressorError::DuplicateMember(name) if name == "first"));
+}
+
+#[derive(Debug)]
+struct EveryHundredthFilter;
+
+impl SequenceFilter for EveryHundredthFilter {
+    fn accepts(&self, sequence: &FastqSequence) -> bool {
+        sequence.identifier().str().ends_with("00/1")
+    }
+}
+
+#[test]
+fn test_decompress_with_sequence_filter() {
+    let data = compress_in_small_blocks(SEQ_1K_READS.as_slice());
+
+    let reader_params = IdnDecompressorParams::builder()
+        .sequence_filter(Arc::new(EveryHundredthFilter))
+        .build();
+    let mut idn_reader = IdnDecompressor::with_params(data.as_slice(), reader_params);
+    for sequence in SEQ_1K_READS.iter().skip(99).step_by(100) {
+        assert_eq!(idn_reader.next_sequence().unwrap().as_ref(), Some(sequence));
+    }
+    assert_eq!(idn_reader.next_sequence().unwrap(), None);
+}
+
+#[derive(Debug)]
+struct DuplicatesFilter;
+
+impl SequenceFilter for DuplicatesFilter {
+    fn accepts(&self, sequence: &FastqSequence) -> bool {
+        sequence.identifier().str().starts_with("SEQ_DUP")
+    }
+}
+
+#[test]
+fn test_decompress_duplicates_with_sequence_filter() {
+    let params = IdnCompressorParams::builder()
+        .deduplicate(true)
+        .build()
+        .unwrap();
+    let mut data = Vec::new();
+    let mut idn_writer = IdnCompressor::with_params(&mut data, params);
+    for sequence in sequences_with_duplicates() {
+        idn_writer.add_sequence(sequence).unwrap();
+    }
+    idn_writer.finish().unwrap();
+
+    // The duplicates can be restored even if the sequences they refer to are
+    // filtered out
+    let reader_params = IdnDecompressorParams::builder()
+        .sequence_filter(Arc::new(DuplicatesFilter))
+        .build();
+    let idn_reader = IdnDecompressor::with_params(data.as_slice(), reader_params);
+    let sequences: Vec<_> = idn_reader.into_iter().map(Result::unwrap).collect();
+    assert_eq!(sequences, sequences_with_duplicates()[2..4].to_vec());
+}
+
+#[test]
+fn test_indexed_sequences() {
+    let data = compress_in_small_blocks(SEQ_1K_READS.as_slice());
+    let file_info = inspect(data.as_slice()).unwrap();
+    let expected_positions: Vec<_> = file_info
+        .blocks()
+        .iter()
+        .enumerate()
+        .flat_map(|(block_index, block)| {
+            (0..block.sequence_num()).map(move |read_index| (block_index, read_index))
+        })
+        .collect();
+    assert!(file_info.blocks().len() > 1);
+
+    let indexed: Vec<_> = IdnDecompressor::new(data.as_slice())
+        .indexed_sequences()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    let positions: Vec<_> = indexed
+        .iter()
+        .map(|(block_index, read_index, _)| (*block_index, *read_index))
+        .collect();
+    let sequences: Vec<_> = indexed
+        .into_iter()
+        .map(|(_, _, sequence)| sequence)
+        .collect();
+    assert_eq!(positions, expected_positions);
+    assert_eq!(sequences, SEQ_1K_READS.as_slice());
+}
+
+#[test]
+fn test_indexed_sequences_member_with_sequence_filter() {
+    let (first, second) = SEQ_1K_READS.split_at(300);
+    let data = compress_members(&[("first", first), ("second", second)]);
+    let all: Vec<_> = IdnDecompressor::new(data.as_slice())
+        .indexed_sequences()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    let expected: Vec<_> = all[first.len()..]
+        .iter()
+        .filter(|(_, _, sequence)| EveryHundredthFilter.accepts(sequence))
+        .cloned()
+        .collect();
+
+    for thread_num in [0, 4] {
+        let reader_params = IdnDecompressorParams::builder()
+            .thread_num(thread_num)
+            .member("second")
+            .sequence_filter(Arc::new(EveryHundredthFilter))
+            .build();
+        let indexed: Vec<_> = IdnDecompressor::with_params(data.as_slice(), reader_params)
+            .indexed_sequences()
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        assert!(!indexed.is_empty());
+        assert_eq!(indexed, expected);
+    }
+}
+
+/// A writer that is slow to write the data and can be made to fail after given
+/// number of writes, to simulate slow or unreliable output devices.
+#[derive(Debug)]
+struct SlowWriter {
+    data: Vec<u8>,
+    writes_left: Option<usize>,
+}
+
+impl std::io::Write for SlowWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        std::thread::sleep(Duration::from_micros(100));
+        if let Some(writes_left) = &mut self.writes_left {
+            if *writes_left == 0 {
+                return Err(std::io::Error::new(std::io::ErrorKind::Other, "disk full"));
+            }
+            *writes_left -= 1;
+        }
+
+        self.data.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_compressor_slow_writer() {
+    for thread_num in [0, 1, 4] {
+        let mut writer = SlowWriter {
+            data: Vec::new(),
+            writes_left: None,
+        };
+        let params = IdnCompressorParams::builder()
+            .fast(true)
+            .max_block_total_len(760)
+            .thread_num(thread_num)
+            .build()
+            .unwrap();
+        let mut idn_writer = IdnCompressor::with_params(&mut writer, params);
+        for sequence in SEQ_1K_READS.iter() {
+            idn_writer.add_sequence(sequence.clone()).unwrap();
+        }
+        idn_writer.finish().unwrap();
+
+        let idn_reader = IdnDecompressor::new(writer.data.as_slice());
+        let sequences: Vec<FastqSequence> =
+            idn_reader.into_iter().collect::<Result<_, _>>().unwrap();
+        assert_eq!(sequences, SEQ_1K_READS.as_slice());
+    }
+}
+
+#[test]
+fn test_compressor_failing_writer() {
+    for thread_num in [0, 4] {
+        let mut writer = SlowWriter {
+            data: Vec::new(),
+            writes_left: Some(20),
+        };
+        let params = IdnCompressorParams::builder()
+            .fast(true)
+            .max_block_total_len(760)
+            .thread_num(thread_num)
+            .build()
+            .unwrap();
+        let mut idn_writer = IdnCompressor::with_params(&mut writer, params);
+        let result = SEQ_1K_READS
+            .iter()
+            .try_for_each(|sequence| idn_writer.add_sequence(sequence.clone()))
+            .and_then(|_| idn_writer.finish());
+
+        // The block headers are serialized with binrw, which wraps the I/O
+        // errors
+        assert!(matches!(
+            result,
+            Err(IdnCompressorError::IoError(_) | IdnCompressorError::SerializeError(_))
+        ));
+    }
+}
+
+#[test]
+fn test_decompressor_drop_before_eof() {
+    let data = compress_in_small_blocks(SEQ_1K_READS.as_slice());
+
+    for thread_num in [0, 4] {
+        let reader_params = IdnDecompressorParams::builder()
+            .thread_num(thread_num)
+            .build();
+        let mut idn_reader = IdnDecompressor::with_params(data.as_slice(), reader_params);
+        assert_eq!(
+            idn_reader.next_sequence().unwrap().as_ref(),
+            Some(&SEQ_1K_READS[0])
+        );
+        drop(idn_reader);
+    }
+}
+
+#[test]
+fn test_decompressor_close() {
+    let data = compress_in_small_blocks(SEQ_1K_READS.as_slice());
+
+    for thread_num in [0, 4] {
+        let reader_params = IdnDecompressorParams::builder()
+            .thread_num(thread_num)
+            .build();
+        let mut idn_reader = IdnDecompressor::with_params(data.as_slice(), reader_params);
+        for sequence in SEQ_1K_READS.iter().take(150) {
+            assert_eq!(idn_reader.next_sequence().unwrap().as_ref(), Some(sequence));
+        }
+        idn_reader.close().unwrap();
+    }
+}
+
+#[test]
+fn test_decompressor_read_ahead() {
+    let data = compress_in_small_blocks(SEQ_1K_READS.as_slice());
+
+    for thread_num in [0, 1, 4] {
+        for read_ahead in [1, 16] {
+            let reader_params = IdnDecompressorParams::builder()
+                .thread_num(thread_num)
+                .read_ahead(read_ahead)
+                .build();
+            let idn_reader = IdnDecompressor::with_params(data.as_slice(), reader_params);
+            let sequences: Vec<FastqSequence> =
+                idn_reader.into_iter().collect::<Result<_, _>>().unwrap();
+
+            assert_eq!(sequences, SEQ_1K_READS.as_slice());
+        }
+    }
+}
+
+#[test]
+fn test_decompressor_read_ahead_close() {
+    let data = compress_in_small_blocks(SEQ_1K_READS.as_slice());
+
+    for thread_num in [0, 4] {
+        let reader_params = IdnDecompressorParams::builder()
+            .thread_num(thread_num)
+            .read_ahead(1)
+            .build();
+        let mut idn_reader = IdnDecompressor::with_params(data.as_slice(), reader_params);
+        for sequence in SEQ_1K_READS.iter().take(150) {
+            assert_eq!(idn_reader.next_sequence().unwrap().as_ref(), Some(sequence));
+        }
+        idn_reader.close().unwrap();
+    }
+}
+
+#[test]
+fn test_decompressor_batches() {
+    let data = compress_in_small_blocks(SEQ_1K_READS.as_slice());
+
+    for thread_num in [0, 4] {
+        let reader_params = IdnDecompressorParams::builder()
+            .thread_num(thread_num)
+            .build();
+        let idn_reader = IdnDecompressor::with_params(data.as_slice(), reader_params);
+        let batches: Vec<Vec<FastqSequence>> =
+            idn_reader.into_batches().collect::<Result<_, _>>().unwrap();
+
+        assert!(batches.iter().all(|batch| !batch.is_empty()));
+        assert_eq!(batches.concat(), SEQ_1K_READS.as_slice());
+    }
+}
+
+#[test]
+fn test_decompressor_next_batch_after_next_sequence() {
+    let data = compress_in_small_blocks(SEQ_1K_READS.as_slice());
+
+    let mut idn_reader = IdnDecompressor::new(data.as_slice());
+    assert_eq!(
+        idn_reader.next_sequence().unwrap().as_ref(),
+        Some(&SEQ_1K_READS[0])
+    );
+    let mut sequences = Vec::new();
+    while let Some(batch) = idn_reader.next_batch().unwrap() {
+        sequences.extend(batch);
+    }
+
+    assert_eq!(sequences, &SEQ_1K_READS[1..]);
+    assert_eq!(idn_reader.next_sequence().unwrap(), None);
+}
+
+#[derive(Debug, Default)]
+struct StageRecorder {
+    stages: Mutex<Vec<ProgressStage>>,
+}
+
+impl ProgressNotifier for StageRecorder {
+    fn processed_bytes(&self, _bytes: ByteNum) {}
+
+    fn set_iter_num(&self, _num_iter: u64) {}
+
+    fn inc_iter(&self) {}
+
+    fn start_stage(&self, stage: ProgressStage, _total: Option<u64>) {
+        self.stages.lock().unwrap().push(stage);
+    }
+}
+
+/// Counts the blocks decompressed, i.e. the units of the decompressing stage;
+/// the units of the other stages (such as preprocessing the models) are
+/// ignored.
+#[derive(Debug, Default)]
+struct BlockCounter {
+    stage: Mutex<Option<ProgressStage>>,
+    blocks: Mutex<u64>,
+}
+
+impl ProgressNotifier for BlockCounter {
+    fn processed_bytes(&self, _bytes: ByteNum) {}
+
+    fn set_iter_num(&self, _num_iter: u64) {}
+
+    fn inc_iter(&self) {}
+
+    fn start_stage(&self, stage: ProgressStage, _total: Option<u64>) {
+        *self.stage.lock().unwrap() = Some(stage);
+    }
+
+    fn advance_stage(&self, units: u64) {
+        if *self.stage.lock().unwrap() == Some(ProgressStage::Decompressing) {
+            *self.blocks.lock().unwrap() += units;
+        }
+    }
+}
+
+#[test]
+fn test_decompressor_max_buffered_blocks() {
+    let data = compress_in_small_blocks(SEQ_1K_READS.as_slice());
+
+    for thread_num in [0, 4] {
+        let counter = Arc::new(BlockCounter::default());
+        let reader_params = IdnDecompressorParams::builder()
+            .thread_num(thread_num)
+            .max_buffered_blocks(2)
+            .progress_notifier(counter.clone())
+            .build();
+        let mut idn_reader = IdnDecompressor::with_params(data.as_slice(), reader_params);
+        assert_eq!(
+            idn_reader.next_sequence().unwrap().as_ref(),
+            Some(&SEQ_1K_READS[0])
+        );
+
+        // Only two blocks can be decompressed ahead of the one being read
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(*counter.blocks.lock().unwrap() <= 3);
+
+        let sequences: Vec<FastqSequence> =
+            idn_reader.into_iter().collect::<Result<_, _>>().unwrap();
+        assert_eq!(sequences, &SEQ_1K_READS[1..]);
+    }
+}
+
+#[derive(Debug, Default)]
+struct ByteCounter {
+    bytes: Mutex<usize>,
+}
+
+impl ProgressNotifier for ByteCounter {
+    fn processed_bytes(&self, bytes: ByteNum) {
+        *self.bytes.lock().unwrap() += bytes.get();
+    }
+
+    fn set_iter_num(&self, _num_iter: u64) {}
+
+    fn inc_iter(&self) {}
+}
+
+#[test]
+fn test_progress_granularity() {
+    let mut compressed_totals = Vec::new();
+    let mut decompressed_totals = Vec::new();
+
+    for granularity in [0, 100, usize::MAX] {
+        let counter = Arc::new(ByteCounter::default());
+        let mut data = Vec::new();
+        let params = IdnCompressorParams::builder()
+            .fast(true)
+            .max_block_total_len(760)
+            .progress_notifier(counter.clone())
+            .progress_granularity(granularity)
+            .build()
+            .unwrap();
+        let mut idn_writer = IdnCompressor::with_params(&mut data, params);
+        for sequence in SEQ_1K_READS.iter() {
+            idn_writer.add_sequence(sequence.clone()).unwrap();
+        }
+        idn_writer.finish().unwrap();
+        compressed_totals.push(*counter.bytes.lock().unwrap());
+
+        let counter = Arc::new(ByteCounter::default());
+        let reader_params = IdnDecompressorParams::builder()
+            .progress_notifier(counter.clone())
+            .progress_granularity(granularity)
+            .build();
+        let idn_reader = IdnDecompressor::with_params(data.as_slice(), reader_params);
+        let sequences: Vec<FastqSequence> =
+            idn_reader.into_iter().collect::<Result<_, _>>().unwrap();
+        assert_eq!(sequences, SEQ_1K_READS.as_slice());
+        decompressed_totals.push(*counter.bytes.lock().unwrap());
+    }
+
+    // Batching only delays the reports; no bytes can be lost on the way
+    assert!(compressed_totals[0] > 0);
+    assert!(compressed_totals.iter().all(|&x| x == compressed_totals[0]));
+    assert!(decompressed_totals[0] > 0);
+    assert!(decompressed_totals
+        .iter()
+        .all(|&x| x == decompressed_totals[0]));
+}
+
+#[test]
+fn test_decompressor_untrusted_limits() {
+    let data = compress_in_small_blocks(SEQ_1K_READS.as_slice());
+
+    for read_ahead in [0, 1] {
+        let reader_params = IdnDecompressorParams::builder()
+            .read_ahead(read_ahead)
+            .limits(IdnDecompressorLimits::untrusted())
+            .build();
+        let idn_reader = IdnDecompressor::with_params(data.as_slice(), reader_params);
+        let sequences: Vec<FastqSequence> =
+            idn_reader.into_iter().collect::<Result<_, _>>().unwrap();
+
+        assert_eq!(sequences, SEQ_1K_READS.as_slice());
+    }
+}
+
+#[test]
+fn test_decompressor_block_too_large() {
+    let data = compress_in_small_blocks(SEQ_1K_READS.as_slice());
+
+    for read_ahead in [0, 1] {
+        let reader_params = IdnDecompressorParams::builder()
+            .read_ahead(read_ahead)
+            .limits(IdnDecompressorLimits::new().max_block_len(16))
+            .build();
+        let mut idn_reader = IdnDecompressor::with_params(data.as_slice(), reader_params);
+        let error = idn_reader.next_sequence().unwrap_err();
+        assert!(matches!(error, IdnDecompressorError::BlockTooLarge(_, 16)));
+    }
+}
+
+#[test]
+fn test_decompressor_identifiers_too_large() {
+    let data = compress_in_small_blocks(SEQ_1K_READS.as_slice());
+
+    let reader_params = IdnDecompressorParams::builder()
+        .limits(IdnDecompressorLimits::new().max_identifiers_len(8))
+        .build();
+    let mut idn_reader = IdnDecompressor::with_params(data.as_slice(), reader_params);
+    let error = idn_reader.next_sequence().unwrap_err();
+    assert!(matches!(
+        error,
+        IdnDecompressorError::IdentifiersTooLarge(8)
+    ));
+}
+
+#[test]
+fn test_decompressor_decompressed_identifiers_too_large() {
+    // The identifiers compress very well, so only their decompressed length
+    // exceeds the limit, which is detected while they are being streamed
+    let sequences: Vec<_> = SEQ_1K_READS[..100]
+        .iter()
+        .enumerate()
+        .map(|(index, sequence)| {
+            let identifier = format!("{}{}", "READ".repeat(50), index);
+            sequence.clone().with_identifier(identifier)
+        })
+        .collect();
+    let mut data = Vec::new();
+    let mut idn_writer = IdnCompressor::new(&mut data);
+    for sequence in &sequences {
+        idn_writer.add_sequence(sequence.clone()).unwrap();
+    }
+    idn_writer.finish().unwrap();
+
+    let reader_params = IdnDecompressorParams::builder()
+        .limits(IdnDecompressorLimits::new().max_identifiers_len(2000))
+        .build();
+    let mut idn_reader = IdnDecompressor::with_params(data.as_slice(), reader_params);
+    let error = idn_reader.next_sequence().unwrap_err();
+    assert!(matches!(
+        error,
+        IdnDecompressorError::IdentifiersTooLarge(2000)
+    ));
+
+    let idn_reader = IdnDecompressor::new(data.as_slice());
+    let decompressed: Vec<FastqSequence> =
+        idn_reader.into_iter().collect::<Result<_, _>>().unwrap();
+    assert_eq!(decompressed, sequences);
+}
+
+#[test]
+fn test_decompressor_sequence_too_long() {
+    let data = compress_in_small_blocks(SEQ_1K_READS.as_slice());
+
+    let reader_params = IdnDecompressorParams::builder()
+        .limits(IdnDecompressorLimits::new().max_sequence_len(1))
+        .build();
+    let mut idn_reader = IdnDecompressor::with_params(data.as_slice(), reader_params);
+    let error = idn_reader.next_sequence().unwrap_err();
+    assert!(matches!(error, IdnDecompressorError::SequenceTooLong(_, 1)));
+}
+
+#[test]
+fn test_decompressor_model_identifier_mismatch() {
+    let params = IdnCompressorParams::builder()
+        .model_provider(ModelProvider::with_empty_models())
+        .fast(true)
+        .build()
+        .unwrap();
+    let mut data = Vec::new();
+    let mut idn_writer = IdnCompressor::with_params(&mut data, params);
+    idn_writer
+        .add_sequence(SIMPLE_TEST_SEQUENCE.clone())
+        .unwrap();
+    idn_writer.finish().unwrap();
+
+    let empty_acid_model = Model::empty(ModelType::Acids);
+    let edited_acid_model = SIMPLE_ACID_MODEL
+        .clone()
+        .with_stored_identifier(empty_acid_model.identifier().clone());
+    let model_provider = ModelProvider::new(vec![
+        edited_acid_model,
+        Model::empty(ModelType::QualityScores),
+    ]);
+    let reader_params = IdnDecompressorParams::builder()
+        .model_provider(model_provider)
+        .build();
+    let mut idn_reader = IdnDecompressor::with_params(data.as_slice(), reader_params);
+    let error = idn_reader.next_sequence().unwrap_err();
+    assert!(matches!(
+        error,
+        IdnDecompressorError::ModelIdentifierMismatch(e) if e.stored() == empty_acid_model.identifier()
+    ));
+}
+
+#[test]
+fn test_decompressor_too_many_metadata_items() {
+    let data = compress_in_small_blocks(SEQ_1K_READS.as_slice());
+
+    let reader_params = IdnDecompressorParams::builder()
+        .limits(IdnDecompressorLimits::new().max_metadata_items(0))
+        .build();
+    let mut idn_reader = IdnDecompressor::with_params(data.as_slice(), reader_params);
+    let error = idn_reader.next_sequence().unwrap_err();
+    assert!(matches!(
+        error,
+        IdnDecompressorError::TooManyMetadataItems(_, 0)
+    ));
+}
+
+#[test]
+fn test_decompressor_slice_out_of_bounds() {
+    let mut data = b"IDENCOMP\x01\x00".to_vec();
+    // Block header (length and sequence checksum), followed by an uncompressed
+    // identifiers slice claiming 1000 bytes of data, which are not there
+    data.extend([0, 0, 0, 6, 0, 0, 0, 0]);
+    data.extend([0, 0, 0, 0x03, 0xe8, 2]);
+
+    let mut idn_reader = IdnDecompressor::new(data.as_slice());
+    let error = idn_reader.next_sequence().unwrap_err();
+    assert!(matches!(
+        error,
+        IdnDecompressorError::SliceOutOfBounds(1000, 0)
+    ));
+}
+
+#[test]
+fn test_decompressor_sequence_data_too_short() {
+    let mut data = b"IDENCOMP\x01\x00".to_vec();
+    // Block header, followed by a sequence slice of a single acid whose data
+    // does not even contain the final states of the rANS encoders
+    data.extend([0, 0, 0, 12, 0, 0, 0, 0]);
+    data.extend([2, 0, 0, 0, 3, 0, 0, 0, 1, 0, 0, 0]);
+
+    let mut idn_reader = IdnDecompressor::new(data.as_slice());
+    let error = idn_reader.next_sequence().unwrap_err();
+    assert!(matches!(
+        error,
+        IdnDecompressorError::SequenceDataTooShort(3, 8)
+    ));
+}
+
+#[test]
+fn test_decompressor_unused_model_switch() {
+    let mut data = b"IDENCOMP\x01\x00".to_vec();
+    // Block header, followed by a model switch within a sequence, and then
+    // by a duplicate sequence, which the switch cannot apply to
+    data.extend([0, 0, 0, 19, 0, 0, 0, 0]);
+    data.extend([6, 0, 0, 0, 1, 0]);
+    data.extend([3, 0, 0, 0, 1, 0, 0, 0, 4, 0, 0, 0, 0]);
+
+    let mut idn_reader = IdnDecompressor::new(data.as_slice());
+    let error = idn_reader.next_sequence().unwrap_err();
+    assert!(matches!(error, IdnDecompressorError::UnusedModelSwitch));
+}
+
+/// A lossless transform that reverses the order of the acids and the quality
+/// scores.
+#[derive(Debug)]
+struct ReverseTransform;
+
+impl SequenceTransform for ReverseTransform {
+    fn identifier(&self) -> String {
+        "reverse".to_owned()
+    }
+
+    fn is_lossy(&self) -> bool {
+        false
+    }
+
+    fn apply(&self, sequence: FastqSequence) -> FastqSequence {
+        let identifier = sequence.identifier().clone();
+        let (mut acids, mut q_scores) = sequence.into_data();
+        acids.reverse();
+        q_scores.reverse();
+        FastqSequence::new(identifier, acids, q_scores)
+    }
+
+    fn inverse(&self, sequence: FastqSequence) -> FastqSequence {
+        self.apply(sequence)
+    }
+}
+
+#[test]
+fn test_round_trip_transforms() {
+    let mut data = Vec::new();
+    let params = IdnCompressorParams::builder()
+        .transform(Arc::new(ReverseTransform))
+        .transform(Arc::new(QualityBinning))
+        .build()
+        .unwrap();
+    let mut idn_writer = IdnCompressor::with_params(&mut data, params);
+    idn_writer
+        .add_sequence(SIMPLE_TEST_SEQUENCE.clone())
+        .unwrap();
+    idn_writer.finish().unwrap();
+
+    let reader_params = IdnDecompressorParams::builder()
+        .transform(Arc::new(ReverseTransform))
+        .build();
+    let mut idn_reader = IdnDecompressor::with_params(data.as_slice(), reader_params);
+    let expected = QualityBinning.apply(SIMPLE_TEST_SEQUENCE.clone());
+    assert_eq!(idn_reader.next_sequence().unwrap(), Some(expected));
+    assert_eq!(idn_reader.next_sequence().unwrap(), None);
+}
+
+#[test]
+fn test_round_trip_quality_delta() {
+    let mut data = Vec::new();
+    let params = IdnCompressorParams::builder()
+        .transform(Arc::new(QualityDelta))
+        .build()
+        .unwrap();
+    let mut idn_writer = IdnCompressor::with_params(&mut data, params);
+    for sequence in SEQ_1K_READS.iter() {
+        idn_writer.add_sequence(sequence.clone()).unwrap();
+    }
+    idn_writer.finish().unwrap();
+
+    let reader_params = IdnDecompressorParams::builder()
+        .transform(Arc::new(QualityDelta))
+        .build();
+    let idn_reader = IdnDecompressor::with_params(data.as_slice(), reader_params);
+    let decompressed: Vec<FastqSequence> =
+        idn_reader.into_iter().collect::<Result<_, _>>().unwrap();
+    assert_eq!(decompressed, SEQ_1K_READS.as_slice());
+}
+
+#[test]
+fn test_decompressor_builtin_quality_delta() {
+    let mut data = Vec::new();
+    let params = IdnCompressorParams::builder()
+        .transform(Arc::new(QualityDelta))
+        .build()
+        .unwrap();
+    let mut idn_writer = IdnCompressor::with_params(&mut data, params);
+    idn_writer
+        .add_sequence(SIMPLE_TEST_SEQUENCE.clone())
+        .unwrap();
+    idn_writer.finish().unwrap();
+
+    let mut idn_reader = IdnDecompressor::new(data.as_slice());
+    assert_eq!(
+        idn_reader.next_sequence().unwrap(),
+        Some(SIMPLE_TEST_SEQUENCE.clone())
+    );
+    assert_eq!(idn_reader.next_sequence().unwrap(), None);
+}
+
+#[test]
+fn test_decompressor_unknown_transform() {
+    let mut data = Vec::new();
+    let params = IdnCompressorParams::builder()
+        .transform(Arc::new(ReverseTransform))
+        .build()
+        .unwrap();
+    let mut idn_writer = IdnCompressor::with_params(&mut data, params);
+    idn_writer
+        .add_sequence(SIMPLE_TEST_SEQUENCE.clone())
+        .unwrap();
+    idn_writer.finish().unwrap();
+
+    let mut idn_reader = IdnDecompressor::new(data.as_slice());
+    let error = idn_reader.next_sequence().unwrap_err();
+    assert!(matches!(
+        error,
+        IdnDecompressorError::UnknownTransform(identifier) if identifier == "reverse"
+    ));
+}
+
+#[test]
+fn test_round_trip_quality_alphabet() {
+    let sequences: Vec<_> = SEQ_1K_READS
+        .iter()
+        .cloned()
+        .map(|sequence| QualityBinning.apply(sequence))
+        .collect();
+    let model_provider = ModelProvider::new(vec![
+        SIMPLE_ACID_MODEL.clone(),
+        SIMPLE_Q_SCORE_MODEL.clone(),
+    ]);
+
+    round_trip_sequences_custom(&sequences, &sequences, model_provider, |builder| {
+        builder.quality_alphabet(QualityBinning::alphabet())
+    });
+}
+
+#[test]
+fn test_quality_alphabet_stored_in_file() {
+    let mut data = Vec::new();
+    let params = IdnCompressorParams::builder()
+        .quality_alphabet(QualityBinning::alphabet())
+        .build()
+        .unwrap();
+    let mut idn_writer = IdnCompressor::with_params(&mut data, params);
+    idn_writer
+        .add_sequence(QualityBinning.apply(SIMPLE_TEST_SEQUENCE.clone()))
+        .unwrap();
+    idn_writer.finish().unwrap();
+
+    let info = inspect(data.as_slice()).unwrap();
+    assert_eq!(*info.quality_alphabet(), QualityBinning::alphabet());
+}
+
+#[test]
+fn test_quality_score_not_in_alphabet() {
+    let alphabet = QualityAlphabet::new([2, 12].map(FastqQualityScore::new));
+    let params = IdnCompressorParams::builder()
+        .quality_alphabet(alphabet)
+        .build()
+        .unwrap();
+    let mut idn_writer = IdnCompressor::with_params(Vec::new(), params);
+    let sequence = FastqSequence::new(
+        "SEQ_1",
+        [Acid::A, Acid::C],
+        [FastqQualityScore::new(12), FastqQualityScore::new(13)],
+    );
+
+    let result = idn_writer.add_sequence(sequence);
+    assert!(matches!(
+        result,
+        Err(IdnCompressorError::QualityScoreNotInAlphabet(q_score)) if q_score.get() == 13
+    ));
+}
+
+#[test]
+fn test_progress_stages() {
+    for thread_num in [0, 4] {
+        let recorder = Arc::new(StageRecorder::default());
+        let mut data = Vec::new();
+        let params = IdnCompressorParams::builder()
+            .fast(true)
+            .max_block_total_len(760)
+            .thread_num(thread_num)
+            .progress_notifier(recorder.clone())
+            .build()
+            .unwrap();
+        let mut idn_writer = IdnCompressor::with_params(&mut data, params);
+        for sequence in SEQ_1K_READS.iter().take(100) {
+            idn_writer.add_sequence(sequence.clone()).unwrap();
+        }
+        idn_writer.finish().unwrap();
+        assert_eq!(
+            *recorder.stages.lock().unwrap(),
+            [
+                ProgressStage::Parsing,
+                ProgressStage::ModelPreprocessing,
+                ProgressStage::Compressing,
+                ProgressStage::Flushing,
+            ]
+        );
+
+        let recorder = Arc::new(StageRecorder::default());
+        let reader_params = IdnDecompressorParams::builder()
+            .thread_num(thread_num)
+            .progress_notifier(recorder.clone())
+            .build();
+        let idn_reader = IdnDecompressor::with_params(data.as_slice(), reader_params);
+        assert_eq!(idn_reader.into_iter().count(), 100);
+        assert_eq!(
+            *recorder.stages.lock().unwrap(),
+            [
+                ProgressStage::ModelPreprocessing,
+                ProgressStage::Decompressing
+            ]
+        );
+    }
+}
+
+#[derive(Debug, Default)]
+struct ExplanationRecorder {
+    explanations: Mutex<Vec<SequenceExplanation>>,
+}
+
+impl SequenceExplainer for ExplanationRecorder {
+    fn explain(&self, explanation: SequenceExplanation) {
+        self.explanations.lock().unwrap().push(explanation);
+    }
+}
+
+#[test]
+fn test_explain() {
+    let model_provider = ModelProvider::new(vec![
+        TEST_ACID_MODEL_PREFER_A.clone(),
+        TEST_ACID_MODEL_PREFER_C.clone(),
+        Model::empty(ModelType::QualityScores),
+    ]);
+    let recorder = Arc::new(ExplanationRecorder::default());
+    let params = IdnCompressorParams::builder()
+        .model_provider(model_provider)
+        .deduplicate(true)
+        .explainer(recorder.clone())
+        .build()
+        .unwrap();
+
+    let mut data = Vec::new();
+    let mut idn_writer = IdnCompressor::with_params(&mut data, params);
+    for sequence in [
+        TEST_SEQUENCE_PREFER_A.clone(),
+        TEST_SEQUENCE_PREFER_A.clone().with_identifier("DUPLICATE"),
+        TEST_SEQUENCE_PREFER_C.clone(),
+    ] {
+        idn_writer.add_sequence(sequence).unwrap();
+    }
+    idn_writer.finish().unwrap();
+
+    let explanations = recorder.explanations.lock().unwrap();
+    // The duplicate is not encoded with the models, so it is not explained
+    assert_eq!(explanations.len(), 2);
+    let (explanation_a, explanation_c) = (&explanations[0], &explanations[1]);
+    assert_eq!(explanation_a.identifier(), "PREFER_A");
+    assert_eq!(explanation_a.index(), 0);
+    assert_eq!(
+        explanation_a.acid_model(),
+        TEST_ACID_MODEL_PREFER_A.identifier()
+    );
+    assert_eq!(explanation_c.identifier(), "PREFER_C");
+    assert_eq!(explanation_c.index(), 2);
+    assert_eq!(
+        explanation_c.acid_model(),
+        TEST_ACID_MODEL_PREFER_C.identifier()
+    );
+    for explanation in [explanation_a, explanation_c] {
+        assert_eq!(explanation.block(), 0);
+        assert_eq!(explanation.length(), 100);
+        assert!(explanation.estimated_bytes().is_some());
+        assert!(explanation.actual_bytes() > 0);
+        assert_eq!(explanation.acid_dummy_hits(), 0);
+        assert_eq!(explanation.q_score_dummy_hits(), 100);
+    }
+}
+
+#[derive(Debug, Default)]
+struct WarningRecorder {
+    warnings: Mutex<Vec<Warning>>,
+}
+
+impl WarningSink for WarningRecorder {
+    fn warn(&self, warning: Warning) {
+        self.warnings.lock().unwrap().push(warning);
+    }
+}
+
+#[test]
+fn test_warning_model_poor_fit() {
+    let recorder = Arc::new(WarningRecorder::default());
+    let params = IdnCompressorParams::builder()
+        .model_provider(ModelProvider::with_empty_models())
+        .fast(true)
+        .warning_sink(recorder.clone())
+        .build()
+        .unwrap();
+
+    let mut data = Vec::new();
+    let mut idn_writer = IdnCompressor::with_params(&mut data, params);
+    for sequence in SEQ_1K_READS.iter().take(10) {
+        idn_writer.add_sequence(sequence.clone()).unwrap();
+    }
+    idn_writer.finish().unwrap();
+
+    // The uniform models take more than 8 bits per symbol
+    assert!(recorder
+        .warnings
+        .lock()
+        .unwrap()
+        .contains(&Warning::ModelPoorFit {
+            block: 0,
+            sequences: 10
+        }));
+}
+
+#[test]
+fn test_identifier_compression_small_blocks() {
+    let compression = |builder: IdnCompressorParamsBuilder, sequence_num: usize| {
+        let mut data = Vec::new();
+        let mut idn_writer = IdnCompressor::with_params(&mut data, builder.build().unwrap());
+        for sequence in SEQ_1K_READS.iter().take(sequence_num) {
+            idn_writer.add_sequence(sequence.clone()).unwrap();
+        }
+        idn_writer.finish().unwrap();
+
+        let idn_reader = IdnDecompressor::new(data.as_slice());
+        let sequences: Vec<FastqSequence> =
+            idn_reader.into_iter().collect::<Result<_, _>>().unwrap();
+        assert_eq!(sequences, &SEQ_1K_READS[..sequence_num]);
+
+        inspect(data.as_slice()).unwrap().blocks()[0].identifier_compression()
+    };
+
+    // The identifiers of a few reads are too short to be worth compressing,
+    // no matter the quality
+    for quality in [CompressionQuality::MIN, CompressionQuality::MAX] {
+        assert_eq!(
+            compression(IdnCompressorParams::builder().quality(quality), 10),
+            Some(IdentifierCompression::Uncompressed)
+        );
+    }
+    assert_eq!(
+        compression(IdnCompressorParams::builder().deflate_level(6), 10),
+        Some(IdentifierCompression::Deflate)
+    );
+    assert_eq!(
+        compression(IdnCompressorParams::builder().fast(true), 100),
+        Some(IdentifierCompression::Deflate)
+    );
+}
+
+#[test]
+fn test_warning_identifier_fallback() {
+    let sequence = SIMPLE_TEST_SEQUENCE.clone().with_identifier("S");
+    let recorder = Arc::new(WarningRecorder::default());
+    // The tiny identifiers are only compressed when it is requested explicitly
+    let params = IdnCompressorParams::builder()
+        .max_block_reads(1)
+        .deflate_level(6)
+        .warning_sink(recorder.clone())
+        .build()
+        .unwrap();
+
+    let mut data = Vec::new();
+    let mut idn_writer = IdnCompressor::with_params(&mut data, params);
+    for _ in 0..3 {
+        idn_writer.add_sequence(sequence.clone()).unwrap();
+    }
+    idn_writer.finish().unwrap();
+
+    // The warning is only reported for the first block
+    let fallbacks: Vec<_> = recorder
+        .warnings
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|warning| matches!(warning, Warning::IdentifierFallback { .. }))
+        .cloned()
+        .collect();
+    assert_eq!(fallbacks, [Warning::IdentifierFallback { block: 0 }]);
+    let info = inspect(data.as_slice()).unwrap();
+    for block in 0..3 {
+        assert_eq!(
+            info.blocks()[block].identifier_compression(),
+            Some(IdentifierCompression::Uncompressed)
+        );
+    }
+    let idn_reader = IdnDecompressor::new(data.as_slice());
+    let sequences: Vec<FastqSequence> = idn_reader.into_iter().collect::<Result<_, _>>().unwrap();
+    assert_eq!(sequences, vec![sequence; 3]);
+}
+
+#[test]
+fn test_warning_lossy_transform() {
+    let mut data = Vec::new();
+    let params = IdnCompressorParams::builder()
+        .transform(Arc::new(QualityBinning))
+        .build()
+        .unwrap();
+    let mut idn_writer = IdnCompressor::with_params(&mut data, params);
+    idn_writer
+        .add_sequence(SIMPLE_TEST_SEQUENCE.clone())
+        .unwrap();
+    idn_writer.finish().unwrap();
+
+    let recorder = Arc::new(WarningRecorder::default());
+    let reader_params = IdnDecompressorParams::builder()
+        .warning_sink(recorder.clone())
+        .build();
+    let idn_reader = IdnDecompressor::with_params(data.as_slice(), reader_params);
+    assert_eq!(idn_reader.into_iter().count(), 1);
+    assert_eq!(
+        *recorder.warnings.lock().unwrap(),
+        [Warning::LossyTransform {
+            identifier: QualityBinning.identifier()
+        }]
+    );
+}
+
+#[test_log::test]
+fn test_intra_read_model_switch() {
+    let model_provider = ModelProvider::new(vec![
+        TEST_ACID_MODEL_PREFER_A.clone(),
+        TEST_Q_SCORE_MODEL_PREFER_0.clone(),
+        TEST_Q_SCORE_MODEL_PREFER_50.clone(),
+    ]);
+    let q_scores = [0; 100].into_iter().chain([50; 150]).chain([0; 50]);
+    let sequence = FastqSequence::new(
+        "DRIFT",
+        [Acid::A; 300],
+        q_scores.map(FastqQualityScore::new).collect::<Vec<_>>(),
+    );
+    let sequences = [sequence, TEST_SEQUENCE_PREFER_C.clone()];
+
+    let recorder = Arc::new(ExplanationRecorder::default());
+    let params = IdnCompressorParams::builder()
+        .model_provider(model_provider.clone())
+        .intra_read_window(50)
+        .explainer(recorder.clone())
+        .build()
+        .unwrap();
+    let mut data = Vec::new();
+    let mut idn_writer = IdnCompressor::with_params(&mut data, params);
+    for sequence in &sequences {
+        idn_writer.add_sequence(sequence.clone()).unwrap();
+    }
+    idn_writer.finish().unwrap();
+
+    let explanations = recorder.explanations.lock().unwrap();
+    assert_eq!(explanations[0].q_score_model_switches(), 2);
+    assert_eq!(
+        explanations[0].q_score_model(),
+        TEST_Q_SCORE_MODEL_PREFER_0.identifier()
+    );
+    // The second sequence is shorter than the window, so the model is chosen
+    // for all of it
+    assert_eq!(explanations[1].q_score_model_switches(), 0);
+    assert_eq!(
+        explanations[1].q_score_model(),
+        TEST_Q_SCORE_MODEL_PREFER_50.identifier()
+    );
+
+    let info = inspect(data.as_slice()).unwrap();
+    // Acid model, initial quality score model, two switches within the first
+    // sequence, and back to the 50-preferring model for the second one
+    assert_eq!(info.blocks()[0].model_switch_num(), 5);
+
+    let params = IdnDecompressorParams::builder()
+        .model_provider(model_provider)
+        .build();
+    let idn_reader = IdnDecompressor::with_params(data.as_slice(), params);
+    let decompressed: Vec<FastqSequence> =
+        idn_reader.into_iter().collect::<Result<_, _>>().unwrap();
+    assert_eq!(decompressed, sequences);
+}
+
+#[test]
+fn test_round_trip_long_read() {
+    // Longer than the sequence length limit of the default block length
+    const LENGTH: usize = 2 * 1024 * 1024 + 1;
+
+    let model_provider = ModelProvider::new(vec![
+        TEST_ACID_MODEL_PREFER_A.clone(),
+        TEST_Q_SCORE_MODEL_PREFER_0.clone(),
+    ]);
+    let sequence = FastqSequence::new(
+        "LONG",
+        vec![Acid::A; LENGTH],
+        vec![FastqQualityScore::new(0); LENGTH],
+    );
+
+    let params = IdnCompressorParams::builder()
+        .model_provider(model_provider.clone())
+        .build()
+        .unwrap();
+    let mut idn_writer = IdnCompressor::with_params(Vec::new(), params);
+    let error = idn_writer.add_sequence(sequence.clone()).unwrap_err();
+    assert!(matches!(
+        error,
+        IdnCompressorError::SequenceTooLong(LENGTH, _)
+    ));
+
+    let params = IdnCompressorParams::builder()
+        .model_provider(model_provider.clone())
+        .long_reads(true)
+        .build()
+        .unwrap();
+    let mut data = Vec::new();
+    let mut idn_writer = IdnCompressor::with_params(&mut data, params);
+    idn_writer.add_sequence(sequence.clone()).unwrap();
+    idn_writer.finish().unwrap();
+
+    let params = IdnDecompressorParams::builder()
+        .model_provider(model_provider)
+        .build();
+    let idn_reader = IdnDecompressor::with_params(data.as_slice(), params);
+    let decompressed: Vec<FastqSequence> =
+        idn_reader.into_iter().collect::<Result<_, _>>().unwrap();
+    assert_eq!(decompressed, [sequence]);
+}
+
+#[test]
+fn test_compress_with_prepared_models() {
+    let model_provider = ModelProvider::new(vec![
+        TEST_ACID_MODEL_PREFER_A.clone(),
+        TEST_ACID_MODEL_PREFER_C.clone(),
+        Model::empty(ModelType::QualityScores),
+    ]);
+    let prepared = model_provider.prepare_for_compression();
+    let params = IdnCompressorParams::builder().build().unwrap();
+
+    let files = [
+        vec![TEST_SEQUENCE_PREFER_A.clone()],
+        vec![
+            TEST_SEQUENCE_PREFER_C.clone(),
+            TEST_SEQUENCE_PREFER_A.clone(),
+        ],
+    ];
+    for sequences in &files {
+        let mut data = Vec::new();
+        let mut idn_writer = IdnCompressor::with_prepared(&mut data, &prepared, params.clone());
+        for sequence in sequences {
+            idn_writer.add_sequence(sequence.clone()).unwrap();
+        }
+        idn_writer.finish().unwrap();
+
+        let params = IdnDecompressorParams::builder()
+            .model_provider(model_provider.clone())
+            .build();
+        let idn_reader = IdnDecompressor::with_params(data.as_slice(), params);
+        let decompressed: Vec<FastqSequence> =
+            idn_reader.into_iter().collect::<Result<_, _>>().unwrap();
+        assert_eq!(&decompressed, sequences);
+    }
+}
+
+fn compress_in_small_blocks(sequences: &[FastqSequence]) -> Vec<u8> {
+    let mut data = Vec::new();
+    let params = IdnCompressorParams::builder()
+        .fast(true)
+        .max_block_total_len(760)
+        .build()
+        .unwrap();
+    let mut idn_writer = IdnCompressor::with_params(&mut data, params);
+    for sequence in sequences {
+        idn_writer.add_sequence(sequence.clone()).unwrap();
+    }
+    idn_writer.finish().unwrap();
+
+    data
+}
+
+fn compress_members(members: &[(&str, &[FastqSequence])]) -> Vec<u8> {
+    let mut data = Vec::new();
+    let params = IdnCompressorParams::builder()
+        .fast(true)
+        .max_block_total_len(760)
+        .build()
+        .unwrap();
+    let mut idn_writer = IdnCompressor::with_params(&mut data, params);
+    for (name, sequences) in members {
+        idn_writer.start_member(*name).unwrap();
+        for sequence in *sequences {
+            idn_writer.add_sequence(sequence.clone()).unwrap();
+        }
+    }
+    idn_writer.finish().unwrap();
+
+    data
+}
+
 fn round_trip_sequences(sequences: &[FastqSequence]) {
     round_trip_sequences_with_model_provider(sequences, ModelProvider::default())
 }
@@ -92,7 +2416,7 @@ fn round_trip_sequences_with_model_provider(
     sequences: &[FastqSequence],
     model_provider: ModelProvider,
 ) {
-    round_trip_sequences_custom(sequences, sequences, model_provider, |_| {});
+    round_trip_sequences_custom(sequences, sequences, model_provider, |builder| builder);
 }
 
 fn round_trip_sequences_custom<F>(
@@ -101,14 +2425,13 @@ fn round_trip_sequences_custom<F>(
     model_provider: ModelProvider,
     params_modifier: F,
 ) where
-    F: FnOnce(&mut IdnCompressorParamsBuilder),
+    F: FnOnce(IdnCompressorParamsBuilder) -> IdnCompressorParamsBuilder,
 {
     let mut data = Vec::new();
 
-    let mut writer_params_builder = IdnCompressorParams::builder();
-    writer_params_builder.model_provider(model_provider.clone());
-    params_modifier(&mut writer_params_builder);
-    let writer_params = writer_params_builder.build();
+    let writer_params_builder =
+        IdnCompressorParams::builder().model_provider(model_provider.clone());
+    let writer_params = params_modifier(writer_params_builder).build().unwrap();
 
     let mut idn_writer = IdnCompressor::with_params(&mut data, writer_params);
     for sequence in sequences_in {
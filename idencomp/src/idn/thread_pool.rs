@@ -1,16 +1,42 @@
+//! A thread pool running the compression and decompression jobs.
+//!
+//! Unlike the general purpose pools, the jobs can return errors, which are
+//! reported by the next call of [`ThreadPool::execute()`],
+//! [`ThreadPool::get_status()`] or [`ThreadPool::join()`], and the jobs can
+//! spawn further jobs through the child pools created with
+//! [`ThreadPool::make_child()`]. If a job panics, the panic is captured and
+//! resumed on the thread calling one of these methods, so the panics in the
+//! worker threads are never silently lost.
+
+use std::any::Any;
 use std::error::Error;
+use std::fmt::{Debug, Formatter};
 use std::mem;
+use std::panic::{self, AssertUnwindSafe};
 use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+
+/// The payload of a panic captured in a job, as returned by
+/// [`std::panic::catch_unwind()`].
+struct PanicPayload(Box<dyn Any + Send>);
+
+impl Debug for PanicPayload {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("PanicPayload").finish()
+    }
+}
 
 #[derive(Debug)]
 struct ErrorReceiver<E> {
     error: Arc<Mutex<Option<E>>>,
+    panic: Arc<Mutex<Option<PanicPayload>>>,
 }
 
 impl<E> Clone for ErrorReceiver<E> {
     fn clone(&self) -> Self {
         Self {
             error: self.error.clone(),
+            panic: self.panic.clone(),
         }
     }
 }
@@ -20,6 +46,7 @@ impl<E: Default> ErrorReceiver<E> {
     pub fn new() -> Self {
         Self {
             error: Arc::new(Mutex::new(None)),
+            panic: Arc::new(Mutex::new(None)),
         }
     }
 
@@ -34,6 +61,21 @@ impl<E: Default> ErrorReceiver<E> {
         *guard = Some(error);
     }
 
+    fn set_panic(&self, payload: Box<dyn Any + Send>) {
+        let mut guard = self.panic.lock().expect("Could not acquire panic lock");
+        // Only the first panic is kept, as the subsequent ones are usually
+        // its consequences
+        if guard.is_none() {
+            *guard = Some(PanicPayload(payload));
+        }
+    }
+
+    /// Returns the payload of the first panic captured in the jobs, if any.
+    fn take_panic(&self) -> Option<Box<dyn Any + Send>> {
+        let mut guard = self.panic.lock().expect("Could not acquire panic lock");
+        guard.take().map(|PanicPayload(payload)| payload)
+    }
+
     pub fn status(&self) -> Result<(), E> {
         let mut guard = self.error.lock().expect("Could not acquire error lock");
 
@@ -46,27 +88,89 @@ impl<E: Default> ErrorReceiver<E> {
     }
 }
 
+/// The function called in the worker thread after a job has panicked, e.g. to
+/// wake up the threads waiting for the results of the job.
+type PanicHandler = Arc<dyn Fn() + Send + Sync>;
+
 pub type ThreadPoolJobResult<E> = Result<(), E>;
 
+#[cfg(feature = "parallel")]
+type InnerPool = threadpool::ThreadPool;
+
+#[cfg(feature = "parallel")]
+fn new_inner_pool(thread_num: usize, thread_name: &str) -> Option<InnerPool> {
+    let pool = threadpool::Builder::new()
+        .num_threads(thread_num)
+        .thread_name(thread_name.to_owned())
+        .build();
+    Some(pool)
+}
+
+/// Without the `parallel` feature, the thread pool always runs the jobs in the
+/// foreground, so the inner pool is never created.
+#[cfg(not(feature = "parallel"))]
 #[derive(Debug)]
+enum InnerPool {}
+
+#[cfg(not(feature = "parallel"))]
+impl InnerPool {
+    fn execute<F>(&self, _job: F) {
+        match *self {}
+    }
+
+    fn join(&self) {
+        match *self {}
+    }
+
+    fn active_count(&self) -> usize {
+        match *self {}
+    }
+
+    fn queued_count(&self) -> usize {
+        match *self {}
+    }
+}
+
+#[cfg(not(feature = "parallel"))]
+fn new_inner_pool(_thread_num: usize, _thread_name: &str) -> Option<InnerPool> {
+    None
+}
+
+/// A pool of named worker threads, running the jobs in the foreground if
+/// created with no threads.
 pub(in crate::idn) struct ThreadPool<E> {
-    inner: Option<Arc<Mutex<threadpool::ThreadPool>>>,
+    inner: Option<Arc<Mutex<InnerPool>>>,
     thread_num: usize,
     parent: bool,
     in_thread: bool,
     child_num: Arc<(Mutex<u8>, Condvar)>,
     error_receiver: ErrorReceiver<E>,
+    panic_handler: Option<PanicHandler>,
+}
+
+impl<E: Debug> Debug for ThreadPool<E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ThreadPool")
+            .field("inner", &self.inner)
+            .field("thread_num", &self.thread_num)
+            .field("parent", &self.parent)
+            .field("in_thread", &self.in_thread)
+            .field("child_num", &self.child_num)
+            .field("error_receiver", &self.error_receiver)
+            .finish_non_exhaustive()
+    }
 }
 
 impl<E: Error + Default + Send + 'static> ThreadPool<E> {
     #[must_use]
     pub fn new(thread_num: usize, thread_name: &str) -> Self {
+        let thread_num = if cfg!(feature = "parallel") {
+            thread_num
+        } else {
+            0
+        };
         let inner = if thread_num > 0 {
-            let pool = threadpool::Builder::new()
-                .num_threads(thread_num)
-                .thread_name(thread_name.to_owned())
-                .build();
-            Some(Arc::new(Mutex::new(pool)))
+            new_inner_pool(thread_num, thread_name).map(|pool| Arc::new(Mutex::new(pool)))
         } else {
             None
         };
@@ -78,9 +182,24 @@ impl<E: Error + Default + Send + 'static> ThreadPool<E> {
             in_thread: false,
             child_num: Arc::new((Mutex::new(0), Condvar::new())),
             error_receiver: ErrorReceiver::new(),
+            panic_handler: None,
         }
     }
 
+    /// Sets the function called in the worker thread when any of the jobs of
+    /// this pool or its children panics. The panic itself is resumed by the
+    /// next call of [`Self::execute()`], [`Self::get_status()`] or
+    /// [`Self::join()`], so the handler should only make sure that the other
+    /// threads do not wait forever for the job that has failed.
+    #[must_use]
+    pub fn on_panic<F>(mut self, handler: F) -> Self
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        self.panic_handler = Some(Arc::new(handler));
+        self
+    }
+
     #[must_use]
     pub fn make_child(&self) -> Self {
         let in_thread = if self.thread_num > 0 {
@@ -109,6 +228,7 @@ impl<E: Error + Default + Send + 'static> ThreadPool<E> {
             in_thread,
             child_num: self.child_num.clone(),
             error_receiver: self.error_receiver.clone(),
+            panic_handler: self.panic_handler.clone(),
         }
     }
 
@@ -117,33 +237,61 @@ impl<E: Error + Default + Send + 'static> ThreadPool<E> {
         self.thread_num == 0
     }
 
+    /// Runs given job in the pool, or in the current thread if the pool has
+    /// no threads. Returns the error of any of the jobs run so far, including
+    /// the given one if it is run in the foreground.
     pub fn execute<'a, F>(&'a self, job: F) -> ThreadPoolJobResult<E>
     where
         F: FnOnce() -> ThreadPoolJobResult<E> + Send + 'a,
     {
-        self.error_receiver.status()?;
+        self.status()?;
+        self.spawn(job);
+        if self.inner.is_none() {
+            self.status()?;
+        }
+
+        Ok(())
+    }
 
+    /// Runs given job in the pool, or in the current thread if the pool has
+    /// no threads. Unlike [`Self::execute()`], the errors are only reported
+    /// by [`Self::get_status()`] and [`Self::join()`], which is useful for
+    /// starting the first job in a newly created pool.
+    pub fn spawn<'a, F>(&'a self, job: F)
+    where
+        F: FnOnce() -> ThreadPoolJobResult<E> + Send + 'a,
+    {
         if let Some(pool) = &self.inner {
             let inner_guard = pool.lock().expect("Could not acquire thread pool lock");
 
             let inner_job: Box<dyn FnOnce() -> ThreadPoolJobResult<E> + Send + 'a> = Box::new(job);
+            // SAFETY: the parent pool waits for all the jobs in `join()` and
+            // refuses to be dropped while any of them are active, so the job
+            // does not outlive the data it borrows
             let inner_job: Box<dyn FnOnce() -> ThreadPoolJobResult<E> + Send + 'static> =
                 unsafe { mem::transmute(inner_job) };
             let error_receiver = self.error_receiver.clone();
-            let job = move || {
-                error_receiver.handle_result(inner_job());
+            let panic_handler = self.panic_handler.clone();
+            let job = move || match panic::catch_unwind(AssertUnwindSafe(inner_job)) {
+                Ok(result) => error_receiver.handle_result(result),
+                Err(payload) => {
+                    error_receiver.set_panic(payload);
+                    if let Some(panic_handler) = panic_handler {
+                        panic_handler();
+                    }
+                }
             };
             inner_guard.execute(job);
         } else {
             self.error_receiver.handle_result(job());
-            self.error_receiver.status()?;
         }
-
-        Ok(())
     }
 
+    /// Returns the error of any of the jobs run so far, waiting for all the
+    /// jobs to finish if there was one. If any of the jobs has panicked, the
+    /// panic is resumed in the current thread.
     pub fn get_status(&self) -> Result<(), E> {
-        let result = self.error_receiver.status();
+        let result = self.status();
         if let Err(error) = result {
             self.inner_join();
             return Err(error);
@@ -152,13 +300,34 @@ impl<E: Error + Default + Send + 'static> ThreadPool<E> {
         Ok(())
     }
 
+    /// Waits for all the jobs to finish, including the ones run by the child
+    /// pools, and returns the error of any of them. If any of the jobs has
+    /// panicked, the panic is resumed in the current thread.
     pub fn join(&self) -> Result<(), E> {
         self.inner_join();
-        self.error_receiver.status()?;
+        self.status()?;
 
         Ok(())
     }
 
+    /// Returns the error of any of the jobs run so far, or resumes the panic
+    /// of any of them. The parent pool waits for all the jobs to finish
+    /// before resuming the panic, so that none of them outlive the data they
+    /// borrow.
+    fn status(&self) -> Result<(), E> {
+        if let Some(payload) = self.error_receiver.take_panic() {
+            if self.parent {
+                self.inner_join();
+            }
+            // Panicking again would abort the process
+            if !thread::panicking() {
+                panic::resume_unwind(payload);
+            }
+        }
+
+        self.error_receiver.status()
+    }
+
     fn inner_join(&self) {
         if !self.parent {
             panic!("Can do join() only on parent ThreadPool");
@@ -212,8 +381,13 @@ impl<E> Drop for ThreadPool<E> {
 mod tests {
     use std::error::Error;
     use std::fmt::{Display, Formatter};
+    #[cfg(feature = "parallel")]
+    use std::panic::{self, AssertUnwindSafe};
+    #[cfg(feature = "parallel")]
+    use std::sync::atomic::{AtomicBool, Ordering};
     use std::sync::{Arc, Mutex};
     use std::thread;
+    #[cfg(feature = "parallel")]
     use std::time::Duration;
 
     use crate::idn::thread_pool::ThreadPool;
@@ -262,6 +436,7 @@ mod tests {
         assert_eq!(*result.lock().unwrap(), current_id);
     }
 
+    #[cfg(feature = "parallel")]
     #[test]
     fn test_thread_pool_background() {
         let pool: ThreadPool<TestError> = ThreadPool::new(1, "test");
@@ -280,6 +455,7 @@ mod tests {
         assert_ne!(*result.lock().unwrap(), current_id);
     }
 
+    #[cfg(feature = "parallel")]
     #[test]
     fn test_thread_pool_error_on_join() {
         let pool: ThreadPool<TestError> = ThreadPool::new(1, "test");
@@ -291,7 +467,40 @@ mod tests {
         assert_eq!(result.unwrap_err(), TestError::new("error in execute"));
     }
 
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_thread_pool_spawn_error_on_join() {
+        let pool: ThreadPool<TestError> = ThreadPool::new(1, "test");
+
+        pool.spawn(move || Err(TestError::new("error in spawn")));
+        let result = pool.join();
+
+        assert_eq!(result.unwrap_err(), TestError::new("error in spawn"));
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_thread_pool_panic_resumed_on_join() {
+        let handler_called = Arc::new(AtomicBool::new(false));
+        let handler_called_thread = handler_called.clone();
+        let pool: ThreadPool<TestError> = ThreadPool::new(2, "test")
+            .on_panic(move || handler_called_thread.store(true, Ordering::Relaxed));
+
+        let child = pool.make_child();
+        pool.spawn(move || {
+            child.execute(|| panic!("panic in child job"))?;
+            Ok(())
+        });
+        let payload = panic::catch_unwind(AssertUnwindSafe(|| pool.join())).unwrap_err();
+
+        assert_eq!(payload.downcast_ref::<&str>(), Some(&"panic in child job"));
+        assert!(handler_called.load(Ordering::Relaxed));
+        // The panic is only resumed once
+        assert!(pool.join().is_ok());
+    }
+
     #[test]
+    #[cfg(feature = "parallel")]
     #[should_panic(expected = "Cannot drop ThreadPool when any jobs are active")]
     fn test_thread_pool_drop_when_active() {
         let pool: ThreadPool<TestError> = ThreadPool::new(1, "test");
@@ -0,0 +1,189 @@
+use std::time::{Duration, Instant};
+
+use crate::idn::common::start_time;
+use crate::progress::ByteNum;
+
+/// How much work the compressor puts into compressing a block. The levels
+/// are ordered from the cheapest to the most expensive one.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+pub(super) enum CompressionEffort {
+    /// The models are only chosen for the first sequence of the block and the
+    /// identifiers are compressed with the fastest settings (and with Deflate
+    /// rather than Brotli, if available).
+    Minimal,
+    /// The models are chosen for every
+    /// [`REDUCED_EFFORT_MODEL_CHOOSER_INTERVAL`]-th sequence and the
+    /// identifiers are compressed with the medium settings.
+    Reduced,
+    /// Everything is done as set in the compression parameters.
+    Full,
+}
+
+/// The number of sequences the models are kept for before choosing them again
+/// with [`CompressionEffort::Reduced`].
+pub(super) const REDUCED_EFFORT_MODEL_CHOOSER_INTERVAL: usize = 16;
+#[cfg(feature = "brotli")]
+const REDUCED_EFFORT_BROTLI_QUALITY: u32 = 5;
+#[cfg(feature = "deflate")]
+const REDUCED_EFFORT_DEFLATE_LEVEL: u32 = 4;
+#[cfg(feature = "brotli")]
+const MINIMAL_EFFORT_BROTLI_QUALITY: u32 = 1;
+#[cfg(feature = "deflate")]
+const MINIMAL_EFFORT_DEFLATE_LEVEL: u32 = 1;
+
+/// The fraction of the budget the compression is projected to use (or, if the
+/// input size is unknown, has already used) up to which the full effort is
+/// kept.
+const FULL_EFFORT_MAX_BUDGET_USAGE: f64 = 0.8;
+/// The fraction of the budget the compression is projected to use (or, if the
+/// input size is unknown, has already used) up to which the effort is only
+/// reduced rather than being minimal.
+const REDUCED_EFFORT_MAX_BUDGET_USAGE: f64 = 1.0;
+
+impl CompressionEffort {
+    #[must_use]
+    pub fn model_chooser_interval(self) -> usize {
+        match self {
+            CompressionEffort::Minimal => usize::MAX,
+            CompressionEffort::Reduced => REDUCED_EFFORT_MODEL_CHOOSER_INTERVAL,
+            CompressionEffort::Full => 1,
+        }
+    }
+
+    #[cfg(feature = "brotli")]
+    #[must_use]
+    pub fn brotli_quality(self, quality: u32) -> u32 {
+        match self {
+            CompressionEffort::Minimal => quality.min(MINIMAL_EFFORT_BROTLI_QUALITY),
+            CompressionEffort::Reduced => quality.min(REDUCED_EFFORT_BROTLI_QUALITY),
+            CompressionEffort::Full => quality,
+        }
+    }
+
+    #[cfg(feature = "deflate")]
+    #[must_use]
+    pub fn deflate_level(self, level: u32) -> u32 {
+        match self {
+            CompressionEffort::Minimal => level.min(MINIMAL_EFFORT_DEFLATE_LEVEL),
+            CompressionEffort::Reduced => level.min(REDUCED_EFFORT_DEFLATE_LEVEL),
+            CompressionEffort::Full => level,
+        }
+    }
+}
+
+/// Keeps track of the time spent compressing and chooses the effort for the
+/// subsequent blocks, so that the compression finishes within the budget.
+#[derive(Debug)]
+pub(super) struct TimeBudget {
+    budget: Duration,
+    input_size: Option<ByteNum>,
+    start_time: Option<Instant>,
+}
+
+impl TimeBudget {
+    #[must_use]
+    pub fn new(budget: Duration, input_size: Option<ByteNum>) -> Self {
+        Self {
+            budget,
+            input_size,
+            start_time: start_time(),
+        }
+    }
+
+    /// Returns the effort to compress the next block with, given the number
+    /// of input bytes compressed so far.
+    ///
+    /// If the input size is known, the time the whole compression is going to
+    /// take is projected from the throughput so far. Otherwise, the effort is
+    /// lowered as the budget gets used up.
+    #[must_use]
+    pub fn effort(&self, processed: ByteNum) -> CompressionEffort {
+        let elapsed = match self.start_time {
+            Some(start_time) => start_time.elapsed(),
+            None => return CompressionEffort::Full,
+        };
+        if elapsed >= self.budget {
+            return CompressionEffort::Minimal;
+        }
+
+        let used = elapsed.as_secs_f64() / self.budget.as_secs_f64();
+        let usage = match self.input_size {
+            Some(input_size) if processed.get() > 0 => {
+                let done = (processed.get() as f64 / input_size.get() as f64).min(1.0);
+                used / done
+            }
+            Some(_) => 0.0,
+            None => used,
+        };
+
+        Self::effort_for_usage(usage)
+    }
+
+    #[must_use]
+    fn effort_for_usage(usage: f64) -> CompressionEffort {
+        if usage <= FULL_EFFORT_MAX_BUDGET_USAGE {
+            CompressionEffort::Full
+        } else if usage <= REDUCED_EFFORT_MAX_BUDGET_USAGE {
+            CompressionEffort::Reduced
+        } else {
+            CompressionEffort::Minimal
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::idn::time_budget::{CompressionEffort, TimeBudget};
+    use crate::progress::ByteNum;
+
+    #[test]
+    fn test_effort_for_usage() {
+        assert_eq!(TimeBudget::effort_for_usage(0.0), CompressionEffort::Full);
+        assert_eq!(TimeBudget::effort_for_usage(0.8), CompressionEffort::Full);
+        assert_eq!(
+            TimeBudget::effort_for_usage(0.9),
+            CompressionEffort::Reduced
+        );
+        assert_eq!(
+            TimeBudget::effort_for_usage(1.5),
+            CompressionEffort::Minimal
+        );
+    }
+
+    #[test]
+    fn test_effort_budget_exceeded() {
+        let budget = TimeBudget::new(Duration::ZERO, None);
+
+        assert_eq!(budget.effort(ByteNum::ZERO), CompressionEffort::Minimal);
+    }
+
+    #[test]
+    fn test_effort_large_budget() {
+        let budget = TimeBudget::new(Duration::from_secs(3600), Some(ByteNum::new(1000)));
+
+        assert_eq!(budget.effort(ByteNum::ZERO), CompressionEffort::Full);
+        assert_eq!(budget.effort(ByteNum::new(1000)), CompressionEffort::Full);
+    }
+
+    #[test]
+    fn test_effort_settings() {
+        assert_eq!(CompressionEffort::Full.model_chooser_interval(), 1);
+        assert!(CompressionEffort::Minimal < CompressionEffort::Reduced);
+    }
+
+    #[cfg(feature = "brotli")]
+    #[test]
+    fn test_effort_brotli_quality() {
+        assert_eq!(CompressionEffort::Full.brotli_quality(11), 11);
+        assert_eq!(CompressionEffort::Reduced.brotli_quality(11), 5);
+        assert_eq!(CompressionEffort::Reduced.brotli_quality(3), 3);
+    }
+
+    #[cfg(feature = "deflate")]
+    #[test]
+    fn test_effort_deflate_level() {
+        assert_eq!(CompressionEffort::Minimal.deflate_level(9), 1);
+    }
+}
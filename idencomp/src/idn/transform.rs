@@ -0,0 +1,498 @@
+use std::fmt::Debug;
+
+use crate::fastq::{FastqQualityScore, FastqSequence, FASTQ_Q_END};
+use crate::idn::data::IdnTransformMetadata;
+use crate::quality_alphabet::QualityAlphabet;
+use crate::sequence::Acid;
+
+/// The maximum number of transforms stored in the metadata of an IDN file.
+pub(crate) const MAX_TRANSFORMS: usize = u8::MAX as usize;
+/// The maximum length of a transform identifier, in bytes.
+pub(crate) const MAX_TRANSFORM_IDENTIFIER_LEN: usize = u8::MAX as usize;
+
+/// Transformation applied to the sequences before they are compressed, such
+/// as quality score binning, trimming or masking.
+///
+/// The transforms are set with
+/// [`IdnCompressorParamsBuilder::transform()`](crate::idn::compressor::IdnCompressorParamsBuilder::transform)
+/// and their identifiers are stored in the compressed file. When
+/// decompressing, the inverse of each transform is applied if the transform
+/// has been registered with
+/// [`IdnDecompressorParamsBuilder::transform()`](crate::idn::decompressor::IdnDecompressorParamsBuilder::transform).
+///
+/// The transforms are applied in the order they have been added, when the
+/// sequences are passed to
+/// [`IdnCompressor::add_sequence()`](crate::idn::compressor::IdnCompressor::add_sequence).
+pub trait SequenceTransform: Debug + Send + Sync {
+    /// Returns the identifier of the transform, stored in the compressed file.
+    /// It should include the parameters of the transform, if any, and must be
+    /// at most 255 bytes long.
+    fn identifier(&self) -> String;
+
+    /// Returns whether the transform loses information, i.e. the original
+    /// sequence cannot be restored with [`Self::inverse()`].
+    fn is_lossy(&self) -> bool;
+
+    /// Transforms given sequence before it is compressed.
+    fn apply(&self, sequence: FastqSequence) -> FastqSequence;
+
+    /// Reverts the transform on a decompressed sequence. The default
+    /// implementation returns the sequence unchanged, which is only suitable
+    /// for the lossy transforms.
+    fn inverse(&self, sequence: FastqSequence) -> FastqSequence {
+        sequence
+    }
+}
+
+/// Information about a [`SequenceTransform`] that has been applied to the
+/// sequences of an IDN file, as stored in the file metadata.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct TransformInfo {
+    identifier: String,
+    lossy: bool,
+}
+
+impl TransformInfo {
+    pub(super) fn new(identifier: String, lossy: bool) -> Self {
+        Self { identifier, lossy }
+    }
+
+    pub(super) fn of(transform: &dyn SequenceTransform) -> Self {
+        Self::new(transform.identifier(), transform.is_lossy())
+    }
+
+    pub(super) fn from_metadata(metadata: &IdnTransformMetadata) -> Self {
+        let identifier = String::from_utf8_lossy(&metadata.identifier).into_owned();
+        Self::new(identifier, metadata.lossy != 0)
+    }
+
+    pub(super) fn to_metadata(&self) -> IdnTransformMetadata {
+        let identifier: Vec<u8> = self
+            .identifier
+            .bytes()
+            .take(MAX_TRANSFORM_IDENTIFIER_LEN)
+            .collect();
+
+        IdnTransformMetadata {
+            lossy: self.lossy as u8,
+            identifier_len: identifier.len() as u8,
+            identifier,
+        }
+    }
+
+    /// Returns the identifier of the transform.
+    #[must_use]
+    pub fn identifier(&self) -> &str {
+        &self.identifier
+    }
+
+    /// Returns whether the transform loses information.
+    #[must_use]
+    pub fn is_lossy(&self) -> bool {
+        self.lossy
+    }
+}
+
+/// Illumina 8-level quality score binning. The quality scores are replaced
+/// with the representative values of the bins they fall into, which makes them
+/// compress considerably better.
+///
+/// # Examples
+/// ```
+/// use idencomp::fastq::{FastqQualityScore, FastqSequence};
+/// use idencomp::idn::transform::{QualityBinning, SequenceTransform};
+/// use idencomp::sequence::Acid;
+///
+/// let sequence = FastqSequence::new(
+///     "SEQ_1",
+///     [Acid::A, Acid::C],
+///     [FastqQualityScore::new(12), FastqQualityScore::new(41)],
+/// );
+/// let binned = QualityBinning.apply(sequence);
+/// assert_eq!(
+///     binned.quality_scores(),
+///     [FastqQualityScore::new(15), FastqQualityScore::new(40)]
+/// );
+/// ```
+#[derive(Debug, Copy, Clone, Default)]
+pub struct QualityBinning;
+
+impl QualityBinning {
+    /// Returns the [`QualityAlphabet`] consisting of the quality scores the
+    /// binned sequences can contain, which can be set with
+    /// [`IdnCompressorParamsBuilder::quality_alphabet()`](crate::idn::compressor::IdnCompressorParamsBuilder::quality_alphabet)
+    /// to compress them more efficiently.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::idn::transform::QualityBinning;
+    ///
+    /// assert_eq!(QualityBinning::alphabet().to_string(), "0,1,6,15,22,27,33,37,40");
+    /// ```
+    #[must_use]
+    pub fn alphabet() -> QualityAlphabet {
+        QualityAlphabet::new(
+            (0..FASTQ_Q_END as u8).map(|value| Self::bin(FastqQualityScore::new(value))),
+        )
+    }
+
+    #[must_use]
+    fn bin(q_score: FastqQualityScore) -> FastqQualityScore {
+        let value = match q_score.get() {
+            0..=1 => return q_score,
+            2..=9 => 6,
+            10..=19 => 15,
+            20..=24 => 22,
+            25..=29 => 27,
+            30..=34 => 33,
+            35..=39 => 37,
+            _ => 40,
+        };
+
+        FastqQualityScore::new(value)
+    }
+}
+
+impl SequenceTransform for QualityBinning {
+    fn identifier(&self) -> String {
+        "quality-binning-illumina8".to_owned()
+    }
+
+    fn is_lossy(&self) -> bool {
+        true
+    }
+
+    fn apply(&self, sequence: FastqSequence) -> FastqSequence {
+        let identifier = sequence.identifier().clone();
+        let size = sequence.size();
+        let (acids, q_scores) = sequence.into_data();
+        let q_scores: Vec<_> = q_scores.into_iter().map(Self::bin).collect();
+
+        FastqSequence::with_size(identifier, acids, q_scores, size)
+    }
+}
+
+/// Trims the bases with the quality score lower than given threshold off the
+/// end of the read. At least one base is always kept.
+///
+/// # Examples
+/// ```
+/// use idencomp::fastq::{FastqQualityScore, FastqSequence};
+/// use idencomp::idn::transform::{QualityTrimming, SequenceTransform};
+/// use idencomp::sequence::Acid;
+///
+/// let sequence = FastqSequence::new(
+///     "SEQ_1",
+///     [Acid::A, Acid::C, Acid::G],
+///     [
+///         FastqQualityScore::new(30),
+///         FastqQualityScore::new(5),
+///         FastqQualityScore::new(2),
+///     ],
+/// );
+/// let trimmed = QualityTrimming::new(10).apply(sequence);
+/// assert_eq!(trimmed.acids(), [Acid::A]);
+/// ```
+#[derive(Debug, Copy, Clone)]
+pub struct QualityTrimming {
+    min_q_score: u8,
+}
+
+impl QualityTrimming {
+    /// Creates a new `QualityTrimming` instance that trims the bases with the
+    /// quality score lower than `min_q_score`.
+    #[must_use]
+    pub fn new(min_q_score: u8) -> Self {
+        Self { min_q_score }
+    }
+}
+
+impl SequenceTransform for QualityTrimming {
+    fn identifier(&self) -> String {
+        format!("quality-trimming-q{}", self.min_q_score)
+    }
+
+    fn is_lossy(&self) -> bool {
+        true
+    }
+
+    fn apply(&self, sequence: FastqSequence) -> FastqSequence {
+        let len = sequence
+            .quality_scores()
+            .iter()
+            .rposition(|q_score| q_score.get() >= self.min_q_score as usize)
+            .map_or(1, |index| index + 1)
+            .min(sequence.len());
+        if len == sequence.len() {
+            return sequence;
+        }
+
+        let identifier = sequence.identifier().clone();
+        let size = sequence.size();
+        let (mut acids, mut q_scores) = sequence.into_data();
+        acids.truncate(len);
+        q_scores.truncate(len);
+
+        FastqSequence::with_size(identifier, acids, q_scores, size)
+    }
+}
+
+/// Replaces the acids with the quality score lower than given threshold with
+/// [`Acid::N`].
+///
+/// # Examples
+/// ```
+/// use idencomp::fastq::{FastqQualityScore, FastqSequence};
+/// use idencomp::idn::transform::{QualityMasking, SequenceTransform};
+/// use idencomp::sequence::Acid;
+///
+/// let sequence = FastqSequence::new(
+///     "SEQ_1",
+///     [Acid::A, Acid::C],
+///     [FastqQualityScore::new(30), FastqQualityScore::new(5)],
+/// );
+/// let masked = QualityMasking::new(10).apply(sequence);
+/// assert_eq!(masked.acids(), [Acid::A, Acid::N]);
+/// ```
+#[derive(Debug, Copy, Clone)]
+pub struct QualityMasking {
+    min_q_score: u8,
+}
+
+impl QualityMasking {
+    /// Creates a new `QualityMasking` instance that masks the acids with the
+    /// quality score lower than `min_q_score`.
+    #[must_use]
+    pub fn new(min_q_score: u8) -> Self {
+        Self { min_q_score }
+    }
+}
+
+impl SequenceTransform for QualityMasking {
+    fn identifier(&self) -> String {
+        format!("quality-masking-q{}", self.min_q_score)
+    }
+
+    fn is_lossy(&self) -> bool {
+        true
+    }
+
+    fn apply(&self, sequence: FastqSequence) -> FastqSequence {
+        let identifier = sequence.identifier().clone();
+        let size = sequence.size();
+        let (mut acids, q_scores) = sequence.into_data();
+        for (acid, q_score) in acids.iter_mut().zip(&q_scores) {
+            if q_score.get() < self.min_q_score as usize {
+                *acid = Acid::N;
+            }
+        }
+
+        FastqSequence::with_size(identifier, acids, q_scores, size)
+    }
+}
+
+/// Delta-encodes the quality scores along the read: each quality score, but
+/// the first one, is replaced with its difference from the preceding one.
+/// The quality scores of many instruments change slowly along the read, so
+/// the differences are mostly close to zero and can be modelled with lower
+/// entropy than the scores themselves.
+///
+/// The transform is lossless and registered in the decompressor by default,
+/// so the files compressed with it can be read without any additional
+/// configuration. The differences are taken modulo the number of the
+/// FASTQ quality scores and interleaved, so that 0, -1, 1, -2, 2, ... are
+/// stored as 0, 1, 2, 3, 4, ...; the transformed scores can therefore be any
+/// of the FASTQ quality scores, and the models used should be trained on the
+/// transformed data.
+///
+/// Only the differences along a single read are supported. Encoding the
+/// scores against the previous read would make each read depend on all the
+/// preceding ones, which breaks decoding the members, indexed reads and
+/// split files independently. As the transform applies to the whole file,
+/// it cannot be configured per model either.
+///
+/// # Examples
+/// ```
+/// use idencomp::fastq::{FastqQualityScore, FastqSequence};
+/// use idencomp::idn::transform::{QualityDelta, SequenceTransform};
+/// use idencomp::sequence::Acid;
+///
+/// let q_scores = [30, 31, 29, 29].map(FastqQualityScore::new);
+/// let sequence = FastqSequence::new("SEQ_1", [Acid::A; 4], q_scores);
+/// let transformed = QualityDelta.apply(sequence.clone());
+/// assert_eq!(
+///     transformed.quality_scores(),
+///     [30, 2, 3, 0].map(FastqQualityScore::new)
+/// );
+/// assert_eq!(QualityDelta.inverse(transformed), sequence);
+/// ```
+#[derive(Debug, Copy, Clone, Default)]
+pub struct QualityDelta;
+
+impl QualityDelta {
+    #[must_use]
+    fn encode(previous: FastqQualityScore, current: FastqQualityScore) -> FastqQualityScore {
+        let delta = (current.get() + FASTQ_Q_END - previous.get()) % FASTQ_Q_END;
+        let value = if delta < FASTQ_Q_END / 2 {
+            2 * delta
+        } else {
+            2 * (FASTQ_Q_END - delta) - 1
+        };
+
+        FastqQualityScore::new(value as u8)
+    }
+
+    #[must_use]
+    fn decode(previous: FastqQualityScore, encoded: FastqQualityScore) -> FastqQualityScore {
+        let value = encoded.get();
+        let delta = if value % 2 == 0 {
+            value / 2
+        } else {
+            FASTQ_Q_END - (value + 1) / 2
+        };
+
+        FastqQualityScore::new(((previous.get() + delta) % FASTQ_Q_END) as u8)
+    }
+
+    fn map_q_scores<F>(sequence: FastqSequence, mut f: F) -> FastqSequence
+    where
+        F: FnMut(&mut [FastqQualityScore]),
+    {
+        let identifier = sequence.identifier().clone();
+        let size = sequence.size();
+        let (acids, mut q_scores) = sequence.into_data();
+        f(&mut q_scores);
+
+        FastqSequence::with_size(identifier, acids, q_scores, size)
+    }
+}
+
+impl SequenceTransform for QualityDelta {
+    fn identifier(&self) -> String {
+        "quality-delta".to_owned()
+    }
+
+    fn is_lossy(&self) -> bool {
+        false
+    }
+
+    fn apply(&self, sequence: FastqSequence) -> FastqSequence {
+        Self::map_q_scores(sequence, |q_scores| {
+            // Going backwards, so that the preceding scores are still the
+            // original ones
+            for i in (1..q_scores.len()).rev() {
+                q_scores[i] = Self::encode(q_scores[i - 1], q_scores[i]);
+            }
+        })
+    }
+
+    fn inverse(&self, sequence: FastqSequence) -> FastqSequence {
+        Self::map_q_scores(sequence, |q_scores| {
+            for i in 1..q_scores.len() {
+                q_scores[i] = Self::decode(q_scores[i - 1], q_scores[i]);
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::_internal_test_data::{EMPTY_TEST_SEQUENCE, SIMPLE_TEST_SEQUENCE};
+    use crate::fastq::FASTQ_Q_END;
+    use crate::fastq::{FastqQualityScore, FastqSequence};
+    use crate::idn::transform::{
+        QualityBinning, QualityDelta, QualityMasking, QualityTrimming, SequenceTransform,
+        TransformInfo,
+    };
+    use crate::sequence::Acid;
+
+    #[test]
+    fn test_quality_binning() {
+        let q_scores: Vec<_> = [0, 1, 2, 9, 10, 19, 20, 24, 25, 29, 30, 34, 35, 39, 40, 93]
+            .into_iter()
+            .map(FastqQualityScore::new)
+            .collect();
+        let sequence = FastqSequence::new("SEQ", vec![Acid::A; q_scores.len()], q_scores);
+
+        let binned: Vec<_> = QualityBinning
+            .apply(sequence.clone())
+            .quality_scores()
+            .iter()
+            .map(FastqQualityScore::get)
+            .collect();
+        assert_eq!(
+            binned,
+            [0, 1, 6, 6, 15, 15, 22, 22, 27, 27, 33, 33, 37, 37, 40, 40]
+        );
+        assert_eq!(
+            QualityBinning.apply(sequence.clone()).size(),
+            sequence.size()
+        );
+        assert_eq!(QualityBinning.inverse(sequence.clone()), sequence);
+    }
+
+    #[test]
+    fn test_quality_trimming() {
+        let q_scores = [20, 3, 25, 4, 1].map(FastqQualityScore::new);
+        let sequence = FastqSequence::new("SEQ", [Acid::A; 5], q_scores);
+
+        let trimmed = QualityTrimming::new(10).apply(sequence.clone());
+        assert_eq!(trimmed.quality_scores(), &q_scores[..3]);
+        assert_eq!(trimmed.acids(), [Acid::A; 3]);
+        assert_eq!(QualityTrimming::new(0).apply(sequence.clone()), sequence);
+        assert_eq!(QualityTrimming::new(50).apply(sequence).len(), 1);
+        assert_eq!(
+            QualityTrimming::new(50).apply(EMPTY_TEST_SEQUENCE.clone()),
+            *EMPTY_TEST_SEQUENCE
+        );
+    }
+
+    #[test]
+    fn test_quality_masking() {
+        let q_scores = [20, 3, 25].map(FastqQualityScore::new);
+        let sequence = FastqSequence::new("SEQ", [Acid::A, Acid::C, Acid::G], q_scores);
+
+        let masked = QualityMasking::new(10).apply(sequence);
+        assert_eq!(masked.acids(), [Acid::A, Acid::N, Acid::G]);
+        assert_eq!(masked.quality_scores(), q_scores);
+    }
+
+    #[test]
+    fn test_quality_delta() {
+        let q_scores: Vec<_> = (0..FASTQ_Q_END as u8)
+            .chain((0..FASTQ_Q_END as u8).rev())
+            .chain([0, 93, 0, 47, 46, 0])
+            .map(FastqQualityScore::new)
+            .collect();
+        let sequence = FastqSequence::new("SEQ", vec![Acid::A; q_scores.len()], q_scores);
+
+        let transformed = QualityDelta.apply(sequence.clone());
+        assert_eq!(transformed.size(), sequence.size());
+        assert_eq!(transformed.quality_scores()[1], FastqQualityScore::new(2));
+        assert_eq!(QualityDelta.inverse(transformed), sequence);
+        assert_eq!(
+            QualityDelta.apply(EMPTY_TEST_SEQUENCE.clone()),
+            *EMPTY_TEST_SEQUENCE
+        );
+        assert!(!QualityDelta.is_lossy());
+    }
+
+    #[test]
+    fn test_transform_info() {
+        let info = TransformInfo::of(&QualityMasking::new(10));
+
+        assert_eq!(info.identifier(), "quality-masking-q10");
+        assert!(info.is_lossy());
+        assert_eq!(TransformInfo::from_metadata(&info.to_metadata()), info);
+        assert_eq!(
+            QualityTrimming::new(10).identifier(),
+            "quality-trimming-q10"
+        );
+        assert_eq!(
+            QualityBinning
+                .apply(SIMPLE_TEST_SEQUENCE.clone())
+                .identifier(),
+            SIMPLE_TEST_SEQUENCE.identifier()
+        );
+    }
+}
@@ -0,0 +1,149 @@
+use std::fmt::{Debug, Display, Formatter};
+
+use log::warn;
+
+/// A non-fatal issue encountered during compression or decompression.
+///
+/// The warnings are always logged, and additionally passed to the
+/// [`WarningSink`] set with
+/// [`IdnCompressorParamsBuilder::warning_sink()`](crate::idn::compressor::IdnCompressorParamsBuilder::warning_sink)
+/// or
+/// [`IdnDecompressorParamsBuilder::warning_sink()`](crate::idn::decompressor::IdnDecompressorParamsBuilder::warning_sink),
+/// if any.
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum Warning {
+    /// Some of the sequences of a block have been compressed to more than one
+    /// byte per symbol, which means the models do not fit the data well.
+    ModelPoorFit {
+        /// The index of the block.
+        block: u32,
+        /// The number of the sequences compressed poorly.
+        sequences: usize,
+    },
+    /// The identifiers of a block could not be compressed to fewer bytes than
+    /// they take, so they have been stored uncompressed. It is only reported
+    /// for the first such block of the file. The identifiers too short to be
+    /// worth compressing are stored uncompressed without a warning.
+    IdentifierFallback {
+        /// The index of the block.
+        block: u32,
+    },
+    /// The sequences of a block have been encoded into more bytes than
+    /// allowed by
+    /// [`IdnCompressorParamsBuilder::raw_fallback_ratio()`](crate::idn::compressor::IdnCompressorParamsBuilder::raw_fallback_ratio),
+    /// so they have been stored raw.
+    RawFallback {
+        /// The index of the block.
+        block: u32,
+    },
+    /// The sequences have been compressed with a lossy transform, so the
+    /// original data cannot be restored.
+    LossyTransform {
+        /// The identifier of the transform.
+        identifier: String,
+    },
+}
+
+impl Display for Warning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Warning::ModelPoorFit { block, sequences } => write!(
+                f,
+                "{} sequences of block {} have been compressed to more than one byte per \
+                symbol; the models do not fit the data well",
+                sequences, block
+            ),
+            Warning::IdentifierFallback { block } => write!(
+                f,
+                "The identifiers of block {} could not be compressed and have been stored \
+                uncompressed",
+                block
+            ),
+            Warning::RawFallback { block } => write!(
+                f,
+                "The sequences of block {} could not be compressed well enough and have been \
+                stored raw",
+                block
+            ),
+            Warning::LossyTransform { identifier } => write!(
+                f,
+                "The sequences have been transformed with lossy transform `{}`; the \
+                original data cannot be restored",
+                identifier
+            ),
+        }
+    }
+}
+
+/// Receiver of the [`Warning`]s, which makes it possible to handle the
+/// non-fatal issues programmatically rather than only having them logged.
+///
+/// # Examples
+/// ```
+/// use std::sync::{Arc, Mutex};
+///
+/// use idencomp::idn::compressor::IdnCompressorParams;
+/// use idencomp::idn::warning::{Warning, WarningSink};
+///
+/// #[derive(Debug, Default)]
+/// struct Collector(Mutex<Vec<Warning>>);
+///
+/// impl WarningSink for Collector {
+///     fn warn(&self, warning: Warning) {
+///         self.0.lock().unwrap().push(warning);
+///     }
+/// }
+///
+/// let _params = IdnCompressorParams::builder()
+///     .warning_sink(Arc::new(Collector::default()))
+///     .build()?;
+///
+/// # Ok::<(), idencomp::Error>(())
+/// ```
+pub trait WarningSink: Debug + Send + Sync {
+    /// Receives a single warning.
+    fn warn(&self, warning: Warning);
+}
+
+/// Logs given warning and passes it to the sink, if one is set.
+pub(super) fn emit(sink: Option<&dyn WarningSink>, warning: Warning) {
+    warn!("{}", warning);
+    if let Some(sink) = sink {
+        sink.warn(warning);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::idn::warning::Warning;
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            Warning::ModelPoorFit {
+                block: 1,
+                sequences: 4
+            }
+            .to_string(),
+            "4 sequences of block 1 have been compressed to more than one byte per symbol; the \
+            models do not fit the data well"
+        );
+        assert_eq!(
+            Warning::IdentifierFallback { block: 3 }.to_string(),
+            "The identifiers of block 3 could not be compressed and have been stored uncompressed"
+        );
+        assert_eq!(
+            Warning::RawFallback { block: 2 }.to_string(),
+            "The sequences of block 2 could not be compressed well enough and have been stored raw"
+        );
+        assert_eq!(
+            Warning::LossyTransform {
+                identifier: "bin".to_owned()
+            }
+            .to_string(),
+            "The sequences have been transformed with lossy transform `bin`; the original data \
+            cannot be restored"
+        );
+    }
+}
@@ -3,13 +3,16 @@ use std::io::{Cursor, Seek, Write};
 
 use binrw::BinWrite;
 
-use crate::fastq::FastqSequence;
+use crate::fastq::{FastqQualityScore, FastqSequence};
 use crate::idn::compressor::IdnCompressResult;
 use crate::idn::data::{
-    IdnBlockHeader, IdnIdentifierCompression, IdnIdentifiersHeader, IdnSequenceHeader,
-    IdnSliceHeader, IdnSwitchModelHeader,
+    IdnAuxDataHeader, IdnBlockHeader, IdnDuplicateHeader, IdnGroupHeader, IdnIdentifierCompression,
+    IdnIdentifiersHeader, IdnMemberHeader, IdnNQualityScoreHeader, IdnPackedHeader, IdnRawHeader,
+    IdnSeparatorTextHeader, IdnSequenceHeader, IdnSliceHeader, IdnSwitchModelAtHeader,
+    IdnSwitchModelHeader,
 };
 
+#[derive(Debug)]
 pub(super) struct BlockWriter {
     data: Cursor<Vec<u8>>,
     hasher: crc32fast::Hasher,
@@ -39,6 +42,21 @@ impl BlockWriter {
         Ok(())
     }
 
+    /// Returns the number of bytes written to the block so far.
+    #[must_use]
+    pub fn data_len(&self) -> usize {
+        self.data.get_ref().len()
+    }
+
+    /// Discards everything written to the block after its first `len` bytes.
+    /// The checksum of the sequences is discarded as well, so none of the
+    /// sequences can be written before that point.
+    pub fn truncate(&mut self, len: usize) {
+        self.data.get_mut().truncate(len);
+        self.data.set_position(len as u64);
+        self.hasher = crc32fast::Hasher::new();
+    }
+
     pub fn write_identifiers(
         &mut self,
         compression_method: IdnIdentifierCompression,
@@ -56,6 +74,44 @@ impl BlockWriter {
         Ok(())
     }
 
+    /// Writes the (already encoded and compressed) auxiliary data of all the
+    /// sequences in the block.
+    pub fn write_aux_data(
+        &mut self,
+        compression_method: IdnIdentifierCompression,
+        data: &[u8],
+    ) -> IdnCompressResult<()> {
+        let header = IdnAuxDataHeader {
+            length: data.len() as u32,
+            compression: compression_method,
+        };
+        let header = IdnSliceHeader::AuxData(header);
+
+        self.write_slice_header(header)?;
+        self.data.write_all(data)?;
+
+        Ok(())
+    }
+
+    /// Writes the (already encoded and compressed) separator texts of all the
+    /// sequences in the block.
+    pub fn write_separator_texts(
+        &mut self,
+        compression_method: IdnIdentifierCompression,
+        data: &[u8],
+    ) -> IdnCompressResult<()> {
+        let header = IdnSeparatorTextHeader {
+            length: data.len() as u32,
+            compression: compression_method,
+        };
+        let header = IdnSliceHeader::SeparatorText(header);
+
+        self.write_slice_header(header)?;
+        self.data.write_all(data)?;
+
+        Ok(())
+    }
+
     pub fn write_sequence(
         &mut self,
         sequence: &FastqSequence,
@@ -75,12 +131,101 @@ impl BlockWriter {
         Ok(())
     }
 
+    /// Writes a sequence that has the same acids as the one written
+    /// `distance` sequences earlier in this block. `q_score_data` are the
+    /// encoded quality scores of the sequence, or an empty slice if they are
+    /// the same as the ones of the earlier sequence.
+    pub fn write_duplicate(
+        &mut self,
+        sequence: &FastqSequence,
+        distance: usize,
+        q_score_data: &[u8],
+    ) -> IdnCompressResult<()> {
+        sequence.hash(&mut self.hasher);
+
+        let header = IdnDuplicateHeader {
+            distance: distance as u32,
+            seq_len: sequence.len() as u32,
+            length: q_score_data.len() as u32,
+        };
+        let header = IdnSliceHeader::Duplicate(header);
+        self.write_slice_header(header)?;
+        self.data.write_all(q_score_data)?;
+
+        Ok(())
+    }
+
+    /// Marks the rest of the block as packed: the acids of the subsequent
+    /// sequences are stored using 2 bits per acid, and all their quality
+    /// scores are equal to `q_score`.
+    pub fn write_packed(&mut self, q_score: FastqQualityScore) -> IdnCompressResult<()> {
+        let header = IdnPackedHeader {
+            q_score: q_score.get() as u8,
+        };
+        let header = IdnSliceHeader::Packed(header);
+        self.write_slice_header(header)
+    }
+
+    /// Marks the rest of the block as raw: the subsequent sequences are stored
+    /// uncompressed, as returned by [`raw::store_raw()`].
+    ///
+    /// [`raw::store_raw()`]: crate::idn::raw::store_raw
+    pub fn write_raw(&mut self) -> IdnCompressResult<()> {
+        let header = IdnSliceHeader::Raw(IdnRawHeader {});
+        self.write_slice_header(header)
+    }
+
+    /// Sets the quality score of all the [`Acid::N`] symbols in the rest of
+    /// the block, so that only the acids are encoded at their positions.
+    ///
+    /// [`Acid::N`]: crate::sequence::Acid::N
+    pub fn write_n_q_score(&mut self, q_score: FastqQualityScore) -> IdnCompressResult<()> {
+        let header = IdnNQualityScoreHeader {
+            q_score: q_score.get() as u8,
+        };
+        let header = IdnSliceHeader::NQualityScore(header);
+        self.write_slice_header(header)
+    }
+
+    /// Marks the start of a container member with given name.
+    pub fn write_member(&mut self, name: &str) -> IdnCompressResult<()> {
+        let header = IdnMemberHeader {
+            name_len: name.len() as u16,
+            name: name.as_bytes().to_vec(),
+        };
+        let header = IdnSliceHeader::Member(header);
+        self.write_slice_header(header)
+    }
+
+    /// Stores the key of the group all the sequences in the block belong to.
+    pub fn write_group(&mut self, key: &str) -> IdnCompressResult<()> {
+        let header = IdnGroupHeader {
+            key_len: key.len() as u16,
+            key: key.as_bytes().to_vec(),
+        };
+        let header = IdnSliceHeader::Group(header);
+        self.write_slice_header(header)
+    }
+
     pub fn write_switch_model(&mut self, index: u8) -> IdnCompressResult<()> {
         let header = IdnSwitchModelHeader { model_index: index };
         let header = IdnSliceHeader::SwitchModel(header);
         self.write_slice_header(header)
     }
 
+    /// Switches to the model with given index within the next sequence,
+    /// starting at the symbol with given offset. After the sequence, the model
+    /// stays active as if it has been switched to with
+    /// [`Self::write_switch_model()`].
+    pub fn write_switch_model_at(&mut self, offset: usize, index: u8) -> IdnCompressResult<()> {
+        let header = IdnSwitchModelAtHeader {
+            offset: offset as u32,
+            model_index: index,
+        };
+        let header = IdnSliceHeader::SwitchModelAt(header);
+        self.write_slice_header(header)
+    }
+
     fn write_slice_header(&mut self, header: IdnSliceHeader) -> IdnCompressResult<()> {
         header.write_to(&mut self.data)?;
         Ok(())
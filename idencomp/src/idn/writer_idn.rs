@@ -1,16 +1,26 @@
-use std::io::{Seek, Write};
+use std::io::{Seek, SeekFrom, Write};
+use std::mem;
 
 use binrw::BinWrite;
 use itertools::Itertools;
 
 use crate::idn::compressor::IdnCompressResult;
-use crate::idn::data::{IdnHeader, IdnMetadataHeader, IdnMetadataItem, IdnModelsMetadata};
+use crate::idn::data::{
+    IdnHeader, IdnMemberIndexEntry, IdnMemberIndexMetadata, IdnMetadataHeader, IdnMetadataItem,
+    IdnModelsMetadata, IdnQualityAlphabetMetadata, IdnSourceDigestMetadata, IdnTransformsMetadata,
+};
+use crate::idn::source_digest::SourceDigest;
+use crate::idn::transform::TransformInfo;
 use crate::model::ModelIdentifier;
+use crate::quality_alphabet::QualityAlphabet;
 
 #[derive(Debug)]
 pub(super) struct IdnWriter<W> {
     writer: W,
     metadata_items: Option<Vec<IdnMetadataItem>>,
+    seekable: bool,
+    source_digest_position: Option<u64>,
+    members: Vec<IdnMemberIndexEntry>,
 }
 
 impl<W: Write + Seek> IdnWriter<W> {
@@ -19,6 +29,20 @@ impl<W: Write + Seek> IdnWriter<W> {
         Self {
             writer,
             metadata_items: Some(Vec::new()),
+            seekable: false,
+            source_digest_position: None,
+            members: Vec::new(),
+        }
+    }
+
+    /// Creates a new writer over a writer that supports arbitrary seeks, which
+    /// allows the metadata to be patched in place once all the data has been
+    /// written.
+    #[must_use]
+    pub fn new_seekable(writer: W) -> Self {
+        Self {
+            seekable: true,
+            ..Self::new(writer)
         }
     }
 
@@ -41,17 +65,157 @@ impl<W: Write + Seek> IdnWriter<W> {
             .push(item);
     }
 
+    /// Adds the list of the transforms applied to the sequences. Nothing is
+    /// added if the list is empty, so that the files with no transforms can be
+    /// read by the decompressors that do not support them.
+    pub fn add_transforms_metadata(&mut self, transforms: &[TransformInfo]) {
+        if transforms.is_empty() {
+            return;
+        }
+
+        let metadata = IdnTransformsMetadata {
+            num_transforms: transforms.len() as u8,
+            transforms: transforms.iter().map(TransformInfo::to_metadata).collect(),
+        };
+
+        let item = IdnMetadataItem::Transforms(metadata);
+        self.metadata_items
+            .as_mut()
+            .expect("Metadata already written")
+            .push(item);
+    }
+
+    /// Adds the quality alphabet the quality scores are encoded with. Nothing
+    /// is added for the full alphabet, which is assumed when the item is
+    /// missing.
+    pub fn add_quality_alphabet_metadata(&mut self, alphabet: &QualityAlphabet) {
+        if alphabet.is_full() {
+            return;
+        }
+
+        let metadata = IdnQualityAlphabetMetadata {
+            num_values: alphabet.symbol_num() as u8,
+            values: alphabet
+                .values()
+                .iter()
+                .map(|value| value.get() as u8)
+                .collect(),
+        };
+
+        let item = IdnMetadataItem::QualityAlphabet(metadata);
+        self.metadata_items
+            .as_mut()
+            .expect("Metadata already written")
+            .push(item);
+    }
+
+    /// Reserves the space for the source digest in the metadata, so that it
+    /// can be patched in place by [`Self::write_source_digest()`]. Nothing is
+    /// reserved if the writer is not seekable.
+    pub fn reserve_source_digest(&mut self) {
+        if !self.seekable {
+            return;
+        }
+
+        let metadata = IdnSourceDigestMetadata { sha256: [0; 32] };
+        let item = IdnMetadataItem::SourceDigest(metadata);
+        self.metadata_items
+            .as_mut()
+            .expect("Metadata already written")
+            .push(item);
+    }
+
     pub fn write_metadata(&mut self) -> IdnCompressResult<()> {
         let metadata_items = self
             .metadata_items
             .take()
             .expect("Metadata already written");
+        self.write_metadata_items(metadata_items)
+    }
+
+    /// Records a block that is about to be written in the member index. If
+    /// `member` is not `None`, the block starts a new container member.
+    /// The blocks written before the first member are not recorded.
+    pub fn record_block(
+        &mut self,
+        member: Option<&str>,
+        sequence_num: usize,
+    ) -> IdnCompressResult<()> {
+        debug_assert!(self.is_metadata_written());
+
+        if let Some(name) = member {
+            self.members.push(IdnMemberIndexEntry {
+                offset: self.writer.stream_position()?,
+                block_num: 0,
+                sequence_num: 0,
+                name_len: name.len() as u16,
+                name: name.as_bytes().to_vec(),
+            });
+        }
+        if let Some(entry) = self.members.last_mut() {
+            entry.block_num += 1;
+            entry.sequence_num += sequence_num as u64;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the metadata known only once all the blocks have been written:
+    /// the digest of the source data and the member index. If the space for
+    /// the digest has been reserved with [`Self::reserve_source_digest()`], it
+    /// is patched in place. The remaining items are written as the trailing
+    /// metadata, placed after the end-of-file block, which is ignored by the
+    /// decompressor. Nothing is written if there are no such items.
+    pub fn write_trailing_metadata(
+        &mut self,
+        source_digest: Option<&SourceDigest>,
+    ) -> IdnCompressResult<()> {
+        debug_assert!(self.is_metadata_written());
+
+        let mut items = Vec::new();
+        if let Some(source_digest) = source_digest {
+            let metadata = IdnSourceDigestMetadata {
+                sha256: *source_digest.as_bytes(),
+            };
+            let item = IdnMetadataItem::SourceDigest(metadata);
+            match self.source_digest_position {
+                Some(position) => {
+                    let end = self.writer.stream_position()?;
+                    self.writer.seek(SeekFrom::Start(position))?;
+                    item.write_to(&mut self.writer)?;
+                    self.writer.seek(SeekFrom::Start(end))?;
+                }
+                None => items.push(item),
+            }
+        }
+        if !self.members.is_empty() {
+            let members = mem::take(&mut self.members);
+            let metadata = IdnMemberIndexMetadata {
+                num_members: members.len() as u32,
+                members,
+            };
+            items.push(IdnMetadataItem::MemberIndex(metadata));
+        }
+
+        if items.is_empty() {
+            return Ok(());
+        }
+        self.write_metadata_items(items)
+    }
+
+    fn write_metadata_items(
+        &mut self,
+        metadata_items: Vec<IdnMetadataItem>,
+    ) -> IdnCompressResult<()> {
         let metadata_header = IdnMetadataHeader {
             item_num: metadata_items.len() as u8,
         };
 
         metadata_header.write_to(&mut self.writer)?;
         for item in metadata_items {
+            if self.seekable && matches!(item, IdnMetadataItem::SourceDigest(_)) {
+                self.source_digest_position = Some(self.writer.stream_position()?);
+            }
             item.write_to(&mut self.writer)?;
         }
 
@@ -16,7 +16,9 @@
 //! utilize multiple cores/threads for all the critical parts. It contains a CLI
 //! interface and an accompanying Rust library.
 
-mod compressor;
+/// rANS entropy coder working on the [`Context`](context::Context)s, with
+/// any number of interleaved channels.
+pub mod compressor;
 /// Statistical model for a single local situation.
 pub mod context;
 /// Context binning module that can be used to make smaller models while
@@ -25,17 +27,31 @@ pub mod context_binning;
 /// Context specifier generators that can describe local situations in a
 /// sequence with a single number.
 pub mod context_spec;
+mod error;
 /// FASTQ file reader and writer.
 pub mod fastq;
+/// Random generators of the sequences, contexts and models, for fuzzing and
+/// property-based testing.
+#[cfg(feature = "fuzzing")]
+pub mod fuzzing;
 /// IDN compressor, decompressor, and utilities around.
 pub mod idn;
+/// Memory-mapped file reader.
+#[cfg(feature = "mmap")]
+pub mod mmap;
 /// Statistical model used to compress/decompress sequences.
 pub mod model;
 /// Utilities that can be used to create models using nucleotide sequences.
 pub mod model_generator;
+/// Set of the quality score values the quality score models are built for.
+pub mod quality_alphabet;
 /// Nucleotide sequence and its building blocks.
 pub mod sequence;
-mod sequence_compressor;
+/// Compressor and decompressor of single sequences, with no IDN framing, that
+/// can be used to embed the compressed reads in other formats.
+pub mod sequence_compressor;
+/// Sources of the sequences to compress, such as FASTQ readers.
+pub mod sequence_source;
 
 #[doc(hidden)]
 pub mod _internal_test_data;
@@ -43,6 +59,9 @@ mod clustering;
 mod int_queue;
 /// Serializer and deserializer of the statistical model.
 pub mod model_serializer;
+mod parallel;
 /// Progress notifier that can be used to get the progress of the long-running
 /// operations.
 pub mod progress;
+
+pub use error::{Error, Result};
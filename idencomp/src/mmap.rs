@@ -0,0 +1,162 @@
+use std::fs::File;
+use std::io::{BufRead, ErrorKind, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use memmap2::Mmap;
+
+/// Reader of a memory-mapped file.
+///
+/// [`BufRead::fill_buf()`] returns the whole remaining part of the file, so the
+/// readers that use it, such as
+/// [`FastqReader`](crate::fastq::reader::FastqReader), read straight from the
+/// mapped memory without copying the data to an intermediate buffer. Seeking
+/// is cheap as well, so the input can be rewound at no cost.
+#[derive(Debug)]
+pub struct MmapReader {
+    mmap: Mmap,
+    position: usize,
+}
+
+impl MmapReader {
+    /// Memory-maps the file at given path.
+    ///
+    /// # Safety
+    /// The file must not be modified or truncated (by this or any other
+    /// process) as long as the returned reader exists; otherwise, the
+    /// behavior is undefined.
+    pub unsafe fn open<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let file = File::open(path)?;
+        Self::from_file(&file)
+    }
+
+    /// Memory-maps given file.
+    ///
+    /// # Safety
+    /// The file must not be modified or truncated (by this or any other
+    /// process) as long as the returned reader exists; otherwise, the
+    /// behavior is undefined.
+    pub unsafe fn from_file(file: &File) -> std::io::Result<Self> {
+        let mmap = Mmap::map(file)?;
+
+        Ok(Self { mmap, position: 0 })
+    }
+
+    /// Returns the whole contents of the file.
+    #[must_use]
+    pub fn as_slice(&self) -> &[u8] {
+        &self.mmap
+    }
+
+    /// Returns the length of the file in bytes.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.mmap.len()
+    }
+
+    /// Returns whether the file is empty.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.mmap.is_empty()
+    }
+
+    fn remaining(&self) -> &[u8] {
+        let position = self.position.min(self.mmap.len());
+        &self.mmap[position..]
+    }
+}
+
+impl Read for MmapReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let remaining = self.remaining();
+        let len = remaining.len().min(buf.len());
+        buf[..len].copy_from_slice(&remaining[..len]);
+        self.position += len;
+
+        Ok(len)
+    }
+}
+
+impl BufRead for MmapReader {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        Ok(self.remaining())
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.position += amt;
+    }
+}
+
+impl Seek for MmapReader {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(offset) => (0, offset as i64),
+            SeekFrom::End(offset) => (self.mmap.len() as i64, offset),
+            SeekFrom::Current(offset) => (self.position as i64, offset),
+        };
+
+        match base.checked_add(offset) {
+            Some(position) if position >= 0 => {
+                self.position = position as usize;
+                Ok(position as u64)
+            }
+            _ => Err(std::io::Error::new(
+                ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::io::{BufRead, Read, Seek, SeekFrom};
+    use std::path::PathBuf;
+
+    use crate::_internal_test_data::SEQ_1K_READS_FASTQ;
+    use crate::fastq::reader::FastqReader;
+    use crate::mmap::MmapReader;
+
+    fn write_temp_file(name: &str, data: &[u8]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("idencomp-{}-{}", std::process::id(), name));
+        fs::write(&path, data).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_read_fastq() {
+        let path = write_temp_file("mmap-fastq", SEQ_1K_READS_FASTQ);
+        let reader = unsafe { MmapReader::open(&path) }.unwrap();
+        assert_eq!(reader.as_slice(), SEQ_1K_READS_FASTQ);
+
+        let result: Result<Vec<_>, _> = FastqReader::new(reader).into_iter().collect();
+        let expected: Result<Vec<_>, _> =
+            FastqReader::new(SEQ_1K_READS_FASTQ).into_iter().collect();
+        assert_eq!(result.unwrap(), expected.unwrap());
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_read_and_seek() {
+        let path = write_temp_file("mmap-seek", b"0123456789");
+        let mut reader = unsafe { MmapReader::open(&path) }.unwrap();
+        assert_eq!(reader.len(), 10);
+
+        let mut buf = [0; 4];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"0123");
+        assert_eq!(reader.fill_buf().unwrap(), b"456789");
+
+        assert_eq!(reader.seek(SeekFrom::Current(-2)).unwrap(), 2);
+        reader.consume(1);
+        assert_eq!(reader.fill_buf().unwrap(), b"3456789");
+        assert_eq!(reader.seek(SeekFrom::End(-1)).unwrap(), 9);
+        assert_eq!(reader.fill_buf().unwrap(), b"9");
+        assert_eq!(reader.seek(SeekFrom::Start(20)).unwrap(), 20);
+        assert_eq!(reader.read(&mut buf).unwrap(), 0);
+        assert!(reader.seek(SeekFrom::Current(-21)).is_err());
+
+        fs::remove_file(path).unwrap();
+    }
+}
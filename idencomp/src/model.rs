@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::error::Error;
 use std::fmt::{Display, Formatter};
 use std::hash::Hash;
 
@@ -12,7 +13,12 @@ use crate::context::Context;
 use crate::context_binning::ComplexContext;
 use crate::context_spec::{ContextSpec, ContextSpecType};
 use crate::fastq::FastqQualityScore;
+use crate::progress::ByteNum;
+use crate::quality_alphabet::QualityAlphabet;
 use crate::sequence::{Acid, Symbol};
+use crate::sequence_compressor::{
+    AcidRansDecModel, AcidRansEncModel, QScoreRansDecModel, QScoreRansEncModel,
+};
 
 /// Compression rate of given model, expressed as bits per value (bpv) float.
 #[derive(Deref, Copy, Debug, PartialOrd, Clone, Default)]
@@ -96,7 +102,7 @@ impl Display for ModelType {
 
 impl ModelType {
     #[must_use]
-    fn symbols_num(&self) -> usize {
+    pub(crate) fn symbols_num(&self) -> usize {
         match self {
             ModelType::Acids => Acid::SIZE,
             ModelType::QualityScores => FastqQualityScore::SIZE,
@@ -171,6 +177,7 @@ pub struct Model {
     identifier: ModelIdentifier,
     model_type: ModelType,
     spec_type: ContextSpecType,
+    symbol_num: usize,
     contexts: Vec<Context>,
     map: HashMap<ContextSpec, usize>,
 }
@@ -180,15 +187,17 @@ impl Model {
     fn new(
         model_type: ModelType,
         spec_type: ContextSpecType,
+        symbol_num: usize,
         contexts: Vec<Context>,
         map: HashMap<ContextSpec, usize>,
     ) -> Self {
-        let identifier = Self::make_identifier(model_type, spec_type, &contexts, &map);
+        let identifier = Self::make_identifier(model_type, spec_type, symbol_num, &contexts, &map);
 
         Self {
             identifier,
             model_type,
             spec_type,
+            symbol_num,
             contexts,
             map,
         }
@@ -209,22 +218,51 @@ impl Model {
     ///     Model::with_model_and_spec_type(ModelType::Acids, ContextSpecType::Dummy, [complex_ctx]);
     /// ```
     ///
+    /// The quality score models can have fewer symbols than there are quality
+    /// scores in the FASTQ format, in which case they are only usable with a
+    /// [`QualityAlphabet`] of the same size (see
+    /// [`Self::with_quality_alphabet()`]).
+    ///
     /// # Panics
-    /// Panics if the number of symbols in any context is not equal to
-    /// `model_type.symbols_num()`.
+    /// Panics if the contexts have different numbers of symbols, or the number
+    /// of symbols is not valid for `model_type` (see [`Self::symbol_num()`]).
     #[must_use]
     pub fn with_model_and_spec_type<T: Into<Vec<ComplexContext>>>(
         model_type: ModelType,
         spec_type: ContextSpecType,
         contexts: T,
     ) -> Self {
-        let (context_vec, map) = Self::map_contexts(contexts);
+        let contexts: Vec<ComplexContext> = contexts.into();
+        let symbol_num = contexts
+            .first()
+            .map_or(model_type.symbols_num(), |context| {
+                context.context.symbol_num()
+            });
 
-        assert!(context_vec
-            .iter()
-            .all(|x| x.symbol_num() == model_type.symbols_num()));
+        Self::with_symbol_num(model_type, spec_type, symbol_num, contexts)
+    }
 
-        Self::new(model_type, spec_type, context_vec, map)
+    /// Constructs a new [`Model`] instance, like
+    /// [`Self::with_model_and_spec_type()`], but with an explicitly given
+    /// number of symbols, so that it is preserved for the models that do not
+    /// contain any contexts.
+    #[must_use]
+    pub(crate) fn with_symbol_num<T: Into<Vec<ComplexContext>>>(
+        model_type: ModelType,
+        spec_type: ContextSpecType,
+        symbol_num: usize,
+        contexts: T,
+    ) -> Self {
+        match model_type {
+            ModelType::Acids => assert_eq!(symbol_num, Acid::SIZE),
+            ModelType::QualityScores => {
+                assert!((1..=FastqQualityScore::SIZE).contains(&symbol_num));
+            }
+        }
+        let (context_vec, map) = Self::map_contexts(contexts);
+        assert!(context_vec.iter().all(|x| x.symbol_num() == symbol_num));
+
+        Self::new(model_type, spec_type, symbol_num, context_vec, map)
     }
 
     fn map_contexts<T: Into<Vec<ComplexContext>>>(
@@ -265,11 +303,69 @@ impl Model {
         Self::new(
             model_type,
             ContextSpecType::Dummy,
+            model_type.symbols_num(),
             Vec::new(),
             HashMap::new(),
         )
     }
 
+    /// Returns the quality score model adapted to given [`QualityAlphabet`].
+    /// The probability of each of the quality scores is added to the symbol
+    /// of the closest value of the alphabet, so the model is the best suited
+    /// for the data that has been binned to the alphabet values.
+    ///
+    /// The returned model has [`QualityAlphabet::symbol_num()`] symbols, which
+    /// makes it proportionally smaller when preprocessed.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::fastq::FastqQualityScore;
+    /// use idencomp::model::{Model, ModelType};
+    /// use idencomp::quality_alphabet::QualityAlphabet;
+    ///
+    /// let alphabet = QualityAlphabet::new([2, 12, 23, 37].map(FastqQualityScore::new));
+    /// let model = Model::empty(ModelType::QualityScores).with_quality_alphabet(&alphabet);
+    /// assert_eq!(model.symbol_num(), 4);
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if this is not a quality score model, or it has already been
+    /// adapted to a quality alphabet.
+    #[must_use]
+    pub fn with_quality_alphabet(&self, alphabet: &QualityAlphabet) -> Self {
+        assert_eq!(self.model_type, ModelType::QualityScores);
+        assert_eq!(self.symbol_num, FastqQualityScore::SIZE);
+
+        let contexts: Vec<ComplexContext> = self
+            .as_complex_contexts()
+            .into_iter()
+            .map(|context| {
+                let (specs, context) = context.into_spec_and_context();
+                ComplexContext::new(specs, Self::reduce_context(&context, alphabet))
+            })
+            .collect();
+
+        Self::with_symbol_num(
+            self.model_type,
+            self.spec_type,
+            alphabet.symbol_num(),
+            contexts,
+        )
+    }
+
+    fn reduce_context(context: &Context, alphabet: &QualityAlphabet) -> Context {
+        let mut symbol_prob = vec![0.0_f32; alphabet.symbol_num()];
+        for (value, prob) in context.symbol_prob.iter().enumerate() {
+            let q_score = FastqQualityScore::new(value as u8);
+            symbol_prob[alphabet.nearest_index(q_score)] += prob.get();
+        }
+
+        Context::new_from(
+            context.context_prob,
+            symbol_prob.into_iter().map(|prob| prob.min(1.0)),
+        )
+    }
+
     /// Returns the number of contexts in this [`Model`] instance.
     ///
     /// # Examples
@@ -319,6 +415,48 @@ impl Model {
         &self.identifier
     }
 
+    /// Recomputes the identifier from the current contents of this model and
+    /// compares it to the stored one.
+    ///
+    /// The identifier is stored alongside the model when it is serialized, so
+    /// a mismatch means that the model file has been corrupted or edited by
+    /// hand.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::model::{Model, ModelType};
+    ///
+    /// let model = Model::empty(ModelType::Acids);
+    /// assert!(model.verify_identifier().is_ok());
+    /// ```
+    pub fn verify_identifier(&self) -> Result<(), ModelIdentifierMismatchError> {
+        let computed = Self::make_identifier(
+            self.model_type,
+            self.spec_type,
+            self.symbol_num,
+            &self.contexts,
+            &self.map,
+        );
+
+        if computed == self.identifier {
+            Ok(())
+        } else {
+            Err(ModelIdentifierMismatchError::new(
+                self.identifier.clone(),
+                computed,
+            ))
+        }
+    }
+
+    /// Replaces the identifier of this model with one that has been stored
+    /// elsewhere (e.g. in a model file), without checking it. Use
+    /// [`Self::verify_identifier`] to make sure it matches the contents.
+    #[must_use]
+    pub(crate) fn with_stored_identifier(mut self, identifier: ModelIdentifier) -> Self {
+        self.identifier = identifier;
+        self
+    }
+
     /// Returns the model type of this [`Model`] instance.
     ///
     /// # Examples
@@ -334,6 +472,24 @@ impl Model {
         self.model_type
     }
 
+    /// Returns the number of symbols of this [`Model`] instance. This is
+    /// always 5 for the acid models. For the quality score models, this is 94
+    /// unless the model has been adapted to a smaller [`QualityAlphabet`] with
+    /// [`Self::with_quality_alphabet()`].
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::model::{Model, ModelType};
+    ///
+    /// assert_eq!(Model::empty(ModelType::Acids).symbol_num(), 5);
+    /// assert_eq!(Model::empty(ModelType::QualityScores).symbol_num(), 94);
+    /// ```
+    #[inline]
+    #[must_use]
+    pub fn symbol_num(&self) -> usize {
+        self.symbol_num
+    }
+
     /// Returns the context specifier type of this [`Model`] instance.
     ///
     /// # Examples
@@ -455,9 +611,60 @@ impl Model {
         )
     }
 
+    /// Returns the approximate number of bytes the compressor version of this
+    /// model takes in memory, e.g. after calling
+    /// [`ModelProvider::preprocess_compressor_models()`](crate::idn::model_provider::ModelProvider::preprocess_compressor_models).
+    ///
+    /// The encoder contexts always have the same size, regardless of the
+    /// number of scale bits. The map of context specs grows with the number of specs of the
+    /// model's [`ContextSpecType`], so this can be large even if the model
+    /// contains only a few contexts.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::model::{Model, ModelType};
+    ///
+    /// let model = Model::empty(ModelType::Acids);
+    /// assert!(model.estimated_enc_memory().get() > 0);
+    /// ```
+    #[must_use]
+    pub fn estimated_enc_memory(&self) -> ByteNum {
+        let bytes = match self.model_type {
+            ModelType::Acids => AcidRansEncModel::estimated_memory(self),
+            ModelType::QualityScores => QScoreRansEncModel::estimated_memory(self),
+        };
+
+        ByteNum::new(bytes)
+    }
+
+    /// Returns the approximate number of bytes the decompressor version of
+    /// this model takes in memory.
+    ///
+    /// The decoder contexts do not store a full cumulative frequency table, so
+    /// like [`Self::estimated_enc_memory()`], this does not depend on the
+    /// number of scale bits.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::model::{Model, ModelType};
+    ///
+    /// let model = Model::empty(ModelType::Acids);
+    /// assert!(model.estimated_dec_memory().get() > 0);
+    /// ```
+    #[must_use]
+    pub fn estimated_dec_memory(&self) -> ByteNum {
+        let bytes = match self.model_type {
+            ModelType::Acids => AcidRansDecModel::estimated_memory(self),
+            ModelType::QualityScores => QScoreRansDecModel::estimated_memory(self),
+        };
+
+        ByteNum::new(bytes)
+    }
+
     fn make_identifier(
         model_type: ModelType,
         spec_type: ContextSpecType,
+        symbol_num: usize,
         contexts: &Vec<Context>,
         map: &HashMap<ContextSpec, usize>,
     ) -> ModelIdentifier {
@@ -465,6 +672,11 @@ impl Model {
 
         hasher.write_u8(model_type as u8).unwrap();
         hasher.update(spec_type.name().as_bytes());
+        // Only hashed when non-default, so that the identifiers of the
+        // existing models do not change
+        if symbol_num != model_type.symbols_num() {
+            hasher.write_u32::<BigEndian>(symbol_num as u32).unwrap();
+        }
 
         for context in contexts {
             for &prob in &context.symbol_prob {
@@ -482,6 +694,45 @@ impl Model {
     }
 }
 
+/// Error returned when the identifier stored with a [`Model`] does not match
+/// its contents.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ModelIdentifierMismatchError {
+    stored: ModelIdentifier,
+    computed: ModelIdentifier,
+}
+
+impl ModelIdentifierMismatchError {
+    #[must_use]
+    fn new(stored: ModelIdentifier, computed: ModelIdentifier) -> Self {
+        Self { stored, computed }
+    }
+
+    /// Returns the identifier stored with the model.
+    #[must_use]
+    pub fn stored(&self) -> &ModelIdentifier {
+        &self.stored
+    }
+
+    /// Returns the identifier computed from the model contents.
+    #[must_use]
+    pub fn computed(&self) -> &ModelIdentifier {
+        &self.computed
+    }
+}
+
+impl Display for ModelIdentifierMismatchError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Model identifier mismatch: stored {}, computed {}",
+            self.stored, self.computed
+        )
+    }
+}
+
+impl Error for ModelIdentifierMismatchError {}
+
 #[cfg(test)]
 mod tests {
     use std::collections::HashMap;
@@ -492,7 +743,9 @@ mod tests {
     use crate::context::Context;
     use crate::context_binning::ComplexContext;
     use crate::context_spec::{ContextSpec, ContextSpecType, GenericContextSpec};
+    use crate::fastq::FastqQualityScore;
     use crate::model::{CompressionRate, Model, ModelIdentifier, ModelType};
+    use crate::quality_alphabet::QualityAlphabet;
     use crate::sequence::Acid;
 
     #[test]
@@ -507,6 +760,53 @@ mod tests {
         assert_eq!(model.rate(), CompressionRate::ZERO);
     }
 
+    #[test]
+    fn test_estimated_memory() {
+        let empty_model = Model::empty(ModelType::QualityScores);
+        let q_score_model = &*SIMPLE_Q_SCORE_MODEL;
+
+        assert!(q_score_model.estimated_enc_memory() > empty_model.estimated_enc_memory());
+        assert!(q_score_model.estimated_dec_memory() > empty_model.estimated_dec_memory());
+    }
+
+    #[test]
+    fn test_with_quality_alphabet() {
+        let alphabet = QualityAlphabet::new([2, 12, 23, 37].map(FastqQualityScore::new));
+        let q_score_model = &*SIMPLE_Q_SCORE_MODEL;
+        let reduced_model = q_score_model.with_quality_alphabet(&alphabet);
+
+        assert_eq!(reduced_model.symbol_num(), 4);
+        assert_eq!(reduced_model.len(), q_score_model.len());
+        assert_eq!(reduced_model.map(), q_score_model.map());
+        assert_ne!(reduced_model.identifier(), q_score_model.identifier());
+        assert!(reduced_model.estimated_enc_memory() < q_score_model.estimated_enc_memory());
+        assert!(reduced_model.estimated_dec_memory() < q_score_model.estimated_dec_memory());
+        for (context, reduced_context) in q_score_model
+            .contexts()
+            .iter()
+            .zip(reduced_model.contexts())
+        {
+            let total: f32 = context.symbol_prob.iter().map(|prob| prob.get()).sum();
+            let reduced_total: f32 = reduced_context
+                .symbol_prob
+                .iter()
+                .map(|prob| prob.get())
+                .sum();
+            assert!((total - reduced_total).abs() < 1e-4);
+        }
+
+        let empty_model = Model::empty(ModelType::QualityScores);
+        let reduced_empty_model = empty_model.with_quality_alphabet(&alphabet);
+        assert_eq!(reduced_empty_model.symbol_num(), 4);
+        assert_ne!(reduced_empty_model.identifier(), empty_model.identifier());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_with_quality_alphabet_acid_model() {
+        let _model = Model::empty(ModelType::Acids).with_quality_alphabet(&QualityAlphabet::full());
+    }
+
     #[test]
     fn test_new_model() {
         let ctx1 = Context::new_from(0.25, [0.80, 0.10, 0.05, 0.05, 0.00]);
@@ -585,6 +885,21 @@ mod tests {
         assert!(models.iter().map(|model| model.identifier()).all_unique());
     }
 
+    #[test]
+    fn test_verify_identifier() {
+        assert!(SIMPLE_ACID_MODEL.verify_identifier().is_ok());
+        assert!(SIMPLE_Q_SCORE_MODEL.verify_identifier().is_ok());
+
+        let stored = ModelIdentifier::new([1; 32]);
+        let model = SIMPLE_ACID_MODEL
+            .clone()
+            .with_stored_identifier(stored.clone());
+        let error = model.verify_identifier().unwrap_err();
+
+        assert_eq!(error.stored(), &stored);
+        assert_eq!(error.computed(), SIMPLE_ACID_MODEL.identifier());
+    }
+
     #[test]
     fn test_compression_rate_display() {
         assert_eq!(format!("{}", CompressionRate::new(0.0)), "0.0000bpv");
@@ -69,11 +69,24 @@ pub struct SerializableModel {
     model_type: ModelType,
     context_spec_type: ContextSpecType,
     contexts: Vec<SerializableComplexContext>,
+    /// The number of symbols, stored only for the quality score models
+    /// adapted to a [`QualityAlphabet`](crate::quality_alphabet::QualityAlphabet).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    symbol_num: Option<usize>,
 }
 
 impl SerializableModel {
     /// Reads a [`Model`] instance using given [`Read`] object.
     ///
+    /// The identifier stored in the file is verified against the model
+    /// contents with [`Model::verify_identifier`]; see
+    /// [`Self::read_model_unchecked`] for a version that keeps it as-is.
+    ///
+    /// # Errors
+    /// Returns [`Error::ModelIdentifierMismatch`](crate::Error::ModelIdentifierMismatch)
+    /// if the stored identifier does not match the model contents, which means
+    /// the model file has been corrupted or edited by hand.
+    ///
     /// # Examples
     /// ```
     /// use idencomp::model::{Model, ModelType};
@@ -85,9 +98,32 @@ impl SerializableModel {
     /// let loaded_model = SerializableModel::read_model(buf.as_slice())?;
     /// assert_eq!(model, loaded_model);
     ///
-    /// # Ok::<(), anyhow::Error>(())
+    /// # Ok::<(), idencomp::Error>(())
+    /// ```
+    pub fn read_model<R: Read>(reader: R) -> crate::Result<Model> {
+        let model = Self::read_model_unchecked(reader)?;
+        model.verify_identifier()?;
+        Ok(model)
+    }
+
+    /// Reads a [`Model`] instance using given [`Read`] object, keeping the
+    /// identifier stored in the file as-is, without checking it against the
+    /// model contents.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::model::{Model, ModelType};
+    /// use idencomp::model_serializer::SerializableModel;
+    ///
+    /// let model = Model::empty(ModelType::Acids);
+    /// let mut buf = Vec::new();
+    /// SerializableModel::write_model(&model, &mut buf)?;
+    /// let loaded_model = SerializableModel::read_model_unchecked(buf.as_slice())?;
+    /// assert_eq!(model, loaded_model);
+    ///
+    /// # Ok::<(), idencomp::Error>(())
     /// ```
-    pub fn read_model<R: Read>(reader: R) -> anyhow::Result<Model> {
+    pub fn read_model_unchecked<R: Read>(reader: R) -> crate::Result<Model> {
         let result = Self::read(reader)?;
         Ok(result.into())
     }
@@ -106,9 +142,9 @@ impl SerializableModel {
     /// let loaded_model = SerializableModel::read(buf.as_slice())?;
     /// assert_eq!(model, Model::from(loaded_model));
     ///
-    /// # Ok::<(), anyhow::Error>(())
+    /// # Ok::<(), idencomp::Error>(())
     /// ```
-    pub fn read<R: Read>(reader: R) -> anyhow::Result<Self> {
+    pub fn read<R: Read>(reader: R) -> crate::Result<Self> {
         let result = rmp_serde::from_read(reader)?;
         Ok(result)
     }
@@ -126,9 +162,9 @@ impl SerializableModel {
     /// let loaded_model = SerializableModel::read_model(buf.as_slice())?;
     /// assert_eq!(model, loaded_model);
     ///
-    /// # Ok::<(), anyhow::Error>(())
+    /// # Ok::<(), idencomp::Error>(())
     /// ```
-    pub fn write_model<W: Write>(model: &Model, mut writer: W) -> anyhow::Result<()> {
+    pub fn write_model<W: Write>(model: &Model, mut writer: W) -> crate::Result<()> {
         Self::from(model).write(&mut writer)?;
         writer.flush()?;
         Ok(())
@@ -148,9 +184,9 @@ impl SerializableModel {
     /// let loaded_model = SerializableModel::read(buf.as_slice())?;
     /// assert_eq!(model, Model::from(loaded_model));
     ///
-    /// # Ok::<(), anyhow::Error>(())
+    /// # Ok::<(), idencomp::Error>(())
     /// ```
-    pub fn write<W: Write>(&self, mut writer: W) -> anyhow::Result<()> {
+    pub fn write<W: Write>(&self, mut writer: W) -> crate::Result<()> {
         self.serialize(&mut rmp_serde::Serializer::new(&mut writer))?;
         writer.flush()?;
         Ok(())
@@ -170,6 +206,8 @@ impl From<&Model> for SerializableModel {
                 .cloned()
                 .map_into()
                 .collect(),
+            symbol_num: (model.symbol_num() != model.model_type().symbols_num())
+                .then(|| model.symbol_num()),
         }
     }
 }
@@ -177,14 +215,15 @@ impl From<&Model> for SerializableModel {
 impl From<SerializableModel> for Model {
     fn from(ser_model: SerializableModel) -> Self {
         let contexts: Vec<ComplexContext> = ser_model.contexts.into_iter().map_into().collect();
-        let model = Model::with_model_and_spec_type(
+        Model::with_symbol_num(
             ser_model.model_type,
             ser_model.context_spec_type,
+            ser_model
+                .symbol_num
+                .unwrap_or_else(|| ser_model.model_type.symbols_num()),
             contexts,
-        );
-
-        assert_eq!(model.identifier(), &ser_model.identifier);
-        model
+        )
+        .with_stored_identifier(ser_model.identifier)
     }
 }
 
@@ -194,9 +233,12 @@ mod tests {
     use crate::context::Context;
     use crate::context_binning::ComplexContext;
     use crate::context_spec::{ContextSpec, ContextSpecType, GenericContextSpec};
-    use crate::model::{Model, ModelType};
+    use crate::fastq::FastqQualityScore;
+    use crate::model::{Model, ModelIdentifier, ModelType};
     use crate::model_serializer::SerializableModel;
+    use crate::quality_alphabet::QualityAlphabet;
     use crate::sequence::Acid;
+    use crate::Error;
 
     #[test]
     fn test_model_to_serializable() {
@@ -228,6 +270,19 @@ mod tests {
         assert_eq!(model, model_2);
     }
 
+    #[test]
+    fn test_write_and_read_model_with_quality_alphabet() {
+        let alphabet = QualityAlphabet::new([2, 12, 23, 37].map(FastqQualityScore::new));
+        let mut data = Vec::new();
+        let model = Model::empty(ModelType::QualityScores).with_quality_alphabet(&alphabet);
+
+        SerializableModel::write_model(&model, &mut data).unwrap();
+        let model_2 = SerializableModel::read_model(data.as_slice()).unwrap();
+
+        assert_eq!(model, model_2);
+        assert_eq!(model_2.symbol_num(), 4);
+    }
+
     #[test]
     fn test_write_and_read_model() {
         let mut data = Vec::new();
@@ -238,4 +293,23 @@ mod tests {
 
         assert_eq!(model, model_2);
     }
+
+    #[test]
+    fn test_read_model_with_invalid_identifier() {
+        let mut serializable_model = SerializableModel::from(&*SIMPLE_ACID_MODEL);
+        serializable_model.identifier = ModelIdentifier::new([1; 32]);
+        let mut data = Vec::new();
+        serializable_model.write(&mut data).unwrap();
+
+        let model = SerializableModel::read_model_unchecked(data.as_slice()).unwrap();
+
+        let error = model.verify_identifier().unwrap_err();
+        assert_eq!(error.computed(), SIMPLE_ACID_MODEL.identifier());
+
+        let result = SerializableModel::read_model(data.as_slice());
+        assert!(matches!(
+            result,
+            Err(Error::ModelIdentifierMismatch(e)) if e.computed() == SIMPLE_ACID_MODEL.identifier()
+        ));
+    }
 }
@@ -0,0 +1,139 @@
+//! Parallel iterator traits used across the crate.
+//!
+//! With the `parallel` feature enabled, these are simply re-exported from
+//! `rayon`. Otherwise, they are replaced with sequential shims that provide the
+//! same method names on top of the standard iterators, so that the crate can
+//! be compiled for targets without thread support (e.g.
+//! `wasm32-unknown-unknown`).
+
+#[cfg(feature = "parallel")]
+pub(crate) use rayon::prelude::{
+    IndexedParallelIterator, IntoParallelIterator, IntoParallelRefIterator, ParallelIterator,
+    ParallelSliceMut,
+};
+#[cfg(not(feature = "parallel"))]
+pub(crate) use sequential::*;
+
+#[cfg(not(feature = "parallel"))]
+mod sequential {
+    use std::cmp::Ordering;
+
+    pub(crate) use std::iter::Iterator as ParallelIterator;
+
+    pub(crate) trait IntoParallelIterator {
+        type Iter: Iterator;
+
+        fn into_par_iter(self) -> Self::Iter;
+    }
+
+    impl<T: IntoIterator> IntoParallelIterator for T {
+        type Iter = T::IntoIter;
+
+        fn into_par_iter(self) -> Self::Iter {
+            self.into_iter()
+        }
+    }
+
+    pub(crate) trait IntoParallelRefIterator<'a> {
+        type Iter: Iterator;
+
+        fn par_iter(&'a self) -> Self::Iter;
+    }
+
+    impl<'a, T: 'a + ?Sized> IntoParallelRefIterator<'a> for T
+    where
+        &'a T: IntoIterator,
+    {
+        type Iter = <&'a T as IntoIterator>::IntoIter;
+
+        fn par_iter(&'a self) -> Self::Iter {
+            self.into_iter()
+        }
+    }
+
+    pub(crate) trait IndexedParallelIterator: Iterator {
+        fn collect_into_vec(self, target: &mut Vec<Self::Item>)
+        where
+            Self: Sized,
+        {
+            target.clear();
+            target.extend(self);
+        }
+    }
+
+    impl<I: Iterator> IndexedParallelIterator for I {}
+
+    pub(crate) trait ParallelSliceMut<T> {
+        fn par_sort_unstable_by<F>(&mut self, compare: F)
+        where
+            F: FnMut(&T, &T) -> Ordering;
+    }
+
+    impl<T> ParallelSliceMut<T> for [T] {
+        fn par_sort_unstable_by<F>(&mut self, compare: F)
+        where
+            F: FnMut(&T, &T) -> Ordering,
+        {
+            self.sort_unstable_by(compare);
+        }
+    }
+}
+
+/// A dedicated pool running the parallel iterators, so that the work of a
+/// compressor uses the number of threads it has been configured with instead
+/// of the global pool shared with the rest of the process.
+#[cfg(feature = "parallel")]
+#[derive(Debug)]
+pub(crate) struct WorkerPool(rayon::ThreadPool);
+
+#[cfg(feature = "parallel")]
+impl WorkerPool {
+    /// Creates a pool of named worker threads. Returns `None` if there should
+    /// be no threads, or they could not be spawned, in which case the work
+    /// should be done in the current thread.
+    #[must_use]
+    pub(crate) fn new(thread_num: usize, thread_name: &str) -> Option<Self> {
+        if thread_num == 0 {
+            return None;
+        }
+
+        let thread_name = thread_name.to_owned();
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(thread_num)
+            .thread_name(move |index| format!("{}-{}", thread_name, index))
+            .build()
+            .ok()
+            .map(Self)
+    }
+
+    /// Runs given function in the pool, so that the parallel iterators used
+    /// inside of it are run by the threads of the pool.
+    pub(crate) fn install<R, F>(&self, op: F) -> R
+    where
+        R: Send,
+        F: FnOnce() -> R + Send,
+    {
+        self.0.install(op)
+    }
+}
+
+/// Without the `parallel` feature, the pool is never created, so the work is
+/// always done in the current thread.
+#[cfg(not(feature = "parallel"))]
+#[derive(Debug)]
+pub(crate) enum WorkerPool {}
+
+#[cfg(not(feature = "parallel"))]
+impl WorkerPool {
+    #[must_use]
+    pub(crate) fn new(_thread_num: usize, _thread_name: &str) -> Option<Self> {
+        None
+    }
+
+    pub(crate) fn install<R, F>(&self, _op: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        match *self {}
+    }
+}
@@ -1,5 +1,9 @@
-use std::fmt::Debug;
+use std::collections::VecDeque;
+use std::fmt::{Debug, Display, Formatter};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
+use crossbeam_channel::{Receiver, Sender};
 use derive_more::{Add, AddAssign};
 
 /// An integer number of bytes.
@@ -42,12 +46,55 @@ impl ByteNum {
     }
 }
 
+/// A stage of the compression or decompression process.
+///
+/// The stages are reported to [`ProgressNotifier::start_stage()`] in the order
+/// they are entered. Not every operation goes through all of them, e.g. the
+/// decompression never reports [`Self::Parsing`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum ProgressStage {
+    /// Reading the input sequences until there is enough data to choose the
+    /// models.
+    Parsing,
+    /// Converting the models into a form that can be used by the compressor or
+    /// decompressor. The progress is measured in models.
+    ModelPreprocessing,
+    /// Compressing the blocks of sequences. The progress is measured in blocks.
+    Compressing,
+    /// Decompressing the blocks of sequences. The progress is measured in
+    /// blocks.
+    Decompressing,
+    /// Compressing and writing the blocks remaining after all the input has
+    /// been read. The progress is measured in blocks.
+    Flushing,
+}
+
+impl Display for ProgressStage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ProgressStage::Parsing => "parsing",
+            ProgressStage::ModelPreprocessing => "model preprocessing",
+            ProgressStage::Compressing => "compressing",
+            ProgressStage::Decompressing => "decompressing",
+            ProgressStage::Flushing => "flushing",
+        };
+        write!(f, "{}", name)
+    }
+}
+
 /// An object that can track progress of any long-running operation. This can be
 /// implemented e.g. as a progress bar in CLI/GUI.
 pub trait ProgressNotifier: Debug + Send + Sync {
     /// Indicates that the specified number of bytes has been processed.
     fn processed_bytes(&self, bytes: ByteNum);
 
+    /// Indicates that there will be specified number of bytes to be processed
+    /// in total, so that the ETA can be calculated. The default implementation
+    /// does nothing.
+    fn set_total_bytes(&self, _bytes: ByteNum) {
+        // do nothing
+    }
+
     /// Indicates that there will be specified number of items to be processed
     /// (so that the maximum value for a progress bar can be set).
     fn set_iter_num(&self, num_iter: u64);
@@ -55,6 +102,19 @@ pub trait ProgressNotifier: Debug + Send + Sync {
     /// Indicates that a single item has been processed. This is usually used
     /// after calling [`Self::set_iter_num()`].
     fn inc_iter(&self);
+
+    /// Indicates that a new stage of the operation has started, with given
+    /// total number of units to be processed in it, if known. The default
+    /// implementation does nothing.
+    fn start_stage(&self, _stage: ProgressStage, _total: Option<u64>) {
+        // do nothing
+    }
+
+    /// Indicates that given number of units of the current stage has been
+    /// processed. The default implementation does nothing.
+    fn advance_stage(&self, _units: u64) {
+        // do nothing
+    }
 }
 
 impl<T: ProgressNotifier> ProgressNotifier for &T {
@@ -62,6 +122,10 @@ impl<T: ProgressNotifier> ProgressNotifier for &T {
         T::processed_bytes(self, bytes)
     }
 
+    fn set_total_bytes(&self, bytes: ByteNum) {
+        T::set_total_bytes(self, bytes)
+    }
+
     fn set_iter_num(&self, num_iter: u64) {
         T::set_iter_num(self, num_iter)
     }
@@ -69,6 +133,14 @@ impl<T: ProgressNotifier> ProgressNotifier for &T {
     fn inc_iter(&self) {
         T::inc_iter(self)
     }
+
+    fn start_stage(&self, stage: ProgressStage, total: Option<u64>) {
+        T::start_stage(self, stage, total)
+    }
+
+    fn advance_stage(&self, units: u64) {
+        T::advance_stage(self, units)
+    }
 }
 
 /// A no-operation implementation of [`ProgressNotifier`].
@@ -89,9 +161,358 @@ impl ProgressNotifier for DummyProgressNotifier {
     }
 }
 
+/// The default number of bytes [`BatchedProgress`] accumulates before passing
+/// them to the [`ProgressNotifier`].
+pub(crate) const DEFAULT_PROGRESS_GRANULARITY: usize = 64 * 1024;
+
+/// Accumulates the numbers of bytes processed and passes them to a
+/// [`ProgressNotifier`] once there are at least `granularity` of them, so that
+/// the notifier (which is shared between all the threads) is not called for
+/// every single sequence. The remaining bytes are reported when the object is
+/// dropped.
+#[derive(Debug)]
+pub(crate) struct BatchedProgress {
+    notifier: Arc<dyn ProgressNotifier>,
+    granularity: usize,
+    pending: usize,
+}
+
+impl BatchedProgress {
+    #[must_use]
+    pub fn new(notifier: Arc<dyn ProgressNotifier>, granularity: usize) -> Self {
+        Self {
+            notifier,
+            granularity,
+            pending: 0,
+        }
+    }
+
+    pub fn processed_bytes(&mut self, bytes: ByteNum) {
+        self.pending += bytes.get();
+        if self.pending >= self.granularity {
+            self.flush();
+        }
+    }
+
+    /// Reports all the bytes accumulated so far.
+    pub fn flush(&mut self) {
+        if self.pending > 0 {
+            self.notifier.processed_bytes(ByteNum::new(self.pending));
+            self.pending = 0;
+        }
+    }
+}
+
+impl Drop for BatchedProgress {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+/// An event emitted by [`ProgressEvents`]. Each variant corresponds to a
+/// single method of [`ProgressNotifier`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ProgressEvent {
+    /// See [`ProgressNotifier::processed_bytes()`].
+    ProcessedBytes(ByteNum),
+    /// See [`ProgressNotifier::set_total_bytes()`].
+    TotalBytes(ByteNum),
+    /// See [`ProgressNotifier::set_iter_num()`].
+    IterNum(u64),
+    /// See [`ProgressNotifier::inc_iter()`].
+    IterDone,
+    /// See [`ProgressNotifier::start_stage()`].
+    StageStarted {
+        /// The stage that has been started.
+        stage: ProgressStage,
+        /// Total number of units to be processed in the stage, if known.
+        total: Option<u64>,
+    },
+    /// See [`ProgressNotifier::advance_stage()`]. When compressing or
+    /// decompressing, this is emitted once per each processed block.
+    StageAdvanced(u64),
+}
+
+/// An implementation of [`ProgressNotifier`] that sends all the notifications
+/// as [`ProgressEvent`]s to channels, so they can be consumed asynchronously,
+/// e.g. by a GUI thread.
+///
+/// Each receiver created with [`Self::subscribe()`] gets all the events sent
+/// after it has been created. Receivers can be dropped at any time.
+///
+/// # Examples
+/// ```
+/// use std::sync::Arc;
+///
+/// use idencomp::idn::compressor::{IdnCompressor, IdnCompressorParams};
+/// use idencomp::progress::{ProgressEvent, ProgressEvents, ProgressStage};
+///
+/// let events = Arc::new(ProgressEvents::new());
+/// let receiver = events.subscribe();
+///
+/// let mut data = Vec::new();
+/// let params = IdnCompressorParams::builder()
+///     .progress_notifier(events)
+///     .build()?;
+/// let compressor = IdnCompressor::with_params(&mut data, params);
+/// compressor.finish()?;
+///
+/// assert_eq!(
+///     receiver.recv().unwrap(),
+///     ProgressEvent::StageStarted {
+///         stage: ProgressStage::Parsing,
+///         total: None,
+///     }
+/// );
+/// # Ok::<(), idencomp::Error>(())
+/// ```
+#[derive(Debug, Default)]
+pub struct ProgressEvents {
+    senders: Mutex<Vec<Sender<ProgressEvent>>>,
+}
+
+impl ProgressEvents {
+    /// Creates a new `ProgressEvents` instance with no subscribers.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::progress::ProgressEvents;
+    ///
+    /// let events = ProgressEvents::new();
+    /// ```
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new unbounded channel that will receive all the events
+    /// emitted from now on and returns its receiving end.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::progress::{ByteNum, ProgressEvent, ProgressEvents, ProgressNotifier};
+    ///
+    /// let events = ProgressEvents::new();
+    /// let receiver = events.subscribe();
+    /// events.processed_bytes(ByteNum::new(10));
+    /// assert_eq!(
+    ///     receiver.try_recv().unwrap(),
+    ///     ProgressEvent::ProcessedBytes(ByteNum::new(10))
+    /// );
+    /// ```
+    #[must_use]
+    pub fn subscribe(&self) -> Receiver<ProgressEvent> {
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        self.senders
+            .lock()
+            .expect("Could not acquire the senders lock")
+            .push(sender);
+        receiver
+    }
+
+    fn send(&self, event: ProgressEvent) {
+        let mut senders = self
+            .senders
+            .lock()
+            .expect("Could not acquire the senders lock");
+        // Sending fails only if the receiver has been dropped
+        senders.retain(|sender| sender.send(event).is_ok());
+    }
+}
+
+impl ProgressNotifier for ProgressEvents {
+    fn processed_bytes(&self, bytes: ByteNum) {
+        self.send(ProgressEvent::ProcessedBytes(bytes));
+    }
+
+    fn set_total_bytes(&self, bytes: ByteNum) {
+        self.send(ProgressEvent::TotalBytes(bytes));
+    }
+
+    fn set_iter_num(&self, num_iter: u64) {
+        self.send(ProgressEvent::IterNum(num_iter));
+    }
+
+    fn inc_iter(&self) {
+        self.send(ProgressEvent::IterDone);
+    }
+
+    fn start_stage(&self, stage: ProgressStage, total: Option<u64>) {
+        self.send(ProgressEvent::StageStarted { stage, total });
+    }
+
+    fn advance_stage(&self, units: u64) {
+        self.send(ProgressEvent::StageAdvanced(units));
+    }
+}
+
+/// The time span used to calculate the instantaneous throughput.
+const THROUGHPUT_WINDOW: Duration = Duration::from_secs(5);
+
+/// Tracks the progress of a long-running operation and calculates its
+/// throughput and estimated time of arrival. This can be used to implement
+/// [`ProgressNotifier`].
+///
+/// The progress is measured in arbitrary units, such as bytes or processed
+/// items.
+///
+/// # Examples
+/// ```
+/// use idencomp::progress::ProgressTracker;
+///
+/// let mut tracker = ProgressTracker::new();
+/// tracker.set_total(Some(1000));
+/// tracker.add(250);
+///
+/// let snapshot = tracker.snapshot();
+/// assert_eq!(snapshot.processed(), 250);
+/// assert_eq!(snapshot.total(), Some(1000));
+/// assert_eq!(snapshot.fraction(), Some(0.25));
+/// ```
+#[derive(Debug, Clone)]
+pub struct ProgressTracker {
+    start_time: Instant,
+    processed: u64,
+    total: Option<u64>,
+    samples: VecDeque<(Instant, u64)>,
+}
+
+impl ProgressTracker {
+    /// Creates a new `ProgressTracker` instance with the start time set to now.
+    #[must_use]
+    pub fn new() -> Self {
+        let start_time = Instant::now();
+
+        Self {
+            start_time,
+            processed: 0,
+            total: None,
+            samples: VecDeque::from([(start_time, 0)]),
+        }
+    }
+
+    /// Sets the total number of units to be processed, or `None` if it is
+    /// unknown.
+    pub fn set_total(&mut self, total: Option<u64>) {
+        self.total = total;
+    }
+
+    /// Indicates that given number of units has been processed.
+    pub fn add(&mut self, value: u64) {
+        self.add_at(value, Instant::now());
+    }
+
+    fn add_at(&mut self, value: u64, now: Instant) {
+        self.processed += value;
+        self.samples.push_back((now, self.processed));
+
+        while self.samples.len() > 2 && now - self.samples[1].0 >= THROUGHPUT_WINDOW {
+            self.samples.pop_front();
+        }
+    }
+
+    /// Returns the current state of the progress.
+    #[must_use]
+    pub fn snapshot(&self) -> ProgressSnapshot {
+        self.snapshot_at(Instant::now())
+    }
+
+    fn snapshot_at(&self, now: Instant) -> ProgressSnapshot {
+        let (window_start, window_processed) = self.samples[0];
+        let window_time = (now - window_start).as_secs_f64();
+        let per_sec = if window_time > 0.0 {
+            (self.processed - window_processed) as f64 / window_time
+        } else {
+            0.0
+        };
+
+        let eta = match self.total {
+            Some(total) if per_sec > 0.0 => {
+                let remaining = total.saturating_sub(self.processed);
+                Some(Duration::from_secs_f64(remaining as f64 / per_sec))
+            }
+            _ => None,
+        };
+
+        ProgressSnapshot {
+            processed: self.processed,
+            total: self.total,
+            elapsed: now - self.start_time,
+            per_sec,
+            eta,
+        }
+    }
+}
+
+impl Default for ProgressTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The state of the progress at some point in time, as returned by
+/// [`ProgressTracker::snapshot()`].
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ProgressSnapshot {
+    processed: u64,
+    total: Option<u64>,
+    elapsed: Duration,
+    per_sec: f64,
+    eta: Option<Duration>,
+}
+
+impl ProgressSnapshot {
+    /// Returns the number of units processed so far.
+    #[must_use]
+    pub fn processed(&self) -> u64 {
+        self.processed
+    }
+
+    /// Returns the total number of units to process, if known.
+    #[must_use]
+    pub fn total(&self) -> Option<u64> {
+        self.total
+    }
+
+    /// Returns the fraction of the work done (between `0.0` and `1.0`), if the
+    /// total number of units is known.
+    #[must_use]
+    pub fn fraction(&self) -> Option<f64> {
+        self.total
+            .filter(|&total| total > 0)
+            .map(|total| (self.processed as f64 / total as f64).min(1.0))
+    }
+
+    /// Returns the time elapsed since the start of the operation.
+    #[must_use]
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Returns the instantaneous throughput, in units per second, calculated
+    /// over the last few seconds.
+    #[must_use]
+    pub fn per_sec(&self) -> f64 {
+        self.per_sec
+    }
+
+    /// Returns the estimated time remaining until the end of the operation, if
+    /// the total number of units is known.
+    #[must_use]
+    pub fn eta(&self) -> Option<Duration> {
+        self.eta
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::progress::{ByteNum, DummyProgressNotifier, ProgressNotifier};
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    use crate::progress::{
+        BatchedProgress, ByteNum, DummyProgressNotifier, ProgressEvent, ProgressEvents,
+        ProgressNotifier, ProgressStage, ProgressTracker,
+    };
 
     #[test]
     fn test_dummy_progress_notifier() {
@@ -100,4 +521,83 @@ mod tests {
         let notifier_2 = notifier;
         notifier_2.processed_bytes(ByteNum::new(666));
     }
+
+    #[test]
+    fn test_progress_events() {
+        let events = ProgressEvents::new();
+        let receiver_1 = events.subscribe();
+        events.start_stage(ProgressStage::ModelPreprocessing, Some(2));
+        let receiver_2 = events.subscribe();
+        events.advance_stage(1);
+        drop(receiver_1);
+        events.inc_iter();
+
+        assert_eq!(
+            receiver_2.try_iter().collect::<Vec<_>>(),
+            [ProgressEvent::StageAdvanced(1), ProgressEvent::IterDone]
+        );
+        assert_eq!(events.senders.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_batched_progress() {
+        let events = Arc::new(ProgressEvents::new());
+        let receiver = events.subscribe();
+        let mut progress = BatchedProgress::new(events, 100);
+
+        progress.processed_bytes(ByteNum::new(60));
+        assert!(receiver.try_recv().is_err());
+        progress.processed_bytes(ByteNum::new(60));
+        assert_eq!(
+            receiver.try_recv().unwrap(),
+            ProgressEvent::ProcessedBytes(ByteNum::new(120))
+        );
+        progress.processed_bytes(ByteNum::new(10));
+        drop(progress);
+
+        assert_eq!(
+            receiver.try_iter().collect::<Vec<_>>(),
+            [ProgressEvent::ProcessedBytes(ByteNum::new(10))]
+        );
+    }
+
+    #[test]
+    fn test_progress_tracker_throughput() {
+        let mut tracker = ProgressTracker::new();
+        let start = tracker.start_time;
+        tracker.set_total(Some(1000));
+        tracker.add_at(100, start + Duration::from_secs(1));
+        tracker.add_at(100, start + Duration::from_secs(2));
+
+        let snapshot = tracker.snapshot_at(start + Duration::from_secs(2));
+        assert_eq!(snapshot.processed(), 200);
+        assert_eq!(snapshot.elapsed(), Duration::from_secs(2));
+        assert_eq!(snapshot.per_sec(), 100.0);
+        assert_eq!(snapshot.eta(), Some(Duration::from_secs(8)));
+    }
+
+    #[test]
+    fn test_progress_tracker_window() {
+        let mut tracker = ProgressTracker::new();
+        let start = tracker.start_time;
+        tracker.add_at(1000, start + Duration::from_secs(1));
+        for i in 2..=20 {
+            tracker.add_at(10, start + Duration::from_secs(i));
+        }
+
+        // Only the last few seconds are taken into account
+        let snapshot = tracker.snapshot_at(start + Duration::from_secs(20));
+        assert_eq!(snapshot.per_sec(), 10.0);
+        assert_eq!(snapshot.eta(), None);
+    }
+
+    #[test]
+    fn test_progress_tracker_empty() {
+        let tracker = ProgressTracker::new();
+
+        let snapshot = tracker.snapshot_at(Instant::now());
+        assert_eq!(snapshot.processed(), 0);
+        assert_eq!(snapshot.fraction(), None);
+        assert_eq!(snapshot.eta(), None);
+    }
 }
@@ -0,0 +1,202 @@
+use std::fmt::{Display, Formatter};
+
+use crate::fastq::{FastqQualityScore, FASTQ_Q_END};
+use crate::sequence::InvalidQualityScoreError;
+
+/// Set of the quality score values that can appear in the sequences of a
+/// file.
+///
+/// The quality score models encode the index of a value in the alphabet, not
+/// the value itself, so the data that only contains a few distinct quality
+/// scores (e.g. after [quality binning](crate::idn::transform::QualityBinning))
+/// can be compressed with the models that have proportionally fewer symbols,
+/// and hence take less memory and compress better. The default alphabet
+/// contains all the quality scores possible in the FASTQ format.
+///
+/// # Examples
+/// ```
+/// use idencomp::fastq::FastqQualityScore;
+/// use idencomp::quality_alphabet::QualityAlphabet;
+///
+/// let alphabet = QualityAlphabet::new([2, 12, 23, 37].map(FastqQualityScore::new));
+/// assert_eq!(alphabet.symbol_num(), 4);
+/// assert_eq!(alphabet.index_of(FastqQualityScore::new(23)), Some(2));
+/// assert_eq!(alphabet.index_of(FastqQualityScore::new(24)), None);
+/// assert_eq!(alphabet.get(3), Some(FastqQualityScore::new(37)));
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct QualityAlphabet {
+    values: Vec<FastqQualityScore>,
+    /// The index of each of the quality score values, or [`Self::NO_INDEX`]
+    /// if the value is not in the alphabet.
+    indices: [u8; FASTQ_Q_END],
+}
+
+impl QualityAlphabet {
+    const NO_INDEX: u8 = u8::MAX;
+
+    /// Creates a new `QualityAlphabet` instance consisting of given quality
+    /// score values. The values are sorted and the duplicates are removed.
+    ///
+    /// # Panics
+    /// This function panics if `values` is empty.
+    #[must_use]
+    pub fn new<T: IntoIterator<Item = FastqQualityScore>>(values: T) -> Self {
+        let mut values: Vec<_> = values.into_iter().collect();
+        values.sort();
+        values.dedup();
+        assert!(!values.is_empty(), "Quality alphabet cannot be empty");
+
+        let mut indices = [Self::NO_INDEX; FASTQ_Q_END];
+        for (index, value) in values.iter().enumerate() {
+            indices[value.get()] = index as u8;
+        }
+
+        Self { values, indices }
+    }
+
+    /// Creates a new `QualityAlphabet` instance containing all the quality
+    /// scores possible in the FASTQ format.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::fastq::{FastqQualityScore, FASTQ_Q_END};
+    /// use idencomp::quality_alphabet::QualityAlphabet;
+    ///
+    /// let alphabet = QualityAlphabet::full();
+    /// assert_eq!(alphabet.symbol_num(), FASTQ_Q_END);
+    /// assert!(alphabet.is_full());
+    /// assert_eq!(alphabet.index_of(FastqQualityScore::new(40)), Some(40));
+    /// ```
+    #[must_use]
+    pub fn full() -> Self {
+        Self::new((0..FASTQ_Q_END as u8).map(FastqQualityScore::new))
+    }
+
+    /// Returns the number of values in the alphabet, which is the number of
+    /// symbols of the quality score models used with it.
+    #[inline]
+    #[must_use]
+    pub fn symbol_num(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns `true` if the alphabet contains all the quality scores possible
+    /// in the FASTQ format.
+    #[inline]
+    #[must_use]
+    pub fn is_full(&self) -> bool {
+        self.values.len() == FASTQ_Q_END
+    }
+
+    /// Returns the quality score values of the alphabet, in ascending order.
+    #[inline]
+    #[must_use]
+    pub fn values(&self) -> &[FastqQualityScore] {
+        &self.values
+    }
+
+    /// Returns the index of given quality score in the alphabet, or `None` if
+    /// the alphabet does not contain it.
+    #[inline]
+    #[must_use]
+    pub fn index_of(&self, q_score: FastqQualityScore) -> Option<usize> {
+        match self.indices[q_score.get()] {
+            Self::NO_INDEX => None,
+            index => Some(index as usize),
+        }
+    }
+
+    /// Returns the quality score with given index in the alphabet, or `None`
+    /// if the index is out of range.
+    #[inline]
+    #[must_use]
+    pub fn get(&self, index: usize) -> Option<FastqQualityScore> {
+        self.values.get(index).copied()
+    }
+
+    /// Returns the quality score with given index in the alphabet, or an error
+    /// if the index is out of range.
+    #[inline]
+    pub(crate) fn try_get(
+        &self,
+        index: usize,
+    ) -> Result<FastqQualityScore, InvalidQualityScoreError> {
+        self.get(index)
+            .ok_or_else(|| InvalidQualityScoreError::new(index as u8, self.symbol_num()))
+    }
+
+    /// Returns the index of the value of the alphabet closest to given quality
+    /// score. The lower value is preferred in case of a tie.
+    #[must_use]
+    pub(crate) fn nearest_index(&self, q_score: FastqQualityScore) -> usize {
+        self.values
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, value)| value.get().abs_diff(q_score.get()))
+            .map(|(index, _)| index)
+            .expect("Quality alphabet cannot be empty")
+    }
+}
+
+impl Default for QualityAlphabet {
+    fn default() -> Self {
+        Self::full()
+    }
+}
+
+impl Display for QualityAlphabet {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (index, value) in self.values.iter().enumerate() {
+            if index > 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{}", value.get())?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fastq::FastqQualityScore;
+    use crate::quality_alphabet::QualityAlphabet;
+
+    #[test]
+    fn test_new_sorts_values() {
+        let alphabet = QualityAlphabet::new([30, 10, 20, 10].map(FastqQualityScore::new));
+
+        assert_eq!(alphabet.values(), [10, 20, 30].map(FastqQualityScore::new));
+        assert_eq!(alphabet.index_of(FastqQualityScore::new(30)), Some(2));
+        assert!(!alphabet.is_full());
+        assert_eq!(alphabet.to_string(), "10,20,30");
+    }
+
+    #[test]
+    #[should_panic(expected = "Quality alphabet cannot be empty")]
+    fn test_new_empty() {
+        let _alphabet = QualityAlphabet::new(Vec::<FastqQualityScore>::new());
+    }
+
+    #[test]
+    fn test_nearest_index() {
+        let alphabet = QualityAlphabet::new([2, 12, 23, 37].map(FastqQualityScore::new));
+
+        let nearest: Vec<_> = [0, 7, 8, 17, 18, 30, 31, 93]
+            .map(FastqQualityScore::new)
+            .into_iter()
+            .map(|q_score| alphabet.nearest_index(q_score))
+            .collect();
+        assert_eq!(nearest, [0, 0, 1, 1, 2, 2, 3, 3]);
+    }
+
+    #[test]
+    fn test_try_get() {
+        let alphabet = QualityAlphabet::new([2, 12].map(FastqQualityScore::new));
+
+        assert_eq!(alphabet.try_get(1), Ok(FastqQualityScore::new(12)));
+        assert_eq!(alphabet.try_get(2).unwrap_err().value(), 2);
+        assert_eq!(QualityAlphabet::default(), QualityAlphabet::full());
+    }
+}
@@ -1,3 +1,4 @@
+use std::error::Error;
 use std::fmt::{Display, Formatter};
 use std::hash::{Hash, Hasher};
 use std::mem;
@@ -105,6 +106,81 @@ impl NucleotideSequenceIdentifier {
     pub fn str(&self) -> &str {
         &self.0
     }
+
+    /// Parses the flow cell coordinates of the read from an Illumina
+    /// identifier. Both the Casava 1.8+ format
+    /// (`instrument:run:flowcell:lane:tile:x:y[:umi] read:filtered:control:index`)
+    /// and the older one (`instrument:lane:tile:x:y[#index][/read]`) are
+    /// supported. Returns `None` if the identifier is in neither of these
+    /// formats.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::sequence::{IlluminaCoordinates, NucleotideSequenceIdentifier};
+    ///
+    /// let identifier =
+    ///     NucleotideSequenceIdentifier::from("EAS139:136:FC706VJ:2:2104:15343:197393 1:Y:18:ATCACG");
+    /// assert_eq!(
+    ///     identifier.illumina_coordinates(),
+    ///     Some(IlluminaCoordinates {
+    ///         lane: 2,
+    ///         tile: 2104,
+    ///         x: 15343,
+    ///         y: 197393
+    ///     })
+    /// );
+    ///
+    /// let identifier = NucleotideSequenceIdentifier::from("HWUSI-EAS100R:6:73:941:1973#0/1");
+    /// assert_eq!(
+    ///     identifier.illumina_coordinates(),
+    ///     Some(IlluminaCoordinates {
+    ///         lane: 6,
+    ///         tile: 73,
+    ///         x: 941,
+    ///         y: 1973
+    ///     })
+    /// );
+    ///
+    /// let identifier = NucleotideSequenceIdentifier::from("SRR1518133.1 1/1");
+    /// assert_eq!(identifier.illumina_coordinates(), None);
+    /// ```
+    #[must_use]
+    pub fn illumina_coordinates(&self) -> Option<IlluminaCoordinates> {
+        let name = self.0.split_whitespace().next()?;
+        let fields: Vec<&str> = name.split(':').collect();
+        let coordinates = match fields.len() {
+            5 => {
+                let y = fields[4].split(['#', '/']).next()?;
+                [fields[1], fields[2], fields[3], y]
+            }
+            7 | 8 => [fields[3], fields[4], fields[5], fields[6]],
+            _ => return None,
+        };
+
+        let mut values = coordinates
+            .into_iter()
+            .map(|value| value.parse::<u32>().ok());
+        Some(IlluminaCoordinates {
+            lane: values.next()??,
+            tile: values.next()??,
+            x: values.next()??,
+            y: values.next()??,
+        })
+    }
+}
+
+/// The location of a cluster on an Illumina flow cell, as stored in the read
+/// identifier.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct IlluminaCoordinates {
+    /// The flow cell lane.
+    pub lane: u32,
+    /// The tile within the lane.
+    pub tile: u32,
+    /// The x coordinate of the cluster within the tile.
+    pub x: u32,
+    /// The y coordinate of the cluster within the tile.
+    pub y: u32,
 }
 
 impl Display for NucleotideSequenceIdentifier {
@@ -133,6 +209,8 @@ pub struct NucleotideSequence<const Q_END: usize> {
     acids: Vec<Acid>,
     quality_scores: Vec<QualityScore<Q_END>>,
     size: ByteNum,
+    aux_data: Vec<u8>,
+    separator_text: Vec<u8>,
 }
 
 impl<const Q_END: usize> NucleotideSequence<Q_END> {
@@ -223,6 +301,8 @@ impl<const Q_END: usize> NucleotideSequence<Q_END> {
             acids,
             quality_scores,
             size,
+            aux_data: Vec::new(),
+            separator_text: Vec::new(),
         }
     }
 
@@ -279,8 +359,86 @@ impl<const Q_END: usize> NucleotideSequence<Q_END> {
         &self.quality_scores
     }
 
+    /// Returns the auxiliary per-read data (e.g. a UMI tag or a barcode call)
+    /// attached to this sequence, which is empty by default.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::sequence::NucleotideSequence;
+    ///
+    /// let seq: NucleotideSequence<20> = NucleotideSequence::new("SEQ_1", [], []);
+    /// assert!(seq.aux_data().is_empty());
+    /// let seq = seq.with_aux_data(*b"UMI:ACGT");
+    /// assert_eq!(seq.aux_data(), b"UMI:ACGT");
+    /// ```
+    #[must_use]
+    pub fn aux_data(&self) -> &[u8] {
+        &self.aux_data
+    }
+
+    /// Returns a new instance of `NucleotideSequence`, identical as `self`, but
+    /// with given auxiliary data attached. See [`Self::aux_data()`].
+    #[must_use]
+    pub fn with_aux_data<T>(mut self, aux_data: T) -> Self
+    where
+        T: Into<Vec<u8>>,
+    {
+        self.aux_data = aux_data.into();
+        self
+    }
+
+    /// Detaches the auxiliary data from this sequence, so that it can be
+    /// attached back with [`Self::with_aux_data()`] after the sequence is
+    /// rebuilt (e.g. by a transform).
+    #[must_use]
+    pub(crate) fn split_aux_data(mut self) -> (Self, Vec<u8>) {
+        let aux_data = mem::take(&mut self.aux_data);
+        (self, aux_data)
+    }
+
+    /// Returns the text following the quality score separator (`+`) in the
+    /// FASTQ representation of this sequence. It is only kept if the sequence
+    /// has been read with
+    /// [`FastqReaderParamsBuilder::preserve_separator()`](crate::fastq::reader::FastqReaderParamsBuilder::preserve_separator),
+    /// and is empty otherwise.
+    ///
+    /// # Examples
+    /// ```
+    /// use idencomp::sequence::NucleotideSequence;
+    ///
+    /// let seq: NucleotideSequence<20> = NucleotideSequence::new("SEQ_1", [], []);
+    /// assert!(seq.separator_text().is_empty());
+    /// let seq = seq.with_separator_text(*b"SEQ_1");
+    /// assert_eq!(seq.separator_text(), b"SEQ_1");
+    /// ```
+    #[must_use]
+    pub fn separator_text(&self) -> &[u8] {
+        &self.separator_text
+    }
+
+    /// Returns a new instance of `NucleotideSequence`, identical as `self`, but
+    /// with given separator text. See [`Self::separator_text()`].
+    #[must_use]
+    pub fn with_separator_text<T>(mut self, separator_text: T) -> Self
+    where
+        T: Into<Vec<u8>>,
+    {
+        self.separator_text = separator_text.into();
+        self
+    }
+
+    /// Detaches the separator text from this sequence, so that it can be
+    /// attached back with [`Self::with_separator_text()`] after the sequence
+    /// is rebuilt (e.g. by a transform).
+    #[must_use]
+    pub(crate) fn split_separator_text(mut self) -> (Self, Vec<u8>) {
+        let separator_text = mem::take(&mut self.separator_text);
+        (self, separator_text)
+    }
+
     /// Returns a new instance of `NucleotideSequence`, identical as `self`, but
-    /// with an empty identifier.
+    /// with an empty identifier. The separator text (which usually repeats the
+    /// identifier) is discarded as well.
     #[must_use]
     pub fn with_identifier_discarded(self) -> Self {
         Self::with_size(
@@ -289,6 +447,7 @@ impl<const Q_END: usize> NucleotideSequence<Q_END> {
             self.quality_scores,
             self.size,
         )
+        .with_aux_data(self.aux_data)
     }
 
     /// Returns a new instance of `NucleotideSequence`, identical as `self`, but
@@ -299,6 +458,8 @@ impl<const Q_END: usize> NucleotideSequence<Q_END> {
         T: Into<NucleotideSequenceIdentifier>,
     {
         Self::new(identifier, self.acids, self.quality_scores)
+            .with_aux_data(self.aux_data)
+            .with_separator_text(self.separator_text)
     }
 
     /// Consumes this sequence and returns a vector of acids and quality scores.
@@ -374,6 +535,12 @@ impl<const Q_END: usize> PartialEq for NucleotideSequence<Q_END> {
         if self.quality_scores != other.quality_scores {
             return false;
         }
+        if self.aux_data != other.aux_data {
+            return false;
+        }
+        if self.separator_text != other.separator_text {
+            return false;
+        }
         true
     }
 }
@@ -447,6 +614,39 @@ impl Display for Acid {
     }
 }
 
+/// Error returned when trying to create a [`QualityScore`] with a value out of
+/// its allowed range.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct InvalidQualityScoreError {
+    value: u8,
+    q_end: usize,
+}
+
+impl InvalidQualityScoreError {
+    #[must_use]
+    pub(crate) fn new(value: u8, q_end: usize) -> Self {
+        Self { value, q_end }
+    }
+
+    /// Returns the invalid quality score value.
+    #[must_use]
+    pub fn value(&self) -> u8 {
+        self.value
+    }
+}
+
+impl Display for InvalidQualityScoreError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Invalid quality score: {} (must be lower than {})",
+            self.value, self.q_end
+        )
+    }
+}
+
+impl Error for InvalidQualityScoreError {}
+
 /// Quality score (how certain a specific read is) for a read.
 #[derive(Deref, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Clone, Copy)]
 #[repr(transparent)]
@@ -466,12 +666,35 @@ impl<const Q_END: usize> QualityScore<Q_END> {
     /// ```
     ///
     /// # Panics
-    /// This function panics if `value` > `Q_END`.
+    /// This function panics if `value` >= `Q_END`. See [`Self::try_new()`]
+    /// for a non-panicking version.
     #[must_use]
     pub const fn new(value: u8) -> Self {
-        assert!((value as usize) < Q_END);
+        match Self::try_new(value) {
+            Ok(score) => score,
+            Err(_) => panic!("Invalid quality score"),
+        }
+    }
 
-        Self(value)
+    /// Constructs a new QualityScore instance, or returns an error if `value`
+    /// >= `Q_END`.
+    ///
+    /// ## Examples
+    /// ```
+    /// use idencomp::sequence::QualityScore;
+    ///
+    /// assert_eq!(QualityScore::<5>::try_new(3), Ok(QualityScore::new(3)));
+    /// assert_eq!(QualityScore::<5>::try_new(5).unwrap_err().value(), 5);
+    /// ```
+    pub const fn try_new(value: u8) -> Result<Self, InvalidQualityScoreError> {
+        if (value as usize) < Q_END {
+            Ok(Self(value))
+        } else {
+            Err(InvalidQualityScoreError {
+                value,
+                q_end: Q_END,
+            })
+        }
     }
 
     /// Return the integer value of this `QualityScore` instance.
@@ -536,7 +759,8 @@ impl<const Q_END: usize> Symbol for QualityScore<Q_END> {
 mod tests {
     use crate::fastq::FastqQualityScore;
     use crate::sequence::{
-        Acid, NucleotideSequence, NucleotideSequenceIdentifier, QualityScore, Symbol,
+        Acid, IlluminaCoordinates, NucleotideSequence, NucleotideSequenceIdentifier, QualityScore,
+        Symbol,
     };
 
     #[test]
@@ -572,6 +796,73 @@ mod tests {
         assert_eq!(seq_2.with_identifier(identifier), seq_1);
     }
 
+    #[test]
+    fn test_sequence_aux_data() {
+        let acids = [Acid::A, Acid::G];
+        let q_scores = [QualityScore::<10>::new(0), QualityScore::<10>::new(1)];
+
+        let seq = NucleotideSequence::new("TEST", acids, q_scores);
+        let seq_aux = seq.clone().with_aux_data(*b"BC:1");
+
+        assert_ne!(seq, seq_aux);
+        assert_eq!(seq_aux.clone().with_identifier("OTHER").aux_data(), b"BC:1");
+        assert_eq!(
+            seq_aux.clone().with_identifier_discarded().aux_data(),
+            b"BC:1"
+        );
+        let (seq_2, aux_data) = seq_aux.split_aux_data();
+        assert_eq!(seq_2, seq);
+        assert_eq!(aux_data, b"BC:1");
+    }
+
+    #[test]
+    fn test_sequence_separator_text() {
+        let acids = [Acid::A, Acid::G];
+        let q_scores = [QualityScore::<10>::new(0), QualityScore::<10>::new(1)];
+
+        let seq = NucleotideSequence::new("TEST", acids, q_scores);
+        let seq_separator = seq.clone().with_separator_text(*b"TEST\x1f");
+
+        assert_ne!(seq, seq_separator);
+        assert_eq!(
+            seq_separator
+                .clone()
+                .with_identifier("OTHER")
+                .separator_text(),
+            b"TEST\x1f"
+        );
+        let (seq_2, separator_text) = seq_separator.split_separator_text();
+        assert_eq!(seq_2, seq);
+        assert_eq!(separator_text, b"TEST\x1f");
+    }
+
+    #[test]
+    fn test_illumina_coordinates() {
+        let coordinates = |identifier: &str| {
+            NucleotideSequenceIdentifier::from(identifier).illumina_coordinates()
+        };
+        let expected = Some(IlluminaCoordinates {
+            lane: 1,
+            tile: 1101,
+            x: 1234,
+            y: 5678,
+        });
+
+        assert_eq!(
+            coordinates("M00123:42:000000000-ABCDE:1:1101:1234:5678"),
+            expected
+        );
+        assert_eq!(
+            coordinates("M00123:42:000000000-ABCDE:1:1101:1234:5678:ACGTACGT 2:N:0:1"),
+            expected
+        );
+        assert_eq!(coordinates("HWUSI:1:1101:1234:5678"), expected);
+        assert_eq!(coordinates("HWUSI:1:1101:1234:5678/2"), expected);
+        assert_eq!(coordinates("M00123:42:FC:1:tile:1234:5678"), None);
+        assert_eq!(coordinates("HWUSI:1:1101:1234"), None);
+        assert_eq!(coordinates(""), None);
+    }
+
     #[test]
     fn test_acid_display() {
         assert_eq!(format!("{}", Acid::A), "A");
@@ -1,30 +1,49 @@
-use itertools::izip;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::mem::size_of;
+
+use itertools::{izip, Itertools};
 use log::{debug, trace};
 
-use crate::compressor::{RansCompressor, RansDecContext, RansDecompressor, RansEncContext};
+use crate::compressor::{
+    RansCompressor, RansDataTooShortError, RansDecContext, RansDecompressor, RansEncContext,
+};
 use crate::context::Context;
 use crate::context_spec::{ContextSpec, ContextSpecGenerator, ContextSpecType};
 use crate::fastq::{FastqQualityScore, FastqSequence};
-use crate::model::{Model, ModelIdentifier};
-use crate::sequence::Acid;
-use crate::sequence::Symbol;
-
+use crate::model::{Model, ModelIdentifier, ModelType};
+use crate::quality_alphabet::QualityAlphabet;
+use crate::sequence::{Acid, InvalidQualityScoreError, Symbol};
+
+/// A [`Model`] prepared to be used for encoding the sequences with
+/// [`SequenceCompressor`]. The context specs that are not present in the model
+/// are encoded with a dummy context, giving all the symbols equal
+/// probabilities.
 #[derive(Debug, Clone)]
-pub struct RansEncModel<const SYMBOLS_NUM: usize> {
+pub struct RansEncModel {
     identifier: ModelIdentifier,
+    model_type: ModelType,
     context_spec_type: ContextSpecType,
-    contexts: Vec<RansEncContext<SYMBOLS_NUM>>,
+    contexts: Vec<RansEncContext>,
     map: Vec<usize>,
 }
 
-impl<const SYMBOLS_NUM: usize> RansEncModel<SYMBOLS_NUM> {
+impl RansEncModel {
+    /// Creates a new `RansEncModel` from given model, with the symbol
+    /// probabilities quantized to `scale_bits` bits. The matching
+    /// [`RansDecModel`] has to be created with the same `scale_bits` value.
+    ///
+    /// # Panics
+    /// This function panics if the model has more than 65536 contexts, or
+    /// `scale_bits` is not valid for its contexts (see
+    /// [`RansEncContext::from_context()`]).
+    #[must_use]
     pub fn from_model(model: &Model, scale_bits: u8) -> Self {
         check_model(model);
 
-        let mut contexts: Vec<RansEncContext<SYMBOLS_NUM>> =
-            Vec::with_capacity(model.contexts().len() + 1);
+        let mut contexts: Vec<RansEncContext> = Vec::with_capacity(model.contexts().len() + 1);
         contexts.push(RansEncContext::from_context(
-            &Context::dummy(SYMBOLS_NUM),
+            &Context::dummy(model.symbol_num()),
             scale_bits,
         ));
         contexts.extend(
@@ -41,43 +60,152 @@ impl<const SYMBOLS_NUM: usize> RansEncModel<SYMBOLS_NUM> {
 
         Self {
             identifier: model.identifier().clone(),
+            model_type: model.model_type(),
             context_spec_type: model.context_spec_type(),
             contexts,
             map,
         }
     }
 
+    /// Returns the approximate number of bytes an instance created from given
+    /// model with [`Self::from_model()`] takes.
+    #[must_use]
+    pub fn estimated_memory(model: &Model) -> usize {
+        estimated_model_memory::<Self>(model, RansEncContext::estimated_memory(model.symbol_num()))
+    }
+
+    /// Returns the identifier of the model this has been created from.
     #[must_use]
     pub fn identifier(&self) -> &ModelIdentifier {
         &self.identifier
     }
 
+    /// Returns the type of the model this has been created from.
+    #[must_use]
+    pub fn model_type(&self) -> ModelType {
+        self.model_type
+    }
+
+    /// Returns the type of the context specs the model is indexed with.
     #[must_use]
     pub fn context_spec_type(&self) -> ContextSpecType {
         self.context_spec_type
     }
 
-    pub fn context_for(&self, spec: ContextSpec) -> &RansEncContext<SYMBOLS_NUM> {
-        &self.contexts[self.map[spec.get() as usize]]
+    /// Returns the context the symbol is encoded with in the situation
+    /// described by given spec.
+    #[must_use]
+    pub fn context_for(&self, spec: ContextSpec) -> &RansEncContext {
+        &self.contexts[self.context_index_for(spec)]
+    }
+
+    /// Returns the index of the context used for given spec: 0 for the dummy
+    /// context, or the index of the model context plus one.
+    #[must_use]
+    pub fn context_index_for(&self, spec: ContextSpec) -> usize {
+        self.map[spec.get() as usize]
     }
-}
 
-pub type AcidRansEncModel = RansEncModel<{ Acid::SIZE }>;
-pub type QScoreRansEncModel = RansEncModel<{ FastqQualityScore::SIZE }>;
+    /// Returns the number of contexts, including the dummy one.
+    #[must_use]
+    pub fn context_num(&self) -> usize {
+        self.contexts.len()
+    }
+}
 
+/// A [`RansEncModel`] created from an acid model.
+pub type AcidRansEncModel = RansEncModel;
+/// A [`RansEncModel`] created from a quality score model.
+pub type QScoreRansEncModel = RansEncModel;
+
+/// Compressor of a single sequence (its acids and quality scores), encoded
+/// with the rANS coder using given models. The output contains no framing:
+/// the sequence length, the models used, and the identifier have to be stored
+/// separately by the caller, as they are needed to decompress the sequence
+/// with [`SequenceDecompressor`].
+///
+/// # Examples
+/// ```
+/// use idencomp::fastq::{FastqQualityScore, FastqSequence};
+/// use idencomp::model::{Model, ModelType};
+/// use idencomp::quality_alphabet::QualityAlphabet;
+/// use idencomp::sequence::Acid;
+/// use idencomp::sequence_compressor::{
+///     RansDecModel, RansEncModel, SequenceCompressor, SequenceDecodeError, SequenceDecompressor,
+/// };
+///
+/// let acid_model = Model::empty(ModelType::Acids);
+/// let q_score_model = Model::empty(ModelType::QualityScores);
+/// let sequence = FastqSequence::new(
+///     "",
+///     [Acid::A, Acid::C, Acid::N],
+///     [30, 31, 2].map(FastqQualityScore::new),
+/// );
+///
+/// let mut compressor = SequenceCompressor::new(sequence.len(), QualityAlphabet::full());
+/// let mut data = compressor
+///     .compress(
+///         &sequence,
+///         &RansEncModel::from_model(&acid_model, 14),
+///         &RansEncModel::from_model(&q_score_model, 14),
+///     )
+///     .to_vec();
+///
+/// let mut decompressor = SequenceDecompressor::new(QualityAlphabet::full());
+/// let decompressed = decompressor.decompress(
+///     &mut data,
+///     sequence.len(),
+///     &RansDecModel::from_model(&acid_model, 14),
+///     &RansDecModel::from_model(&q_score_model, 14),
+/// )?;
+/// assert_eq!(decompressed, sequence);
+///
+/// # Ok::<(), SequenceDecodeError>(())
+/// ```
 #[derive(Debug)]
 pub struct SequenceCompressor {
     compressor: RansCompressor<2>,
+    q_score_compressor: RansCompressor<1>,
+    quality_alphabet: QualityAlphabet,
+    n_q_score: Option<FastqQualityScore>,
 }
 
 impl SequenceCompressor {
+    /// Creates a new `SequenceCompressor` with the buffer preallocated for
+    /// sequences of at most `max_seq_len` length. Longer sequences can still be
+    /// compressed, but the buffer is reallocated in such case.
+    ///
+    /// The quality scores are encoded as their indices in `quality_alphabet`.
     #[must_use]
-    pub fn new() -> Self {
+    pub fn new(max_seq_len: usize, quality_alphabet: QualityAlphabet) -> Self {
         Self {
-            compressor: RansCompressor::new(),
+            compressor: RansCompressor::with_max_symbols(max_seq_len),
+            // Only used for the duplicated sequences, so it is grown on demand
+            q_score_compressor: RansCompressor::with_max_symbols(0),
+            quality_alphabet,
+            n_q_score: None,
         }
     }
 
+    /// Sets the quality score shared by all the [`Acid::N`] symbols of the
+    /// subsequent sequences. If set, only the acids are encoded at the `N`
+    /// positions; the decompressor has to be given the same quality score
+    /// with [`SequenceDecompressor::set_n_q_score()`].
+    ///
+    /// # Panics
+    /// [`Self::compress_segmented()`] panics (in debug builds) if any of the
+    /// `N` symbols has a different quality score.
+    pub fn set_n_q_score(&mut self, n_q_score: Option<FastqQualityScore>) {
+        self.n_q_score = n_q_score;
+    }
+
+    /// Compresses given sequence with a single acid model and a single quality
+    /// score model, returning the compressed data. The identifier of the
+    /// sequence is not stored.
+    ///
+    /// # Panics
+    /// This function panics if any of the quality scores is not in the quality
+    /// alphabet of the compressor.
     #[must_use]
     pub fn compress(
         &mut self,
@@ -85,12 +213,41 @@ impl SequenceCompressor {
         acid_model: &AcidRansEncModel,
         q_score_model: &QScoreRansEncModel,
     ) -> &[u8] {
-        self.compressor.reset();
+        self.compress_segmented(sequence, &[(0, acid_model)], &[(0, q_score_model)])
+    }
+
+    /// Compresses given sequence with the models switched within the
+    /// sequence. Each of the model segments consists of the position the
+    /// model is used from and the model itself; the first segment has to
+    /// start at position 0.
+    #[must_use]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "rans_encode", level = "trace", skip_all)
+    )]
+    pub fn compress_segmented(
+        &mut self,
+        sequence: &FastqSequence,
+        acid_models: &[ModelSegment<'_, AcidRansEncModel>],
+        q_score_models: &[ModelSegment<'_, QScoreRansEncModel>],
+    ) -> &[u8] {
+        self.compressor.reset_for(sequence.len());
 
         let identifier = sequence.identifier().clone();
 
-        let (acid_contexts, q_score_contexts) =
-            Self::gen_contexts(sequence, acid_model, q_score_model);
+        let (acid_specs, q_score_specs) = Self::gen_contexts(sequence, acid_models, q_score_models);
+        let mut acid_segments = SegmentCursor::new(acid_models);
+        let mut q_score_segments = SegmentCursor::new(q_score_models);
+        let acid_contexts: Vec<&RansEncContext> = acid_specs
+            .into_iter()
+            .enumerate()
+            .map(|(position, spec)| acid_segments.model_at(position).context_for(spec))
+            .collect();
+        let q_score_contexts: Vec<&RansEncContext> = q_score_specs
+            .into_iter()
+            .enumerate()
+            .map(|(position, spec)| q_score_segments.model_at(position).context_for(spec))
+            .collect();
 
         let acids = sequence.acids().iter().copied().rev();
         let q_scores = sequence.quality_scores().iter().copied().rev();
@@ -100,85 +257,223 @@ impl SequenceCompressor {
         trace!("Compressing sequence {}", identifier);
         trace!("Acids: {:?}", acids);
         trace!("Quality scores: {:?}", q_scores);
-        for (acid, q_score, acid_spec, q_score_spec) in
+        for (acid, q_score, acid_context, q_score_context) in
             izip!(acids, q_scores, acid_contexts, q_score_contexts)
         {
             let acid_sym_num = acid as usize;
-            let q_score_sym_num = q_score.get();
+            let q_score_sym_num = self
+                .quality_alphabet
+                .index_of(q_score)
+                .expect("Quality score not in the quality alphabet");
 
             trace!(
-                "Putting {}, {}: acid_spec: `{}`; q_score_spec: `{}`; acid_sym_num: {}; q_score_sym_num: {}",
-                acid, q_score,
-                acid_spec, q_score_spec, acid_sym_num, q_score_sym_num
-            );
-            self.compressor.put(
-                acid_model.context_for(acid_spec),
+                "Putting {}, {}: acid_sym_num: {}; q_score_sym_num: {}",
+                acid,
+                q_score,
                 acid_sym_num,
-                q_score_model.context_for(q_score_spec),
-                q_score_sym_num,
+                q_score_sym_num
             );
+            if acid == Acid::N && self.n_q_score.is_some() {
+                debug_assert_eq!(Some(q_score), self.n_q_score);
+                self.compressor.put_first(acid_context, acid_sym_num);
+            } else {
+                self.compressor
+                    .put(acid_context, acid_sym_num, q_score_context, q_score_sym_num);
+            }
         }
         self.compressor.flush();
 
         self.compressor.data()
     }
 
-    fn gen_contexts(
+    /// Compresses only the quality scores of given sequence with a single
+    /// quality score model, returning the compressed data. The acids are not
+    /// stored, so they have to be known when decompressing with
+    /// [`SequenceDecompressor::decompress_q_scores()`].
+    ///
+    /// # Panics
+    /// This function panics if any of the quality scores is not in the quality
+    /// alphabet of the compressor.
+    #[must_use]
+    pub fn compress_q_scores(
+        &mut self,
         sequence: &FastqSequence,
-        acid_model: &AcidRansEncModel,
         q_score_model: &QScoreRansEncModel,
+    ) -> &[u8] {
+        self.q_score_compressor.reset_for(sequence.len());
+
+        let spec_type = q_score_model.context_spec_type;
+        let mut generators = SpecGenerators::new([spec_type], sequence.len());
+        let mut contexts = Vec::with_capacity(sequence.len());
+        for (&acid, &q_score) in sequence.acids().iter().zip(sequence.quality_scores()) {
+            contexts.push(q_score_model.context_for(generators.current_context(spec_type)));
+            generators.update(acid, q_score);
+        }
+
+        let acids = sequence.acids().iter().copied().rev();
+        let q_scores = sequence.quality_scores().iter().copied().rev();
+        for (acid, q_score, context) in izip!(acids, q_scores, contexts.into_iter().rev()) {
+            if acid == Acid::N && self.n_q_score.is_some() {
+                debug_assert_eq!(Some(q_score), self.n_q_score);
+                continue;
+            }
+
+            let q_score_sym_num = self
+                .quality_alphabet
+                .index_of(q_score)
+                .expect("Quality score not in the quality alphabet");
+            self.q_score_compressor.put(context, q_score_sym_num);
+        }
+        self.q_score_compressor.flush();
+
+        self.q_score_compressor.data()
+    }
+
+    /// Returns the context specs of each of the acids and quality scores of
+    /// given sequence, generated for the models used at the respective
+    /// positions.
+    pub(crate) fn gen_contexts(
+        sequence: &FastqSequence,
+        acid_models: &[ModelSegment<'_, AcidRansEncModel>],
+        q_score_models: &[ModelSegment<'_, QScoreRansEncModel>],
     ) -> (Vec<ContextSpec>, Vec<ContextSpec>) {
         let mut acid_contexts = Vec::with_capacity(sequence.len());
         let mut q_score_contexts = Vec::with_capacity(sequence.len());
 
-        let mut acid_spec_generator: Box<dyn ContextSpecGenerator> =
-            acid_model.context_spec_type.generator(sequence.len());
-        let mut q_score_spec_generator: Box<dyn ContextSpecGenerator> =
-            q_score_model.context_spec_type.generator(sequence.len());
+        let spec_types = acid_models
+            .iter()
+            .chain(q_score_models)
+            .map(|(_, model)| model.context_spec_type);
+        let mut generators = SpecGenerators::new(spec_types, sequence.len());
+        let mut acid_segments = SegmentCursor::new(acid_models);
+        let mut q_score_segments = SegmentCursor::new(q_score_models);
 
-        for (&acid, &q_score) in sequence
+        for (position, (&acid, &q_score)) in sequence
             .acids()
             .iter()
             .zip(sequence.quality_scores().iter())
+            .enumerate()
         {
-            let acid_spec = acid_spec_generator.current_context();
-            let q_score_spec = q_score_spec_generator.current_context();
+            let acid_spec_type = acid_segments.model_at(position).context_spec_type;
+            let q_score_spec_type = q_score_segments.model_at(position).context_spec_type;
+            acid_contexts.push(generators.current_context(acid_spec_type));
+            q_score_contexts.push(generators.current_context(q_score_spec_type));
 
-            acid_contexts.push(acid_spec);
-            q_score_contexts.push(q_score_spec);
-
-            acid_spec_generator.update(acid, q_score);
-            q_score_spec_generator.update(acid, q_score);
+            generators.update(acid, q_score);
         }
 
         (acid_contexts, q_score_contexts)
     }
 }
 
-impl Default for SequenceCompressor {
-    fn default() -> Self {
-        Self::new()
+/// A part of a sequence encoded with a single model: the position the part
+/// starts at, and the model.
+pub type ModelSegment<'a, T> = (usize, &'a T);
+
+/// Goes through the [`ModelSegment`]s of a sequence, returning the model used
+/// at each position.
+#[derive(Debug)]
+pub(crate) struct SegmentCursor<'a, 'b, T> {
+    segments: &'a [ModelSegment<'b, T>],
+    index: usize,
+}
+
+impl<'a, 'b, T> SegmentCursor<'a, 'b, T> {
+    /// Creates a new `SegmentCursor` going through given segments. The
+    /// segments have to be sorted by their start positions, the first one
+    /// starting at position 0.
+    #[must_use]
+    pub fn new(segments: &'a [ModelSegment<'b, T>]) -> Self {
+        assert_eq!(segments.first().map(|(start, _)| *start), Some(0));
+
+        Self { segments, index: 0 }
+    }
+
+    /// Returns the model used at given position. The positions passed to
+    /// the subsequent calls cannot decrease.
+    #[inline]
+    pub fn model_at(&mut self, position: usize) -> &'b T {
+        while self
+            .segments
+            .get(self.index + 1)
+            .map_or(false, |(start, _)| *start <= position)
+        {
+            self.index += 1;
+        }
+
+        self.segments[self.index].1
     }
 }
 
+/// The context spec generators of all the context spec types used within a
+/// single sequence. All of them are updated with every symbol, so that the
+/// model, and so the context spec type, can be switched at any position.
+struct SpecGenerators {
+    generators: Vec<(ContextSpecType, Box<dyn ContextSpecGenerator>)>,
+}
+
+impl SpecGenerators {
+    #[must_use]
+    fn new<I: IntoIterator<Item = ContextSpecType>>(spec_types: I, length: usize) -> Self {
+        let generators = spec_types
+            .into_iter()
+            .unique()
+            .map(|spec_type| (spec_type, spec_type.generator(length)))
+            .collect();
+
+        Self { generators }
+    }
+
+    #[inline]
+    #[must_use]
+    fn current_context(&self, spec_type: ContextSpecType) -> ContextSpec {
+        let (_, generator) = self
+            .generators
+            .iter()
+            .find(|(generator_spec_type, _)| *generator_spec_type == spec_type)
+            .expect("No generator for given context spec type");
+
+        generator.current_context()
+    }
+
+    #[inline]
+    fn update(&mut self, acid: Acid, q_score: FastqQualityScore) {
+        for (_, generator) in &mut self.generators {
+            generator.update(acid, q_score);
+        }
+    }
+}
+
+/// A [`Model`] prepared to be used for decoding the sequences with
+/// [`SequenceDecompressor`]. This is the counterpart of [`RansEncModel`].
 #[derive(Debug, Clone)]
-pub struct RansDecModel<const SYMBOLS_NUM: usize> {
+pub struct RansDecModel {
     context_spec_type: ContextSpecType,
-    contexts: Vec<RansDecContext<SYMBOLS_NUM>>,
+    contexts: Vec<RansDecContext>,
     map: Vec<usize>,
 }
 
-pub type AcidRansDecModel = RansDecModel<{ Acid::SIZE }>;
-pub type QScoreRansDecModel = RansDecModel<{ FastqQualityScore::SIZE }>;
-
-impl<const SYMBOLS_NUM: usize> RansDecModel<SYMBOLS_NUM> {
+/// A [`RansDecModel`] created from an acid model.
+pub type AcidRansDecModel = RansDecModel;
+/// A [`RansDecModel`] created from a quality score model.
+pub type QScoreRansDecModel = RansDecModel;
+
+impl RansDecModel {
+    /// Creates a new `RansDecModel` from given model, with the symbol
+    /// probabilities quantized to `scale_bits` bits, which has to be the same
+    /// value the matching [`RansEncModel`] has been created with.
+    ///
+    /// # Panics
+    /// This function panics if the model has more than 65536 contexts, or
+    /// `scale_bits` is not valid for its contexts (see
+    /// [`RansDecContext::from_context()`]).
+    #[must_use]
     pub fn from_model(model: &Model, scale_bits: u8) -> Self {
         check_model(model);
 
-        let mut contexts: Vec<RansDecContext<SYMBOLS_NUM>> =
-            Vec::with_capacity(model.contexts().len() + 1);
+        let mut contexts: Vec<RansDecContext> = Vec::with_capacity(model.contexts().len() + 1);
         contexts.push(RansDecContext::from_context(
-            &Context::dummy(SYMBOLS_NUM),
+            &Context::dummy(model.symbol_num()),
             scale_bits,
         ));
         contexts.extend(
@@ -200,11 +495,31 @@ impl<const SYMBOLS_NUM: usize> RansDecModel<SYMBOLS_NUM> {
         }
     }
 
-    pub fn context_for(&self, spec: ContextSpec) -> &RansDecContext<SYMBOLS_NUM> {
+    /// Returns the approximate number of bytes an instance created from given
+    /// model with [`Self::from_model()`] takes.
+    #[must_use]
+    pub fn estimated_memory(model: &Model) -> usize {
+        estimated_model_memory::<Self>(model, RansDecContext::estimated_memory(model.symbol_num()))
+    }
+
+    /// Returns the context the symbol is decoded with in the situation
+    /// described by given spec.
+    #[must_use]
+    pub fn context_for(&self, spec: ContextSpec) -> &RansDecContext {
         &self.contexts[self.map[spec.get() as usize]]
     }
 }
 
+/// Estimates the memory taken by a rANS model, consisting of the contexts
+/// (including the dummy one) and the context spec to context index map.
+#[must_use]
+fn estimated_model_memory<T>(model: &Model, context_size: usize) -> usize {
+    let contexts_size = (model.len() + 1) * context_size;
+    let map_size = model.context_spec_type().spec_num() as usize * size_of::<usize>();
+
+    size_of::<T>() + contexts_size + map_size
+}
+
 /// Checks the model before preprocessing to avoid using too much memory
 fn check_model(model: &Model) {
     const MAX_CONTEXT_NUM: usize = 65_536;
@@ -218,51 +533,116 @@ fn check_model(model: &Model) {
     }
 }
 
+/// Decompressor of the sequences compressed with [`SequenceCompressor`]. See
+/// [`SequenceCompressor`] for an example.
+///
+/// The data has to be exactly what the compressor has returned, and the
+/// sequence length, the models and the quality alphabet have to be the same as
+/// the ones used for compressing; otherwise, the decoded sequence is
+/// meaningless.
 #[derive(Debug)]
-pub struct SequenceDecompressor {}
+pub struct SequenceDecompressor {
+    quality_alphabet: QualityAlphabet,
+    n_q_score: Option<FastqQualityScore>,
+}
 
 impl SequenceDecompressor {
+    /// Creates a new `SequenceDecompressor` that decodes the quality scores as
+    /// their indices in `quality_alphabet`.
     #[must_use]
-    pub fn new() -> Self {
-        Self {}
+    pub fn new(quality_alphabet: QualityAlphabet) -> Self {
+        Self {
+            quality_alphabet,
+            n_q_score: None,
+        }
     }
 
-    #[must_use]
+    /// Sets the quality score of the [`Acid::N`] symbols of the subsequent
+    /// sequences, which has been passed to
+    /// [`SequenceCompressor::set_n_q_score()`] when compressing them.
+    pub fn set_n_q_score(&mut self, n_q_score: Option<FastqQualityScore>) {
+        self.n_q_score = n_q_score;
+    }
+
+    /// Decompresses a sequence compressed with [`SequenceCompressor::compress()`],
+    /// given the same models. The data is modified in place while decoding.
+    /// The returned sequence has an empty identifier.
+    ///
+    /// # Errors
+    /// See [`Self::decompress_segmented()`].
     pub fn decompress(
         &mut self,
         data: &mut [u8],
         seq_length: usize,
         acid_model: &AcidRansDecModel,
         q_score_model: &QScoreRansDecModel,
-    ) -> FastqSequence {
+    ) -> Result<FastqSequence, SequenceDecodeError> {
+        self.decompress_segmented(data, seq_length, &[(0, acid_model)], &[(0, q_score_model)])
+    }
+
+    /// Decompresses a sequence compressed with
+    /// [`SequenceCompressor::compress_segmented()`], given the same model
+    /// segments.
+    ///
+    /// # Errors
+    /// Returns [`SequenceDecodeError::Truncated`] if `data` is shorter than
+    /// [`RansDecompressor::<2>::MIN_DATA_LEN`](RansDecompressor::MIN_DATA_LEN),
+    /// and [`SequenceDecodeError::InvalidQualityScore`] if a decoded quality
+    /// score symbol is out of the quality alphabet. Both can happen if the data
+    /// is corrupted.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "rans_decode", level = "trace", skip_all)
+    )]
+    pub fn decompress_segmented(
+        &mut self,
+        data: &mut [u8],
+        seq_length: usize,
+        acid_models: &[ModelSegment<'_, AcidRansDecModel>],
+        q_score_models: &[ModelSegment<'_, QScoreRansDecModel>],
+    ) -> Result<FastqSequence, SequenceDecodeError> {
         debug!(
             "Decompressing sequence: data_len {}; seq_len {}",
             data.len(),
             seq_length
         );
 
-        let mut acid_generator: Box<dyn ContextSpecGenerator> =
-            acid_model.context_spec_type.generator(seq_length);
-        let mut q_score_generator: Box<dyn ContextSpecGenerator> =
-            q_score_model.context_spec_type.generator(seq_length);
+        let spec_types = acid_models
+            .iter()
+            .chain(q_score_models)
+            .map(|(_, model)| model.context_spec_type);
+        let mut generators = SpecGenerators::new(spec_types, seq_length);
+        let mut acid_segments = SegmentCursor::new(acid_models);
+        let mut q_score_segments = SegmentCursor::new(q_score_models);
 
-        let mut decompressor: RansDecompressor<2> = RansDecompressor::new(data);
+        let mut decompressor: RansDecompressor<2> = RansDecompressor::new(data)?;
 
         let mut acids = Vec::with_capacity(seq_length);
         let mut q_scores = Vec::with_capacity(seq_length);
-        for _ in 0..seq_length {
-            let acid_spec: ContextSpec = acid_generator.current_context();
-            let q_score_spec: ContextSpec = q_score_generator.current_context();
+        for position in 0..seq_length {
+            let acid_model = acid_segments.model_at(position);
+            let q_score_model = q_score_segments.model_at(position);
+            let acid_spec: ContextSpec = generators.current_context(acid_model.context_spec_type);
+            let q_score_spec: ContextSpec =
+                generators.current_context(q_score_model.context_spec_type);
 
             let acid_ctx = acid_model.context_for(acid_spec);
             let q_score_ctx = q_score_model.context_for(q_score_spec);
 
-            let (acid_symbol, q_score_symbol) = decompressor.get(acid_ctx, q_score_ctx);
+            let skip_n = self.n_q_score.is_some();
+            let (acid_symbol, q_score_symbol) =
+                decompressor.get_conditional(acid_ctx, q_score_ctx, |acid_symbol| {
+                    skip_n && acid_symbol == Acid::N.to_usize()
+                });
             let acid = Acid::from_usize(acid_symbol);
-            let q_score = FastqQualityScore::new(q_score_symbol as u8);
+            let q_score = match (q_score_symbol, self.n_q_score) {
+                (Some(q_score_symbol), _) => self.quality_alphabet.try_get(q_score_symbol)?,
+                (None, Some(n_q_score)) => n_q_score,
+                (None, None) => unreachable!("Quality score skipped with no N quality score set"),
+            };
 
             trace!(
-                "Got {}, {}: acid_spec: `{}`; q_score_spec: `{}`; acid_sym_num: {}; q_score_sym_num: {}",
+                "Got {}, {}: acid_spec: `{}`; q_score_spec: `{}`; acid_sym_num: {}; q_score_sym_num: {:?}",
                 acid, q_score,
                 acid_spec, q_score_spec, acid_symbol, q_score_symbol
             );
@@ -270,11 +650,88 @@ impl SequenceDecompressor {
             acids.push(acid);
             q_scores.push(q_score);
 
-            acid_generator.update(acid, q_score);
-            q_score_generator.update(acid, q_score);
+            generators.update(acid, q_score);
         }
 
-        FastqSequence::new("", acids, q_scores)
+        Ok(FastqSequence::new("", acids, q_scores))
+    }
+
+    /// Decompresses the quality scores compressed with
+    /// [`SequenceCompressor::compress_q_scores()`], given the acids of the
+    /// sequence and the same model. The data is modified in place while
+    /// decoding.
+    ///
+    /// # Errors
+    /// Returns [`SequenceDecodeError::Truncated`] if `data` is shorter than
+    /// [`RansDecompressor::<1>::MIN_DATA_LEN`](RansDecompressor::MIN_DATA_LEN),
+    /// and [`SequenceDecodeError::InvalidQualityScore`] if a decoded quality
+    /// score symbol is out of the quality alphabet.
+    pub fn decompress_q_scores(
+        &mut self,
+        data: &mut [u8],
+        acids: &[Acid],
+        q_score_model: &QScoreRansDecModel,
+    ) -> Result<Vec<FastqQualityScore>, SequenceDecodeError> {
+        let spec_type = q_score_model.context_spec_type;
+        let mut generators = SpecGenerators::new([spec_type], acids.len());
+        let mut decompressor: RansDecompressor<1> = RansDecompressor::new(data)?;
+
+        let mut q_scores = Vec::with_capacity(acids.len());
+        for &acid in acids {
+            let q_score = match self.n_q_score {
+                Some(n_q_score) if acid == Acid::N => n_q_score,
+                _ => {
+                    let spec = generators.current_context(spec_type);
+                    let q_score_symbol = decompressor.get(q_score_model.context_for(spec));
+                    self.quality_alphabet.try_get(q_score_symbol)?
+                }
+            };
+
+            q_scores.push(q_score);
+            generators.update(acid, q_score);
+        }
+
+        Ok(q_scores)
+    }
+}
+
+/// Error returned by [`SequenceDecompressor`] when the compressed data cannot
+/// be decoded.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum SequenceDecodeError {
+    /// The data is too short to contain the final states of the rANS encoder.
+    Truncated(RansDataTooShortError),
+    /// A decoded quality score is out of the quality alphabet.
+    InvalidQualityScore(InvalidQualityScoreError),
+}
+
+impl From<RansDataTooShortError> for SequenceDecodeError {
+    fn from(e: RansDataTooShortError) -> Self {
+        Self::Truncated(e)
+    }
+}
+
+impl From<InvalidQualityScoreError> for SequenceDecodeError {
+    fn from(e: InvalidQualityScoreError) -> Self {
+        Self::InvalidQualityScore(e)
+    }
+}
+
+impl Display for SequenceDecodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SequenceDecodeError::Truncated(e) => write!(f, "{}", e),
+            SequenceDecodeError::InvalidQualityScore(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for SequenceDecodeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SequenceDecodeError::Truncated(e) => Some(e),
+            SequenceDecodeError::InvalidQualityScore(e) => Some(e),
+        }
     }
 }
 
@@ -282,13 +739,17 @@ impl SequenceDecompressor {
 mod tests {
 
     use crate::_internal_test_data::{
-        SHORT_TEST_SEQUENCE, SIMPLE_ACID_MODEL, SIMPLE_Q_SCORE_MODEL, SIMPLE_TEST_SEQUENCE,
+        RANDOM_200_CTX_Q_SCORE_MODEL, SHORT_TEST_SEQUENCE, SIMPLE_ACID_MODEL, SIMPLE_Q_SCORE_MODEL,
+        SIMPLE_TEST_SEQUENCE, TEST_ACID_MODEL_PREFER_A,
     };
-    use crate::fastq::FastqSequence;
+    use crate::compressor::RansDecompressor;
+    use crate::fastq::{FastqQualityScore, FastqSequence};
     use crate::model::{Model, ModelType};
+    use crate::quality_alphabet::QualityAlphabet;
+    use crate::sequence::Acid;
     use crate::sequence_compressor::{
         AcidRansDecModel, AcidRansEncModel, QScoreRansDecModel, QScoreRansEncModel,
-        SequenceCompressor, SequenceDecompressor,
+        SequenceCompressor, SequenceDecodeError, SequenceDecompressor,
     };
 
     #[test]
@@ -333,6 +794,171 @@ mod tests {
         assert_eq!(sequence, decompressed_sequence);
     }
 
+    #[test_log::test]
+    fn round_trip_segmented() {
+        let sequence = SIMPLE_TEST_SEQUENCE.clone().with_identifier_discarded();
+        let acid_models = [&*SIMPLE_ACID_MODEL, &*TEST_ACID_MODEL_PREFER_A];
+        let q_score_models = [&*SIMPLE_Q_SCORE_MODEL, &*RANDOM_200_CTX_Q_SCORE_MODEL];
+
+        let enc_acid_models =
+            acid_models.map(|model| AcidRansEncModel::from_model(model, SCALE_BITS));
+        let enc_q_score_models =
+            q_score_models.map(|model| QScoreRansEncModel::from_model(model, SCALE_BITS));
+        let mut compressor = SequenceCompressor::new(sequence.len(), QualityAlphabet::full());
+        let mut data = compressor
+            .compress_segmented(
+                &sequence,
+                &[(0, &enc_acid_models[0]), (30, &enc_acid_models[1])],
+                &[
+                    (0, &enc_q_score_models[0]),
+                    (20, &enc_q_score_models[1]),
+                    (40, &enc_q_score_models[0]),
+                ],
+            )
+            .to_owned();
+
+        let dec_acid_models =
+            acid_models.map(|model| AcidRansDecModel::from_model(model, SCALE_BITS));
+        let dec_q_score_models =
+            q_score_models.map(|model| QScoreRansDecModel::from_model(model, SCALE_BITS));
+        let mut decompressor = SequenceDecompressor::new(QualityAlphabet::full());
+        let decompressed_sequence = decompressor
+            .decompress_segmented(
+                &mut data,
+                sequence.len(),
+                &[(0, &dec_acid_models[0]), (30, &dec_acid_models[1])],
+                &[
+                    (0, &dec_q_score_models[0]),
+                    (20, &dec_q_score_models[1]),
+                    (40, &dec_q_score_models[0]),
+                ],
+            )
+            .unwrap();
+
+        assert_eq!(sequence, decompressed_sequence);
+    }
+
+    #[test_log::test]
+    fn round_trip_n_q_score() {
+        let n_q_score = FastqQualityScore::new(2);
+        let (acids, q_scores): (Vec<_>, Vec<_>) = (0..100)
+            .map(|i| match i % 3 {
+                0 => (Acid::N, n_q_score),
+                1 => (Acid::G, FastqQualityScore::new(30)),
+                _ => (Acid::T, FastqQualityScore::new(i % 40)),
+            })
+            .unzip();
+        let sequence = FastqSequence::new("", acids, q_scores);
+
+        let acid_model = Model::empty(ModelType::Acids);
+        let q_score_model = Model::empty(ModelType::QualityScores);
+        let enc_acid_model = AcidRansEncModel::from_model(&acid_model, SCALE_BITS);
+        let enc_q_score_model = QScoreRansEncModel::from_model(&q_score_model, SCALE_BITS);
+        let mut compressor = SequenceCompressor::new(sequence.len(), QualityAlphabet::full());
+        let full_len = compressor
+            .compress(&sequence, &enc_acid_model, &enc_q_score_model)
+            .len();
+        compressor.set_n_q_score(Some(n_q_score));
+        let mut data = compressor
+            .compress(&sequence, &enc_acid_model, &enc_q_score_model)
+            .to_owned();
+        assert!(data.len() < full_len);
+
+        let dec_acid_model = AcidRansDecModel::from_model(&acid_model, SCALE_BITS);
+        let dec_q_score_model = QScoreRansDecModel::from_model(&q_score_model, SCALE_BITS);
+        let mut decompressor = SequenceDecompressor::new(QualityAlphabet::full());
+        decompressor.set_n_q_score(Some(n_q_score));
+        let decompressed_sequence = decompressor
+            .decompress(
+                &mut data,
+                sequence.len(),
+                &dec_acid_model,
+                &dec_q_score_model,
+            )
+            .unwrap();
+
+        assert_eq!(sequence, decompressed_sequence);
+    }
+
+    #[test]
+    fn round_trip_q_scores_only() {
+        let sequence = &*SIMPLE_TEST_SEQUENCE;
+        let enc_q_score_model = QScoreRansEncModel::from_model(&SIMPLE_Q_SCORE_MODEL, SCALE_BITS);
+        let mut compressor = SequenceCompressor::new(0, QualityAlphabet::full());
+        let mut data = compressor
+            .compress_q_scores(sequence, &enc_q_score_model)
+            .to_owned();
+
+        let dec_q_score_model = QScoreRansDecModel::from_model(&SIMPLE_Q_SCORE_MODEL, SCALE_BITS);
+        let mut decompressor = SequenceDecompressor::new(QualityAlphabet::full());
+        let q_scores = decompressor
+            .decompress_q_scores(&mut data, sequence.acids(), &dec_q_score_model)
+            .unwrap();
+
+        assert_eq!(q_scores, sequence.quality_scores());
+    }
+
+    #[test]
+    fn decompress_q_score_out_of_alphabet() {
+        let sequence = &*SHORT_TEST_SEQUENCE;
+        let mut data = compress(sequence, &SIMPLE_ACID_MODEL, &SIMPLE_Q_SCORE_MODEL);
+
+        let dec_acid_model = AcidRansDecModel::from_model(&SIMPLE_ACID_MODEL, SCALE_BITS);
+        let dec_q_score_model = QScoreRansDecModel::from_model(&SIMPLE_Q_SCORE_MODEL, SCALE_BITS);
+        let alphabet = QualityAlphabet::new([0, 1].map(FastqQualityScore::new));
+        let mut decompressor = SequenceDecompressor::new(alphabet);
+        let result = decompressor.decompress(
+            &mut data,
+            sequence.len(),
+            &dec_acid_model,
+            &dec_q_score_model,
+        );
+
+        assert!(matches!(
+            result,
+            Err(SequenceDecodeError::InvalidQualityScore(e)) if e.value() == 13
+        ));
+    }
+
+    #[test]
+    fn decompress_truncated() {
+        let sequence = &*SHORT_TEST_SEQUENCE;
+        let data = compress(sequence, &SIMPLE_ACID_MODEL, &SIMPLE_Q_SCORE_MODEL);
+
+        let dec_acid_model = AcidRansDecModel::from_model(&SIMPLE_ACID_MODEL, SCALE_BITS);
+        let dec_q_score_model = QScoreRansDecModel::from_model(&SIMPLE_Q_SCORE_MODEL, SCALE_BITS);
+        let mut decompressor = SequenceDecompressor::new(QualityAlphabet::full());
+        let mut truncated = data[..3].to_vec();
+        let result = decompressor.decompress(
+            &mut truncated,
+            sequence.len(),
+            &dec_acid_model,
+            &dec_q_score_model,
+        );
+        assert!(matches!(
+            result,
+            Err(SequenceDecodeError::Truncated(e)) if e.data_len() == 3 && e.min_len() == 8
+        ));
+
+        let mut truncated = data[..3].to_vec();
+        let result =
+            decompressor.decompress_q_scores(&mut truncated, sequence.acids(), &dec_q_score_model);
+        assert!(matches!(
+            result,
+            Err(SequenceDecodeError::Truncated(e)) if e.data_len() == 3 && e.min_len() == 4
+        ));
+    }
+
+    #[test]
+    fn sequence_decode_error_display() {
+        let mut data = [0; 3];
+        let e = RansDecompressor::<2>::new(&mut data).err().unwrap();
+        assert_eq!(
+            SequenceDecodeError::from(e).to_string(),
+            "rANS data too short (length: 3, minimum: 8)"
+        );
+    }
+
     const SCALE_BITS: u8 = 10;
 
     fn compress(sequence: &FastqSequence, acid_model: &Model, q_score_model: &Model) -> Vec<u8> {
@@ -342,7 +968,7 @@ mod tests {
         let enc_acid_model = AcidRansEncModel::from_model(acid_model, SCALE_BITS);
         let enc_q_score_model = QScoreRansEncModel::from_model(q_score_model, SCALE_BITS);
 
-        let mut compressor = SequenceCompressor::new();
+        let mut compressor = SequenceCompressor::new(sequence.len(), QualityAlphabet::full());
         let data = compressor.compress(sequence, &enc_acid_model, &enc_q_score_model);
 
         data.to_owned()
@@ -360,8 +986,10 @@ mod tests {
         let dec_acid_model = AcidRansDecModel::from_model(acid_model, SCALE_BITS);
         let dec_q_score_model = QScoreRansDecModel::from_model(q_score_model, SCALE_BITS);
 
-        let mut decompressor = SequenceDecompressor::new();
+        let mut decompressor = SequenceDecompressor::new(QualityAlphabet::full());
 
-        decompressor.decompress(data, seq_length, &dec_acid_model, &dec_q_score_model)
+        decompressor
+            .decompress(data, seq_length, &dec_acid_model, &dec_q_score_model)
+            .unwrap()
     }
 }
@@ -0,0 +1,96 @@
+use std::io::{BufRead, Read};
+
+use crate::fastq::parallel_reader::ParallelFastqReader;
+use crate::fastq::reader::{FastqReader, FastqReaderError};
+use crate::fastq::FastqSequence;
+use crate::idn::decompressor::IdnDecompressor;
+
+/// A source of the sequences, such as a file reader, that can be used as the
+/// input of [`IdnCompressor::compress_from()`].
+///
+/// [`IdnCompressor::compress_from()`]: crate::idn::compressor::IdnCompressor::compress_from
+///
+/// # Examples
+/// ```
+/// use idencomp::fastq::reader::FastqReader;
+/// use idencomp::sequence_source::SequenceSource;
+///
+/// let mut reader = FastqReader::new(b"@SEQ\nACGT\n+\n!!!!\n".as_slice());
+/// assert_eq!(reader.next_sequence()?.unwrap().len(), 4);
+/// assert!(reader.next_sequence()?.is_none());
+///
+/// # Ok::<(), idencomp::Error>(())
+/// ```
+pub trait SequenceSource {
+    /// Reads and returns next sequence. Returns `Ok(None)` if there are no
+    /// more sequences.
+    fn next_sequence(&mut self) -> crate::Result<Option<FastqSequence>>;
+}
+
+impl<S: SequenceSource + ?Sized> SequenceSource for &mut S {
+    fn next_sequence(&mut self) -> crate::Result<Option<FastqSequence>> {
+        (**self).next_sequence()
+    }
+}
+
+impl<R: BufRead> SequenceSource for FastqReader<R> {
+    fn next_sequence(&mut self) -> crate::Result<Option<FastqSequence>> {
+        match self.read_sequence() {
+            Ok(sequence) => Ok(Some(sequence)),
+            Err(FastqReaderError::EofReached) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+impl<R: BufRead> SequenceSource for ParallelFastqReader<R> {
+    fn next_sequence(&mut self) -> crate::Result<Option<FastqSequence>> {
+        Ok(self.next().transpose()?)
+    }
+}
+
+impl<R: Read + Send> SequenceSource for IdnDecompressor<R> {
+    fn next_sequence(&mut self) -> crate::Result<Option<FastqSequence>> {
+        Ok(IdnDecompressor::next_sequence(self)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::_internal_test_data::{SEQ_1K_READS, SEQ_1K_READS_FASTQ};
+    use crate::fastq::parallel_reader::ParallelFastqReader;
+    use crate::fastq::reader::FastqReader;
+    use crate::fastq::FastqSequence;
+    use crate::sequence_source::SequenceSource;
+    use crate::Error;
+
+    fn read_all<S: SequenceSource>(mut source: S) -> crate::Result<Vec<FastqSequence>> {
+        let mut sequences = Vec::new();
+        while let Some(sequence) = source.next_sequence()? {
+            sequences.push(sequence);
+        }
+
+        Ok(sequences)
+    }
+
+    #[test]
+    fn test_fastq_reader_source() {
+        let sequences = read_all(FastqReader::new(SEQ_1K_READS_FASTQ)).unwrap();
+
+        assert_eq!(sequences, SEQ_1K_READS.as_slice());
+    }
+
+    #[test]
+    fn test_parallel_fastq_reader_source() {
+        let sequences = read_all(ParallelFastqReader::new(SEQ_1K_READS_FASTQ)).unwrap();
+
+        assert_eq!(sequences, SEQ_1K_READS.as_slice());
+    }
+
+    #[test]
+    fn test_fastq_reader_source_error() {
+        let result = read_all(FastqReader::new(b"@SEQ\nAXGT\n+\n!!!!\n".as_slice()));
+
+        assert!(matches!(result, Err(Error::FastqReader(_))));
+    }
+}
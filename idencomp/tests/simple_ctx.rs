@@ -7,7 +7,8 @@ fn test_compress_simple_1m() {
     let mut data = Vec::new();
     let params = IdnCompressorParams::builder()
         .model_provider(SIMPLE_MODEL_PROVIDER.clone())
-        .build();
+        .build()
+        .unwrap();
 
     let mut idn_compressor = IdnCompressor::with_params(&mut data, params);
     idn_compressor.add_sequence(SEQ_1M.clone()).unwrap();
@@ -38,7 +39,8 @@ fn test_round_trip_many_sequences() {
     // Compress
     let params = IdnCompressorParams::builder()
         .model_provider(SIMPLE_MODEL_PROVIDER.clone())
-        .build();
+        .build()
+        .unwrap();
 
     let mut idn_compressor = IdnCompressor::with_params(&mut data, params);
     for sequence in SEQ_1K_READS.iter() {
@@ -66,7 +68,8 @@ fn test_round_trip_small_blocks() {
     let params = IdnCompressorParams::builder()
         .model_provider(SIMPLE_MODEL_PROVIDER.clone())
         .max_block_total_len(200)
-        .build();
+        .build()
+        .unwrap();
 
     let mut idn_compressor = IdnCompressor::with_params(&mut data, params);
     for sequence in SEQ_1K_READS.iter() {
@@ -95,7 +98,8 @@ fn test_round_trip_small_blocks_threaded() {
         .model_provider(SIMPLE_MODEL_PROVIDER.clone())
         .max_block_total_len(200)
         .thread_num(8)
-        .build();
+        .build()
+        .unwrap();
 
     let mut idn_compressor = IdnCompressor::with_params(&mut data, params);
     for sequence in SEQ_1K_READS.iter() {